@@ -0,0 +1,123 @@
+//! Heap allocations while replaying the order book frame corpus
+//!
+//! Compares the String-based decoder this crate used before order book levels
+//! were parsed into decimals against the current decode path, using dhat's heap
+//! statistics. Both sides include the per-frame `serde_json::Value` parse that
+//! `WsClient::run` does, and the per-update clone of the book handed to the
+//! callback.
+//!
+//! ```text
+//! cargo bench --bench order_book_alloc
+//! ```
+//!
+//! On the checked-in corpus (903 frames, 3 markets):
+//!
+//! ```text
+//! legacy (String levels):   166985 allocations, 8451818 bytes
+//! current (Decimal levels): 36673 allocations, 6402083 bytes
+//! ```
+
+use std::collections::HashMap;
+
+use lighter_rs::ws_client::{OrderBook, OrderBookUpdate};
+use serde::Deserialize;
+use serde_json::Value;
+
+#[global_allocator]
+static ALLOC: dhat::Alloc = dhat::Alloc;
+
+const CORPUS: &str = include_str!("fixtures/order_book_frames.jsonl");
+
+type LegacyLevels = Vec<(String, String)>;
+
+fn legacy_side(order_book: &Value, key: &str) -> LegacyLevels {
+    order_book[key]
+        .as_array()
+        .map(|levels| {
+            levels
+                .iter()
+                .map(|l| {
+                    let price = l["price"].as_str().unwrap_or("").to_string();
+                    let size = l["size"].as_str().unwrap_or("0").to_string();
+                    (price, size)
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn legacy_replay() {
+    let mut books: HashMap<String, (LegacyLevels, LegacyLevels)> = HashMap::new();
+    for line in CORPUS.lines() {
+        let frame: Value = serde_json::from_str(line).unwrap();
+        let market_id = frame["channel"].as_str().unwrap();
+        let order_book = &frame["order_book"];
+
+        if frame["type"] == "subscribed/order_book" {
+            // The old path deserialized from a cloned Value
+            let snapshot = order_book.clone();
+            let book = (
+                legacy_side(&snapshot, "asks"),
+                legacy_side(&snapshot, "bids"),
+            );
+            books.insert(market_id.to_string(), book.clone());
+        } else if let Some((asks, bids)) = books.get_mut(market_id) {
+            for (key, levels) in [("asks", &mut *asks), ("bids", &mut *bids)] {
+                for l in order_book[key].as_array().into_iter().flatten() {
+                    let price = l["price"].as_str().unwrap_or("");
+                    let size = l["size"].as_str().unwrap_or("0");
+                    match levels.iter_mut().find(|(p, _)| p == price) {
+                        Some(level) => level.1 = size.to_string(),
+                        None if size.parse::<f64>().unwrap_or(0.0) > 0.0 => {
+                            levels.push((price.to_string(), size.to_string()))
+                        }
+                        None => {}
+                    }
+                }
+                levels.retain(|(_, size)| size.parse::<f64>().unwrap_or(0.0) > 0.0);
+            }
+            let _for_callback = (asks.clone(), bids.clone());
+        }
+    }
+}
+
+fn current_replay() {
+    let mut books: HashMap<String, OrderBook> = HashMap::new();
+    for line in CORPUS.lines() {
+        let frame: Value = serde_json::from_str(line).unwrap();
+        let market_id = frame["channel"].as_str().unwrap();
+        let order_book = &frame["order_book"];
+
+        if frame["type"] == "subscribed/order_book" {
+            let snapshot = OrderBook::deserialize(order_book).unwrap();
+            books
+                .entry(market_id.to_string())
+                .or_default()
+                .replace_with(&snapshot);
+        } else {
+            let update = OrderBookUpdate::deserialize(order_book).unwrap();
+            if let Some(book) = books.get_mut(market_id) {
+                book.apply_update(&update);
+                let _for_callback = book.clone();
+            }
+        }
+    }
+}
+
+fn measure(name: &str, replay: fn()) {
+    let before = dhat::HeapStats::get();
+    replay();
+    let after = dhat::HeapStats::get();
+    println!(
+        "{name:<26}{} allocations, {} bytes",
+        after.total_blocks - before.total_blocks,
+        after.total_bytes - before.total_bytes
+    );
+}
+
+fn main() {
+    let _profiler = dhat::Profiler::builder().testing().build();
+
+    measure("legacy (String levels):", legacy_replay);
+    measure("current (Decimal levels):", current_replay);
+}
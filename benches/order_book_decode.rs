@@ -0,0 +1,91 @@
+//! Order book decode throughput on a corpus of order book channel frames
+//!
+//! Each iteration replays the whole corpus the way `WsClient::run` handles it:
+//! decode the frame, then apply the snapshot or update to the maintained book
+//! for its market. `replay_corpus_via_value` is the previous dispatch, which
+//! parsed every frame into a `serde_json::Value` first.
+//!
+//! Compare the JSON backends with:
+//!
+//! ```text
+//! cargo bench --bench order_book_decode
+//! cargo bench --bench order_book_decode --features simd
+//! ```
+//!
+//! On the checked-in corpus (903 frames, x86_64), simd-json stays behind
+//! serde_json because every payload still goes through serde, so `simd` is
+//! opt-in:
+//!
+//! ```text
+//!                           serde_json   simd
+//! replay_corpus             3.2 ms       4.3 ms
+//! replay_corpus_via_value   4.7 ms       5.4 ms
+//! ```
+
+use std::collections::HashMap;
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use lighter_rs::ws_client::{parse_frame, OrderBook, OrderBookUpdate, WsFrame};
+use serde::Deserialize;
+use serde_json::Value;
+
+const CORPUS: &str = include_str!("fixtures/order_book_frames.jsonl");
+
+fn replay(books: &mut HashMap<String, OrderBook>) {
+    for line in CORPUS.lines() {
+        match WsFrame::decode(line.to_string()).unwrap() {
+            WsFrame::OrderBookSnapshot {
+                market_id,
+                order_book,
+            } => books
+                .entry(market_id)
+                .or_default()
+                .replace_with(&order_book),
+            WsFrame::OrderBookUpdate { market_id, update } => {
+                if let Some(book) = books.get_mut(&market_id) {
+                    book.apply_update(&update);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn replay_via_value(books: &mut HashMap<String, OrderBook>) {
+    for line in CORPUS.lines() {
+        let frame: Value = parse_frame(line.to_string()).unwrap();
+        let market_id = frame["channel"].as_str().unwrap();
+        let order_book = &frame["order_book"];
+
+        if frame["type"] == "subscribed/order_book" {
+            let snapshot = OrderBook::deserialize(order_book).unwrap();
+            books
+                .entry(market_id.to_string())
+                .or_default()
+                .replace_with(&snapshot);
+        } else {
+            let update = OrderBookUpdate::deserialize(order_book).unwrap();
+            if let Some(book) = books.get_mut(market_id) {
+                book.apply_update(&update);
+            }
+        }
+    }
+}
+
+fn bench_order_book_decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("order_book_decode");
+    group.throughput(Throughput::Elements(CORPUS.lines().count() as u64));
+
+    let mut books = HashMap::new();
+    group.bench_function("replay_corpus", |b| b.iter(|| replay(&mut books)));
+
+    let mut books = HashMap::new();
+    group.bench_function("replay_corpus_via_value", |b| {
+        b.iter(|| replay_via_value(&mut books))
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_order_book_decode);
+criterion_main!(benches);
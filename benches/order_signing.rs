@@ -0,0 +1,64 @@
+//! Create-and-sign latency with a warm nonce cache versus fetching the nonce per order
+//!
+//! The nonce endpoint is served by a local mock so the numbers only reflect the
+//! client's own overhead plus one loopback round trip for the cold path.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use lighter_rs::client::TxClient;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+const PRIVATE_KEY: &str =
+    "0x0102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f202122232425262728";
+
+/// Minimal HTTP server answering every request with a nextNonce response
+async fn spawn_nonce_server() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        while let Ok((mut socket, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                let mut buf = vec![0u8; 4096];
+                let body = r#"{"code":200,"nonce":1}"#;
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                while let Ok(n) = socket.read(&mut buf).await {
+                    if n == 0 || socket.write_all(response.as_bytes()).await.is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+    });
+
+    format!("http://{addr}")
+}
+
+fn bench_create_and_sign(c: &mut Criterion) {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let url = rt.block_on(spawn_nonce_server());
+    let client = TxClient::new(&url, PRIVATE_KEY, 1, 0, 304).unwrap();
+    rt.block_on(client.warm_up()).unwrap();
+
+    c.bench_function("create_limit_order/warm_nonce_cache", |b| {
+        b.iter(|| {
+            rt.block_on(client.create_limit_order(0, 1, 1000, 3_000_000_000, 0, false, None))
+                .unwrap()
+        })
+    });
+
+    c.bench_function("create_limit_order/nonce_fetch_per_order", |b| {
+        b.iter(|| {
+            client.nonces().invalidate_all();
+            rt.block_on(client.create_limit_order(0, 1, 1000, 3_000_000_000, 0, false, None))
+                .unwrap()
+        })
+    });
+}
+
+criterion_group!(benches, bench_create_and_sign);
+criterion_main!(benches);
@@ -0,0 +1,116 @@
+//! Event-loop latency during a 500-signature burst for each signing strategy
+//!
+//! A ticker task on a single-threaded runtime wakes every millisecond and
+//! records how late it was. The reported time per iteration is the worst
+//! wake-up lag observed while the burst was being signed, which approximates
+//! how long a WebSocket read loop on the same runtime would have stalled.
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use lighter_rs::client::TxClient;
+use lighter_rs::constants::{ORDER_TYPE_LIMIT, TIME_IN_FORCE_GOOD_TILL_TIME};
+use lighter_rs::types::{CreateOrderTxReq, TransactOpts};
+use lighter_rs::SigningStrategy;
+
+const PRIVATE_KEY: &str =
+    "0x0102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f202122232425262728";
+const BURST: i64 = 500;
+const TICK: Duration = Duration::from_millis(1);
+
+fn burst() -> Vec<CreateOrderTxReq> {
+    (0..BURST)
+        .map(|i| CreateOrderTxReq {
+            market_index: 0,
+            client_order_index: i,
+            base_amount: 1000,
+            price: 3_000_000_000,
+            is_ask: 0,
+            order_type: ORDER_TYPE_LIMIT,
+            time_in_force: TIME_IN_FORCE_GOOD_TILL_TIME,
+            reduce_only: 0,
+            trigger_price: 0,
+            order_expiry: 0,
+        })
+        .collect()
+}
+
+/// Sign one burst and return the worst lag of the ticker task meanwhile
+async fn max_tick_lag(client: &TxClient, reqs: &[CreateOrderTxReq]) -> Duration {
+    let done = Arc::new(AtomicBool::new(false));
+    let max_lag_ns = Arc::new(AtomicU64::new(0));
+
+    let ticker = {
+        let done = done.clone();
+        let max_lag_ns = max_lag_ns.clone();
+        tokio::spawn(async move {
+            while !done.load(Ordering::Relaxed) {
+                let expected = Instant::now() + TICK;
+                tokio::time::sleep(TICK).await;
+                let lag = Instant::now().saturating_duration_since(expected);
+                max_lag_ns.fetch_max(lag.as_nanos() as u64, Ordering::Relaxed);
+            }
+        })
+    };
+    // Let the ticker start before the burst
+    tokio::task::yield_now().await;
+
+    let opts = TransactOpts {
+        nonce: Some(0),
+        ..Default::default()
+    };
+    // Sign each order as its own task, as an application reacting to a burst would
+    let tasks: Vec<_> = reqs
+        .iter()
+        .map(|req| {
+            let req = req.clone();
+            let opts = opts.clone();
+            async move { client.create_order(&req, Some(opts)).await.unwrap() }
+        })
+        .collect();
+    futures_util::future::join_all(tasks).await;
+
+    done.store(true, Ordering::Relaxed);
+    ticker.await.unwrap();
+    Duration::from_nanos(max_lag_ns.load(Ordering::Relaxed))
+}
+
+fn bench_signing_strategies(c: &mut Criterion) {
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let reqs = burst();
+
+    let mut group = c.benchmark_group("ws_loop_lag_during_500_signatures");
+    group.sample_size(10);
+
+    for (name, strategy) in [
+        ("inline", SigningStrategy::Inline),
+        ("blocking", SigningStrategy::Blocking),
+        ("pool_4", SigningStrategy::Pool(4)),
+    ] {
+        let client = TxClient::builder()
+            .private_key(PRIVATE_KEY)
+            .account_index(1)
+            .chain_id(304)
+            .signing_strategy(strategy)
+            .build()
+            .unwrap();
+
+        group.bench_function(name, |b| {
+            b.iter_custom(|iters| {
+                (0..iters)
+                    .map(|_| rt.block_on(max_tick_lag(&client, &reqs)))
+                    .sum()
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_signing_strategies);
+criterion_main!(benches);
@@ -0,0 +1,85 @@
+//! Offline hot paths of order submission: hashing, signing, order construction
+//! and sendTx body serialization
+//!
+//! Everything runs without a network: the client is built without an API URL
+//! and the fixture supplies the nonce. Order book frame decode is covered by
+//! the `order_book_decode` bench on the shared frame corpus.
+//!
+//! Before measuring, each path is timed once against a ceiling far above its
+//! expected cost. The ceilings only catch order-of-magnitude regressions (such
+//! as a key being re-derived on every call) and are loose enough not to trip on
+//! a busy CI machine; criterion's own reports are the baseline to compare.
+
+use std::time::{Duration, Instant};
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use lighter_rs::client::{HTTPClient, TxClient};
+use lighter_rs::signer::Signer;
+use lighter_rs::types::{CreateOrderTxReq, TransactOpts, TxInfo};
+use serde::Deserialize;
+
+const FIXTURE: &str = include_str!("fixtures/create_order.json");
+
+#[derive(Deserialize)]
+struct Fixture {
+    private_key: String,
+    chain_id: u32,
+    request: CreateOrderTxReq,
+    opts: TransactOpts,
+}
+
+/// Run `f` a few times and fail if the mean exceeds `ceiling`
+fn assert_under<T>(name: &str, ceiling: Duration, mut f: impl FnMut() -> T) {
+    const RUNS: u32 = 20;
+    let start = Instant::now();
+    for _ in 0..RUNS {
+        black_box(f());
+    }
+    let mean = start.elapsed() / RUNS;
+    assert!(
+        mean < ceiling,
+        "{name} took {mean:?} per call, ceiling is {ceiling:?}"
+    );
+}
+
+fn bench_transactions(c: &mut Criterion) {
+    let fixture: Fixture = serde_json::from_str(FIXTURE).unwrap();
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    let client = TxClient::builder()
+        .private_key(&fixture.private_key)
+        .chain_id(fixture.chain_id)
+        .build()
+        .unwrap();
+    let http = HTTPClient::new("http://127.0.0.1:1").unwrap();
+
+    let create_order = || {
+        rt.block_on(client.create_order(&fixture.request, Some(fixture.opts.clone())))
+            .unwrap()
+    };
+    let signed = create_order();
+    let msg_hash = signed.hash(fixture.chain_id).unwrap();
+    let send_tx_body = || http.send_tx_body(&signed).unwrap();
+
+    assert_under("poseidon hash", Duration::from_millis(2), || {
+        signed.hash(fixture.chain_id)
+    });
+    assert_under("schnorr sign", Duration::from_millis(10), || {
+        client.key_manager().sign(&msg_hash)
+    });
+    assert_under("create order", Duration::from_millis(20), create_order);
+    assert_under("sendTx body", Duration::from_micros(500), send_tx_body);
+
+    c.bench_function("poseidon_hash/create_order", |b| {
+        b.iter(|| signed.hash(black_box(fixture.chain_id)).unwrap())
+    });
+    c.bench_function("schnorr_sign", |b| {
+        b.iter(|| client.key_manager().sign(black_box(&msg_hash)).unwrap())
+    });
+    c.bench_function("create_order/offline", |b| b.iter(create_order));
+    c.bench_function("send_tx_body/create_order", |b| b.iter(send_tx_body));
+}
+
+criterion_group!(benches, bench_transactions);
+criterion_main!(benches);
@@ -71,7 +71,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     // Strategy: Place a BUY order at $1 (current ETH ~$3000)
     // This ensures zero risk of execution
-    let order_price = 1_000_000u32; // $1.00 (way below market)
+    let order_price = 1_000_000u64; // $1.00 (way below market)
     let order_amount = 10_000_000i64; // $10.00
 
     tracing::info!("📝 Step 3: Create Order (Local)");
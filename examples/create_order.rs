@@ -1,5 +1,6 @@
 use dotenv::dotenv;
 use lighter_rs::client::TxClient;
+use lighter_rs::constants::NIL_CLIENT_ORDER_INDEX;
 use std::env;
 
 #[tokio::main]
@@ -39,8 +40,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     match tx_client
         .create_market_order(
             market_index,
-            chrono::Utc::now().timestamp_millis(),
-            100_000, // Small size for demo
+            NIL_CLIENT_ORDER_INDEX, // picked by the client
+            100_000,                // Small size for demo
             mid_price,
             0,     // BUY (0 = buy, 1 = sell)
             false, // not reduce-only
@@ -0,0 +1,94 @@
+//! Example: Creating a sub-account and querying it
+//!
+//! This example demonstrates how to:
+//! 1. Sign and send a create sub-account transaction
+//! 2. Wait for the transaction to execute
+//! 3. Find the new sub-account among the accounts of the L1 address
+//! 4. Query the new sub-account's state
+//!
+//! Prerequisites:
+//! - Set environment variables:
+//!   * LIGHTER_API_KEY - Your private API key (hex format)
+//!   * LIGHTER_ACCOUNT_INDEX - Your main account index
+//!   * LIGHTER_API_KEY_INDEX - Your API key index (usually 0)
+//!   * LIGHTER_L1_ADDRESS - The L1 address owning the account
+//!
+//! Run with: cargo run --example create_sub_account
+
+use lighter_rs::client::TxClient;
+use std::env;
+use std::time::Duration;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    tracing_subscriber::fmt::init();
+    tracing::info!("=== Lighter RS: Create Sub-Account Example ===\n");
+
+    // Load configuration from environment variables
+    let api_key =
+        env::var("LIGHTER_API_KEY").expect("LIGHTER_API_KEY environment variable not set");
+
+    let account_index: i64 = env::var("LIGHTER_ACCOUNT_INDEX")
+        .expect("LIGHTER_ACCOUNT_INDEX environment variable not set")
+        .parse()
+        .expect("LIGHTER_ACCOUNT_INDEX must be a valid number");
+
+    let api_key_index: u8 = env::var("LIGHTER_API_KEY_INDEX")
+        .unwrap_or_else(|_| "0".to_string())
+        .parse()
+        .expect("LIGHTER_API_KEY_INDEX must be a valid number");
+
+    let l1_address =
+        env::var("LIGHTER_L1_ADDRESS").expect("LIGHTER_L1_ADDRESS environment variable not set");
+
+    // Testnet configuration
+    let tx_client = TxClient::new(
+        "https://api-testnet.lighter.xyz",
+        &api_key,
+        account_index,
+        api_key_index,
+        300,
+    )?;
+    let http = tx_client.http().expect("client has an API URL");
+
+    // Remember the accounts that exist already
+    let before = http.get_accounts_by_l1_address(&l1_address).await?;
+    tracing::info!("Accounts of {}: {:?}", l1_address, before);
+
+    // Sign and send; the nonce is fetched from the API
+    let response = tx_client.send_create_sub_account(None).await?;
+    if !response.is_success() {
+        tracing::error!(
+            "✗ Create sub-account rejected: {} {:?}",
+            response.code,
+            response.message
+        );
+        return Ok(());
+    }
+    let tx_hash = response.tx_hash.unwrap_or_default();
+    tracing::info!("✓ Create sub-account sent: {}", tx_hash);
+
+    // The response doesn't carry the new index, so wait for execution
+    loop {
+        match http.get_transaction(&tx_hash).await? {
+            Some(status) if status.is_executed() => break,
+            _ => tokio::time::sleep(Duration::from_secs(1)).await,
+        }
+    }
+    tracing::info!("✓ Transaction executed");
+
+    let after = http.get_accounts_by_l1_address(&l1_address).await?;
+    let Some(sub_account) = after.into_iter().find(|index| !before.contains(index)) else {
+        tracing::error!("✗ No new account found for {}", l1_address);
+        return Ok(());
+    };
+    tracing::info!("✓ New sub-account: {}", sub_account);
+
+    let state = http.get_account_state(sub_account).await?;
+    tracing::info!("  Collateral: {} USDC", state.collateral);
+    tracing::info!("  Open positions: {}", state.positions.len());
+
+    tracing::info!("\n✓ Example completed successfully!");
+
+    Ok(())
+}
@@ -0,0 +1,125 @@
+//! Example: Paper Trading Against the Simulator
+//!
+//! Runs a simulated Lighter exchange on localhost, fed with live order books
+//! from the real WebSocket stream or with a recorded frame file, so any bot
+//! can trade against real prices without risking funds.
+//!
+//! Setup:
+//! 1. Start the simulator:
+//!    cargo run --example paper_trading --features simulator
+//! 2. In another terminal, point a bot at it, e.g.:
+//!    LIGHTER_API_URL=http://127.0.0.1:8765 LIGHTER_WS_HOST=ws://127.0.0.1:8765 \
+//!    cargo run --example websocket_circuit_breaker
+//!
+//! Environment:
+//! - LIGHTER_ACCOUNT_INDEX: account to fund (required)
+//! - SIM_COLLATERAL: starting USDC collateral (default 10000)
+//! - SIM_ADDR: address to serve on (default 127.0.0.1:8765)
+//! - SIM_REPLAY: JSONL file of recorded frames to replay instead of the live feed
+//! - LIGHTER_WS_HOST: live feed host (default api-testnet.lighter.xyz)
+
+use dotenv::dotenv;
+use lighter_rs::simulator::SimulatedExchange;
+use lighter_rs::ws_client::WsClient;
+use lighter_rs::Decimal;
+use std::env;
+use std::time::Duration;
+
+// Market 0 scaling: prices in cents, sizes in 1/10000
+const PRICE_DECIMALS: u32 = 2;
+const SIZE_DECIMALS: u32 = 4;
+const REPLAY_INTERVAL: Duration = Duration::from_millis(100);
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    tracing_subscriber::fmt::init();
+    dotenv().ok();
+
+    let account_index: i64 = env::var("LIGHTER_ACCOUNT_INDEX")
+        .expect("LIGHTER_ACCOUNT_INDEX not set")
+        .parse()
+        .expect("LIGHTER_ACCOUNT_INDEX must be a number");
+    let collateral: Decimal = env::var("SIM_COLLATERAL")
+        .unwrap_or_else(|_| "10000".to_string())
+        .parse()
+        .expect("SIM_COLLATERAL must be a number");
+    let chain_id: u32 = env::var("LIGHTER_CHAIN_ID")
+        .unwrap_or_else(|_| "300".to_string())
+        .parse()
+        .unwrap_or(300);
+    let addr = env::var("SIM_ADDR").unwrap_or_else(|_| "127.0.0.1:8765".to_string());
+
+    let exchange = SimulatedExchange::builder()
+        .market(0, PRICE_DECIMALS, SIZE_DECIMALS)
+        .account(account_index, collateral)
+        .chain_id(chain_id)
+        .build()?;
+    let server = exchange.serve(addr.as_str()).await?;
+
+    tracing::info!("✓ Simulator listening on {}", server.addr());
+    tracing::info!(
+        "  Account {} funded with {} USDC",
+        account_index,
+        collateral
+    );
+    tracing::info!("");
+    tracing::info!("Point a bot at it with:");
+    tracing::info!(
+        "  LIGHTER_API_URL={} LIGHTER_WS_HOST={} cargo run --example websocket_circuit_breaker",
+        server.url(),
+        server.ws_host()
+    );
+    tracing::info!("");
+
+    let feed = match env::var("SIM_REPLAY") {
+        Ok(path) => {
+            tracing::info!("✓ Replaying recorded frames from {}", path);
+            let frames = std::fs::read_to_string(&path)?;
+            let exchange = exchange.clone();
+            tokio::spawn(async move {
+                for frame in frames.lines() {
+                    exchange.apply_frame(frame.to_string())?;
+                    tokio::time::sleep(REPLAY_INTERVAL).await;
+                }
+                Ok(())
+            })
+        }
+        Err(_) => {
+            let ws_host = env::var("LIGHTER_WS_HOST")
+                .unwrap_or_else(|_| "api-testnet.lighter.xyz".to_string());
+            tracing::info!("✓ Feeding order books from {}", ws_host);
+            let ws_client = WsClient::builder()
+                .host(&ws_host)
+                .order_books(vec![0])
+                .build()?;
+            exchange.feed_from(ws_client)
+        }
+    };
+
+    tracing::info!("Press Ctrl+C to stop");
+    tokio::select! {
+        result = feed => match result? {
+            Ok(()) => tracing::info!("Feed ended"),
+            Err(e) => tracing::warn!("Feed failed: {}", e),
+        },
+        _ = tokio::signal::ctrl_c() => {}
+    }
+
+    if let Some(account) = exchange.account(account_index) {
+        tracing::info!("");
+        tracing::info!("Final collateral: {} USDC", account.collateral);
+        tracing::info!("Fills: {}", account.fills.len());
+        for (market_index, position) in &account.positions {
+            tracing::info!(
+                "  Market {}: {} @ {} (realized {})",
+                market_index,
+                position.base_amount,
+                position.entry_price,
+                position.realized_pnl
+            );
+        }
+        tracing::info!("Unrealized PnL: {}", exchange.unrealized_pnl(account_index));
+    }
+
+    Ok(())
+}
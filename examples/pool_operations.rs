@@ -37,6 +37,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         expired_at: 1000000000,
         nonce: Some(1),
         dry_run: false,
+        ..Default::default()
     };
 
     let _create_pool_tx = tx_client
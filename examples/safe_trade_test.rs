@@ -55,7 +55,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // IMPORTANT: Place order far from market to ensure it won't fill
     // Current ETH price ~$3000, we'll place buy order at $1 (way below market)
     // This ensures the order will NOT execute
-    let safe_price = 1_000_000u32; // $1.00 with 6 decimals (WAY below market)
+    let safe_price = 1_000_000u64; // $1.00 with 6 decimals (WAY below market)
     let base_amount = 10_000_000i64; // $10 worth (minimum for most markets)
 
     tracing::info!("  Market: ETH (index: {})", market_index);
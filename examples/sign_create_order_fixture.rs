@@ -0,0 +1,32 @@
+/// Sign the create-order fixture offline and print its tx_info JSON
+///
+/// CI feeds this native output to the wasm32 test in `tests/wasm.rs`, which
+/// signs the same fixture in the browser build and must produce identical bytes.
+use lighter_rs::client::TxClient;
+use lighter_rs::types::{CreateOrderTxReq, TransactOpts, TxInfo};
+use serde::Deserialize;
+
+const FIXTURE: &str = include_str!("../benches/fixtures/create_order.json");
+
+#[derive(Deserialize)]
+struct Fixture {
+    private_key: String,
+    chain_id: u32,
+    request: CreateOrderTxReq,
+    opts: TransactOpts,
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let fixture: Fixture = serde_json::from_str(FIXTURE)?;
+    let tx_client = TxClient::builder()
+        .private_key(&fixture.private_key)
+        .chain_id(fixture.chain_id)
+        .build()?;
+
+    let order = tx_client
+        .create_order(&fixture.request, Some(fixture.opts))
+        .await?;
+    println!("{}", order.get_tx_info()?);
+    Ok(())
+}
@@ -5,7 +5,7 @@
 //! 2. Create and send a limit order
 //! 3. Cancel an order
 //! 4. Transfer funds
-//! 5. Update leverage
+//! 5. Update leverage, in cross and isolated margin mode
 //!
 //! Prerequisites:
 //! - Set environment variables:
@@ -67,10 +67,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let order_req = CreateOrderTxReq {
         market_index: 0,
-        client_order_index: chrono::Utc::now().timestamp_millis(), // Use timestamp as unique ID
-        base_amount: 1_000_000,                                    // 1 unit (assuming 6 decimals)
-        price: 100_000_000,                                        // Price
-        is_ask: 0,                                                 // 0 = BUY, 1 = SELL
+        client_order_index: NIL_CLIENT_ORDER_INDEX, // Picked by the client
+        base_amount: 1_000_000,                     // 1 unit (assuming 6 decimals)
+        price: 100_000_000,                         // Price
+        is_ask: 0,                                  // 0 = BUY, 1 = SELL
         order_type: ORDER_TYPE_LIMIT,
         time_in_force: TIME_IN_FORCE_GOOD_TILL_TIME,
         reduce_only: 0,
@@ -118,13 +118,13 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let market_order = tx_client
         .create_market_order(
-            0,                                     // market_index
-            chrono::Utc::now().timestamp_millis(), // client_order_index
-            500_000,                               // base_amount (0.5 units)
-            105_000_000,                           // price (max acceptable price for buy)
-            0,                                     // is_ask (BUY)
-            false,                                 // reduce_only
-            None,                                  // opts
+            0,                      // market_index
+            NIL_CLIENT_ORDER_INDEX, // client_order_index, picked by the client
+            500_000,                // base_amount (0.5 units)
+            105_000_000,            // price (max acceptable price for buy)
+            0,                      // is_ask (BUY)
+            false,                  // reduce_only
+            None,                   // opts
         )
         .await?;
 
@@ -145,14 +145,14 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let sl_order = tx_client
         .create_sl_order(
-            0,                                     // market_index
-            chrono::Utc::now().timestamp_millis(), // client_order_index
-            1_000_000,                             // base_amount
-            95_000_000,                            // trigger_price
-            94_000_000,                            // price
-            1,                                     // is_ask (SELL)
-            false,                                 // reduce_only
-            None,                                  // opts
+            0,                      // market_index
+            NIL_CLIENT_ORDER_INDEX, // client_order_index, picked by the client
+            1_000_000,              // base_amount
+            95_000_000,             // trigger_price
+            94_000_000,             // price
+            1,                      // is_ask (SELL)
+            false,                  // reduce_only
+            None,                   // opts
         )
         .await?;
 
@@ -165,6 +165,22 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let sl_response = tx_client.send_transaction(&sl_order).await?;
     print_tx_response(&sl_response);
 
+    // TWAP: sell 0.01 ETH in slices over 30 minutes, no lower than $3000
+    let twap_order = tx_client
+        .create_twap_order(
+            0,                                       // market_index (ETH)
+            NIL_CLIENT_ORDER_INDEX,                  // client_order_index, picked by the client
+            100,                                     // base_amount (0.01 ETH)
+            300_000,                                 // price bound ($3000.00)
+            1,                                       // is_ask (SELL)
+            std::time::Duration::from_secs(30 * 60), // duration
+            None,                                    // opts
+        )
+        .await?;
+    tracing::info!("\nSubmitting 30 minute TWAP sell...");
+    let twap_response = tx_client.send_transaction(&twap_order).await?;
+    print_tx_response(&twap_response);
+
     tracing::info!("\n");
     tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
 
@@ -189,6 +205,30 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let leverage_response = tx_client.send_transaction(&leverage_tx).await?;
     print_tx_response(&leverage_response);
 
+    // Isolated margin: refused while a cross-margin position is open in the market
+    match tx_client.update_leverage_isolated(1, 3, None).await {
+        Ok(isolated_tx) => {
+            tracing::info!(
+                "\nSwitching market {} to isolated margin at 3x...",
+                isolated_tx.market_index
+            );
+            let isolated_response = tx_client.send_transaction(&isolated_tx).await?;
+            print_tx_response(&isolated_response);
+        }
+        Err(e) => tracing::warn!("Isolated margin not set: {}", e),
+    }
+
+    // Top up an isolated position in the same market with $1 of margin
+    let margin_tx = tx_client
+        .update_margin_usdc(1, 1.0, MARGIN_ADD_TO_ISOLATED, None)
+        .await?;
+    tracing::info!(
+        "\nAdding {} USDC units of isolated margin...",
+        margin_tx.usdc_amount
+    );
+    let margin_response = tx_client.send_transaction(&margin_tx).await?;
+    print_tx_response(&margin_response);
+
     tracing::info!("\n");
     tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
 
@@ -16,7 +16,9 @@
 //! Run with: cargo run --example trading_bot_simple
 
 use lighter_rs::client::TxClient;
+use lighter_rs::utils::price_to_units;
 use lighter_rs::ws_client::{OrderBook, WsClient};
+use rust_decimal::prelude::ToPrimitive;
 use serde_json::Value;
 use std::env;
 use std::sync::atomic::{AtomicBool, Ordering};
@@ -95,8 +97,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             tracing::info!("  Best Bid: {} @ {}", best_bid.size, best_bid.price);
 
             // Parse prices
-            if let (Ok(ask_price), Ok(bid_price)) =
-                (best_ask.price.parse::<f64>(), best_bid.price.parse::<f64>())
+            if let (Some(ask_price), Some(bid_price)) =
+                (best_ask.price.to_f64(), best_bid.price.to_f64())
             {
                 let spread = ask_price - bid_price;
                 let spread_bps = (spread / bid_price) * 10000.0;
@@ -112,8 +114,15 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
                     // Spawn task to place order (non-blocking)
                     tokio::spawn(async move {
-                        // Place a small buy order at mid price
-                        let mid_price = ((ask_price + bid_price) / 2.0) as u32;
+                        // Place a small buy order at mid price, in ticks of
+                        // ETH's 2 price decimals
+                        let mid_price = match price_to_units((ask_price + bid_price) / 2.0, 2) {
+                            Ok(price) => price,
+                            Err(e) => {
+                                tracing::error!("  ✗ Failed to price order: {}", e);
+                                return;
+                            }
+                        };
 
                         match tx_client
                             .create_market_order(
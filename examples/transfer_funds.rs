@@ -47,6 +47,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         expired_at: 1000000000,
         nonce: Some(1),
         dry_run: false,
+        ..Default::default()
     };
 
     // Sign the transaction
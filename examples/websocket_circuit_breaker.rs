@@ -20,6 +20,8 @@
 use dotenv::dotenv;
 use lighter_rs::client::TxClient;
 use lighter_rs::ws_client::{OrderBook, WsClient};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
 use serde_json::Value;
 use std::env;
 use std::sync::atomic::{AtomicU32, AtomicU8, Ordering};
@@ -200,8 +202,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             if let (Some(best_ask), Some(best_bid)) =
                 (order_book.asks.first(), order_book.bids.first())
             {
-                if let (Ok(ask_price), Ok(bid_price)) =
-                    (best_ask.price.parse::<f64>(), best_bid.price.parse::<f64>())
+                if let (Some(ask_price), Some(bid_price)) =
+                    (best_ask.price.to_f64(), best_bid.price.to_f64())
                 {
                     let spread = ask_price - bid_price;
                     let spread_bps = (spread / bid_price) * 10000.0;
@@ -230,12 +232,13 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
                             // Place a small market buy order
                             let result = tx_client
-                                .create_market_order(
+                                .create_market_order_with_slippage(
                                     market_id_num,
                                     chrono::Utc::now().timestamp_millis(),
-                                    100_000,                   // Small size for demo
-                                    (mid_price * 1.01) as u32, // 1% slippage tolerance
-                                    0,                         // BUY
+                                    100_000, // Small size for demo
+                                    (best_ask.price + best_bid.price) / Decimal::TWO,
+                                    100, // 1% slippage tolerance
+                                    0,   // BUY
                                     false,
                                     None,
                                 )
@@ -8,6 +8,7 @@
 //! Run with: cargo run --example websocket_combined
 
 use lighter_rs::ws_client::{OrderBook, WsClient};
+use rust_decimal::prelude::ToPrimitive;
 use serde_json::Value;
 use std::env;
 
@@ -51,9 +52,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             tracing::info!("  Best Ask: {} @ {}", best_ask.size, best_ask.price);
             tracing::info!("  Best Bid: {} @ {}", best_bid.size, best_bid.price);
 
-            if let (Ok(ask), Ok(bid)) =
-                (best_ask.price.parse::<f64>(), best_bid.price.parse::<f64>())
-            {
+            if let (Some(ask), Some(bid)) = (best_ask.price.to_f64(), best_bid.price.to_f64()) {
                 let mid = (ask + bid) / 2.0;
                 tracing::info!("  Mid Price: {:.2}", mid);
             }
@@ -8,6 +8,7 @@
 //! Run with: cargo run --example websocket_orderbook
 
 use lighter_rs::ws_client::{OrderBook, WsClient};
+use rust_decimal::prelude::ToPrimitive;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -54,8 +55,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         // Calculate spread
         if let (Some(best_ask), Some(best_bid)) = (order_book.asks.first(), order_book.bids.first())
         {
-            if let (Ok(ask_price), Ok(bid_price)) =
-                (best_ask.price.parse::<f64>(), best_bid.price.parse::<f64>())
+            if let (Some(ask_price), Some(bid_price)) =
+                (best_ask.price.to_f64(), best_bid.price.to_f64())
             {
                 let spread = ask_price - bid_price;
                 let spread_bps = (spread / bid_price) * 10000.0;
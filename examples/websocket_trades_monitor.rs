@@ -13,7 +13,9 @@
 
 use dotenv::dotenv;
 use lighter_rs::client::TxClient;
+use lighter_rs::utils::price_to_units;
 use lighter_rs::ws_client::{OrderBook, WsClient};
+use rust_decimal::prelude::ToPrimitive;
 use serde_json::Value;
 use std::env;
 use std::sync::atomic::{AtomicU32, Ordering};
@@ -98,8 +100,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
         if let (Some(best_ask), Some(best_bid)) = (order_book.asks.first(), order_book.bids.first())
         {
-            if let (Ok(ask_price), Ok(bid_price)) =
-                (best_ask.price.parse::<f64>(), best_bid.price.parse::<f64>())
+            if let (Some(ask_price), Some(bid_price)) =
+                (best_ask.price.to_f64(), best_bid.price.to_f64())
             {
                 let mid_price = (ask_price + bid_price) / 2.0;
                 let spread = ask_price - bid_price;
@@ -116,14 +118,14 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                     .asks
                     .iter()
                     .take(5)
-                    .filter_map(|level| level.size.parse::<f64>().ok())
+                    .filter_map(|level| level.size.to_f64())
                     .sum();
 
                 let bid_depth: f64 = order_book
                     .bids
                     .iter()
                     .take(5)
-                    .filter_map(|level| level.size.parse::<f64>().ok())
+                    .filter_map(|level| level.size.to_f64())
                     .sum();
 
                 tracing::info!(
@@ -165,19 +167,25 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                                 mid_price
                             );
 
-                            // Create small market order
-                            match tx_client
-                                .create_market_order(
-                                    0,
-                                    chrono::Utc::now().timestamp_millis(),
-                                    50_000, // Very small size
-                                    mid_price as u32,
-                                    is_ask,
-                                    false,
-                                    None,
-                                )
-                                .await
-                            {
+                            // Create small market order, priced in ticks of
+                            // ETH's 2 price decimals
+                            let order = match price_to_units(mid_price, 2) {
+                                Ok(price) => {
+                                    tx_client
+                                        .create_market_order(
+                                            0,
+                                            chrono::Utc::now().timestamp_millis(),
+                                            50_000, // Very small size
+                                            price,
+                                            is_ask,
+                                            false,
+                                            None,
+                                        )
+                                        .await
+                                }
+                                Err(e) => Err(e),
+                            };
+                            match order {
                                 Ok(order) => {
                                     tracing::info!("     ✓ Order signed (nonce: {})", order.nonce);
 
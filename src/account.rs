@@ -0,0 +1,355 @@
+//! Account balances and cross-margin requirements
+//!
+//! [`AccountState`] holds an account's collateral and positions, as fetched
+//! with [`HTTPClient::get_account_state`](crate::client::HTTPClient::get_account_state).
+//! [`AccountState::margin_summary`] combines them with the markets' margin
+//! fractions from [`HTTPClient::get_market_details`](crate::client::HTTPClient::get_market_details):
+//!
+//! - equity is collateral plus the unrealized PnL of every position
+//! - initial margin is each position's notional times its initial margin
+//!   fraction, which is set by the leverage chosen for the market
+//! - maintenance margin is each position's notional times the market's
+//!   maintenance margin fraction
+//! - free collateral is equity less initial margin, and never negative
+//! - the additional notional a market can take is free collateral divided by
+//!   the initial margin fraction of the requested leverage
+//!
+//! Margin held by resting orders is not counted, so free collateral is an
+//! upper bound while orders are open.
+//!
+//! ```
+//! use lighter_rs::account::AccountState;
+//! use lighter_rs::client::MarketDetails;
+//! use lighter_rs::Decimal;
+//!
+//! # fn example(account: AccountState, markets: Vec<MarketDetails>) -> lighter_rs::Result<()> {
+//! let summary = account.margin_summary(&markets, 10)?;
+//! println!("equity {} free {}", summary.equity, summary.free_collateral);
+//! for market in &summary.markets {
+//!     println!("market {} can add {}", market.market_id, market.max_additional_notional);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::client::{AccountPosition, MarketDetails};
+use crate::constants::MARGIN_FRACTION_TICK;
+use crate::errors::{LighterError, Result};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+
+/// Collateral and positions of an account
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct AccountState {
+    #[serde(default, alias = "index")]
+    pub account_index: i64,
+    /// L1 address owning the account, and any sub-accounts of it
+    #[serde(default)]
+    pub l1_address: String,
+    /// Deposited collateral in USDC, before unrealized PnL
+    #[serde(default)]
+    pub collateral: Decimal,
+    #[serde(default)]
+    pub positions: Vec<AccountPosition>,
+}
+
+/// Margin figures of one market, returned in [`MarginSummary::markets`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MarketMargin {
+    pub market_id: u8,
+    /// Absolute notional of the open position
+    pub notional: Decimal,
+    pub initial_margin: Decimal,
+    pub maintenance_margin: Decimal,
+    /// Notional that can still be opened at the requested leverage
+    pub max_additional_notional: Decimal,
+}
+
+/// Account-wide margin figures returned by [`AccountState::margin_summary`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MarginSummary {
+    /// Collateral plus unrealized PnL
+    pub equity: Decimal,
+    /// Initial margin used by open positions
+    pub initial_margin: Decimal,
+    /// Margin below which the account can be liquidated
+    pub maintenance_margin: Decimal,
+    /// Equity not used as initial margin
+    pub free_collateral: Decimal,
+    /// One entry per market passed in, in the same order
+    pub markets: Vec<MarketMargin>,
+}
+
+impl MarginSummary {
+    /// Whether equity has fallen below the maintenance margin
+    pub fn is_liquidatable(&self) -> bool {
+        self.equity < self.maintenance_margin
+    }
+}
+
+impl AccountState {
+    /// Compute margin usage and headroom at `leverage` in each of `markets`
+    ///
+    /// `markets` must cover every market the account holds a position in.
+    /// Leverage is capped by each market's minimum initial margin fraction,
+    /// just as [`TxClient::update_leverage_with_multiplier`](crate::client::TxClient::update_leverage_with_multiplier)
+    /// converts it.
+    pub fn margin_summary(
+        &self,
+        markets: &[MarketDetails],
+        leverage: u16,
+    ) -> Result<MarginSummary> {
+        if leverage == 0 {
+            return Err(LighterError::ValidationError(
+                "Leverage must be greater than 0".to_string(),
+            ));
+        }
+
+        let market = |market_id: u8| {
+            markets
+                .iter()
+                .find(|market| market.market_id == market_id)
+                .ok_or_else(|| {
+                    LighterError::InvalidConfiguration(format!(
+                        "No market details for market {market_id}"
+                    ))
+                })
+        };
+
+        let mut equity = self.collateral;
+        let mut positions = Vec::with_capacity(self.positions.len());
+        for position in &self.positions {
+            equity += position.unrealized_pnl;
+            if position.position.is_zero() {
+                continue;
+            }
+            let market = market(position.market_id)?;
+            let notional = position_notional(position, market);
+            let initial_fraction = position_initial_fraction(position, market);
+            positions.push((
+                position.market_id,
+                notional,
+                notional * initial_fraction,
+                notional * ticks_to_fraction(market.maintenance_margin_fraction),
+            ));
+        }
+
+        let initial_margin: Decimal = positions.iter().map(|(_, _, initial, _)| *initial).sum();
+        let maintenance_margin = positions.iter().map(|(_, _, _, maint)| *maint).sum();
+        let free_collateral = (equity - initial_margin).max(Decimal::ZERO);
+
+        let leverage_ticks = MARGIN_FRACTION_TICK / i64::from(leverage);
+        let markets = markets
+            .iter()
+            .map(|market| {
+                let (notional, initial_margin, maintenance_margin) = positions
+                    .iter()
+                    .filter(|(market_id, ..)| *market_id == market.market_id)
+                    .fold(
+                        (Decimal::ZERO, Decimal::ZERO, Decimal::ZERO),
+                        |(n, i, m), (_, notional, initial, maint)| {
+                            (n + notional, i + initial, m + maint)
+                        },
+                    );
+                let fraction =
+                    Decimal::from(leverage_ticks.max(market.min_initial_margin_fraction.into()))
+                        / Decimal::from(MARGIN_FRACTION_TICK);
+                let max_additional_notional = if fraction.is_zero() {
+                    Decimal::ZERO
+                } else {
+                    free_collateral / fraction
+                };
+                MarketMargin {
+                    market_id: market.market_id,
+                    notional,
+                    initial_margin,
+                    maintenance_margin,
+                    max_additional_notional,
+                }
+            })
+            .collect();
+
+        Ok(MarginSummary {
+            equity,
+            initial_margin,
+            maintenance_margin,
+            free_collateral,
+            markets,
+        })
+    }
+}
+
+fn ticks_to_fraction(ticks: u32) -> Decimal {
+    Decimal::from(ticks) / Decimal::from(MARGIN_FRACTION_TICK)
+}
+
+/// Notional as reported by the API, or marked at the last trade price when missing
+fn position_notional(position: &AccountPosition, market: &MarketDetails) -> Decimal {
+    if position.position_value.is_zero() {
+        position.position * market.last_trade_price
+    } else {
+        position.position_value.abs()
+    }
+}
+
+/// Fraction from the position's leverage setting, floored at the market minimum
+fn position_initial_fraction(position: &AccountPosition, market: &MarketDetails) -> Decimal {
+    // The API reports the position's fraction in percent
+    let fraction = position.initial_margin_fraction / Decimal::ONE_HUNDRED;
+    fraction.max(ticks_to_fraction(market.min_initial_margin_fraction))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dec(value: &str) -> Decimal {
+        value.parse().unwrap()
+    }
+
+    fn market(market_id: u8, last: &str, min_imf: u32, mmf: u32) -> MarketDetails {
+        serde_json::from_value(serde_json::json!({
+            "market_id": market_id,
+            "size_decimals": 4,
+            "price_decimals": 2,
+            "last_trade_price": last,
+            "min_initial_margin_fraction": min_imf,
+            "maintenance_margin_fraction": mmf,
+        }))
+        .unwrap()
+    }
+
+    fn position(
+        market_id: u8,
+        sign: i8,
+        size: &str,
+        value: &str,
+        imf: &str,
+        upnl: &str,
+    ) -> AccountPosition {
+        serde_json::from_value(serde_json::json!({
+            "market_id": market_id,
+            "sign": sign,
+            "position": size,
+            "position_value": value,
+            "initial_margin_fraction": imf,
+            "unrealized_pnl": upnl,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_flat_account_can_use_full_leverage() {
+        let account = AccountState {
+            account_index: 1,
+            l1_address: String::new(),
+            collateral: dec("1000"),
+            positions: vec![],
+        };
+        // ETH allows 25x (4%), BTC 50x (2%)
+        let markets = [market(0, "3000", 400, 250), market(1, "60000", 200, 120)];
+
+        let summary = account.margin_summary(&markets, 10).unwrap();
+        assert_eq!(summary.equity, dec("1000"));
+        assert_eq!(summary.initial_margin, Decimal::ZERO);
+        assert_eq!(summary.free_collateral, dec("1000"));
+        assert_eq!(summary.markets[0].max_additional_notional, dec("10000"));
+
+        // 50x is capped at ETH's 25x but allowed on BTC
+        let summary = account.margin_summary(&markets, 50).unwrap();
+        assert_eq!(summary.markets[0].max_additional_notional, dec("25000"));
+        assert_eq!(summary.markets[1].max_additional_notional, dec("50000"));
+    }
+
+    #[test]
+    fn test_positions_use_margin() {
+        let account = AccountState {
+            account_index: 1,
+            l1_address: String::new(),
+            collateral: dec("2000"),
+            positions: vec![
+                // 2 ETH long at 10x, up 100
+                position(0, 1, "2", "6100", "10.00", "100"),
+                // 0.05 BTC short at 20x, down 50
+                position(1, -1, "0.05", "3050", "5.00", "-50"),
+            ],
+        };
+        let markets = [market(0, "3050", 400, 250), market(1, "61000", 200, 120)];
+
+        let summary = account.margin_summary(&markets, 5).unwrap();
+        // 2000 + 100 - 50
+        assert_eq!(summary.equity, dec("2050"));
+        // 6100 * 10% + 3050 * 5% = 610 + 152.5
+        assert_eq!(summary.initial_margin, dec("762.5"));
+        // 6100 * 2.5% + 3050 * 1.2% = 152.5 + 36.6
+        assert_eq!(summary.maintenance_margin, dec("189.1"));
+        assert_eq!(summary.free_collateral, dec("1287.5"));
+        assert!(!summary.is_liquidatable());
+
+        assert_eq!(summary.markets[0].notional, dec("6100"));
+        assert_eq!(summary.markets[0].initial_margin, dec("610"));
+        assert_eq!(summary.markets[1].maintenance_margin, dec("36.6"));
+        // 1287.5 at 5x
+        assert_eq!(summary.markets[0].max_additional_notional, dec("6437.5"));
+        assert_eq!(summary.markets[1].max_additional_notional, dec("6437.5"));
+    }
+
+    #[test]
+    fn test_missing_position_value_marks_at_last_trade() {
+        let account = AccountState {
+            account_index: 1,
+            l1_address: String::new(),
+            collateral: dec("500"),
+            // Leverage below the market minimum is floored at 4%
+            positions: vec![position(0, 1, "1", "0", "1.00", "0")],
+        };
+        let markets = [market(0, "3000", 400, 250)];
+
+        let summary = account.margin_summary(&markets, 3).unwrap();
+        assert_eq!(summary.markets[0].notional, dec("3000"));
+        assert_eq!(summary.initial_margin, dec("120"));
+        assert_eq!(summary.free_collateral, dec("380"));
+        // 380 at 10_000 / 3 = 3333 ticks
+        assert_eq!(
+            summary.markets[0].max_additional_notional,
+            dec("380") / dec("0.3333")
+        );
+    }
+
+    #[test]
+    fn test_underwater_account() {
+        let account = AccountState {
+            account_index: 1,
+            l1_address: String::new(),
+            collateral: dec("300"),
+            positions: vec![position(0, 1, "3", "8400", "4.00", "-600")],
+        };
+        let markets = [market(0, "2800", 400, 250)];
+
+        let summary = account.margin_summary(&markets, 10).unwrap();
+        assert_eq!(summary.equity, dec("-300"));
+        assert_eq!(summary.initial_margin, dec("336"));
+        assert_eq!(summary.maintenance_margin, dec("210"));
+        assert_eq!(summary.free_collateral, Decimal::ZERO);
+        assert_eq!(summary.markets[0].max_additional_notional, Decimal::ZERO);
+        assert!(summary.is_liquidatable());
+    }
+
+    #[test]
+    fn test_rejects_missing_market_and_zero_leverage() {
+        let account = AccountState {
+            account_index: 1,
+            l1_address: String::new(),
+            collateral: dec("100"),
+            positions: vec![position(7, 1, "1", "100", "10.00", "0")],
+        };
+        assert!(matches!(
+            account.margin_summary(&[], 5),
+            Err(LighterError::InvalidConfiguration(_))
+        ));
+        assert!(matches!(
+            account.margin_summary(&[market(7, "100", 500, 300)], 0),
+            Err(LighterError::ValidationError(_))
+        ));
+    }
+}
@@ -0,0 +1,112 @@
+//! Auth tokens for authenticated REST endpoints and private WebSocket channels
+//!
+//! A token is `{deadline}:{account_index}:{api_key_index}:{signature}`, the
+//! deadline in Unix seconds and the signature in hex without a `0x` prefix,
+//! as built by lighter-go's `ConstructAuthToken`. The API key signs the
+//! Poseidon2 hash of the message before the signature, its bytes read as
+//! little-endian field elements of 8 bytes each.
+//!
+//! ```no_run
+//! use lighter_rs::client::TxClient;
+//!
+//! # async fn example(tx_client: TxClient) -> lighter_rs::Result<()> {
+//! let auth = tx_client.create_auth_token(None)?;
+//! let http = tx_client.http().expect("client has an API URL");
+//! let trades = http.get_account_trades(tx_client.account_index(), 10, &auth).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use chrono::{DateTime, Utc};
+
+use crate::client::TxClient;
+use crate::constants::DEFAULT_AUTH_TOKEN_EXPIRY;
+use crate::errors::{LighterError, Result};
+use crate::signer::Signer;
+
+/// The message an auth token signs, `{deadline}:{account_index}:{api_key_index}`
+pub fn auth_token_message(
+    deadline: DateTime<Utc>,
+    account_index: i64,
+    api_key_index: u8,
+) -> String {
+    format!("{}:{account_index}:{api_key_index}", deadline.timestamp())
+}
+
+/// Poseidon2 hash of an auth token message, as signed
+pub fn auth_token_hash(message: &str) -> Vec<u8> {
+    use poseidon_hash::{hash_to_quintic_extension, Goldilocks};
+
+    let elements: Vec<Goldilocks> = message
+        .as_bytes()
+        .chunks(8)
+        .map(|chunk| {
+            let mut bytes = [0u8; 8];
+            bytes[..chunk.len()].copy_from_slice(chunk);
+            Goldilocks::from(u64::from_le_bytes(bytes))
+        })
+        .collect();
+    hash_to_quintic_extension(&elements).to_bytes_le().to_vec()
+}
+
+impl TxClient {
+    /// Create an auth token for the client's account and API key, valid
+    /// until `deadline`
+    ///
+    /// Without a deadline the token is valid for
+    /// [`DEFAULT_AUTH_TOKEN_EXPIRY`] from now. A deadline that has passed is
+    /// refused.
+    pub fn create_auth_token(&self, deadline: Option<DateTime<Utc>>) -> Result<String> {
+        let now = self.clock().now_utc();
+        let deadline =
+            deadline.unwrap_or(now + chrono::Duration::milliseconds(DEFAULT_AUTH_TOKEN_EXPIRY));
+        if deadline <= now {
+            return Err(LighterError::ValidationError(format!(
+                "Auth token deadline {deadline} has passed"
+            )));
+        }
+
+        let message = auth_token_message(deadline, self.account_index(), self.api_key_index());
+        let signature = self.key_manager().sign(&auth_token_hash(&message))?;
+        Ok(format!("{message}:{}", hex::encode(signature)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::test_support::TEST_KEY;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_auth_token_is_signed_by_the_api_key() {
+        let tx_client = TxClient::builder()
+            .private_key(TEST_KEY)
+            .account_index(7)
+            .api_key_index(2)
+            .chain_id(304)
+            .clock(Arc::new(ManualClock::at_ms(1_700_000_000_000)))
+            .build()
+            .unwrap();
+
+        // Ten minutes from now by default
+        let token = tx_client.create_auth_token(None).unwrap();
+        let (message, signature) = token.rsplit_once(':').unwrap();
+        assert_eq!(message, "1700000600:7:2");
+        assert!(tx_client
+            .key_manager()
+            .verify(&auth_token_hash(message), &hex::decode(signature).unwrap())
+            .unwrap());
+
+        let deadline = DateTime::from_timestamp(1_700_003_600, 0).unwrap();
+        let token = tx_client.create_auth_token(Some(deadline)).unwrap();
+        assert!(token.starts_with("1700003600:7:2:"));
+
+        let passed = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        assert!(matches!(
+            tx_client.create_auth_token(Some(passed)),
+            Err(LighterError::ValidationError(_))
+        ));
+    }
+}
@@ -0,0 +1,464 @@
+//! Synchronous transaction client, enabled with the `blocking` feature
+//!
+//! [`TxClient`] wraps the async [`client::TxClient`](crate::client::TxClient)
+//! with its own current-thread tokio runtime, the way `reqwest::blocking` does,
+//! so synchronous code can sign and submit transactions without managing a
+//! runtime. The WebSocket client stays async-only.
+//!
+//! ```no_run
+//! use lighter_rs::blocking::TxClient;
+//!
+//! # fn example() -> lighter_rs::Result<()> {
+//! let tx_client = TxClient::new("https://api.lighter.xyz", "your_api_key_hex", 12345, 0, 304)?;
+//! let order = tx_client.create_limit_order(0, 1, 1000, 3_000_000_000, 0, false, None)?;
+//! let response = tx_client.send_transaction(&order)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::future::Future;
+
+use tokio::runtime::{Handle, Runtime};
+
+use crate::client::{
+    self, build_http_client, ChunkPolicy, PipelinedOutcome, SubmitAllReport, TxClientBuilder,
+    TxResponse,
+};
+use crate::errors::{LighterError, Result};
+use crate::kill_switch::{KillSwitchConfig, KillSwitchReport};
+use crate::nonce::NonceManager;
+use crate::trigger_direction::TriggerCheck;
+use crate::types::*;
+
+/// Blocking transaction client
+///
+/// Every method blocks the calling thread until the underlying async call
+/// completes. The client must be created and dropped outside of an async
+/// runtime: [`TxClient::new`] returns an error when called from one, and
+/// dropping the client inside a runtime panics, as with `reqwest::blocking`.
+pub struct TxClient {
+    inner: client::TxClient,
+    runtime: Runtime,
+}
+
+impl TxClient {
+    /// Create a new blocking transaction client
+    ///
+    /// Takes the same arguments as [`client::TxClient::new`].
+    pub fn new(
+        api_client_url: &str,
+        api_key_private_key: &str,
+        account_index: i64,
+        api_key_index: u8,
+        chain_id: u32,
+    ) -> Result<Self> {
+        Self::from_builder(
+            TxClientBuilder::new()
+                .api_url(api_client_url)
+                .private_key(api_key_private_key)
+                .account_index(account_index)
+                .api_key_index(api_key_index)
+                .chain_id(chain_id),
+        )
+    }
+
+    /// Build a blocking client from an async client builder
+    ///
+    /// The client always gets its own connection pool: pooled connections are
+    /// driven by the runtime that opened them, so sharing a pool with clients
+    /// on other runtimes could stall requests.
+    pub fn from_builder(builder: TxClientBuilder) -> Result<Self> {
+        if Handle::try_current().is_ok() {
+            return Err(LighterError::InvalidConfiguration(
+                "blocking::TxClient cannot be created inside an async runtime; use client::TxClient instead"
+                    .to_string(),
+            ));
+        }
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| {
+                LighterError::InvalidConfiguration(format!("Failed to start runtime: {e}"))
+            })?;
+        let inner = builder.http_client(build_http_client()?).build()?;
+
+        Ok(Self { inner, runtime })
+    }
+
+    fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.runtime.block_on(future)
+    }
+
+    /// Get the wrapped async client
+    pub fn inner(&self) -> &client::TxClient {
+        &self.inner
+    }
+
+    /// Get the account index
+    pub fn account_index(&self) -> i64 {
+        self.inner.account_index()
+    }
+
+    /// Get the API key index
+    pub fn api_key_index(&self) -> u8 {
+        self.inner.api_key_index()
+    }
+
+    /// Get the local nonce cache
+    pub fn nonces(&self) -> &NonceManager {
+        self.inner.nonces()
+    }
+
+    /// Fetch the next nonce from the API and seed the local cache with it
+    pub fn warm_up(&self) -> Result<()> {
+        self.block_on(self.inner.warm_up())
+    }
+
+    /// Construct and sign a create order transaction
+    pub fn create_order(
+        &self,
+        req: &CreateOrderTxReq,
+        opts: Option<TransactOpts>,
+    ) -> Result<L2CreateOrderTxInfo> {
+        self.block_on(self.inner.create_order(req, opts))
+    }
+
+    /// Create a stop loss or take profit order, checking its trigger against
+    /// the market first
+    pub fn create_trigger_order(
+        &self,
+        req: &CreateOrderTxReq,
+        check: TriggerCheck,
+        opts: Option<TransactOpts>,
+    ) -> Result<L2CreateOrderTxInfo> {
+        self.block_on(self.inner.create_trigger_order(req, check, opts))
+    }
+
+    /// Construct and sign several create order transactions with consecutive nonces
+    pub fn create_orders(
+        &self,
+        reqs: &[CreateOrderTxReq],
+        opts: Option<TransactOpts>,
+    ) -> Result<Vec<L2CreateOrderTxInfo>> {
+        self.block_on(self.inner.create_orders(reqs, opts))
+    }
+
+    /// Construct and sign a cancel order transaction
+    pub fn cancel_order(
+        &self,
+        req: &CancelOrderTxReq,
+        opts: Option<TransactOpts>,
+    ) -> Result<L2CancelOrderTxInfo> {
+        self.block_on(self.inner.cancel_order(req, opts))
+    }
+
+    /// Sign a cancel of the order placed with `client_order_index`
+    pub fn cancel_order_by_client_index(
+        &self,
+        market_index: u8,
+        client_order_index: i64,
+        opts: Option<TransactOpts>,
+    ) -> Result<L2CancelOrderTxInfo> {
+        self.block_on(self.inner.cancel_order_by_client_index(
+            market_index,
+            client_order_index,
+            opts,
+        ))
+    }
+
+    /// Sign a cancel of the order the exchange assigned `order_index`
+    pub fn cancel_order_by_order_index(
+        &self,
+        market_index: u8,
+        order_index: i64,
+        opts: Option<TransactOpts>,
+    ) -> Result<L2CancelOrderTxInfo> {
+        self.block_on(
+            self.inner
+                .cancel_order_by_order_index(market_index, order_index, opts),
+        )
+    }
+
+    /// Construct and sign a modify order transaction
+    pub fn modify_order(
+        &self,
+        req: &ModifyOrderTxReq,
+        opts: Option<TransactOpts>,
+    ) -> Result<L2ModifyOrderTxInfo> {
+        self.block_on(self.inner.modify_order(req, opts))
+    }
+
+    /// Construct and sign a cancel all orders transaction
+    pub fn cancel_all_orders(
+        &self,
+        req: &CancelAllOrdersTxReq,
+        opts: Option<TransactOpts>,
+    ) -> Result<L2CancelAllOrdersTxInfo> {
+        self.block_on(self.inner.cancel_all_orders(req, opts))
+    }
+
+    /// Create a limit order
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_limit_order(
+        &self,
+        market_index: u8,
+        client_order_index: i64,
+        base_amount: i64,
+        price: u64,
+        is_ask: u8,
+        reduce_only: bool,
+        opts: Option<TransactOpts>,
+    ) -> Result<L2CreateOrderTxInfo> {
+        self.block_on(self.inner.create_limit_order(
+            market_index,
+            client_order_index,
+            base_amount,
+            price,
+            is_ask,
+            reduce_only,
+            opts,
+        ))
+    }
+
+    /// Create a limit order with the given time in force
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_limit_order_with_tif(
+        &self,
+        market_index: u8,
+        client_order_index: i64,
+        base_amount: i64,
+        price: u64,
+        is_ask: u8,
+        time_in_force: u8,
+        reduce_only: bool,
+        opts: Option<TransactOpts>,
+    ) -> Result<L2CreateOrderTxInfo> {
+        self.block_on(self.inner.create_limit_order_with_tif(
+            market_index,
+            client_order_index,
+            base_amount,
+            price,
+            is_ask,
+            time_in_force,
+            reduce_only,
+            opts,
+        ))
+    }
+
+    /// Create a market order
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_market_order(
+        &self,
+        market_index: u8,
+        client_order_index: i64,
+        base_amount: i64,
+        price: u64,
+        is_ask: u8,
+        reduce_only: bool,
+        opts: Option<TransactOpts>,
+    ) -> Result<L2CreateOrderTxInfo> {
+        self.block_on(self.inner.create_market_order(
+            market_index,
+            client_order_index,
+            base_amount,
+            price,
+            is_ask,
+            reduce_only,
+            opts,
+        ))
+    }
+
+    /// Create a take profit order
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_tp_order(
+        &self,
+        market_index: u8,
+        client_order_index: i64,
+        base_amount: i64,
+        trigger_price: u64,
+        price: u64,
+        is_ask: u8,
+        reduce_only: bool,
+        opts: Option<TransactOpts>,
+    ) -> Result<L2CreateOrderTxInfo> {
+        self.block_on(self.inner.create_tp_order(
+            market_index,
+            client_order_index,
+            base_amount,
+            trigger_price,
+            price,
+            is_ask,
+            reduce_only,
+            opts,
+        ))
+    }
+
+    /// Create a take profit limit order
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_tp_limit_order(
+        &self,
+        market_index: u8,
+        client_order_index: i64,
+        base_amount: i64,
+        trigger_price: u64,
+        price: u64,
+        is_ask: u8,
+        reduce_only: bool,
+        opts: Option<TransactOpts>,
+    ) -> Result<L2CreateOrderTxInfo> {
+        self.block_on(self.inner.create_tp_limit_order(
+            market_index,
+            client_order_index,
+            base_amount,
+            trigger_price,
+            price,
+            is_ask,
+            reduce_only,
+            opts,
+        ))
+    }
+
+    /// Create a stop loss order
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_sl_order(
+        &self,
+        market_index: u8,
+        client_order_index: i64,
+        base_amount: i64,
+        trigger_price: u64,
+        price: u64,
+        is_ask: u8,
+        reduce_only: bool,
+        opts: Option<TransactOpts>,
+    ) -> Result<L2CreateOrderTxInfo> {
+        self.block_on(self.inner.create_sl_order(
+            market_index,
+            client_order_index,
+            base_amount,
+            trigger_price,
+            price,
+            is_ask,
+            reduce_only,
+            opts,
+        ))
+    }
+
+    /// Create a stop loss limit order
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_sl_limit_order(
+        &self,
+        market_index: u8,
+        client_order_index: i64,
+        base_amount: i64,
+        trigger_price: u64,
+        price: u64,
+        is_ask: u8,
+        reduce_only: bool,
+        opts: Option<TransactOpts>,
+    ) -> Result<L2CreateOrderTxInfo> {
+        self.block_on(self.inner.create_sl_limit_order(
+            market_index,
+            client_order_index,
+            base_amount,
+            trigger_price,
+            price,
+            is_ask,
+            reduce_only,
+            opts,
+        ))
+    }
+
+    /// Submit signed transactions with up to `max_in_flight` requests in flight
+    ///
+    /// See [`client::TxClient::submit_pipelined`].
+    pub fn submit_pipelined(
+        &self,
+        txs: Vec<SignedTx>,
+        max_in_flight: usize,
+    ) -> Result<Vec<PipelinedOutcome>> {
+        self.block_on(self.inner.submit_pipelined(txs, max_in_flight))
+    }
+
+    /// Submit any number of signed transactions in batches
+    ///
+    /// See [`client::TxClient::submit_all`].
+    pub fn submit_all(&self, txs: Vec<SignedTx>, policy: ChunkPolicy) -> Result<SubmitAllReport> {
+        self.block_on(self.inner.submit_all(txs, policy))
+    }
+
+    /// Send a signed transaction to the API
+    pub fn send_transaction<T: TxInfo>(&self, tx_info: &T) -> Result<TxResponse> {
+        self.block_on(self.inner.send_transaction(tx_info))
+    }
+
+    /// Cancel every order and flatten every position
+    ///
+    /// See [`client::TxClient::kill_switch`].
+    pub fn kill_switch(&self, config: &KillSwitchConfig) -> KillSwitchReport {
+        self.block_on(self.inner.kill_switch(config))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TEST_KEY;
+
+    #[test]
+    fn test_signs_without_api() {
+        let tx_client = TxClient::new("", TEST_KEY, 1, 0, 304).unwrap();
+        tx_client.nonces().set(1, 0, 7);
+
+        let order = tx_client
+            .create_limit_order(0, 1, 1000, 3_000_000_000, 0, false, None)
+            .unwrap();
+        assert_eq!(order.nonce, 7);
+        assert!(order.sig.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_rejected_inside_runtime() {
+        assert!(matches!(
+            TxClient::new("", TEST_KEY, 1, 0, 304),
+            Err(LighterError::InvalidConfiguration(_))
+        ));
+    }
+
+    #[cfg(feature = "test-util")]
+    #[test]
+    fn test_blocking_client_against_mock_server() {
+        use crate::testing::{MockLighter, NEXT_NONCE_PATH, SEND_TX_PATH};
+
+        // The mock server runs on its own runtime, outside the calling thread
+        let server = Runtime::new().unwrap();
+        let mock = server.block_on(MockLighter::start()).unwrap();
+        mock.set_response(NEXT_NONCE_PATH, 200, r#"{"code":200,"nonce":12}"#);
+
+        let tx_client = TxClient::new(&mock.url(), TEST_KEY, 1, 0, 304).unwrap();
+        let order = tx_client
+            .create_limit_order(0, 1, 1000, 3_000_000_000, 0, false, None)
+            .unwrap();
+        assert!(tx_client.send_transaction(&order).unwrap().is_success());
+
+        let cancel = tx_client
+            .cancel_order(
+                &CancelOrderTxReq {
+                    market_index: 0,
+                    index: 1,
+                },
+                None,
+            )
+            .unwrap();
+        assert_eq!(cancel.nonce, 13);
+        assert!(tx_client.send_transaction(&cancel).unwrap().is_success());
+
+        let sent = mock.requests_to(SEND_TX_PATH);
+        assert_eq!(sent.len(), 2);
+        assert_eq!(
+            sent[1].form_field("tx_info").unwrap(),
+            Some(cancel.get_tx_info().unwrap())
+        );
+        assert_eq!(mock.requests_to(NEXT_NONCE_PATH).len(), 1);
+    }
+}
@@ -0,0 +1,932 @@
+//! Recording order books to CSV or Parquet for research
+//!
+//! A [`BookRecorder`] turns the books a [`WsClient`](crate::ws_client::WsClient)
+//! maintains into rows of depth snapshots: a timestamp, the market, and the
+//! top `depth` bid and ask levels. Prices and sizes are written as integers
+//! scaled by the market's decimals, the same units the order APIs use.
+//!
+//! Rows are sampled either whenever a market's top of book changes or on a
+//! fixed interval driven by [`BookRecorder::run`]. They are appended to CSV
+//! files, or Parquet files with the `arrow` feature, rotated once a file
+//! reaches a size or age limit. [`BookRecorder::finish`], also run on drop,
+//! flushes and closes the current file so it is always complete on disk.
+//!
+//! ```no_run
+//! use lighter_rs::book_recorder::{BookRecorder, Sampling};
+//! use lighter_rs::ws_client::WsClient;
+//! use std::sync::Arc;
+//! use std::time::Duration;
+//!
+//! # async fn example() -> lighter_rs::Result<()> {
+//! let recorder = Arc::new(
+//!     BookRecorder::builder("./books")
+//!         .market("0", 2, 4)
+//!         .depth(10)
+//!         .sampling(Sampling::Interval(Duration::from_millis(500)))
+//!         .max_file_age(Duration::from_secs(3600))
+//!         .build()?,
+//! );
+//!
+//! let sampler = recorder.clone();
+//! tokio::spawn(async move { sampler.run().await });
+//!
+//! let observer = recorder.clone();
+//! let ws = WsClient::builder().order_books(vec![0]).build()?;
+//! ws.run(
+//!     move |market_id, book| {
+//!         if let Err(e) = observer.observe(&market_id, &book) {
+//!             eprintln!("recording failed: {e}");
+//!         }
+//!     },
+//!     |_, _| {},
+//! )
+//! .await?;
+//! recorder.finish()?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+
+use crate::clock::{Clock, SystemClock};
+use crate::errors::{LighterError, Result};
+use crate::snapshot_sync::SnapshotSync;
+use crate::ws_client::{maintain_book, OrderBook, OrderBookUpdate, PriceLevel, WsFrame};
+
+/// Levels per side recorded by default
+const DEFAULT_DEPTH: usize = 5;
+
+/// File format of a recording
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RecordFormat {
+    /// Comma-separated values with a header row; missing levels are empty
+    #[default]
+    Csv,
+    /// Parquet with one nullable `Int64` column per level field
+    #[cfg(feature = "arrow")]
+    Parquet,
+}
+
+impl RecordFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            RecordFormat::Csv => "csv",
+            #[cfg(feature = "arrow")]
+            RecordFormat::Parquet => "parquet",
+        }
+    }
+}
+
+/// When rows are recorded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Sampling {
+    /// Whenever a market's best bid or ask price or size changes
+    #[default]
+    TopOfBookChange,
+    /// Every market's latest book at this interval, while
+    /// [`BookRecorder::run`] is running
+    Interval(Duration),
+}
+
+/// Scaling of a market's prices and sizes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MarketScale {
+    pub price_decimals: u32,
+    pub size_decimals: u32,
+}
+
+/// One recorded depth snapshot
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BookRow {
+    /// Milliseconds since the Unix epoch
+    pub timestamp_ms: i64,
+    pub market_id: String,
+    /// Scaled `(price, size)` levels, best first
+    pub bids: Vec<(i64, i64)>,
+    /// Scaled `(price, size)` levels, best first
+    pub asks: Vec<(i64, i64)>,
+}
+
+impl BookRow {
+    /// The top `depth` levels of `book`, scaled by `scale`
+    ///
+    /// Empty levels are skipped, whatever order the book keeps its levels in.
+    pub fn from_book(
+        timestamp_ms: i64,
+        market_id: &str,
+        book: &OrderBook,
+        scale: MarketScale,
+        depth: usize,
+    ) -> Result<Self> {
+        let side = |levels: &[PriceLevel],
+                    best_first: fn(&Decimal, &Decimal) -> std::cmp::Ordering| {
+            let mut levels: Vec<&PriceLevel> = levels
+                .iter()
+                .filter(|level| level.size > Decimal::ZERO)
+                .collect();
+            levels.sort_by(|a, b| best_first(&a.price, &b.price));
+            levels
+                .into_iter()
+                .take(depth)
+                .map(|level| {
+                    Ok((
+                        scaled(level.price, scale.price_decimals)?,
+                        scaled(level.size, scale.size_decimals)?,
+                    ))
+                })
+                .collect::<Result<Vec<_>>>()
+        };
+        Ok(Self {
+            timestamp_ms,
+            market_id: market_id.to_string(),
+            bids: side(&book.bids, |a, b| b.cmp(a))?,
+            asks: side(&book.asks, |a, b| a.cmp(b))?,
+        })
+    }
+
+    fn top(&self) -> Top {
+        (self.bids.first().copied(), self.asks.first().copied())
+    }
+
+    /// Price and size of level `i` of a side, or `None` past its end
+    fn level(levels: &[(i64, i64)], i: usize) -> (Option<i64>, Option<i64>) {
+        levels
+            .get(i)
+            .map_or((None, None), |&(price, size)| (Some(price), Some(size)))
+    }
+}
+
+/// Best scaled bid and ask levels of a row
+type Top = (Option<(i64, i64)>, Option<(i64, i64)>);
+
+fn scaled(value: Decimal, decimals: u32) -> Result<i64> {
+    (value * Decimal::from(10i64.pow(decimals)))
+        .round()
+        .to_i64()
+        .ok_or_else(|| {
+            LighterError::ValidationError(format!(
+                "{value} doesn't fit in an integer with {decimals} decimals"
+            ))
+        })
+}
+
+/// Column names of a recording `depth` levels deep
+///
+/// `timestamp_ms,market_id`, then `bid_price_1,bid_size_1` through
+/// `bid_price_{depth},bid_size_{depth}` and the same for asks.
+pub fn columns(depth: usize) -> Vec<String> {
+    let mut columns = vec!["timestamp_ms".to_string(), "market_id".to_string()];
+    for side in ["bid", "ask"] {
+        for level in 1..=depth {
+            columns.push(format!("{side}_price_{level}"));
+            columns.push(format!("{side}_size_{level}"));
+        }
+    }
+    columns
+}
+
+/// An open recording file
+trait Sink: Send {
+    fn write(&mut self, row: &BookRow) -> Result<()>;
+
+    /// Bytes written so far, including buffered ones
+    fn bytes(&self) -> u64;
+
+    fn flush(&mut self) -> Result<()>;
+
+    /// Flush and close the file
+    fn finish(self: Box<Self>) -> Result<()>;
+}
+
+struct CsvSink {
+    path: PathBuf,
+    out: BufWriter<File>,
+    depth: usize,
+    bytes: u64,
+}
+
+impl CsvSink {
+    fn create(path: PathBuf, depth: usize) -> Result<Self> {
+        let file = File::create(&path).map_err(|e| io_error(&path, e))?;
+        let mut sink = Self {
+            path,
+            out: BufWriter::new(file),
+            depth,
+            bytes: 0,
+        };
+        sink.write_line(&(columns(depth).join(",") + "\n"))?;
+        Ok(sink)
+    }
+
+    fn write_line(&mut self, line: &str) -> Result<()> {
+        self.out
+            .write_all(line.as_bytes())
+            .map_err(|e| io_error(&self.path, e))?;
+        self.bytes += line.len() as u64;
+        Ok(())
+    }
+}
+
+impl Sink for CsvSink {
+    fn write(&mut self, row: &BookRow) -> Result<()> {
+        let optional = |value: Option<i64>| value.map(|v| v.to_string()).unwrap_or_default();
+        let mut line = format!("{},{}", row.timestamp_ms, row.market_id);
+        for levels in [&row.bids, &row.asks] {
+            for i in 0..self.depth {
+                let (price, size) = BookRow::level(levels, i);
+                line.push(',');
+                line.push_str(&optional(price));
+                line.push(',');
+                line.push_str(&optional(size));
+            }
+        }
+        line.push('\n');
+        self.write_line(&line)
+    }
+
+    fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.out.flush().map_err(|e| io_error(&self.path, e))
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        self.flush()?;
+        self.out
+            .get_ref()
+            .sync_all()
+            .map_err(|e| io_error(&self.path, e))
+    }
+}
+
+#[cfg(feature = "arrow")]
+mod parquet_sink {
+    use std::fs::File;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    use arrow_array::builder::{Int64Builder, StringBuilder};
+    use arrow_array::{ArrayRef, RecordBatch};
+    use arrow_schema::{DataType, Field, Schema, SchemaRef};
+    use parquet::arrow::ArrowWriter;
+
+    use super::{columns, BookRow, Sink};
+    use crate::errors::{LighterError, Result};
+
+    /// Rows buffered before they are handed to the Parquet writer
+    const BATCH_ROWS: usize = 1024;
+
+    /// Average encoded size of a buffered row's field, for size-based rotation
+    const FIELD_BYTES: u64 = 8;
+
+    pub(super) struct ParquetSink {
+        path: PathBuf,
+        writer: ArrowWriter<File>,
+        schema: SchemaRef,
+        depth: usize,
+        rows: Vec<BookRow>,
+    }
+
+    impl ParquetSink {
+        pub(super) fn create(path: PathBuf, depth: usize) -> Result<Self> {
+            let names = columns(depth);
+            let fields: Vec<Field> = names
+                .iter()
+                .enumerate()
+                .map(|(i, name)| match i {
+                    0 => Field::new(name, DataType::Int64, false),
+                    1 => Field::new(name, DataType::Utf8, false),
+                    _ => Field::new(name, DataType::Int64, true),
+                })
+                .collect();
+            let schema = Arc::new(Schema::new(fields));
+            let file = File::create(&path).map_err(|e| super::io_error(&path, e))?;
+            let writer = ArrowWriter::try_new(file, schema.clone(), None)
+                .map_err(|e| parquet_error(&path, e))?;
+            Ok(Self {
+                path,
+                writer,
+                schema,
+                depth,
+                rows: Vec::with_capacity(BATCH_ROWS),
+            })
+        }
+
+        /// Hand the buffered rows to the writer as one record batch
+        fn write_batch(&mut self) -> Result<()> {
+            if self.rows.is_empty() {
+                return Ok(());
+            }
+            let mut timestamps = Int64Builder::with_capacity(self.rows.len());
+            let mut markets = StringBuilder::new();
+            let mut levels: Vec<Int64Builder> = (0..self.depth * 4)
+                .map(|_| Int64Builder::with_capacity(self.rows.len()))
+                .collect();
+            for row in self.rows.drain(..) {
+                timestamps.append_value(row.timestamp_ms);
+                markets.append_value(&row.market_id);
+                for (side, levels_of_side) in [&row.bids, &row.asks].into_iter().enumerate() {
+                    for i in 0..self.depth {
+                        let (price, size) = BookRow::level(levels_of_side, i);
+                        let column = (side * self.depth + i) * 2;
+                        levels[column].append_option(price);
+                        levels[column + 1].append_option(size);
+                    }
+                }
+            }
+            let mut arrays: Vec<ArrayRef> =
+                vec![Arc::new(timestamps.finish()), Arc::new(markets.finish())];
+            arrays.extend(
+                levels
+                    .iter_mut()
+                    .map(|builder| Arc::new(builder.finish()) as ArrayRef),
+            );
+            let batch = RecordBatch::try_new(self.schema.clone(), arrays).map_err(|e| {
+                LighterError::Other(format!(
+                    "Book recorder batch for {}: {e}",
+                    self.path.display()
+                ))
+            })?;
+            self.writer
+                .write(&batch)
+                .map_err(|e| parquet_error(&self.path, e))
+        }
+    }
+
+    impl Sink for ParquetSink {
+        fn write(&mut self, row: &BookRow) -> Result<()> {
+            self.rows.push(row.clone());
+            if self.rows.len() >= BATCH_ROWS {
+                self.write_batch()?;
+            }
+            Ok(())
+        }
+
+        fn bytes(&self) -> u64 {
+            let buffered = self.rows.len() as u64 * self.schema.fields().len() as u64 * FIELD_BYTES;
+            (self.writer.bytes_written() + self.writer.in_progress_size()) as u64 + buffered
+        }
+
+        fn flush(&mut self) -> Result<()> {
+            self.write_batch()?;
+            self.writer
+                .flush()
+                .map_err(|e| parquet_error(&self.path, e))
+        }
+
+        fn finish(mut self: Box<Self>) -> Result<()> {
+            self.write_batch()?;
+            let ParquetSink { path, writer, .. } = *self;
+            writer.close().map_err(|e| parquet_error(&path, e))?;
+            Ok(())
+        }
+    }
+
+    fn parquet_error(path: &std::path::Path, e: impl std::fmt::Display) -> LighterError {
+        LighterError::Other(format!("Book recorder Parquet on {}: {e}", path.display()))
+    }
+}
+
+fn io_error(path: &Path, e: std::io::Error) -> LighterError {
+    LighterError::Other(format!("Book recorder I/O on {}: {e}", path.display()))
+}
+
+/// Builder for [`BookRecorder`]
+pub struct BookRecorderBuilder {
+    dir: PathBuf,
+    prefix: String,
+    format: RecordFormat,
+    depth: usize,
+    sampling: Sampling,
+    max_file_bytes: Option<u64>,
+    max_file_age: Option<Duration>,
+    markets: HashMap<String, MarketScale>,
+    clock: Arc<dyn Clock>,
+}
+
+impl BookRecorderBuilder {
+    /// Record `market_id` with its prices and sizes scaled by these decimals
+    ///
+    /// Books of markets that weren't added are ignored.
+    pub fn market(
+        mut self,
+        market_id: impl Into<String>,
+        price_decimals: u32,
+        size_decimals: u32,
+    ) -> Self {
+        self.markets.insert(
+            market_id.into(),
+            MarketScale {
+                price_decimals,
+                size_decimals,
+            },
+        );
+        self
+    }
+
+    /// Start file names with `prefix` (defaults to "books")
+    pub fn prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    pub fn format(mut self, format: RecordFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Levels recorded per side (defaults to 5)
+    pub fn depth(mut self, depth: usize) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    pub fn sampling(mut self, sampling: Sampling) -> Self {
+        self.sampling = sampling;
+        self
+    }
+
+    /// Start a new file once the current one reaches `bytes`
+    pub fn max_file_bytes(mut self, bytes: u64) -> Self {
+        self.max_file_bytes = Some(bytes);
+        self
+    }
+
+    /// Start a new file once the current one has been open for `age`
+    pub fn max_file_age(mut self, age: Duration) -> Self {
+        self.max_file_age = Some(age);
+        self
+    }
+
+    /// Use `clock` for timestamps, sampling and rotation instead of the
+    /// system clock
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Create the output directory and build the recorder
+    ///
+    /// No file is created until the first row is recorded.
+    pub fn build(self) -> Result<BookRecorder> {
+        if self.depth == 0 {
+            return Err(LighterError::ValidationError(
+                "Book recorder depth must be at least 1".to_string(),
+            ));
+        }
+        if self.markets.is_empty() {
+            return Err(LighterError::ValidationError(
+                "Book recorder needs at least one market".to_string(),
+            ));
+        }
+        if self.sampling == Sampling::Interval(Duration::ZERO) {
+            return Err(LighterError::ValidationError(
+                "Book recorder sampling interval must be positive".to_string(),
+            ));
+        }
+        std::fs::create_dir_all(&self.dir).map_err(|e| io_error(&self.dir, e))?;
+
+        Ok(BookRecorder {
+            dir: self.dir,
+            prefix: self.prefix,
+            format: self.format,
+            depth: self.depth,
+            sampling: self.sampling,
+            max_file_bytes: self.max_file_bytes,
+            max_file_age: self.max_file_age,
+            markets: self.markets,
+            clock: self.clock,
+            state: Mutex::new(State::default()),
+        })
+    }
+}
+
+#[derive(Default)]
+struct State {
+    file: Option<Box<dyn Sink>>,
+    opened_at_ms: i64,
+    files: Vec<PathBuf>,
+    rows: u64,
+    /// Latest book of every recorded market, for interval sampling
+    latest: BTreeMap<String, OrderBook>,
+    /// Top of book of the last row recorded per market
+    tops: HashMap<String, Top>,
+    /// Books maintained from replayed frames
+    replayed: HashMap<String, OrderBook>,
+    replay_syncs: HashMap<String, SnapshotSync<OrderBookUpdate>>,
+}
+
+/// Appends sampled order book depth to rotating CSV or Parquet files
+pub struct BookRecorder {
+    dir: PathBuf,
+    prefix: String,
+    format: RecordFormat,
+    depth: usize,
+    sampling: Sampling,
+    max_file_bytes: Option<u64>,
+    max_file_age: Option<Duration>,
+    markets: HashMap<String, MarketScale>,
+    clock: Arc<dyn Clock>,
+    state: Mutex<State>,
+}
+
+impl BookRecorder {
+    /// Record into files in `dir`
+    pub fn builder(dir: impl Into<PathBuf>) -> BookRecorderBuilder {
+        BookRecorderBuilder {
+            dir: dir.into(),
+            prefix: "books".to_string(),
+            format: RecordFormat::default(),
+            depth: DEFAULT_DEPTH,
+            sampling: Sampling::default(),
+            max_file_bytes: None,
+            max_file_age: None,
+            markets: HashMap::new(),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Take in the latest book of `market_id`
+    ///
+    /// With [`Sampling::TopOfBookChange`] a row is recorded when the best bid
+    /// or ask moved since the market's last row; with [`Sampling::Interval`]
+    /// the book is kept for the next sample.
+    pub fn observe(&self, market_id: &str, book: &OrderBook) -> Result<()> {
+        let Some(&scale) = self.markets.get(market_id) else {
+            return Ok(());
+        };
+        let mut state = self.lock();
+        match self.sampling {
+            Sampling::Interval(_) => {
+                state.latest.insert(market_id.to_string(), book.clone());
+                Ok(())
+            }
+            Sampling::TopOfBookChange => {
+                let row =
+                    BookRow::from_book(self.clock.now_ms(), market_id, book, scale, self.depth)?;
+                if state.tops.get(market_id) == Some(&row.top()) {
+                    return Ok(());
+                }
+                self.record(&mut state, &row)
+            }
+        }
+    }
+
+    /// Feed one captured WebSocket frame, maintaining books as
+    /// [`WsClient`](crate::ws_client::WsClient) does
+    ///
+    /// Lets a session of recorded frames be turned into rows after the fact.
+    pub fn replay_frame(&self, text: String) -> Result<()> {
+        let frame = WsFrame::decode(text)?;
+        let changed = {
+            let mut state = self.lock();
+            let State {
+                replayed,
+                replay_syncs,
+                ..
+            } = &mut *state;
+            maintain_book(replayed, replay_syncs, frame)
+        };
+        match changed {
+            Some((market_id, book)) => self.observe(&market_id, &book),
+            None => Ok(()),
+        }
+    }
+
+    /// Record a row for the latest book of every market, returning the
+    /// number of rows
+    ///
+    /// Called on every tick by [`BookRecorder::run`]; with
+    /// [`Sampling::TopOfBookChange`] no books are kept, so this records
+    /// nothing.
+    pub fn sample(&self) -> Result<usize> {
+        let now_ms = self.clock.now_ms();
+        let mut state = self.lock();
+        let rows = state
+            .latest
+            .iter()
+            .map(|(market_id, book)| {
+                BookRow::from_book(now_ms, market_id, book, self.markets[market_id], self.depth)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        for row in &rows {
+            self.record(&mut state, row)?;
+        }
+        Ok(rows.len())
+    }
+
+    /// Sample every [`Sampling::Interval`] until recording fails
+    ///
+    /// Returns straight away with [`Sampling::TopOfBookChange`], whose rows
+    /// are recorded by [`BookRecorder::observe`].
+    pub async fn run(&self) -> Result<()> {
+        let Sampling::Interval(interval) = self.sampling else {
+            return Ok(());
+        };
+        loop {
+            self.clock.sleep(interval).await;
+            self.sample()?;
+        }
+    }
+
+    /// Write buffered rows through to the current file
+    pub fn flush(&self) -> Result<()> {
+        match &mut self.lock().file {
+            Some(file) => file.flush(),
+            None => Ok(()),
+        }
+    }
+
+    /// Flush and close the current file
+    ///
+    /// Recording can continue afterwards; the next row starts a new file.
+    pub fn finish(&self) -> Result<()> {
+        match self.lock().file.take() {
+            Some(file) => file.finish(),
+            None => Ok(()),
+        }
+    }
+
+    /// Files created so far, oldest first
+    pub fn files(&self) -> Vec<PathBuf> {
+        self.lock().files.clone()
+    }
+
+    /// Rows recorded so far
+    pub fn rows(&self) -> u64 {
+        self.lock().rows
+    }
+
+    fn record(&self, state: &mut State, row: &BookRow) -> Result<()> {
+        let now_ms = self.clock.now_ms();
+        let full = state.file.as_ref().is_some_and(|file| {
+            self.max_file_bytes.is_some_and(|max| file.bytes() >= max)
+                || self
+                    .max_file_age
+                    .is_some_and(|max| now_ms - state.opened_at_ms >= max.as_millis() as i64)
+        });
+        if full {
+            if let Some(file) = state.file.take() {
+                file.finish()?;
+            }
+        }
+
+        let file = match &mut state.file {
+            Some(file) => file,
+            None => {
+                let path = self.dir.join(format!(
+                    "{}-{now_ms}-{:04}.{}",
+                    self.prefix,
+                    state.files.len(),
+                    self.format.extension()
+                ));
+                let file: Box<dyn Sink> = match self.format {
+                    RecordFormat::Csv => Box::new(CsvSink::create(path.clone(), self.depth)?),
+                    #[cfg(feature = "arrow")]
+                    RecordFormat::Parquet => {
+                        Box::new(parquet_sink::ParquetSink::create(path.clone(), self.depth)?)
+                    }
+                };
+                tracing::debug!(path = %path.display(), "Book recorder opened a new file");
+                state.files.push(path);
+                state.opened_at_ms = now_ms;
+                state.file.insert(file)
+            }
+        };
+        file.write(row)?;
+        state.rows += 1;
+        state.tops.insert(row.market_id.clone(), row.top());
+        Ok(())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Drop for BookRecorder {
+    fn drop(&mut self) {
+        if let Err(e) = self.finish() {
+            tracing::warn!(error = %e, "Book recorder couldn't close its file");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("lighter-rs-books-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    /// A session of order book frames for market 0, with the top of book
+    /// each frame leaves behind as `(bid price, bid size, ask price, ask size)`
+    fn session() -> Vec<(String, Option<[i64; 4]>)> {
+        let frame = |kind: &str, offset: u64, bids: &str, asks: &str| {
+            format!(
+                r#"{{"type":"{kind}/order_book","channel":"order_book:0","order_book":{{"bids":[{bids}],"asks":[{asks}],"offset":{offset}}}}}"#
+            )
+        };
+        let level = |price: &str, size: &str| format!(r#"{{"price":"{price}","size":"{size}"}}"#);
+        vec![
+            (
+                frame(
+                    "subscribed",
+                    10,
+                    &[level("3000.00", "1.5000"), level("2999.50", "2.0000")].join(","),
+                    &level("3000.50", "0.2500"),
+                ),
+                Some([300000, 15000, 300050, 2500]),
+            ),
+            // A deeper level changes but the top doesn't
+            (frame("update", 11, &level("2999.00", "4.0000"), ""), None),
+            (
+                frame("update", 12, "", &level("3000.25", "1.0000")),
+                Some([300000, 15000, 300025, 10000]),
+            ),
+            // The best bid is pulled, so the next level becomes the top
+            (
+                frame("update", 13, &level("3000.00", "0"), ""),
+                Some([299950, 20000, 300025, 10000]),
+            ),
+            // Stale offset, dropped
+            (frame("update", 12, &level("3001.00", "9.0000"), ""), None),
+        ]
+    }
+
+    fn read_csv(path: &Path) -> Vec<Vec<String>> {
+        std::fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|line| line.split(',').map(str::to_string).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_replayed_session_rows_match_frames() {
+        let dir = temp_dir("replay");
+        let clock = Arc::new(ManualClock::at_ms(1_000));
+        let recorder = BookRecorder::builder(&dir)
+            .market("0", 2, 4)
+            .depth(2)
+            .clock(clock.clone())
+            .build()
+            .unwrap();
+
+        let mut expected = Vec::new();
+        for (frame, top) in session() {
+            clock.advance(Duration::from_millis(100));
+            recorder.replay_frame(frame).unwrap();
+            if let Some(top) = top {
+                expected.push((clock.now_ms(), top));
+            }
+        }
+        assert!(recorder.files().iter().all(|path| path.exists()));
+        recorder.finish().unwrap();
+
+        let files = recorder.files();
+        assert_eq!(files.len(), 1);
+        let rows = read_csv(&files[0]);
+        assert_eq!(rows[0], columns(2));
+        assert_eq!(rows.len() - 1, expected.len());
+        for (row, (timestamp, [bid, bid_size, ask, ask_size])) in rows[1..].iter().zip(&expected) {
+            assert_eq!(row[0], timestamp.to_string());
+            assert_eq!(row[1], "0");
+            assert_eq!(row[2..4], [bid.to_string(), bid_size.to_string()]);
+            assert_eq!(row[6..8], [ask.to_string(), ask_size.to_string()]);
+        }
+        // Second levels, and empty cells where a side runs out
+        assert_eq!(rows[1][4..6], ["299950", "20000"]);
+        assert_eq!(rows[1][8..10], ["", ""]);
+        assert_eq!(rows[3][4..6], ["299900", "40000"]);
+        assert_eq!(rows[3][8..10], ["300050", "2500"]);
+
+        drop(recorder);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_interval_sampling_rotates_files() {
+        let dir = temp_dir("rotate");
+        let clock = Arc::new(ManualClock::at_ms(0));
+        let recorder = BookRecorder::builder(&dir)
+            .market("0", 2, 4)
+            .market("1", 3, 2)
+            .prefix("session")
+            .depth(1)
+            .sampling(Sampling::Interval(Duration::from_millis(250)))
+            .max_file_age(Duration::from_secs(1))
+            .clock(clock.clone())
+            .build()
+            .unwrap();
+
+        let book = |bid: i64, ask: i64| OrderBook {
+            bids: vec![PriceLevel {
+                price: Decimal::new(bid, 2),
+                size: Decimal::ONE,
+            }],
+            asks: vec![PriceLevel {
+                price: Decimal::new(ask, 2),
+                size: Decimal::ONE,
+            }],
+            offset: None,
+        };
+        recorder.observe("0", &book(300000, 300050)).unwrap();
+        recorder.observe("1", &book(16000, 16010)).unwrap();
+        recorder.observe("7", &book(100, 101)).unwrap();
+        assert_eq!(recorder.rows(), 0);
+
+        // Eight ticks of two markets, over two seconds
+        for _ in 0..8 {
+            clock.sleep(Duration::from_millis(250)).await;
+            assert_eq!(recorder.sample().unwrap(), 2);
+        }
+        recorder.finish().unwrap();
+
+        let files = recorder.files();
+        assert_eq!(files.len(), 2);
+        assert!(files[0]
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .starts_with("session-250-0000"));
+        let first = read_csv(&files[0]);
+        let second = read_csv(&files[1]);
+        assert_eq!(first.len() + second.len() - 2, 16);
+        assert_eq!(first[1], ["250", "0", "300000", "10000", "300050", "10000"]);
+        assert_eq!(first[2], ["250", "1", "160000", "100", "160100", "100"]);
+        assert_eq!(second[1][0], "1250");
+
+        drop(recorder);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(feature = "arrow")]
+    #[test]
+    fn test_parquet_rotates_by_size_and_reads_back() {
+        use arrow_array::cast::AsArray;
+        use arrow_array::types::Int64Type;
+        use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+        let dir = temp_dir("parquet");
+        let clock = Arc::new(ManualClock::at_ms(0));
+        let recorder = BookRecorder::builder(&dir)
+            .market("0", 2, 4)
+            .format(RecordFormat::Parquet)
+            .depth(3)
+            .max_file_bytes(1)
+            .clock(clock.clone())
+            .build()
+            .unwrap();
+
+        for (frame, _) in session() {
+            clock.advance(Duration::from_millis(100));
+            recorder.replay_frame(frame).unwrap();
+        }
+        drop(recorder);
+
+        let mut files: Vec<PathBuf> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        files.sort();
+        // Every row fills its file, so each gets one
+        assert_eq!(files.len(), 3);
+
+        let mut best_bids = Vec::new();
+        for path in &files {
+            let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(path).unwrap())
+                .unwrap()
+                .build()
+                .unwrap();
+            for batch in reader {
+                let batch = batch.unwrap();
+                assert_eq!(batch.num_columns(), columns(3).len());
+                let bids = batch.column(2).as_primitive::<Int64Type>();
+                best_bids.extend(bids.iter().flatten());
+                // Only two levels of asks at most, so the third is null
+                assert!(batch.column(12).is_null(0));
+            }
+        }
+        assert_eq!(best_bids, vec![300000, 300000, 299950]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,258 @@
+//! Bracket orders: an entry protected by a take profit and a stop loss
+//!
+//! [`TxClient::create_bracket_order`] signs three orders with consecutive
+//! nonces: a limit entry, then a reduce-only take profit and stop loss on
+//! the opposite side. [`TxClient::send_bracket_order`] also submits them in
+//! one batch.
+//!
+//! ```no_run
+//! use lighter_rs::client::TxClient;
+//!
+//! # async fn example(tx_client: TxClient) -> lighter_rs::Result<()> {
+//! // Long 0.01 ETH at 3000.00, out at 3300.00 or 2850.00
+//! let responses = tx_client
+//!     .send_bracket_order(0, 1, 100, 300_000, 330_000, 285_000, 0, None)
+//!     .await?;
+//! assert!(responses.iter().all(|response| response.is_success()));
+//! # Ok(())
+//! # }
+//! ```
+
+use rust_decimal::Decimal;
+
+use crate::client::{TxClient, TxResponse};
+use crate::constants::*;
+use crate::errors::Result;
+use crate::trigger_direction::check_trigger;
+use crate::types::{CreateOrderTxReq, L2CreateOrderTxInfo, TransactOpts};
+
+/// How far through their trigger the exits of a bracket are priced, in
+/// basis points
+const EXIT_SLIPPAGE_BPS: u64 = 100;
+
+/// The three signed orders of a bracket, in nonce order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BracketOrder {
+    pub entry: L2CreateOrderTxInfo,
+    pub take_profit: L2CreateOrderTxInfo,
+    pub stop_loss: L2CreateOrderTxInfo,
+}
+
+impl BracketOrder {
+    /// The orders in nonce order, as they must be submitted
+    pub fn into_orders(self) -> [L2CreateOrderTxInfo; 3] {
+        [self.entry, self.take_profit, self.stop_loss]
+    }
+}
+
+impl TxClient {
+    /// Sign an entry limit order at `entry_price` with a take profit and a
+    /// stop loss closing it
+    ///
+    /// The entry is good till [`DEFAULT_ORDER_EXPIRY_PERIOD`] from now, like
+    /// [`TxClient::create_limit_order`]. The exits are reduce-only market
+    /// orders on the other side, with client order indexes following
+    /// `client_order_index`, priced 1% through their trigger. A long's take
+    /// profit must trigger above the entry and its stop loss below, a short's
+    /// the other way round, or this fails with
+    /// [`LighterError::InvertedTrigger`](crate::errors::LighterError::InvertedTrigger).
+    #[allow(clippy::too_many_arguments)]
+    pub async fn create_bracket_order(
+        &self,
+        market_index: u8,
+        client_order_index: i64,
+        base_amount: i64,
+        entry_price: u64,
+        tp_trigger: u64,
+        sl_trigger: u64,
+        is_ask: u8,
+        opts: Option<TransactOpts>,
+    ) -> Result<BracketOrder> {
+        let exit_is_ask = is_ask == 0;
+        let entry = CreateOrderTxReq {
+            market_index,
+            client_order_index,
+            base_amount,
+            price: entry_price,
+            is_ask,
+            order_type: ORDER_TYPE_LIMIT,
+            time_in_force: TIME_IN_FORCE_GOOD_TILL_TIME,
+            reduce_only: 0,
+            trigger_price: 0,
+            order_expiry: self.default_order_expiry(),
+        };
+        let exit = |order_type, offset, trigger_price| -> Result<CreateOrderTxReq> {
+            check_trigger(
+                order_type,
+                exit_is_ask,
+                Decimal::from(trigger_price),
+                Decimal::from(entry_price),
+            )?;
+            Ok(CreateOrderTxReq {
+                client_order_index: client_order_index + offset,
+                price: exit_price(exit_is_ask, trigger_price),
+                is_ask: u8::from(exit_is_ask),
+                order_type,
+                time_in_force: TIME_IN_FORCE_IMMEDIATE_OR_CANCEL,
+                reduce_only: 1,
+                trigger_price,
+                order_expiry: 0,
+                ..entry.clone()
+            })
+        };
+        let take_profit = exit(ORDER_TYPE_TAKE_PROFIT, 1, tp_trigger)?;
+        let stop_loss = exit(ORDER_TYPE_STOP_LOSS, 2, sl_trigger)?;
+
+        let [entry, take_profit, stop_loss]: [L2CreateOrderTxInfo; 3] = self
+            .create_orders(&[entry, take_profit, stop_loss], opts)
+            .await?
+            .try_into()
+            .expect("one signed order per request");
+        Ok(BracketOrder {
+            entry,
+            take_profit,
+            stop_loss,
+        })
+    }
+
+    /// Sign a bracket like [`TxClient::create_bracket_order`] and submit it
+    /// in one batch, with a response for each of the entry, take profit and
+    /// stop loss
+    #[allow(clippy::too_many_arguments)]
+    pub async fn send_bracket_order(
+        &self,
+        market_index: u8,
+        client_order_index: i64,
+        base_amount: i64,
+        entry_price: u64,
+        tp_trigger: u64,
+        sl_trigger: u64,
+        is_ask: u8,
+        opts: Option<TransactOpts>,
+    ) -> Result<Vec<TxResponse>> {
+        let bracket = self
+            .create_bracket_order(
+                market_index,
+                client_order_index,
+                base_amount,
+                entry_price,
+                tp_trigger,
+                sl_trigger,
+                is_ask,
+                opts,
+            )
+            .await?;
+        self.send_transactions(&bracket.into_orders()).await
+    }
+}
+
+/// Worst price of an exit triggered at `trigger_price`
+fn exit_price(is_ask: bool, trigger_price: u64) -> u64 {
+    let price = if is_ask {
+        trigger_price * (10_000 - EXIT_SLIPPAGE_BPS) / 10_000
+    } else {
+        (trigger_price * (10_000 + EXIT_SLIPPAGE_BPS)).div_ceil(10_000)
+    };
+    price.clamp(MIN_ORDER_PRICE, MAX_ORDER_PRICE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::LighterError;
+    use crate::test_support::mock_builder;
+    use crate::transport::{HttpResponse, MockTransport};
+    use crate::types::TxInfo;
+    use std::sync::Arc;
+
+    const BATCH_PATH: &str = "/api/v1/sendTxBatch";
+
+    #[tokio::test]
+    async fn test_bracket_order_protects_the_entry() {
+        let mock = Arc::new(MockTransport::new());
+        mock.set_handler(BATCH_PATH, |_| {
+            Ok(HttpResponse::new(
+                200,
+                r#"{"code":200,"tx_hash":["0x1","0x2","0x3"]}"#,
+            ))
+        });
+        let tx_client = mock_builder(&mock).build().unwrap();
+        tx_client.nonces().set(1, 0, 5);
+
+        // A long at 3000.00 sells at 3300.00 or 2850.00
+        let long = tx_client
+            .create_bracket_order(0, 10, 100, 300_000, 330_000, 285_000, 0, None)
+            .await
+            .unwrap();
+        assert_eq!(
+            (
+                long.entry.nonce,
+                long.take_profit.nonce,
+                long.stop_loss.nonce
+            ),
+            (5, 6, 7)
+        );
+        assert_eq!(
+            (long.entry.order_type, long.entry.reduce_only),
+            (ORDER_TYPE_LIMIT, 0)
+        );
+        for (exit, order_type, client_order_index, trigger_price, price) in [
+            (
+                &long.take_profit,
+                ORDER_TYPE_TAKE_PROFIT,
+                11,
+                330_000,
+                326_700,
+            ),
+            (&long.stop_loss, ORDER_TYPE_STOP_LOSS, 12, 285_000, 282_150),
+        ] {
+            assert_eq!(
+                (
+                    exit.order_type,
+                    exit.client_order_index,
+                    exit.is_ask,
+                    exit.reduce_only
+                ),
+                (order_type, client_order_index, 1, 1)
+            );
+            assert_eq!((exit.trigger_price, exit.price), (trigger_price, price));
+            assert_eq!(exit.base_amount, 100);
+        }
+
+        // A short buys back above its take profit's trigger
+        let short = tx_client
+            .create_bracket_order(0, 20, 100, 300_000, 270_000, 315_000, 1, None)
+            .await
+            .unwrap();
+        assert_eq!(
+            (short.take_profit.is_ask, short.take_profit.price),
+            (0, 272_700)
+        );
+        assert_eq!(short.stop_loss.price, 318_150);
+
+        // Exits on the wrong side of the entry
+        for (tp_trigger, sl_trigger, is_ask) in [
+            (270_000, 285_000, 0),
+            (330_000, 300_000, 0),
+            (330_000, 315_000, 1),
+        ] {
+            assert!(matches!(
+                tx_client
+                    .create_bracket_order(0, 30, 100, 300_000, tp_trigger, sl_trigger, is_ask, None)
+                    .await,
+                Err(LighterError::InvertedTrigger { .. })
+            ));
+        }
+
+        let responses = tx_client
+            .send_bracket_order(0, 40, 100, 300_000, 330_000, 285_000, 0, None)
+            .await
+            .unwrap();
+        assert_eq!(responses.len(), 3);
+        assert!(responses.iter().all(TxResponse::is_success));
+        let fields: Vec<(String, String)> =
+            serde_urlencoded::from_bytes(&mock.requests_to(BATCH_PATH)[0].body).unwrap();
+        let tx_type = long.entry.get_tx_type();
+        assert_eq!(fields[0].1, format!("[{tx_type},{tx_type},{tx_type}]"));
+    }
+}
@@ -0,0 +1,483 @@
+//! Candlesticks from REST and the WebSocket stream
+//!
+//! [`HTTPClient::get_candles`](crate::client::HTTPClient::get_candles) reads
+//! the candlestick endpoint, and
+//! [`WsClientBuilder::candles`](crate::ws_client::WsClientBuilder::candles)
+//! subscribes to a market's live candles.
+//!
+//! A [`CandleFeed`] joins the two into one continuous series: it fetches the
+//! last `history_len` candles, then follows the live ones. The candle in
+//! progress when the history was fetched shows up in both; it is matched by
+//! its start time, so it is neither skipped nor repeated. Whenever a live
+//! candle starts later than the one after the current candle, for instance
+//! after the stream was down for a while, the missing candles are fetched
+//! from REST before the live one is handed out.
+//!
+//! ```no_run
+//! use lighter_rs::candles::{CandleEvent, CandleFeed, CandleResolution};
+//! use lighter_rs::client::HTTPClient;
+//! use lighter_rs::ws_client::WsClient;
+//! use std::sync::Arc;
+//!
+//! # async fn example(http: HTTPClient) -> lighter_rs::Result<()> {
+//! let ws_client = Arc::new(
+//!     WsClient::builder()
+//!         .candles(0, CandleResolution::OneMinute)
+//!         .build()?,
+//! );
+//! let runner = ws_client.clone();
+//! tokio::spawn(async move { runner.run(|_, _| {}, |_, _| {}).await });
+//!
+//! let mut feed =
+//!     CandleFeed::start(&http, &ws_client, 0, CandleResolution::OneMinute, 200).await?;
+//! println!("warm start with {} candles", feed.history().len());
+//! while let Some(event) = feed.next().await {
+//!     if let CandleEvent::Closed(candle) = event {
+//!         println!("{} closed at {}", candle.start_time, candle.close);
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt;
+use std::str::FromStr;
+
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use crate::errors::LighterError;
+
+/// Length of one candle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CandleResolution {
+    OneMinute,
+    FiveMinutes,
+    FifteenMinutes,
+    ThirtyMinutes,
+    OneHour,
+    FourHours,
+    TwelveHours,
+    OneDay,
+    OneWeek,
+}
+
+impl CandleResolution {
+    /// Name the API uses for the resolution, such as `1m`
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CandleResolution::OneMinute => "1m",
+            CandleResolution::FiveMinutes => "5m",
+            CandleResolution::FifteenMinutes => "15m",
+            CandleResolution::ThirtyMinutes => "30m",
+            CandleResolution::OneHour => "1h",
+            CandleResolution::FourHours => "4h",
+            CandleResolution::TwelveHours => "12h",
+            CandleResolution::OneDay => "1d",
+            CandleResolution::OneWeek => "1w",
+        }
+    }
+
+    /// Length of one candle in milliseconds
+    pub fn millis(self) -> i64 {
+        const MINUTE: i64 = 60_000;
+        match self {
+            CandleResolution::OneMinute => MINUTE,
+            CandleResolution::FiveMinutes => 5 * MINUTE,
+            CandleResolution::FifteenMinutes => 15 * MINUTE,
+            CandleResolution::ThirtyMinutes => 30 * MINUTE,
+            CandleResolution::OneHour => 60 * MINUTE,
+            CandleResolution::FourHours => 4 * 60 * MINUTE,
+            CandleResolution::TwelveHours => 12 * 60 * MINUTE,
+            CandleResolution::OneDay => 24 * 60 * MINUTE,
+            CandleResolution::OneWeek => 7 * 24 * 60 * MINUTE,
+        }
+    }
+}
+
+impl fmt::Display for CandleResolution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for CandleResolution {
+    type Err = LighterError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "1m" => CandleResolution::OneMinute,
+            "5m" => CandleResolution::FiveMinutes,
+            "15m" => CandleResolution::FifteenMinutes,
+            "30m" => CandleResolution::ThirtyMinutes,
+            "1h" => CandleResolution::OneHour,
+            "4h" => CandleResolution::FourHours,
+            "12h" => CandleResolution::TwelveHours,
+            "1d" => CandleResolution::OneDay,
+            "1w" => CandleResolution::OneWeek,
+            other => {
+                return Err(LighterError::ValidationError(format!(
+                    "Unknown candle resolution: {other}"
+                )))
+            }
+        })
+    }
+}
+
+/// One candlestick, as the candlestick endpoint and stream report it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Candle {
+    /// Start of the candle, in milliseconds since the Unix epoch
+    #[serde(rename = "timestamp")]
+    pub start_time: i64,
+    pub open: Decimal,
+    pub high: Decimal,
+    pub low: Decimal,
+    pub close: Decimal,
+    /// Traded volume in base currency
+    #[serde(rename = "volume0", default)]
+    pub base_volume: Decimal,
+    /// Traded volume in quote currency
+    #[serde(rename = "volume1", default)]
+    pub quote_volume: Decimal,
+}
+
+/// Candles of one market from the stream
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CandleUpdate {
+    pub market_id: u8,
+    pub resolution: CandleResolution,
+    /// Candles in the frame, oldest first
+    pub candles: Vec<Candle>,
+}
+
+#[cfg(feature = "native")]
+pub use feed::{CandleEvent, CandleFeed};
+
+#[cfg(feature = "native")]
+mod feed {
+    use std::collections::VecDeque;
+
+    use tokio::sync::broadcast;
+
+    use super::{Candle, CandleResolution, CandleUpdate};
+    use crate::client::HTTPClient;
+    use crate::errors::Result;
+    use crate::snapshot_sync::now_ms;
+    use crate::ws_client::WsClient;
+
+    /// A step of a [`CandleFeed`]
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub enum CandleEvent {
+        /// A candle that is over, live or backfilled from REST
+        Closed(Candle),
+        /// The candle in progress changed
+        InProgress(Candle),
+        /// Candles between the two start times are missing because the
+        /// backfill request failed
+        Gap { from_ms: i64, to_ms: i64 },
+    }
+
+    /// History and live candles of one market as one continuous series
+    pub struct CandleFeed {
+        http: HTTPClient,
+        market_id: u8,
+        resolution: CandleResolution,
+        history_len: usize,
+        updates: broadcast::Receiver<CandleUpdate>,
+        /// Closed candles, oldest first
+        history: VecDeque<Candle>,
+        current: Option<Candle>,
+        /// Events not handed out yet
+        pending: VecDeque<CandleEvent>,
+    }
+
+    impl CandleFeed {
+        /// Fetch the last `history_len` closed candles and the one in
+        /// progress, then follow `ws_client`'s live candles
+        ///
+        /// `ws_client` must subscribe to the market's candles at this
+        /// resolution with
+        /// [`WsClientBuilder::candles`](crate::ws_client::WsClientBuilder::candles);
+        /// live candles received while the history is fetched are kept.
+        pub async fn start(
+            http: &HTTPClient,
+            ws_client: &WsClient,
+            market_id: u8,
+            resolution: CandleResolution,
+            history_len: usize,
+        ) -> Result<Self> {
+            // Subscribe first so nothing falls between history and stream
+            let updates = ws_client.subscribe_candles();
+            let now = now_ms() as i64;
+            let count = history_len as u32 + 1;
+            let start = now - i64::from(count) * resolution.millis();
+            let mut candles = http
+                .get_candles(market_id, resolution, start, now, count)
+                .await?;
+            candles.dedup_by_key(|candle| candle.start_time);
+
+            let current = candles
+                .last()
+                .filter(|candle| candle.start_time + resolution.millis() > now)
+                .cloned();
+            if current.is_some() {
+                candles.pop();
+            }
+            let skip = candles.len().saturating_sub(history_len);
+            Ok(Self {
+                http: http.clone(),
+                market_id,
+                resolution,
+                history_len,
+                updates,
+                history: candles.into_iter().skip(skip).collect(),
+                current,
+                pending: VecDeque::new(),
+            })
+        }
+
+        /// Closed candles, oldest first, at most `history_len` of them
+        pub fn history(&self) -> &VecDeque<Candle> {
+            &self.history
+        }
+
+        /// The candle in progress, if one has been seen
+        pub fn current(&self) -> Option<&Candle> {
+            self.current.as_ref()
+        }
+
+        /// Wait for the next event, or `None` once the WebSocket client is
+        /// gone
+        pub async fn next(&mut self) -> Option<CandleEvent> {
+            loop {
+                if let Some(event) = self.pending.pop_front() {
+                    return Some(event);
+                }
+                match self.updates.recv().await {
+                    Ok(update) => {
+                        if update.market_id != self.market_id
+                            || update.resolution != self.resolution
+                        {
+                            continue;
+                        }
+                        for candle in update.candles {
+                            self.apply(candle).await;
+                        }
+                    }
+                    // Missed candles show up as a gap before the next one
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        tracing::warn!(skipped, "Candle feed fell behind the stream");
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        }
+
+        /// Fold a live candle into the series
+        async fn apply(&mut self, candle: Candle) {
+            let Some(current) = self.current.take() else {
+                self.in_progress(candle);
+                return;
+            };
+            if candle.start_time < current.start_time {
+                // Already closed
+                self.current = Some(current);
+                return;
+            }
+            if candle.start_time == current.start_time {
+                self.in_progress(candle);
+                return;
+            }
+
+            let next_start = current.start_time + self.resolution.millis();
+            self.close(current);
+            if candle.start_time > next_start {
+                self.backfill(next_start, candle.start_time).await;
+            }
+            self.in_progress(candle);
+        }
+
+        /// Fetch the closed candles starting in `from_ms..to_ms`
+        async fn backfill(&mut self, from_ms: i64, to_ms: i64) {
+            let missing = ((to_ms - from_ms) / self.resolution.millis()) as u32;
+            tracing::info!(
+                market_id = self.market_id,
+                resolution = %self.resolution,
+                missing,
+                "Backfilling candles missed by the stream"
+            );
+            let fetched = self
+                .http
+                .get_candles(self.market_id, self.resolution, from_ms, to_ms, missing)
+                .await;
+            match fetched {
+                Ok(mut candles) => {
+                    candles.dedup_by_key(|candle| candle.start_time);
+                    // The endpoint may pad the range with candles on either side
+                    for candle in candles {
+                        if (from_ms..to_ms).contains(&candle.start_time) {
+                            self.close(candle);
+                        }
+                    }
+                }
+                Err(e) => {
+                    tracing::warn!(error = %e, from_ms, to_ms, "Failed to backfill candles");
+                    self.pending.push_back(CandleEvent::Gap { from_ms, to_ms });
+                }
+            }
+        }
+
+        fn close(&mut self, candle: Candle) {
+            self.history.push_back(candle.clone());
+            while self.history.len() > self.history_len {
+                self.history.pop_front();
+            }
+            self.pending.push_back(CandleEvent::Closed(candle));
+        }
+
+        fn in_progress(&mut self, candle: Candle) {
+            self.current = Some(candle.clone());
+            self.pending.push_back(CandleEvent::InProgress(candle));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolutions() {
+        for resolution in [
+            CandleResolution::OneMinute,
+            CandleResolution::FourHours,
+            CandleResolution::OneWeek,
+        ] {
+            assert_eq!(
+                resolution.as_str().parse::<CandleResolution>().unwrap(),
+                resolution
+            );
+        }
+        assert_eq!(CandleResolution::FifteenMinutes.millis(), 900_000);
+        assert!("2m".parse::<CandleResolution>().is_err());
+
+        let candle: Candle = serde_json::from_str(
+            r#"{"timestamp":1700000040000,"open":3024.5,"high":"3030","low":3020,"close":3025.1,"volume0":1.5,"volume1":4537.65,"last_trade_id":9}"#,
+        )
+        .unwrap();
+        assert_eq!(candle.start_time, 1_700_000_040_000);
+        assert_eq!(candle.high, Decimal::new(3030, 0));
+        assert_eq!(candle.base_volume, Decimal::new(15, 1));
+    }
+
+    #[cfg(feature = "test-util")]
+    #[tokio::test]
+    async fn test_feed_stitches_history_and_stream() {
+        use crate::client::HTTPClient;
+        use crate::snapshot_sync::now_ms;
+        use crate::testing::MockLighter;
+        use crate::ws_client::WsClient;
+        use serde_json::{json, Value};
+        use std::sync::Arc;
+
+        const MINUTE: i64 = 60_000;
+        const PATH: &str = "/api/v1/candlesticks";
+        let base = now_ms() as i64;
+        let candle = |start: i64, close: i64| json!({"timestamp": start, "open": 10, "high": close.max(10), "low": 10, "close": close});
+        let frame = |candles: Vec<Value>| {
+            json!({"type": "update/candlestick", "channel": "candlestick:0:1m", "candlesticks": candles})
+                .to_string()
+        };
+        let closed =
+            |start: i64| CandleEvent::Closed(serde_json::from_value(candle(start, 10)).unwrap());
+
+        let mock = MockLighter::start().await.unwrap();
+        // History, ending with the candle in progress
+        let history: Vec<Value> = (-4..=0).map(|i| candle(base + i * MINUTE, 10)).collect();
+        mock.push_response(
+            PATH,
+            200,
+            json!({"code": 200, "candlesticks": history}).to_string(),
+        );
+        // Backfill padded with a candle on either side
+        let backfill: Vec<Value> = (1..=4).map(|i| candle(base + i * MINUTE, 10)).collect();
+        mock.push_response(
+            PATH,
+            200,
+            json!({"code": 200, "candlesticks": backfill}).to_string(),
+        );
+        mock.push_response(PATH, 500, "");
+
+        let ws_client = Arc::new(
+            WsClient::builder()
+                .url(mock.ws_url())
+                .candles(0, CandleResolution::OneMinute)
+                .build()
+                .unwrap(),
+        );
+        let runner = ws_client.clone();
+        let run = tokio::spawn(async move { runner.run(|_, _| {}, |_, _| {}).await });
+        let subscriptions = mock.wait_for_subscriptions(1).await;
+        assert_eq!(subscriptions[0]["channel"], "candlestick/0/1m");
+
+        let http = HTTPClient::new(&mock.url()).unwrap();
+        let mut feed = CandleFeed::start(&http, &ws_client, 0, CandleResolution::OneMinute, 3)
+            .await
+            .unwrap();
+        let starts: Vec<i64> = feed.history().iter().map(|c| c.start_time).collect();
+        assert_eq!(
+            starts,
+            [base - 3 * MINUTE, base - 2 * MINUTE, base - MINUTE]
+        );
+        assert_eq!(feed.current().unwrap().start_time, base);
+
+        // The overlapping candle updates the one in progress; older ones are dropped
+        mock.push_frame(frame(vec![candle(base - MINUTE, 10), candle(base, 11)]));
+        let in_progress =
+            CandleEvent::InProgress(serde_json::from_value(candle(base, 11)).unwrap());
+        assert_eq!(feed.next().await.unwrap(), in_progress);
+
+        // The next candle closes it
+        mock.push_frame(frame(vec![candle(base + MINUTE, 10)]));
+        assert_eq!(
+            feed.next().await.unwrap(),
+            CandleEvent::Closed(serde_json::from_value(candle(base, 11)).unwrap())
+        );
+        assert!(matches!(
+            feed.next().await.unwrap(),
+            CandleEvent::InProgress(_)
+        ));
+
+        // A candle after a gap pulls the missing ones from REST first
+        mock.push_frame(frame(vec![candle(base + 4 * MINUTE, 10)]));
+        for start in [base + MINUTE, base + 2 * MINUTE, base + 3 * MINUTE] {
+            assert_eq!(feed.next().await.unwrap(), closed(start));
+        }
+        assert!(matches!(
+            feed.next().await.unwrap(),
+            CandleEvent::InProgress(_)
+        ));
+        let request = mock.requests_to(PATH).pop().unwrap();
+        assert!(request
+            .url
+            .contains(&format!("start_timestamp={}", base + 2 * MINUTE)));
+        assert_eq!(feed.history().len(), 3);
+
+        // A failed backfill is reported as a gap
+        mock.push_frame(frame(vec![candle(base + 7 * MINUTE, 10)]));
+        assert_eq!(feed.next().await.unwrap(), closed(base + 4 * MINUTE));
+        assert_eq!(
+            feed.next().await.unwrap(),
+            CandleEvent::Gap {
+                from_ms: base + 5 * MINUTE,
+                to_ms: base + 7 * MINUTE
+            }
+        );
+        assert!(matches!(
+            feed.next().await.unwrap(),
+            CandleEvent::InProgress(_)
+        ));
+        run.abort();
+    }
+}
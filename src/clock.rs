@@ -0,0 +1,148 @@
+//! Time source for everything that reads the time or waits
+//!
+//! Components that schedule, expire or pace work take an `Arc<dyn Clock>`
+//! instead of calling `Utc::now()`, `Instant::now()` or `tokio::time::sleep`
+//! themselves. [`TxClient`](crate::client::TxClient) uses
+//! [`TxClientBuilder::clock`](crate::client::TxClientBuilder::clock) for its
+//! default order expiries, rate limits and status cache, and the components
+//! built on a client, such as trailing stops, the DCA scheduler and the
+//! submission scheduler, default to the client's clock.
+//!
+//! [`ManualClock`] only moves when told to, so tests of time-based logic run
+//! in milliseconds with exact control over what time it is:
+//!
+//! ```
+//! use lighter_rs::clock::{Clock, ManualClock};
+//! use std::time::Duration;
+//!
+//! # tokio_test::block_on(async {
+//! let clock = ManualClock::at_ms(1_700_000_000_000);
+//! clock.advance(Duration::from_secs(2));
+//! clock.sleep(Duration::from_secs(60)).await;
+//! assert_eq!(clock.now_ms(), 1_700_000_062_000);
+//! # });
+//! ```
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use futures_util::future::BoxFuture;
+
+/// Source of wall-clock and monotonic time, and of waiting
+pub trait Clock: Send + Sync {
+    /// Current wall-clock time
+    fn now_utc(&self) -> DateTime<Utc>;
+
+    /// Current monotonic time, for measuring intervals
+    fn now_instant(&self) -> Instant;
+
+    /// Wait for `duration` to pass on this clock
+    fn sleep(&self, duration: Duration) -> BoxFuture<'_, ()>;
+
+    /// Milliseconds since the Unix epoch
+    fn now_ms(&self) -> i64 {
+        self.now_utc().timestamp_millis()
+    }
+}
+
+/// The system clock and tokio timers
+///
+/// Without the `native` feature there is no timer and `sleep` returns at
+/// once; nothing in the crate waits on the clock in such builds.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_utc(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+
+    fn now_instant(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep(&self, duration: Duration) -> BoxFuture<'_, ()> {
+        #[cfg(feature = "native")]
+        return Box::pin(tokio::time::sleep(duration));
+        #[cfg(not(feature = "native"))]
+        {
+            let _ = duration;
+            Box::pin(futures_util::future::ready(()))
+        }
+    }
+}
+
+/// A clock that only moves when told to
+///
+/// [`ManualClock::advance`] moves it forward, and so does every `sleep`,
+/// which returns at once: code waiting on the clock runs without real
+/// delays, and afterwards the clock reads as if the waits had happened.
+#[derive(Debug)]
+pub struct ManualClock {
+    start: DateTime<Utc>,
+    start_instant: Instant,
+    /// Nanoseconds since `start`
+    elapsed: AtomicU64,
+}
+
+impl ManualClock {
+    /// A clock reading `start`
+    pub fn new(start: DateTime<Utc>) -> Self {
+        Self {
+            start,
+            start_instant: Instant::now(),
+            elapsed: AtomicU64::new(0),
+        }
+    }
+
+    /// A clock reading `start_ms` milliseconds since the Unix epoch
+    pub fn at_ms(start_ms: i64) -> Self {
+        Self::new(DateTime::from_timestamp_millis(start_ms).unwrap_or_default())
+    }
+
+    /// Move the clock forward by `duration`
+    pub fn advance(&self, duration: Duration) {
+        self.elapsed
+            .fetch_add(duration.as_nanos() as u64, Ordering::SeqCst);
+    }
+
+    /// Time the clock has moved since it was created
+    pub fn elapsed(&self) -> Duration {
+        Duration::from_nanos(self.elapsed.load(Ordering::SeqCst))
+    }
+}
+
+impl Clock for ManualClock {
+    fn now_utc(&self) -> DateTime<Utc> {
+        self.start + self.elapsed()
+    }
+
+    fn now_instant(&self) -> Instant {
+        self.start_instant + self.elapsed()
+    }
+
+    fn sleep(&self, duration: Duration) -> BoxFuture<'_, ()> {
+        self.advance(duration);
+        Box::pin(futures_util::future::ready(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_manual_clock_moves_only_when_told() {
+        let clock = ManualClock::at_ms(1_000);
+        let instant = clock.now_instant();
+        assert_eq!(clock.now_ms(), 1_000);
+        assert_eq!(clock.now_ms(), 1_000);
+
+        clock.advance(Duration::from_millis(250));
+        clock.sleep(Duration::from_secs(1)).await;
+        assert_eq!(clock.now_ms(), 2_250);
+        assert_eq!(clock.now_instant() - instant, Duration::from_millis(1_250));
+        assert_eq!(clock.elapsed(), Duration::from_millis(1_250));
+    }
+}
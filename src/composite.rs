@@ -0,0 +1,604 @@
+//! Multi-step operations bounded by a single deadline
+//!
+//! Each operation takes an optional [`Deadline`] covering all of its
+//! requests; see the [`deadline`](crate::deadline) module. When it passes,
+//! [`LighterError::DeadlineExceeded`] lists the steps that completed, for
+//! example whether a replaced order's cancel was already accepted.
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use lighter_rs::client::TxClient;
+//! use lighter_rs::deadline::Deadline;
+//! use lighter_rs::types::{CancelOrderTxReq, CreateOrderTxReq};
+//!
+//! # async fn example(tx_client: TxClient, cancel: CancelOrderTxReq, create: CreateOrderTxReq) -> lighter_rs::Result<()> {
+//! let deadline = Deadline::after(Duration::from_secs(3));
+//! let replaced = tx_client.replace_order(&cancel, &create, Some(deadline)).await?;
+//! if let Some(hash) = &replaced.create.tx_hash {
+//!     let executed = tx_client
+//!         .wait_for_transaction(hash, Duration::from_millis(200), Some(deadline))
+//!         .await?;
+//!     println!("executed in block {}", executed.block_height);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::time::Duration;
+
+use rust_decimal::Decimal;
+
+use crate::client::{HTTPClient, TransactionStatus, TxClient, TxResponse};
+use crate::deadline::{Deadline, Progress};
+use crate::errors::{LighterError, Result};
+use crate::fresh_price::FreshPrice;
+use crate::kill_switch::flatten_order;
+use crate::types::{CancelOrderTxReq, CreateOrderTxReq, L2CreateOrderTxInfo};
+
+/// Responses of the two transactions sent by [`TxClient::replace_order`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplaceOrderResponse {
+    pub cancel: TxResponse,
+    pub create: TxResponse,
+}
+
+impl TxClient {
+    /// Cancel an order, then create its replacement once the cancel is accepted
+    ///
+    /// Unlike [`TxClient::modify_order`] the replacement can change side,
+    /// type or market. Completed steps are `cancel signed`, `cancel sent`,
+    /// `create signed` and `create sent`; the replacement is never sent if
+    /// the cancel is rejected.
+    pub async fn replace_order(
+        &self,
+        cancel: &CancelOrderTxReq,
+        create: &CreateOrderTxReq,
+        deadline: Option<Deadline>,
+    ) -> Result<ReplaceOrderResponse> {
+        let mut progress = Progress::new(deadline);
+        let tx = progress
+            .step("cancel signed", self.cancel_order(cancel, None))
+            .await?;
+        let cancel = progress
+            .step("cancel sent", async {
+                accepted(self.send_transaction(&tx).await?)
+            })
+            .await?;
+        let tx = progress
+            .step("create signed", self.create_order(create, None))
+            .await?;
+        let create = progress
+            .step("create sent", async {
+                accepted(self.send_transaction(&tx).await?)
+            })
+            .await?;
+        Ok(ReplaceOrderResponse { cancel, create })
+    }
+
+    /// Close this client's position in one market with a reduce-only market order
+    ///
+    /// The order is priced `max_slippage_bps` through the market's last trade
+    /// price, as the kill switch prices it. Returns `None` when there is no
+    /// position to close. Completed steps are `positions fetched`,
+    /// `market details fetched`, `close signed` and `close sent`.
+    pub async fn close_position(
+        &self,
+        market_index: u8,
+        max_slippage_bps: Decimal,
+        deadline: Option<Deadline>,
+    ) -> Result<Option<TxResponse>> {
+        self.close_position_priced(market_index, Reduce::All, None, max_slippage_bps, deadline)
+            .await
+    }
+
+    /// Close `fraction` of this client's position in one market, between 0
+    /// exclusive and 1 inclusive
+    ///
+    /// The size closed is rounded to the market's size decimals; a fraction
+    /// too small for an order is refused. Otherwise works like
+    /// [`TxClient::close_position`].
+    pub async fn close_position_partial(
+        &self,
+        market_index: u8,
+        fraction: Decimal,
+        max_slippage_bps: Decimal,
+        deadline: Option<Deadline>,
+    ) -> Result<Option<TxResponse>> {
+        if fraction <= Decimal::ZERO || fraction > Decimal::ONE {
+            return Err(LighterError::ValidationError(format!(
+                "Fraction {fraction} of a position is not between 0 and 1"
+            )));
+        }
+        self.close_position_priced(
+            market_index,
+            Reduce::Fraction(fraction),
+            None,
+            max_slippage_bps,
+            deadline,
+        )
+        .await
+    }
+
+    /// Close this client's position in one market, priced `max_slippage_bps`
+    /// through `reference`, such as the live book's mid
+    ///
+    /// `reference` is checked just before the order is signed, after the
+    /// position and market details are fetched; if it is older than
+    /// `max_staleness` by then, nothing is sent and
+    /// [`LighterError::StalePrice`] is returned. Otherwise works like
+    /// [`TxClient::close_position`].
+    pub async fn close_position_at(
+        &self,
+        market_index: u8,
+        reference: FreshPrice,
+        max_staleness: Duration,
+        max_slippage_bps: Decimal,
+        deadline: Option<Deadline>,
+    ) -> Result<Option<TxResponse>> {
+        self.close_position_priced(
+            market_index,
+            Reduce::All,
+            Some((reference, max_staleness)),
+            max_slippage_bps,
+            deadline,
+        )
+        .await
+    }
+
+    async fn close_position_priced(
+        &self,
+        market_index: u8,
+        reduce: Reduce,
+        reference: Option<(FreshPrice, Duration)>,
+        max_slippage_bps: Decimal,
+        deadline: Option<Deadline>,
+    ) -> Result<Option<TxResponse>> {
+        let mut progress = Progress::new(deadline);
+        let Some(close) = self
+            .sign_reduce_order(
+                market_index,
+                reduce,
+                reference,
+                max_slippage_bps,
+                &mut progress,
+            )
+            .await?
+        else {
+            return Ok(None);
+        };
+        let response = progress
+            .step("close sent", async {
+                accepted(self.send_transaction(&close.tx).await?)
+            })
+            .await?;
+        Ok(Some(response))
+    }
+
+    /// Sign the reduce-only market order taking `reduce` off the position in
+    /// one market
+    ///
+    /// The order
+    /// is priced off `reference` if it is still within its staleness limit,
+    /// or the last trade price without one. Returns `None` when there is
+    /// nothing to reduce.
+    pub(crate) async fn sign_reduce_order(
+        &self,
+        market_index: u8,
+        reduce: Reduce,
+        reference: Option<(FreshPrice, Duration)>,
+        max_slippage_bps: Decimal,
+        progress: &mut Progress,
+    ) -> Result<Option<ReduceOrder>> {
+        let http = self.http_client()?;
+        let positions = progress
+            .step(
+                "positions fetched",
+                http.get_account_positions(self.account_index()),
+            )
+            .await?;
+        let Some(mut position) = positions
+            .into_iter()
+            .find(|position| position.market_id == market_index && !position.size().is_zero())
+        else {
+            return Ok(None);
+        };
+        match reduce {
+            Reduce::All => {}
+            Reduce::Exposure(exposure) => {
+                if exposure.is_zero() || exposure.is_sign_negative() != (position.sign < 0) {
+                    return Ok(None);
+                }
+                position.position = position.position.min(exposure.abs());
+            }
+            Reduce::Fraction(fraction) => position.position *= fraction,
+        }
+
+        let details = progress
+            .step(
+                "market details fetched",
+                http.get_market_details(market_index),
+            )
+            .await?;
+        let reference_price = match reference {
+            Some((price, max_staleness)) => price.fresh(self.clock().as_ref(), max_staleness)?,
+            None => details.last_trade_price,
+        };
+        let order = flatten_order(
+            max_slippage_bps,
+            &position,
+            reference_price,
+            details.price_decimals,
+            details.size_decimals,
+        )?;
+
+        let client_order_index = self.next_client_order_index();
+        let tx = progress
+            .step(
+                "close signed",
+                self.create_market_order(
+                    market_index,
+                    client_order_index,
+                    order.base_amount,
+                    order.price,
+                    order.is_ask,
+                    true,
+                    None,
+                ),
+            )
+            .await?;
+        let size = Decimal::new(order.base_amount, details.size_decimals);
+        Ok(Some(ReduceOrder {
+            tx,
+            size: if position.sign < 0 { -size } else { size },
+        }))
+    }
+
+    /// Poll a transaction every `poll_interval` until it has been executed
+    ///
+    /// Without a deadline this waits indefinitely. Completed steps are the
+    /// lookups made, `poll 1`, `poll 2` and so on.
+    pub async fn wait_for_transaction(
+        &self,
+        tx_hash: &str,
+        poll_interval: Duration,
+        deadline: Option<Deadline>,
+    ) -> Result<TransactionStatus> {
+        let http = self.http_client()?;
+        let mut progress = Progress::new(deadline);
+
+        let mut poll = 0;
+        loop {
+            poll += 1;
+            let status = progress
+                .step(format!("poll {poll}"), http.get_transaction(tx_hash))
+                .await?;
+            if let Some(status) = status.filter(TransactionStatus::is_executed) {
+                return Ok(status);
+            }
+            progress.pause(poll_interval).await?;
+        }
+    }
+
+    pub(crate) fn http_client(&self) -> Result<&HTTPClient> {
+        self.http().ok_or_else(|| {
+            LighterError::InvalidConfiguration("HTTPClient is not configured".to_string())
+        })
+    }
+}
+
+/// How much of a position [`TxClient::sign_reduce_order`] takes off
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Reduce {
+    All,
+    /// Exposure signed like
+    /// [`AccountPosition::size`](crate::client::AccountPosition::size); only
+    /// as much of it as the position holds on that side is reduced
+    Exposure(Decimal),
+    /// A fraction of the position
+    Fraction(Decimal),
+}
+
+/// A signed reduce-only order and the position it takes off
+pub(crate) struct ReduceOrder {
+    pub(crate) tx: L2CreateOrderTxInfo,
+    /// Signed like [`AccountPosition::size`](crate::client::AccountPosition::size)
+    pub(crate) size: Decimal,
+}
+
+/// The response of an accepted transaction, or the rejection as an error
+pub(crate) fn accepted(response: TxResponse) -> Result<TxResponse> {
+    if response.is_success() {
+        Ok(response)
+    } else {
+        Err(LighterError::ApiError(format!(
+            "Rejected with code {}: {}",
+            response.code,
+            response.message.unwrap_or_default()
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::*;
+    use crate::test_support::mock_builder;
+    use crate::transport::{HttpRequest, HttpResponse, MockTransport, Transport, TransportFuture};
+    use serde_json::json;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    const SEND_TX_PATH: &str = "/api/v1/sendTx";
+    const TX_PATH: &str = "/api/v1/tx";
+
+    fn setup() -> (TxClient, Arc<MockTransport>) {
+        let mock = Arc::new(MockTransport::new());
+        mock.set_handler(SEND_TX_PATH, |_| {
+            Ok(HttpResponse::new(200, r#"{"code":200,"tx_hash":"0xabc"}"#))
+        });
+        mock.set_handler("/api/v1/account", |_| {
+            let positions = json!([
+                {"market_id": 0, "sign": -1, "position": "0.5000", "avg_entry_price": "2900.00"},
+            ]);
+            Ok(HttpResponse::new(
+                200,
+                json!({"code": 200, "accounts": [{"positions": positions}]}).to_string(),
+            ))
+        });
+        mock.set_handler("/api/v1/orderBookDetails", |_| {
+            Ok(HttpResponse::new(
+                200,
+                r#"{"code":200,"order_book_details":[{"market_id":0,"size_decimals":4,"price_decimals":2,"last_trade_price":"3000.00"}]}"#,
+            ))
+        });
+
+        let tx_client = mock_builder(&mock).build().unwrap();
+        tx_client.nonces().set(1, 0, 0);
+        (tx_client, mock)
+    }
+
+    fn completed_steps(result: Result<impl std::fmt::Debug>) -> Vec<String> {
+        match result {
+            Err(LighterError::DeadlineExceeded { completed_steps }) => completed_steps,
+            other => panic!("expected DeadlineExceeded, got {other:?}"),
+        }
+    }
+
+    fn replacement() -> (CancelOrderTxReq, CreateOrderTxReq) {
+        let cancel = CancelOrderTxReq {
+            market_index: 0,
+            index: 42,
+        };
+        let create = CreateOrderTxReq {
+            market_index: 0,
+            client_order_index: 7,
+            base_amount: 1_000,
+            price: 300_000,
+            is_ask: 1,
+            order_type: ORDER_TYPE_LIMIT,
+            time_in_force: TIME_IN_FORCE_GOOD_TILL_TIME,
+            reduce_only: 0,
+            trigger_price: 0,
+            order_expiry: 0,
+        };
+        (cancel, create)
+    }
+
+    #[tokio::test]
+    async fn test_replace_order_sends_cancel_then_create() {
+        let (tx_client, mock) = setup();
+        let (cancel, create) = replacement();
+
+        let replaced = tx_client
+            .replace_order(
+                &cancel,
+                &create,
+                Some(Deadline::after(Duration::from_secs(5))),
+            )
+            .await
+            .unwrap();
+        assert!(replaced.cancel.is_success() && replaced.create.is_success());
+        assert_eq!(mock.requests_to(SEND_TX_PATH).len(), 2);
+    }
+
+    /// Holds the response to every sendTx after the first
+    struct SlowSecondSend {
+        inner: Arc<MockTransport>,
+        sends: AtomicUsize,
+    }
+
+    impl Transport for SlowSecondSend {
+        fn execute(&self, request: HttpRequest) -> TransportFuture<'_> {
+            let slow =
+                request.path() == SEND_TX_PATH && self.sends.fetch_add(1, Ordering::SeqCst) > 0;
+            let response = self.inner.execute(request);
+            Box::pin(async move {
+                if slow {
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                }
+                response.await
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_replace_order_reports_accepted_cancel() {
+        let (_, mock) = setup();
+        let transport = Arc::new(SlowSecondSend {
+            inner: mock.clone(),
+            sends: AtomicUsize::new(0),
+        });
+        let tx_client = mock_builder(&mock).transport(transport).build().unwrap();
+        tx_client.nonces().set(1, 0, 0);
+        let (cancel, create) = replacement();
+
+        let deadline = Deadline::after(Duration::from_millis(100));
+        let result = tx_client
+            .replace_order(&cancel, &create, Some(deadline))
+            .await;
+        assert_eq!(
+            completed_steps(result),
+            vec!["cancel signed", "cancel sent", "create signed"]
+        );
+        // The create went out, but its response never arrived
+        assert_eq!(mock.requests_to(SEND_TX_PATH).len(), 2);
+        assert!(deadline.is_expired());
+    }
+
+    #[tokio::test]
+    async fn test_replace_order_stops_on_rejected_cancel() {
+        let (tx_client, mock) = setup();
+        let (cancel, create) = replacement();
+        mock.push_response(
+            SEND_TX_PATH,
+            200,
+            r#"{"code":21500,"message":"order not found"}"#,
+        );
+
+        let result = tx_client.replace_order(&cancel, &create, None).await;
+        assert!(matches!(result, Err(LighterError::ApiError(_))));
+        assert_eq!(mock.requests_to(SEND_TX_PATH).len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_close_position_within_deadline() {
+        let (tx_client, mock) = setup();
+
+        let response = tx_client
+            .close_position(0, Decimal::new(100, 0), None)
+            .await
+            .unwrap();
+        assert!(response.unwrap().is_success());
+        assert_eq!(mock.requests_to(SEND_TX_PATH).len(), 1);
+        // Nothing to close in market 1
+        assert!(tx_client
+            .close_position(1, Decimal::new(100, 0), None)
+            .await
+            .unwrap()
+            .is_none());
+
+        mock.set_delay("/api/v1/orderBookDetails", Duration::from_secs(5));
+        let result = tx_client
+            .close_position(
+                0,
+                Decimal::new(100, 0),
+                Some(Deadline::after(Duration::from_millis(100))),
+            )
+            .await;
+        assert_eq!(completed_steps(result), vec!["positions fetched"]);
+        assert_eq!(mock.requests_to(SEND_TX_PATH).len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_close_position_partial_closes_a_fraction() {
+        let (tx_client, mock) = setup();
+        let sent_order = |i: usize| -> serde_json::Value {
+            let sent = mock.requests_to(SEND_TX_PATH);
+            let form: Vec<(String, String)> = serde_urlencoded::from_bytes(&sent[i].body).unwrap();
+            serde_json::from_str(&form[1].1).unwrap()
+        };
+
+        // A quarter of the 0.5 short is bought back, reduce-only
+        tx_client
+            .close_position_partial(0, Decimal::new(25, 2), Decimal::new(100, 0), None)
+            .await
+            .unwrap()
+            .unwrap();
+        let order = sent_order(0);
+        assert_eq!(order["BaseAmount"], 1250);
+        assert_eq!(
+            (order["IsAsk"].as_u64(), order["ReduceOnly"].as_u64()),
+            (Some(0), Some(1))
+        );
+
+        tx_client
+            .close_position_partial(0, Decimal::ONE, Decimal::new(100, 0), None)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(sent_order(1)["BaseAmount"], 5000);
+
+        for fraction in [Decimal::ZERO, Decimal::new(-1, 1), Decimal::new(11, 1)] {
+            assert!(matches!(
+                tx_client
+                    .close_position_partial(0, fraction, Decimal::new(100, 0), None)
+                    .await,
+                Err(LighterError::ValidationError(_))
+            ));
+        }
+        assert_eq!(mock.requests_to(SEND_TX_PATH).len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_close_position_at_refuses_a_stale_reference() {
+        let (tx_client, mock) = setup();
+        let clock = tx_client.clock().clone();
+        let max_staleness = Duration::from_secs(2);
+
+        // The short is bought back 100 bps over the book's 3100.00 mid
+        let mid = FreshPrice::observed(Decimal::new(310000, 2), clock.as_ref());
+        let response = tx_client
+            .close_position_at(0, mid, max_staleness, Decimal::new(100, 0), None)
+            .await
+            .unwrap();
+        assert!(response.unwrap().is_success());
+        let sent = mock.requests_to(SEND_TX_PATH);
+        let form: Vec<(String, String)> = serde_urlencoded::from_bytes(&sent[0].body).unwrap();
+        let order: serde_json::Value = serde_json::from_str(&form[1].1).unwrap();
+        assert_eq!(order["Price"], 313100);
+
+        // A mid from a book that stopped updating three seconds ago
+        let frozen = FreshPrice::new(
+            Decimal::new(310000, 2),
+            clock.now_instant() - Duration::from_secs(3),
+        );
+        let result = tx_client
+            .close_position_at(0, frozen, max_staleness, Decimal::new(100, 0), None)
+            .await;
+        assert!(matches!(result, Err(LighterError::StalePrice { .. })));
+        assert_eq!(mock.requests_to(SEND_TX_PATH).len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_transaction() {
+        let (tx_client, mock) = setup();
+        mock.push_response(TX_PATH, 404, "not found");
+        mock.push_response(TX_PATH, 200, r#"{"code":200,"hash":"0xabc","status":1}"#);
+        mock.set_handler(TX_PATH, |_| {
+            Ok(HttpResponse::new(
+                200,
+                r#"{"code":200,"hash":"0xabc","status":3,"block_height":9,"executed_at":1717254000000}"#,
+            ))
+        });
+
+        let status = tx_client
+            .wait_for_transaction("0xabc", Duration::from_millis(1), None)
+            .await
+            .unwrap();
+        assert_eq!(status.block_height, 9);
+        let lookups = mock.requests_to(TX_PATH);
+        assert_eq!(lookups.len(), 3);
+        assert!(lookups[0].url.ends_with("by=hash&value=0xabc"));
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_transaction_times_out() {
+        let (tx_client, mock) = setup();
+        mock.set_handler(TX_PATH, |_| Ok(HttpResponse::new(404, "not found")));
+
+        let result = tx_client
+            .wait_for_transaction(
+                "0xabc",
+                Duration::from_millis(40),
+                Some(Deadline::after(Duration::from_millis(100))),
+            )
+            .await;
+        // Polls at 0, 40 and 80ms fit; the exact count depends on scheduling
+        let steps = completed_steps(result);
+        assert!(steps.len() >= 2, "{steps:?}");
+        assert!(steps
+            .iter()
+            .enumerate()
+            .all(|(i, step)| *step == format!("poll {}", i + 1)));
+    }
+}
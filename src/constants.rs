@@ -42,9 +42,13 @@ pub const ORDER_TYPE_LIQUIDATION: u8 = 8;
 pub const API_MAX_ORDER_TYPE: u8 = ORDER_TYPE_TWAP;
 
 // Order Time-In-Force
+//
+// The protocol has no fill-or-kill; an immediate-or-cancel order may fill in
+// part, and any other value is refused when signing.
 pub const TIME_IN_FORCE_IMMEDIATE_OR_CANCEL: u8 = 0;
 pub const TIME_IN_FORCE_GOOD_TILL_TIME: u8 = 1;
 pub const TIME_IN_FORCE_POST_ONLY: u8 = 2;
+pub const MAX_TIME_IN_FORCE: u8 = TIME_IN_FORCE_POST_ONLY;
 
 // Grouping Types
 pub const GROUPING_TYPE_DEFAULT: u8 = 0;
@@ -121,9 +125,9 @@ pub const MAX_ORDER_BASE_AMOUNT: i64 = (1i64 << 48) - 1;
 pub const NIL_ORDER_BASE_AMOUNT: i64 = 0;
 
 // Order Price Limits
-pub const NIL_ORDER_PRICE: u32 = 0;
-pub const MIN_ORDER_PRICE: u32 = 1;
-pub const MAX_ORDER_PRICE: u32 = u32::MAX;
+pub const NIL_ORDER_PRICE: u64 = 0;
+pub const MIN_ORDER_PRICE: u64 = 1;
+pub const MAX_ORDER_PRICE: u64 = u32::MAX as u64;
 
 // Order Cancel All Period Limits (milliseconds)
 pub const MIN_ORDER_CANCEL_ALL_PERIOD: i64 = 1000 * 60 * 5; // 5 minutes
@@ -135,15 +139,24 @@ pub const MIN_ORDER_EXPIRY: i64 = 1;
 pub const MAX_ORDER_EXPIRY: i64 = i64::MAX;
 pub const MIN_ORDER_EXPIRY_PERIOD: i64 = 1000 * 60 * 5; // 5 minutes
 pub const MAX_ORDER_EXPIRY_PERIOD: i64 = 1000 * 60 * 60 * 24 * 30; // 30 days
+/// Expiry period of the good-till-time orders built by the helpers, as in the Python SDK
+pub const DEFAULT_ORDER_EXPIRY_PERIOD: i64 = 1000 * 60 * 60 * 24 * 28; // 28 days
+
+// Auth Tokens
+/// How long an auth token is valid by default, as in the Python SDK
+pub const DEFAULT_AUTH_TOKEN_EXPIRY: i64 = 1000 * 60 * 10; // 10 minutes
 
 // Order Trigger Price Limits
-pub const NIL_ORDER_TRIGGER_PRICE: u32 = 0;
-pub const MIN_ORDER_TRIGGER_PRICE: u32 = 1;
-pub const MAX_ORDER_TRIGGER_PRICE: u32 = u32::MAX;
+pub const NIL_ORDER_TRIGGER_PRICE: u64 = 0;
+pub const MIN_ORDER_TRIGGER_PRICE: u64 = 1;
+pub const MAX_ORDER_TRIGGER_PRICE: u64 = u32::MAX as u64;
 
 // Grouped Orders
 pub const MAX_GROUPED_ORDER_COUNT: i64 = 3;
 
+// Transaction Batch Limits
+pub const MAX_TX_BATCH_SIZE: usize = 50;
+
 // Timestamp Limits
 pub const MAX_TIMESTAMP: i64 = (1i64 << 48) - 1;
 
@@ -158,6 +171,20 @@ pub const MAX_TRANSFER_AMOUNT: i64 = MAX_EXCHANGE_USDC;
 pub const MIN_WITHDRAWAL_AMOUNT: u64 = 1;
 pub const MAX_WITHDRAWAL_AMOUNT: u64 = MAX_EXCHANGE_USDC as u64;
 
+// Isolated Margin Update Limits
+pub const MIN_UPDATE_MARGIN_AMOUNT: i64 = 1;
+pub const MAX_UPDATE_MARGIN_AMOUNT: i64 = MAX_EXCHANGE_USDC;
+
+// API Response Codes
+pub const API_CODE_SUCCESS: u16 = 200;
+pub const API_CODE_TO_ACCOUNT_NOT_FOUND: u16 = 21102;
+pub const API_CODE_INVALID_NONCE: u16 = 21104;
+pub const API_CODE_API_KEY_NOT_FOUND: u16 = 21109;
+pub const API_CODE_INVALID_BASE_AMOUNT: u16 = 21701;
+
+/// Response codes the API uses to reject a transaction because of its nonce
+pub const NONCE_ERROR_CODES: &[u16] = &[API_CODE_INVALID_NONCE];
+
 #[cfg(test)]
 mod tests {
     use super::*;
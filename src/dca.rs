@@ -0,0 +1,619 @@
+//! Dollar-cost averaging: buy a fixed USD notional on a fixed interval
+//!
+//! [`DcaScheduler::run`] places one slippage-bounded market buy per
+//! interval. Each buy is sized from the market's last trade price at the
+//! time it is placed, and recorded in [`DcaScheduler::history`]; feed the
+//! account WebSocket channel to [`DcaScheduler::apply_account_frame`] to
+//! fill in the executed price, size and fees.
+//!
+//! The schedule runs on the exchange's clock: the offset to it is measured
+//! when `run` starts, and each buy is due one interval after the previous
+//! one was scheduled, so late wake-ups don't push the schedule back. Persist
+//! [`DcaScheduler::last_execution_at`] and pass it to
+//! [`DcaScheduler::resume_from`] to carry on after a restart without buying
+//! early.
+//!
+//! ```no_run
+//! use lighter_rs::client::TxClient;
+//! use lighter_rs::dca::{DcaEnd, DcaScheduler, DcaSpec};
+//! use lighter_rs::Decimal;
+//! use std::sync::Arc;
+//! use std::time::Duration;
+//!
+//! # async fn example(tx_client: TxClient, saved: Option<i64>) -> lighter_rs::Result<()> {
+//! let spec = DcaSpec {
+//!     market_index: 0,
+//!     notional_per_buy: Decimal::new(100, 0),
+//!     interval: Duration::from_secs(6 * 3600),
+//!     max_slippage_bps: Decimal::new(50, 0),
+//!     end: DcaEnd::Never,
+//! };
+//! let mut scheduler = DcaScheduler::new(Arc::new(tx_client), spec);
+//! if let Some(last_execution_at) = saved {
+//!     scheduler = scheduler.resume_from(last_execution_at);
+//! }
+//! scheduler.run().await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use serde_json::Value;
+
+use crate::client::TxClient;
+use crate::constants::*;
+use crate::errors::{LighterError, Result};
+use crate::utils::{json_decimal, json_integer};
+
+pub use crate::clock::{Clock, SystemClock};
+
+/// When a [`DcaScheduler`] stops buying
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DcaEnd {
+    /// Run until the task is dropped
+    Never,
+    /// Stop after this many buys by this scheduler
+    Executions(u32),
+    /// Stop once the next buy would be due after this exchange time, in
+    /// milliseconds since the Unix epoch
+    Until(i64),
+}
+
+/// What to buy and how often
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DcaSpec {
+    pub market_index: u8,
+    /// USD spent per buy, converted to a base amount at the last trade price
+    pub notional_per_buy: Decimal,
+    pub interval: Duration,
+    /// Worst price accepted, in basis points above the last trade price
+    pub max_slippage_bps: Decimal,
+    pub end: DcaEnd,
+}
+
+/// One buy placed by a [`DcaScheduler`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DcaExecution {
+    /// Exchange time the buy was scheduled for, in milliseconds
+    pub executed_at: i64,
+    pub client_order_index: i64,
+    pub tx_hash: Option<String>,
+    /// Last trade price the buy was sized from
+    pub reference_price: Decimal,
+    /// Integer limit price of the market order
+    pub price: u64,
+    /// Integer base amount ordered
+    pub base_amount: i64,
+    /// Base size filled, as reported by the account channel
+    pub filled_size: Decimal,
+    /// Size-weighted average fill price, zero until a fill is seen
+    pub fill_price: Decimal,
+    /// Fees paid on the fills
+    pub fees: Decimal,
+}
+
+#[derive(Default)]
+struct State {
+    last_execution_at: Option<i64>,
+    history: Vec<DcaExecution>,
+}
+
+/// Places a fixed-notional market buy every interval
+pub struct DcaScheduler {
+    tx_client: Arc<TxClient>,
+    spec: DcaSpec,
+    clock: Arc<dyn Clock>,
+    state: Mutex<State>,
+}
+
+impl DcaScheduler {
+    pub fn new(tx_client: Arc<TxClient>, spec: DcaSpec) -> Self {
+        Self {
+            clock: tx_client.clock().clone(),
+            tx_client,
+            spec,
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    /// Schedule the first buy one interval after `last_execution_at`, an
+    /// exchange time in milliseconds saved from a previous run
+    pub fn resume_from(self, last_execution_at: i64) -> Self {
+        self.lock().last_execution_at = Some(last_execution_at);
+        self
+    }
+
+    /// Use `clock` instead of the client's
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    pub fn spec(&self) -> &DcaSpec {
+        &self.spec
+    }
+
+    /// Exchange time the latest buy was scheduled for; persist it to resume
+    pub fn last_execution_at(&self) -> Option<i64> {
+        self.lock().last_execution_at
+    }
+
+    /// Buys placed by this scheduler, oldest first
+    pub fn history(&self) -> Vec<DcaExecution> {
+        self.lock().history.clone()
+    }
+
+    /// Buy on schedule until the end condition is met
+    ///
+    /// Returns the first error from sizing or placing a buy. The failed buy
+    /// isn't recorded, so calling `run` again retries it straight away.
+    pub async fn run(&self) -> Result<()> {
+        if self.spec.interval.is_zero() {
+            return Err(LighterError::ValidationError(
+                "DCA interval must be greater than 0".to_string(),
+            ));
+        }
+        let interval = i64::try_from(self.spec.interval.as_millis()).unwrap_or(i64::MAX);
+        let offset = self.server_offset().await;
+        let mut executions = 0u32;
+
+        loop {
+            if let DcaEnd::Executions(limit) = self.spec.end {
+                if executions >= limit {
+                    return Ok(());
+                }
+            }
+
+            let now = self.clock.now_ms() + offset;
+            let due = self
+                .last_execution_at()
+                .map_or(now, |last| last.saturating_add(interval));
+            if let DcaEnd::Until(until) = self.spec.end {
+                if due > until {
+                    return Ok(());
+                }
+            }
+            if due > now {
+                self.clock
+                    .sleep(Duration::from_millis((due - now) as u64))
+                    .await;
+            }
+
+            // After missing more than a whole interval, restart the schedule
+            // from now rather than catching up
+            let now = self.clock.now_ms() + offset;
+            let scheduled = if now - due >= interval { now } else { due };
+            let execution = self.buy(scheduled).await?;
+            let mut state = self.lock();
+            state.last_execution_at = Some(scheduled);
+            state.history.push(execution);
+            executions += 1;
+        }
+    }
+
+    /// Record the fills in an `account_all` frame against the buys placed
+    ///
+    /// Trades are matched by `client_order_index`; a `fee` field is added to
+    /// the buy's fees when present.
+    pub fn apply_account_frame(&self, data: &Value) {
+        let trades: Vec<&Value> = match data.get("trades") {
+            Some(Value::Array(list)) => list.iter().collect(),
+            Some(Value::Object(groups)) => groups
+                .values()
+                .filter_map(Value::as_array)
+                .flatten()
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        let mut state = self.lock();
+        for trade in trades {
+            let Some(client_order_index) = json_integer(trade.get("client_order_index")) else {
+                continue;
+            };
+            let Some(execution) = state
+                .history
+                .iter_mut()
+                .find(|execution| execution.client_order_index == client_order_index)
+            else {
+                continue;
+            };
+            let (Some(size), Some(price)) = (
+                json_decimal(trade.get("size")),
+                json_decimal(trade.get("price")),
+            ) else {
+                continue;
+            };
+            let filled = execution.filled_size + size;
+            if !filled.is_zero() {
+                execution.fill_price =
+                    (execution.fill_price * execution.filled_size + price * size) / filled;
+            }
+            execution.filled_size = filled;
+            execution.fees += json_decimal(trade.get("fee")).unwrap_or_default();
+        }
+    }
+
+    /// Milliseconds to add to the local clock to get exchange time
+    async fn server_offset(&self) -> i64 {
+        let Some(http) = self.tx_client.http() else {
+            return 0;
+        };
+        let before = self.clock.now_ms();
+        match http.get_server_time().await {
+            Ok(server) => {
+                let local = before + (self.clock.now_ms() - before) / 2;
+                server - local
+            }
+            Err(e) => {
+                tracing::warn!(error = %e, "Could not read exchange time, using the local clock");
+                0
+            }
+        }
+    }
+
+    /// Size and place one market buy
+    async fn buy(&self, scheduled: i64) -> Result<DcaExecution> {
+        let http = self.tx_client.http().ok_or_else(|| {
+            LighterError::InvalidConfiguration("HTTPClient is not configured".to_string())
+        })?;
+        let details = http.get_market_details(self.spec.market_index).await?;
+        let (reference_price, price, base_amount) = size_buy(
+            &self.spec,
+            details.last_trade_price,
+            details.price_decimals,
+            details.size_decimals,
+        )?;
+
+        let client_order_index = self.tx_client.client_order_namespace().wrap(scheduled);
+        let tx = self
+            .tx_client
+            .create_market_order(
+                self.spec.market_index,
+                client_order_index,
+                base_amount,
+                price,
+                0,
+                false,
+                None,
+            )
+            .await?;
+        let response = self.tx_client.send_transaction(&tx).await?;
+        if !response.is_success() {
+            return Err(LighterError::ApiError(format!(
+                "DCA buy rejected with code {}: {}",
+                response.code,
+                response.message.unwrap_or_default()
+            )));
+        }
+
+        tracing::info!(
+            market_index = self.spec.market_index,
+            base_amount,
+            price,
+            "Placed DCA buy"
+        );
+        Ok(DcaExecution {
+            executed_at: scheduled,
+            client_order_index,
+            tx_hash: response.tx_hash,
+            reference_price,
+            price,
+            base_amount,
+            filled_size: Decimal::ZERO,
+            fill_price: Decimal::ZERO,
+            fees: Decimal::ZERO,
+        })
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Reference price, integer limit price and integer base amount of one buy
+fn size_buy(
+    spec: &DcaSpec,
+    last_trade_price: Decimal,
+    price_decimals: u32,
+    size_decimals: u32,
+) -> Result<(Decimal, u64, i64)> {
+    if last_trade_price <= Decimal::ZERO {
+        return Err(LighterError::ValidationError(format!(
+            "No reference price for market {}",
+            spec.market_index
+        )));
+    }
+
+    let slippage = spec.max_slippage_bps / Decimal::from(10_000);
+    let price =
+        (last_trade_price * (Decimal::ONE + slippage) * Decimal::from(10u64.pow(price_decimals)))
+            .ceil();
+    let price = price
+        .to_u64()
+        .filter(|price| (MIN_ORDER_PRICE..=MAX_ORDER_PRICE).contains(price))
+        .ok_or_else(|| LighterError::ValidationError(format!("Price {price} is out of range")))?;
+
+    let size = spec.notional_per_buy / last_trade_price;
+    let base_amount = (size * Decimal::from(10u64.pow(size_decimals)))
+        .floor()
+        .to_i64()
+        .filter(|amount| *amount >= MIN_ORDER_BASE_AMOUNT)
+        .ok_or_else(|| {
+            LighterError::ValidationError(format!(
+                "Notional {} buys less than the minimum size of market {}",
+                spec.notional_per_buy, spec.market_index
+            ))
+        })?;
+
+    Ok((last_trade_price, price, base_amount))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::test_support::mock_client;
+    use crate::transport::{HttpRequest, HttpResponse, MockTransport};
+
+    const SEND_TX_PATH: &str = "/api/v1/sendTx";
+
+    /// Scheduler for one buy on market 0 at a last trade price of 2500.00,
+    /// with the exchange clock at `now` seconds
+    fn mock_scheduler(now: i64) -> (DcaScheduler, Arc<MockTransport>) {
+        let (tx_client, mock) = mock_client();
+        mock.set_handler("/", move |_| {
+            Ok(HttpResponse::new(
+                200,
+                format!(r#"{{"status":1,"network_id":1,"timestamp":{now}}}"#),
+            ))
+        });
+        mock.set_handler("/api/v1/orderBookDetails", |_| {
+            Ok(HttpResponse::new(
+                200,
+                r#"{"code":200,"order_book_details":[{"market_id":0,"size_decimals":4,"price_decimals":2,"last_trade_price":"2500.00"}]}"#,
+            ))
+        });
+        mock.set_handler("/api/v1/nextNonce", |_| {
+            Ok(HttpResponse::new(200, r#"{"code":200,"nonce":5}"#))
+        });
+        let spec = DcaSpec {
+            market_index: 0,
+            notional_per_buy: Decimal::new(100, 0),
+            interval: Duration::from_secs(3600),
+            max_slippage_bps: Decimal::new(50, 0),
+            end: DcaEnd::Executions(1),
+        };
+        let clock = Arc::new(ManualClock::at_ms(now * 1000));
+        let scheduler = DcaScheduler::new(Arc::new(tx_client), spec).clock(clock);
+        (scheduler, mock)
+    }
+
+    fn sent_order(request: &HttpRequest) -> Value {
+        let fields: Vec<(String, String)> = serde_urlencoded::from_bytes(&request.body).unwrap();
+        serde_json::from_str(&fields[1].1).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_places_a_sized_buy_and_reads_fills_from_numbers_or_strings() {
+        let (scheduler, mock) = mock_scheduler(1_700_000_000);
+        mock.push_response(SEND_TX_PATH, 200, r#"{"code":200,"tx_hash":"0xbuy"}"#);
+
+        scheduler.run().await.unwrap();
+
+        let history = scheduler.history();
+        assert_eq!(history.len(), 1);
+        let execution = &history[0];
+        assert_eq!(execution.executed_at, 1_700_000_000_000);
+        assert_eq!(execution.tx_hash.as_deref(), Some("0xbuy"));
+        // 100 USD at 2500 is 0.04, limited 50 bps above
+        assert_eq!((execution.base_amount, execution.price), (400, 251_250));
+        let order = sent_order(&mock.requests_to(SEND_TX_PATH)[0]);
+        assert_eq!(order["BaseAmount"], 400);
+        assert_eq!(order["Price"], 251_250);
+        assert_eq!(order["IsAsk"], 0);
+        assert_eq!(order["Nonce"], 5);
+
+        let client_order_index = execution.client_order_index;
+        scheduler.apply_account_frame(&serde_json::json!({
+            "trades": {
+                "0": [
+                    {"client_order_index": client_order_index, "size": "0.0100", "price": "2500.00", "fee": "0.01"},
+                    {"client_order_index": client_order_index.to_string(), "size": 0.03, "price": 2501},
+                    {"client_order_index": client_order_index + 1, "size": "1", "price": "1"},
+                    {"client_order_index": client_order_index, "size": "bad", "price": "1"}
+                ]
+            }
+        }));
+        let execution = &scheduler.history()[0];
+        assert_eq!(execution.filled_size, Decimal::new(400, 4));
+        assert_eq!(execution.fill_price, Decimal::new(250_075, 2));
+        assert_eq!(execution.fees, Decimal::new(1, 2));
+    }
+
+    #[tokio::test]
+    async fn test_a_rejected_buy_is_an_error_and_not_recorded() {
+        let (scheduler, mock) = mock_scheduler(1_700_000_000);
+        mock.push_response(
+            SEND_TX_PATH,
+            200,
+            r#"{"code":21701,"message":"invalid base amount"}"#,
+        );
+
+        assert!(matches!(
+            scheduler.run().await,
+            Err(LighterError::ApiError(message)) if message.contains("21701")
+        ));
+        assert!(scheduler.history().is_empty());
+        assert_eq!(scheduler.last_execution_at(), None);
+    }
+
+    #[tokio::test]
+    async fn test_a_zero_interval_is_refused_before_any_request() {
+        let (scheduler, mock) = mock_scheduler(1_700_000_000);
+        let scheduler = DcaScheduler {
+            spec: DcaSpec {
+                interval: Duration::ZERO,
+                ..scheduler.spec().clone()
+            },
+            ..scheduler
+        };
+
+        assert!(matches!(
+            scheduler.run().await,
+            Err(LighterError::ValidationError(_))
+        ));
+        assert!(mock.requests().is_empty());
+    }
+
+    #[cfg(feature = "simulator")]
+    mod simulator {
+        use super::*;
+        use crate::clock::ManualClock;
+        use crate::simulator::SimulatedExchange;
+        use crate::test_support::TEST_KEY;
+        use crate::ws_client::{OrderBook, PriceLevel};
+
+        const HOUR: i64 = 3_600_000;
+
+        fn spec(end: DcaEnd) -> DcaSpec {
+            DcaSpec {
+                market_index: 0,
+                notional_per_buy: Decimal::new(100, 0),
+                interval: Duration::from_secs(4 * 3600),
+                max_slippage_bps: Decimal::new(50, 0),
+                end,
+            }
+        }
+
+        /// Scheduler on a manual clock set to the exchange's time
+        fn scheduler(end: DcaEnd) -> (DcaScheduler, SimulatedExchange, Arc<ManualClock>) {
+            let exchange = SimulatedExchange::builder()
+                .market(0, 2, 4)
+                .account(1, Decimal::new(10_000, 0))
+                .build()
+                .unwrap();
+            exchange.update_order_book(
+                0,
+                &OrderBook {
+                    asks: vec![PriceLevel {
+                        price: Decimal::new(250_100, 2),
+                        size: Decimal::ONE_HUNDRED,
+                    }],
+                    bids: vec![PriceLevel {
+                        price: Decimal::new(249_900, 2),
+                        size: Decimal::ONE_HUNDRED,
+                    }],
+                    offset: None,
+                },
+            );
+            let tx_client = TxClient::builder()
+                .api_url("http://simulator")
+                .private_key(TEST_KEY)
+                .account_index(1)
+                .chain_id(304)
+                .transport(Arc::new(exchange.clone()))
+                .build()
+                .unwrap();
+            // Whole seconds, like the status endpoint
+            let clock = Arc::new(ManualClock::at_ms(chrono::Utc::now().timestamp() * 1000));
+            let scheduler = DcaScheduler::new(Arc::new(tx_client), spec(end)).clock(clock.clone());
+            (scheduler, exchange, clock)
+        }
+
+        #[tokio::test]
+        async fn test_buys_on_schedule_and_records_fills() {
+            let (scheduler, exchange, clock) = scheduler(DcaEnd::Executions(3));
+            let start = clock.now_ms();
+            let mut frames = exchange.subscribe();
+
+            scheduler.run().await.unwrap();
+
+            let history = scheduler.history();
+            assert_eq!(history.len(), 3);
+            // Intervals are exact despite the status endpoint's coarse clock
+            let times: Vec<i64> = history
+                .iter()
+                .map(|e| e.executed_at - history[0].executed_at)
+                .collect();
+            assert_eq!(times, vec![0, 4 * HOUR, 8 * HOUR]);
+            assert!((history[0].executed_at - start).abs() < 2_000);
+            assert_eq!(scheduler.last_execution_at(), Some(history[2].executed_at));
+
+            // 100 USD at a 2500 mid is 0.04, limited 50 bps above
+            assert_eq!(history[0].reference_price, Decimal::new(2500, 0));
+            assert_eq!(history[0].base_amount, 400);
+            assert_eq!(history[0].price, 251_250);
+
+            while let Ok(frame) = frames.try_recv() {
+                let data: Value = serde_json::from_str(&frame).unwrap();
+                scheduler.apply_account_frame(&data);
+            }
+            for execution in scheduler.history() {
+                assert_eq!(execution.filled_size, Decimal::new(400, 4));
+                assert_eq!(execution.fill_price, Decimal::new(250_100, 2));
+                assert_eq!(execution.fees, Decimal::ZERO);
+            }
+            let position = &exchange.account(1).unwrap().positions[&0];
+            assert_eq!(position.base_amount, 1200);
+        }
+
+        #[tokio::test]
+        async fn test_resumes_without_buying_early() {
+            let (scheduler, _exchange, clock) = scheduler(DcaEnd::Executions(1));
+            // The previous run bought an hour ago
+            let last = clock.now_ms() - HOUR;
+            let scheduler = scheduler.resume_from(last);
+
+            scheduler.run().await.unwrap();
+
+            let history = scheduler.history();
+            assert_eq!(history.len(), 1);
+            assert_eq!(history[0].executed_at, last + 4 * HOUR);
+            assert!(clock.now_ms() >= last + 4 * HOUR - 2_000);
+        }
+
+        #[tokio::test]
+        async fn test_long_downtime_restarts_schedule_and_end_time_stops() {
+            let (scheduler, _exchange, clock) = scheduler(DcaEnd::Until(0));
+            let now = clock.now_ms();
+            let scheduler = DcaScheduler {
+                spec: spec(DcaEnd::Until(now + 5 * HOUR)),
+                ..scheduler
+            }
+            .resume_from(now - 30 * HOUR);
+
+            scheduler.run().await.unwrap();
+
+            // One buy straight away, one an interval later, then past the end
+            let history = scheduler.history();
+            assert_eq!(history.len(), 2);
+            assert!((history[0].executed_at - now).abs() < 2_000);
+            assert_eq!(history[1].executed_at, history[0].executed_at + 4 * HOUR);
+        }
+
+        #[tokio::test]
+        async fn test_unsizable_buy_is_an_error() {
+            let (scheduler, _exchange, _clock) = scheduler(DcaEnd::Executions(1));
+            let scheduler = DcaScheduler {
+                spec: DcaSpec {
+                    notional_per_buy: Decimal::new(1, 2),
+                    ..spec(DcaEnd::Executions(1))
+                },
+                ..scheduler
+            };
+            assert!(matches!(
+                scheduler.run().await,
+                Err(LighterError::ValidationError(_))
+            ));
+            assert!(scheduler.history().is_empty());
+            assert_eq!(scheduler.last_execution_at(), None);
+        }
+    }
+}
@@ -0,0 +1,183 @@
+//! One deadline for a whole multi-step operation
+//!
+//! Operations such as [`TxClient::replace_order`](crate::client::TxClient::replace_order)
+//! chain several requests, and per-request timeouts add up. A [`Deadline`]
+//! bounds all of them together: every step gets only the time that is left,
+//! and once it runs out the operation stops with
+//! [`LighterError::DeadlineExceeded`], listing the steps that completed.
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use lighter_rs::client::TxClient;
+//! use lighter_rs::deadline::Deadline;
+//! use lighter_rs::LighterError;
+//!
+//! # async fn example(tx_client: TxClient) -> lighter_rs::Result<()> {
+//! let deadline = Deadline::after(Duration::from_secs(2));
+//! match tx_client.close_position(0, 50.into(), Some(deadline)).await {
+//!     Ok(Some(response)) => println!("closing order {:?}", response.tx_hash),
+//!     Ok(None) => println!("no position to close"),
+//!     Err(LighterError::DeadlineExceeded { completed_steps }) => {
+//!         eprintln!("gave up after {completed_steps:?}");
+//!     }
+//!     Err(e) => return Err(e),
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::future::Future;
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use crate::errors::{LighterError, Result};
+
+/// Point in time by which an operation must finish
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Deadline(Instant);
+
+impl Deadline {
+    /// Deadline `budget` from now
+    pub fn after(budget: Duration) -> Self {
+        Self(Instant::now() + budget)
+    }
+
+    pub fn at(instant: Instant) -> Self {
+        Self(instant)
+    }
+
+    pub fn instant(&self) -> Instant {
+        self.0
+    }
+
+    /// Time left, zero once the deadline has passed
+    pub fn remaining(&self) -> Duration {
+        self.0.saturating_duration_since(Instant::now())
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.remaining().is_zero()
+    }
+}
+
+impl From<Instant> for Deadline {
+    fn from(instant: Instant) -> Self {
+        Self(instant)
+    }
+}
+
+impl From<std::time::Instant> for Deadline {
+    fn from(instant: std::time::Instant) -> Self {
+        Self(Instant::from_std(instant))
+    }
+}
+
+/// Steps of one operation run against an optional deadline
+pub(crate) struct Progress {
+    deadline: Option<Deadline>,
+    completed: Vec<String>,
+}
+
+impl Progress {
+    pub(crate) fn new(deadline: Option<Deadline>) -> Self {
+        Self {
+            deadline,
+            completed: Vec::new(),
+        }
+    }
+
+    /// Run `step` with the time that is left, recording `name` once it succeeds
+    ///
+    /// A step that would start after the deadline is not started at all.
+    pub(crate) async fn step<T>(
+        &mut self,
+        name: impl Into<String>,
+        step: impl Future<Output = Result<T>>,
+    ) -> Result<T> {
+        let value = match self.deadline {
+            Some(deadline) => {
+                if deadline.is_expired() {
+                    return Err(self.exceeded());
+                }
+                tokio::time::timeout_at(deadline.instant(), step)
+                    .await
+                    .map_err(|_| self.exceeded())??
+            }
+            None => step.await?,
+        };
+        self.completed.push(name.into());
+        Ok(value)
+    }
+
+    /// Sleep for `duration`, or fail if the deadline comes first
+    pub(crate) async fn pause(&self, duration: Duration) -> Result<()> {
+        match self.deadline {
+            Some(deadline) if deadline.remaining() <= duration => {
+                tokio::time::sleep_until(deadline.instant()).await;
+                Err(self.exceeded())
+            }
+            _ => {
+                tokio::time::sleep(duration).await;
+                Ok(())
+            }
+        }
+    }
+
+    fn exceeded(&self) -> LighterError {
+        LighterError::DeadlineExceeded {
+            completed_steps: self.completed.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_progress_records_completed_steps() {
+        let mut progress = Progress::new(Some(Deadline::after(Duration::from_millis(100))));
+        assert_eq!(progress.step("fast", async { Ok(1) }).await.unwrap(), 1);
+
+        let slow = progress
+            .step("slow", async {
+                tokio::time::sleep(Duration::from_secs(5)).await;
+                Ok(2)
+            })
+            .await;
+        match slow {
+            Err(LighterError::DeadlineExceeded { completed_steps }) => {
+                assert_eq!(completed_steps, vec!["fast".to_string()]);
+            }
+            other => panic!("expected DeadlineExceeded, got {other:?}"),
+        }
+
+        // Nothing is started once the deadline has passed
+        let mut started = false;
+        let late = progress
+            .step("late", async {
+                started = true;
+                Ok(())
+            })
+            .await;
+        assert!(matches!(late, Err(LighterError::DeadlineExceeded { .. })));
+        assert!(!started);
+    }
+
+    #[tokio::test]
+    async fn test_step_errors_pass_through() {
+        let mut progress = Progress::new(None);
+        let failed: Result<()> = progress
+            .step("rejected", async { Err(LighterError::Timeout) })
+            .await;
+        assert!(matches!(failed, Err(LighterError::Timeout)));
+        assert!(progress.pause(Duration::ZERO).await.is_ok());
+
+        let progress = Progress::new(Some(Deadline::after(Duration::from_millis(10))));
+        assert!(matches!(
+            progress.pause(Duration::from_secs(5)).await,
+            Err(LighterError::DeadlineExceeded { .. })
+        ));
+    }
+}
@@ -99,13 +99,13 @@ pub enum LighterError {
         "Order price {0} is too low, minimum is {}",
         crate::constants::MIN_ORDER_PRICE
     )]
-    PriceTooLow(u32),
+    PriceTooLow(u64),
 
     #[error(
         "Order price {0} is too high, maximum is {}",
         crate::constants::MAX_ORDER_PRICE
     )]
-    PriceTooHigh(u32),
+    PriceTooHigh(u64),
 
     #[error("IsAsk should be 0 or 1")]
     IsAskInvalid,
@@ -116,6 +116,10 @@ pub enum LighterError {
     #[error("Order time-in-force is invalid")]
     OrderTimeInForceInvalid,
 
+    /// Post-only on an order that isn't a plain limit order
+    #[error("Post-only is only valid for limit orders, got order type {0}")]
+    PostOnlyOrderTypeInvalid(u8),
+
     #[error("Order reduce-only flag is invalid")]
     OrderReduceOnlyInvalid,
 
@@ -256,6 +260,23 @@ pub enum LighterError {
     )]
     FromAccountIndexTooHigh(i64),
 
+    /// The API refused a transfer because its destination account doesn't exist
+    #[error("Transfer to account {to_account_index} rejected with code {code}: {message}")]
+    InvalidDestinationAccount {
+        to_account_index: i64,
+        code: u16,
+        message: String,
+    },
+
+    /// A transfer meant for a sub-account names an account of another owner
+    #[error(
+        "Account {to_account_index} is not a sub-account of the owner of account {account_index}"
+    )]
+    NotASubAccount {
+        account_index: i64,
+        to_account_index: i64,
+    },
+
     // Margin Errors
     #[error("Initial margin fraction is too low, minimum is 0")]
     InitialMarginFractionTooLow,
@@ -269,9 +290,82 @@ pub enum LighterError {
     #[error("Margin mode is invalid")]
     InvalidMarginMode,
 
+    /// Leverage beyond what the market allows, from its minimum initial
+    /// margin fraction
+    #[error("Leverage {leverage}x is out of range for market {market_index}, allowed 1x to {max_leverage}x")]
+    LeverageOutOfRange {
+        market_index: u8,
+        leverage: u16,
+        max_leverage: u16,
+    },
+
+    /// Switching a market to isolated margin while the account holds a
+    /// cross-margin position in it
+    #[error("Market {market_index} has an open cross-margin position and can't switch to isolated margin")]
+    IsolatedMarginWithOpenCrossPosition { market_index: u8 },
+
     #[error("Margin movement direction is invalid")]
     InvalidUpdateMarginDirection,
 
+    #[error(
+        "Margin update amount {0} is too low, minimum is {}",
+        crate::constants::MIN_UPDATE_MARGIN_AMOUNT
+    )]
+    UpdateMarginAmountTooLow(i64),
+
+    #[error(
+        "Margin update amount {0} is too high, maximum is {}",
+        crate::constants::MAX_UPDATE_MARGIN_AMOUNT
+    )]
+    UpdateMarginAmountTooHigh(i64),
+
+    /// A USDC amount that isn't positive or has more than USDC's 6 decimals
+    #[error("USDC amount {0} is not a positive amount with at most 6 decimals")]
+    InvalidUsdcAmount(f64),
+
+    /// A notional that comes to less than its market's minimum order once
+    /// converted to a base amount
+    #[error(
+        "Notional {notional} is {size} in market {market_index}, below its minimum order of {min_base_amount} or a notional of {min_quote_amount}"
+    )]
+    NotionalBelowMarketMinimum {
+        market_index: u8,
+        notional: rust_decimal::Decimal,
+        size: rust_decimal::Decimal,
+        min_base_amount: rust_decimal::Decimal,
+        min_quote_amount: rust_decimal::Decimal,
+    },
+
+    /// An order book side that can't fill any size within an average price
+    #[error(
+        "No {} liquidity in the book fills at an average of {max_avg_price} or better",
+        if *is_ask { "bid" } else { "ask" }
+    )]
+    InsufficientLiquidity {
+        is_ask: bool,
+        max_avg_price: rust_decimal::Decimal,
+    },
+
+    /// A price that isn't positive or doesn't fit an order's price field
+    /// once scaled to the market's decimals
+    #[error("Price {price} with {price_decimals} decimals is not a valid order price")]
+    InvalidOrderPrice { price: f64, price_decimals: u32 },
+
+    /// A slippage-bounded price that doesn't fit an order's price field
+    #[error("Price of {reference} with {slippage_bps} bps of slippage is out of range")]
+    SlippagePriceOutOfRange {
+        reference: rust_decimal::Decimal,
+        slippage_bps: u32,
+    },
+
+    /// A TWAP duration outside the exchange's order expiry window
+    #[error(
+        "TWAP duration of {0:?} is out of range, allowed {}ms to {}ms",
+        crate::constants::MIN_ORDER_EXPIRY_PERIOD,
+        crate::constants::MAX_ORDER_EXPIRY_PERIOD
+    )]
+    TwapDurationOutOfRange(std::time::Duration),
+
     // General Errors
     #[error("Nonce {0} is too low, minimum is {}", crate::constants::MIN_NONCE)]
     NonceTooLow(i64),
@@ -323,6 +417,93 @@ pub enum LighterError {
     #[error("Network timeout")]
     Timeout,
 
+    /// A multi-step operation ran out of time
+    ///
+    /// `completed_steps` lists the steps that finished, in order. The step
+    /// that was cut short may still have reached the exchange.
+    #[error("Deadline exceeded after completing {completed_steps:?}")]
+    DeadlineExceeded { completed_steps: Vec<String> },
+
+    /// A required step of [`Session::connect`](crate::session::Session::connect)
+    /// failed
+    #[cfg(feature = "native")]
+    #[error("Session step {step} failed: {source}")]
+    SessionStepFailed {
+        step: crate::session::SessionStep,
+        source: Box<LighterError>,
+    },
+
+    /// A prepared transaction was submitted after its signed expiry
+    #[error("Prepared transaction expired at {expired_at}, {} ms before it was submitted", now_ms - expired_at)]
+    PreparedTxExpired { expired_at: i64, now_ms: i64 },
+
+    // Risk Errors
+    #[error("Risk limit breached: {rule} is limited to {limit}, attempted {attempted}")]
+    RiskLimitBreached {
+        rule: crate::risk::RiskRule,
+        limit: rust_decimal::Decimal,
+        attempted: rust_decimal::Decimal,
+    },
+
+    /// An order's notional was above the client's `max_notional_per_order`
+    #[error("Order notional {notional} on market {market_index} is above the {cap} cap per order")]
+    NotionalCapExceeded {
+        market_index: u8,
+        notional: rust_decimal::Decimal,
+        cap: rust_decimal::Decimal,
+    },
+
+    /// An order's price or trigger price was outside the band the exchange
+    /// accepts around the mark
+    #[error("Price {attempted} is outside the exchange's band of {allowed_min} to {allowed_max}")]
+    PriceOutsideBand {
+        allowed_min: rust_decimal::Decimal,
+        allowed_max: rust_decimal::Decimal,
+        attempted: rust_decimal::Decimal,
+    },
+
+    /// A stop loss or take profit's trigger was on the side of the market
+    /// that fires it at once
+    #[error(
+        "A {} {kind} must trigger {} the reference price {reference_price}, got {trigger_price}",
+        if *is_ask { "sell" } else { "buy" },
+        if kind.triggers_below(*is_ask) { "below" } else { "above" }
+    )]
+    InvertedTrigger {
+        kind: crate::trigger_direction::TriggerKind,
+        is_ask: bool,
+        trigger_price: rust_decimal::Decimal,
+        reference_price: rust_decimal::Decimal,
+    },
+
+    /// A trigger order's execution price was past its trigger, so it
+    /// couldn't fill once triggered
+    #[error(
+        "A {} trigger order's price must be at or {} its trigger {trigger_price}, got {price}",
+        if *is_ask { "sell" } else { "buy" },
+        if *is_ask { "below" } else { "above" }
+    )]
+    ExecutionPriceBeyondTrigger {
+        is_ask: bool,
+        price: u64,
+        trigger_price: u64,
+    },
+
+    /// The exchange reported trading paused on the order's market
+    #[error("Trading is paused on market {market_index}: {}", message.as_deref().unwrap_or("no reason given"))]
+    TradingPaused {
+        market_index: u8,
+        message: Option<String>,
+    },
+
+    // Market Data Errors
+    /// A price was older than the caller allowed
+    #[error("Price is {age:?} old, older than the {max_staleness:?} allowed")]
+    StalePrice {
+        age: std::time::Duration,
+        max_staleness: std::time::Duration,
+    },
+
     // JSON Errors
     #[error("JSON serialization/deserialization error: {0}")]
     JsonError(#[from] serde_json::Error),
@@ -334,6 +515,10 @@ pub enum LighterError {
     #[error("Invalid configuration: {0}")]
     InvalidConfiguration(String),
 
+    /// A client built read-only was asked to submit a transaction
+    #[error("Client is read-only and cannot submit transactions")]
+    ReadOnlyMode,
+
     #[error("Validation error: {0}")]
     ValidationError(String),
 
@@ -341,6 +526,55 @@ pub enum LighterError {
     Other(String),
 }
 
+impl LighterError {
+    /// Whether this error means the transaction was rejected because of its nonce
+    ///
+    /// Covers local nonce validation as well as API rejections carrying one of
+    /// the [`NONCE_ERROR_CODES`](crate::constants::NONCE_ERROR_CODES).
+    pub fn is_nonce_error(&self) -> bool {
+        match self {
+            LighterError::NonceTooLow(_) => true,
+            LighterError::ApiError(msg) => is_nonce_error_message(msg),
+            _ => false,
+        }
+    }
+}
+
+/// Check whether an API response code or message describes a nonce rejection
+///
+/// This is the single source of truth used by [`TxResponse::is_nonce_error`](crate::client::TxResponse::is_nonce_error)
+/// and [`LighterError::is_nonce_error`].
+pub fn is_nonce_rejection(code: u16, message: Option<&str>) -> bool {
+    crate::constants::NONCE_ERROR_CODES.contains(&code)
+        || message.is_some_and(is_nonce_error_message)
+}
+
+/// Check whether a raw error message (or response body) describes a nonce rejection
+fn is_nonce_error_message(msg: &str) -> bool {
+    let lower = msg.to_ascii_lowercase();
+    lower.contains("nonce")
+        || crate::constants::NONCE_ERROR_CODES
+            .iter()
+            .any(|code| lower.contains(&code.to_string()))
+}
+
+/// Check whether an API response rejects a transfer because its destination
+/// account doesn't exist
+///
+/// Only [`API_CODE_TO_ACCOUNT_NOT_FOUND`](crate::constants::API_CODE_TO_ACCOUNT_NOT_FOUND)
+/// or the server's exact message for it count; nonce rejections and ones
+/// blaming the sending account never do.
+pub(crate) fn is_destination_rejection(code: u16, message: Option<&str>) -> bool {
+    if is_nonce_rejection(code, message) {
+        return false;
+    }
+    code == crate::constants::API_CODE_TO_ACCOUNT_NOT_FOUND
+        || message.is_some_and(|message| {
+            ["to account not found", "to account does not exist"]
+                .contains(&message.trim().to_ascii_lowercase().as_str())
+        })
+}
+
 impl From<String> for LighterError {
     fn from(s: String) -> Self {
         LighterError::Other(s)
@@ -352,3 +586,65 @@ impl From<&str> for LighterError {
         LighterError::Other(s.to_string())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // (code, message, expected) as returned by sendTx on mainnet
+    const NONCE_CASES: &[(u16, Option<&str>, bool)] = &[
+        (21104, Some("invalid nonce"), true),
+        (21104, None, true),
+        (21109, Some("api key not found"), false),
+        (21701, Some("invalid base amount"), false),
+        (200, None, false),
+    ];
+
+    #[test]
+    fn test_is_nonce_rejection_table() {
+        for (code, message, expected) in NONCE_CASES {
+            assert_eq!(
+                is_nonce_rejection(*code, *message),
+                *expected,
+                "code {code} message {message:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_is_destination_rejection_table() {
+        for (code, message, expected) in [
+            (21102, Some("to account not found"), true),
+            (21102, None, true),
+            (400, Some("To account does not exist"), true),
+            // Nonce and sender rejections mention accounts too
+            (21104, Some("invalid nonce for account"), false),
+            (21102, Some("invalid nonce"), false),
+            (400, Some("invalid signature for account 1"), false),
+            (400, Some("from account not found"), false),
+            (21109, Some("api key not found"), false),
+            (200, None, false),
+        ] {
+            assert_eq!(
+                is_destination_rejection(code, message),
+                expected,
+                "code {code} message {message:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_lighter_error_is_nonce_error() {
+        assert!(LighterError::NonceTooLow(-1).is_nonce_error());
+        assert!(LighterError::ApiError(
+            r#"Failed to send transaction: {"code":21104,"message":"invalid nonce"}"#.to_string()
+        )
+        .is_nonce_error());
+        assert!(!LighterError::ApiError(
+            r#"Failed to send transaction: {"code":21109,"message":"api key not found"}"#
+                .to_string()
+        )
+        .is_nonce_error());
+        assert!(!LighterError::Timeout.is_nonce_error());
+    }
+}
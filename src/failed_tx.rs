@@ -0,0 +1,474 @@
+//! Signed transactions whose submission failed
+//!
+//! A signed transaction that never made it onto the exchange still stands for
+//! work: an order a strategy wanted, a nonce it was given. A
+//! [`FailedTxSink`] set with
+//! [`TxClientBuilder::failed_tx_sink`](crate::client::TxClientBuilder::failed_tx_sink)
+//! receives every signed transaction whose submission ended in failure, along
+//! with the failure and the attempts made, so none of it is lost silently:
+//!
+//! - [`TxFailure::Rejected`]: the API refused it
+//! - [`TxFailure::Unconfirmed`]: the request failed, so whether the exchange
+//!   applied it is unknown
+//! - [`TxFailure::NotAttempted`]: it was never sent because an earlier
+//!   transaction of the same pipeline or batch failed
+//!
+//! [`JsonLinesSink`] appends them to a file, one JSON object per line, and
+//! [`TxClient::resubmit_from_file`](crate::client::TxClient::resubmit_from_file)
+//! signs the rejected and unsent ones again with fresh nonces and submits
+//! them.
+//!
+//! ```no_run
+//! use lighter_rs::client::{ChunkPolicy, TxClient};
+//! use lighter_rs::failed_tx::JsonLinesSink;
+//! use std::sync::Arc;
+//!
+//! # async fn example() -> lighter_rs::Result<()> {
+//! let tx_client = TxClient::builder()
+//!     .api_url("https://testnet.zklighter.elliot.ai")
+//!     .private_key("0x...")
+//!     .failed_tx_sink(Arc::new(JsonLinesSink::new("failed-txs.jsonl")?))
+//!     .build()?;
+//!
+//! // ... trade, then after a look at the file ...
+//!
+//! let report = tx_client
+//!     .resubmit_from_file("failed-txs.jsonl", ChunkPolicy::default())
+//!     .await?;
+//! println!("{} resubmitted", report.results.len());
+//! # Ok(())
+//! # }
+//! ```
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::Result;
+use crate::types::SignedTx;
+
+/// How the submission of a transaction failed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TxFailure {
+    /// The API refused the transaction
+    Rejected { code: u16, message: Option<String> },
+    /// The request failed, so whether the API applied the transaction is
+    /// unknown
+    Unconfirmed { error: String },
+    /// Not sent because an earlier transaction failed
+    NotAttempted,
+}
+
+impl TxFailure {
+    /// Whether the transaction certainly wasn't applied, so it is safe to
+    /// sign again with a fresh nonce
+    pub fn is_safe_to_resubmit(&self) -> bool {
+        !matches!(self, TxFailure::Unconfirmed { .. })
+    }
+}
+
+/// One attempt to submit a transaction
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TxAttempt {
+    /// When the request was started, in milliseconds since the Unix epoch
+    pub sent_at_ms: i64,
+    pub error: String,
+}
+
+/// A signed transaction whose submission failed, as handed to a
+/// [`FailedTxSink`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FailedTx {
+    pub tx_type: u8,
+    pub tx_info: String,
+    pub tx_hash: Option<String>,
+    pub failure: TxFailure,
+    /// Attempts made, oldest first; empty for
+    /// [`TxFailure::NotAttempted`]
+    pub attempts: Vec<TxAttempt>,
+    /// When the failure was recorded, in milliseconds since the Unix epoch
+    pub failed_at_ms: i64,
+}
+
+impl FailedTx {
+    pub(crate) fn new(
+        tx: &SignedTx,
+        failure: TxFailure,
+        sent_at_ms: Option<i64>,
+        failed_at_ms: i64,
+    ) -> Self {
+        let attempts = match (&failure, sent_at_ms) {
+            (TxFailure::NotAttempted, _) | (_, None) => Vec::new(),
+            (TxFailure::Rejected { code, message }, Some(sent_at_ms)) => vec![TxAttempt {
+                sent_at_ms,
+                error: match message {
+                    Some(message) => format!("code {code}: {message}"),
+                    None => format!("code {code}"),
+                },
+            }],
+            (TxFailure::Unconfirmed { error }, Some(sent_at_ms)) => vec![TxAttempt {
+                sent_at_ms,
+                error: error.clone(),
+            }],
+        };
+        Self {
+            tx_type: tx.tx_type,
+            tx_info: tx.tx_info.clone(),
+            tx_hash: tx.tx_hash.clone(),
+            failure,
+            attempts,
+            failed_at_ms,
+        }
+    }
+
+    /// The transaction as it was signed
+    pub fn signed_tx(&self) -> SignedTx {
+        SignedTx {
+            tx_type: self.tx_type,
+            tx_info: self.tx_info.clone(),
+            tx_hash: self.tx_hash.clone(),
+        }
+    }
+}
+
+/// Receives signed transactions whose submission failed
+///
+/// Called on the sending task, so implementations should be quick. Errors
+/// are logged; they don't change the outcome of the submission.
+pub trait FailedTxSink: Send + Sync {
+    fn record(&self, failed: &FailedTx) -> Result<()>;
+}
+
+#[cfg(feature = "native")]
+pub use file::{read_failed_txs, JsonLinesSink};
+
+#[cfg(feature = "native")]
+mod file {
+    use std::fs::{File, OpenOptions};
+    use std::io::{BufRead, BufReader, Write};
+    use std::path::{Path, PathBuf};
+    use std::sync::Mutex;
+
+    use super::{FailedTx, FailedTxSink};
+    use crate::client::{ChunkPolicy, SubmitAllReport, TxClient};
+    use crate::errors::{LighterError, Result};
+
+    /// Appends failed transactions to a file, one JSON object per line
+    pub struct JsonLinesSink {
+        path: PathBuf,
+        file: Mutex<File>,
+    }
+
+    impl JsonLinesSink {
+        /// Append to `path`, creating it if needed
+        pub fn new(path: impl Into<PathBuf>) -> Result<Self> {
+            let path = path.into();
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+                .map_err(|e| io_error(&path, e))?;
+            Ok(Self {
+                path,
+                file: Mutex::new(file),
+            })
+        }
+
+        pub fn path(&self) -> &Path {
+            &self.path
+        }
+    }
+
+    impl FailedTxSink for JsonLinesSink {
+        fn record(&self, failed: &FailedTx) -> Result<()> {
+            let mut line = serde_json::to_vec(failed)?;
+            line.push(b'\n');
+            let mut file = self
+                .file
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            // One write per line, so concurrent records don't interleave
+            file.write_all(&line)
+                .and_then(|()| file.flush())
+                .map_err(|e| io_error(&self.path, e))
+        }
+    }
+
+    /// Read the transactions a [`JsonLinesSink`] wrote, oldest first
+    ///
+    /// Blank lines are skipped; any other line that doesn't parse is an
+    /// error.
+    pub fn read_failed_txs(path: impl AsRef<Path>) -> Result<Vec<FailedTx>> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|e| io_error(path, e))?;
+        let mut failed = Vec::new();
+        for (number, line) in BufReader::new(file).lines().enumerate() {
+            let line = line.map_err(|e| io_error(path, e))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            failed.push(serde_json::from_str(&line).map_err(|e| {
+                LighterError::InvalidResponse(format!(
+                    "Failed transaction on line {} doesn't parse: {e}",
+                    number + 1
+                ))
+            })?);
+        }
+        Ok(failed)
+    }
+
+    fn io_error(path: &Path, e: std::io::Error) -> LighterError {
+        LighterError::Other(format!(
+            "Failed transaction file I/O on {}: {e}",
+            path.display()
+        ))
+    }
+
+    impl TxClient {
+        /// Sign the transactions in a [`JsonLinesSink`] file again with fresh
+        /// nonces and submit them with [`TxClient::submit_all`]
+        ///
+        /// Only rejected and unsent transactions are resubmitted, in file
+        /// order. [`TxFailure::Unconfirmed`](super::TxFailure::Unconfirmed)
+        /// ones may have been applied, so they are skipped with a warning;
+        /// check them against the exchange first. The file is left as it is.
+        pub async fn resubmit_from_file(
+            &self,
+            path: impl AsRef<Path>,
+            policy: ChunkPolicy,
+        ) -> Result<SubmitAllReport> {
+            let (safe, unconfirmed): (Vec<FailedTx>, Vec<FailedTx>) = read_failed_txs(path)?
+                .into_iter()
+                .partition(|failed| failed.failure.is_safe_to_resubmit());
+            if !unconfirmed.is_empty() {
+                tracing::warn!(
+                    skipped = unconfirmed.len(),
+                    "Not resubmitting unconfirmed transactions"
+                );
+            }
+            if safe.is_empty() {
+                return Ok(SubmitAllReport::default());
+            }
+
+            let decoded = safe
+                .iter()
+                .map(|failed| failed.signed_tx().decode())
+                .collect::<Result<Vec<_>>>()?;
+            let txs = self.resign(decoded).await?;
+            self.submit_all(txs, policy).await
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_attempts_follow_failure() {
+        let tx = SignedTx {
+            tx_type: 14,
+            tx_info: "{}".to_string(),
+            tx_hash: Some("0xabc".to_string()),
+        };
+        let rejected = FailedTx::new(
+            &tx,
+            TxFailure::Rejected {
+                code: 21701,
+                message: Some("invalid base amount".to_string()),
+            },
+            Some(1_000),
+            1_050,
+        );
+        assert_eq!(
+            rejected.attempts,
+            [TxAttempt {
+                sent_at_ms: 1_000,
+                error: "code 21701: invalid base amount".to_string()
+            }]
+        );
+        assert_eq!(rejected.signed_tx(), tx);
+
+        let skipped = FailedTx::new(&tx, TxFailure::NotAttempted, Some(1_000), 1_050);
+        assert!(skipped.attempts.is_empty());
+        assert!(skipped.failure.is_safe_to_resubmit());
+
+        let line = serde_json::to_string(&rejected).unwrap();
+        assert!(line.contains(r#""kind":"rejected""#));
+        assert_eq!(serde_json::from_str::<FailedTx>(&line).unwrap(), rejected);
+    }
+
+    #[cfg(feature = "native")]
+    mod client {
+        use super::*;
+        use crate::client::{ChunkPolicy, TxClient};
+        use crate::errors::LighterError;
+        use crate::test_support::mock_builder;
+        use crate::transport::{HttpResponse, MockTransport};
+        use crate::types::{DecodedTx, TxInfo};
+        use std::path::PathBuf;
+        use std::sync::{Arc, Mutex};
+
+        const SEND_TX_PATH: &str = "/api/v1/sendTx";
+        const SEND_TX_BATCH_PATH: &str = "/api/v1/sendTxBatch";
+
+        #[derive(Default)]
+        struct MemorySink(Mutex<Vec<FailedTx>>);
+
+        impl FailedTxSink for MemorySink {
+            fn record(&self, failed: &FailedTx) -> Result<()> {
+                self.0.lock().unwrap().push(failed.clone());
+                Ok(())
+            }
+        }
+
+        impl MemorySink {
+            fn take(&self) -> Vec<TxFailure> {
+                self.0
+                    .lock()
+                    .unwrap()
+                    .drain(..)
+                    .map(|failed| failed.failure)
+                    .collect()
+            }
+        }
+
+        fn client(sink: Arc<dyn FailedTxSink>) -> (TxClient, Arc<MockTransport>) {
+            let mock = Arc::new(MockTransport::new());
+            mock.set_handler("/api/v1/nextNonce", |_| {
+                Ok(HttpResponse::new(200, r#"{"code":200,"nonce":0}"#))
+            });
+            let tx_client = mock_builder(&mock).failed_tx_sink(sink).build().unwrap();
+            (tx_client, mock)
+        }
+
+        fn cancel(nonce: i64) -> SignedTx {
+            SignedTx {
+                tx_type: crate::constants::TX_TYPE_L2_CANCEL_ORDER,
+                tx_info: format!(r#"{{"Nonce":{nonce}}}"#),
+                tx_hash: None,
+            }
+        }
+
+        fn rejected() -> TxFailure {
+            TxFailure::Rejected {
+                code: 21701,
+                message: Some("invalid base amount".to_string()),
+            }
+        }
+
+        fn temp_file(name: &str) -> PathBuf {
+            let path = std::env::temp_dir().join(format!(
+                "lighter-rs-failed-{name}-{}.jsonl",
+                std::process::id()
+            ));
+            let _ = std::fs::remove_file(&path);
+            path
+        }
+
+        #[tokio::test]
+        async fn test_sink_receives_each_failure_class() {
+            let sink = Arc::new(MemorySink::default());
+            let (tx_client, mock) = client(sink.clone());
+            let rejection = r#"{"code":21701,"message":"invalid base amount"}"#;
+
+            mock.push_response(SEND_TX_PATH, 200, r#"{"code":200}"#);
+            tx_client.send_signed(&cancel(0)).await.unwrap();
+            assert!(sink.take().is_empty());
+
+            mock.push_response(SEND_TX_PATH, 200, rejection);
+            tx_client.send_signed(&cancel(1)).await.unwrap();
+            let recorded = sink.0.lock().unwrap()[0].clone();
+            assert_eq!(recorded.signed_tx(), cancel(1));
+            assert_eq!(recorded.attempts.len(), 1);
+            assert_eq!(sink.take(), [rejected()]);
+
+            mock.push_error(SEND_TX_PATH, LighterError::Timeout);
+            assert!(tx_client.send_signed(&cancel(2)).await.is_err());
+            assert_eq!(
+                sink.take(),
+                [TxFailure::Unconfirmed {
+                    error: LighterError::Timeout.to_string()
+                }]
+            );
+
+            // The pipeline stops at the rejection and skips the rest
+            mock.push_response(SEND_TX_PATH, 200, rejection);
+            tx_client
+                .submit_pipelined(vec![cancel(3), cancel(4), cancel(5)], 1)
+                .await
+                .unwrap();
+            assert_eq!(
+                sink.take(),
+                [rejected(), TxFailure::NotAttempted, TxFailure::NotAttempted]
+            );
+
+            // So does a batch, and the batches after it go unsent
+            mock.push_response(
+                SEND_TX_BATCH_PATH,
+                200,
+                r#"{"code":21701,"message":"invalid base amount","tx_hash":["0x6"]}"#,
+            );
+            let policy = ChunkPolicy {
+                max_per_batch: 3,
+                ..ChunkPolicy::default()
+            };
+            let txs = (6..11).map(cancel).collect();
+            tx_client.submit_all(txs, policy).await.unwrap();
+            assert_eq!(
+                sink.take(),
+                [
+                    rejected(),
+                    TxFailure::NotAttempted,
+                    TxFailure::NotAttempted,
+                    TxFailure::NotAttempted
+                ]
+            );
+        }
+
+        #[tokio::test]
+        async fn test_resubmit_from_file_signs_with_fresh_nonces() {
+            let path = temp_file("resubmit");
+            let sink = Arc::new(JsonLinesSink::new(&path).unwrap());
+            let (tx_client, mock) = client(sink);
+
+            let order = tx_client
+                .create_limit_order(0, 7, 1000, 300000, 0, false, None)
+                .await
+                .unwrap();
+            mock.push_response(
+                SEND_TX_PATH,
+                200,
+                r#"{"code":21701,"message":"invalid base amount"}"#,
+            );
+            tx_client.send_transaction(&order).await.unwrap();
+            mock.push_error(SEND_TX_PATH, LighterError::Timeout);
+            assert!(tx_client.send_signed(&cancel(1)).await.is_err());
+
+            let failed = read_failed_txs(&path).unwrap();
+            assert_eq!(failed.len(), 2);
+            assert_eq!(failed[0].tx_hash, order.get_tx_hash());
+
+            mock.push_response(
+                SEND_TX_BATCH_PATH,
+                200,
+                r#"{"code":200,"tx_hash":["0xnew"]}"#,
+            );
+            let report = tx_client
+                .resubmit_from_file(&path, ChunkPolicy::default())
+                .await
+                .unwrap();
+            assert!(report.is_success());
+            // The unconfirmed cancel is left out
+            assert_eq!(report.results.len(), 1);
+
+            let DecodedTx::CreateOrder(resigned) = report.results[0].tx.decode().unwrap() else {
+                panic!("expected a create order");
+            };
+            assert_eq!(resigned.nonce, order.nonce + 1);
+            assert_eq!(resigned.client_order_index, 7);
+            assert_ne!(report.results[0].tx.tx_hash, order.get_tx_hash());
+            assert_eq!(mock.requests_to(SEND_TX_BATCH_PATH).len(), 1);
+            std::fs::remove_file(&path).unwrap();
+        }
+    }
+}
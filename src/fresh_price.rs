@@ -0,0 +1,131 @@
+//! Prices stamped with when they were observed
+//!
+//! A price read from a book that stopped updating looks just like a current
+//! one. [`FreshPrice`] carries the monotonic time the price was observed, so
+//! the helpers that act on it can refuse one older than their
+//! `max_staleness` with [`LighterError::StalePrice`]. Ages are measured on
+//! [`Clock::now_instant`], never on wall time, so a clock step can't make a
+//! stale price look fresh.
+//!
+//! [`WsClient::mid_price`](crate::ws_client::WsClient::mid_price) and
+//! [`PositionManager::mark_price`](crate::positions::PositionManager::mark_price)
+//! produce them; [`TxClient::close_position_at`](crate::client::TxClient::close_position_at),
+//! trailing stops and the price band risk limit consume them.
+//!
+//! ```
+//! use lighter_rs::clock::{Clock, ManualClock};
+//! use lighter_rs::fresh_price::FreshPrice;
+//! use lighter_rs::Decimal;
+//! use std::time::Duration;
+//!
+//! let clock = ManualClock::at_ms(0);
+//! let mid = FreshPrice::observed(Decimal::new(302450, 2), &clock);
+//! assert!(mid.fresh(&clock, Duration::from_secs(2)).is_ok());
+//!
+//! clock.advance(Duration::from_secs(3));
+//! assert!(mid.fresh(&clock, Duration::from_secs(2)).is_err());
+//! ```
+
+use std::time::{Duration, Instant};
+
+use rust_decimal::Decimal;
+
+use crate::clock::Clock;
+use crate::errors::{LighterError, Result};
+
+/// A price and the monotonic time it was observed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FreshPrice {
+    pub value: Decimal,
+    /// When the price was observed, on the producing clock's
+    /// [`Clock::now_instant`]
+    pub observed_at: Instant,
+}
+
+impl FreshPrice {
+    pub fn new(value: Decimal, observed_at: Instant) -> Self {
+        Self { value, observed_at }
+    }
+
+    /// A price observed now on `clock`
+    pub fn observed(value: Decimal, clock: &dyn Clock) -> Self {
+        Self::new(value, clock.now_instant())
+    }
+
+    /// Time since the price was observed on `clock`
+    pub fn age(&self, clock: &dyn Clock) -> Duration {
+        clock
+            .now_instant()
+            .saturating_duration_since(self.observed_at)
+    }
+
+    /// The price, if it is no older than `max_staleness`
+    pub fn fresh(&self, clock: &dyn Clock, max_staleness: Duration) -> Result<Decimal> {
+        let age = self.age(clock);
+        if age > max_staleness {
+            return Err(LighterError::StalePrice { age, max_staleness });
+        }
+        Ok(self.value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+
+    #[test]
+    fn test_frozen_feed_goes_stale() {
+        let clock = ManualClock::at_ms(1_000);
+        let price = FreshPrice::observed(Decimal::new(3000, 0), &clock);
+        let max = Duration::from_millis(500);
+
+        clock.advance(Duration::from_millis(500));
+        assert_eq!(price.fresh(&clock, max).unwrap(), Decimal::new(3000, 0));
+
+        clock.advance(Duration::from_millis(1));
+        match price.fresh(&clock, max) {
+            Err(LighterError::StalePrice { age, max_staleness }) => {
+                assert_eq!(age, Duration::from_millis(501));
+                assert_eq!(max_staleness, max);
+            }
+            other => panic!("expected a stale price, got {other:?}"),
+        }
+    }
+
+    /// A clock whose wall time can be set back while monotonic time moves on
+    struct SteppedBack(ManualClock, std::sync::atomic::AtomicBool);
+
+    impl Clock for SteppedBack {
+        fn now_utc(&self) -> chrono::DateTime<chrono::Utc> {
+            if self.1.load(std::sync::atomic::Ordering::SeqCst) {
+                self.0.now_utc() - chrono::Duration::hours(1)
+            } else {
+                self.0.now_utc()
+            }
+        }
+
+        fn now_instant(&self) -> Instant {
+            self.0.now_instant()
+        }
+
+        fn sleep(&self, duration: Duration) -> futures_util::future::BoxFuture<'_, ()> {
+            self.0.sleep(duration)
+        }
+    }
+
+    #[test]
+    fn test_age_ignores_wall_time() {
+        let clock = SteppedBack(ManualClock::at_ms(1_000), Default::default());
+        let price = FreshPrice::observed(Decimal::ONE, &clock);
+        clock.0.advance(Duration::from_secs(10));
+        clock.1.store(true, std::sync::atomic::Ordering::SeqCst);
+        assert!(clock.now_ms() < 1_000);
+        assert_eq!(price.age(&clock), Duration::from_secs(10));
+        assert!(price.fresh(&clock, Duration::from_secs(5)).is_err());
+
+        // A price stamped after the clock was read is not negative in age
+        let later = FreshPrice::new(Decimal::ONE, clock.now_instant() + Duration::from_secs(1));
+        assert_eq!(later.age(&clock), Duration::ZERO);
+    }
+}
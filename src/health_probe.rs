@@ -0,0 +1,509 @@
+//! Periodic self-test of the whole signing and submission path
+//!
+//! A broken key, a stuck nonce or a dead connection usually shows up when
+//! the first real order of the day fails. A [`HealthProbe`] finds out first:
+//! every [`HealthProbeConfig::interval`] it signs a post-only bid and its
+//! cancel with consecutive nonces, sends both in one batch and times the
+//! round trip. The exchange applies a batch in order, so the bid is off the
+//! book as soon as it was placed; being post-only, it could never take
+//! liquidity anyway. Price it well below the market but inside the
+//! exchange's price band, or the exchange refuses it.
+//!
+//! Probe orders take their client order indexes from a namespace of their
+//! own, which must not overlap the client's
+//! [`client_order_namespace`](crate::client::TxClientBuilder::client_order_namespace),
+//! so they never collide with real orders. At most
+//! [`HealthProbeConfig::max_probes`] probes are sent per
+//! [`HealthProbeConfig::budget_window`], and none while the
+//! [`HealthProbe::circuit_breaker`] reports open or the exchange reports
+//! trading paused.
+//!
+//! Every result is passed to the [`HealthProbe::on_result`] hook, for a
+//! metrics sink, and summed up in [`HealthProbe::status`].
+//!
+//! ```no_run
+//! use lighter_rs::client::TxClient;
+//! use lighter_rs::health_probe::{HealthProbe, HealthProbeConfig};
+//! use lighter_rs::order_namespace::ClientOrderNamespace;
+//! use std::sync::Arc;
+//!
+//! # async fn example() -> lighter_rs::Result<()> {
+//! let tx_client = Arc::new(
+//!     TxClient::builder()
+//!         .api_url("https://mainnet.zklighter.elliot.ai")
+//!         .private_key("0x0102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f202122232425262728")
+//!         .client_order_namespace(ClientOrderNamespace::new(0, 1)?)
+//!         .build()?,
+//! );
+//! let config = HealthProbeConfig::new(0, 100, 250_000, ClientOrderNamespace::new(1, 1)?);
+//! let probe = Arc::new(HealthProbe::new(tx_client, config)?.on_result(|result| {
+//!     println!("probe: {:?}", result.outcome);
+//! }));
+//! tokio::spawn({
+//!     let probe = probe.clone();
+//!     async move { probe.run().await }
+//! });
+//! // ... later
+//! let healthy = probe.status().consecutive_failures == 0;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::client::TxClient;
+use crate::constants::*;
+use crate::errors::{LighterError, Result};
+use crate::order_namespace::{ClientOrderIndexes, ClientOrderNamespace};
+use crate::types::{CancelOrderTxReq, CreateOrderTxReq, SignedTx, TransactOpts};
+
+/// Default of [`HealthProbeConfig::interval`]
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Default of [`HealthProbeConfig::max_probes`]
+const DEFAULT_MAX_PROBES: u32 = 60;
+
+/// Default of [`HealthProbeConfig::budget_window`]
+const DEFAULT_BUDGET_WINDOW: Duration = Duration::from_secs(3600);
+
+/// Expiry of a probe order, should its cancel ever fail
+const PROBE_ORDER_EXPIRY: Duration = Duration::from_secs(600);
+
+/// What and how often a [`HealthProbe`] probes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HealthProbeConfig {
+    pub market_index: u8,
+    /// Size of the probe bid, in integer base units; at least the market's
+    /// minimum
+    pub base_amount: i64,
+    /// Integer price of the probe bid: far below the market, inside the
+    /// price band
+    pub price: u64,
+    /// Where the probe orders' client order indexes come from
+    pub namespace: ClientOrderNamespace,
+    /// Time between two probes of [`HealthProbe::run`]
+    pub interval: Duration,
+    /// Most probes sent per [`HealthProbeConfig::budget_window`]
+    pub max_probes: u32,
+    pub budget_window: Duration,
+}
+
+impl HealthProbeConfig {
+    pub fn new(
+        market_index: u8,
+        base_amount: i64,
+        price: u64,
+        namespace: ClientOrderNamespace,
+    ) -> Self {
+        Self {
+            market_index,
+            base_amount,
+            price,
+            namespace,
+            interval: DEFAULT_INTERVAL,
+            max_probes: DEFAULT_MAX_PROBES,
+            budget_window: DEFAULT_BUDGET_WINDOW,
+        }
+    }
+
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Send at most `max_probes` probes in any `window`
+    pub fn budget(mut self, max_probes: u32, window: Duration) -> Self {
+        self.max_probes = max_probes;
+        self.budget_window = window;
+        self
+    }
+}
+
+/// Why a probe wasn't sent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkipReason {
+    CircuitOpen,
+    BudgetExhausted,
+    /// The exchange reported trading paused on the probe's market
+    TradingPaused,
+}
+
+/// How one probe went
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProbeOutcome {
+    /// The bid and its cancel were both accepted
+    Healthy {
+        client_order_index: i64,
+        /// From signing the bid to the batch's response
+        round_trip: Duration,
+    },
+    /// Signing, submission or the exchange failed the probe
+    Failed {
+        client_order_index: i64,
+        error: String,
+        round_trip: Duration,
+    },
+    Skipped(SkipReason),
+}
+
+/// One probe and when it ran
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProbeResult {
+    pub at_ms: i64,
+    pub outcome: ProbeOutcome,
+}
+
+/// Summary of the probes so far
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProbeStatus {
+    pub last: Option<ProbeResult>,
+    /// When the last healthy probe ran
+    pub last_healthy_ms: Option<i64>,
+    /// Failed probes since the last healthy one; skipped probes don't count
+    pub consecutive_failures: u32,
+    /// Probes sent, healthy or not
+    pub probes_sent: u64,
+}
+
+impl ProbeStatus {
+    /// Whether the last probe sent was healthy
+    pub fn is_healthy(&self) -> bool {
+        self.probes_sent > 0 && self.consecutive_failures == 0
+    }
+}
+
+type ResultHook = Box<dyn Fn(&ProbeResult) + Send + Sync>;
+type CircuitBreaker = Box<dyn Fn() -> bool + Send + Sync>;
+
+/// Probes a [`TxClient`] with harmless transactions
+pub struct HealthProbe {
+    tx_client: Arc<TxClient>,
+    config: HealthProbeConfig,
+    indexes: ClientOrderIndexes,
+    on_result: Option<ResultHook>,
+    circuit_breaker: Option<CircuitBreaker>,
+    /// When the probes within the budget window were sent
+    sent_at_ms: Mutex<VecDeque<i64>>,
+    status: Mutex<ProbeStatus>,
+}
+
+impl HealthProbe {
+    /// Fails if the probe's namespace overlaps the client's
+    pub fn new(tx_client: Arc<TxClient>, config: HealthProbeConfig) -> Result<Self> {
+        let client_namespace = tx_client.client_order_namespace();
+        if config.namespace.overlaps(&client_namespace) {
+            return Err(LighterError::InvalidConfiguration(format!(
+                "The probe namespace {} of {} bits overlaps the client's namespace {} of {} bits",
+                config.namespace.id(),
+                config.namespace.bits(),
+                client_namespace.id(),
+                client_namespace.bits()
+            )));
+        }
+        let indexes = ClientOrderIndexes::starting_at(config.namespace, tx_client.clock().now_ms());
+        Ok(Self {
+            tx_client,
+            config,
+            indexes,
+            on_result: None,
+            circuit_breaker: None,
+            sent_at_ms: Mutex::default(),
+            status: Mutex::default(),
+        })
+    }
+
+    /// Call `hook` with every result, skipped probes included
+    pub fn on_result(mut self, hook: impl Fn(&ProbeResult) + Send + Sync + 'static) -> Self {
+        self.on_result = Some(Box::new(hook));
+        self
+    }
+
+    /// Skip probes while `is_open` returns true
+    pub fn circuit_breaker(mut self, is_open: impl Fn() -> bool + Send + Sync + 'static) -> Self {
+        self.circuit_breaker = Some(Box::new(is_open));
+        self
+    }
+
+    pub fn config(&self) -> &HealthProbeConfig {
+        &self.config
+    }
+
+    pub fn status(&self) -> ProbeStatus {
+        lock(&self.status).clone()
+    }
+
+    /// Probe now, unless the circuit breaker or the budget says otherwise
+    pub async fn probe(&self) -> ProbeResult {
+        let at_ms = self.tx_client.clock().now_ms();
+        let outcome = match self.skip_reason(at_ms) {
+            Some(reason) => ProbeOutcome::Skipped(reason),
+            None => self.send_probe().await,
+        };
+        let result = ProbeResult { at_ms, outcome };
+
+        {
+            let mut status = lock(&self.status);
+            match &result.outcome {
+                ProbeOutcome::Healthy { .. } => {
+                    status.probes_sent += 1;
+                    status.consecutive_failures = 0;
+                    status.last_healthy_ms = Some(at_ms);
+                }
+                ProbeOutcome::Failed { error, .. } => {
+                    status.probes_sent += 1;
+                    status.consecutive_failures += 1;
+                    tracing::warn!(error = %error, "Health probe failed");
+                }
+                ProbeOutcome::Skipped(reason) => {
+                    tracing::debug!(?reason, "Health probe skipped");
+                }
+            }
+            status.last = Some(result.clone());
+        }
+        if let Some(hook) = &self.on_result {
+            hook(&result);
+        }
+        result
+    }
+
+    /// Probe every [`HealthProbeConfig::interval`] until the task is dropped
+    pub async fn run(&self) {
+        let clock = self.tx_client.clock().clone();
+        loop {
+            self.probe().await;
+            clock.sleep(self.config.interval).await;
+        }
+    }
+
+    /// Why a probe at `now_ms` can't be sent; otherwise take it from the
+    /// budget
+    fn skip_reason(&self, now_ms: i64) -> Option<SkipReason> {
+        if self
+            .circuit_breaker
+            .as_ref()
+            .is_some_and(|is_open| is_open())
+        {
+            return Some(SkipReason::CircuitOpen);
+        }
+        let mut sent_at_ms = lock(&self.sent_at_ms);
+        let window_ms = self.config.budget_window.as_millis() as i64;
+        while sent_at_ms
+            .front()
+            .is_some_and(|&sent| now_ms - sent >= window_ms)
+        {
+            sent_at_ms.pop_front();
+        }
+        if sent_at_ms.len() >= self.config.max_probes as usize {
+            return Some(SkipReason::BudgetExhausted);
+        }
+        sent_at_ms.push_back(now_ms);
+        None
+    }
+
+    async fn send_probe(&self) -> ProbeOutcome {
+        let clock = self.tx_client.clock();
+        let started = clock.now_instant();
+        let client_order_index = self.indexes.next();
+        let result = self.sign_and_send(client_order_index).await;
+        let round_trip = clock.now_instant().saturating_duration_since(started);
+        match result {
+            Ok(()) => ProbeOutcome::Healthy {
+                client_order_index,
+                round_trip,
+            },
+            Err(LighterError::TradingPaused { .. }) => {
+                ProbeOutcome::Skipped(SkipReason::TradingPaused)
+            }
+            Err(e) => ProbeOutcome::Failed {
+                client_order_index,
+                error: e.to_string(),
+                round_trip,
+            },
+        }
+    }
+
+    /// Sign the bid and its cancel with consecutive nonces and send both
+    async fn sign_and_send(&self, client_order_index: i64) -> Result<()> {
+        let (opts, reservation) = self.tx_client.fill_opts_reserving(None, 2).await?;
+        let nonce = opts.nonce.unwrap();
+        let bid = self
+            .tx_client
+            .create_order(
+                &CreateOrderTxReq {
+                    market_index: self.config.market_index,
+                    client_order_index,
+                    base_amount: self.config.base_amount,
+                    price: self.config.price,
+                    is_ask: 0,
+                    order_type: ORDER_TYPE_LIMIT,
+                    time_in_force: TIME_IN_FORCE_POST_ONLY,
+                    reduce_only: 0,
+                    trigger_price: 0,
+                    order_expiry: self.tx_client.clock().now_ms()
+                        + PROBE_ORDER_EXPIRY.as_millis() as i64,
+                },
+                Some(opts.clone()),
+            )
+            .await?;
+        let cancel = self
+            .tx_client
+            .cancel_order(
+                &CancelOrderTxReq {
+                    market_index: self.config.market_index,
+                    index: client_order_index,
+                },
+                Some(TransactOpts {
+                    nonce: Some(nonce + 1),
+                    ..opts
+                }),
+            )
+            .await?;
+        reservation.keep();
+
+        let response = self
+            .tx_client
+            .send_batch(&[SignedTx::new(&bid)?, SignedTx::new(&cancel)?])
+            .await?;
+        if !response.is_success() {
+            return Err(LighterError::ApiError(format!(
+                "Probe rejected with code {}: {}",
+                response.code,
+                response.message.as_deref().unwrap_or_default()
+            )));
+        }
+        Ok(())
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::test_support::mock_builder;
+    use crate::transport::MockTransport;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    const BATCH_PATH: &str = "/api/v1/sendTxBatch";
+
+    fn client(mock: Arc<MockTransport>, clock: Arc<ManualClock>) -> Arc<TxClient> {
+        let tx_client = mock_builder(&mock)
+            .clock(clock)
+            .client_order_namespace(ClientOrderNamespace::new(0, 1).unwrap())
+            .build()
+            .unwrap();
+        tx_client.nonces().set(1, 0, 0);
+        Arc::new(tx_client)
+    }
+
+    #[tokio::test]
+    async fn test_probe_round_trip_budget_and_circuit_breaker() {
+        let mock = Arc::new(MockTransport::new());
+        let clock = Arc::new(ManualClock::at_ms(1_700_000_000_000));
+        let tx_client = client(mock.clone(), clock.clone());
+        let namespace = ClientOrderNamespace::new(1, 1).unwrap();
+
+        // The probe's indexes must be its own
+        assert!(matches!(
+            HealthProbe::new(
+                tx_client.clone(),
+                HealthProbeConfig::new(0, 100, 1000, ClientOrderNamespace::ALL)
+            ),
+            Err(LighterError::InvalidConfiguration(_))
+        ));
+
+        let open = Arc::new(AtomicBool::new(false));
+        let reported = Arc::new(Mutex::new(Vec::new()));
+        let probe = HealthProbe::new(
+            tx_client.clone(),
+            HealthProbeConfig::new(0, 100, 1000, namespace).budget(2, Duration::from_secs(60)),
+        )
+        .unwrap()
+        .circuit_breaker({
+            let open = open.clone();
+            move || open.load(Ordering::SeqCst)
+        })
+        .on_result({
+            let reported = reported.clone();
+            move |result| reported.lock().unwrap().push(result.outcome.clone())
+        });
+
+        mock.push_response(BATCH_PATH, 200, r#"{"code":200,"tx_hash":["0x1","0x2"]}"#);
+        let result = probe.probe().await;
+        let ProbeOutcome::Healthy {
+            client_order_index, ..
+        } = result.outcome
+        else {
+            panic!("expected a healthy probe, got {result:?}");
+        };
+        assert!(namespace.contains(client_order_index));
+
+        // A post-only bid then its cancel, with consecutive nonces
+        let batch = mock.requests_to(BATCH_PATH);
+        let body: Vec<(String, String)> = serde_urlencoded::from_bytes(&batch[0].body).unwrap();
+        let field = |key: &str| &body.iter().find(|(k, _)| k == key).unwrap().1;
+        assert_eq!(
+            field("tx_types"),
+            &format!("[{TX_TYPE_L2_CREATE_ORDER},{TX_TYPE_L2_CANCEL_ORDER}]")
+        );
+        let tx_infos: Vec<serde_json::Value> =
+            serde_json::from_str::<Vec<String>>(field("tx_infos"))
+                .unwrap()
+                .iter()
+                .map(|info| serde_json::from_str(info).unwrap())
+                .collect();
+        assert_eq!(tx_infos[0]["TimeInForce"], TIME_IN_FORCE_POST_ONLY);
+        assert_eq!(tx_infos[0]["ClientOrderIndex"], client_order_index);
+        assert_eq!(tx_infos[1]["Index"], client_order_index);
+        assert_eq!(
+            (tx_infos[0]["Nonce"].as_i64(), tx_infos[1]["Nonce"].as_i64()),
+            (Some(0), Some(1))
+        );
+
+        // Rejected by the exchange
+        mock.push_response(
+            BATCH_PATH,
+            200,
+            r#"{"code":21104,"message":"invalid nonce"}"#,
+        );
+        assert!(matches!(
+            probe.probe().await.outcome,
+            ProbeOutcome::Failed { .. }
+        ));
+        let status = probe.status();
+        assert_eq!((status.probes_sent, status.consecutive_failures), (2, 1));
+        assert!(!status.is_healthy());
+        assert_eq!(status.last_healthy_ms, Some(1_700_000_000_000));
+
+        // The budget of two per minute is spent
+        assert_eq!(
+            probe.probe().await.outcome,
+            ProbeOutcome::Skipped(SkipReason::BudgetExhausted)
+        );
+        clock.advance(Duration::from_secs(60));
+
+        // Nothing is sent while the breaker is open, and no budget is used
+        open.store(true, Ordering::SeqCst);
+        assert_eq!(
+            probe.probe().await.outcome,
+            ProbeOutcome::Skipped(SkipReason::CircuitOpen)
+        );
+        open.store(false, Ordering::SeqCst);
+        tx_client.nonces().set(1, 0, 2);
+        mock.push_response(BATCH_PATH, 200, r#"{"code":200,"tx_hash":["0x3","0x4"]}"#);
+        assert!(matches!(
+            probe.probe().await.outcome,
+            ProbeOutcome::Healthy { .. }
+        ));
+        assert!(probe.status().is_healthy());
+        assert_eq!(mock.requests_to(BATCH_PATH).len(), 3);
+        assert_eq!(reported.lock().unwrap().len(), 5);
+    }
+}
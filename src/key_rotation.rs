@@ -0,0 +1,321 @@
+//! Rotating an API key to a new public key from the SDK
+//!
+//! [`TxClient::change_api_key`] signs and sends a change public key
+//! transaction. The exchange requires two signatures on it:
+//!
+//! - the new key's, over the transaction hash, proving it is held; pass the
+//!   new key's [`PoseidonKeyManager`], or pass raw public key bytes from a
+//!   client built with the new private key
+//! - the account's L1 wallet's, over
+//!   [`L2ChangePubKeyTxInfo::l1_signature_body`](crate::types::L2ChangePubKeyTxInfo::l1_signature_body),
+//!   produced by the `l1_signer` callback as 0x-prefixed hex
+//!
+//! The rotation has finished once the apikeys endpoint lists the new key;
+//! [`TxClient::wait_for_api_key`] polls it until then. Later transactions
+//! of that API key index must be signed with the new key.
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use lighter_rs::client::TxClient;
+//! use lighter_rs::deadline::Deadline;
+//! use lighter_rs::signer::{KeyManager, PoseidonKeyManager};
+//!
+//! # fn sign_with_wallet(message: &str) -> lighter_rs::Result<String> { unimplemented!() }
+//! # async fn example(tx_client: TxClient, new_private_key: &str) -> lighter_rs::Result<()> {
+//! let new_key = PoseidonKeyManager::from_hex(new_private_key)?;
+//! let response = tx_client
+//!     .change_api_key(&new_key, sign_with_wallet, None)
+//!     .await?;
+//! assert!(response.is_success());
+//! tx_client
+//!     .wait_for_api_key(
+//!         tx_client.api_key_index(),
+//!         new_key.pub_key(),
+//!         Duration::from_secs(1),
+//!         Some(Deadline::after(Duration::from_secs(60))),
+//!     )
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::client::{TxClient, TxResponse};
+use crate::constants::PUBLIC_KEY_LENGTH;
+use crate::deadline::{Deadline, Progress};
+use crate::errors::Result;
+use crate::signer::{KeyManager, PoseidonKeyManager};
+use crate::types::{ChangePubKeyTxReq, TransactOpts};
+
+/// The key an API key is rotated to
+pub enum NewApiKey<'a> {
+    /// Public key bytes; the transaction is signed with the client's own
+    /// key, which must be the new key
+    PubKey(Vec<u8>),
+    /// The new key, which signs the transaction
+    KeyManager(&'a PoseidonKeyManager),
+}
+
+impl NewApiKey<'_> {
+    /// The new public key
+    pub fn pub_key(&self) -> &[u8] {
+        match self {
+            NewApiKey::PubKey(pub_key) => pub_key,
+            NewApiKey::KeyManager(key_manager) => key_manager.pub_key(),
+        }
+    }
+}
+
+impl From<Vec<u8>> for NewApiKey<'_> {
+    fn from(pub_key: Vec<u8>) -> Self {
+        NewApiKey::PubKey(pub_key)
+    }
+}
+
+impl From<&[u8]> for NewApiKey<'_> {
+    fn from(pub_key: &[u8]) -> Self {
+        NewApiKey::PubKey(pub_key.to_vec())
+    }
+}
+
+impl From<[u8; PUBLIC_KEY_LENGTH]> for NewApiKey<'_> {
+    fn from(pub_key: [u8; PUBLIC_KEY_LENGTH]) -> Self {
+        NewApiKey::PubKey(pub_key.to_vec())
+    }
+}
+
+impl<'a> From<&'a PoseidonKeyManager> for NewApiKey<'a> {
+    fn from(key_manager: &'a PoseidonKeyManager) -> Self {
+        NewApiKey::KeyManager(key_manager)
+    }
+}
+
+impl TxClient {
+    /// Sign and send a change public key transaction rotating an API key
+    ///
+    /// The API key index rotated is that of `opts`, by default the client's.
+    /// `l1_signer` is given the message the account's L1 wallet must sign
+    /// and returns the signature; when it fails nothing is sent and the
+    /// nonce is given back for the next transaction.
+    pub async fn change_api_key<'a>(
+        &self,
+        new_key: impl Into<NewApiKey<'a>>,
+        l1_signer: impl FnOnce(&str) -> Result<String>,
+        opts: Option<TransactOpts>,
+    ) -> Result<TxResponse> {
+        let new_key = new_key.into();
+        let req = ChangePubKeyTxReq {
+            pub_key: new_key.pub_key().to_vec(),
+        };
+        let key_manager = match new_key {
+            NewApiKey::PubKey(_) => None,
+            NewApiKey::KeyManager(key_manager) => Some(Arc::new(PoseidonKeyManager::new(
+                &key_manager.prv_key_bytes(),
+            )?)),
+        };
+        let (mut tx_info, reservation) = self
+            .change_pub_key_reserving(&req, key_manager, opts)
+            .await?;
+        tx_info.l1_sig = Some(l1_signer(&tx_info.l1_signature_body())?);
+        reservation.keep();
+        self.send_transaction(&tx_info).await
+    }
+
+    /// Poll the apikeys endpoint every `poll_interval` until `pub_key` is
+    /// registered at `api_key_index` of the client's account
+    ///
+    /// Without a deadline this waits indefinitely. Completed steps are the
+    /// lookups made, `poll 1`, `poll 2` and so on.
+    pub async fn wait_for_api_key(
+        &self,
+        api_key_index: u8,
+        pub_key: &[u8],
+        poll_interval: Duration,
+        deadline: Option<Deadline>,
+    ) -> Result<()> {
+        let http = self.http_client()?;
+        let expected = hex::encode(pub_key);
+        let mut progress = Progress::new(deadline);
+
+        let mut poll = 0;
+        loop {
+            poll += 1;
+            let registered = progress
+                .step(
+                    format!("poll {poll}"),
+                    http.get_api_key_public_key(self.account_index(), api_key_index),
+                )
+                .await?;
+            let active = registered.is_some_and(|registered| {
+                registered
+                    .trim_start_matches("0x")
+                    .eq_ignore_ascii_case(&expected)
+            });
+            if active {
+                return Ok(());
+            }
+            progress.pause(poll_interval).await?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::TX_TYPE_L2_CHANGE_PUB_KEY;
+    use crate::errors::LighterError;
+    use crate::test_support::mock_builder;
+    use crate::transport::{HttpRequest, HttpResponse, MockTransport};
+    use crate::types::{L2ChangePubKeyTxInfo, TxInfo};
+
+    const NEW_KEY: &str =
+        "0x2827262524232221201f1e1d1c1b1a191817161514131211100f0e0d0c0b0a090807060504030201";
+    const SEND_TX_PATH: &str = "/api/v1/sendTx";
+    const API_KEYS_PATH: &str = "/api/v1/apikeys";
+
+    fn mock_client() -> (TxClient, Arc<MockTransport>) {
+        let mock = Arc::new(MockTransport::new());
+        mock.set_handler("/api/v1/nextNonce", |_| {
+            Ok(HttpResponse::new(200, r#"{"code":200,"nonce":3}"#))
+        });
+        mock.set_handler(SEND_TX_PATH, |_| {
+            Ok(HttpResponse::new(200, r#"{"code":200,"tx_hash":"0x1"}"#))
+        });
+        let tx_client = mock_builder(&mock)
+            .account_index(7)
+            .api_key_index(2)
+            .build()
+            .unwrap();
+        (tx_client, mock)
+    }
+
+    fn sent_tx(request: &HttpRequest) -> L2ChangePubKeyTxInfo {
+        let fields: Vec<(String, String)> = serde_urlencoded::from_bytes(&request.body).unwrap();
+        assert_eq!(fields[0].1, TX_TYPE_L2_CHANGE_PUB_KEY.to_string());
+        serde_json::from_str(&fields[1].1).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_change_api_key_is_signed_by_the_new_key_and_the_l1_wallet() {
+        let (tx_client, mock) = mock_client();
+        let new_key = PoseidonKeyManager::from_hex(NEW_KEY).unwrap();
+
+        let mut signed_body = String::new();
+        let response = tx_client
+            .change_api_key(
+                &new_key,
+                |body| {
+                    signed_body = body.to_string();
+                    Ok("0xl1sig".to_string())
+                },
+                None,
+            )
+            .await
+            .unwrap();
+        assert!(response.is_success());
+
+        let tx = sent_tx(&mock.requests_to(SEND_TX_PATH)[0]);
+        assert_eq!(tx.pub_key, new_key.pub_key());
+        assert_eq!((tx.account_index, tx.api_key_index), (7, 2));
+        assert_eq!(tx.l1_sig.as_deref(), Some("0xl1sig"));
+        assert_eq!(signed_body, tx.l1_signature_body());
+        let hash = tx.hash(304).unwrap();
+        assert!(new_key.verify(&hash, tx.sig.as_ref().unwrap()).unwrap());
+
+        // Raw bytes are signed with the client's own key
+        tx_client
+            .change_api_key(
+                tx_client.key_manager().pub_key(),
+                |_| Ok("0x".to_string()),
+                None,
+            )
+            .await
+            .unwrap();
+        let tx = sent_tx(&mock.requests_to(SEND_TX_PATH)[1]);
+        let hash = tx.hash(304).unwrap();
+        assert!(tx_client
+            .key_manager()
+            .verify(&hash, tx.sig.as_ref().unwrap())
+            .unwrap());
+
+        // Nothing is sent when the wallet refuses
+        let refused = tx_client
+            .change_api_key(
+                &new_key,
+                |_| Err(LighterError::CryptoError("rejected".to_string())),
+                None,
+            )
+            .await;
+        assert!(matches!(refused, Err(LighterError::CryptoError(_))));
+        assert_eq!(mock.requests_to(SEND_TX_PATH).len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_a_refused_l1_signature_gives_the_nonce_back() {
+        let (tx_client, mock) = mock_client();
+        let new_key = PoseidonKeyManager::from_hex(NEW_KEY).unwrap();
+        tx_client.nonces().set(7, 2, 10);
+
+        let refused = tx_client
+            .change_api_key(
+                &new_key,
+                |_| Err(LighterError::CryptoError("rejected".to_string())),
+                None,
+            )
+            .await;
+        assert!(matches!(refused, Err(LighterError::CryptoError(_))));
+        assert_eq!(tx_client.nonces().peek(7, 2), Some(10));
+
+        tx_client
+            .change_api_key(&new_key, |_| Ok("0xl1sig".to_string()), None)
+            .await
+            .unwrap();
+        let tx = sent_tx(&mock.requests_to(SEND_TX_PATH)[0]);
+        assert_eq!(tx.nonce, 10);
+        assert_eq!(tx_client.nonces().peek(7, 2), Some(11));
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_api_key_polls_until_the_key_is_registered() {
+        let (tx_client, mock) = mock_client();
+        let new_key = PoseidonKeyManager::from_hex(NEW_KEY).unwrap();
+        let old = hex::encode(tx_client.key_manager().pub_key());
+        let new = hex::encode(new_key.pub_key());
+        for public_key in [old.as_str(), old.as_str(), new.as_str()] {
+            mock.push_response(
+                API_KEYS_PATH,
+                200,
+                format!(r#"{{"code":200,"api_keys":[{{"api_key_index":2,"public_key":"0x{public_key}"}}]}}"#),
+            );
+        }
+
+        tx_client
+            .wait_for_api_key(2, new_key.pub_key(), Duration::from_millis(1), None)
+            .await
+            .unwrap();
+        let lookups = mock.requests_to(API_KEYS_PATH);
+        assert_eq!(lookups.len(), 3);
+        assert!(lookups[0].url.ends_with("?account_index=7&api_key_index=2"));
+
+        // A rotation that never lands runs into the deadline
+        mock.set_handler(API_KEYS_PATH, move |_| {
+            Ok(HttpResponse::new(
+                200,
+                format!(
+                    r#"{{"code":200,"api_keys":[{{"api_key_index":2,"public_key":"{old}"}}]}}"#
+                ),
+            ))
+        });
+        let result = tx_client
+            .wait_for_api_key(
+                2,
+                new_key.pub_key(),
+                Duration::from_millis(5),
+                Some(Deadline::after(Duration::from_millis(30))),
+            )
+            .await;
+        assert!(matches!(result, Err(LighterError::DeadlineExceeded { .. })));
+    }
+}
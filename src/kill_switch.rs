@@ -0,0 +1,630 @@
+//! Emergency stop: cancel every order and flatten every position
+//!
+//! [`TxClient::kill_switch`] runs three steps, each retried up to
+//! [`KillSwitchConfig::max_attempts`] times:
+//!
+//! 1. cancel all open orders
+//! 2. fetch the account's positions
+//! 3. send a reduce-only market order for each open position
+//!
+//! A step that keeps failing doesn't stop the ones after it, and one market
+//! that can't be flattened doesn't stop the others. The returned
+//! [`KillSwitchReport`] records what happened at every step.
+//! [`KillSwitchConfig::deadline`] bounds the whole run on top of the
+//! per-market timeout.
+//!
+//! ```no_run
+//! use lighter_rs::client::TxClient;
+//! use lighter_rs::kill_switch::KillSwitchConfig;
+//!
+//! # async fn example(tx_client: TxClient) {
+//! let report = tx_client.kill_switch(&KillSwitchConfig::default()).await;
+//! for market in report.open_markets() {
+//!     eprintln!("market {} still open: {:?}", market.market_index, market.outcome);
+//! }
+//! # }
+//! ```
+
+use std::future::Future;
+use std::time::Duration;
+
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+
+use crate::client::{AccountPosition, TxClient, TxResponse};
+use crate::constants::*;
+use crate::deadline::Deadline;
+use crate::errors::{LighterError, Result};
+use crate::types::CancelAllOrdersTxReq;
+
+/// Kill switch settings
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KillSwitchConfig {
+    /// Worst price of each flattening order, in basis points from the market's
+    /// last trade price
+    pub max_slippage_bps: Decimal,
+    /// Time allowed for flattening one market, retries included
+    pub per_market_timeout: Duration,
+    /// Fetch positions and plan the flattening orders without sending anything
+    pub dry_run: bool,
+    /// Attempts per step before giving up on it
+    pub max_attempts: u32,
+    /// Pause between attempts
+    pub retry_delay: Duration,
+    /// Bound on the whole kill switch; steps still pending when it passes
+    /// are recorded as failed
+    pub deadline: Option<Deadline>,
+}
+
+impl Default for KillSwitchConfig {
+    fn default() -> Self {
+        Self {
+            max_slippage_bps: Decimal::new(100, 0),
+            per_market_timeout: Duration::from_secs(10),
+            dry_run: false,
+            max_attempts: 3,
+            retry_delay: Duration::from_millis(250),
+            deadline: None,
+        }
+    }
+}
+
+/// Result of one kill switch step
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StepOutcome {
+    /// The API accepted the transaction, or answered the query
+    Succeeded {
+        attempts: u32,
+        tx_hash: Option<String>,
+    },
+    /// Every attempt failed; `error` is the last failure
+    Failed { attempts: u32, error: String },
+    /// Nothing was sent because of [`KillSwitchConfig::dry_run`]
+    DryRun,
+}
+
+impl StepOutcome {
+    pub fn is_failed(&self) -> bool {
+        matches!(self, StepOutcome::Failed { .. })
+    }
+}
+
+/// Reduce-only market order that closes a position
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FlattenOrder {
+    pub is_ask: u8,
+    pub base_amount: i64,
+    /// Worst acceptable price
+    pub price: u64,
+}
+
+/// What happened to one open position
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MarketReport {
+    pub market_index: u8,
+    /// Signed position before flattening: positive when long
+    pub size: Decimal,
+    /// The flattening order, once it could be worked out
+    pub order: Option<FlattenOrder>,
+    pub outcome: StepOutcome,
+}
+
+/// Everything the kill switch did
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KillSwitchReport {
+    pub cancel_all: StepOutcome,
+    /// Fetching positions; when this failed no market was flattened
+    pub positions: StepOutcome,
+    /// One entry per open position, in the order the API listed them
+    pub markets: Vec<MarketReport>,
+}
+
+impl KillSwitchReport {
+    /// Whether no step failed
+    ///
+    /// Accepted flattening orders are market orders, so a thin book can still
+    /// leave part of a position open; check positions again to be sure.
+    pub fn is_complete(&self) -> bool {
+        !self.cancel_all.is_failed()
+            && !self.positions.is_failed()
+            && self.open_markets().count() == 0
+    }
+
+    /// Markets whose position could not be flattened
+    pub fn open_markets(&self) -> impl Iterator<Item = &MarketReport> {
+        self.markets
+            .iter()
+            .filter(|market| market.outcome.is_failed())
+    }
+}
+
+impl TxClient {
+    /// Cancel every order and flatten every position of this client's account
+    ///
+    /// Never returns early: failures are recorded in the report and the
+    /// remaining steps still run.
+    pub async fn kill_switch(&self, config: &KillSwitchConfig) -> KillSwitchReport {
+        let cancel_all = if config.dry_run {
+            StepOutcome::DryRun
+        } else {
+            let mut attempts = 0;
+            let result = before_deadline(
+                config,
+                retry(config, self, &mut attempts, || async {
+                    let req = CancelAllOrdersTxReq {
+                        time_in_force: CANCEL_ALL_IMMEDIATE,
+                        time: 0,
+                    };
+                    let tx = self.cancel_all_orders(&req, None).await?;
+                    accepted(self.send_transaction(&tx).await?)
+                }),
+            )
+            .await;
+            outcome(attempts, result)
+        };
+        if let StepOutcome::Failed { error, .. } = &cancel_all {
+            tracing::error!(%error, "Kill switch could not cancel all orders");
+        }
+
+        let mut attempts = 0;
+        let positions = before_deadline(
+            config,
+            retry(config, self, &mut attempts, || async {
+                let client = self.http().ok_or_else(|| {
+                    LighterError::InvalidConfiguration("HTTPClient is not configured".to_string())
+                })?;
+                client.get_account_positions(self.account_index()).await
+            }),
+        )
+        .await;
+        let (positions, positions_outcome) = match positions {
+            Ok(positions) => (
+                positions,
+                StepOutcome::Succeeded {
+                    attempts,
+                    tx_hash: None,
+                },
+            ),
+            Err(e) => {
+                tracing::error!(error = %e, "Kill switch could not fetch positions");
+                (Vec::new(), outcome(attempts, Err(e)))
+            }
+        };
+
+        let mut markets = Vec::new();
+        for position in positions.iter().filter(|p| !p.size().is_zero()) {
+            let report = self.flatten(config, position).await;
+            if let StepOutcome::Failed { error, .. } = &report.outcome {
+                tracing::error!(market_index = report.market_index, %error, "Kill switch could not flatten market");
+            }
+            markets.push(report);
+        }
+
+        KillSwitchReport {
+            cancel_all,
+            positions: positions_outcome,
+            markets,
+        }
+    }
+
+    /// Send a reduce-only market order closing `position`
+    async fn flatten(&self, config: &KillSwitchConfig, position: &AccountPosition) -> MarketReport {
+        let mut order = None;
+        let mut attempts = 0;
+        let flattening = async {
+            let details = retry(config, self, &mut attempts, || async {
+                let client = self.http().ok_or_else(|| {
+                    LighterError::InvalidConfiguration("HTTPClient is not configured".to_string())
+                })?;
+                client.get_market_details(position.market_id).await
+            })
+            .await?;
+            let planned = flatten_order(
+                config.max_slippage_bps,
+                position,
+                details.last_trade_price,
+                details.price_decimals,
+                details.size_decimals,
+            )?;
+            order = Some(planned);
+            if config.dry_run {
+                return Ok(None);
+            }
+
+            attempts = 0;
+            retry(config, self, &mut attempts, || async {
+                let client_order_index = self.next_client_order_index();
+                let tx = self
+                    .create_market_order(
+                        position.market_id,
+                        client_order_index,
+                        planned.base_amount,
+                        planned.price,
+                        planned.is_ask,
+                        true,
+                        None,
+                    )
+                    .await?;
+                accepted(self.send_transaction(&tx).await?)
+            })
+            .await
+        };
+        let timeout = config
+            .deadline
+            .map_or(config.per_market_timeout, |deadline| {
+                deadline.remaining().min(config.per_market_timeout)
+            });
+        let outcome = match tokio::time::timeout(timeout, flattening).await {
+            Ok(Ok(None)) if config.dry_run => StepOutcome::DryRun,
+            Ok(result) => outcome(attempts, result),
+            Err(_) => StepOutcome::Failed {
+                attempts,
+                error: format!("Timed out after {timeout:?}"),
+            },
+        };
+        MarketReport {
+            market_index: position.market_id,
+            size: position.size(),
+            order,
+            outcome,
+        }
+    }
+}
+
+/// The order closing `position`, priced `max_slippage_bps` through the
+/// reference price
+pub(crate) fn flatten_order(
+    max_slippage_bps: Decimal,
+    position: &AccountPosition,
+    last_trade_price: Decimal,
+    price_decimals: u32,
+    size_decimals: u32,
+) -> Result<FlattenOrder> {
+    let reference = if last_trade_price.is_zero() {
+        position.avg_entry_price
+    } else {
+        last_trade_price
+    };
+    if reference <= Decimal::ZERO {
+        return Err(LighterError::ValidationError(format!(
+            "No reference price for market {}",
+            position.market_id
+        )));
+    }
+
+    let size = position.size();
+    let is_ask = size > Decimal::ZERO;
+    let slippage = max_slippage_bps / Decimal::from(10_000);
+    let price_scale = Decimal::from(10u64.pow(price_decimals));
+    let price = if is_ask {
+        (reference * (Decimal::ONE - slippage) * price_scale).floor()
+    } else {
+        (reference * (Decimal::ONE + slippage) * price_scale).ceil()
+    };
+    let price = price
+        .to_u64()
+        .map(|price| price.max(MIN_ORDER_PRICE))
+        .filter(|price| *price <= MAX_ORDER_PRICE)
+        .ok_or_else(|| LighterError::ValidationError(format!("Price {price} is out of range")))?;
+
+    let base_amount = (size.abs() * Decimal::from(10u64.pow(size_decimals)))
+        .round()
+        .to_i64()
+        .filter(|amount| *amount >= MIN_ORDER_BASE_AMOUNT)
+        .ok_or_else(|| {
+            LighterError::ValidationError(format!(
+                "Position {size} in market {} is not a valid order size",
+                position.market_id
+            ))
+        })?;
+
+    Ok(FlattenOrder {
+        is_ask: u8::from(is_ask),
+        base_amount,
+        price,
+    })
+}
+
+/// Run `step`, failing it if [`KillSwitchConfig::deadline`] passes first
+async fn before_deadline<T>(
+    config: &KillSwitchConfig,
+    step: impl Future<Output = Result<T>>,
+) -> Result<T> {
+    match config.deadline {
+        Some(deadline) => tokio::time::timeout_at(deadline.instant(), step)
+            .await
+            .unwrap_or_else(|_| {
+                Err(LighterError::DeadlineExceeded {
+                    completed_steps: Vec::new(),
+                })
+            }),
+        None => step.await,
+    }
+}
+
+/// Run `attempt` until it succeeds or `max_attempts` runs out, counting
+/// attempts in `attempts` and retries in the client's stats
+async fn retry<T, F, Fut>(
+    config: &KillSwitchConfig,
+    tx_client: &TxClient,
+    attempts: &mut u32,
+    mut attempt: F,
+) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    loop {
+        *attempts += 1;
+        match attempt().await {
+            Ok(value) => return Ok(value),
+            Err(e) if *attempts >= config.max_attempts.max(1) => return Err(e),
+            Err(e) => {
+                tracing::warn!(attempt = *attempts, error = %e, "Kill switch step failed, retrying");
+                tx_client.count_retries(1);
+                tx_client.clock().sleep(config.retry_delay).await;
+            }
+        }
+    }
+}
+
+/// The transaction hash of an accepted transaction, or the rejection as an error
+fn accepted(response: TxResponse) -> Result<Option<String>> {
+    crate::composite::accepted(response).map(|response| response.tx_hash)
+}
+
+fn outcome(attempts: u32, result: Result<Option<String>>) -> StepOutcome {
+    match result {
+        Ok(tx_hash) => StepOutcome::Succeeded { attempts, tx_hash },
+        Err(e) => StepOutcome::Failed {
+            attempts,
+            error: e.to_string(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::mock_builder;
+    use crate::transport::{HttpRequest, HttpResponse, MockTransport};
+    use serde_json::{json, Value};
+    use std::sync::Arc;
+
+    const SEND_TX_PATH: &str = "/api/v1/sendTx";
+    const ACCOUNT_PATH: &str = "/api/v1/account";
+    const DETAILS_PATH: &str = "/api/v1/orderBookDetails";
+
+    fn config() -> KillSwitchConfig {
+        KillSwitchConfig {
+            retry_delay: Duration::ZERO,
+            ..KillSwitchConfig::default()
+        }
+    }
+
+    /// Long 0.5 in market 0, short 1.2 in market 1, flat in market 2
+    fn setup() -> (TxClient, Arc<MockTransport>) {
+        let mock = Arc::new(MockTransport::new());
+        mock.set_handler(ACCOUNT_PATH, |_| {
+            let positions = json!([
+                {"market_id": 0, "sign": 1, "position": "0.5000", "avg_entry_price": "2900.00"},
+                {"market_id": 1, "sign": -1, "position": "1.20", "avg_entry_price": "150.000"},
+                {"market_id": 2, "sign": 1, "position": "0", "avg_entry_price": "0"},
+            ]);
+            Ok(HttpResponse::new(
+                200,
+                json!({"code": 200, "accounts": [{"positions": positions}]}).to_string(),
+            ))
+        });
+        mock.set_handler(DETAILS_PATH, |request| {
+            let details = if market_id(request) == 0 {
+                json!({"market_id": 0, "size_decimals": 4, "price_decimals": 2, "last_trade_price": 3000.0})
+            } else {
+                json!({"market_id": 1, "size_decimals": 2, "price_decimals": 3, "last_trade_price": "160.000"})
+            };
+            Ok(HttpResponse::new(
+                200,
+                json!({"code": 200, "order_book_details": [details]}).to_string(),
+            ))
+        });
+        mock.set_handler(SEND_TX_PATH, |_| {
+            Ok(HttpResponse::new(200, r#"{"code":200,"tx_hash":"0xabc"}"#))
+        });
+
+        let tx_client = mock_builder(&mock).build().unwrap();
+        tx_client.nonces().set(1, 0, 0);
+        (tx_client, mock)
+    }
+
+    fn market_id(request: &HttpRequest) -> u8 {
+        request.url.rsplit('=').next().unwrap().parse().unwrap()
+    }
+
+    /// Transaction types and infos sent so far
+    fn sent(mock: &MockTransport) -> Vec<(u8, Value)> {
+        mock.requests_to(SEND_TX_PATH)
+            .iter()
+            .map(|request| {
+                let form: Vec<(String, String)> =
+                    serde_urlencoded::from_bytes(&request.body).unwrap();
+                (
+                    form[0].1.parse().unwrap(),
+                    serde_json::from_str(&form[1].1).unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_cancels_and_flattens_every_position() {
+        let (tx_client, mock) = setup();
+        let report = tx_client.kill_switch(&config()).await;
+
+        assert!(report.is_complete());
+        assert!(matches!(
+            report.cancel_all,
+            StepOutcome::Succeeded { attempts: 1, .. }
+        ));
+        assert_eq!(report.markets.len(), 2);
+        // Long sells 1% under the last trade, short buys 1% over it
+        assert_eq!(
+            report.markets[0].order,
+            Some(FlattenOrder {
+                is_ask: 1,
+                base_amount: 5000,
+                price: 297000
+            })
+        );
+        assert_eq!(report.markets[1].size, Decimal::new(-120, 2));
+        assert_eq!(
+            report.markets[1].order,
+            Some(FlattenOrder {
+                is_ask: 0,
+                base_amount: 120,
+                price: 161600
+            })
+        );
+
+        let sent = sent(&mock);
+        assert_eq!(sent.len(), 3);
+        assert_eq!(sent[0].0, TX_TYPE_L2_CANCEL_ALL_ORDERS);
+        for (tx_type, order) in &sent[1..] {
+            assert_eq!(*tx_type, TX_TYPE_L2_CREATE_ORDER);
+            assert_eq!(order["Type"], ORDER_TYPE_MARKET);
+            assert_eq!(order["ReduceOnly"], 1);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failing_market_does_not_stop_the_others() {
+        let (tx_client, mock) = setup();
+        mock.set_handler(DETAILS_PATH, |request| {
+            if market_id(request) == 0 {
+                return Ok(HttpResponse::new(503, "unavailable"));
+            }
+            Ok(HttpResponse::new(
+                200,
+                r#"{"order_book_details":[{"market_id":1,"size_decimals":2,"price_decimals":3,"last_trade_price":"160.000"}]}"#,
+            ))
+        });
+
+        let report = tx_client.kill_switch(&config()).await;
+        assert!(!report.is_complete());
+        let open: Vec<_> = report.open_markets().collect();
+        assert_eq!(open.len(), 1);
+        assert_eq!(open[0].market_index, 0);
+        assert_eq!(open[0].order, None);
+        assert!(matches!(
+            &open[0].outcome,
+            StepOutcome::Failed { attempts: 3, error } if error.contains("503")
+        ));
+        assert!(matches!(
+            report.markets[1].outcome,
+            StepOutcome::Succeeded { .. }
+        ));
+        assert_eq!(sent(&mock).len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_retries_rejected_transactions() {
+        let (tx_client, mock) = setup();
+        mock.push_response(SEND_TX_PATH, 200, r#"{"code":21120,"message":"busy"}"#);
+        mock.push_response(SEND_TX_PATH, 200, r#"{"code":200,"tx_hash":"0x1"}"#);
+        for _ in 0..3 {
+            mock.push_response(
+                SEND_TX_PATH,
+                200,
+                r#"{"code":21701,"message":"invalid base amount"}"#,
+            );
+        }
+
+        let report = tx_client.kill_switch(&config()).await;
+        assert_eq!(
+            report.cancel_all,
+            StepOutcome::Succeeded {
+                attempts: 2,
+                tx_hash: Some("0x1".to_string())
+            }
+        );
+        assert!(matches!(
+            &report.markets[0].outcome,
+            StepOutcome::Failed { attempts: 3, error } if error.contains("21701")
+        ));
+        assert!(matches!(
+            report.markets[1].outcome,
+            StepOutcome::Succeeded { attempts: 1, .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_positions_are_reported() {
+        let (tx_client, mock) = setup();
+        mock.set_handler(ACCOUNT_PATH, |_| Err(LighterError::Timeout));
+
+        let report = tx_client.kill_switch(&config()).await;
+        assert!(!report.cancel_all.is_failed());
+        assert!(matches!(
+            report.positions,
+            StepOutcome::Failed { attempts: 3, .. }
+        ));
+        assert!(report.markets.is_empty());
+        assert!(!report.is_complete());
+    }
+
+    #[tokio::test]
+    async fn test_market_timeout() {
+        let (tx_client, mock) = setup();
+        mock.set_handler(DETAILS_PATH, |_| Err(LighterError::Timeout));
+        let config = KillSwitchConfig {
+            per_market_timeout: Duration::from_millis(20),
+            retry_delay: Duration::from_secs(5),
+            ..KillSwitchConfig::default()
+        };
+
+        let report = tx_client.kill_switch(&config).await;
+        assert_eq!(report.open_markets().count(), 2);
+        assert!(matches!(
+            &report.markets[0].outcome,
+            StepOutcome::Failed { attempts: 1, error } if error.starts_with("Timed out")
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_deadline_bounds_the_whole_run() {
+        let (tx_client, mock) = setup();
+        mock.set_delay(ACCOUNT_PATH, Duration::from_secs(5));
+        let config = KillSwitchConfig {
+            deadline: Some(Deadline::after(Duration::from_millis(100))),
+            ..config()
+        };
+
+        let started = std::time::Instant::now();
+        let report = tx_client.kill_switch(&config).await;
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert!(matches!(report.cancel_all, StepOutcome::Succeeded { .. }));
+        assert!(matches!(
+            &report.positions,
+            StepOutcome::Failed { attempts: 1, error } if error.starts_with("Deadline exceeded")
+        ));
+        assert!(report.markets.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_sends_nothing() {
+        let (tx_client, mock) = setup();
+        let config = KillSwitchConfig {
+            dry_run: true,
+            ..config()
+        };
+
+        let report = tx_client.kill_switch(&config).await;
+        assert!(report.is_complete());
+        assert_eq!(report.cancel_all, StepOutcome::DryRun);
+        assert_eq!(report.markets.len(), 2);
+        assert!(report
+            .markets
+            .iter()
+            .all(|market| market.outcome == StepOutcome::DryRun && market.order.is_some()));
+        assert!(sent(&mock).is_empty());
+    }
+}
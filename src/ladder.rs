@@ -0,0 +1,654 @@
+//! Ladders of limit orders spread evenly between two prices
+//!
+//! [`TxClient::place_ladder`] turns a [`LadderSpec`] into one limit order per
+//! level, signs them with consecutive nonces and submits them in a single
+//! batch. The returned [`Ladder`] remembers the client order indexes so
+//! exactly that ladder can be cancelled later.
+//! [`TxClient::create_scaled_orders`] does the same from integer prices and
+//! base amounts, like [`TxClient::create_limit_order`] takes, and returns a
+//! response for each order.
+//!
+//! ```no_run
+//! use lighter_rs::client::TxClient;
+//! use lighter_rs::ladder::{LadderDistribution, LadderSpec};
+//! use lighter_rs::Decimal;
+//!
+//! # async fn example(tx_client: TxClient) -> lighter_rs::Result<()> {
+//! let spec = LadderSpec {
+//!     levels: 10,
+//!     price_from: Decimal::new(300000, 2),
+//!     price_to: Decimal::new(295000, 2),
+//!     total_size: Decimal::new(1, 0),
+//!     distribution: LadderDistribution::Uniform,
+//!     price_decimals: 2,
+//!     size_decimals: 4,
+//! };
+//! let ladder = tx_client.place_ladder(0, 0, &spec).await?;
+//! // ...
+//! ladder.cancel_all().await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::{Decimal, RoundingStrategy};
+
+use crate::client::{BatchTxResponse, TxClient, TxResponse};
+use crate::constants::*;
+use crate::errors::{LighterError, Result};
+use crate::types::{CancelOrderTxReq, CreateOrderTxReq, SignedTx, TransactOpts};
+
+/// How a ladder's total size is split across its levels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LadderDistribution {
+    /// Every level gets the same size
+    Uniform,
+    /// Sizes grow by the same step from level to level: the level at
+    /// `price_from` gets one part, the next two, and so on
+    Linear,
+    /// Each level is `ratio` times the size of the one before it, starting
+    /// from `price_from`
+    Geometric { ratio: Decimal },
+}
+
+/// Shape of a ladder
+///
+/// Prices and sizes are decimals; `price_decimals` and `size_decimals` give
+/// the market's tick and step size.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LadderSpec {
+    /// Number of orders, at most [`MAX_TX_BATCH_SIZE`]
+    pub levels: usize,
+    /// Price of the first level
+    pub price_from: Decimal,
+    /// Price of the last level
+    pub price_to: Decimal,
+    /// Combined size of all levels
+    pub total_size: Decimal,
+    pub distribution: LadderDistribution,
+    pub price_decimals: u32,
+    pub size_decimals: u32,
+}
+
+/// One order of a ladder, in integer price ticks and base units
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LadderLevel {
+    pub client_order_index: i64,
+    pub price: u64,
+    pub base_amount: i64,
+}
+
+impl LadderSpec {
+    /// Prices and sizes of each level, in integer price ticks and base units
+    ///
+    /// Level prices are interpolated between `price_from` and `price_to` and
+    /// rounded to the nearest tick. Sizes are rounded down to the step size
+    /// and the leftover steps go to the levels that lost the most to
+    /// rounding, so they always add up to `total_size` exactly.
+    ///
+    /// ```
+    /// use lighter_rs::ladder::{LadderDistribution, LadderSpec};
+    /// use lighter_rs::Decimal;
+    ///
+    /// let spec = LadderSpec {
+    ///     levels: 3,
+    ///     price_from: Decimal::new(10000, 2),
+    ///     price_to: Decimal::new(10100, 2),
+    ///     total_size: Decimal::new(10, 4),
+    ///     distribution: LadderDistribution::Uniform,
+    ///     price_decimals: 2,
+    ///     size_decimals: 4,
+    /// };
+    /// assert_eq!(
+    ///     spec.to_levels().unwrap(),
+    ///     vec![(10000, 4), (10050, 3), (10100, 3)]
+    /// );
+    /// ```
+    pub fn to_levels(&self) -> Result<Vec<(u64, i64)>> {
+        if self.levels == 0 || self.levels > MAX_TX_BATCH_SIZE {
+            return Err(LighterError::ValidationError(format!(
+                "Ladder must have between 1 and {MAX_TX_BATCH_SIZE} levels, got {}",
+                self.levels
+            )));
+        }
+        let prices = self.prices()?;
+        let sizes = self.sizes()?;
+        Ok(prices.into_iter().zip(sizes).collect())
+    }
+
+    fn prices(&self) -> Result<Vec<u64>> {
+        let from = to_units(self.price_from, self.price_decimals, "price_from")?;
+        let to = to_units(self.price_to, self.price_decimals, "price_to")?;
+        let steps = Decimal::from(self.levels.saturating_sub(1).max(1));
+
+        let mut prices: Vec<u64> = Vec::with_capacity(self.levels);
+        for level in 0..self.levels {
+            let price = (from + (to - from) * Decimal::from(level) / steps)
+                .round_dp_with_strategy(0, RoundingStrategy::MidpointAwayFromZero);
+            let price = price
+                .to_u64()
+                .filter(|price| (MIN_ORDER_PRICE..=MAX_ORDER_PRICE).contains(price))
+                .ok_or_else(|| {
+                    LighterError::ValidationError(format!("Ladder price {price} is out of range"))
+                })?;
+            if prices.last() == Some(&price) {
+                return Err(LighterError::ValidationError(format!(
+                    "Ladder levels are closer than one tick at price {price}"
+                )));
+            }
+            prices.push(price);
+        }
+        Ok(prices)
+    }
+
+    fn sizes(&self) -> Result<Vec<i64>> {
+        let total = to_units(self.total_size, self.size_decimals, "total_size")?;
+
+        let weights: Vec<Decimal> = match self.distribution {
+            LadderDistribution::Uniform => vec![Decimal::ONE; self.levels],
+            LadderDistribution::Linear => (1..=self.levels).map(Decimal::from).collect(),
+            LadderDistribution::Geometric { ratio } => {
+                if ratio <= Decimal::ZERO {
+                    return Err(LighterError::ValidationError(format!(
+                        "Geometric ratio must be positive, got {ratio}"
+                    )));
+                }
+                std::iter::successors(Some(Some(Decimal::ONE)), |weight| {
+                    Some(weight.and_then(|weight| weight.checked_mul(ratio)))
+                })
+                .take(self.levels)
+                .collect::<Option<_>>()
+                .ok_or_else(|| {
+                    LighterError::ValidationError(format!(
+                        "Geometric ratio {ratio} is too large for {} levels",
+                        self.levels
+                    ))
+                })?
+            }
+        };
+        let weight_sum: Decimal = weights.iter().sum();
+
+        // Largest remainder: round every level down, then hand the leftover
+        // steps to the largest fractional parts, earlier levels first on ties
+        let ideal: Vec<Decimal> = weights.iter().map(|w| total * w / weight_sum).collect();
+        let mut sizes: Vec<i64> = ideal
+            .iter()
+            .map(|size| size.floor().to_i64().unwrap_or(0))
+            .collect();
+        let residue = total.to_i64().unwrap_or(0) - sizes.iter().sum::<i64>();
+        let mut by_remainder: Vec<usize> = (0..self.levels).collect();
+        by_remainder.sort_by(|&a, &b| {
+            (ideal[b] - ideal[b].floor())
+                .cmp(&(ideal[a] - ideal[a].floor()))
+                .then(a.cmp(&b))
+        });
+        for &level in by_remainder.iter().take(residue.max(0) as usize) {
+            sizes[level] += 1;
+        }
+
+        if let Some(level) = sizes.iter().position(|&size| size < MIN_ORDER_BASE_AMOUNT) {
+            return Err(LighterError::ValidationError(format!(
+                "Ladder level {level} rounds to zero size"
+            )));
+        }
+        Ok(sizes)
+    }
+}
+
+/// `value` in integer units of `10^-decimals`, rejecting finer values
+fn to_units(value: Decimal, decimals: u32, field: &str) -> Result<Decimal> {
+    let scale =
+        Decimal::from(10i64.checked_pow(decimals).ok_or_else(|| {
+            LighterError::ValidationError(format!("Too many decimals: {decimals}"))
+        })?);
+    let units = value
+        .checked_mul(scale)
+        .ok_or_else(|| LighterError::ValidationError(format!("{field} {value} is too large")))?;
+    if units.fract() != Decimal::ZERO {
+        return Err(LighterError::ValidationError(format!(
+            "{field} {value} is not a multiple of {}",
+            Decimal::ONE / scale
+        )));
+    }
+    Ok(units.trunc())
+}
+
+/// A placed ladder
+pub struct Ladder<'a> {
+    tx_client: &'a TxClient,
+    market_index: u8,
+    is_ask: u8,
+    levels: Vec<LadderLevel>,
+    response: BatchTxResponse,
+}
+
+impl Ladder<'_> {
+    pub fn market_index(&self) -> u8 {
+        self.market_index
+    }
+
+    pub fn is_ask(&self) -> u8 {
+        self.is_ask
+    }
+
+    /// Orders of the ladder, from `price_from` to `price_to`
+    pub fn levels(&self) -> &[LadderLevel] {
+        &self.levels
+    }
+
+    /// Client order indexes of the ladder's orders
+    pub fn client_order_indexes(&self) -> impl Iterator<Item = i64> + '_ {
+        self.levels.iter().map(|level| level.client_order_index)
+    }
+
+    /// The API's response to the batch that placed the ladder
+    pub fn response(&self) -> &BatchTxResponse {
+        &self.response
+    }
+
+    /// Cancel every order of the ladder in one batch
+    ///
+    /// Orders already filled or cancelled are skipped by the exchange.
+    pub async fn cancel_all(&self) -> Result<BatchTxResponse> {
+        let reqs: Vec<CancelOrderTxReq> = self
+            .client_order_indexes()
+            .map(|index| CancelOrderTxReq {
+                market_index: self.market_index,
+                index,
+            })
+            .collect();
+        let txs = self
+            .tx_client
+            .cancel_orders(&reqs, None)
+            .await?
+            .iter()
+            .map(SignedTx::new)
+            .collect::<Result<Vec<_>>>()?;
+        self.tx_client.send_batch(&txs).await
+    }
+}
+
+impl TxClient {
+    /// Place a ladder of limit orders on one side of a market
+    ///
+    /// The orders are good-till-time for 28 days, like
+    /// [`TxClient::create_limit_order`], and go out in one sendTxBatch
+    /// request. A rejected batch is returned as an error.
+    pub async fn place_ladder(
+        &self,
+        market_index: u8,
+        is_ask: u8,
+        spec: &LadderSpec,
+    ) -> Result<Ladder<'_>> {
+        let levels = self.ladder_levels(spec)?;
+        let reqs = self.ladder_orders(market_index, is_ask, &levels);
+
+        let txs = self
+            .create_orders(&reqs, None)
+            .await?
+            .iter()
+            .map(SignedTx::new)
+            .collect::<Result<Vec<_>>>()?;
+        let response = self.send_batch(&txs).await?;
+        if !response.is_success() {
+            return Err(LighterError::ApiError(format!(
+                "Ladder rejected with code {}: {}",
+                response.code,
+                response.message.as_deref().unwrap_or_default()
+            )));
+        }
+
+        Ok(Ladder {
+            tx_client: self,
+            market_index,
+            is_ask,
+            levels,
+            response,
+        })
+    }
+
+    /// Place `count` limit orders of `total_base_amount` combined, spread
+    /// from `price_from` to `price_to`, with a response for each
+    ///
+    /// Prices are integer ticks and amounts integer base units, like
+    /// [`TxClient::create_limit_order`] takes. Level prices are rounded to
+    /// the nearest tick and the size is split by `distribution`, as
+    /// [`LadderSpec::to_levels`] does. The orders are signed with
+    /// consecutive nonces and sent in one batch; each comes back with its
+    /// client order index, so rungs can be cancelled one by one with
+    /// [`TxClient::cancel_order_by_client_index`].
+    #[allow(clippy::too_many_arguments)]
+    pub async fn create_scaled_orders(
+        &self,
+        market_index: u8,
+        total_base_amount: i64,
+        price_from: u64,
+        price_to: u64,
+        count: usize,
+        is_ask: u8,
+        distribution: LadderDistribution,
+        opts: Option<TransactOpts>,
+    ) -> Result<Vec<(i64, TxResponse)>> {
+        let spec = LadderSpec {
+            levels: count,
+            price_from: Decimal::from(price_from),
+            price_to: Decimal::from(price_to),
+            total_size: Decimal::from(total_base_amount),
+            distribution,
+            price_decimals: 0,
+            size_decimals: 0,
+        };
+        let levels = self.ladder_levels(&spec)?;
+        let reqs = self.ladder_orders(market_index, is_ask, &levels);
+
+        let signed = self.create_orders(&reqs, opts).await?;
+        let responses = self.send_transactions(&signed).await?;
+        Ok(levels
+            .iter()
+            .map(|level| level.client_order_index)
+            .zip(responses)
+            .collect())
+    }
+
+    /// Levels of `spec`, with client order indexes from
+    /// [`TxClient::next_client_order_indexes`]
+    fn ladder_levels(&self, spec: &LadderSpec) -> Result<Vec<LadderLevel>> {
+        let levels = spec.to_levels()?;
+        let first_index = self.next_client_order_indexes(levels.len())?;
+        Ok(levels
+            .into_iter()
+            .enumerate()
+            .map(|(i, (price, base_amount))| LadderLevel {
+                client_order_index: first_index + i as i64,
+                price,
+                base_amount,
+            })
+            .collect())
+    }
+
+    /// Good-till-time limit orders for `levels`
+    fn ladder_orders(
+        &self,
+        market_index: u8,
+        is_ask: u8,
+        levels: &[LadderLevel],
+    ) -> Vec<CreateOrderTxReq> {
+        let order_expiry = self.default_order_expiry();
+        levels
+            .iter()
+            .map(|level| CreateOrderTxReq {
+                market_index,
+                client_order_index: level.client_order_index,
+                base_amount: level.base_amount,
+                price: level.price,
+                is_ask,
+                order_type: ORDER_TYPE_LIMIT,
+                time_in_force: TIME_IN_FORCE_GOOD_TILL_TIME,
+                reduce_only: 0,
+                trigger_price: 0,
+                order_expiry,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::order_namespace::ClientOrderNamespace;
+    use crate::test_support::mock_builder;
+    use crate::transport::{HttpResponse, MockTransport};
+    use serde_json::Value;
+    use std::sync::Arc;
+
+    const BATCH_PATH: &str = "/api/v1/sendTxBatch";
+
+    fn spec(levels: usize, total_size: Decimal, distribution: LadderDistribution) -> LadderSpec {
+        LadderSpec {
+            levels,
+            price_from: Decimal::new(300000, 2),
+            price_to: Decimal::new(295000, 2),
+            total_size,
+            distribution,
+            price_decimals: 2,
+            size_decimals: 4,
+        }
+    }
+
+    fn sizes(spec: &LadderSpec) -> Vec<i64> {
+        spec.to_levels()
+            .unwrap()
+            .into_iter()
+            .map(|(_, size)| size)
+            .collect()
+    }
+
+    #[test]
+    fn test_uniform_residue_goes_to_first_levels() {
+        // 1.0003 over 10 levels: 1000 steps each plus 3 left over
+        let spec = spec(10, Decimal::new(10003, 4), LadderDistribution::Uniform);
+        let sizes = sizes(&spec);
+        assert_eq!(sizes.iter().sum::<i64>(), 10003);
+        assert_eq!(&sizes[..4], &[1001, 1001, 1001, 1000]);
+        assert!(sizes[3..].iter().all(|&size| size == 1000));
+    }
+
+    #[test]
+    fn test_geometric_sizes_add_up_exactly() {
+        let spec = spec(
+            7,
+            Decimal::new(12345, 4),
+            LadderDistribution::Geometric {
+                ratio: Decimal::new(15, 1),
+            },
+        );
+        let sizes = sizes(&spec);
+        assert_eq!(sizes.iter().sum::<i64>(), 12345);
+        assert!(sizes.windows(2).all(|pair| pair[0] < pair[1]));
+        // Weights 1, 1.5, ..., 1.5^6 sum to 32.171875
+        assert_eq!(sizes[0], 384);
+        assert_eq!(sizes[6], 4371);
+    }
+
+    #[test]
+    fn test_linear_sizes_grow_by_equal_steps() {
+        // Parts 1 to 4 of 1.0000, a tenth each
+        let tenths = spec(4, Decimal::ONE, LadderDistribution::Linear);
+        assert_eq!(sizes(&tenths), vec![1000, 2000, 3000, 4000]);
+
+        // 1, 2 and 3 sixths of 7 steps round down to 1, 2 and 3, and the
+        // leftover step goes to the largest remainder
+        let sixths = spec(3, Decimal::new(7, 4), LadderDistribution::Linear);
+        assert_eq!(sizes(&sixths), vec![1, 2, 4]);
+    }
+
+    #[test]
+    fn test_prices_round_to_ticks() {
+        let mut spec = spec(4, Decimal::ONE, LadderDistribution::Uniform);
+        spec.price_to = Decimal::new(299990, 2);
+        let prices: Vec<u64> = spec
+            .to_levels()
+            .unwrap()
+            .into_iter()
+            .map(|(price, _)| price)
+            .collect();
+        // 3000.00 → 2999.90 in thirds of 3.33 ticks
+        assert_eq!(prices, vec![300000, 299997, 299993, 299990]);
+    }
+
+    #[test]
+    fn test_rejects_off_grid_and_degenerate_specs() {
+        let mut off_tick = spec(3, Decimal::ONE, LadderDistribution::Uniform);
+        off_tick.price_from = Decimal::new(3000005, 3);
+        assert!(off_tick.to_levels().is_err());
+
+        let off_step = spec(3, Decimal::new(100005, 5), LadderDistribution::Uniform);
+        assert!(off_step.to_levels().is_err());
+
+        let mut too_dense = spec(3, Decimal::ONE, LadderDistribution::Uniform);
+        too_dense.price_to = Decimal::new(300001, 2);
+        assert!(too_dense.to_levels().is_err());
+
+        let too_small = spec(3, Decimal::new(2, 4), LadderDistribution::Uniform);
+        assert!(too_small.to_levels().is_err());
+
+        assert!(spec(0, Decimal::ONE, LadderDistribution::Uniform)
+            .to_levels()
+            .is_err());
+        assert!(spec(51, Decimal::ONE, LadderDistribution::Uniform)
+            .to_levels()
+            .is_err());
+    }
+
+    /// Transaction infos of a batch request
+    fn batch_txs(body: &[u8]) -> (Vec<u8>, Vec<Value>) {
+        let form: Vec<(String, String)> = serde_urlencoded::from_bytes(body).unwrap();
+        let field = |name: &str| {
+            form.iter()
+                .find_map(|(field, value)| (field == name).then(|| value.clone()))
+                .unwrap()
+        };
+        let tx_types: Vec<u8> = serde_json::from_str(&field("tx_types")).unwrap();
+        let tx_infos: Vec<String> = serde_json::from_str(&field("tx_infos")).unwrap();
+        let tx_infos = tx_infos
+            .iter()
+            .map(|info| serde_json::from_str(info).unwrap())
+            .collect();
+        (tx_types, tx_infos)
+    }
+
+    #[tokio::test]
+    async fn test_place_and_cancel_ladder_in_batches() {
+        let mock = Arc::new(MockTransport::new());
+        mock.set_handler(BATCH_PATH, |_| {
+            Ok(HttpResponse::new(
+                200,
+                r#"{"code":200,"tx_hash":["0x1","0x2","0x3"]}"#,
+            ))
+        });
+        let namespace = ClientOrderNamespace::new(9, 8).unwrap();
+        let tx_client = mock_builder(&mock)
+            .client_order_namespace(namespace)
+            .build()
+            .unwrap();
+        tx_client.nonces().set(1, 0, 40);
+
+        let spec = spec(3, Decimal::new(3, 1), LadderDistribution::Uniform);
+        let ladder = tx_client.place_ladder(0, 1, &spec).await.unwrap();
+        assert_eq!(ladder.response().tx_hash.len(), 3);
+
+        let requests = mock.requests_to(BATCH_PATH);
+        assert_eq!(requests.len(), 1);
+        let (tx_types, orders) = batch_txs(&requests[0].body);
+        assert_eq!(tx_types, vec![TX_TYPE_L2_CREATE_ORDER; 3]);
+        let nonces: Vec<i64> = orders
+            .iter()
+            .map(|o| o["Nonce"].as_i64().unwrap())
+            .collect();
+        assert_eq!(nonces, vec![40, 41, 42]);
+        let indexes: Vec<i64> = orders
+            .iter()
+            .map(|o| o["ClientOrderIndex"].as_i64().unwrap())
+            .collect();
+        assert_eq!(indexes, ladder.client_order_indexes().collect::<Vec<_>>());
+        assert_eq!(indexes[1], indexes[0] + 1);
+        assert!(indexes.iter().all(|&index| namespace.contains(index)));
+        assert_eq!(orders[2]["Price"], 295000);
+
+        ladder.cancel_all().await.unwrap();
+        let requests = mock.requests_to(BATCH_PATH);
+        assert_eq!(requests.len(), 2);
+        let (tx_types, cancels) = batch_txs(&requests[1].body);
+        assert_eq!(tx_types, vec![TX_TYPE_L2_CANCEL_ORDER; 3]);
+        let cancelled: Vec<i64> = cancels
+            .iter()
+            .map(|c| c["Index"].as_i64().unwrap())
+            .collect();
+        assert_eq!(cancelled, indexes);
+        assert_eq!(cancels[0]["Nonce"], 43);
+    }
+
+    #[tokio::test]
+    async fn test_scaled_orders_respond_per_rung() {
+        let mock = Arc::new(MockTransport::new());
+        mock.set_handler(BATCH_PATH, |_| {
+            Ok(HttpResponse::new(
+                200,
+                r#"{"code":200,"tx_hash":["0x1","0x2","0x3","0x4","0x5"]}"#,
+            ))
+        });
+        let tx_client = mock_builder(&mock).build().unwrap();
+        tx_client.nonces().set(1, 0, 10);
+
+        // 5 buys of 15_002 base units combined from 295_000 to 295_010: the
+        // 2.5 tick step rounds to the nearest tick, and the 2 units the
+        // linear split leaves over go to the largest fractions
+        let rungs = tx_client
+            .create_scaled_orders(
+                0,
+                15_002,
+                295_000,
+                295_010,
+                5,
+                0,
+                LadderDistribution::Linear,
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(rungs.len(), 5);
+        assert!(rungs.iter().all(|(_, response)| response.is_success()));
+        assert_eq!(rungs[4].1.tx_hash.as_deref(), Some("0x5"));
+
+        let requests = mock.requests_to(BATCH_PATH);
+        assert_eq!(requests.len(), 1);
+        let (tx_types, orders) = batch_txs(&requests[0].body);
+        assert_eq!(tx_types, vec![TX_TYPE_L2_CREATE_ORDER; 5]);
+        let field =
+            |name: &str| -> Vec<i64> { orders.iter().map(|o| o[name].as_i64().unwrap()).collect() };
+        assert_eq!(
+            field("Price"),
+            vec![295_000, 295_003, 295_005, 295_008, 295_010]
+        );
+        assert_eq!(field("BaseAmount"), vec![1_000, 2_000, 3_000, 4_001, 5_001]);
+        assert_eq!(field("Nonce"), vec![10, 11, 12, 13, 14]);
+        assert_eq!(
+            field("ClientOrderIndex"),
+            rungs.iter().map(|(index, _)| *index).collect::<Vec<_>>()
+        );
+
+        // Nothing is signed for a ladder that doesn't fit the grid
+        assert!(tx_client
+            .create_scaled_orders(
+                0,
+                15_000,
+                295_000,
+                295_002,
+                5,
+                0,
+                LadderDistribution::Uniform,
+                None
+            )
+            .await
+            .is_err());
+        assert_eq!(tx_client.nonces().peek(1, 0), Some(15));
+    }
+
+    #[tokio::test]
+    async fn test_rejected_ladder_is_an_error() {
+        let mock = Arc::new(MockTransport::new());
+        mock.push_response(
+            BATCH_PATH,
+            200,
+            r#"{"code":21701,"message":"invalid base amount"}"#,
+        );
+        let tx_client = mock_builder(&mock).build().unwrap();
+        tx_client.nonces().set(1, 0, 0);
+
+        let spec = spec(2, Decimal::ONE, LadderDistribution::Uniform);
+        assert!(matches!(
+            tx_client.place_ladder(0, 0, &spec).await,
+            Err(LighterError::ApiError(_))
+        ));
+    }
+}
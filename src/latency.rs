@@ -0,0 +1,279 @@
+//! Per-submission latency of the order round trip
+//!
+//! Every [`TxResponse`](crate::client::TxResponse) from
+//! [`TxClient::send_transaction`](crate::client::TxClient::send_transaction)
+//! or [`TxClient::send_signed`](crate::client::TxClient::send_signed) can
+//! carry a [`LatencyBreakdown`] splitting the time since the transaction's
+//! nonce was allocated into stages:
+//!
+//! - `nonce`: allocating the nonce, a cache hit or a `nextNonce` request
+//! - `sign`: validating, hashing and signing
+//! - `queued`: waiting between signing and the send call
+//! - `serialize`: encoding the request body
+//! - `round_trip`: from handing the request to the transport until the full
+//!   response arrived, which includes the `server` time when the API reports
+//!   one in a `Server-Timing` or `X-Response-Time` header
+//! - `parse`: decoding the response
+//!
+//! Transactions signed by another client or process only get the stages from
+//! the send call on.
+//!
+//! Capturing is a handful of `Instant::now()` calls per submission, and only
+//! happens when a hook is set with
+//! [`TxClientBuilder::latency_hook`](crate::client::TxClientBuilder::latency_hook)
+//! or a tracing subscriber listens for debug events on the
+//! `lighter_rs::latency` target; otherwise `latency` stays `None`.
+//!
+//! ```no_run
+//! use lighter_rs::client::TxClient;
+//!
+//! # fn example() -> lighter_rs::Result<()> {
+//! let tx_client = TxClient::builder()
+//!     .api_url("https://testnet.zklighter.elliot.ai")
+//!     .private_key("0x...")
+//!     .latency_hook(|latency| {
+//!         if latency.total.as_millis() > 50 {
+//!             eprintln!("slow order: {latency:?}");
+//!         }
+//!     })
+//!     .build()?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Tracing target the breakdowns are logged under, at debug level
+pub const LATENCY_TARGET: &str = "lighter_rs::latency";
+
+/// Signed transactions whose signing stages are kept for their send
+const MAX_PENDING: usize = 1024;
+
+/// A stage of the order round trip, in order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Stage {
+    Nonce,
+    Sign,
+    Queued,
+    Serialize,
+    RoundTrip,
+    Parse,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 6] = [
+        Stage::Nonce,
+        Stage::Sign,
+        Stage::Queued,
+        Stage::Serialize,
+        Stage::RoundTrip,
+        Stage::Parse,
+    ];
+}
+
+/// How long each stage of one submission took
+///
+/// The stages follow each other, so they add up to `total`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LatencyBreakdown {
+    pub nonce: Duration,
+    pub sign: Duration,
+    pub queued: Duration,
+    pub serialize: Duration,
+    pub round_trip: Duration,
+    /// Processing time reported by the server, part of `round_trip`
+    pub server: Option<Duration>,
+    pub parse: Duration,
+    pub total: Duration,
+    /// Strategy the transaction was signed for, see the
+    /// [`strategy`](crate::strategy) module
+    pub strategy_id: Option<Arc<str>>,
+}
+
+impl LatencyBreakdown {
+    /// Duration of `stage`
+    pub fn stage(&self, stage: Stage) -> Duration {
+        match stage {
+            Stage::Nonce => self.nonce,
+            Stage::Sign => self.sign,
+            Stage::Queued => self.queued,
+            Stage::Serialize => self.serialize,
+            Stage::RoundTrip => self.round_trip,
+            Stage::Parse => self.parse,
+        }
+    }
+
+    /// Round trip time not accounted for by the server, or `None` when the
+    /// server didn't report its time
+    pub fn network(&self) -> Option<Duration> {
+        self.server
+            .map(|server| self.round_trip.saturating_sub(server))
+    }
+}
+
+/// Instants at which a submission finished each stage
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Stopwatch {
+    started: Instant,
+    marks: [Option<Instant>; Stage::ALL.len()],
+}
+
+impl Stopwatch {
+    fn start() -> Self {
+        Self {
+            started: Instant::now(),
+            marks: [None; Stage::ALL.len()],
+        }
+    }
+
+    /// Record that `stage` just finished
+    pub(crate) fn mark(&mut self, stage: Stage) {
+        self.marks[stage as usize] = Some(Instant::now());
+    }
+
+    /// Durations between consecutive marks; stages never marked take no time
+    pub(crate) fn finish(&self, server: Option<Duration>) -> LatencyBreakdown {
+        let mut breakdown = LatencyBreakdown {
+            server,
+            ..LatencyBreakdown::default()
+        };
+        let mut previous = self.started;
+        for stage in Stage::ALL {
+            if let Some(mark) = self.marks[stage as usize] {
+                let elapsed = mark.saturating_duration_since(previous);
+                match stage {
+                    Stage::Nonce => breakdown.nonce = elapsed,
+                    Stage::Sign => breakdown.sign = elapsed,
+                    Stage::Queued => breakdown.queued = elapsed,
+                    Stage::Serialize => breakdown.serialize = elapsed,
+                    Stage::RoundTrip => breakdown.round_trip = elapsed,
+                    Stage::Parse => breakdown.parse = elapsed,
+                }
+                previous = previous.max(mark);
+            }
+        }
+        breakdown.total = previous.saturating_duration_since(self.started);
+        breakdown
+    }
+}
+
+/// Receives the breakdown of every submission
+pub type LatencyHook = Arc<dyn Fn(&LatencyBreakdown) + Send + Sync>;
+
+/// A client's latency capture: whether it is on, where breakdowns go, and
+/// the signing stages of transactions not sent yet
+#[derive(Default)]
+pub(crate) struct LatencyRecorder {
+    hook: Option<LatencyHook>,
+    /// Stopwatches of signed transactions by tx hash, oldest first
+    pending: Mutex<VecDeque<(String, Stopwatch)>>,
+}
+
+impl LatencyRecorder {
+    pub(crate) fn new(hook: Option<LatencyHook>) -> Self {
+        Self {
+            hook,
+            pending: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// A running stopwatch, or `None` when nothing would see the result
+    pub(crate) fn start(&self) -> Option<Stopwatch> {
+        // std's Instant panics on wasm32-unknown-unknown
+        if cfg!(target_arch = "wasm32") {
+            return None;
+        }
+        let enabled =
+            self.hook.is_some() || tracing::enabled!(target: LATENCY_TARGET, tracing::Level::DEBUG);
+        enabled.then(Stopwatch::start)
+    }
+
+    /// Keep a signed transaction's stopwatch until it is sent
+    pub(crate) fn signed(&self, tx_hash: Option<String>, stopwatch: Stopwatch) {
+        let Some(tx_hash) = tx_hash else {
+            return;
+        };
+        let mut pending = self.lock();
+        if pending.len() >= MAX_PENDING {
+            pending.pop_front();
+        }
+        pending.push_back((tx_hash, stopwatch));
+    }
+
+    /// The stopwatch of a transaction about to be sent: the one started when
+    /// it was signed, or a new one
+    pub(crate) fn sending(&self, tx_hash: Option<&str>) -> Option<Stopwatch> {
+        let signed = tx_hash.and_then(|tx_hash| {
+            let mut pending = self.lock();
+            let i = pending.iter().rposition(|(hash, _)| hash == tx_hash)?;
+            pending.remove(i).map(|(_, stopwatch)| stopwatch)
+        });
+        let mut stopwatch = signed.or_else(|| self.start())?;
+        stopwatch.mark(Stage::Queued);
+        Some(stopwatch)
+    }
+
+    /// Hand a finished breakdown to the hook and the log
+    pub(crate) fn report(&self, breakdown: &LatencyBreakdown) {
+        tracing::debug!(
+            target: LATENCY_TARGET,
+            strategy_id = breakdown.strategy_id.as_deref(),
+            nonce_us = breakdown.nonce.as_micros() as u64,
+            sign_us = breakdown.sign.as_micros() as u64,
+            queued_us = breakdown.queued.as_micros() as u64,
+            serialize_us = breakdown.serialize.as_micros() as u64,
+            round_trip_us = breakdown.round_trip.as_micros() as u64,
+            server_us = breakdown.server.map(|server| server.as_micros() as u64),
+            parse_us = breakdown.parse.as_micros() as u64,
+            total_us = breakdown.total.as_micros() as u64,
+            "Submission latency"
+        );
+        if let Some(hook) = &self.hook {
+            hook(breakdown);
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, VecDeque<(String, Stopwatch)>> {
+        self.pending
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unmarked_stages_take_no_time() {
+        let mut stopwatch = Stopwatch::start();
+        std::thread::sleep(Duration::from_millis(2));
+        stopwatch.mark(Stage::Queued);
+        std::thread::sleep(Duration::from_millis(2));
+        stopwatch.mark(Stage::Parse);
+
+        let breakdown = stopwatch.finish(None);
+        assert_eq!(breakdown.nonce, Duration::ZERO);
+        assert_eq!(breakdown.round_trip, Duration::ZERO);
+        assert!(breakdown.queued >= Duration::from_millis(2));
+        assert!(breakdown.parse >= Duration::from_millis(2));
+        assert_eq!(breakdown.total, breakdown.queued + breakdown.parse);
+        assert_eq!(breakdown.network(), None);
+    }
+
+    #[test]
+    fn test_disabled_without_hook_or_subscriber() {
+        let recorder = LatencyRecorder::new(None);
+        assert!(recorder.start().is_none());
+        assert!(recorder.sending(Some("0xabc")).is_none());
+
+        let recorder = LatencyRecorder::new(Some(Arc::new(|_: &LatencyBreakdown| {})));
+        let stopwatch = recorder.start().unwrap();
+        recorder.signed(Some("0xabc".to_string()), stopwatch);
+        let sending = recorder.sending(Some("0xabc")).unwrap();
+        assert_eq!(sending.started, stopwatch.started);
+        assert!(recorder.lock().is_empty());
+    }
+}
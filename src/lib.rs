@@ -12,9 +12,59 @@
 //!
 //! - `constants`: Core constants and limits used throughout the protocol
 //! - `signer`: Cryptographic key management and signing functionality
+//! - `signing`: Strategies for where transactions are signed
 //! - `types`: Transaction types and request builders
 //! - `client`: HTTP client for API interactions
+//! - `endpoints`: REST endpoint paths, API prefix and per-endpoint overrides
 //! - `errors`: Error types and handling
+//! - `failed_tx`: Signed transactions whose submission failed, kept for inspection and resubmission
+//! - `fresh_price`: Prices stamped with when they were observed, refused once stale
+//! - `health_probe`: Periodic self-test of signing, nonces and submission with harmless orders (requires the default `native` feature)
+//! - `bracket`: Entry orders placed with a take profit and a stop loss
+//! - `book_recorder`: Order book depth recorded to CSV or Parquet (requires the default `native` feature; Parquet requires the `arrow` feature)
+//! - `candles`: Candlesticks, with history and live candles joined into one series
+//! - `clock`: Time source for time-based features, with a manual clock for tests
+//! - `composite`: Multi-step operations bounded by one deadline (requires the default `native` feature)
+//! - `deadline`: Deadlines for multi-step operations (requires the default `native` feature)
+//! - `dca`: Scheduled fixed-notional buys (requires the default `native` feature)
+//! - `latency`: Per-submission latency of the order round trip
+//! - `nonce`: Local nonce allocation
+//! - `nonce_gap`: Healing the nonce gap a dropped submission leaves behind
+//! - `order_expiry`: Warnings for orders nearing their expiry (requires the default `native` feature)
+//! - `order_namespace`: Client order index namespaces for bots sharing an account
+//! - `order_preview`: Notional, expected fees and margin of an order before it is sent
+//! - `account`: Account collateral and margin requirements
+//! - `auth_token`: Auth tokens for authenticated endpoints and private WebSocket channels
+//! - `key_rotation`: Rotating an API key to a new public key (requires the default `native` feature)
+//! - `kill_switch`: Cancel everything and flatten all positions (requires the default `native` feature)
+//! - `ladder`: Ladders of limit orders placed and cancelled in one batch
+//! - `multi_leg`: Multi-leg trades unwound when a leg falls short (requires the default `native` feature)
+//! - `prepared`: Orders signed now and sent once confirmed, with their nonce given back when discarded
+//! - `price_band`: Pre-check of limit and trigger prices against the exchange's band around the mark
+//! - `read_only`: Clients that can never submit a transaction
+//! - `risk`: Pre-trade risk limits enforced when signing orders
+//! - `session`: Connecting and warming up everything a trading session needs in one call (requires the default `native` feature)
+//! - `session_stats`: Counts of orders, submissions and stream frames over a session
+//! - `portfolio`: End-of-day portfolio snapshots as JSON or CSV (requires the default `native` feature)
+//! - `positions`: Live positions, PnL and exposure (requires the default `native` feature)
+//! - `ws_client`: WebSocket client (requires the default `native` feature)
+//! - `blocking`: Synchronous transaction client (requires the `blocking` feature)
+//! - `transport`: Pluggable HTTP transport, including an in-memory mock
+//! - `tls`: TLS backend selection (`rustls-tls` or `native-tls` features)
+//! - `testing`: Mock Lighter server (requires the `test-util` feature)
+//! - `quoter`: Two-sided quote management (requires the `quoter` feature)
+//! - `simulator`: Paper-trading exchange (requires the `simulator` feature)
+//! - `spread`: Spreads between markets and reference prices (requires the default `native` feature)
+//! - `submission`: Prioritized transaction submission paced by the order rate budget (requires the default `native` feature)
+//! - `state_store`: Saving client state and restoring it after a restart (requires the default `native` feature)
+//! - `snapshot_sync`: Joining REST snapshots with the WebSocket deltas around them
+//! - `strategy`: Attributing orders, log lines and latency metrics to the strategy that signed them
+//! - `sub_account`: Transfers to the sub-accounts of an account, refusing other destinations
+//! - `sweep`: Marketable limit orders sweeping the book up to an average price (requires the default `native` feature)
+//! - `system_status`: Exchange maintenance and trading pauses
+//! - `tracker`: Order lifecycle tracking (requires the default `native` feature)
+//! - `trailing_stop`: Client-side trailing stops (requires the default `native` feature)
+//! - `trigger_direction`: Checks that stop loss and take profit triggers are on the right side of the market
 //!
 //! ## Example
 //!
@@ -39,12 +89,88 @@
 //! # }
 //! ```
 
+pub mod account;
+pub mod auth_token;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+#[cfg(feature = "native")]
+pub mod book_recorder;
+pub mod bracket;
+pub mod candles;
 pub mod client;
+pub mod clock;
+#[cfg(feature = "native")]
+pub mod composite;
 pub mod constants;
+#[cfg(feature = "native")]
+pub mod dca;
+#[cfg(feature = "native")]
+pub mod deadline;
+pub mod endpoints;
 pub mod errors;
+pub mod failed_tx;
+pub mod fresh_price;
+#[cfg(feature = "native")]
+pub mod health_probe;
+#[cfg(feature = "native")]
+pub mod key_rotation;
+#[cfg(feature = "native")]
+pub mod kill_switch;
+pub mod ladder;
+pub mod latency;
+#[cfg(any(feature = "test-util", feature = "simulator"))]
+mod loopback;
+#[cfg(feature = "native")]
+pub mod multi_leg;
+pub mod nonce;
+pub mod nonce_gap;
+#[cfg(feature = "native")]
+pub mod order_expiry;
+pub mod order_namespace;
+pub mod order_preview;
+#[cfg(feature = "native")]
+pub mod portfolio;
+#[cfg(feature = "native")]
+pub mod positions;
+pub mod prepared;
+pub mod price_band;
+#[cfg(feature = "quoter")]
+pub mod quoter;
+pub mod read_only;
+pub mod risk;
+#[cfg(feature = "native")]
+pub mod session;
+pub mod session_stats;
 pub mod signer;
+pub mod signing;
+#[cfg(feature = "simulator")]
+pub mod simulator;
+pub mod snapshot_sync;
+#[cfg(feature = "native")]
+pub mod spread;
+#[cfg(feature = "native")]
+pub mod state_store;
+pub mod strategy;
+pub mod sub_account;
+#[cfg(feature = "native")]
+pub mod submission;
+#[cfg(feature = "native")]
+pub mod sweep;
+pub mod system_status;
+#[cfg(test)]
+pub(crate) mod test_support;
+#[cfg(feature = "test-util")]
+pub mod testing;
+pub mod tls;
+#[cfg(feature = "native")]
+pub mod tracker;
+#[cfg(feature = "native")]
+pub mod trailing_stop;
+pub mod transport;
+pub mod trigger_direction;
 pub mod types;
 pub mod utils;
+#[cfg(feature = "native")]
 pub mod ws_client;
 
 // Re-export commonly used types
@@ -52,7 +178,11 @@ pub use client::TxResponse;
 pub use constants::*;
 pub use errors::{LighterError, Result};
 pub use signer::{KeyManager, Signer};
+pub use signing::SigningStrategy;
 pub use types::{TransactOpts, TxInfo};
 
+// Decimal type used for order book prices and sizes
+pub use rust_decimal::Decimal;
+
 /// Library version
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
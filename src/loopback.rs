@@ -0,0 +1,129 @@
+//! Minimal HTTP/1.1 plumbing shared by the localhost servers in `testing` and
+//! `simulator`
+
+use bytes::Bytes;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Method, StatusCode};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use crate::errors::{LighterError, Result};
+use crate::transport::{HttpRequest, HttpResponse};
+
+impl HttpRequest {
+    /// Decode a form-urlencoded request body into its fields
+    pub fn form(&self) -> Result<Vec<(String, String)>> {
+        serde_urlencoded::from_bytes(&self.body)
+            .map_err(|e| LighterError::InvalidResponse(format!("Invalid form body: {e}")))
+    }
+
+    /// Value of one field of a form-urlencoded request body
+    pub fn form_field(&self, name: &str) -> Result<Option<String>> {
+        Ok(self
+            .form()?
+            .into_iter()
+            .find_map(|(field, value)| (field == name).then_some(value)))
+    }
+}
+
+/// Serve keep-alive HTTP requests on `socket` until the peer goes away
+pub(crate) async fn serve_http(
+    mut socket: TcpStream,
+    mut respond: impl FnMut(HttpRequest) -> HttpResponse,
+) {
+    let mut pending = Vec::new();
+    let mut buf = vec![0u8; 8192];
+    loop {
+        // Serve every complete request already buffered
+        while let Some(request) = take_request(&mut pending) {
+            let response = respond(request);
+
+            let reason = StatusCode::from_u16(response.status)
+                .ok()
+                .and_then(|status| status.canonical_reason())
+                .unwrap_or("");
+            let head = format!(
+                "HTTP/1.1 {} {reason}\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n",
+                response.status,
+                response.body.len()
+            );
+            let written = async {
+                socket.write_all(head.as_bytes()).await?;
+                socket.write_all(response.body.as_bytes()).await
+            };
+            if written.await.is_err() {
+                return;
+            }
+        }
+        match socket.read(&mut buf).await {
+            Ok(0) | Err(_) => return,
+            Ok(n) => pending.extend_from_slice(&buf[..n]),
+        }
+    }
+}
+
+/// Split the first complete HTTP/1.1 request off `pending`, if there is one
+fn take_request(pending: &mut Vec<u8>) -> Option<HttpRequest> {
+    let end = pending.windows(4).position(|w| w == b"\r\n\r\n")?;
+    let head = String::from_utf8_lossy(&pending[..end]).into_owned();
+    let mut lines = head.split("\r\n");
+
+    let mut request_line = lines.next()?.split(' ');
+    let method = Method::from_bytes(request_line.next()?.as_bytes()).ok()?;
+    let url = request_line.next()?.to_string();
+
+    let mut headers = HeaderMap::new();
+    for line in lines {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        if let (Ok(name), Ok(value)) = (
+            HeaderName::from_bytes(name.trim().as_bytes()),
+            HeaderValue::from_str(value.trim()),
+        ) {
+            headers.append(name, value);
+        }
+    }
+
+    let len = headers
+        .get("content-length")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(0);
+    if pending.len() < end + 4 + len {
+        return None;
+    }
+    let request: Vec<u8> = pending.drain(..end + 4 + len).collect();
+
+    Some(HttpRequest {
+        method,
+        url,
+        headers,
+        body: Bytes::copy_from_slice(&request[end + 4..]),
+    })
+}
+
+/// Whether the connection opens with a WebSocket upgrade request
+///
+/// Peeks at the request head without consuming it, so the socket can still be
+/// handed to either the HTTP or the WebSocket server.
+pub(crate) async fn is_websocket_upgrade(socket: &TcpStream) -> bool {
+    let mut buf = vec![0u8; 8192];
+    loop {
+        let n = match socket.peek(&mut buf).await {
+            Ok(0) | Err(_) => return false,
+            Ok(n) => n,
+        };
+        if let Some(end) = buf[..n].windows(4).position(|w| w == b"\r\n\r\n") {
+            let head = String::from_utf8_lossy(&buf[..end]).to_ascii_lowercase();
+            return head
+                .lines()
+                .any(|line| line.starts_with("upgrade:") && line.contains("websocket"));
+        }
+        if n == buf.len() {
+            return false;
+        }
+        // Wait for more of the head to arrive
+        tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+    }
+}
@@ -0,0 +1,631 @@
+//! Entering several orders together and unwinding them if any falls short
+//!
+//! [`MultiLegExecutor::execute`] signs every leg, sends them all at once and
+//! follows them through an [`OrderTracker`] until each has filled, failed or
+//! run out of time. When every leg fills in full the trade is done. Otherwise
+//! the legs still open are cancelled and, with
+//! [`LegFailurePolicy::flatten`], whatever did fill is unwound with
+//! reduce-only market orders priced like
+//! [`TxClient::close_position`](crate::client::TxClient::close_position).
+//!
+//! Each leg's unwind is retried on its own up to
+//! [`LegFailurePolicy::rollback_attempts`] times, since a failed unwind leaves
+//! exactly the one-sided exposure the trade was meant to avoid. The
+//! [`MultiLegReport`] says what filled, what was unwound and what exposure is
+//! left.
+//!
+//! Fills are only seen through the tracker, so it must be fed the account
+//! channel while the executor runs.
+//!
+//! ```no_run
+//! use lighter_rs::multi_leg::{LegFailurePolicy, LegSpec, MultiLegExecutor};
+//! use lighter_rs::tracker::OrderTracker;
+//!
+//! # async fn example(tracker: OrderTracker) -> lighter_rs::Result<()> {
+//! let executor = MultiLegExecutor::new(tracker);
+//! let report = executor
+//!     .execute(
+//!         vec![
+//!             LegSpec::market(0, false, 1000, 310000),
+//!             LegSpec::market(1, true, 20000, 6000),
+//!         ],
+//!         LegFailurePolicy::default(),
+//!     )
+//!     .await?;
+//! if !report.completed {
+//!     println!("left exposed: {:?}", report.residual_exposure());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use futures_util::future::join_all;
+use rust_decimal::Decimal;
+
+use crate::composite::Reduce;
+use crate::deadline::Progress;
+use crate::errors::Result;
+use crate::tracker::{OrderState, OrderTracker};
+use crate::types::{CancelOrderTxReq, L2CreateOrderTxInfo};
+
+/// How a leg is placed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LegOrder {
+    /// Immediate-or-cancel, with the price as the worst acceptable
+    Market,
+    /// Resting until it fills or the fill timeout cancels it
+    Limit,
+}
+
+/// One order of a multi-leg trade
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LegSpec {
+    pub market_index: u8,
+    pub is_ask: bool,
+    pub base_amount: i64,
+    pub price: u64,
+    pub order: LegOrder,
+}
+
+impl LegSpec {
+    pub fn market(market_index: u8, is_ask: bool, base_amount: i64, worst_price: u64) -> Self {
+        Self {
+            market_index,
+            is_ask,
+            base_amount,
+            price: worst_price,
+            order: LegOrder::Market,
+        }
+    }
+
+    pub fn limit(market_index: u8, is_ask: bool, base_amount: i64, price: u64) -> Self {
+        Self {
+            market_index,
+            is_ask,
+            base_amount,
+            price,
+            order: LegOrder::Limit,
+        }
+    }
+}
+
+/// What [`MultiLegExecutor::execute`] does when a leg doesn't fill in full
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LegFailurePolicy {
+    /// How long the legs have to fill, together
+    pub fill_timeout: Duration,
+    /// How long to wait for a cancelled leg or an unwind to settle
+    pub settle_timeout: Duration,
+    /// Unwind what filled; otherwise the fills are kept and only reported
+    pub flatten: bool,
+    /// How far through the last trade price unwinds may fill
+    pub max_slippage_bps: Decimal,
+    /// Unwind orders sent per leg before giving up
+    pub rollback_attempts: u32,
+    /// Wait between unwind attempts
+    pub rollback_backoff: Duration,
+}
+
+impl Default for LegFailurePolicy {
+    fn default() -> Self {
+        Self {
+            fill_timeout: Duration::from_secs(10),
+            settle_timeout: Duration::from_secs(5),
+            flatten: true,
+            max_slippage_bps: Decimal::from(50),
+            rollback_attempts: 5,
+            rollback_backoff: Duration::from_millis(500),
+        }
+    }
+}
+
+/// What happened to one leg
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LegReport {
+    pub spec: LegSpec,
+    pub client_order_index: i64,
+    /// Last state seen by the tracker
+    pub state: OrderState,
+    /// Why the leg couldn't be sent, if it couldn't
+    pub error: Option<String>,
+    /// Base amount filled, as the account channel reports it
+    pub filled: Decimal,
+    /// Part of `filled` unwound again
+    pub rolled_back: Decimal,
+    /// Unwind orders sent
+    pub rollback_attempts: u32,
+    /// Why the last unwind attempt failed, if the unwind is incomplete
+    pub rollback_error: Option<String>,
+}
+
+impl LegReport {
+    /// Position left from this leg: positive when long, negative when short
+    pub fn residual(&self) -> Decimal {
+        let residual = self.filled - self.rolled_back;
+        if self.spec.is_ask {
+            -residual
+        } else {
+            residual
+        }
+    }
+
+    /// Whether the leg filled in full
+    pub fn is_filled(&self) -> bool {
+        self.state == OrderState::Filled
+    }
+}
+
+/// Outcome of [`MultiLegExecutor::execute`], with legs in input order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MultiLegReport {
+    /// Every leg filled in full and nothing was unwound
+    pub completed: bool,
+    pub legs: Vec<LegReport>,
+}
+
+impl MultiLegReport {
+    /// Position left by the trade per market, leaving out flat markets
+    pub fn residual_exposure(&self) -> Vec<(u8, Decimal)> {
+        let mut exposure: Vec<(u8, Decimal)> = Vec::new();
+        for leg in &self.legs {
+            let market_index = leg.spec.market_index;
+            match exposure
+                .iter_mut()
+                .find(|(market, _)| *market == market_index)
+            {
+                Some((_, size)) => *size += leg.residual(),
+                None => exposure.push((market_index, leg.residual())),
+            }
+        }
+        exposure.retain(|(_, size)| !size.is_zero());
+        exposure
+    }
+
+    /// Whether the trade left no position behind
+    pub fn is_flat(&self) -> bool {
+        self.residual_exposure().is_empty()
+    }
+}
+
+/// Sends the legs of a trade together and unwinds partial executions
+#[derive(Clone)]
+pub struct MultiLegExecutor {
+    tracker: OrderTracker,
+}
+
+impl MultiLegExecutor {
+    /// Execute through `tracker` and its client
+    pub fn new(tracker: OrderTracker) -> Self {
+        Self { tracker }
+    }
+
+    /// Send `legs` together and wait for them to fill
+    ///
+    /// Fails only if a leg can't be signed, in which case nothing was sent.
+    /// Once the legs are sent the outcome, including failed sends and unwinds,
+    /// is in the report.
+    pub async fn execute(
+        &self,
+        legs: Vec<LegSpec>,
+        policy: LegFailurePolicy,
+    ) -> Result<MultiLegReport> {
+        let tx_client = self.tracker.tx_client();
+        let mut signed = Vec::with_capacity(legs.len());
+        for spec in &legs {
+            match self.sign(spec).await {
+                Ok(tx) => signed.push(tx),
+                Err(e) => {
+                    // The nonces already taken will never be used
+                    tx_client
+                        .nonces()
+                        .invalidate(tx_client.account_index(), tx_client.api_key_index());
+                    return Err(e);
+                }
+            }
+        }
+
+        let sent = join_all(signed.iter().map(|tx| self.tracker.submit(tx))).await;
+        let mut reports: Vec<LegReport> = legs
+            .into_iter()
+            .zip(&signed)
+            .zip(sent)
+            .map(|((spec, tx), sent)| LegReport {
+                spec,
+                client_order_index: tx.client_order_index,
+                state: OrderState::Submitted,
+                error: match sent {
+                    Ok(response) if response.is_success() => None,
+                    Ok(response) => Some(format!(
+                        "Rejected with code {}: {}",
+                        response.code,
+                        response.message.unwrap_or_default()
+                    )),
+                    Err(e) => Some(e.to_string()),
+                },
+                filled: Decimal::ZERO,
+                rolled_back: Decimal::ZERO,
+                rollback_attempts: 0,
+                rollback_error: None,
+            })
+            .collect();
+
+        self.await_fills(&reports, policy.fill_timeout).await;
+        self.cancel_open(&reports, policy.settle_timeout).await;
+
+        let mut size_decimals = HashMap::new();
+        for leg in &mut reports {
+            let Some(tracked) = self.tracker.order(leg.client_order_index) else {
+                continue;
+            };
+            leg.state = tracked.state;
+            leg.filled = if tracked.state == OrderState::Filled {
+                let decimals = self
+                    .size_decimals(leg.spec.market_index, &mut size_decimals)
+                    .await;
+                Decimal::new(leg.spec.base_amount, decimals)
+            } else {
+                tracked.filled_base_amount
+            };
+        }
+
+        let completed = reports.iter().all(LegReport::is_filled);
+        if !completed && policy.flatten {
+            join_all(
+                reports
+                    .iter_mut()
+                    .filter(|leg| !leg.filled.is_zero())
+                    .map(|leg| self.roll_back(leg, &policy)),
+            )
+            .await;
+        }
+        Ok(MultiLegReport {
+            completed,
+            legs: reports,
+        })
+    }
+
+    async fn sign(&self, spec: &LegSpec) -> Result<L2CreateOrderTxInfo> {
+        let tx_client = self.tracker.tx_client();
+        let client_order_index = tx_client.next_client_order_index();
+        let is_ask = u8::from(spec.is_ask);
+        match spec.order {
+            LegOrder::Market => {
+                tx_client
+                    .create_market_order(
+                        spec.market_index,
+                        client_order_index,
+                        spec.base_amount,
+                        spec.price,
+                        is_ask,
+                        false,
+                        None,
+                    )
+                    .await
+            }
+            LegOrder::Limit => {
+                tx_client
+                    .create_limit_order(
+                        spec.market_index,
+                        client_order_index,
+                        spec.base_amount,
+                        spec.price,
+                        is_ask,
+                        false,
+                        None,
+                    )
+                    .await
+            }
+        }
+    }
+
+    /// Wait until every leg has filled, one has failed, or `timeout` passes
+    async fn await_fills(&self, legs: &[LegReport], timeout: Duration) {
+        let clock = self.tracker.tx_client().clock();
+        let deadline = clock.now_instant() + timeout;
+        let mut changes = self.tracker.changes();
+        loop {
+            let mut all_filled = true;
+            for leg in legs {
+                match self.tracker.state(leg.client_order_index) {
+                    Some(OrderState::Filled) => {}
+                    Some(state) if state.is_terminal() => return,
+                    _ if leg.error.is_some() => return,
+                    _ => all_filled = false,
+                }
+            }
+            if all_filled {
+                return;
+            }
+            let remaining = deadline.saturating_duration_since(clock.now_instant());
+            if remaining.is_zero() {
+                return;
+            }
+            tokio::select! {
+                _ = changes.changed() => {}
+                _ = clock.sleep(remaining) => return,
+            }
+        }
+    }
+
+    /// Cancel the legs still on the book and give them `timeout` to settle
+    async fn cancel_open(&self, legs: &[LegReport], timeout: Duration) {
+        let tx_client = self.tracker.tx_client();
+        let cancels = legs.iter().filter_map(|leg| {
+            let tracked = self.tracker.order(leg.client_order_index)?;
+            if !tracked.state.is_open() {
+                return None;
+            }
+            let Some(index) = tracked.order_index else {
+                tracing::warn!(
+                    client_order_index = leg.client_order_index,
+                    "Can't cancel a leg the exchange hasn't reported yet"
+                );
+                return None;
+            };
+            Some(async move {
+                let cancel = CancelOrderTxReq {
+                    market_index: leg.spec.market_index,
+                    index,
+                };
+                let sent = match tx_client.cancel_order(&cancel, None).await {
+                    Ok(tx) => tx_client.send_transaction(&tx).await,
+                    Err(e) => Err(e),
+                };
+                if let Err(e) = sent {
+                    tracing::warn!(
+                        client_order_index = leg.client_order_index,
+                        error = %e,
+                        "Failed to cancel leg"
+                    );
+                }
+                let _ = self
+                    .tracker
+                    .await_terminal(leg.client_order_index, timeout)
+                    .await;
+            })
+        });
+        join_all(cancels).await;
+    }
+
+    /// Unwind a leg's fill with reduce-only orders until none of it is left
+    /// or the attempts run out
+    async fn roll_back(&self, leg: &mut LegReport, policy: &LegFailurePolicy) {
+        let tx_client = self.tracker.tx_client();
+        while leg.rollback_attempts < policy.rollback_attempts {
+            let remaining = leg.residual();
+            if remaining.is_zero() {
+                leg.rollback_error = None;
+                return;
+            }
+            if leg.rollback_attempts > 0 {
+                tx_client.count_retries(1);
+                tx_client.clock().sleep(policy.rollback_backoff).await;
+            }
+            leg.rollback_attempts += 1;
+
+            let mut progress = Progress::new(None);
+            let reduce = match tx_client
+                .sign_reduce_order(
+                    leg.spec.market_index,
+                    Reduce::Exposure(remaining),
+                    None,
+                    policy.max_slippage_bps,
+                    &mut progress,
+                )
+                .await
+            {
+                Ok(Some(reduce)) => reduce,
+                Ok(None) => {
+                    leg.rollback_error = Some("No position left to reduce".to_string());
+                    continue;
+                }
+                Err(e) => {
+                    leg.rollback_error = Some(e.to_string());
+                    continue;
+                }
+            };
+            match self.tracker.submit(&reduce.tx).await {
+                Ok(response) if response.is_success() => {}
+                Ok(response) => {
+                    leg.rollback_error = Some(format!(
+                        "Rejected with code {}: {}",
+                        response.code,
+                        response.message.unwrap_or_default()
+                    ));
+                    continue;
+                }
+                Err(e) => {
+                    leg.rollback_error = Some(e.to_string());
+                    continue;
+                }
+            }
+
+            let client_order_index = reduce.tx.client_order_index;
+            let _ = self
+                .tracker
+                .await_terminal(client_order_index, policy.settle_timeout)
+                .await;
+            let unwound = match self.tracker.order(client_order_index) {
+                Some(tracked) if tracked.state == OrderState::Filled => reduce.size.abs(),
+                Some(tracked) => tracked.filled_base_amount,
+                None => Decimal::ZERO,
+            };
+            leg.rolled_back += unwound;
+            if unwound < reduce.size.abs() {
+                leg.rollback_error =
+                    Some(format!("Unwind filled {unwound} of {}", reduce.size.abs()));
+            }
+        }
+        if leg.residual().is_zero() {
+            leg.rollback_error = None;
+        } else {
+            tracing::error!(
+                market_index = leg.spec.market_index,
+                residual = %leg.residual(),
+                error = ?leg.rollback_error,
+                "Failed to unwind leg"
+            );
+        }
+    }
+
+    /// Size decimals of a market, fetched once per execution
+    async fn size_decimals(&self, market_index: u8, known: &mut HashMap<u8, u32>) -> u32 {
+        if let Some(&decimals) = known.get(&market_index) {
+            return decimals;
+        }
+        let details = match self.tracker.tx_client().http_client() {
+            Ok(http) => http.get_market_details(market_index).await,
+            Err(e) => Err(e),
+        };
+        let decimals = details.map_or_else(
+            |e| {
+                tracing::warn!(market_index, error = %e, "Can't get size decimals; assuming none");
+                0
+            },
+            |details| details.size_decimals,
+        );
+        known.insert(market_index, decimals);
+        decimals
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::mock_builder;
+    use crate::tracker::OrderEvent;
+    use crate::transport::{HttpResponse, MockTransport};
+    use serde_json::json;
+    use std::sync::Arc;
+
+    const SEND_TX_PATH: &str = "/api/v1/sendTx";
+
+    fn setup() -> (OrderTracker, Arc<MockTransport>) {
+        let mock = Arc::new(MockTransport::new());
+        mock.set_handler(SEND_TX_PATH, |_| {
+            Ok(HttpResponse::new(200, r#"{"code":200,"tx_hash":"0xabc"}"#))
+        });
+        mock.set_handler("/api/v1/account", |_| {
+            let positions = json!([
+                {"market_id": 0, "sign": 1, "position": "0.1000", "avg_entry_price": "3000.00"},
+            ]);
+            Ok(HttpResponse::new(
+                200,
+                json!({"code": 200, "accounts": [{"positions": positions}]}).to_string(),
+            ))
+        });
+        mock.set_handler("/api/v1/orderBookDetails", |_| {
+            Ok(HttpResponse::new(
+                200,
+                r#"{"code":200,"order_book_details":[{"market_id":0,"size_decimals":4,"price_decimals":2,"last_trade_price":"3000.00"}]}"#,
+            ))
+        });
+        let tx_client = mock_builder(&mock).build().unwrap();
+        tx_client.nonces().set(1, 0, 0);
+        (OrderTracker::new(Arc::new(tx_client)), mock)
+    }
+
+    /// Settles every acknowledged order as the exchange would: market 0
+    /// fills, market 1 is cancelled unfilled
+    fn exchange(tracker: &OrderTracker) -> tokio::task::JoinHandle<()> {
+        let tracker = tracker.clone();
+        tokio::spawn(async move {
+            loop {
+                for order in tracker.open_orders() {
+                    if order.state != OrderState::Acknowledged {
+                        continue;
+                    }
+                    let state = match order.market_index {
+                        0 => OrderState::Filled,
+                        _ => OrderState::Cancelled,
+                    };
+                    tracker.apply(OrderEvent::Update {
+                        client_order_index: order.client_order_index,
+                        order_index: order.client_order_index,
+                        market_index: order.market_index,
+                        state,
+                    });
+                }
+                tokio::time::sleep(Duration::from_millis(2)).await;
+            }
+        })
+    }
+
+    fn policy() -> LegFailurePolicy {
+        LegFailurePolicy {
+            fill_timeout: Duration::from_secs(2),
+            settle_timeout: Duration::from_secs(2),
+            rollback_backoff: Duration::from_millis(1),
+            ..LegFailurePolicy::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_filled_legs_complete() {
+        let (tracker, mock) = setup();
+        let exchange = exchange(&tracker);
+
+        let report = MultiLegExecutor::new(tracker)
+            .execute(
+                vec![
+                    LegSpec::market(0, false, 1000, 310_000),
+                    LegSpec::limit(0, true, 500, 290_000),
+                ],
+                policy(),
+            )
+            .await
+            .unwrap();
+        exchange.abort();
+
+        assert!(report.completed);
+        assert_eq!(report.legs[0].filled, Decimal::new(1000, 4));
+        assert_eq!(report.residual_exposure(), vec![(0, Decimal::new(500, 4))]);
+        assert_eq!(mock.requests_to(SEND_TX_PATH).len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_failed_leg_rolls_back_the_other() {
+        let (tracker, mock) = setup();
+        let exchange = exchange(&tracker);
+        // The first unwind is rejected and retried
+        mock.push_response(SEND_TX_PATH, 200, r#"{"code":200,"tx_hash":"0x1"}"#);
+        mock.push_response(SEND_TX_PATH, 200, r#"{"code":200,"tx_hash":"0x2"}"#);
+        mock.push_response(SEND_TX_PATH, 200, r#"{"code":21120,"message":"busy"}"#);
+
+        let report = MultiLegExecutor::new(tracker.clone())
+            .execute(
+                vec![
+                    LegSpec::market(0, false, 1000, 310_000),
+                    LegSpec::market(1, true, 2000, 6_000),
+                ],
+                policy(),
+            )
+            .await
+            .unwrap();
+        exchange.abort();
+
+        assert!(!report.completed);
+        let long = &report.legs[0];
+        assert_eq!(long.state, OrderState::Filled);
+        assert_eq!(long.filled, Decimal::new(1000, 4));
+        assert_eq!(long.rolled_back, long.filled);
+        assert_eq!(long.rollback_attempts, 2);
+        assert_eq!(long.rollback_error, None);
+        assert_eq!(report.legs[1].state, OrderState::Cancelled);
+        assert_eq!(report.legs[1].rollback_attempts, 0);
+        assert!(report.is_flat());
+
+        let sends = mock.requests_to(SEND_TX_PATH);
+        assert_eq!(sends.len(), 4);
+        // Unwinds are reduce-only asks
+        let body = serde_urlencoded::from_bytes::<Vec<(String, String)>>(&sends[3].body).unwrap();
+        let tx_info = &body.iter().find(|(key, _)| key == "tx_info").unwrap().1;
+        let tx_info: serde_json::Value = serde_json::from_str(tx_info).unwrap();
+        assert_eq!(tx_info["ReduceOnly"], 1);
+        assert_eq!(tx_info["IsAsk"], 1);
+        assert_eq!(tx_info["BaseAmount"], 1000);
+    }
+}
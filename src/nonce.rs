@@ -0,0 +1,280 @@
+//! Local nonce allocation for transaction signing
+//!
+//! Once the next nonce for an (account, API key) pair is known, further nonces
+//! are handed out with a synchronous atomic increment, so signing never has to
+//! wait on a network round trip.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, RwLock};
+
+use serde::{Deserialize, Serialize};
+
+/// Next nonce of one key, as returned by [`NonceManager::export`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NonceSnapshot {
+    pub account_index: i64,
+    pub api_key_index: u8,
+    pub next_nonce: i64,
+}
+
+/// Cache of the next nonce per (account index, API key index)
+#[derive(Debug, Default)]
+pub struct NonceManager {
+    next: RwLock<HashMap<(i64, u8), Arc<AtomicI64>>>,
+}
+
+impl NonceManager {
+    /// Create an empty nonce cache
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allocate the next nonce if the cache is warm for this key
+    pub fn try_next(&self, account_index: i64, api_key_index: u8) -> Option<i64> {
+        self.try_reserve(account_index, api_key_index, 1)
+    }
+
+    /// Reserve `count` consecutive nonces if the cache is warm, returning the first
+    pub fn try_reserve(&self, account_index: i64, api_key_index: u8, count: i64) -> Option<i64> {
+        let next = self.next.read().unwrap_or_else(|e| e.into_inner());
+        next.get(&(account_index, api_key_index))
+            .map(|counter| counter.fetch_add(count, Ordering::SeqCst))
+    }
+
+    /// Allocate a nonce, seeding the cache with `server_nonce` if it is still cold
+    ///
+    /// If another caller seeded the cache in the meantime, its counter wins and
+    /// `server_nonce` is ignored so two callers never receive the same nonce.
+    pub fn next_or_seed(&self, account_index: i64, api_key_index: u8, server_nonce: i64) -> i64 {
+        self.reserve_or_seed(account_index, api_key_index, server_nonce, 1)
+    }
+
+    /// Reserve `count` consecutive nonces, seeding the cache if it is still cold
+    pub fn reserve_or_seed(
+        &self,
+        account_index: i64,
+        api_key_index: u8,
+        server_nonce: i64,
+        count: i64,
+    ) -> i64 {
+        let mut next = self.next.write().unwrap_or_else(|e| e.into_inner());
+        next.entry((account_index, api_key_index))
+            .or_insert_with(|| Arc::new(AtomicI64::new(server_nonce)))
+            .fetch_add(count, Ordering::SeqCst)
+    }
+
+    /// Set the next nonce to hand out, replacing any cached value
+    pub fn set(&self, account_index: i64, api_key_index: u8, next_nonce: i64) {
+        let mut next = self.next.write().unwrap_or_else(|e| e.into_inner());
+        next.insert(
+            (account_index, api_key_index),
+            Arc::new(AtomicI64::new(next_nonce)),
+        );
+    }
+
+    /// Give `nonce` back if it is the last one handed out for this key
+    ///
+    /// Returns false, changing nothing, once a later nonce was handed out:
+    /// `nonce` then has to be used up by some other transaction.
+    pub fn release(&self, account_index: i64, api_key_index: u8, nonce: i64) -> bool {
+        self.release_range(account_index, api_key_index, nonce, 1)
+    }
+
+    /// Give back the `count` nonces from `first` if they are the last ones
+    /// handed out for this key, like [`NonceManager::release`]
+    pub fn release_range(
+        &self,
+        account_index: i64,
+        api_key_index: u8,
+        first: i64,
+        count: i64,
+    ) -> bool {
+        let next = self.next.read().unwrap_or_else(|e| e.into_inner());
+        next.get(&(account_index, api_key_index))
+            .is_some_and(|counter| {
+                counter
+                    .compare_exchange(first + count, first, Ordering::SeqCst, Ordering::SeqCst)
+                    .is_ok()
+            })
+    }
+
+    /// Peek at the next nonce without allocating it
+    pub fn peek(&self, account_index: i64, api_key_index: u8) -> Option<i64> {
+        let next = self.next.read().unwrap_or_else(|e| e.into_inner());
+        next.get(&(account_index, api_key_index))
+            .map(|counter| counter.load(Ordering::SeqCst))
+    }
+
+    /// Snapshot of every cached nonce, ordered by key
+    pub fn export(&self) -> Vec<NonceSnapshot> {
+        let next = self.next.read().unwrap_or_else(|e| e.into_inner());
+        let mut snapshots: Vec<NonceSnapshot> = next
+            .iter()
+            .map(|(&(account_index, api_key_index), counter)| NonceSnapshot {
+                account_index,
+                api_key_index,
+                next_nonce: counter.load(Ordering::SeqCst),
+            })
+            .collect();
+        snapshots.sort_by_key(|snapshot| (snapshot.account_index, snapshot.api_key_index));
+        snapshots
+    }
+
+    /// Forget the cached nonce for one key so the next allocation refetches it
+    pub fn invalidate(&self, account_index: i64, api_key_index: u8) {
+        let mut next = self.next.write().unwrap_or_else(|e| e.into_inner());
+        next.remove(&(account_index, api_key_index));
+    }
+
+    /// Forget all cached nonces
+    pub fn invalidate_all(&self) {
+        let mut next = self.next.write().unwrap_or_else(|e| e.into_inner());
+        next.clear();
+    }
+}
+
+/// Nonces taken from a [`NonceManager`] for transactions being signed
+///
+/// Dropping it before [`NonceReservation::keep`] gives them back, so a
+/// transaction refused before it is sent leaves no gap for later ones to
+/// be rejected over.
+#[must_use]
+pub(crate) struct NonceReservation<'a> {
+    nonces: &'a NonceManager,
+    /// Account, API key, first nonce and count; `None` when nothing was
+    /// taken from the cache, as for a nonce given by the caller
+    range: Option<(i64, u8, i64, i64)>,
+}
+
+impl<'a> NonceReservation<'a> {
+    pub(crate) fn new(
+        nonces: &'a NonceManager,
+        account_index: i64,
+        api_key_index: u8,
+        first: i64,
+        count: i64,
+    ) -> Self {
+        Self {
+            nonces,
+            range: Some((account_index, api_key_index, first, count)),
+        }
+    }
+
+    /// A reservation of nothing
+    pub(crate) fn none(nonces: &'a NonceManager) -> Self {
+        Self {
+            nonces,
+            range: None,
+        }
+    }
+
+    /// Keep the nonces, once the transactions using them are signed
+    pub(crate) fn keep(mut self) {
+        self.range = None;
+    }
+}
+
+impl Drop for NonceReservation<'_> {
+    fn drop(&mut self) {
+        if let Some((account_index, api_key_index, first, count)) = self.range {
+            self.nonces
+                .release_range(account_index, api_key_index, first, count);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cold_cache_returns_none() {
+        let nonces = NonceManager::new();
+        assert_eq!(nonces.try_next(1, 0), None);
+    }
+
+    #[test]
+    fn test_seed_then_increment() {
+        let nonces = NonceManager::new();
+        assert_eq!(nonces.next_or_seed(1, 0, 10), 10);
+        assert_eq!(nonces.try_next(1, 0), Some(11));
+        assert_eq!(nonces.try_next(1, 0), Some(12));
+        // A racing seed does not reset the counter
+        assert_eq!(nonces.next_or_seed(1, 0, 10), 13);
+        // Other keys are independent
+        assert_eq!(nonces.try_next(1, 1), None);
+    }
+
+    #[test]
+    fn test_reserve_block() {
+        let nonces = NonceManager::new();
+        assert_eq!(nonces.reserve_or_seed(1, 0, 10, 3), 10);
+        assert_eq!(nonces.try_reserve(1, 0, 2), Some(13));
+        assert_eq!(nonces.try_next(1, 0), Some(15));
+    }
+
+    #[test]
+    fn test_export() {
+        let nonces = NonceManager::new();
+        nonces.set(2, 0, 7);
+        nonces.set(1, 3, 5);
+        assert_eq!(nonces.try_next(1, 3), Some(5));
+        assert_eq!(
+            nonces.export(),
+            vec![
+                NonceSnapshot {
+                    account_index: 1,
+                    api_key_index: 3,
+                    next_nonce: 6
+                },
+                NonceSnapshot {
+                    account_index: 2,
+                    api_key_index: 0,
+                    next_nonce: 7
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_invalidate() {
+        let nonces = NonceManager::new();
+        nonces.set(1, 0, 5);
+        assert_eq!(nonces.peek(1, 0), Some(5));
+        nonces.invalidate(1, 0);
+        assert_eq!(nonces.try_next(1, 0), None);
+    }
+
+    #[test]
+    fn test_release_only_the_last_nonce() {
+        let nonces = NonceManager::new();
+        assert!(!nonces.release(1, 0, 4));
+        nonces.set(1, 0, 5);
+        assert_eq!(nonces.try_next(1, 0), Some(5));
+        assert_eq!(nonces.try_next(1, 0), Some(6));
+        assert!(!nonces.release(1, 0, 5));
+        assert!(nonces.release(1, 0, 6));
+        assert_eq!(nonces.try_next(1, 0), Some(6));
+    }
+
+    #[test]
+    fn test_reservation_is_given_back_unless_kept() {
+        let nonces = NonceManager::new();
+        nonces.set(1, 0, 5);
+        let first = nonces.try_reserve(1, 0, 3).unwrap();
+        drop(NonceReservation::new(&nonces, 1, 0, first, 3));
+        assert_eq!(nonces.peek(1, 0), Some(5));
+
+        let first = nonces.try_reserve(1, 0, 3).unwrap();
+        NonceReservation::new(&nonces, 1, 0, first, 3).keep();
+        assert_eq!(nonces.peek(1, 0), Some(8));
+
+        // Nonces handed out after it stay taken
+        let first = nonces.try_reserve(1, 0, 2).unwrap();
+        let reservation = NonceReservation::new(&nonces, 1, 0, first, 2);
+        assert_eq!(nonces.try_next(1, 0), Some(10));
+        drop(reservation);
+        assert_eq!(nonces.peek(1, 0), Some(11));
+    }
+}
@@ -0,0 +1,328 @@
+//! Healing the gap a dropped submission leaves in the nonce sequence
+//!
+//! A transaction signed with nonce N from the local cache but never
+//! delivered, because the process crashed or a deadline aborted the request,
+//! burns N locally while the exchange still waits for it, so N+1, N+2... are
+//! all rejected. [`TxClient::heal_nonce_gap`](crate::client::TxClient::heal_nonce_gap)
+//! compares the local counter with the exchange's next nonce and picks one of
+//! the [`NonceGapAction`]s, see [`decide_gap_action`]:
+//!
+//! - counters that agree are left alone
+//! - a local counter behind the exchange, because another process signed
+//!   with the key, is moved forward
+//! - a local counter ahead of the exchange is rewound when no submission is
+//!   in flight, as nothing signed past the gap can still land
+//! - with submissions in flight, rewinding would hand their nonces out
+//!   twice, so the gap is filled instead: a cancel of order index 0, which
+//!   no order has, is signed for each missing nonce and sent in one batch
+//!
+//! With [`TxClientBuilder::nonce_healing`](crate::client::TxClientBuilder::nonce_healing)
+//! set, the client heals on its own after
+//! [`NonceHealing::rejections_before_healing`] nonce rejections in a row,
+//! instead of dropping the nonce cache on every rejection, and at most once
+//! per [`NonceHealing::min_interval`] so a burst of rejections doesn't flood
+//! the exchange with nonce requests and fillers.
+//!
+//! ```
+//! use lighter_rs::nonce_gap::{decide_gap_action, NonceGapAction};
+//!
+//! // Nonce 7 was burned by a dropped submission; 8 and 9 are in flight
+//! assert_eq!(
+//!     decide_gap_action(Some(10), 7, 2, 16),
+//!     NonceGapAction::FillGap { from: 7, to: 10 }
+//! );
+//! // Once nothing is in flight, the counter can simply move back
+//! assert_eq!(
+//!     decide_gap_action(Some(10), 7, 0, 16),
+//!     NonceGapAction::Rewind { from: 10, to: 7 }
+//! );
+//! ```
+
+use std::fmt;
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Default of [`NonceHealing::rejections_before_healing`]
+const DEFAULT_REJECTIONS_BEFORE_HEALING: u32 = 2;
+
+/// Default of [`NonceHealing::min_interval`]
+const DEFAULT_MIN_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Default of [`NonceHealing::max_gap_fill`]
+const DEFAULT_MAX_GAP_FILL: i64 = 16;
+
+/// Order index the gap fillers cancel; no order has it
+pub const FILLER_ORDER_INDEX: i64 = 0;
+
+/// Settings of automatic nonce gap healing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NonceHealing {
+    /// Nonce rejections in a row that start healing; a single one is often
+    /// a race with another signer that resolves itself
+    pub rejections_before_healing: u32,
+    /// Shortest time between two heals
+    pub min_interval: Duration,
+    /// Most fillers signed to close one gap; a wider gap is left for a
+    /// later heal, once nothing is in flight and a rewind is safe
+    pub max_gap_fill: i64,
+    /// Market of the filler cancels
+    pub filler_market_index: u8,
+}
+
+impl NonceHealing {
+    pub fn new() -> Self {
+        Self {
+            rejections_before_healing: DEFAULT_REJECTIONS_BEFORE_HEALING,
+            min_interval: DEFAULT_MIN_INTERVAL,
+            max_gap_fill: DEFAULT_MAX_GAP_FILL,
+            filler_market_index: 0,
+        }
+    }
+
+    pub fn rejections_before_healing(mut self, rejections: u32) -> Self {
+        self.rejections_before_healing = rejections.max(1);
+        self
+    }
+
+    pub fn min_interval(mut self, min_interval: Duration) -> Self {
+        self.min_interval = min_interval;
+        self
+    }
+
+    pub fn max_gap_fill(mut self, max_gap_fill: i64) -> Self {
+        self.max_gap_fill = max_gap_fill;
+        self
+    }
+
+    pub fn filler_market_index(mut self, market_index: u8) -> Self {
+        self.filler_market_index = market_index;
+        self
+    }
+}
+
+impl Default for NonceHealing {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// What healing did about the local counter and the exchange's next nonce
+///
+/// `from` and `to` are next nonces: the local counter moved from `from` to
+/// `to`, or nonces `from..to` were filled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NonceGapAction {
+    /// Nothing to heal; a cold cache is seeded with the exchange's nonce
+    InSync { next_nonce: i64 },
+    /// The exchange was ahead, so the local counter moved forward
+    FastForward { from: i64, to: i64 },
+    /// The local counter was ahead with nothing in flight, so it moved back
+    Rewind { from: i64, to: i64 },
+    /// The local counter was ahead with submissions in flight, so fillers
+    /// were sent for the missing nonces
+    FillGap { from: i64, to: i64 },
+    /// Fillers were needed for more nonces than
+    /// [`NonceHealing::max_gap_fill`]; nothing was done
+    GapTooWide { from: i64, to: i64 },
+}
+
+impl fmt::Display for NonceGapAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            NonceGapAction::InSync { next_nonce } => write!(f, "in sync at {next_nonce}"),
+            NonceGapAction::FastForward { from, to } => {
+                write!(f, "moved forward from {from} to {to}")
+            }
+            NonceGapAction::Rewind { from, to } => write!(f, "rewound from {from} to {to}"),
+            NonceGapAction::FillGap { from, to } => write!(f, "filled nonces {from} to {}", to - 1),
+            NonceGapAction::GapTooWide { from, to } => {
+                write!(f, "left nonces {from} to {} unfilled", to - 1)
+            }
+        }
+    }
+}
+
+/// Decide how to heal a local counter at `local_next` against the
+/// exchange's `server_next`, with `in_flight` submissions awaiting an answer
+///
+/// `local_next` is `None` for a cold cache.
+pub fn decide_gap_action(
+    local_next: Option<i64>,
+    server_next: i64,
+    in_flight: usize,
+    max_gap_fill: i64,
+) -> NonceGapAction {
+    let Some(local_next) = local_next else {
+        return NonceGapAction::InSync {
+            next_nonce: server_next,
+        };
+    };
+    match local_next.cmp(&server_next) {
+        std::cmp::Ordering::Equal => NonceGapAction::InSync {
+            next_nonce: server_next,
+        },
+        std::cmp::Ordering::Less => NonceGapAction::FastForward {
+            from: local_next,
+            to: server_next,
+        },
+        std::cmp::Ordering::Greater if in_flight == 0 => NonceGapAction::Rewind {
+            from: local_next,
+            to: server_next,
+        },
+        std::cmp::Ordering::Greater if local_next - server_next > max_gap_fill => {
+            NonceGapAction::GapTooWide {
+                from: server_next,
+                to: local_next,
+            }
+        }
+        std::cmp::Ordering::Greater => NonceGapAction::FillGap {
+            from: server_next,
+            to: local_next,
+        },
+    }
+}
+
+/// Submissions in flight and the rejections counted towards healing
+#[derive(Debug, Default)]
+pub(crate) struct NonceGapState {
+    healing: Option<NonceHealing>,
+    in_flight: AtomicUsize,
+    rejections: AtomicU32,
+    last_heal: Mutex<Option<Instant>>,
+}
+
+impl NonceGapState {
+    pub fn new(healing: Option<NonceHealing>) -> Self {
+        Self {
+            healing,
+            ..Self::default()
+        }
+    }
+
+    pub fn healing(&self) -> Option<NonceHealing> {
+        self.healing
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::SeqCst)
+    }
+
+    /// Count `count` submissions in flight until the guard is dropped
+    pub fn sending(&self, count: usize) -> InFlight<'_> {
+        self.in_flight.fetch_add(count, Ordering::SeqCst);
+        InFlight { state: self, count }
+    }
+
+    /// A submission got an answer other than a nonce rejection
+    pub fn accepted(&self) {
+        self.rejections.store(0, Ordering::Relaxed);
+    }
+
+    /// Count a nonce rejection; true when it is time to heal
+    ///
+    /// Always false without automatic healing.
+    pub fn rejected(&self, now: Instant) -> bool {
+        let Some(healing) = self.healing else {
+            return false;
+        };
+        let rejections = self.rejections.fetch_add(1, Ordering::Relaxed) + 1;
+        if rejections < healing.rejections_before_healing {
+            return false;
+        }
+        let mut last_heal = self
+            .last_heal
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if last_heal.is_some_and(|last| now.saturating_duration_since(last) < healing.min_interval)
+        {
+            return false;
+        }
+        *last_heal = Some(now);
+        self.rejections.store(0, Ordering::Relaxed);
+        true
+    }
+}
+
+/// Submissions counted in flight, see [`NonceGapState::sending`]
+pub(crate) struct InFlight<'a> {
+    state: &'a NonceGapState,
+    count: usize,
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.state.in_flight.fetch_sub(self.count, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_divergence_direction() {
+        assert_eq!(
+            decide_gap_action(None, 7, 3, 16),
+            NonceGapAction::InSync { next_nonce: 7 }
+        );
+        assert_eq!(
+            decide_gap_action(Some(7), 7, 3, 16),
+            NonceGapAction::InSync { next_nonce: 7 }
+        );
+        assert_eq!(
+            decide_gap_action(Some(5), 7, 3, 16),
+            NonceGapAction::FastForward { from: 5, to: 7 }
+        );
+        assert_eq!(
+            decide_gap_action(Some(10), 7, 0, 16),
+            NonceGapAction::Rewind { from: 10, to: 7 }
+        );
+        assert_eq!(
+            decide_gap_action(Some(10), 7, 1, 16),
+            NonceGapAction::FillGap { from: 7, to: 10 }
+        );
+        assert_eq!(
+            decide_gap_action(Some(10), 7, 1, 3),
+            NonceGapAction::FillGap { from: 7, to: 10 }
+        );
+        assert_eq!(
+            decide_gap_action(Some(10), 7, 1, 2),
+            NonceGapAction::GapTooWide { from: 7, to: 10 }
+        );
+    }
+
+    #[test]
+    fn test_healing_waits_for_persistent_rejections_and_is_throttled() {
+        let state = NonceGapState::new(Some(
+            NonceHealing::new()
+                .rejections_before_healing(2)
+                .min_interval(Duration::from_secs(1)),
+        ));
+        let start = Instant::now();
+        assert!(!state.rejected(start));
+        assert!(state.rejected(start));
+
+        // Throttled within the interval, however many rejections
+        for _ in 0..5 {
+            assert!(!state.rejected(start + Duration::from_millis(500)));
+        }
+        assert!(state.rejected(start + Duration::from_secs(1)));
+
+        // An accepted submission starts the count over
+        let later = start + Duration::from_secs(5);
+        assert!(!state.rejected(later));
+        state.accepted();
+        assert!(!state.rejected(later));
+        assert!(state.rejected(later));
+
+        // Without healing configured, never
+        let state = NonceGapState::new(None);
+        assert!((0..5).all(|_| !state.rejected(start)));
+
+        let guard = state.sending(2);
+        assert_eq!(state.in_flight(), 2);
+        drop(guard);
+        assert_eq!(state.in_flight(), 0);
+    }
+}
@@ -0,0 +1,229 @@
+//! Warnings for orders nearing their expiry
+//!
+//! Good-till-time orders leave the book once their expiry passes, 28 days
+//! out for [`TxClient::create_limit_order`](crate::client::TxClient::create_limit_order),
+//! and a bot that forgot about them is left unhedged. [`ExpiryWatcher`]
+//! checks an [`OrderTracker`] on a timer and broadcasts
+//! [`ExpiryEvent::Approaching`] once for each open order whose expiry falls
+//! within its warning window, then [`ExpiryEvent::Expired`] if the order is
+//! still tracked as open after its expiry. Refresh an order with
+//! [`OrderTracker::refresh_order`].
+//!
+//! The watcher reads the time from and sleeps on the tracker's client
+//! clock, so a [`ManualClock`](crate::clock::ManualClock) given to
+//! [`TxClientBuilder::clock`](crate::client::TxClientBuilder::clock) drives
+//! it in tests.
+//!
+//! ```no_run
+//! use lighter_rs::order_expiry::{ExpiryEvent, ExpiryWatcher};
+//! use lighter_rs::tracker::OrderTracker;
+//! use std::time::Duration;
+//!
+//! # async fn example(tracker: OrderTracker) -> lighter_rs::Result<()> {
+//! let watcher = ExpiryWatcher::new(tracker.clone(), Duration::from_secs(24 * 3600));
+//! let mut events = watcher.subscribe();
+//! tokio::spawn(async move { watcher.run().await });
+//!
+//! while let Ok(event) = events.recv().await {
+//!     if let ExpiryEvent::Approaching { order, .. } = event {
+//!         let in_28_days = tracker.tx_client().clock().now_ms() + 28 * 24 * 3600 * 1000;
+//!         tracker
+//!             .refresh_order(order.client_order_index, in_28_days, None)
+//!             .await?;
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use tokio::sync::broadcast;
+
+use crate::tracker::{OrderTracker, TrackedOrder};
+
+/// Capacity of the event channel; slow subscribers miss older events
+const EVENT_BUFFER: usize = 64;
+
+/// Default of [`ExpiryWatcher::check_interval`]
+const DEFAULT_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// What an [`ExpiryWatcher`] noticed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExpiryEvent {
+    /// An open order expires within the warning window
+    Approaching {
+        order: TrackedOrder,
+        expires_in: Duration,
+    },
+    /// An order still tracked as open is past its expiry, so it has most
+    /// likely left the book
+    Expired { order: TrackedOrder },
+}
+
+/// How far along its expiry an order was last reported
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stage {
+    Approaching,
+    Expired,
+}
+
+/// Watches an [`OrderTracker`] for orders nearing their expiry
+pub struct ExpiryWatcher {
+    tracker: OrderTracker,
+    warn_within: Duration,
+    check_interval: Duration,
+    /// Expiry and stage last reported, by client order index
+    reported: Mutex<HashMap<i64, (i64, Stage)>>,
+    events: broadcast::Sender<ExpiryEvent>,
+}
+
+impl ExpiryWatcher {
+    /// Warn about orders expiring within `warn_within`
+    pub fn new(tracker: OrderTracker, warn_within: Duration) -> Self {
+        let (events, _) = broadcast::channel(EVENT_BUFFER);
+        Self {
+            tracker,
+            warn_within,
+            check_interval: DEFAULT_CHECK_INTERVAL,
+            reported: Mutex::default(),
+            events,
+        }
+    }
+
+    /// Time between two checks of [`ExpiryWatcher::run`]
+    pub fn check_interval(mut self, check_interval: Duration) -> Self {
+        self.check_interval = check_interval;
+        self
+    }
+
+    /// Subscribe to the watcher's events
+    pub fn subscribe(&self) -> broadcast::Receiver<ExpiryEvent> {
+        self.events.subscribe()
+    }
+
+    /// Check the tracked orders now, broadcasting and returning the new
+    /// events
+    ///
+    /// Each order is reported approaching and expired once; an order given a
+    /// new expiry is reported again.
+    pub fn check(&self) -> Vec<ExpiryEvent> {
+        let now_ms = self.tracker.tx_client().clock().now_ms();
+        let expiring = self.tracker.orders_expiring_within(self.warn_within);
+
+        let mut reported = self
+            .reported
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        reported.retain(|client_order_index, _| {
+            expiring
+                .iter()
+                .any(|order| order.client_order_index == *client_order_index)
+        });
+        let mut events = Vec::new();
+        for order in expiring {
+            let Some(expiry) = order.order_expiry else {
+                continue;
+            };
+            let stage = if expiry <= now_ms {
+                Stage::Expired
+            } else {
+                Stage::Approaching
+            };
+            if reported.insert(order.client_order_index, (expiry, stage)) == Some((expiry, stage)) {
+                continue;
+            }
+            events.push(match stage {
+                Stage::Approaching => ExpiryEvent::Approaching {
+                    order,
+                    expires_in: Duration::from_millis((expiry - now_ms) as u64),
+                },
+                Stage::Expired => ExpiryEvent::Expired { order },
+            });
+        }
+        drop(reported);
+
+        for event in &events {
+            // No subscribers is fine
+            let _ = self.events.send(event.clone());
+        }
+        events
+    }
+
+    /// Check every [`ExpiryWatcher::check_interval`] until the task is
+    /// dropped
+    pub async fn run(&self) {
+        let clock = self.tracker.tx_client().clock().clone();
+        loop {
+            self.check();
+            clock.sleep(self.check_interval).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::test_support::mock_builder;
+    use crate::transport::MockTransport;
+    use std::sync::Arc;
+
+    const DAY: Duration = Duration::from_secs(24 * 3600);
+
+    #[tokio::test]
+    async fn test_each_stage_is_reported_once() {
+        let mock = Arc::new(MockTransport::new());
+        mock.set_handler("/api/v1/sendTx", |_| {
+            Ok(crate::transport::HttpResponse::new(200, r#"{"code":200}"#))
+        });
+        let clock = Arc::new(ManualClock::at_ms(1_700_000_000_000));
+        let tx_client = mock_builder(&mock).clock(clock.clone()).build().unwrap();
+        tx_client.nonces().set(1, 0, 0);
+        let tracker = OrderTracker::new(Arc::new(tx_client));
+        let order = tracker
+            .tx_client()
+            .create_limit_order(0, 7, 1000, 300000, 0, false, None)
+            .await
+            .unwrap();
+        tracker.submit(&order).await.unwrap();
+
+        let watcher = ExpiryWatcher::new(tracker.clone(), DAY);
+        let mut events = watcher.subscribe();
+        assert!(watcher.check().is_empty());
+
+        clock.advance(27 * DAY + Duration::from_secs(3600));
+        match watcher.check().as_slice() {
+            [ExpiryEvent::Approaching { order, expires_in }] => {
+                assert_eq!(order.client_order_index, 7);
+                assert_eq!(*expires_in, Duration::from_secs(23 * 3600));
+            }
+            other => panic!("expected one Approaching event, got {other:?}"),
+        }
+        assert!(matches!(
+            events.try_recv(),
+            Ok(ExpiryEvent::Approaching { .. })
+        ));
+        clock.advance(Duration::from_secs(3600));
+        assert!(watcher.check().is_empty());
+
+        clock.advance(DAY);
+        assert!(matches!(
+            watcher.check().as_slice(),
+            [ExpiryEvent::Expired { order }] if order.client_order_index == 7
+        ));
+        assert!(watcher.check().is_empty());
+
+        // Orders that ended are forgotten
+        tracker.apply(crate::tracker::OrderEvent::Update {
+            client_order_index: 7,
+            order_index: 281474976710700,
+            market_index: 0,
+            state: crate::tracker::OrderState::Cancelled,
+        });
+        assert!(watcher.check().is_empty());
+        assert!(watcher.reported.lock().unwrap().is_empty());
+    }
+}
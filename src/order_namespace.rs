@@ -0,0 +1,290 @@
+//! Client order index namespaces for bots sharing one account
+//!
+//! Processes trading the same account can only tell their orders apart if
+//! their client order indexes never overlap. A [`ClientOrderNamespace`]
+//! reserves the indexes whose high bits equal a bot id: with `bits` bits of
+//! id, the 48-bit index space splits into `2^bits` namespaces of
+//! `2^(48 - bits)` indexes each.
+//!
+//! Set one with [`TxClientBuilder::client_order_namespace`](crate::client::TxClientBuilder::client_order_namespace)
+//! and every index the SDK allocates for that client, for ladders, quotes,
+//! stops and the kill switch, falls inside it. After a restart,
+//! [`OrderTracker::adopt_existing`](crate::tracker::OrderTracker::adopt_existing)
+//! picks up the orders a previous run left resting.
+//!
+//! ```
+//! use lighter_rs::order_namespace::ClientOrderNamespace;
+//!
+//! // Bot 3 of up to 256
+//! let namespace = ClientOrderNamespace::new(3, 8)?;
+//! let index = namespace.encode(42)?;
+//! assert_eq!(index, (3 << 40) + 42);
+//! assert_eq!(namespace.decode(index), Some(42));
+//! assert_eq!(ClientOrderNamespace::new(4, 8)?.decode(index), None);
+//! # Ok::<(), lighter_rs::LighterError>(())
+//! ```
+
+use std::sync::atomic::{AtomicI64, Ordering};
+
+use crate::constants::*;
+use crate::errors::{LighterError, Result};
+
+/// Bits in a client order index
+pub const CLIENT_ORDER_INDEX_BITS: u8 = 48;
+/// Widest bot id a namespace can hold, leaving 2^32 indexes per bot
+pub const MAX_NAMESPACE_BITS: u8 = 16;
+
+/// The client order indexes whose top `bits` bits equal `id`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct ClientOrderNamespace {
+    id: u32,
+    bits: u8,
+}
+
+impl ClientOrderNamespace {
+    /// Every client order index; the default
+    pub const ALL: Self = Self { id: 0, bits: 0 };
+
+    /// Namespace of bot `id`, using the top `bits` bits of the index
+    pub fn new(id: u32, bits: u8) -> Result<Self> {
+        if bits > MAX_NAMESPACE_BITS {
+            return Err(LighterError::ValidationError(format!(
+                "Namespace bits must be at most {MAX_NAMESPACE_BITS}, got {bits}"
+            )));
+        }
+        if u64::from(id) >> bits != 0 {
+            return Err(LighterError::ValidationError(format!(
+                "Namespace id {id} doesn't fit in {bits} bits"
+            )));
+        }
+        Ok(Self { id, bits })
+    }
+
+    /// Namespace of the strategy `strategy_id`, using the top `bits` bits
+    ///
+    /// The id is a stable hash of the name, so a strategy restarted on any
+    /// machine or build gets the same namespace back. Different names can
+    /// land on the same id; check the namespaces of the strategies sharing an
+    /// account with [`ClientOrderNamespace::overlaps`].
+    pub fn for_strategy(strategy_id: &str, bits: u8) -> Result<Self> {
+        if bits > MAX_NAMESPACE_BITS {
+            return Self::new(0, bits);
+        }
+        // 64-bit FNV-1a, whose output doesn't change between Rust releases
+        let hash = strategy_id
+            .bytes()
+            .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+                (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+            });
+        let id = match bits {
+            0 => 0,
+            bits => (hash >> (64 - u32::from(bits))) as u32,
+        };
+        Self::new(id, bits)
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    pub fn bits(&self) -> u8 {
+        self.bits
+    }
+
+    fn shift(&self) -> u8 {
+        CLIENT_ORDER_INDEX_BITS - self.bits
+    }
+
+    /// Smallest index in the namespace
+    ///
+    /// Namespace 0 starts at [`MIN_CLIENT_ORDER_INDEX`], since index 0 means
+    /// no client order index.
+    pub fn first(&self) -> i64 {
+        (i64::from(self.id) << self.shift()).max(MIN_CLIENT_ORDER_INDEX)
+    }
+
+    /// Largest index in the namespace
+    pub fn last(&self) -> i64 {
+        ((i64::from(self.id) + 1) << self.shift()) - 1
+    }
+
+    /// Number of indexes in the namespace
+    pub fn capacity(&self) -> i64 {
+        self.last() - self.first() + 1
+    }
+
+    pub fn contains(&self, client_order_index: i64) -> bool {
+        (self.first()..=self.last()).contains(&client_order_index)
+    }
+
+    /// Whether any index belongs to both namespaces
+    pub fn overlaps(&self, other: &ClientOrderNamespace) -> bool {
+        self.first() <= other.last() && other.first() <= self.last()
+    }
+
+    /// The index at position `sequence` in the namespace
+    pub fn encode(&self, sequence: i64) -> Result<i64> {
+        if !(0..self.capacity()).contains(&sequence) {
+            return Err(LighterError::ValidationError(format!(
+                "Sequence {sequence} is outside a namespace of {} indexes",
+                self.capacity()
+            )));
+        }
+        Ok(self.first() + sequence)
+    }
+
+    /// Position of `client_order_index` in the namespace, or `None` when it
+    /// belongs to another one
+    pub fn decode(&self, client_order_index: i64) -> Option<i64> {
+        self.contains(client_order_index)
+            .then(|| client_order_index - self.first())
+    }
+
+    /// Map any value into the namespace, wrapping around its capacity
+    pub fn wrap(&self, value: i64) -> i64 {
+        self.first() + value.rem_euclid(self.capacity())
+    }
+}
+
+/// Hands out client order indexes from one namespace
+///
+/// Starts from the current time in milliseconds, so a restarted process
+/// doesn't reuse its predecessor's recent indexes, then counts up and wraps
+/// around within the namespace.
+#[derive(Debug)]
+pub struct ClientOrderIndexes {
+    namespace: ClientOrderNamespace,
+    /// Sequence of the next index to hand out
+    next: AtomicI64,
+}
+
+impl ClientOrderIndexes {
+    pub fn new(namespace: ClientOrderNamespace) -> Self {
+        Self::starting_at(namespace, chrono::Utc::now().timestamp_millis())
+    }
+
+    /// Allocator whose first index is at `sequence`, wrapped into the namespace
+    pub fn starting_at(namespace: ClientOrderNamespace, sequence: i64) -> Self {
+        Self {
+            namespace,
+            next: AtomicI64::new(sequence.rem_euclid(namespace.capacity())),
+        }
+    }
+
+    pub fn namespace(&self) -> ClientOrderNamespace {
+        self.namespace
+    }
+
+    /// Reserve one index
+    pub fn next(&self) -> i64 {
+        self.reserve(1)
+    }
+
+    /// Reserve `count` consecutive indexes and return the first
+    pub fn next_block(&self, count: usize) -> Result<i64> {
+        match i64::try_from(count) {
+            Ok(count) if count > 0 && count <= self.namespace.capacity() => Ok(self.reserve(count)),
+            _ => Err(LighterError::ValidationError(format!(
+                "Can't reserve {count} indexes from a namespace of {}",
+                self.namespace.capacity()
+            ))),
+        }
+    }
+
+    /// `count` must be between 1 and the namespace's capacity
+    fn reserve(&self, count: i64) -> i64 {
+        let capacity = self.namespace.capacity();
+        let mut first = 0;
+        // The closure always returns Some, so this can't fail
+        let _ = self
+            .next
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |next| {
+                first = if next + count > capacity { 0 } else { next };
+                Some(first + count)
+            });
+        self.namespace.first() + first
+    }
+}
+
+impl Default for ClientOrderIndexes {
+    fn default() -> Self {
+        Self::new(ClientOrderNamespace::ALL)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_namespace_bounds() {
+        assert_eq!(ClientOrderNamespace::ALL.first(), MIN_CLIENT_ORDER_INDEX);
+        assert_eq!(ClientOrderNamespace::ALL.last(), MAX_CLIENT_ORDER_INDEX);
+
+        let first = ClientOrderNamespace::new(0, 16).unwrap();
+        assert_eq!((first.first(), first.last()), (1, (1 << 32) - 1));
+        let last = ClientOrderNamespace::new(u16::MAX.into(), 16).unwrap();
+        assert_eq!(last.last(), MAX_CLIENT_ORDER_INDEX);
+        assert_eq!(last.capacity(), 1 << 32);
+        assert!(!last.contains(MAX_CLIENT_ORDER_INDEX + 1));
+
+        assert!(ClientOrderNamespace::new(256, 8).is_err());
+        assert!(ClientOrderNamespace::new(1, 0).is_err());
+        assert!(ClientOrderNamespace::new(0, 17).is_err());
+    }
+
+    #[test]
+    fn test_strategy_namespaces_are_stable() {
+        // Pinned, so a restart under any build finds its orders again
+        let namespace = ClientOrderNamespace::for_strategy("market-maker", 8).unwrap();
+        assert_eq!((namespace.id(), namespace.bits()), (153, 8));
+        assert_eq!(
+            ClientOrderNamespace::for_strategy("market-maker", 16).unwrap(),
+            ClientOrderNamespace::new(39206, 16).unwrap()
+        );
+        assert_eq!(
+            ClientOrderNamespace::for_strategy("arb", 8).unwrap().id(),
+            231
+        );
+        assert_eq!(
+            ClientOrderNamespace::for_strategy("arb", 0).unwrap(),
+            ClientOrderNamespace::ALL
+        );
+        assert!(ClientOrderNamespace::for_strategy("arb", MAX_NAMESPACE_BITS + 1).is_err());
+    }
+
+    #[test]
+    fn test_encode_decode() {
+        let bot = ClientOrderNamespace::new(5, 4).unwrap();
+        let other = ClientOrderNamespace::new(6, 4).unwrap();
+        for sequence in [0, 1, 12_345, bot.capacity() - 1] {
+            let index = bot.encode(sequence).unwrap();
+            assert_eq!(index >> 44, 5);
+            assert_eq!(bot.decode(index), Some(sequence));
+            assert_eq!(other.decode(index), None);
+        }
+        assert!(!bot.overlaps(&other));
+        assert!(bot.overlaps(&bot));
+        assert!(bot.overlaps(&ClientOrderNamespace::ALL));
+        assert!(ClientOrderNamespace::new(1, 2).unwrap().overlaps(&bot));
+        assert!(bot.encode(bot.capacity()).is_err());
+        assert!(bot.encode(-1).is_err());
+        assert_eq!(bot.wrap(bot.capacity() + 3), bot.encode(3).unwrap());
+        assert_eq!(bot.wrap(-1), bot.last());
+    }
+
+    #[test]
+    fn test_allocation_stays_in_namespace() {
+        let namespace = ClientOrderNamespace::new(2, 16).unwrap();
+        let indexes = ClientOrderIndexes::starting_at(namespace, namespace.capacity() - 3);
+
+        assert_eq!(indexes.next(), namespace.last() - 2);
+        // A block that doesn't fit before the end starts over at the beginning
+        assert_eq!(indexes.next_block(5).unwrap(), namespace.first());
+        assert_eq!(indexes.next(), namespace.first() + 5);
+        assert!(indexes.next_block(0).is_err());
+
+        let time_seeded = ClientOrderIndexes::new(namespace);
+        assert!((0..1_000).all(|_| namespace.contains(time_seeded.next())));
+    }
+}
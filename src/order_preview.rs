@@ -0,0 +1,256 @@
+//! What an order costs before it is sent
+//!
+//! [`TxClient::preview_order`](crate::client::TxClient::preview_order) works
+//! out an order's notional, the fee it would pay as maker and as taker, and
+//! the initial margin it needs at the market's maximum leverage, from the
+//! market details cached by the client. With the details cached, see
+//! [`TxClient::cache_market_details`](crate::client::TxClient::cache_market_details),
+//! no request is made.
+//!
+//! Whether the order would make or take liquidity is decided against a
+//! [`TopOfBook`]: post-only orders make, market orders, stop losses, take
+//! profits and immediate-or-cancel orders take, and any other order takes if
+//! its price crosses the opposite side. A trigger order is judged against the
+//! book as it is now, not as it will be when it triggers.
+//!
+//! All figures are exact decimals. Fees are the market's `maker_fee` and
+//! `taker_fee`, which the API reports in percent of the notional.
+//!
+//! ```
+//! use lighter_rs::client::MarketDetails;
+//! use lighter_rs::order_preview::{preview, Liquidity, TopOfBook};
+//! use lighter_rs::types::CreateOrderTxReq;
+//! use lighter_rs::Decimal;
+//!
+//! # fn example(details: MarketDetails, req: CreateOrderTxReq) {
+//! let top = TopOfBook::new(Some(Decimal::new(300000, 2)), Some(Decimal::new(300010, 2)));
+//! let preview = preview(&req, &details, &top);
+//! if preview.liquidity == Liquidity::Taker {
+//!     println!("pays {} in fees", preview.expected_fee());
+//! }
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use rust_decimal::Decimal;
+
+use crate::client::MarketDetails;
+use crate::constants::*;
+use crate::types::CreateOrderTxReq;
+
+/// Whether an order adds liquidity to the book or takes it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Liquidity {
+    Maker,
+    Taker,
+}
+
+/// Best prices of a market, either side possibly empty
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TopOfBook {
+    pub best_bid: Option<Decimal>,
+    pub best_ask: Option<Decimal>,
+}
+
+impl TopOfBook {
+    pub fn new(best_bid: Option<Decimal>, best_ask: Option<Decimal>) -> Self {
+        Self { best_bid, best_ask }
+    }
+}
+
+#[cfg(feature = "native")]
+impl From<&crate::ws_client::OrderBook> for TopOfBook {
+    fn from(book: &crate::ws_client::OrderBook) -> Self {
+        Self::new(book.best_bid(), book.best_ask())
+    }
+}
+
+/// Expected cost of an order, see [`preview`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OrderPreview {
+    pub market_index: u8,
+    /// Price times size, in USDC
+    pub notional: Decimal,
+    /// Whether the order would make or take liquidity against the book given
+    pub liquidity: Liquidity,
+    pub expected_fee_maker: Decimal,
+    pub expected_fee_taker: Decimal,
+    /// Initial margin at the market's maximum leverage; zero for reduce-only
+    /// orders
+    pub est_margin_required: Decimal,
+}
+
+impl OrderPreview {
+    /// Fee of the side the order would trade on
+    pub fn expected_fee(&self) -> Decimal {
+        match self.liquidity {
+            Liquidity::Maker => self.expected_fee_maker,
+            Liquidity::Taker => self.expected_fee_taker,
+        }
+    }
+}
+
+/// Preview `req` on the market `details` describes, against `top`
+pub fn preview(req: &CreateOrderTxReq, details: &MarketDetails, top: &TopOfBook) -> OrderPreview {
+    let price = Decimal::from_i128_with_scale(i128::from(req.price), details.price_decimals);
+    let size = Decimal::new(req.base_amount, details.size_decimals);
+    let notional = (price * size).abs();
+    let est_margin_required = if req.reduce_only != 0 {
+        Decimal::ZERO
+    } else {
+        notional * Decimal::from(details.min_initial_margin_fraction)
+            / Decimal::from(MARGIN_FRACTION_TICK)
+    };
+    OrderPreview {
+        market_index: req.market_index,
+        notional,
+        liquidity: liquidity(req, price, top),
+        expected_fee_maker: notional * details.maker_fee / Decimal::ONE_HUNDRED,
+        expected_fee_taker: notional * details.taker_fee / Decimal::ONE_HUNDRED,
+        est_margin_required,
+    }
+}
+
+fn liquidity(req: &CreateOrderTxReq, price: Decimal, top: &TopOfBook) -> Liquidity {
+    if req.time_in_force == TIME_IN_FORCE_POST_ONLY {
+        return Liquidity::Maker;
+    }
+    if matches!(
+        req.order_type,
+        ORDER_TYPE_MARKET | ORDER_TYPE_STOP_LOSS | ORDER_TYPE_TAKE_PROFIT
+    ) || req.time_in_force == TIME_IN_FORCE_IMMEDIATE_OR_CANCEL
+    {
+        return Liquidity::Taker;
+    }
+    let crosses = if req.is_ask != 0 {
+        top.best_bid.is_some_and(|bid| price <= bid)
+    } else {
+        top.best_ask.is_some_and(|ask| price >= ask)
+    };
+    if crosses {
+        Liquidity::Taker
+    } else {
+        Liquidity::Maker
+    }
+}
+
+/// Market details a client has fetched or been given, for previews
+#[derive(Default)]
+pub(crate) struct MarketDetailsCache {
+    details: Mutex<HashMap<u8, MarketDetails>>,
+}
+
+impl MarketDetailsCache {
+    pub fn get(&self, market_index: u8) -> Option<MarketDetails> {
+        self.details
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .get(&market_index)
+            .cloned()
+    }
+
+    pub fn insert(&self, details: MarketDetails) {
+        self.details
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .insert(details.market_id, details);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dec(value: &str) -> Decimal {
+        value.parse().unwrap()
+    }
+
+    fn market() -> MarketDetails {
+        serde_json::from_value(serde_json::json!({
+            "market_id": 1,
+            "size_decimals": 4,
+            "price_decimals": 2,
+            "min_initial_margin_fraction": 500,
+            "maker_fee": "0.0020",
+            "taker_fee": "0.0200",
+        }))
+        .unwrap()
+    }
+
+    fn order(is_ask: u8, price: u64, time_in_force: u8) -> CreateOrderTxReq {
+        CreateOrderTxReq {
+            market_index: 1,
+            client_order_index: 1,
+            // 1.5 at 3000.00 or so
+            base_amount: 15_000,
+            price,
+            is_ask,
+            order_type: ORDER_TYPE_LIMIT,
+            time_in_force,
+            reduce_only: 0,
+            trigger_price: 0,
+            order_expiry: 0,
+        }
+    }
+
+    #[test]
+    fn test_maker_and_taker_previews() {
+        let top = TopOfBook::new(Some(dec("2999.00")), Some(dec("3001.00")));
+
+        // A bid under the ask rests: 1.5 * 2995.00 = 4492.50
+        let maker = preview(
+            &order(0, 299_500, TIME_IN_FORCE_GOOD_TILL_TIME),
+            &market(),
+            &top,
+        );
+        assert_eq!(maker.liquidity, Liquidity::Maker);
+        assert_eq!(maker.notional, dec("4492.50"));
+        // 4492.50 * 0.002% and 4492.50 * 0.02%
+        assert_eq!(maker.expected_fee_maker, dec("0.089850"));
+        assert_eq!(maker.expected_fee_taker, dec("0.89850"));
+        assert_eq!(maker.expected_fee(), dec("0.08985"));
+        // 500 / 10000 = 5%, i.e. 20x
+        assert_eq!(maker.est_margin_required, dec("224.625"));
+
+        // A bid through the ask takes: 1.5 * 3005.00 = 4507.50
+        let taker = preview(
+            &order(0, 300_500, TIME_IN_FORCE_GOOD_TILL_TIME),
+            &market(),
+            &top,
+        );
+        assert_eq!(taker.liquidity, Liquidity::Taker);
+        assert_eq!(taker.expected_fee(), dec("0.9015"));
+
+        // Asks cross at or under the bid
+        let at_bid = order(1, 299_900, TIME_IN_FORCE_GOOD_TILL_TIME);
+        assert_eq!(
+            preview(&at_bid, &market(), &top).liquidity,
+            Liquidity::Taker
+        );
+        assert_eq!(
+            preview(&at_bid, &market(), &TopOfBook::default()).liquidity,
+            Liquidity::Maker
+        );
+
+        // Post-only never takes, immediate-or-cancel never rests
+        let post_only = order(0, 300_500, TIME_IN_FORCE_POST_ONLY);
+        assert_eq!(
+            preview(&post_only, &market(), &top).liquidity,
+            Liquidity::Maker
+        );
+        let ioc = order(0, 299_500, TIME_IN_FORCE_IMMEDIATE_OR_CANCEL);
+        assert_eq!(preview(&ioc, &market(), &top).liquidity, Liquidity::Taker);
+
+        // Closing a position needs no margin
+        let reduce_only = CreateOrderTxReq {
+            reduce_only: 1,
+            ..order(1, 300_000, TIME_IN_FORCE_GOOD_TILL_TIME)
+        };
+        assert_eq!(
+            preview(&reduce_only, &market(), &top).est_margin_required,
+            Decimal::ZERO
+        );
+    }
+}
@@ -0,0 +1,468 @@
+//! End-of-day portfolio snapshots for accounting
+//!
+//! [`TxClient::portfolio_snapshot`] gathers the account's collateral,
+//! positions, open orders, and the day's fills and funding payments in one
+//! go, stamped with the exchange's clock. The requests run concurrently, and
+//! a section whose request fails is listed in [`PortfolioSnapshot::missing`]
+//! instead of failing the whole snapshot.
+//!
+//! ```no_run
+//! use lighter_rs::client::TxClient;
+//!
+//! # async fn example(tx_client: TxClient, auth: &str) -> Result<(), Box<dyn std::error::Error>> {
+//! let snapshot = tx_client.portfolio_snapshot(auth).await?;
+//! if !snapshot.is_complete() {
+//!     eprintln!("missing sections: {:?}", snapshot.missing);
+//! }
+//! std::fs::write("portfolio.json", snapshot.to_json()?)?;
+//! std::fs::write("portfolio.csv", snapshot.to_csv())?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt;
+use std::fmt::Write as _;
+
+use futures_util::future::{join4, join_all};
+use rust_decimal::Decimal;
+use serde::Serialize;
+
+use crate::client::{AccountPosition, AccountTrade, ActiveOrder, FundingPayment, TxClient};
+use crate::errors::{LighterError, Result};
+
+/// Most recent trades and funding payments fetched; a day rarely has more
+const HISTORY_LIMIT: u32 = 100;
+
+const DAY_MS: i64 = 86_400_000;
+
+/// A part of a [`PortfolioSnapshot`] backed by its own request
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Section {
+    /// Exchange time; the local clock is used when it is missing
+    ServerTime,
+    /// Collateral and positions
+    Account,
+    OpenOrders,
+    Fills,
+    Funding,
+}
+
+impl fmt::Display for Section {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Section::ServerTime => "server_time",
+            Section::Account => "account",
+            Section::OpenOrders => "open_orders",
+            Section::Fills => "fills",
+            Section::Funding => "funding",
+        })
+    }
+}
+
+/// Balances, positions, orders and the day's activity of one account
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PortfolioSnapshot {
+    pub account_index: i64,
+    /// When the snapshot was taken, in milliseconds since the Unix epoch
+    pub taken_at: i64,
+    /// Start of the UTC day the fills and funding are from
+    pub day_start: i64,
+    pub collateral: Option<Decimal>,
+    pub positions: Vec<AccountPosition>,
+    pub open_orders: Vec<ActiveOrder>,
+    pub fills: Vec<AccountTrade>,
+    pub funding: Vec<FundingPayment>,
+    /// Sections whose request failed and are left empty
+    pub missing: Vec<Section>,
+}
+
+impl PortfolioSnapshot {
+    /// Whether every section was fetched
+    pub fn is_complete(&self) -> bool {
+        self.missing.is_empty()
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// One row per balance, position, order, fill, funding payment and
+    /// missing section
+    ///
+    /// Columns are `section,timestamp,market_id,side,size,price,amount,reference`;
+    /// `amount` is the collateral, unrealized PnL, fill notional or funding
+    /// change, and `reference` the order, trade or funding id.
+    pub fn to_csv(&self) -> String {
+        let mut csv =
+            String::from("section,timestamp,market_id,side,size,price,amount,reference\n");
+        let mut row = |section: &str,
+                       timestamp: i64,
+                       market_id: Option<u8>,
+                       side: &str,
+                       size: Option<Decimal>,
+                       price: Option<Decimal>,
+                       amount: Option<Decimal>,
+                       reference: &dyn fmt::Display| {
+            let optional =
+                |value: Option<Decimal>| value.map(|v| v.to_string()).unwrap_or_default();
+            // Writing to a String can't fail
+            let _ = writeln!(
+                csv,
+                "{section},{timestamp},{},{side},{},{},{},{reference}",
+                market_id.map(|m| m.to_string()).unwrap_or_default(),
+                optional(size),
+                optional(price),
+                optional(amount),
+            );
+        };
+
+        if let Some(collateral) = self.collateral {
+            row(
+                "balance",
+                self.taken_at,
+                None,
+                "",
+                None,
+                None,
+                Some(collateral),
+                &"",
+            );
+        }
+        for position in &self.positions {
+            let side = if position.sign < 0 { "short" } else { "long" };
+            row(
+                "position",
+                self.taken_at,
+                Some(position.market_id),
+                side,
+                Some(position.position),
+                Some(position.avg_entry_price),
+                Some(position.unrealized_pnl),
+                &"",
+            );
+        }
+        for order in &self.open_orders {
+            row(
+                "order",
+                self.taken_at,
+                Some(order.market_index),
+                if order.is_ask { "sell" } else { "buy" },
+                Some(order.remaining_base_amount),
+                Some(order.price),
+                None,
+                &order.order_index,
+            );
+        }
+        for fill in &self.fills {
+            let side = if fill.is_ask(self.account_index) {
+                "sell"
+            } else {
+                "buy"
+            };
+            row(
+                "fill",
+                fill.timestamp,
+                Some(fill.market_id),
+                side,
+                Some(fill.size),
+                Some(fill.price),
+                Some(fill.usd_amount),
+                &fill.trade_id,
+            );
+        }
+        for payment in &self.funding {
+            row(
+                "funding",
+                payment.timestamp * 1000,
+                Some(payment.market_id),
+                &payment.position_side,
+                Some(payment.position_size),
+                Some(payment.rate),
+                Some(payment.change),
+                &payment.funding_id,
+            );
+        }
+        for section in &self.missing {
+            row(
+                "missing",
+                self.taken_at,
+                None,
+                "",
+                None,
+                None,
+                None,
+                section,
+            );
+        }
+        csv
+    }
+}
+
+impl TxClient {
+    /// Take a [`PortfolioSnapshot`] of this client's account
+    ///
+    /// `auth` is an auth token for the account. Open orders are fetched for
+    /// every market the account reports orders in, so they are missing
+    /// whenever the account is.
+    pub async fn portfolio_snapshot(&self, auth: &str) -> Result<PortfolioSnapshot> {
+        let http = self.http().ok_or_else(|| {
+            LighterError::InvalidConfiguration("HTTPClient is not configured".to_string())
+        })?;
+        let account_index = self.account_index();
+
+        let (server_time, account, fills, funding) = join4(
+            http.get_server_time(),
+            http.get_account_state(account_index),
+            http.get_account_trades(account_index, HISTORY_LIMIT, auth),
+            http.get_position_funding(account_index, HISTORY_LIMIT, auth),
+        )
+        .await;
+
+        let mut missing = Vec::new();
+        let mut note = |section: Section, e: &LighterError| {
+            tracing::warn!(%section, error = %e, "Portfolio snapshot section failed");
+            missing.push(section);
+        };
+
+        let taken_at = server_time.unwrap_or_else(|e| {
+            note(Section::ServerTime, &e);
+            self.clock().now_ms()
+        });
+        let day_start = taken_at - taken_at.rem_euclid(DAY_MS);
+
+        let (collateral, positions, open_orders) = match account {
+            Ok(account) => {
+                let markets: Vec<u8> = account
+                    .positions
+                    .iter()
+                    .filter(|position| position.open_order_count > 0)
+                    .map(|position| position.market_id)
+                    .collect();
+                let orders = join_all(
+                    markets
+                        .iter()
+                        .map(|&market| http.get_active_orders(account_index, market, Some(auth))),
+                )
+                .await
+                .into_iter()
+                .collect::<Result<Vec<_>>>();
+                let open_orders = match orders {
+                    Ok(orders) => orders.into_iter().flatten().collect(),
+                    Err(e) => {
+                        note(Section::OpenOrders, &e);
+                        Vec::new()
+                    }
+                };
+                let positions = account
+                    .positions
+                    .into_iter()
+                    .filter(|position| !position.position.is_zero())
+                    .collect();
+                (Some(account.collateral), positions, open_orders)
+            }
+            Err(e) => {
+                note(Section::Account, &e);
+                note(Section::OpenOrders, &e);
+                (None, Vec::new(), Vec::new())
+            }
+        };
+
+        let fills = match fills {
+            Ok(fills) => fills
+                .into_iter()
+                .filter(|fill| (day_start..=taken_at).contains(&fill.timestamp))
+                .collect(),
+            Err(e) => {
+                note(Section::Fills, &e);
+                Vec::new()
+            }
+        };
+        let funding = match funding {
+            Ok(funding) => funding
+                .into_iter()
+                .filter(|payment| (day_start..=taken_at).contains(&(payment.timestamp * 1000)))
+                .collect(),
+            Err(e) => {
+                note(Section::Funding, &e);
+                Vec::new()
+            }
+        };
+
+        Ok(PortfolioSnapshot {
+            account_index,
+            taken_at,
+            day_start,
+            collateral,
+            positions,
+            open_orders,
+            fills,
+            funding,
+            missing,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::mock_builder;
+    use crate::transport::{HttpResponse, MockTransport};
+    use std::sync::Arc;
+
+    /// 2024-06-01 15:00:00 UTC
+    const NOW_S: i64 = 1_717_254_000;
+    const DAY_START_MS: i64 = 1_717_200_000_000;
+
+    fn client() -> (TxClient, Arc<MockTransport>) {
+        let mock = Arc::new(MockTransport::new());
+        mock.set_handler("/", |_| {
+            Ok(HttpResponse::new(
+                200,
+                format!(r#"{{"status":200,"timestamp":{NOW_S}}}"#),
+            ))
+        });
+        mock.set_handler("/api/v1/account", |_| {
+            Ok(HttpResponse::new(
+                200,
+                r#"{"code":200,"accounts":[{"index":1,"collateral":"5000.25","positions":[
+                    {"market_id":0,"sign":1,"position":"0.5","avg_entry_price":"3000",
+                     "unrealized_pnl":"12.5","open_order_count":1},
+                    {"market_id":1,"sign":-1,"position":"0","avg_entry_price":"0",
+                     "open_order_count":0}
+                ]}]}"#,
+            ))
+        });
+        mock.set_handler("/api/v1/accountActiveOrders", |_| {
+            Ok(HttpResponse::new(
+                200,
+                r#"{"code":200,"orders":[
+                    {"order_index":77,"client_order_index":7,"market_index":0,"is_ask":true,
+                     "price":"3100.00","remaining_base_amount":"0.2000","status":"open"}
+                ]}"#,
+            ))
+        });
+        mock.set_handler("/api/v1/trades", |_| {
+            // Newest first; the last one is from yesterday
+            Ok(HttpResponse::new(
+                200,
+                format!(
+                    r#"{{"code":200,"trades":[
+                        {{"trade_id":902,"market_id":0,"size":"0.3","price":"3010",
+                         "usd_amount":"903","ask_account_id":9,"bid_account_id":1,
+                         "timestamp":{}}},
+                        {{"trade_id":901,"market_id":0,"size":"0.2","price":"2990",
+                         "usd_amount":"598","ask_account_id":9,"bid_account_id":1,
+                         "timestamp":{}}}
+                    ]}}"#,
+                    NOW_S * 1000 - 60_000,
+                    DAY_START_MS - 1,
+                ),
+            ))
+        });
+        let tx_client = mock_builder(&mock).build().unwrap();
+        (tx_client, mock)
+    }
+
+    fn funding_ok(mock: &MockTransport) {
+        mock.set_handler("/api/v1/positionFunding", |_| {
+            Ok(HttpResponse::new(
+                200,
+                format!(
+                    r#"{{"code":200,"position_fundings":[
+                        {{"timestamp":{},"market_id":0,"funding_id":55,"change":"-0.75",
+                         "rate":"0.0001","position_size":"0.5","position_side":"long"}}
+                    ]}}"#,
+                    NOW_S - 3_600
+                ),
+            ))
+        });
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_collects_every_section() {
+        let (tx_client, mock) = client();
+        funding_ok(&mock);
+
+        let snapshot = tx_client.portfolio_snapshot("token:1").await.unwrap();
+        assert!(snapshot.is_complete());
+        assert_eq!(snapshot.taken_at, NOW_S * 1000);
+        assert_eq!(snapshot.day_start, DAY_START_MS);
+        assert_eq!(snapshot.collateral, Some(Decimal::new(500_025, 2)));
+        // Flat market 1 is left out, and only market 0 has orders to fetch
+        assert_eq!(snapshot.positions.len(), 1);
+        assert_eq!(snapshot.open_orders.len(), 1);
+        assert_eq!(mock.requests_to("/api/v1/accountActiveOrders").len(), 1);
+        // Yesterday's trade is left out
+        assert_eq!(snapshot.fills.len(), 1);
+        assert_eq!(snapshot.fills[0].trade_id, 902);
+        assert_eq!(snapshot.funding.len(), 1);
+        assert!(mock.requests_to("/api/v1/trades")[0]
+            .url
+            .contains("auth=token%3A1"));
+
+        assert_eq!(
+            snapshot.to_csv(),
+            format!(
+                "section,timestamp,market_id,side,size,price,amount,reference\n\
+                 balance,{t},,,,,5000.25,\n\
+                 position,{t},0,long,0.5,3000,12.5,\n\
+                 order,{t},0,sell,0.2000,3100.00,,77\n\
+                 fill,{f},0,buy,0.3,3010,903,902\n\
+                 funding,{u},0,long,0.5,0.0001,-0.75,55\n",
+                t = NOW_S * 1000,
+                f = NOW_S * 1000 - 60_000,
+                u = (NOW_S - 3_600) * 1000,
+            )
+        );
+        let json: serde_json::Value = serde_json::from_str(&snapshot.to_json().unwrap()).unwrap();
+        assert_eq!(json["collateral"], "5000.25");
+        assert_eq!(json["fills"][0]["trade_id"], 902);
+        assert_eq!(json["missing"], serde_json::json!([]));
+    }
+
+    #[tokio::test]
+    async fn test_failed_sections_are_listed_as_missing() {
+        let (tx_client, mock) = client();
+        mock.set_handler("/api/v1/positionFunding", |_| {
+            Ok(HttpResponse::new(500, "internal error"))
+        });
+        mock.set_handler("/api/v1/accountActiveOrders", |_| {
+            Ok(HttpResponse::new(403, "forbidden"))
+        });
+
+        let snapshot = tx_client.portfolio_snapshot("token:1").await.unwrap();
+        assert_eq!(
+            snapshot.missing,
+            vec![Section::OpenOrders, Section::Funding]
+        );
+        assert_eq!(snapshot.positions.len(), 1);
+        assert_eq!(snapshot.fills.len(), 1);
+        assert!(snapshot.to_csv().ends_with(&format!(
+            "missing,{t},,,,,,open_orders\nmissing,{t},,,,,,funding\n",
+            t = NOW_S * 1000
+        )));
+        let json: serde_json::Value = serde_json::from_str(&snapshot.to_json().unwrap()).unwrap();
+        assert_eq!(
+            json["missing"],
+            serde_json::json!(["open_orders", "funding"])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_missing_account_also_misses_orders() {
+        let (tx_client, mock) = client();
+        funding_ok(&mock);
+        mock.set_handler("/api/v1/account", |_| {
+            Ok(HttpResponse::new(502, "bad gateway"))
+        });
+
+        let snapshot = tx_client.portfolio_snapshot("token:1").await.unwrap();
+        assert_eq!(
+            snapshot.missing,
+            vec![Section::Account, Section::OpenOrders]
+        );
+        assert_eq!(snapshot.collateral, None);
+        assert!(mock.requests_to("/api/v1/accountActiveOrders").is_empty());
+    }
+}
@@ -0,0 +1,1229 @@
+//! Live positions, PnL and exposure for one account
+//!
+//! [`PositionManager`] starts from the REST positions snapshot, follows fills
+//! and position updates from the account WebSocket channel, and marks
+//! positions against the latest order book mid or a price you supply. Each
+//! later snapshot passed to [`PositionManager::reconcile`] is checked against
+//! the incremental state, and markets that drifted apart are reset to the
+//! exchange's numbers with a [`PositionEvent::Divergence`].
+//!
+//! Funding payments and fees are booked alongside price moves, so
+//! [`PositionManager::pnl_breakdown`] can tell what a position made from
+//! the market apart from what it paid to hold, for its whole life or for a
+//! time window.
+//!
+//! Marks are stamped with when they were set; [`PositionManager::mark_price`]
+//! returns them as a [`FreshPrice`] for helpers that refuse stale prices,
+//! and the price band risk limit reads them the same way.
+//!
+//! ```no_run
+//! use lighter_rs::client::HTTPClient;
+//! use lighter_rs::positions::PositionManager;
+//! use lighter_rs::Decimal;
+//!
+//! # async fn example(http: HTTPClient) -> lighter_rs::Result<()> {
+//! let positions = PositionManager::new(12345, Decimal::new(1, 3));
+//! positions.reconcile(&http).await?;
+//!
+//! let mut events = positions.subscribe();
+//! tokio::spawn(async move {
+//!     while let Ok(event) = events.recv().await {
+//!         tracing::warn!("{event:?}");
+//!     }
+//! });
+//!
+//! println!("exposure: {} USD", positions.exposure());
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ops::{Bound, RangeBounds};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::broadcast;
+
+use crate::client::{AccountPosition, FundingPayment, HTTPClient};
+use crate::clock::{Clock, SystemClock};
+use crate::errors::Result;
+use crate::fresh_price::FreshPrice;
+use crate::risk::RiskState;
+use crate::snapshot_sync::{account_frame_timestamp, now_ms, Ingest, SnapshotSync, SyncKey};
+use crate::state_store::{load_json, save_json, RestoreReport, StateStore};
+use crate::utils::{json_decimal, json_integer};
+use crate::ws_client::OrderBook;
+
+/// Events buffered per subscriber before slow ones start skipping
+const EVENT_BUFFER: usize = 64;
+
+/// Name the positions are saved under in a [`StateStore`]
+const POSITIONS_STATE: &str = "positions";
+
+/// Position in one market
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Position {
+    pub market_index: u8,
+    /// Signed size in base units: positive when long, negative when short
+    pub size: Decimal,
+    /// Average entry price of the open position
+    pub avg_entry_price: Decimal,
+    /// Realized PnL as of the last snapshot, plus fills seen since
+    pub realized_pnl: Decimal,
+    /// Funding received (positive) or paid (negative) while tracked
+    #[serde(default)]
+    pub funding_pnl: Decimal,
+    /// Trading fees paid while tracked
+    #[serde(default)]
+    pub fee_paid: Decimal,
+}
+
+impl Position {
+    /// Unrealized PnL if marked at `mark_price`
+    pub fn unrealized_pnl(&self, mark_price: Decimal) -> Decimal {
+        self.size * (mark_price - self.avg_entry_price)
+    }
+
+    /// Realized plus unrealized PnL from price moves alone
+    pub fn price_pnl(&self, mark_price: Decimal) -> Decimal {
+        self.realized_pnl + self.unrealized_pnl(mark_price)
+    }
+
+    /// PnL split into price, funding and fees if marked at `mark_price`
+    pub fn breakdown(&self, mark_price: Decimal) -> PnlBreakdown {
+        PnlBreakdown {
+            realized_pnl: self.realized_pnl,
+            unrealized_pnl: self.unrealized_pnl(mark_price),
+            funding_pnl: self.funding_pnl,
+            fee_paid: self.fee_paid,
+        }
+    }
+
+    /// Book a fill, keeping a weighted average entry and realizing PnL on the
+    /// part that reduces the position
+    pub fn apply_fill(&mut self, is_ask: bool, size: Decimal, price: Decimal) {
+        let signed = if is_ask { -size } else { size };
+        let old = self.size;
+        let new = old + signed;
+
+        if side(old) != 0 && side(old) != side(signed) {
+            let closed = size.min(old.abs());
+            self.realized_pnl += closed * (price - self.avg_entry_price) * Decimal::from(side(old));
+        }
+
+        self.avg_entry_price = if side(new) == 0 {
+            Decimal::ZERO
+        } else if side(old) != side(new) {
+            // Opened or flipped
+            price
+        } else if new.abs() > old.abs() {
+            (self.avg_entry_price * old.abs() + price * size) / new.abs()
+        } else {
+            self.avg_entry_price
+        };
+        self.size = new;
+    }
+}
+
+/// Funding a position of signed `size` receives (positive) or pays
+/// (negative) for one period at `rate`, marked at `mark_price`
+///
+/// Longs pay shorts when the rate is positive and shorts pay longs when it
+/// is negative. This is the only place that convention is encoded.
+pub fn funding_pnl(size: Decimal, mark_price: Decimal, rate: Decimal) -> Decimal {
+    -size * mark_price * rate
+}
+
+/// PnL split by where it came from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct PnlBreakdown {
+    /// PnL locked in by reducing positions
+    pub realized_pnl: Decimal,
+    /// PnL of the open position at its latest mark
+    pub unrealized_pnl: Decimal,
+    /// Funding received (positive) or paid (negative)
+    pub funding_pnl: Decimal,
+    /// Trading fees paid
+    pub fee_paid: Decimal,
+}
+
+impl PnlBreakdown {
+    /// Realized plus unrealized PnL from price moves alone
+    pub fn price_pnl(&self) -> Decimal {
+        self.realized_pnl + self.unrealized_pnl
+    }
+
+    /// Everything together: price PnL and funding, less fees
+    pub fn net(&self) -> Decimal {
+        self.price_pnl() + self.funding_pnl - self.fee_paid
+    }
+}
+
+impl std::ops::AddAssign for PnlBreakdown {
+    fn add_assign(&mut self, other: Self) {
+        self.realized_pnl += other.realized_pnl;
+        self.unrealized_pnl += other.unrealized_pnl;
+        self.funding_pnl += other.funding_pnl;
+        self.fee_paid += other.fee_paid;
+    }
+}
+
+/// Source of one booked PnL amount
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PnlSource {
+    Realized,
+    Funding,
+    Fee,
+}
+
+/// One PnL amount booked at a point in time, for windowed breakdowns
+#[derive(Debug, Clone, Copy)]
+struct PnlEntry {
+    /// Milliseconds since the Unix epoch
+    timestamp_ms: i64,
+    market_index: u8,
+    source: PnlSource,
+    amount: Decimal,
+}
+
+/// Sign of a decimal as -1, 0 or 1, treating negative zero as zero
+fn side(value: Decimal) -> i8 {
+    if value > Decimal::ZERO {
+        1
+    } else if value < Decimal::ZERO {
+        -1
+    } else {
+        0
+    }
+}
+
+/// Something risk checks should hear about
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PositionEvent {
+    /// Incremental state disagreed with an exchange snapshot by more than the
+    /// threshold; the market was reset to the snapshot
+    Divergence {
+        market_index: u8,
+        local_size: Decimal,
+        exchange_size: Decimal,
+    },
+}
+
+#[derive(Default)]
+struct State {
+    positions: BTreeMap<u8, Position>,
+    marks: HashMap<u8, FreshPrice>,
+    /// Whether a snapshot has been applied yet; the first one only seeds
+    seeded: bool,
+    /// Realized PnL, funding and fees booked since tracking began
+    ledger: Vec<PnlEntry>,
+    /// Funding payments already booked, by market and funding id
+    funding_seen: HashSet<(u8, i64)>,
+    /// When positions were last set or moved, in milliseconds since the
+    /// Unix epoch
+    updated_at_ms: Option<i64>,
+}
+
+impl State {
+    /// Mark price of a market, falling back to the entry price before any
+    /// mark has been seen
+    fn mark(&self, position: &Position) -> Decimal {
+        self.marks
+            .get(&position.market_index)
+            .map_or(position.avg_entry_price, |mark| mark.value)
+    }
+
+    fn position_mut(&mut self, market_index: u8) -> &mut Position {
+        self.positions
+            .entry(market_index)
+            .or_insert_with(|| Position {
+                market_index,
+                ..Position::default()
+            })
+    }
+
+    fn book(&mut self, timestamp_ms: i64, market_index: u8, source: PnlSource, amount: Decimal) {
+        if !amount.is_zero() {
+            self.ledger.push(PnlEntry {
+                timestamp_ms,
+                market_index,
+                source,
+                amount,
+            });
+        }
+    }
+
+    /// Book a fill, recording the PnL it realized
+    fn fill(&mut self, timestamp_ms: i64, fill: &Fill) {
+        let position = self.position_mut(fill.market_index);
+        let realized = position.realized_pnl;
+        position.apply_fill(fill.is_ask, fill.size, fill.price);
+        let realized = position.realized_pnl - realized;
+        self.book(
+            timestamp_ms,
+            fill.market_index,
+            PnlSource::Realized,
+            realized,
+        );
+    }
+
+    fn fee(&mut self, timestamp_ms: i64, market_index: u8, fee: Decimal) {
+        self.position_mut(market_index).fee_paid += fee;
+        self.book(timestamp_ms, market_index, PnlSource::Fee, fee);
+    }
+
+    /// Book a funding payment unless it was booked before
+    fn funding(&mut self, payment: &FundingPayment) -> Decimal {
+        let id = if payment.funding_id != 0 {
+            payment.funding_id
+        } else {
+            payment.timestamp
+        };
+        if !self.funding_seen.insert((payment.market_id, id)) {
+            return Decimal::ZERO;
+        }
+        self.position_mut(payment.market_id).funding_pnl += payment.change;
+        self.book(
+            payment.timestamp * 1000,
+            payment.market_id,
+            PnlSource::Funding,
+            payment.change,
+        );
+        payment.change
+    }
+
+    fn breakdown(&self, market_index: Option<u8>, window: &impl RangeBounds<i64>) -> PnlBreakdown {
+        let in_market = |market: u8| market_index.is_none_or(|wanted| wanted == market);
+        let mut breakdown = PnlBreakdown::default();
+        let whole_history = matches!(
+            (window.start_bound(), window.end_bound()),
+            (Bound::Unbounded, Bound::Unbounded)
+        );
+
+        if whole_history {
+            for position in self.positions.values() {
+                if in_market(position.market_index) {
+                    breakdown += position.breakdown(self.mark(position));
+                }
+            }
+            return breakdown;
+        }
+
+        for entry in &self.ledger {
+            if !in_market(entry.market_index) || !window.contains(&entry.timestamp_ms) {
+                continue;
+            }
+            match entry.source {
+                PnlSource::Realized => breakdown.realized_pnl += entry.amount,
+                PnlSource::Funding => breakdown.funding_pnl += entry.amount,
+                PnlSource::Fee => breakdown.fee_paid += entry.amount,
+            }
+        }
+        // A window open at the end runs up to now, so it holds today's marks
+        if matches!(window.end_bound(), Bound::Unbounded) {
+            for position in self.positions.values() {
+                if in_market(position.market_index) {
+                    breakdown.unrealized_pnl += position.unrealized_pnl(self.mark(position));
+                }
+            }
+        }
+        breakdown
+    }
+}
+
+struct Inner {
+    account_index: i64,
+    divergence_threshold: Decimal,
+    state: Mutex<State>,
+    /// Holds account frames back while `reconcile` fetches the snapshot
+    sync: Mutex<SnapshotSync<Value>>,
+    events: broadcast::Sender<PositionEvent>,
+}
+
+/// Positions of one account, kept live from REST snapshots and WebSocket
+/// updates
+///
+/// Cloning is cheap and every clone shares the same state.
+#[derive(Clone)]
+pub struct PositionManager {
+    inner: Arc<Inner>,
+    /// Stamps marks with when they were set
+    clock: Arc<dyn Clock>,
+}
+
+impl PositionManager {
+    /// Track `account_index`, flagging snapshot disagreements larger than
+    /// `divergence_threshold` base units
+    pub fn new(account_index: i64, divergence_threshold: Decimal) -> Self {
+        let (events, _) = broadcast::channel(EVENT_BUFFER);
+        Self {
+            inner: Arc::new(Inner {
+                account_index,
+                divergence_threshold,
+                state: Mutex::new(State::default()),
+                sync: Mutex::new(SnapshotSync::new(SyncKey::Monotonic)),
+                events,
+            }),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Stamp marks on `clock` instead of the system clock
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Receive divergence warnings
+    pub fn subscribe(&self) -> broadcast::Receiver<PositionEvent> {
+        self.inner.events.subscribe()
+    }
+
+    /// Fetch the REST positions snapshot and reconcile against it
+    ///
+    /// Account frames arriving meanwhile are held back, then applied on top
+    /// of the snapshot if they are stamped after the request was sent.
+    pub async fn reconcile(&self, http: &HTTPClient) -> Result<Vec<PositionEvent>> {
+        self.sync().begin_snapshot();
+        let snapshot_ms = now_ms();
+        let snapshot = match http.get_account_positions(self.inner.account_index).await {
+            Ok(snapshot) => snapshot,
+            Err(e) => {
+                let mut sync = self.sync();
+                for data in sync.cancel_snapshot() {
+                    self.apply_frame(&data);
+                }
+                return Err(e);
+            }
+        };
+
+        let mut sync = self.sync();
+        let events = self.apply_snapshot(&snapshot);
+        for data in sync.complete_snapshot(snapshot_ms).events {
+            self.apply_frame(&data);
+        }
+        Ok(events)
+    }
+
+    /// Save the open positions to `store`
+    pub fn save_state(&self, store: &dyn StateStore) -> Result<()> {
+        save_json(store, POSITIONS_STATE, &self.positions())
+    }
+
+    /// Restore the positions saved by [`PositionManager::save_state`], then
+    /// reconcile them against the REST snapshot
+    ///
+    /// Restored positions count as the incremental state, so markets where
+    /// they disagree with the exchange are reset to its numbers with a
+    /// [`PositionEvent::Divergence`]. If the snapshot can't be fetched the
+    /// restored positions are dropped again and the manager stays cold.
+    pub async fn restore_and_reconcile(
+        &self,
+        store: &dyn StateStore,
+        http: &HTTPClient,
+    ) -> Result<RestoreReport> {
+        let Some(restored) = load_json::<Vec<Position>>(store, POSITIONS_STATE) else {
+            self.reconcile(http).await?;
+            return Ok(RestoreReport::default());
+        };
+        {
+            let mut state = self.lock();
+            state.positions = restored
+                .iter()
+                .map(|position| (position.market_index, position.clone()))
+                .collect();
+            state.seeded = true;
+        }
+
+        let divergences = match self.reconcile(http).await {
+            Ok(divergences) => divergences,
+            Err(e) => {
+                let mut state = self.lock();
+                state.positions.clear();
+                state.seeded = false;
+                return Err(e);
+            }
+        };
+        let diverged = |market: u8| {
+            divergences.iter().any(|event| {
+                matches!(event, PositionEvent::Divergence { market_index, .. } if *market_index == market)
+            })
+        };
+        let kept = restored
+            .iter()
+            .filter(|position| !diverged(position.market_index))
+            .count();
+        Ok(RestoreReport {
+            found: true,
+            kept,
+            discarded: divergences.len(),
+        })
+    }
+
+    /// Replace the positions with an exchange snapshot
+    ///
+    /// Markets whose incremental size differs from the snapshot by more than
+    /// the threshold produce a [`PositionEvent::Divergence`], which is also
+    /// broadcast to subscribers and logged. The first snapshot only seeds the
+    /// state. Markets missing from a snapshot are flat.
+    pub fn apply_snapshot(&self, snapshot: &[AccountPosition]) -> Vec<PositionEvent> {
+        let mut events = Vec::new();
+        {
+            let mut state = self.lock();
+            let mut exchange: BTreeMap<u8, Position> = snapshot
+                .iter()
+                .map(|position| {
+                    let position = Position {
+                        market_index: position.market_id,
+                        size: position.size(),
+                        avg_entry_price: position.avg_entry_price,
+                        realized_pnl: position.realized_pnl,
+                        ..Position::default()
+                    };
+                    (position.market_index, position)
+                })
+                .collect();
+
+            let mut markets = Vec::new();
+            if state.seeded {
+                markets.extend(state.positions.keys().chain(exchange.keys()).copied());
+            }
+            markets.sort_unstable();
+            markets.dedup();
+            for market_index in markets {
+                let local_size = state
+                    .positions
+                    .get(&market_index)
+                    .map_or(Decimal::ZERO, |position| position.size);
+                let exchange_size = exchange
+                    .get(&market_index)
+                    .map_or(Decimal::ZERO, |position| position.size);
+                if (local_size - exchange_size).abs() > self.inner.divergence_threshold {
+                    events.push(PositionEvent::Divergence {
+                        market_index,
+                        local_size,
+                        exchange_size,
+                    });
+                }
+            }
+
+            // Snapshots carry no funding or fees; keep what was booked
+            for (&market_index, local) in &state.positions {
+                if local.funding_pnl.is_zero() && local.fee_paid.is_zero() {
+                    continue;
+                }
+                let position = exchange.entry(market_index).or_insert_with(|| Position {
+                    market_index,
+                    ..Position::default()
+                });
+                position.funding_pnl = local.funding_pnl;
+                position.fee_paid = local.fee_paid;
+            }
+            state.positions = exchange;
+            state.seeded = true;
+            state.updated_at_ms = Some(now_ms() as i64);
+        }
+
+        for event in &events {
+            tracing::warn!(?event, "Position state diverged from the exchange snapshot");
+            // No subscribers is fine
+            let _ = self.inner.events.send(event.clone());
+        }
+        events
+    }
+
+    /// Apply the positions, fills and funding in an `account_all` frame
+    ///
+    /// Positions in the frame are taken as the exchange's word and replace
+    /// the market's state; fills are booked incrementally for markets the
+    /// frame has no position for. A fill's `fee` is booked as fees paid, and
+    /// `funding_histories` entries as funding. Fits the account callback of
+    /// [`WsClient::run`](crate::ws_client::WsClient::run).
+    pub fn apply_account_frame(&self, data: &Value) {
+        let mut sync = self.sync();
+        if let Ingest::Apply(data) = sync.push(account_frame_timestamp(data), data.clone()) {
+            self.apply_frame(&data);
+        }
+    }
+
+    fn apply_frame(&self, data: &Value) {
+        let timestamp_ms = account_frame_timestamp(data).unwrap_or_else(now_ms) as i64;
+        let positions = frame_positions(data.get("positions"));
+        let fills = frame_fills(data.get("trades"), self.inner.account_index);
+        let funding = frame_funding(data.get("funding_histories"));
+
+        let mut state = self.lock();
+        state.updated_at_ms = Some(now_ms() as i64);
+        for position in &positions {
+            let current = state.position_mut(position.market_index).clone();
+            let realized_pnl = if position.realized_pnl.is_zero() {
+                current.realized_pnl
+            } else {
+                position.realized_pnl
+            };
+            state.book(
+                timestamp_ms,
+                position.market_index,
+                PnlSource::Realized,
+                realized_pnl - current.realized_pnl,
+            );
+            state.positions.insert(
+                position.market_index,
+                Position {
+                    realized_pnl,
+                    funding_pnl: current.funding_pnl,
+                    fee_paid: current.fee_paid,
+                    ..position.clone()
+                },
+            );
+        }
+        for fill in fills {
+            let timestamp_ms = fill.timestamp_ms.unwrap_or(timestamp_ms);
+            state.fee(timestamp_ms, fill.market_index, fill.fee);
+            if positions
+                .iter()
+                .any(|position| position.market_index == fill.market_index)
+            {
+                continue;
+            }
+            state.fill(timestamp_ms, &fill);
+        }
+        for payment in &funding {
+            state.funding(payment);
+        }
+    }
+
+    /// Book one fill of this account
+    pub fn apply_fill(&self, market_index: u8, is_ask: bool, size: Decimal, price: Decimal) {
+        let fill = Fill {
+            market_index,
+            is_ask,
+            size,
+            price,
+            fee: Decimal::ZERO,
+            timestamp_ms: None,
+        };
+        let mut state = self.lock();
+        state.updated_at_ms = Some(now_ms() as i64);
+        state.fill(now_ms() as i64, &fill);
+    }
+
+    /// Book a trading fee paid in one market
+    pub fn apply_fee(&self, market_index: u8, fee: Decimal) {
+        self.lock().fee(now_ms() as i64, market_index, fee);
+    }
+
+    /// Book funding payments, such as those from
+    /// [`HTTPClient::get_position_funding`]
+    ///
+    /// Payments already booked, matched by market and funding id, are
+    /// skipped, so overlapping history pages and the account stream can both
+    /// be fed in. Returns the funding newly booked.
+    pub fn apply_funding(&self, payments: &[FundingPayment]) -> Decimal {
+        let mut state = self.lock();
+        payments.iter().map(|payment| state.funding(payment)).sum()
+    }
+
+    /// Fetch the latest `limit` funding payments and book the new ones
+    ///
+    /// `auth` is an auth token for the account.
+    pub async fn sync_funding(&self, http: &HTTPClient, limit: u32, auth: &str) -> Result<Decimal> {
+        let payments = http
+            .get_position_funding(self.inner.account_index, limit, auth)
+            .await?;
+        Ok(self.apply_funding(&payments))
+    }
+
+    /// PnL of one market split into price, funding and fees
+    ///
+    /// `window` is a range of millisecond timestamps. The whole range `..`
+    /// gives the position's running totals, including realized PnL the
+    /// exchange reported. Any other window sums what was booked locally
+    /// inside it, plus the current unrealized PnL if it is open at the end.
+    pub fn pnl_breakdown(&self, market_index: u8, window: impl RangeBounds<i64>) -> PnlBreakdown {
+        self.lock().breakdown(Some(market_index), &window)
+    }
+
+    /// PnL across all markets split into price, funding and fees
+    ///
+    /// `window` works as in [`PositionManager::pnl_breakdown`].
+    pub fn total_pnl_breakdown(&self, window: impl RangeBounds<i64>) -> PnlBreakdown {
+        self.lock().breakdown(None, &window)
+    }
+
+    /// Set the price a market's position is marked at, observed now
+    pub fn set_mark_price(&self, market_index: u8, price: Decimal) {
+        let mark = FreshPrice::observed(price, self.clock.as_ref());
+        self.lock().marks.insert(market_index, mark);
+    }
+
+    /// Latest mark of a market and when it was set
+    pub fn mark_price(&self, market_index: u8) -> Option<FreshPrice> {
+        self.lock().marks.get(&market_index).copied()
+    }
+
+    /// Mark a market at its order book mid
+    ///
+    /// Fits the order book callback of
+    /// [`WsClient::run`](crate::ws_client::WsClient::run) once the market id
+    /// is parsed.
+    pub fn apply_order_book(&self, market_index: u8, order_book: &OrderBook) {
+        if let Some(mid) = order_book.mid_price() {
+            self.set_mark_price(market_index, mid);
+        }
+    }
+
+    /// Position in one market, if the account has ever held one there
+    pub fn position(&self, market_index: u8) -> Option<Position> {
+        self.lock().positions.get(&market_index).cloned()
+    }
+
+    /// Every non-flat position, by market index
+    pub fn positions(&self) -> Vec<Position> {
+        self.lock()
+            .positions
+            .values()
+            .filter(|position| !position.size.is_zero())
+            .cloned()
+            .collect()
+    }
+
+    /// Unrealized PnL of one market at its latest mark
+    pub fn unrealized_pnl(&self, market_index: u8) -> Option<Decimal> {
+        let state = self.lock();
+        let position = state.positions.get(&market_index)?;
+        Some(position.unrealized_pnl(state.mark(position)))
+    }
+
+    /// Unrealized PnL across all markets
+    pub fn total_unrealized_pnl(&self) -> Decimal {
+        let state = self.lock();
+        state
+            .positions
+            .values()
+            .map(|position| position.unrealized_pnl(state.mark(position)))
+            .sum()
+    }
+
+    /// Gross exposure in USD: the absolute notional of every position at its
+    /// latest mark
+    pub fn exposure(&self) -> Decimal {
+        let state = self.lock();
+        state
+            .positions
+            .values()
+            .map(|position| position.size.abs() * state.mark(position))
+            .sum()
+    }
+
+    fn sync(&self) -> MutexGuard<'_, SnapshotSync<Value>> {
+        self.inner
+            .sync
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.inner
+            .state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl RiskState for PositionManager {
+    fn position(&self, market_index: u8) -> Option<Decimal> {
+        let state = self.lock();
+        Some(
+            state
+                .positions
+                .get(&market_index)
+                .map(|position| position.size)
+                .unwrap_or_default(),
+        )
+    }
+
+    fn position_updated_at_ms(&self, _market_index: u8) -> Option<i64> {
+        self.lock().updated_at_ms
+    }
+
+    fn mid_price(&self, market_index: u8) -> Option<FreshPrice> {
+        self.mark_price(market_index)
+    }
+}
+
+/// A fill decoded from an account frame
+struct Fill {
+    market_index: u8,
+    is_ask: bool,
+    size: Decimal,
+    price: Decimal,
+    fee: Decimal,
+    timestamp_ms: Option<i64>,
+}
+
+/// Objects in a list, or under an object's keys with the key as a fallback
+/// market index
+fn entries(value: Option<&Value>) -> Vec<(Option<&str>, &Value)> {
+    match value {
+        Some(Value::Array(list)) => list.iter().map(|entry| (None, entry)).collect(),
+        Some(Value::Object(groups)) => groups
+            .iter()
+            .flat_map(|(key, group)| match group {
+                Value::Array(list) => list
+                    .iter()
+                    .map(|entry| (Some(key.as_str()), entry))
+                    .collect(),
+                entry => vec![(Some(key.as_str()), entry)],
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn market_index(key: Option<&str>, entry: &Value) -> Option<u8> {
+    ["market_id", "market_index"]
+        .iter()
+        .find_map(|field| json_integer(entry.get(*field)))
+        .or_else(|| key?.parse().ok())?
+        .try_into()
+        .ok()
+}
+
+fn frame_positions(value: Option<&Value>) -> Vec<Position> {
+    entries(value)
+        .into_iter()
+        .filter_map(|(key, entry)| {
+            let size = json_decimal(entry.get("position"))?;
+            let sign = json_integer(entry.get("sign")).unwrap_or(1);
+            Some(Position {
+                market_index: market_index(key, entry)?,
+                size: if sign < 0 { -size.abs() } else { size.abs() },
+                avg_entry_price: json_decimal(entry.get("avg_entry_price"))
+                    .or_else(|| json_decimal(entry.get("entry_price")))
+                    .unwrap_or_default(),
+                realized_pnl: json_decimal(entry.get("realized_pnl")).unwrap_or_default(),
+                ..Position::default()
+            })
+        })
+        .collect()
+}
+
+fn frame_fills(value: Option<&Value>, account_index: i64) -> Vec<Fill> {
+    entries(value)
+        .into_iter()
+        .filter_map(|(key, entry)| {
+            // Our side comes from `is_ask` when present, otherwise from which
+            // side of the trade the account was on
+            let is_ask = match entry.get("is_ask") {
+                Some(Value::Bool(is_ask)) => *is_ask,
+                Some(value) => json_integer(Some(value))? != 0,
+                None if json_integer(entry.get("ask_account_id")) == Some(account_index) => true,
+                None if json_integer(entry.get("bid_account_id")) == Some(account_index) => false,
+                None => return None,
+            };
+            Some(Fill {
+                market_index: market_index(key, entry)?,
+                is_ask,
+                size: json_decimal(entry.get("size"))?,
+                price: json_decimal(entry.get("price"))?,
+                fee: json_decimal(entry.get("fee")).unwrap_or_default(),
+                timestamp_ms: json_integer(entry.get("timestamp")),
+            })
+        })
+        .collect()
+}
+
+fn frame_funding(value: Option<&Value>) -> Vec<FundingPayment> {
+    entries(value)
+        .into_iter()
+        .filter_map(|(key, entry)| {
+            Some(FundingPayment {
+                market_id: market_index(key, entry)?,
+                funding_id: json_integer(entry.get("funding_id")).unwrap_or_default(),
+                timestamp: json_integer(entry.get("timestamp"))?,
+                change: json_decimal(entry.get("change"))?,
+                rate: json_decimal(entry.get("rate")).unwrap_or_default(),
+                position_size: json_decimal(entry.get("position_size")).unwrap_or_default(),
+                position_side: entry
+                    .get("position_side")
+                    .and_then(Value::as_str)
+                    .unwrap_or_default()
+                    .to_string(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::MockTransport;
+    use crate::ws_client::PriceLevel;
+    use serde_json::json;
+    use std::time::Duration;
+
+    fn dec(value: &str) -> Decimal {
+        value.parse().unwrap()
+    }
+
+    fn snapshot(market_id: u8, sign: i8, position: &str, entry: &str) -> AccountPosition {
+        AccountPosition {
+            market_id,
+            sign,
+            position: dec(position),
+            avg_entry_price: dec(entry),
+            unrealized_pnl: Decimal::ZERO,
+            realized_pnl: Decimal::ZERO,
+            position_value: Decimal::ZERO,
+            initial_margin_fraction: Decimal::ZERO,
+            margin_mode: 0,
+            open_order_count: 0,
+        }
+    }
+
+    #[test]
+    fn test_fills_average_realize_and_flip() {
+        let mut position = Position::default();
+        position.apply_fill(false, dec("1"), dec("3000"));
+        position.apply_fill(false, dec("1"), dec("3100"));
+        assert_eq!(position.size, dec("2"));
+        assert_eq!(position.avg_entry_price, dec("3050"));
+
+        // Sell 3: close 2 at +50 each, open 1 short
+        position.apply_fill(true, dec("3"), dec("3100"));
+        assert_eq!(position.size, dec("-1"));
+        assert_eq!(position.avg_entry_price, dec("3100"));
+        assert_eq!(position.realized_pnl, dec("100"));
+        assert_eq!(position.unrealized_pnl(dec("3000")), dec("100"));
+
+        position.apply_fill(false, dec("1"), dec("3000"));
+        assert!(position.size.is_zero());
+        assert_eq!(position.avg_entry_price, Decimal::ZERO);
+        assert_eq!(position.realized_pnl, dec("200"));
+    }
+
+    #[test]
+    fn test_marks_and_exposure() {
+        let positions = PositionManager::new(1, Decimal::ZERO);
+        positions.apply_snapshot(&[snapshot(0, 1, "0.5", "3000"), snapshot(1, -1, "2", "100")]);
+
+        // Unmarked positions count at entry
+        assert_eq!(positions.exposure(), dec("1700"));
+        assert_eq!(positions.total_unrealized_pnl(), Decimal::ZERO);
+
+        positions.apply_order_book(
+            0,
+            &OrderBook {
+                asks: vec![PriceLevel {
+                    price: dec("3101"),
+                    size: dec("1"),
+                }],
+                bids: vec![PriceLevel {
+                    price: dec("3099"),
+                    size: dec("1"),
+                }],
+                offset: None,
+            },
+        );
+        positions.set_mark_price(1, dec("90"));
+        assert_eq!(positions.unrealized_pnl(0), Some(dec("50")));
+        assert_eq!(positions.unrealized_pnl(1), Some(dec("20")));
+        assert_eq!(positions.total_unrealized_pnl(), dec("70"));
+        assert_eq!(positions.exposure(), dec("1730"));
+    }
+
+    #[test]
+    fn test_marks_age_while_the_feed_is_frozen() {
+        let clock = Arc::new(crate::clock::ManualClock::at_ms(0));
+        let positions = PositionManager::new(1, Decimal::ZERO).clock(clock.clone());
+        assert_eq!(positions.mark_price(0), None);
+
+        positions.set_mark_price(0, dec("3000"));
+        clock.advance(Duration::from_secs(3));
+        let mark = positions.mark_price(0).unwrap();
+        assert_eq!(mark.value, dec("3000"));
+        assert_eq!(mark.age(clock.as_ref()), Duration::from_secs(3));
+        assert!(matches!(
+            RiskState::mid_price(&positions, 0)
+                .unwrap()
+                .fresh(clock.as_ref(), Duration::from_secs(2)),
+            Err(crate::errors::LighterError::StalePrice { .. })
+        ));
+
+        // A new mark is fresh again
+        positions.set_mark_price(0, dec("3010"));
+        assert_eq!(
+            positions
+                .mark_price(0)
+                .unwrap()
+                .fresh(clock.as_ref(), Duration::from_secs(2))
+                .unwrap(),
+            dec("3010")
+        );
+    }
+
+    #[test]
+    fn test_account_frames_update_positions() {
+        let positions = PositionManager::new(7, Decimal::ZERO);
+
+        // Exchange position objects keyed by market
+        positions.apply_account_frame(&json!({
+            "positions": {
+                "0": { "market_id": 0, "sign": -1, "position": "0.2", "avg_entry_price": "3000" }
+            }
+        }));
+        assert_eq!(positions.position(0).unwrap().size, dec("-0.2"));
+
+        // Fills without a position in the same frame are booked
+        positions.apply_account_frame(&json!({
+            "trades": {
+                "0": [{ "market_id": 0, "size": "0.1", "price": "2900",
+                        "ask_account_id": 9, "bid_account_id": 7 }]
+            }
+        }));
+        let position = positions.position(0).unwrap();
+        assert_eq!(position.size, dec("-0.1"));
+        assert_eq!(position.realized_pnl, dec("10"));
+
+        // A fill reported alongside its position isn't counted twice
+        positions.apply_account_frame(&json!({
+            "positions": [{ "market_index": 0, "sign": 1, "position": "0.1", "entry_price": "2950" }],
+            "trades": [{ "market_index": 0, "is_ask": 0, "size": "0.2", "price": "2950" }]
+        }));
+        let position = positions.position(0).unwrap();
+        assert_eq!(position.size, dec("0.1"));
+        assert_eq!(position.avg_entry_price, dec("2950"));
+        assert_eq!(position.realized_pnl, dec("10"));
+    }
+
+    #[tokio::test]
+    async fn test_reconcile_flags_divergence() {
+        let positions = PositionManager::new(1, dec("0.001"));
+        let mut events = positions.subscribe();
+        assert!(positions
+            .apply_snapshot(&[snapshot(0, 1, "1", "3000")])
+            .is_empty());
+
+        // Missed a partial close and a fill in market 1
+        positions.apply_fill(0, true, dec("0.5"), dec("3100"));
+        positions.apply_fill(2, false, dec("0.0005"), dec("10"));
+
+        let mock = Arc::new(MockTransport::new());
+        mock.push_response(
+            "/api/v1/account",
+            200,
+            r#"{"code":200,"total":1,"accounts":[{"index":1,"positions":[
+                {"market_id":0,"sign":1,"position":"0.2","avg_entry_price":"3000"},
+                {"market_id":1,"sign":-1,"position":"3","avg_entry_price":"1.5"},
+                {"market_id":2,"sign":1,"position":"0.0005","avg_entry_price":"10"}
+            ]}]}"#,
+        );
+        let http = HTTPClient::with_transport("http://mock", mock.clone());
+        let divergences = positions.reconcile(&http).await.unwrap();
+
+        let expected = vec![
+            PositionEvent::Divergence {
+                market_index: 0,
+                local_size: dec("0.5"),
+                exchange_size: dec("0.2"),
+            },
+            PositionEvent::Divergence {
+                market_index: 1,
+                local_size: Decimal::ZERO,
+                exchange_size: dec("-3"),
+            },
+        ];
+        assert_eq!(divergences, expected);
+        assert_eq!(events.recv().await.unwrap(), expected[0]);
+        assert_eq!(events.recv().await.unwrap(), expected[1]);
+
+        // State now follows the exchange
+        assert_eq!(positions.position(0).unwrap().size, dec("0.2"));
+        assert_eq!(positions.position(1).unwrap().size, dec("-3"));
+        assert!(mock.requests()[0].url.ends_with("by=index&value=1"));
+
+        // A matching snapshot is quiet
+        assert!(positions
+            .apply_snapshot(&[
+                snapshot(0, 1, "0.2", "3000"),
+                snapshot(1, -1, "3", "1.5"),
+                snapshot(2, 1, "0.0005", "10"),
+            ])
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_funding_sign_matches_payment_history() {
+        let mock = Arc::new(MockTransport::new());
+        mock.set_handler("/api/v1/positionFunding", |_| {
+            Ok(crate::transport::HttpResponse::new(
+                200,
+                r#"{"code":200,"position_fundings":[
+                    {"timestamp":1700007200,"market_id":0,"funding_id":3,"change":"-0.1520",
+                     "rate":"0.0001","position_size":"0.5","position_side":"long"},
+                    {"timestamp":1700003600,"market_id":1,"funding_id":2,"change":"0.0300",
+                     "rate":"0.00005","position_size":"4","position_side":"short"},
+                    {"timestamp":1700000000,"market_id":0,"funding_id":1,"change":"0.0455",
+                     "rate":"-0.00003","position_size":"0.5","position_side":"long"}
+                ]}"#,
+            ))
+        });
+        let http = HTTPClient::with_transport("http://mock", mock);
+
+        let payments = http.get_position_funding(1, 100, "token").await.unwrap();
+        for payment in &payments {
+            let expected = funding_pnl(payment.signed_position_size(), Decimal::ONE, payment.rate);
+            assert_eq!(
+                expected.is_sign_negative(),
+                payment.change.is_sign_negative(),
+                "{payment:?}"
+            );
+            // The mark price the exchange used comes out positive
+            assert!(payment.change / expected > Decimal::ZERO);
+        }
+
+        let positions = PositionManager::new(1, dec("0.001"));
+        assert_eq!(
+            positions.sync_funding(&http, 100, "token").await.unwrap(),
+            dec("-0.0765")
+        );
+        // Overlapping pages are booked once
+        assert_eq!(positions.apply_funding(&payments), Decimal::ZERO);
+        assert_eq!(positions.pnl_breakdown(0, ..).funding_pnl, dec("-0.1065"));
+        assert_eq!(positions.pnl_breakdown(1, ..).funding_pnl, dec("0.0300"));
+        assert_eq!(
+            positions
+                .pnl_breakdown(0, 1_700_000_000_000..1_700_007_200_000)
+                .funding_pnl,
+            dec("0.0455")
+        );
+    }
+
+    #[test]
+    fn test_pnl_breakdown_by_source_and_window() {
+        let positions = PositionManager::new(1, Decimal::ZERO);
+        let trade = |is_ask: bool, size: &str, price: &str, fee: &str, timestamp: i64| {
+            json!({ "trades": [{
+                "market_index": 0, "is_ask": is_ask, "size": size, "price": price,
+                "fee": fee, "timestamp": timestamp
+            }] })
+        };
+        positions.apply_account_frame(&trade(false, "2", "3000", "1.2", 1_000));
+        positions.apply_account_frame(&json!({
+            "funding_histories": { "0": [{
+                "timestamp": 5, "funding_id": 9, "change": "-0.6",
+                "rate": "0.0001", "position_size": "2", "position_side": "long"
+            }] }
+        }));
+        positions.apply_account_frame(&trade(true, "1", "3100", "0.62", 10_000));
+        positions.set_mark_price(0, dec("3050"));
+
+        let all = positions.pnl_breakdown(0, ..);
+        assert_eq!(
+            all,
+            PnlBreakdown {
+                realized_pnl: dec("100"),
+                unrealized_pnl: dec("50"),
+                funding_pnl: dec("-0.6"),
+                fee_paid: dec("1.82"),
+            }
+        );
+        assert_eq!(all.price_pnl(), dec("150"));
+        assert_eq!(all.net(), dec("147.58"));
+        let position = positions.position(0).unwrap();
+        assert_eq!(position.price_pnl(dec("3050")), dec("150"));
+
+        // Before the sale: the entry fee and the funding, no marks
+        let early = positions.pnl_breakdown(0, ..6_000);
+        assert_eq!(early.realized_pnl, Decimal::ZERO);
+        assert_eq!(early.unrealized_pnl, Decimal::ZERO);
+        assert_eq!(early.funding_pnl, dec("-0.6"));
+        assert_eq!(early.fee_paid, dec("1.2"));
+
+        // Since the sale, marked up to now
+        let late = positions.total_pnl_breakdown(6_000..);
+        assert_eq!(late.realized_pnl, dec("100"));
+        assert_eq!(late.unrealized_pnl, dec("50"));
+        assert_eq!(late.fee_paid, dec("0.62"));
+
+        // Funding and fees survive the next exchange snapshot
+        positions.apply_snapshot(&[snapshot(0, 1, "1", "3000")]);
+        assert_eq!(positions.pnl_breakdown(0, ..).fee_paid, dec("1.82"));
+        assert_eq!(positions.pnl_breakdown(0, ..).funding_pnl, dec("-0.6"));
+    }
+
+    #[tokio::test]
+    async fn test_restore_flags_positions_that_moved() {
+        use crate::state_store::{MemoryStateStore, StateStore};
+
+        let saved = PositionManager::new(1, dec("0.001"));
+        saved.apply_snapshot(&[snapshot(0, 1, "1", "3000"), snapshot(1, -1, "2", "100")]);
+        let store = MemoryStateStore::new();
+        saved.save_state(&store).unwrap();
+
+        let mock = Arc::new(MockTransport::new());
+        mock.set_handler("/api/v1/account", |_| {
+            Ok(crate::transport::HttpResponse::new(
+                200,
+                r#"{"code":200,"accounts":[{"index":1,"positions":[
+                    {"market_id":0,"sign":1,"position":"0.4","avg_entry_price":"3000"},
+                    {"market_id":1,"sign":-1,"position":"2","avg_entry_price":"100"}
+                ]}]}"#,
+            ))
+        });
+        let http = HTTPClient::with_transport("http://mock", mock);
+
+        let restarted = PositionManager::new(1, dec("0.001"));
+        let report = restarted
+            .restore_and_reconcile(&store, &http)
+            .await
+            .unwrap();
+        assert_eq!(
+            report,
+            RestoreReport {
+                found: true,
+                kept: 1,
+                discarded: 1
+            }
+        );
+        assert_eq!(restarted.position(0).unwrap().size, dec("0.4"));
+
+        // A corrupt save only seeds from the exchange
+        store.save(POSITIONS_STATE, b"[]").unwrap();
+        let cold = PositionManager::new(1, dec("0.001"));
+        let report = cold.restore_and_reconcile(&store, &http).await.unwrap();
+        assert!(!report.found);
+        assert_eq!(cold.positions().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_reconcile_replays_frames_after_snapshot() {
+        let positions = PositionManager::new(1, dec("0.001"));
+        let feed = positions.clone();
+        let mock = Arc::new(MockTransport::new());
+        mock.set_handler("/api/v1/account", move |_| {
+            let fill = |size: &str, timestamp: u64| {
+                json!({ "trades": [{
+                    "market_index": 0, "is_ask": 0, "size": size, "price": "3000",
+                    "timestamp": timestamp
+                }] })
+            };
+            // The first fill is in the snapshot, the second came after it
+            feed.apply_account_frame(&fill("1", 1_000));
+            feed.apply_account_frame(&fill("0.5", now_ms() + 60_000));
+            Ok(crate::transport::HttpResponse::new(
+                200,
+                r#"{"code":200,"accounts":[{"index":1,"positions":[
+                    {"market_id":0,"sign":1,"position":"1","avg_entry_price":"3000"}
+                ]}]}"#,
+            ))
+        });
+        let http = HTTPClient::with_transport("http://mock", mock);
+
+        positions.reconcile(&http).await.unwrap();
+        assert_eq!(positions.position(0).unwrap().size, dec("1.5"));
+    }
+}
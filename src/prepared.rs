@@ -0,0 +1,381 @@
+//! Orders signed now and sent once confirmed
+//!
+//! For workflows where a person approves large orders,
+//! [`TxClient::prepare_order`](crate::client::TxClient::prepare_order) signs
+//! an order without sending it. The [`PreparedTx`] it returns shows what
+//! would be sent, with a [`PreparedTx::summary`] for display and the
+//! [`OrderPreview`] of its notional, fees and margin, until it is either
+//! sent with [`PreparedTx::submit`] or dropped with [`PreparedTx::discard`].
+//!
+//! The order took its nonce when it was signed, and later transactions can
+//! only land once that nonce is used. Discarding gives it back when nothing
+//! was signed since; otherwise a no-op transaction is sent in its place,
+//! like the fillers of [`nonce_gap`](crate::nonce_gap) healing. A prepared
+//! order dropped without either leaves the same gap behind.
+//!
+//! A signed transaction is only valid until its `expired_at`. Submitting
+//! one past that fails with
+//! [`LighterError::PreparedTxExpired`] instead of sending something the
+//! exchange would reject, and discards it.
+//!
+//! ```no_run
+//! use lighter_rs::client::TxClient;
+//! use lighter_rs::types::CreateOrderTxReq;
+//!
+//! # async fn example(tx_client: TxClient, req: CreateOrderTxReq, approved: bool) -> lighter_rs::Result<()> {
+//! let prepared = tx_client.prepare_order(&req, None).await?;
+//! println!("{}", prepared.summary());
+//! if approved {
+//!     prepared.submit().await?;
+//! } else {
+//!     prepared.discard().await?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt;
+
+use rust_decimal::Decimal;
+
+use crate::client::{MarketDetails, TxClient, TxResponse};
+use crate::constants::*;
+use crate::errors::{LighterError, Result};
+use crate::order_preview::{preview, OrderPreview, TopOfBook};
+use crate::types::{CreateOrderTxReq, L2CreateOrderTxInfo};
+
+/// What discarding did with the prepared order's nonce
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NonceRelease {
+    /// Nothing was signed since, so the next transaction reuses the nonce
+    Released,
+    /// Later nonces were handed out, so a no-op transaction used it up
+    Filled,
+}
+
+/// A signed order held back until it is confirmed
+///
+/// Built with [`TxClient::prepare_order`].
+#[must_use = "a prepared order holds a nonce until it is submitted or discarded"]
+pub struct PreparedTx<'a> {
+    client: &'a TxClient,
+    order: L2CreateOrderTxInfo,
+    req: CreateOrderTxReq,
+    details: MarketDetails,
+    preview: OrderPreview,
+}
+
+impl<'a> PreparedTx<'a> {
+    pub(crate) fn new(
+        client: &'a TxClient,
+        order: L2CreateOrderTxInfo,
+        details: &MarketDetails,
+    ) -> Self {
+        // As signed, after any clamping by the risk checks
+        let req = CreateOrderTxReq {
+            market_index: order.market_index,
+            client_order_index: order.client_order_index,
+            base_amount: order.base_amount,
+            price: order.price,
+            is_ask: order.is_ask,
+            order_type: order.order_type,
+            time_in_force: order.time_in_force,
+            reduce_only: order.reduce_only,
+            trigger_price: order.trigger_price,
+            order_expiry: order.order_expiry,
+        };
+        let preview = preview(&req, details, &TopOfBook::default());
+        Self {
+            client,
+            order,
+            req,
+            details: details.clone(),
+            preview,
+        }
+    }
+
+    /// The signed order
+    pub fn order(&self) -> &L2CreateOrderTxInfo {
+        &self.order
+    }
+
+    pub fn nonce(&self) -> i64 {
+        self.order.nonce
+    }
+
+    /// Milliseconds since the Unix epoch after which the exchange refuses
+    /// the transaction
+    pub fn expired_at(&self) -> i64 {
+        self.order.expired_at
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.client.clock().now_ms() >= self.order.expired_at
+    }
+
+    /// Notional, fees and margin, with the order judged a maker unless its
+    /// type always takes; see [`PreparedTx::preview_against`]
+    pub fn preview(&self) -> &OrderPreview {
+        &self.preview
+    }
+
+    /// The preview with maker or taker decided against `top`
+    pub fn preview_against(&self, top: &TopOfBook) -> OrderPreview {
+        preview(&self.req, &self.details, top)
+    }
+
+    /// One line describing the order, for a person to approve
+    pub fn summary(&self) -> String {
+        self.to_string()
+    }
+
+    /// Send the order
+    ///
+    /// Fails with [`LighterError::PreparedTxExpired`] once its expiry has
+    /// passed, after discarding it.
+    pub async fn submit(self) -> Result<TxResponse> {
+        let now_ms = self.client.clock().now_ms();
+        if now_ms >= self.order.expired_at {
+            let expired_at = self.order.expired_at;
+            if let Err(e) = self.discard().await {
+                tracing::warn!(error = %e, "Failed to release the nonce of an expired prepared order");
+            }
+            return Err(LighterError::PreparedTxExpired { expired_at, now_ms });
+        }
+        self.client.send_transaction(&self.order).await
+    }
+
+    /// Drop the order, giving its nonce back or using it up
+    pub async fn discard(self) -> Result<NonceRelease> {
+        let nonce = self.order.nonce;
+        if self
+            .client
+            .nonces()
+            .release(self.order.account_index, self.order.api_key_index, nonce)
+        {
+            return Ok(NonceRelease::Released);
+        }
+        let filler = self.client.sign_filler(nonce).await?;
+        let response = self.client.send_batch(&[filler]).await?;
+        if !response.is_success() {
+            return Err(LighterError::ApiError(format!(
+                "Filler for the nonce {nonce} of a discarded order rejected: {} {}",
+                response.code,
+                response.message.unwrap_or_default()
+            )));
+        }
+        Ok(NonceRelease::Filled)
+    }
+}
+
+impl fmt::Display for PreparedTx<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let req = &self.req;
+        let market = match self.details.symbol.as_str() {
+            "" => format!("market {}", req.market_index),
+            symbol => format!("{symbol} (market {})", req.market_index),
+        };
+        write!(
+            f,
+            "{} {} {market} at {} as {}",
+            if req.is_ask != 0 { "Sell" } else { "Buy" },
+            Decimal::new(req.base_amount, self.details.size_decimals),
+            Decimal::from_i128_with_scale(i128::from(req.price), self.details.price_decimals),
+            order_type_name(req.order_type),
+        )?;
+        if req.trigger_price != 0 {
+            write!(
+                f,
+                " triggered at {}",
+                Decimal::from_i128_with_scale(
+                    i128::from(req.trigger_price),
+                    self.details.price_decimals
+                )
+            )?;
+        }
+        write!(f, ", {}", time_in_force_name(req.time_in_force))?;
+        if req.reduce_only != 0 {
+            write!(f, ", reduce-only")?;
+        }
+        write!(
+            f,
+            "; notional {}, fee {} as maker or {} as taker, margin {}; nonce {}, valid until {}",
+            self.preview.notional.normalize(),
+            self.preview.expected_fee_maker.normalize(),
+            self.preview.expected_fee_taker.normalize(),
+            self.preview.est_margin_required.normalize(),
+            self.order.nonce,
+            format_ms(self.order.expired_at)
+        )
+    }
+}
+
+impl fmt::Debug for PreparedTx<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PreparedTx")
+            .field("order", &self.order)
+            .field("preview", &self.preview)
+            .finish()
+    }
+}
+
+fn order_type_name(order_type: u8) -> &'static str {
+    match order_type {
+        ORDER_TYPE_LIMIT => "a limit order",
+        ORDER_TYPE_MARKET => "a market order",
+        ORDER_TYPE_STOP_LOSS => "a stop loss",
+        ORDER_TYPE_STOP_LOSS_LIMIT => "a stop loss limit",
+        ORDER_TYPE_TAKE_PROFIT => "a take profit",
+        ORDER_TYPE_TAKE_PROFIT_LIMIT => "a take profit limit",
+        ORDER_TYPE_TWAP => "a TWAP",
+        _ => "an order of unknown type",
+    }
+}
+
+fn time_in_force_name(time_in_force: u8) -> &'static str {
+    match time_in_force {
+        TIME_IN_FORCE_IMMEDIATE_OR_CANCEL => "immediate or cancel",
+        TIME_IN_FORCE_GOOD_TILL_TIME => "good till time",
+        TIME_IN_FORCE_POST_ONLY => "post-only",
+        _ => "unknown time in force",
+    }
+}
+
+fn format_ms(ms: i64) -> String {
+    match chrono::DateTime::from_timestamp_millis(ms) {
+        Some(at) => at.format("%Y-%m-%d %H:%M:%S UTC").to_string(),
+        None => ms.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::test_support::mock_builder;
+    use crate::transport::{HttpResponse, MockTransport};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    const SEND_TX_PATH: &str = "/api/v1/sendTx";
+    const BATCH_PATH: &str = "/api/v1/sendTxBatch";
+
+    fn client() -> (TxClient, Arc<MockTransport>, Arc<ManualClock>) {
+        let mock = Arc::new(MockTransport::new());
+        mock.set_handler("/api/v1/orderBookDetails", |_| {
+            Ok(HttpResponse::new(
+                200,
+                r#"{"code":200,"order_book_details":[{"market_id":0,"symbol":"ETH","size_decimals":4,"price_decimals":2,"min_initial_margin_fraction":1000,"maker_fee":"0.0000","taker_fee":"0.0200"}]}"#,
+            ))
+        });
+        mock.set_handler(SEND_TX_PATH, |_| {
+            Ok(HttpResponse::new(200, r#"{"code":200}"#))
+        });
+        mock.set_handler(BATCH_PATH, |_| {
+            Ok(HttpResponse::new(200, r#"{"code":200}"#))
+        });
+        let clock = Arc::new(ManualClock::at_ms(1_700_000_000_000));
+        let tx_client = mock_builder(&mock).clock(clock.clone()).build().unwrap();
+        tx_client.nonces().set(1, 0, 5);
+        (tx_client, mock, clock)
+    }
+
+    fn bid(client_order_index: i64) -> CreateOrderTxReq {
+        CreateOrderTxReq {
+            market_index: 0,
+            client_order_index,
+            base_amount: 1000,
+            price: 300_000,
+            is_ask: 0,
+            order_type: ORDER_TYPE_LIMIT,
+            time_in_force: TIME_IN_FORCE_GOOD_TILL_TIME,
+            reduce_only: 0,
+            trigger_price: 0,
+            order_expiry: 1_700_086_400_000,
+        }
+    }
+
+    fn sent_nonces(mock: &MockTransport, path: &str) -> Vec<i64> {
+        mock.requests_to(path)
+            .iter()
+            .map(|request| {
+                let form: Vec<(String, String)> =
+                    serde_urlencoded::from_bytes(&request.body).unwrap();
+                let infos = &form
+                    .iter()
+                    .find(|(k, _)| k.starts_with("tx_info"))
+                    .unwrap()
+                    .1;
+                let info: serde_json::Value = match path {
+                    BATCH_PATH => serde_json::from_str(
+                        &serde_json::from_str::<Vec<String>>(infos).unwrap()[0],
+                    )
+                    .unwrap(),
+                    _ => serde_json::from_str(infos).unwrap(),
+                };
+                info["Nonce"].as_i64().unwrap()
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_submit_shows_the_order_and_sends_it() {
+        let (tx_client, mock, _) = client();
+        let prepared = tx_client.prepare_order(&bid(1), None).await.unwrap();
+
+        // 0.1 at 3000.00 is 300.00; 0.02% taker, 10% margin
+        assert_eq!(prepared.nonce(), 5);
+        assert_eq!(prepared.preview().notional, Decimal::new(30_000, 2));
+        assert_eq!(
+            prepared.summary(),
+            "Buy 0.1000 ETH (market 0) at 3000.00 as a limit order, good till time; \
+             notional 300, fee 0 as maker or 0.06 as taker, margin 30; \
+             nonce 5, valid until 2023-11-14 22:23:19 UTC"
+        );
+        assert!(mock.requests_to(SEND_TX_PATH).is_empty());
+
+        assert!(prepared.submit().await.unwrap().is_success());
+        assert_eq!(sent_nonces(&mock, SEND_TX_PATH), vec![5]);
+    }
+
+    #[tokio::test]
+    async fn test_discard_keeps_the_nonces_continuous() {
+        let (tx_client, mock, _) = client();
+
+        // Nothing signed since: the next order takes the nonce back
+        let prepared = tx_client.prepare_order(&bid(1), None).await.unwrap();
+        assert_eq!(prepared.discard().await.unwrap(), NonceRelease::Released);
+        let next = tx_client.create_order(&bid(2), None).await.unwrap();
+        assert_eq!(next.nonce, 5);
+
+        // Signed past: a filler uses the nonce up
+        let prepared = tx_client.prepare_order(&bid(3), None).await.unwrap();
+        let later = tx_client.create_order(&bid(4), None).await.unwrap();
+        assert_eq!((prepared.nonce(), later.nonce), (6, 7));
+        assert_eq!(prepared.discard().await.unwrap(), NonceRelease::Filled);
+        assert_eq!(sent_nonces(&mock, BATCH_PATH), vec![6]);
+        assert_eq!(tx_client.nonces().peek(1, 0), Some(8));
+    }
+
+    #[tokio::test]
+    async fn test_expired_order_is_refused_and_discarded() {
+        let (tx_client, mock, clock) = client();
+        let prepared = tx_client.prepare_order(&bid(1), None).await.unwrap();
+        clock.advance(Duration::from_secs(600));
+        assert!(prepared.is_expired());
+
+        let expired_at = prepared.expired_at();
+        match prepared.submit().await {
+            Err(LighterError::PreparedTxExpired {
+                expired_at: at,
+                now_ms,
+            }) => {
+                assert_eq!(at, expired_at);
+                assert_eq!(now_ms - at, 1000);
+            }
+            other => panic!("expected an expiry error, got {other:?}"),
+        }
+        assert!(mock.requests_to(SEND_TX_PATH).is_empty());
+        assert_eq!(tx_client.nonces().peek(1, 0), Some(5));
+    }
+}
@@ -0,0 +1,204 @@
+//! Pre-checks against the exchange's price band
+//!
+//! The exchange refuses limit and trigger orders priced too far from the
+//! market's mark price, answering with a generic "price limits" error after
+//! the round trip. With
+//! [`TxClientBuilder::price_band_check`](crate::client::TxClientBuilder::price_band_check)
+//! set, [`TxClient::create_order`](crate::client::TxClient::create_order) and
+//! the limit, take-profit and stop-loss helpers built on it check the order
+//! first and fail with [`LighterError::PriceOutsideBand`], the prices in
+//! quote currency rather than integer units.
+//!
+//! Limit orders are checked on their price, take-profit and stop-loss orders
+//! on their trigger price, and the limit variants of those on both. Market
+//! orders are not checked: their price is only a slippage bound.
+//!
+//! The band is drawn around the mark price of an attached
+//! [`RiskState`](crate::risk::RiskState), such as a
+//! [`PositionManager`](crate::positions::PositionManager) following the
+//! order book stream, while it is no older than
+//! [`PriceBandCheck::max_mark_staleness`]. Otherwise the mark comes from
+//! [`HTTPClient::get_market_details`](crate::client::HTTPClient::get_market_details).
+//! Its width is the market's reported `price_band_bps`, or
+//! [`PriceBandCheck::default_band_bps`] for markets that don't report one.
+//! A mark that can't be found skips the check, as the exchange still
+//! enforces the band.
+//!
+//! ```
+//! use lighter_rs::client::TxClient;
+//! use lighter_rs::price_band::PriceBandCheck;
+//!
+//! # fn example() -> lighter_rs::Result<()> {
+//! let tx_client = TxClient::builder()
+//!     .api_url("https://testnet.zklighter.elliot.ai")
+//!     .private_key("0x0102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f202122232425262728")
+//!     .price_band_check(PriceBandCheck::new(100))
+//!     .build()?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use rust_decimal::Decimal;
+
+use crate::client::MarketDetails;
+use crate::constants::*;
+use crate::errors::{LighterError, Result};
+use crate::types::CreateOrderTxReq;
+
+/// Default of [`PriceBandCheck::max_mark_staleness`]
+const DEFAULT_MAX_MARK_STALENESS: Duration = Duration::from_secs(5);
+
+/// Settings of the price band pre-check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PriceBandCheck {
+    /// Half-width of the band, in basis points of the mark, for markets
+    /// whose details don't report one
+    pub default_band_bps: u32,
+    /// Oldest streaming mark the band is drawn around; an older one falls
+    /// back to the REST market details
+    pub max_mark_staleness: Duration,
+}
+
+impl PriceBandCheck {
+    pub fn new(default_band_bps: u32) -> Self {
+        Self {
+            default_band_bps,
+            max_mark_staleness: DEFAULT_MAX_MARK_STALENESS,
+        }
+    }
+
+    pub fn max_mark_staleness(mut self, max_mark_staleness: Duration) -> Self {
+        self.max_mark_staleness = max_mark_staleness;
+        self
+    }
+}
+
+/// The prices the exchange accepts around a mark
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PriceBand {
+    pub mark: Decimal,
+    /// Half-width, in basis points of the mark
+    pub band_bps: u32,
+}
+
+impl PriceBand {
+    pub fn new(mark: Decimal, band_bps: u32) -> Self {
+        Self { mark, band_bps }
+    }
+
+    /// Lowest and highest price in the band
+    pub fn allowed(&self) -> (Decimal, Decimal) {
+        let width = self.mark * Decimal::from(self.band_bps) / Decimal::from(10_000);
+        (self.mark - width, self.mark + width)
+    }
+
+    /// Refuse `price` if it is outside the band
+    pub fn check(&self, price: Decimal) -> Result<()> {
+        let (allowed_min, allowed_max) = self.allowed();
+        if price < allowed_min || price > allowed_max {
+            return Err(LighterError::PriceOutsideBand {
+                allowed_min: allowed_min.normalize(),
+                allowed_max: allowed_max.normalize(),
+                attempted: price.normalize(),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Price band settings of one client and the market details fetched for it
+pub(crate) struct PriceBandGuard {
+    check: PriceBandCheck,
+    details: Mutex<HashMap<u8, MarketDetails>>,
+}
+
+impl PriceBandGuard {
+    pub fn new(check: PriceBandCheck) -> Self {
+        Self {
+            check,
+            details: Mutex::default(),
+        }
+    }
+
+    pub fn settings(&self) -> PriceBandCheck {
+        self.check
+    }
+
+    /// Details of a market, if already fetched
+    pub fn details(&self, market_index: u8) -> Option<MarketDetails> {
+        self.details
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .get(&market_index)
+            .cloned()
+    }
+
+    pub fn remember(&self, details: MarketDetails) {
+        self.details
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .insert(details.market_id, details);
+    }
+
+    /// Band of a market around `mark`, as wide as the market reports
+    pub fn band(&self, details: &MarketDetails, mark: Decimal) -> PriceBand {
+        PriceBand::new(
+            mark,
+            details
+                .price_band_bps
+                .unwrap_or(self.check.default_band_bps),
+        )
+    }
+}
+
+/// Mark a market's REST details report, or else its last trade price
+pub(crate) fn rest_mark(details: &MarketDetails) -> Option<Decimal> {
+    details
+        .mark_price
+        .or(Some(details.last_trade_price))
+        .filter(|mark| !mark.is_zero())
+}
+
+/// Integer prices of an order the exchange holds to the band
+pub(crate) fn banded_prices(req: &CreateOrderTxReq) -> Vec<u64> {
+    match req.order_type {
+        ORDER_TYPE_LIMIT => vec![req.price],
+        ORDER_TYPE_TAKE_PROFIT | ORDER_TYPE_STOP_LOSS => vec![req.trigger_price],
+        ORDER_TYPE_TAKE_PROFIT_LIMIT | ORDER_TYPE_STOP_LOSS_LIMIT => {
+            vec![req.trigger_price, req.price]
+        }
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_band_edges_are_allowed() {
+        let band = PriceBand::new(Decimal::new(2990, 0), 100);
+        assert_eq!(
+            band.allowed(),
+            (Decimal::new(29601, 1), Decimal::new(30199, 1))
+        );
+        assert!(band.check(Decimal::new(29601, 1)).is_ok());
+        assert!(band.check(Decimal::new(30199, 1)).is_ok());
+        match band.check(Decimal::new(30200, 1)) {
+            Err(LighterError::PriceOutsideBand {
+                allowed_min,
+                allowed_max,
+                attempted,
+            }) => {
+                assert_eq!(allowed_min.to_string(), "2960.1");
+                assert_eq!(allowed_max.to_string(), "3019.9");
+                assert_eq!(attempted.to_string(), "3020");
+            }
+            other => panic!("expected PriceOutsideBand, got {other:?}"),
+        }
+    }
+}
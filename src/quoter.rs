@@ -0,0 +1,573 @@
+//! Two-sided quoting plumbing for market makers, enabled with the `quoter`
+//! feature
+//!
+//! [`Quoter`] keeps one bid and one ask resting around the mid price. Feed it
+//! top-of-book prices and it moves the quotes once the target drifts past a
+//! tolerance; feed it account events and it tracks inventory from its own
+//! fills. It holds no strategy: spread, size and limits are whatever the
+//! [`QuoteConfig`] says.
+//!
+//! Once inventory reaches `max_position`, the side that would grow it is
+//! pulled and the other side is quoted reduce-only for at most the inventory.
+//!
+//! ```no_run
+//! use lighter_rs::client::TxClient;
+//! use lighter_rs::quoter::{QuoteConfig, Quoter};
+//! use lighter_rs::Decimal;
+//! use std::sync::Arc;
+//!
+//! # async fn example(tx_client: TxClient) -> lighter_rs::Result<()> {
+//! let quoter = Quoter::new(
+//!     Arc::new(tx_client),
+//!     0,
+//!     QuoteConfig {
+//!         half_spread_bps: Decimal::new(10, 0),
+//!         size: 1000,
+//!         requote_threshold_bps: Decimal::new(2, 0),
+//!         max_position: 5000,
+//!         price_decimals: 2,
+//!         size_decimals: 4,
+//!     },
+//! );
+//! quoter
+//!     .on_top_of_book(Decimal::new(302400, 2), Decimal::new(302500, 2))
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use serde_json::Value;
+
+use crate::client::{PipelinedOutcome, TxClient};
+use crate::errors::{LighterError, Result};
+use crate::tracker::{OrderEvent, OrderState};
+use crate::types::{CancelOrderTxReq, ModifyOrderTxReq, SignedTx};
+use crate::ws_client::OrderBook;
+
+/// Transactions in flight at once when moving quotes
+const PIPELINE_DEPTH: usize = 2;
+
+/// Quoting parameters
+///
+/// Sizes and positions are integer base amounts; `price_decimals` and
+/// `size_decimals` give the market's scaling, as in the order APIs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuoteConfig {
+    /// Distance of each quote from the mid, in basis points
+    pub half_spread_bps: Decimal,
+    /// Base amount of each quote
+    pub size: i64,
+    /// How far a quote's target may drift before the quote is moved, in
+    /// basis points
+    pub requote_threshold_bps: Decimal,
+    /// Largest absolute inventory to quote into
+    pub max_position: i64,
+    pub price_decimals: u32,
+    pub size_decimals: u32,
+}
+
+/// A resting quote
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quote {
+    pub client_order_index: i64,
+    pub price: u64,
+    pub base_amount: i64,
+    pub reduce_only: bool,
+}
+
+/// Both sides of the book as quoted
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Quotes {
+    pub bid: Option<Quote>,
+    pub ask: Option<Quote>,
+}
+
+#[derive(Default)]
+struct State {
+    quotes: Quotes,
+    position: i64,
+}
+
+impl State {
+    fn side_mut(&mut self, is_ask: bool) -> &mut Option<Quote> {
+        if is_ask {
+            &mut self.quotes.ask
+        } else {
+            &mut self.quotes.bid
+        }
+    }
+
+    /// Side and slot of one of our quotes
+    fn find(&mut self, client_order_index: i64) -> Option<(bool, &mut Option<Quote>)> {
+        let is_ours = |quote: Option<Quote>| {
+            quote.is_some_and(|quote| quote.client_order_index == client_order_index)
+        };
+        let is_ask = if is_ours(self.quotes.bid) {
+            false
+        } else if is_ours(self.quotes.ask) {
+            true
+        } else {
+            return None;
+        };
+        Some((is_ask, self.side_mut(is_ask)))
+    }
+}
+
+/// What to do with one side of the book
+#[derive(Debug, Clone, Copy)]
+enum Step {
+    Create,
+    Modify,
+    Cancel,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Action {
+    step: Step,
+    is_ask: bool,
+    quote: Quote,
+}
+
+/// Keeps a bid and an ask resting around the mid
+pub struct Quoter {
+    tx_client: Arc<TxClient>,
+    market_index: u8,
+    config: QuoteConfig,
+    /// Serializes requotes so two ticks never move the same quote
+    requote: tokio::sync::Mutex<()>,
+    state: Mutex<State>,
+}
+
+impl Quoter {
+    /// Create a quoter for one market
+    ///
+    /// Client order indexes come from the client's
+    /// [namespace](crate::order_namespace).
+    pub fn new(tx_client: Arc<TxClient>, market_index: u8, config: QuoteConfig) -> Self {
+        Self {
+            tx_client,
+            market_index,
+            config,
+            requote: tokio::sync::Mutex::new(()),
+            state: Mutex::new(State { ..State::default() }),
+        }
+    }
+
+    /// Current quotes
+    pub fn quotes(&self) -> Quotes {
+        self.lock().quotes
+    }
+
+    /// Inventory in base units: positive when long
+    pub fn position(&self) -> i64 {
+        self.lock().position
+    }
+
+    /// Set the inventory, e.g. from a positions snapshot at startup
+    pub fn set_position(&self, base_amount: i64) {
+        self.lock().position = base_amount;
+    }
+
+    /// Move the quotes to follow the order book's best prices
+    ///
+    /// Does nothing until the book has both a bid and an ask.
+    pub async fn on_order_book(&self, order_book: &OrderBook) -> Result<()> {
+        match (order_book.best_bid(), order_book.best_ask()) {
+            (Some(best_bid), Some(best_ask)) => self.on_top_of_book(best_bid, best_ask).await,
+            _ => Ok(()),
+        }
+    }
+
+    /// Move the quotes to follow a new best bid and ask
+    ///
+    /// Quotes whose target moved by no more than the requote threshold stay
+    /// put. The rest are created, modified or cancelled in one pipelined
+    /// batch. A side whose update the exchange doesn't accept is forgotten and
+    /// quoted afresh on the next tick.
+    pub async fn on_top_of_book(&self, best_bid: Decimal, best_ask: Decimal) -> Result<()> {
+        let _requote = self.requote.lock().await;
+
+        let mid = (best_bid + best_ask) / Decimal::TWO;
+        let actions = {
+            let mut state = self.lock();
+            let mut actions = Vec::new();
+            for is_ask in [false, true] {
+                let target = self.target(&mut state, mid, is_ask)?;
+                let current = *state.side_mut(is_ask);
+                actions.extend(self.plan(is_ask, current, target));
+            }
+            actions
+        };
+        self.execute(actions).await
+    }
+
+    /// Cancel both quotes
+    pub async fn cancel_quotes(&self) -> Result<()> {
+        let _requote = self.requote.lock().await;
+
+        let quotes = self.quotes();
+        let actions = [(false, quotes.bid), (true, quotes.ask)]
+            .into_iter()
+            .filter_map(|(is_ask, quote)| {
+                quote.map(|quote| Action {
+                    step: Step::Cancel,
+                    is_ask,
+                    quote,
+                })
+            })
+            .collect();
+        self.execute(actions).await
+    }
+
+    /// Apply an order event from the account channel
+    ///
+    /// Fills of our quotes move the inventory and shrink the quote; quotes
+    /// the exchange reports filled or cancelled are dropped. Events for other
+    /// orders are ignored.
+    pub fn on_event(&self, event: &OrderEvent) {
+        let mut state = self.lock();
+        match *event {
+            OrderEvent::Fill {
+                client_order_index,
+                base_amount,
+            } => {
+                let base_amount = (base_amount
+                    * Decimal::from(10i64.pow(self.config.size_decimals)))
+                .round()
+                .to_i64()
+                .unwrap_or(0);
+                let Some((is_ask, slot)) = state.find(client_order_index) else {
+                    return;
+                };
+                if let Some(quote) = slot {
+                    quote.base_amount -= base_amount;
+                    if quote.base_amount <= 0 {
+                        *slot = None;
+                    }
+                }
+                state.position += if is_ask { -base_amount } else { base_amount };
+            }
+            OrderEvent::Update {
+                client_order_index,
+                state: order_state,
+                ..
+            } => {
+                if matches!(order_state, OrderState::Filled | OrderState::Cancelled) {
+                    if let Some((_, slot)) = state.find(client_order_index) {
+                        *slot = None;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Apply every order event in an `account_all` frame
+    pub fn apply_account_frame(&self, data: &Value) {
+        for event in OrderEvent::from_account_frame(data) {
+            self.on_event(&event);
+        }
+    }
+
+    /// The quote one side should have, or `None` to pull it
+    fn target(&self, state: &mut State, mid: Decimal, is_ask: bool) -> Result<Option<Quote>> {
+        let config = &self.config;
+        // Inventory this side would reduce; positive means the side closes it
+        let reducible = if is_ask {
+            state.position
+        } else {
+            -state.position
+        };
+        let capped = -reducible >= config.max_position;
+        let reduce_only = reducible >= config.max_position;
+        if capped {
+            return Ok(None);
+        }
+        let base_amount = if reduce_only {
+            config.size.min(reducible)
+        } else {
+            config.size
+        };
+
+        let offset = config.half_spread_bps / Decimal::from(10_000);
+        let scale = Decimal::from(10u64.pow(config.price_decimals));
+        let price = if is_ask {
+            (mid * (Decimal::ONE + offset) * scale).ceil()
+        } else {
+            (mid * (Decimal::ONE - offset) * scale).floor()
+        };
+        let price = price.to_u64().ok_or_else(|| {
+            LighterError::ValidationError(format!("Quote price {price} is out of range"))
+        })?;
+
+        // Keep the current client order index when moving an existing quote
+        let client_order_index = match state.side_mut(is_ask) {
+            Some(quote) if quote.reduce_only == reduce_only => quote.client_order_index,
+            _ => self.tx_client.next_client_order_index(),
+        };
+
+        Ok(Some(Quote {
+            client_order_index,
+            price,
+            base_amount,
+            reduce_only,
+        }))
+    }
+
+    /// Actions that take one side from `current` to `target`
+    fn plan(&self, is_ask: bool, current: Option<Quote>, target: Option<Quote>) -> Vec<Action> {
+        let action = |step, quote| Action {
+            step,
+            is_ask,
+            quote,
+        };
+        match (current, target) {
+            (None, None) => Vec::new(),
+            (Some(current), None) => vec![action(Step::Cancel, current)],
+            (None, Some(target)) => vec![action(Step::Create, target)],
+            // Reduce-only can't be modified, so the quote is replaced
+            (Some(current), Some(target)) if current.reduce_only != target.reduce_only => {
+                vec![action(Step::Cancel, current), action(Step::Create, target)]
+            }
+            (Some(current), Some(target)) => {
+                let drift_bps = Decimal::from(current.price.abs_diff(target.price))
+                    / Decimal::from(target.price.max(1))
+                    * Decimal::from(10_000);
+                if drift_bps > self.config.requote_threshold_bps
+                    || current.base_amount != target.base_amount
+                {
+                    vec![action(Step::Modify, target)]
+                } else {
+                    Vec::new()
+                }
+            }
+        }
+    }
+
+    /// Sign and submit `actions` in order, then record what the exchange
+    /// accepted
+    async fn execute(&self, actions: Vec<Action>) -> Result<()> {
+        if actions.is_empty() {
+            return Ok(());
+        }
+
+        let mut txs = Vec::with_capacity(actions.len());
+        for action in &actions {
+            txs.push(self.sign(action).await?);
+        }
+        let outcomes = self.tx_client.submit_pipelined(txs, PIPELINE_DEPTH).await?;
+
+        let mut skipped = false;
+        let mut state = self.lock();
+        for (action, outcome) in actions.iter().zip(&outcomes) {
+            let slot = state.side_mut(action.is_ask);
+            match (action.step, outcome.is_success()) {
+                (Step::Cancel, true) => *slot = None,
+                (Step::Create | Step::Modify, true) => *slot = Some(action.quote),
+                _ => {
+                    skipped |= matches!(outcome, PipelinedOutcome::Skipped);
+                    tracing::warn!(?action, ?outcome, "Quote update was not accepted");
+                    *slot = None;
+                }
+            }
+        }
+        drop(state);
+
+        // Skipped transactions already took nonces
+        if skipped {
+            self.tx_client.nonces().invalidate_all();
+        }
+        Ok(())
+    }
+
+    async fn sign(&self, action: &Action) -> Result<SignedTx> {
+        let quote = action.quote;
+        match action.step {
+            Step::Create => {
+                let order = self
+                    .tx_client
+                    .create_limit_order(
+                        self.market_index,
+                        quote.client_order_index,
+                        quote.base_amount,
+                        quote.price,
+                        u8::from(action.is_ask),
+                        quote.reduce_only,
+                        None,
+                    )
+                    .await?;
+                SignedTx::new(&order)
+            }
+            Step::Modify => {
+                let req = ModifyOrderTxReq {
+                    market_index: self.market_index,
+                    index: quote.client_order_index,
+                    base_amount: quote.base_amount,
+                    price: quote.price,
+                    trigger_price: 0,
+                };
+                SignedTx::new(&self.tx_client.modify_order(&req, None).await?)
+            }
+            Step::Cancel => {
+                let req = CancelOrderTxReq {
+                    market_index: self.market_index,
+                    index: quote.client_order_index,
+                };
+                SignedTx::new(&self.tx_client.cancel_order(&req, None).await?)
+            }
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(all(test, feature = "simulator"))]
+mod tests {
+    use super::*;
+    use crate::simulator::SimulatedExchange;
+    use crate::test_support::TEST_KEY;
+    use crate::ws_client::PriceLevel;
+
+    fn config() -> QuoteConfig {
+        QuoteConfig {
+            half_spread_bps: Decimal::new(10, 0),
+            size: 1000,
+            requote_threshold_bps: Decimal::new(5, 0),
+            max_position: 1000,
+            price_decimals: 2,
+            size_decimals: 4,
+        }
+    }
+
+    fn quoter() -> (Quoter, SimulatedExchange) {
+        let exchange = SimulatedExchange::builder()
+            .market(0, 2, 4)
+            .account(1, Decimal::new(100_000, 0))
+            .build()
+            .unwrap();
+        let tx_client = TxClient::builder()
+            .api_url("http://simulator")
+            .private_key(TEST_KEY)
+            .account_index(1)
+            .chain_id(304)
+            .transport(Arc::new(exchange.clone()))
+            .build()
+            .unwrap();
+        (Quoter::new(Arc::new(tx_client), 0, config()), exchange)
+    }
+
+    /// Book with a single bid and ask, prices in cents
+    fn book(bid: i64, ask: i64) -> OrderBook {
+        OrderBook {
+            asks: vec![PriceLevel {
+                price: Decimal::new(ask, 2),
+                size: Decimal::new(10_000, 4),
+            }],
+            bids: vec![PriceLevel {
+                price: Decimal::new(bid, 2),
+                size: Decimal::new(10_000, 4),
+            }],
+            offset: None,
+        }
+    }
+
+    /// Resting orders on the exchange as (is_ask, price, size, reduce_only)
+    fn resting(exchange: &SimulatedExchange) -> Vec<(bool, u64, i64, bool)> {
+        let mut orders: Vec<_> = exchange
+            .account(1)
+            .unwrap()
+            .orders
+            .iter()
+            .map(|order| {
+                (
+                    order.is_ask,
+                    order.price,
+                    order.remaining_base_amount,
+                    order.reduce_only,
+                )
+            })
+            .collect();
+        orders.sort();
+        orders
+    }
+
+    #[tokio::test]
+    async fn test_quotes_around_mid() {
+        let (quoter, exchange) = quoter();
+        quoter.on_order_book(&book(302400, 302500)).await.unwrap();
+
+        // Mid 3024.50 ± 10 bps, rounded away from the mid
+        assert_eq!(
+            resting(&exchange),
+            vec![(false, 302147, 1000, false), (true, 302753, 1000, false)]
+        );
+        let quotes = quoter.quotes();
+        assert_eq!(quotes.bid.unwrap().price, 302147);
+        assert_eq!(quotes.ask.unwrap().price, 302753);
+    }
+
+    #[tokio::test]
+    async fn test_requotes_only_past_threshold() {
+        let (quoter, exchange) = quoter();
+        quoter.on_order_book(&book(302400, 302500)).await.unwrap();
+        let quotes = quoter.quotes();
+        let nonce = exchange.next_nonce(1, 0);
+
+        // 1 bps move stays within the 5 bps threshold
+        quoter.on_order_book(&book(302430, 302530)).await.unwrap();
+        assert_eq!(exchange.next_nonce(1, 0), nonce);
+        assert_eq!(quoter.quotes(), quotes);
+
+        // 10 bps move modifies both quotes in place
+        quoter.on_order_book(&book(302700, 302800)).await.unwrap();
+        assert_eq!(exchange.next_nonce(1, 0), nonce + 2);
+        let moved = quoter.quotes();
+        assert_eq!(
+            moved.bid.unwrap().client_order_index,
+            quotes.bid.unwrap().client_order_index
+        );
+        assert_eq!(
+            resting(&exchange),
+            vec![(false, 302447, 1000, false), (true, 303053, 1000, false)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_goes_reduce_only_at_max_position() {
+        let (quoter, exchange) = quoter();
+        let mut frames = exchange.subscribe();
+        quoter.on_order_book(&book(302400, 302500)).await.unwrap();
+
+        // The market trades down through our bid
+        exchange.update_order_book(0, &book(302000, 302100));
+        while let Ok(frame) = frames.try_recv() {
+            quoter.apply_account_frame(&serde_json::from_str(&frame).unwrap());
+        }
+        assert_eq!(quoter.position(), 1000);
+        assert_eq!(quoter.quotes().bid, None);
+
+        // At the cap only a reduce-only ask for the inventory is quoted
+        quoter.on_order_book(&book(302000, 302100)).await.unwrap();
+        assert_eq!(resting(&exchange), vec![(true, 302353, 1000, true)]);
+        let quotes = quoter.quotes();
+        assert_eq!(quotes.bid, None);
+        assert!(quotes.ask.unwrap().reduce_only);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_quotes() {
+        let (quoter, exchange) = quoter();
+        quoter.on_order_book(&book(302400, 302500)).await.unwrap();
+
+        quoter.cancel_quotes().await.unwrap();
+        assert!(resting(&exchange).is_empty());
+        assert_eq!(quoter.quotes(), Quotes::default());
+    }
+}
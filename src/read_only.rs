@@ -0,0 +1,133 @@
+//! Clients that can never submit a transaction
+//!
+//! A service sharing credentials with a trading bot, such as analytics or
+//! monitoring, should not be able to place an order even by mistake. There
+//! are two levels of protection:
+//!
+//! - [`TxClientBuilder::read_only`](crate::client::TxClientBuilder::read_only)
+//!   builds a [`TxClient`] whose every submission path, down to
+//!   [`HTTPClient::send_tx`] and [`HTTPClient::send_tx_batch`], fails with
+//!   [`LighterError::ReadOnlyMode`](crate::errors::LighterError::ReadOnlyMode)
+//!   before any request is made.
+//! - [`TxClientBuilder::build_read_only`](crate::client::TxClientBuilder::build_read_only)
+//!   builds a [`ReadOnlyClient`], which has no signing or submission methods
+//!   at all, so code handed one can't even try. The [`HTTPClient`] it lends
+//!   out for the read endpoints is read-only too.
+//!
+//! The WebSocket stream never submits anything, so a
+//! [`WsClient`](crate::ws_client::WsClient) needs no such mode.
+//!
+//! ```no_run
+//! use lighter_rs::client::TxClient;
+//!
+//! # async fn example() -> lighter_rs::Result<()> {
+//! let client = TxClient::builder()
+//!     .api_url("https://mainnet.zklighter.elliot.ai")
+//!     .private_key("0x0102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f202122232425262728")
+//!     .account_index(12345)
+//!     .build_read_only()?;
+//!
+//! let nonce = client.next_nonce().await?;
+//! let positions = client.http().get_account_positions(client.account_index()).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::client::{HTTPClient, TxClient};
+use crate::errors::Result;
+
+/// A client with read access only
+///
+/// Built with [`TxClientBuilder::build_read_only`](crate::client::TxClientBuilder::build_read_only).
+pub struct ReadOnlyClient {
+    /// Built read-only and never handed out, so nothing can be signed or
+    /// sent through it
+    inner: TxClient,
+}
+
+impl ReadOnlyClient {
+    pub(crate) fn new(inner: TxClient) -> Self {
+        debug_assert!(inner.is_read_only());
+        Self { inner }
+    }
+
+    pub fn account_index(&self) -> i64 {
+        self.inner.account_index()
+    }
+
+    pub fn api_key_index(&self) -> u8 {
+        self.inner.api_key_index()
+    }
+
+    pub fn chain_id(&self) -> u32 {
+        self.inner.chain_id()
+    }
+
+    /// The HTTP client for the read endpoints; its submission methods fail
+    /// with [`LighterError::ReadOnlyMode`](crate::errors::LighterError::ReadOnlyMode)
+    pub fn http(&self) -> &HTTPClient {
+        self.inner
+            .http()
+            .expect("a read-only client is always built with an API URL")
+    }
+
+    /// Fetch the account's next nonce for the client's API key
+    pub async fn next_nonce(&self) -> Result<i64> {
+        self.http()
+            .get_next_nonce(self.account_index(), self.api_key_index())
+            .await
+    }
+
+    /// See [`TxClient::public_key_prefix`]
+    pub fn public_key_prefix(&self) -> String {
+        self.inner.public_key_prefix()
+    }
+
+    /// See [`TxClient::connection_summary`]
+    pub fn connection_summary(&self) -> String {
+        format!("read-only {}", self.inner.connection_summary())
+    }
+}
+
+impl std::fmt::Debug for ReadOnlyClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ReadOnlyClient")
+            .field("connection", &self.inner.connection_summary())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::LighterError;
+    use crate::test_support::{mock_builder, TEST_KEY};
+    use crate::transport::{HttpResponse, MockTransport};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_reads_work_and_the_lent_http_client_cannot_submit() {
+        let mock = Arc::new(MockTransport::new());
+        mock.set_handler("/api/v1/nextNonce", |_| {
+            Ok(HttpResponse::new(200, r#"{"code":200,"nonce":41}"#))
+        });
+        let client = mock_builder(&mock).build_read_only().unwrap();
+
+        assert_eq!(client.next_nonce().await.unwrap(), 41);
+        assert!(client
+            .connection_summary()
+            .starts_with("read-only account 1"));
+        assert!(client.http().is_read_only());
+        assert!(matches!(
+            client.http().send_tx(14, "{}").await,
+            Err(LighterError::ReadOnlyMode)
+        ));
+        assert_eq!(mock.requests().len(), 1);
+
+        // Without an API URL there is nothing to read from
+        assert!(matches!(
+            TxClient::builder().private_key(TEST_KEY).build_read_only(),
+            Err(LighterError::MissingField(_))
+        ));
+    }
+}
@@ -0,0 +1,950 @@
+//! Pre-trade risk checks enforced by [`TxClient`](crate::client::TxClient)
+//!
+//! Limits set with [`TxClientBuilder::risk_limits`](crate::client::TxClientBuilder::risk_limits)
+//! are checked before an order is signed, so an order breaking one never gets
+//! a signature or a nonce. A breach is reported as
+//! [`LighterError::RiskLimitBreached`].
+//!
+//! The order-rate and per-order limits are always enforced. Limits on open
+//! orders, positions and distance from the mid also need live state: attach a
+//! [`RiskState`] source, such as a
+//! [`PositionManager`](crate::positions::PositionManager) or
+//! [`OrderTracker::risk_state`](crate::tracker::OrderTracker::risk_state),
+//! with [`TxClient::attach_risk_state`](crate::client::TxClient::attach_risk_state).
+//! Without one those limits are skipped.
+//!
+//! Reduce-only orders are only held to the open order and order-rate limits,
+//! so the limits never stand in the way of closing a position.
+//!
+//! The exchange rejects a reduce-only order that would grow or flip the
+//! position, but only after the round trip and with a generic error. With
+//! [`RiskLimits::reduce_only`] set, reduce-only orders are checked against
+//! the attached position first: an order on the side of the position, or
+//! against a flat one, is refused, and one larger than the position is
+//! refused or clamped to it as the [`ReduceOnlyPolicy`] says. A position
+//! older than [`ReduceOnlyCheck::max_position_age`], or one whose source
+//! doesn't say when it was updated, is not trusted and the order goes out
+//! unchecked, so stale state never blocks an exit.
+//!
+//! The price band is only as good as the mid it measures from. With
+//! [`RiskLimits::max_mid_staleness`] set, an order checked against a mid
+//! older than that is refused with [`LighterError::StalePrice`] instead of
+//! passing a band drawn around a price the market has left.
+//!
+//! For a blunt cap that covers every order, reduce-only ones included, and
+//! can't be lifted while the client runs, see
+//! [`TxClientBuilder::max_notional_per_order`](crate::client::TxClientBuilder::max_notional_per_order).
+//!
+//! ```
+//! use lighter_rs::client::TxClient;
+//! use lighter_rs::risk::{MarketRiskLimits, RiskLimits};
+//! use lighter_rs::Decimal;
+//!
+//! # fn example() -> lighter_rs::Result<()> {
+//! let limits = RiskLimits {
+//!     max_order_notional: Some(Decimal::new(10_000, 0)),
+//!     max_orders_per_second: Some(20),
+//!     ..RiskLimits::default()
+//! }
+//! .market(0, MarketRiskLimits::new(2, 4));
+//!
+//! let tx_client = TxClient::builder()
+//!     .private_key("0x0102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f202122232425262728")
+//!     .risk_limits(limits.clone())
+//!     .build()?;
+//!
+//! // Limits can be swapped while the client is in use
+//! tx_client.set_risk_limits(RiskLimits {
+//!     max_order_notional: Some(Decimal::new(1_000, 0)),
+//!     ..limits
+//! });
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+use rust_decimal::Decimal;
+
+use crate::clock::Clock;
+use crate::errors::{LighterError, Result};
+use crate::fresh_price::FreshPrice;
+use crate::types::{CreateOrderTxReq, OrderInfo};
+
+/// Window of [`RiskLimits::max_orders_per_second`], in milliseconds
+const RATE_WINDOW_MS: i64 = 1000;
+
+/// Limits checked before every order is signed
+///
+/// Every limit is optional; the default checks nothing. Notional, position
+/// and price band limits only apply to markets listed in `markets`, since
+/// they need the market's scaling.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RiskLimits {
+    /// Largest notional of a single order, in quote currency
+    pub max_order_notional: Option<Decimal>,
+    /// Most orders open at once
+    pub max_open_orders: Option<usize>,
+    /// Most orders signed in any one-second window
+    pub max_orders_per_second: Option<usize>,
+    /// Largest distance of an order's price from the mid, in basis points
+    pub price_band_bps: Option<Decimal>,
+    /// Oldest mid the price band accepts; orders checked against an older
+    /// one are refused
+    pub max_mid_staleness: Option<Duration>,
+    /// Check reduce-only orders against the current position
+    pub reduce_only: Option<ReduceOnlyCheck>,
+    pub markets: HashMap<u8, MarketRiskLimits>,
+}
+
+impl RiskLimits {
+    /// Add or replace the limits of one market
+    pub fn market(mut self, market_index: u8, limits: MarketRiskLimits) -> Self {
+        self.markets.insert(market_index, limits);
+        self
+    }
+}
+
+/// Scaling and limits of one market
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MarketRiskLimits {
+    pub price_decimals: u32,
+    pub size_decimals: u32,
+    /// Largest absolute position, in base currency
+    pub max_position: Option<Decimal>,
+}
+
+impl MarketRiskLimits {
+    /// A market with the given scaling and no position limit
+    pub fn new(price_decimals: u32, size_decimals: u32) -> Self {
+        Self {
+            price_decimals,
+            size_decimals,
+            max_position: None,
+        }
+    }
+
+    /// Limit the absolute position, in base currency
+    pub fn max_position(mut self, max_position: Decimal) -> Self {
+        self.max_position = Some(max_position);
+        self
+    }
+}
+
+/// What to do with a reduce-only order larger than the position
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReduceOnlyPolicy {
+    /// Refuse the order
+    Reject,
+    /// Shrink the order to the position
+    Clamp,
+}
+
+/// Settings of the reduce-only pre-check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReduceOnlyCheck {
+    pub policy: ReduceOnlyPolicy,
+    /// Oldest position the check trusts; older ones skip the check
+    pub max_position_age: Duration,
+}
+
+impl ReduceOnlyCheck {
+    pub fn new(policy: ReduceOnlyPolicy, max_position_age: Duration) -> Self {
+        Self {
+            policy,
+            max_position_age,
+        }
+    }
+}
+
+/// Which limit an order broke
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RiskRule {
+    MaxOrderNotional,
+    MaxPosition,
+    MaxOpenOrders,
+    MaxOrdersPerSecond,
+    PriceBand,
+    /// A reduce-only order that would grow or flip the position
+    ReduceOnly,
+}
+
+impl fmt::Display for RiskRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            RiskRule::MaxOrderNotional => "max order notional",
+            RiskRule::MaxPosition => "max position",
+            RiskRule::MaxOpenOrders => "max open orders",
+            RiskRule::MaxOrdersPerSecond => "max orders per second",
+            RiskRule::PriceBand => "price band (bps from mid)",
+            RiskRule::ReduceOnly => "reduce only (position size)",
+        })
+    }
+}
+
+/// Live account and market state consulted by the stateful limits
+///
+/// Every method defaults to "unknown", which skips the limits relying on it.
+pub trait RiskState: Send + Sync {
+    /// Signed position in base currency: positive when long
+    fn position(&self, _market_index: u8) -> Option<Decimal> {
+        None
+    }
+
+    /// When the position of a market was last updated, in milliseconds since
+    /// the Unix epoch
+    ///
+    /// Positions without an update time are never used to check reduce-only
+    /// orders.
+    fn position_updated_at_ms(&self, _market_index: u8) -> Option<i64> {
+        None
+    }
+
+    /// Number of orders currently open
+    fn open_orders(&self) -> Option<usize> {
+        None
+    }
+
+    /// Latest mid price of a market and when it was observed
+    fn mid_price(&self, _market_index: u8) -> Option<FreshPrice> {
+        None
+    }
+
+    /// Latest mark price of a market and when it was observed
+    ///
+    /// Draws the exchange's price band; see the
+    /// [`price_band`](crate::price_band) module. Defaults to the mid price.
+    fn mark_price(&self, market_index: u8) -> Option<FreshPrice> {
+        self.mid_price(market_index)
+    }
+}
+
+/// An order as the risk checks see it
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct OrderCheck {
+    pub market_index: u8,
+    pub base_amount: i64,
+    pub price: u64,
+    /// `None` for modifications, whose side isn't known
+    pub is_ask: Option<bool>,
+    pub reduce_only: bool,
+}
+
+impl From<&CreateOrderTxReq> for OrderCheck {
+    fn from(req: &CreateOrderTxReq) -> Self {
+        Self {
+            market_index: req.market_index,
+            base_amount: req.base_amount,
+            price: req.price,
+            is_ask: Some(req.is_ask != 0),
+            reduce_only: req.reduce_only != 0,
+        }
+    }
+}
+
+impl From<&OrderInfo> for OrderCheck {
+    fn from(order: &OrderInfo) -> Self {
+        Self {
+            market_index: order.market_index,
+            base_amount: order.base_amount,
+            price: order.price,
+            is_ask: Some(order.is_ask != 0),
+            reduce_only: order.reduce_only != 0,
+        }
+    }
+}
+
+/// Limits and state of one client
+pub(crate) struct RiskGuard {
+    limits: RwLock<Arc<RiskLimits>>,
+    states: RwLock<Vec<Arc<dyn RiskState>>>,
+    /// Millisecond timestamps of orders signed within the rate window
+    recent: Mutex<VecDeque<i64>>,
+    /// Judges how old the mid is
+    clock: Arc<dyn Clock>,
+}
+
+impl RiskGuard {
+    pub fn new(limits: RiskLimits, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            limits: RwLock::new(Arc::new(limits)),
+            states: RwLock::default(),
+            recent: Mutex::default(),
+            clock,
+        }
+    }
+
+    pub fn limits(&self) -> Arc<RiskLimits> {
+        self.limits
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    pub fn set_limits(&self, limits: RiskLimits) {
+        *self
+            .limits
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = Arc::new(limits);
+    }
+
+    pub fn attach(&self, state: Arc<dyn RiskState>) {
+        self.states
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .push(state);
+    }
+
+    /// First answer from the attached state sources
+    fn query<T>(&self, f: impl Fn(&dyn RiskState) -> Option<T>) -> Option<T> {
+        self.states
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .iter()
+            .find_map(|state| f(state.as_ref()))
+    }
+
+    /// Mark price of a market from the attached state sources
+    pub fn mark_price(&self, market_index: u8) -> Option<FreshPrice> {
+        self.query(|state| state.mark_price(market_index))
+    }
+
+    /// Check new orders, counting them against the rate limit if they pass
+    ///
+    /// `now_ms` is the time on the client's clock. Reduce-only orders clamped
+    /// to the position get their new `base_amount`.
+    pub fn check_new(&self, orders: &mut [OrderCheck], now_ms: i64) -> Result<()> {
+        self.check_at(orders, true, now_ms)
+    }
+
+    /// Check a modification of a resting order
+    pub fn check_modify(&self, order: &OrderCheck) -> Result<()> {
+        let mut order = *order;
+        self.check_at(std::slice::from_mut(&mut order), false, 0)
+    }
+
+    fn check_at(&self, orders: &mut [OrderCheck], new: bool, now_ms: i64) -> Result<()> {
+        let limits = self.limits();
+        let mut recent = self
+            .recent
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        if new {
+            while recent
+                .front()
+                .is_some_and(|&signed| signed <= now_ms - RATE_WINDOW_MS)
+            {
+                recent.pop_front();
+            }
+            if let Some(max) = limits.max_orders_per_second {
+                breach_if(
+                    RiskRule::MaxOrdersPerSecond,
+                    max,
+                    recent.len() + orders.len(),
+                )?;
+            }
+            if let Some(max) = limits.max_open_orders {
+                if let Some(open) = self.query(|state| state.open_orders()) {
+                    breach_if(RiskRule::MaxOpenOrders, max, open + orders.len())?;
+                }
+            }
+        }
+
+        // Positions as they would be after the earlier orders of the batch
+        let mut projected: HashMap<u8, Decimal> = HashMap::new();
+        for order in orders.iter_mut() {
+            let Some(market) = limits.markets.get(&order.market_index) else {
+                continue;
+            };
+            // Nothing but the position stands in the way of reducing risk
+            if order.reduce_only {
+                if let (Some(check), Some(is_ask), true) = (limits.reduce_only, order.is_ask, new) {
+                    let current = match projected.get(&order.market_index) {
+                        Some(position) => Some(*position),
+                        None => self.fresh_position(order.market_index, &check, now_ms),
+                    };
+                    if let Some(current) = current {
+                        let size = reduce_only_size(order, market, &check, is_ask, current)?;
+                        let after = if is_ask {
+                            current - size
+                        } else {
+                            current + size
+                        };
+                        projected.insert(order.market_index, after);
+                    }
+                }
+                continue;
+            }
+            let price =
+                Decimal::from_i128_with_scale(i128::from(order.price), market.price_decimals);
+            let size = Decimal::new(order.base_amount, market.size_decimals);
+
+            if let Some(max) = limits.max_order_notional {
+                breach_if(RiskRule::MaxOrderNotional, max, price * size)?;
+            }
+
+            if let Some(band) = limits.price_band_bps {
+                let mid = match self.query(|state| state.mid_price(order.market_index)) {
+                    Some(mid) => match limits.max_mid_staleness {
+                        Some(max) => Some(mid.fresh(self.clock.as_ref(), max)?),
+                        None => Some(mid.value),
+                    },
+                    None => None,
+                };
+                if let Some(mid) = mid.filter(|mid| *mid > Decimal::ZERO) {
+                    let distance_bps =
+                        ((price - mid).abs() / mid * Decimal::from(10_000)).round_dp(2);
+                    breach_if(RiskRule::PriceBand, band, distance_bps)?;
+                }
+            }
+
+            if let (Some(max), Some(is_ask)) = (market.max_position, order.is_ask) {
+                let current = match projected.get(&order.market_index) {
+                    Some(position) => Some(*position),
+                    None => self.query(|state| state.position(order.market_index)),
+                };
+                if let Some(current) = current {
+                    let after = if is_ask {
+                        current - size
+                    } else {
+                        current + size
+                    };
+                    // Orders that shrink the position are always allowed
+                    if after.abs() > current.abs() {
+                        breach_if(RiskRule::MaxPosition, max, after.abs())?;
+                    }
+                    projected.insert(order.market_index, after);
+                }
+            }
+        }
+
+        if new {
+            let len = recent.len() + orders.len();
+            recent.resize(len, now_ms);
+        }
+        Ok(())
+    }
+
+    /// Position of a market if it was updated within the check's max age
+    fn fresh_position(
+        &self,
+        market_index: u8,
+        check: &ReduceOnlyCheck,
+        now_ms: i64,
+    ) -> Option<Decimal> {
+        let max_age_ms = check.max_position_age.as_millis() as i64;
+        self.query(|state| {
+            let updated_at_ms = state.position_updated_at_ms(market_index)?;
+            if now_ms - updated_at_ms > max_age_ms {
+                tracing::debug!(
+                    market_index,
+                    age_ms = now_ms - updated_at_ms,
+                    "Position too old to check a reduce-only order against"
+                );
+                return None;
+            }
+            state.position(market_index)
+        })
+    }
+}
+
+/// Hard cap on the notional of every order a client signs
+///
+/// Set with [`TxClientBuilder::max_notional_per_order`](crate::client::TxClientBuilder::max_notional_per_order).
+/// Unlike [`RiskLimits`] it can't be changed once the client is built and
+/// holds reduce-only orders too.
+pub(crate) struct NotionalCap {
+    cap: Decimal,
+    /// Price and size decimals of the markets checked so far
+    decimals: Mutex<HashMap<u8, (u32, u32)>>,
+}
+
+impl NotionalCap {
+    pub fn new(cap: Decimal) -> Self {
+        Self {
+            cap,
+            decimals: Mutex::default(),
+        }
+    }
+
+    pub fn cap(&self) -> Decimal {
+        self.cap
+    }
+
+    /// Price and size decimals of a market, if already known
+    pub fn decimals(&self, market_index: u8) -> Option<(u32, u32)> {
+        self.decimals
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .get(&market_index)
+            .copied()
+    }
+
+    pub fn remember(&self, market_index: u8, price_decimals: u32, size_decimals: u32) {
+        self.decimals
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .insert(market_index, (price_decimals, size_decimals));
+    }
+
+    /// Refuse `order` if its notional is above the cap
+    pub fn check(&self, order: &OrderCheck, price_decimals: u32, size_decimals: u32) -> Result<()> {
+        let price = Decimal::from_i128_with_scale(i128::from(order.price), price_decimals);
+        let size = Decimal::new(order.base_amount, size_decimals);
+        let notional = (price * size).abs();
+        if notional > self.cap {
+            return Err(LighterError::NotionalCapExceeded {
+                market_index: order.market_index,
+                notional,
+                cap: self.cap,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Size of a reduce-only order against `position`, clamping `order` to it
+/// if the policy says so
+fn reduce_only_size(
+    order: &mut OrderCheck,
+    market: &MarketRiskLimits,
+    check: &ReduceOnlyCheck,
+    is_ask: bool,
+    position: Decimal,
+) -> Result<Decimal> {
+    let size = Decimal::new(order.base_amount, market.size_decimals);
+    // Sells reduce longs and buys reduce shorts
+    let reducible = if is_ask {
+        position.max(Decimal::ZERO)
+    } else {
+        (-position).max(Decimal::ZERO)
+    };
+    if size <= reducible {
+        return Ok(size);
+    }
+    if check.policy == ReduceOnlyPolicy::Clamp && !reducible.is_zero() {
+        let scale = Decimal::from(10i64.pow(market.size_decimals));
+        let clamped = (reducible * scale).floor();
+        if let Ok(base_amount) = i64::try_from(clamped) {
+            if base_amount > 0 {
+                tracing::info!(
+                    market_index = order.market_index,
+                    from = order.base_amount,
+                    to = base_amount,
+                    "Clamped a reduce-only order to the position"
+                );
+                order.base_amount = base_amount;
+                return Ok(clamped / scale);
+            }
+        }
+    }
+    Err(LighterError::RiskLimitBreached {
+        rule: RiskRule::ReduceOnly,
+        limit: reducible,
+        attempted: size,
+    })
+}
+
+fn breach_if<T: Into<Decimal> + PartialOrd>(rule: RiskRule, limit: T, attempted: T) -> Result<()> {
+    if attempted > limit {
+        return Err(LighterError::RiskLimitBreached {
+            rule,
+            limit: limit.into(),
+            attempted: attempted.into(),
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::{ManualClock, SystemClock};
+
+    #[derive(Default)]
+    struct FakeState {
+        position: Option<Decimal>,
+        updated_at_ms: Option<i64>,
+        open_orders: Option<usize>,
+        mid: Option<FreshPrice>,
+    }
+
+    impl RiskState for FakeState {
+        fn position(&self, _market_index: u8) -> Option<Decimal> {
+            self.position
+        }
+
+        fn position_updated_at_ms(&self, _market_index: u8) -> Option<i64> {
+            self.updated_at_ms
+        }
+
+        fn open_orders(&self) -> Option<usize> {
+            self.open_orders
+        }
+
+        fn mid_price(&self, _market_index: u8) -> Option<FreshPrice> {
+            self.mid
+        }
+    }
+
+    /// Buy of `base_amount` 1/10000 units at `price` cents in market 0
+    fn buy(base_amount: i64, price: u64) -> OrderCheck {
+        OrderCheck {
+            market_index: 0,
+            base_amount,
+            price,
+            is_ask: Some(false),
+            reduce_only: false,
+        }
+    }
+
+    fn guard(limits: RiskLimits, state: FakeState) -> RiskGuard {
+        let guard = RiskGuard::new(
+            limits.market(0, MarketRiskLimits::new(2, 4)),
+            Arc::new(SystemClock),
+        );
+        guard.attach(Arc::new(state));
+        guard
+    }
+
+    fn breached_rule(result: Result<()>) -> RiskRule {
+        match result {
+            Err(LighterError::RiskLimitBreached { rule, .. }) => rule,
+            other => panic!("expected a breach, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_max_order_notional() {
+        let guard = guard(
+            RiskLimits {
+                max_order_notional: Some(Decimal::new(1000, 0)),
+                ..RiskLimits::default()
+            },
+            FakeState::default(),
+        );
+
+        // 0.3 at 3000.00 is 900, 0.4 is 1200
+        assert!(guard.check_new(&mut [buy(3000, 300000)], 0).is_ok());
+        match guard.check_new(&mut [buy(4000, 300000)], 0) {
+            Err(LighterError::RiskLimitBreached {
+                rule,
+                limit,
+                attempted,
+            }) => {
+                assert_eq!(rule, RiskRule::MaxOrderNotional);
+                assert_eq!(limit, Decimal::new(1000, 0));
+                assert_eq!(attempted, Decimal::new(1200, 0));
+            }
+            other => panic!("expected a breach, got {other:?}"),
+        }
+
+        // Markets without scaling aren't checked
+        let mut other_market = buy(4000, 300000);
+        other_market.market_index = 1;
+        assert!(guard.check_new(&mut [other_market], 0).is_ok());
+    }
+
+    #[test]
+    fn test_max_position() {
+        let limits = RiskLimits::default().market(
+            0,
+            MarketRiskLimits::new(2, 4).max_position(Decimal::new(1, 0)),
+        );
+        let guard = RiskGuard::new(limits.clone(), Arc::new(SystemClock));
+        // Unknown position: skipped
+        assert!(guard.check_new(&mut [buy(50_000, 300000)], 0).is_ok());
+
+        let guard = RiskGuard::new(limits, Arc::new(SystemClock));
+        guard.attach(Arc::new(FakeState {
+            position: Some(Decimal::new(8, 1)),
+            ..FakeState::default()
+        }));
+        assert!(guard.check_new(&mut [buy(2000, 300000)], 0).is_ok());
+        assert_eq!(
+            breached_rule(guard.check_new(&mut [buy(3000, 300000)], 0)),
+            RiskRule::MaxPosition
+        );
+        // Together the two orders would reach 1.2
+        assert_eq!(
+            breached_rule(guard.check_new(&mut [buy(2000, 300000), buy(2000, 300000)], 0)),
+            RiskRule::MaxPosition
+        );
+
+        // Reducing is always allowed
+        let sell = OrderCheck {
+            is_ask: Some(true),
+            ..buy(5000, 300000)
+        };
+        assert!(guard.check_new(&mut [sell], 0).is_ok());
+        let reduce_only = OrderCheck {
+            reduce_only: true,
+            ..buy(50_000, 300000)
+        };
+        assert!(guard.check_new(&mut [reduce_only], 0).is_ok());
+    }
+
+    #[test]
+    fn test_max_open_orders() {
+        let guard = guard(
+            RiskLimits {
+                max_open_orders: Some(3),
+                ..RiskLimits::default()
+            },
+            FakeState {
+                open_orders: Some(2),
+                ..FakeState::default()
+            },
+        );
+
+        assert!(guard.check_new(&mut [buy(1, 300000)], 0).is_ok());
+        assert_eq!(
+            breached_rule(guard.check_new(&mut [buy(1, 300000), buy(1, 300000)], 0)),
+            RiskRule::MaxOpenOrders
+        );
+        // Modifications don't open anything
+        assert!(guard.check_modify(&buy(1, 300000)).is_ok());
+    }
+
+    #[test]
+    fn test_max_orders_per_second() {
+        let guard = RiskGuard::new(
+            RiskLimits {
+                max_orders_per_second: Some(3),
+                ..RiskLimits::default()
+            },
+            Arc::new(SystemClock),
+        );
+
+        assert!(guard.check_new(&mut [buy(1, 1), buy(1, 1)], 10_000).is_ok());
+        assert!(guard.check_new(&mut [buy(1, 1)], 10_500).is_ok());
+        assert_eq!(
+            breached_rule(guard.check_new(&mut [buy(1, 1)], 10_999)),
+            RiskRule::MaxOrdersPerSecond
+        );
+        // The first two orders leave the window after a second
+        assert!(guard.check_new(&mut [buy(1, 1), buy(1, 1)], 11_000).is_ok());
+    }
+
+    #[test]
+    fn test_price_band() {
+        let guard = guard(
+            RiskLimits {
+                price_band_bps: Some(Decimal::new(50, 0)),
+                ..RiskLimits::default()
+            },
+            FakeState {
+                mid: Some(FreshPrice::observed(Decimal::new(3000, 0), &SystemClock)),
+                ..FakeState::default()
+            },
+        );
+
+        // 3015.00 is exactly 50 bps above the mid
+        assert!(guard.check_new(&mut [buy(1, 301500)], 0).is_ok());
+        assert_eq!(
+            breached_rule(guard.check_new(&mut [buy(1, 301600)], 0)),
+            RiskRule::PriceBand
+        );
+        assert_eq!(
+            breached_rule(guard.check_modify(&buy(1, 298000))),
+            RiskRule::PriceBand
+        );
+        // A reduce-only exit may cross the band
+        let exit = OrderCheck {
+            reduce_only: true,
+            ..buy(1, 330000)
+        };
+        assert!(guard.check_new(&mut [exit], 0).is_ok());
+    }
+
+    #[test]
+    fn test_price_band_refuses_a_stale_mid() {
+        let clock = Arc::new(ManualClock::at_ms(0));
+        let limits = RiskLimits {
+            price_band_bps: Some(Decimal::new(50, 0)),
+            max_mid_staleness: Some(Duration::from_secs(2)),
+            ..RiskLimits::default()
+        };
+        let guard = RiskGuard::new(limits.market(0, MarketRiskLimits::new(2, 4)), clock.clone());
+        guard.attach(Arc::new(FakeState {
+            mid: Some(FreshPrice::observed(Decimal::new(3000, 0), clock.as_ref())),
+            ..FakeState::default()
+        }));
+
+        clock.advance(Duration::from_secs(2));
+        assert!(guard.check_new(&mut [buy(1, 301500)], 0).is_ok());
+
+        // The feed froze: even an order inside the band is refused
+        clock.advance(Duration::from_millis(1));
+        assert!(matches!(
+            guard.check_new(&mut [buy(1, 300000)], 0),
+            Err(LighterError::StalePrice { .. })
+        ));
+        assert!(matches!(
+            guard.check_modify(&buy(1, 300000)),
+            Err(LighterError::StalePrice { .. })
+        ));
+        // Exits don't depend on the mid
+        let exit = OrderCheck {
+            reduce_only: true,
+            ..buy(1, 300000)
+        };
+        assert!(guard.check_new(&mut [exit], 0).is_ok());
+    }
+
+    #[test]
+    fn test_reduce_only_against_position() {
+        let reduce = |is_ask: bool, base_amount: i64| OrderCheck {
+            is_ask: Some(is_ask),
+            reduce_only: true,
+            ..buy(base_amount, 300000)
+        };
+        let guard_at = |policy: ReduceOnlyPolicy, position: &str| {
+            guard(
+                RiskLimits {
+                    reduce_only: Some(ReduceOnlyCheck::new(policy, Duration::from_secs(1))),
+                    ..RiskLimits::default()
+                },
+                FakeState {
+                    position: Some(position.parse().unwrap()),
+                    updated_at_ms: Some(10_000),
+                    ..FakeState::default()
+                },
+            )
+        };
+
+        // Long 0.5: sells reduce it, buys grow it
+        let long = guard_at(ReduceOnlyPolicy::Reject, "0.5");
+        assert!(long.check_new(&mut [reduce(true, 3000)], 10_500).is_ok());
+        assert_eq!(
+            breached_rule(long.check_new(&mut [reduce(false, 1000)], 10_500)),
+            RiskRule::ReduceOnly
+        );
+        assert_eq!(
+            breached_rule(long.check_new(&mut [reduce(true, 6000)], 10_500)),
+            RiskRule::ReduceOnly
+        );
+        // Earlier orders of a batch use up the position
+        assert_eq!(
+            breached_rule(long.check_new(&mut [reduce(true, 3000), reduce(true, 3000)], 10_500)),
+            RiskRule::ReduceOnly
+        );
+
+        // Short 0.5: buys reduce it
+        let short = guard_at(ReduceOnlyPolicy::Reject, "-0.5");
+        assert!(short.check_new(&mut [reduce(false, 5000)], 10_500).is_ok());
+        assert_eq!(
+            breached_rule(short.check_new(&mut [reduce(true, 1000)], 10_500)),
+            RiskRule::ReduceOnly
+        );
+
+        // Clamping shrinks an order to the position, but has nothing to
+        // clamp to when flat
+        let long = guard_at(ReduceOnlyPolicy::Clamp, "0.5");
+        let mut orders = [reduce(true, 3000), reduce(true, 3000)];
+        assert!(long.check_new(&mut orders, 10_500).is_ok());
+        assert_eq!(orders[0].base_amount, 3000);
+        assert_eq!(orders[1].base_amount, 2000);
+        let flat = guard_at(ReduceOnlyPolicy::Clamp, "0");
+        assert_eq!(
+            breached_rule(flat.check_new(&mut [reduce(true, 1000)], 10_500)),
+            RiskRule::ReduceOnly
+        );
+        assert_eq!(
+            breached_rule(flat.check_new(&mut [reduce(false, 1000)], 10_500)),
+            RiskRule::ReduceOnly
+        );
+
+        // A stale position, or one of unknown age, doesn't block an exit
+        let mut order = [reduce(true, 6000)];
+        assert!(flat.check_new(&mut order, 11_001).is_ok());
+        assert_eq!(order[0].base_amount, 6000);
+        let unknown_age = guard(
+            RiskLimits {
+                reduce_only: Some(ReduceOnlyCheck::new(
+                    ReduceOnlyPolicy::Reject,
+                    Duration::from_secs(1),
+                )),
+                ..RiskLimits::default()
+            },
+            FakeState {
+                position: Some(Decimal::ZERO),
+                ..FakeState::default()
+            },
+        );
+        assert!(unknown_age
+            .check_new(&mut [reduce(true, 1000)], 10_500)
+            .is_ok());
+    }
+
+    #[cfg(feature = "native")]
+    #[tokio::test]
+    async fn test_client_refuses_breaching_orders() {
+        use crate::client::TxClient;
+        use crate::positions::PositionManager;
+        use crate::test_support::TEST_KEY;
+
+        let tx_client = TxClient::builder()
+            .private_key(TEST_KEY)
+            .account_index(1)
+            .chain_id(304)
+            .risk_limits(
+                RiskLimits::default()
+                    .market(0, MarketRiskLimits::new(2, 4).max_position(Decimal::ONE)),
+            )
+            .build()
+            .unwrap();
+        tx_client.nonces().set(1, 0, 5);
+        let positions = PositionManager::new(1, Decimal::ZERO);
+        positions.apply_fill(0, false, Decimal::new(8, 1), Decimal::new(3000, 0));
+        tx_client.attach_risk_state(Arc::new(positions));
+
+        let refused = tx_client
+            .create_limit_order(0, 1, 3000, 300000, 0, false, None)
+            .await;
+        assert_eq!(breached_rule(refused.map(|_| ())), RiskRule::MaxPosition);
+
+        // The refused order took no nonce
+        let order = tx_client
+            .create_limit_order(0, 2, 2000, 300000, 0, false, None)
+            .await
+            .unwrap();
+        assert_eq!(order.nonce, 5);
+
+        // Reduce-only sells of the 0.8 long are clamped to it
+        tx_client.set_risk_limits(RiskLimits {
+            reduce_only: Some(ReduceOnlyCheck::new(
+                ReduceOnlyPolicy::Clamp,
+                Duration::from_secs(60),
+            )),
+            ..(*tx_client.risk_limits()).clone()
+        });
+        let order = tx_client
+            .create_limit_order(0, 3, 10_000, 300000, 1, true, None)
+            .await
+            .unwrap();
+        assert_eq!(order.base_amount, 8000);
+    }
+
+    #[test]
+    fn test_limits_are_hot_swappable() {
+        let guard = guard(RiskLimits::default(), FakeState::default());
+        assert!(guard.check_new(&mut [buy(100_000, 300000)], 0).is_ok());
+
+        guard.set_limits(
+            RiskLimits {
+                max_order_notional: Some(Decimal::new(100, 0)),
+                ..RiskLimits::default()
+            }
+            .market(0, MarketRiskLimits::new(2, 4)),
+        );
+        assert_eq!(
+            breached_rule(guard.check_new(&mut [buy(100_000, 300000)], 0)),
+            RiskRule::MaxOrderNotional
+        );
+    }
+}
@@ -0,0 +1,605 @@
+//! Connecting and warming up everything a trading session needs in one call
+//!
+//! [`Session::connect`] runs the start-up steps of a bot and hands back the
+//! clients once everything needed for trading is live:
+//!
+//! 1. [`SessionStep::TlsWarmUp`]: a status request opens the TLS connection
+//!    to the REST host, which the following requests reuse
+//! 2. [`SessionStep::KeyRegistration`]: the public key registered at the
+//!    client's API key index must be the client's own
+//! 3. [`SessionStep::MarketMetadata`]: the details of every configured
+//!    market, also cached for [`TxClient::preview_order`]
+//! 4. [`SessionStep::Nonce`]: the nonce cache is seeded, as with
+//!    [`TxClient::warm_up`]
+//! 5. [`SessionStep::WsConnect`]: the WebSocket stream is connected
+//! 6. [`SessionStep::BookSnapshot`]: a snapshot arrived for every order book
+//!    subscribed to
+//!
+//! The REST steps after the warm-up run concurrently, alongside the
+//! WebSocket connection; the snapshots are awaited once the stream is
+//! connected. The chain id has no endpoint to fetch it from, so the one the
+//! [`TxClient`] was built with is used as is.
+//!
+//! Every step is required unless marked with [`SessionConfig::optional`].
+//! A required step that fails or outlasts [`SessionConfig::step_timeout`]
+//! fails the connection with [`LighterError::SessionStepFailed`], naming the
+//! step; an optional one is recorded in [`Session::report`] and logged.
+//!
+//! ```no_run
+//! use lighter_rs::client::TxClient;
+//! use lighter_rs::session::{Session, SessionConfig, SessionStep};
+//! use lighter_rs::ws_client::WsClient;
+//!
+//! # async fn example() -> lighter_rs::Result<()> {
+//! let tx_client = TxClient::new(
+//!     "https://mainnet.zklighter.elliot.ai",
+//!     "0x0102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f202122232425262728",
+//!     12345,
+//!     0,
+//!     304,
+//! )?;
+//! let ws_client = WsClient::builder()
+//!     .host("mainnet.zklighter.elliot.ai")
+//!     .order_books(vec![0, 1])
+//!     .build()?;
+//!
+//! let session = Session::connect(
+//!     SessionConfig::new(tx_client)
+//!         .ws_client(ws_client)
+//!         .markets(vec![0, 1])
+//!         .optional(SessionStep::TlsWarmUp),
+//! )
+//! .await?;
+//! for step in session.report() {
+//!     println!("{}: {:?} in {:?}", step.step, step.status, step.elapsed);
+//! }
+//! let eth = session.market(0).unwrap();
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::task::JoinHandle;
+
+use crate::client::{MarketDetails, TxClient};
+use crate::errors::{LighterError, Result};
+use crate::signer::KeyManager;
+use crate::utils::bytes_to_hex_no_prefix;
+use crate::ws_client::WsClient;
+
+/// Default of [`SessionConfig::step_timeout`]
+const DEFAULT_STEP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Time between two looks at the WebSocket client while waiting on it
+const WS_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Digits of a public key shown in errors
+const KEY_PREFIX_DIGITS: usize = 8;
+
+/// One start-up step of a [`Session`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SessionStep {
+    TlsWarmUp,
+    KeyRegistration,
+    MarketMetadata,
+    Nonce,
+    WsConnect,
+    BookSnapshot,
+}
+
+impl fmt::Display for SessionStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SessionStep::TlsWarmUp => "TLS warm-up",
+            SessionStep::KeyRegistration => "key registration",
+            SessionStep::MarketMetadata => "market metadata",
+            SessionStep::Nonce => "nonce",
+            SessionStep::WsConnect => "WebSocket connection",
+            SessionStep::BookSnapshot => "book snapshot",
+        })
+    }
+}
+
+/// How a step ended
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StepStatus {
+    Done,
+    /// Only reported for optional steps; a required one fails the session
+    Failed(String),
+    /// Not run: there is no WebSocket client, or the connection it needs
+    /// failed
+    Skipped,
+}
+
+/// How one step went and how long it took
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StepReport {
+    pub step: SessionStep,
+    pub status: StepStatus,
+    pub elapsed: Duration,
+}
+
+/// What [`Session::connect`] starts from
+pub struct SessionConfig {
+    tx_client: TxClient,
+    ws_client: Option<WsClient>,
+    markets: Vec<u8>,
+    optional: HashSet<SessionStep>,
+    step_timeout: Duration,
+}
+
+impl SessionConfig {
+    /// Connect `tx_client`, without a WebSocket stream or market metadata
+    pub fn new(tx_client: TxClient) -> Self {
+        Self {
+            tx_client,
+            ws_client: None,
+            markets: Vec::new(),
+            optional: HashSet::new(),
+            step_timeout: DEFAULT_STEP_TIMEOUT,
+        }
+    }
+
+    /// Stream to connect, waiting for a snapshot of each of its order books
+    pub fn ws_client(mut self, ws_client: WsClient) -> Self {
+        self.ws_client = Some(ws_client);
+        self
+    }
+
+    /// Markets whose details are loaded
+    pub fn markets(mut self, markets: Vec<u8>) -> Self {
+        self.markets = markets;
+        self
+    }
+
+    /// Let the session start even if `step` fails
+    pub fn optional(mut self, step: SessionStep) -> Self {
+        self.optional.insert(step);
+        self
+    }
+
+    /// Longest any one step may take
+    pub fn step_timeout(mut self, step_timeout: Duration) -> Self {
+        self.step_timeout = step_timeout;
+        self
+    }
+}
+
+/// A running WebSocket stream
+///
+/// The stream is closed when the handle is dropped.
+pub struct WsHandle {
+    client: Arc<WsClient>,
+    task: JoinHandle<Result<()>>,
+}
+
+impl WsHandle {
+    /// Run `client` on a task of its own
+    fn spawn(client: WsClient) -> Self {
+        let client = Arc::new(client);
+        let runner = client.clone();
+        let task = tokio::spawn(async move { runner.run(|_, _| {}, |_, _| {}).await });
+        Self { client, task }
+    }
+
+    /// The client, for its books, accounts and events
+    pub fn client(&self) -> &Arc<WsClient> {
+        &self.client
+    }
+
+    /// Whether the stream is still running
+    pub fn is_running(&self) -> bool {
+        !self.task.is_finished()
+    }
+
+    /// Wait until `ready` holds, failing if the stream ends first
+    async fn wait_until(&mut self, ready: impl Fn(&WsClient) -> bool) -> Result<()> {
+        while !ready(&self.client) {
+            self.poll().await?;
+        }
+        Ok(())
+    }
+
+    /// Wait until every subscribed order book has its snapshot
+    async fn wait_for_books(&mut self) -> Result<()> {
+        let subscriptions = self.client.subscriptions();
+        for market in subscriptions
+            .iter()
+            .filter_map(|channel| channel.strip_prefix("order_book/"))
+        {
+            while self.client.get_order_book(market).await.is_none() {
+                self.poll().await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Pause before looking at the client again, failing if the stream ended
+    async fn poll(&mut self) -> Result<()> {
+        if self.task.is_finished() {
+            let result = (&mut self.task)
+                .await
+                .map_err(|e| LighterError::Other(format!("WebSocket task failed: {e}")))?;
+            return Err(result.err().unwrap_or_else(|| {
+                LighterError::InvalidResponse("WebSocket stream ended".to_string())
+            }));
+        }
+        tokio::time::sleep(WS_POLL_INTERVAL).await;
+        Ok(())
+    }
+}
+
+impl Drop for WsHandle {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Clients ready for trading
+pub struct Session {
+    tx_client: Arc<TxClient>,
+    ws: Option<WsHandle>,
+    markets: HashMap<u8, MarketDetails>,
+    report: Vec<StepReport>,
+}
+
+impl Session {
+    /// Run the start-up steps and return once the required ones are done
+    ///
+    /// See the [module documentation](self) for the steps.
+    pub async fn connect(config: SessionConfig) -> Result<Self> {
+        let SessionConfig {
+            tx_client,
+            ws_client,
+            markets,
+            optional,
+            step_timeout,
+        } = config;
+        let run = StepRunner {
+            tx_client: &tx_client,
+            step_timeout,
+        };
+
+        let mut ws = ws_client.map(WsHandle::spawn);
+        let rest_steps = async {
+            let warm_up = run
+                .step(SessionStep::TlsWarmUp, async {
+                    http(&tx_client)?.get_server_time().await.map(|_| ())
+                })
+                .await;
+            let (key, details, nonce) = tokio::join!(
+                run.step(SessionStep::KeyRegistration, check_key(&tx_client)),
+                run.step(
+                    SessionStep::MarketMetadata,
+                    load_markets(&tx_client, &markets)
+                ),
+                run.step(SessionStep::Nonce, tx_client.warm_up()),
+            );
+            (warm_up, key, details, nonce)
+        };
+        let connect_steps = async {
+            let Some(ws) = ws.as_mut() else {
+                return (None, None);
+            };
+            let connected = run
+                .step(
+                    SessionStep::WsConnect,
+                    ws.wait_until(|client| client.stats().connections > 0),
+                )
+                .await;
+            if connected.0.is_err() {
+                return (Some(connected), None);
+            }
+            let books = run
+                .step(SessionStep::BookSnapshot, ws.wait_for_books())
+                .await;
+            (Some(connected), Some(books))
+        };
+        let ((warm_up, key, details, nonce), (connected, books)) =
+            tokio::join!(rest_steps, connect_steps);
+
+        let mut report = Vec::new();
+        let mut loaded = HashMap::new();
+        // `None` for a step that didn't run
+        let mut record = |step: SessionStep, outcome: Option<(Result<()>, Duration)>| {
+            let Some((result, elapsed)) = outcome else {
+                report.push(StepReport {
+                    step,
+                    status: StepStatus::Skipped,
+                    elapsed: Duration::ZERO,
+                });
+                return Ok(());
+            };
+            let status = match result {
+                Ok(()) => StepStatus::Done,
+                Err(e) if optional.contains(&step) => {
+                    tracing::warn!(step = %step, error = %e, "Optional session step failed");
+                    StepStatus::Failed(e.to_string())
+                }
+                Err(e) => {
+                    return Err(LighterError::SessionStepFailed {
+                        step,
+                        source: Box::new(e),
+                    })
+                }
+            };
+            report.push(StepReport {
+                step,
+                status,
+                elapsed,
+            });
+            Ok(())
+        };
+        record(SessionStep::TlsWarmUp, Some(warm_up))?;
+        record(SessionStep::KeyRegistration, Some(key))?;
+        let (details, elapsed) = details;
+        record(
+            SessionStep::MarketMetadata,
+            Some((details.map(|details| loaded = details), elapsed)),
+        )?;
+        record(SessionStep::Nonce, Some(nonce))?;
+        record(SessionStep::WsConnect, connected)?;
+        record(SessionStep::BookSnapshot, books)?;
+
+        Ok(Self {
+            tx_client: Arc::new(tx_client),
+            ws,
+            markets: loaded,
+            report,
+        })
+    }
+
+    pub fn tx_client(&self) -> &Arc<TxClient> {
+        &self.tx_client
+    }
+
+    /// The running stream, if the session was given a WebSocket client
+    pub fn ws(&self) -> Option<&WsHandle> {
+        self.ws.as_ref()
+    }
+
+    /// Details of a configured market
+    pub fn market(&self, market_id: u8) -> Option<&MarketDetails> {
+        self.markets.get(&market_id)
+    }
+
+    /// Details of every configured market whose metadata loaded
+    pub fn markets(&self) -> &HashMap<u8, MarketDetails> {
+        &self.markets
+    }
+
+    /// Every step, in the order listed in the module documentation
+    pub fn report(&self) -> &[StepReport] {
+        &self.report
+    }
+}
+
+/// Runs steps under the step timeout, timing them on the client's clock
+struct StepRunner<'a> {
+    tx_client: &'a TxClient,
+    step_timeout: Duration,
+}
+
+impl StepRunner<'_> {
+    async fn step<T>(
+        &self,
+        step: SessionStep,
+        future: impl Future<Output = Result<T>>,
+    ) -> (Result<T>, Duration) {
+        let started = self.tx_client.clock().now_instant();
+        let result = tokio::time::timeout(self.step_timeout, future)
+            .await
+            .unwrap_or(Err(LighterError::Timeout));
+        let elapsed = self
+            .tx_client
+            .clock()
+            .now_instant()
+            .saturating_duration_since(started);
+        tracing::debug!(step = %step, ?elapsed, ok = result.is_ok(), "Session step finished");
+        (result, elapsed)
+    }
+}
+
+fn http(tx_client: &TxClient) -> Result<&crate::client::HTTPClient> {
+    tx_client.http().ok_or_else(|| {
+        LighterError::MissingField("cannot start a session without an HTTPClient".to_string())
+    })
+}
+
+/// Refuse a client whose key isn't the one registered at its index
+async fn check_key(tx_client: &TxClient) -> Result<()> {
+    let account_index = tx_client.account_index();
+    let api_key_index = tx_client.api_key_index();
+    let registered = http(tx_client)?
+        .get_api_key_public_key(account_index, api_key_index)
+        .await?
+        .ok_or_else(|| {
+            LighterError::InvalidConfiguration(format!(
+                "No public key is registered at API key {api_key_index} of account {account_index}"
+            ))
+        })?;
+    let registered = registered.trim_start_matches("0x").to_ascii_lowercase();
+    let own = bytes_to_hex_no_prefix(tx_client.key_manager().pub_key());
+    if registered != own {
+        return Err(LighterError::InvalidConfiguration(format!(
+            "API key {api_key_index} of account {account_index} is registered to public key {}..., not this client's {}...",
+            &registered[..KEY_PREFIX_DIGITS.min(registered.len())],
+            &own[..KEY_PREFIX_DIGITS.min(own.len())]
+        )));
+    }
+    Ok(())
+}
+
+async fn load_markets(tx_client: &TxClient, markets: &[u8]) -> Result<HashMap<u8, MarketDetails>> {
+    let http = http(tx_client)?;
+    let details = futures_util::future::try_join_all(
+        markets
+            .iter()
+            .map(|&market| http.get_market_details(market)),
+    )
+    .await?;
+    Ok(details
+        .into_iter()
+        .map(|details| {
+            tx_client.cache_market_details(details.clone());
+            (details.market_id, details)
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::mock_builder;
+    use crate::transport::MockTransport;
+
+    const STATUS: &str = r#"{"status":200,"timestamp":1700000000}"#;
+    #[cfg(feature = "test-util")]
+    const DETAILS: &str = r#"{"code":200,"order_book_details":[{"market_id":0,"symbol":"ETH","size_decimals":4,"price_decimals":2}]}"#;
+
+    fn api_keys(public_key: &str) -> String {
+        format!(
+            r#"{{"code":200,"api_keys":[{{"account_index":1,"api_key_index":0,"nonce":0,"public_key":"{public_key}"}}]}}"#
+        )
+    }
+
+    fn own_public_key(tx_client: &TxClient) -> String {
+        bytes_to_hex_no_prefix(tx_client.key_manager().pub_key())
+    }
+
+    #[tokio::test]
+    async fn test_failed_steps_are_named_or_skipped_when_optional() {
+        let mock = Arc::new(MockTransport::new());
+        mock.set_handler("/", |_| {
+            Ok(crate::transport::HttpResponse::new(200, STATUS))
+        });
+        mock.set_handler("/api/v1/apikeys", |_| {
+            Ok(crate::transport::HttpResponse::new(
+                200,
+                api_keys(&"ab".repeat(40)),
+            ))
+        });
+        mock.set_handler("/api/v1/nextNonce", |_| {
+            Ok(crate::transport::HttpResponse::new(
+                200,
+                r#"{"code":200,"nonce":12}"#,
+            ))
+        });
+        let tx_client = || mock_builder(&mock).build().unwrap();
+
+        match Session::connect(SessionConfig::new(tx_client())).await {
+            Err(LighterError::SessionStepFailed { step, source }) => {
+                assert_eq!(step, SessionStep::KeyRegistration);
+                assert!(
+                    source
+                        .to_string()
+                        .contains("registered to public key abababab..."),
+                    "{source}"
+                );
+            }
+            Err(e) => panic!("expected SessionStepFailed, got {e}"),
+            Ok(_) => panic!("expected SessionStepFailed, got a session"),
+        }
+
+        let session = Session::connect(
+            SessionConfig::new(tx_client()).optional(SessionStep::KeyRegistration),
+        )
+        .await
+        .unwrap();
+        let statuses: Vec<_> = session
+            .report()
+            .iter()
+            .map(|report| (report.step, report.status.clone()))
+            .collect();
+        assert!(matches!(
+            statuses.as_slice(),
+            [
+                (SessionStep::TlsWarmUp, StepStatus::Done),
+                (SessionStep::KeyRegistration, StepStatus::Failed(_)),
+                (SessionStep::MarketMetadata, StepStatus::Done),
+                (SessionStep::Nonce, StepStatus::Done),
+                (SessionStep::WsConnect, StepStatus::Skipped),
+                (SessionStep::BookSnapshot, StepStatus::Skipped),
+            ]
+        ));
+        assert!(session.ws().is_none());
+        assert_eq!(session.tx_client().nonces().peek(1, 0), Some(12));
+
+        // A required step that hangs times out
+        mock.set_delay("/api/v1/nextNonce", Duration::from_secs(5));
+        mock.set_handler("/api/v1/apikeys", {
+            let public_key = own_public_key(&tx_client());
+            move |_| {
+                Ok(crate::transport::HttpResponse::new(
+                    200,
+                    api_keys(&public_key),
+                ))
+            }
+        });
+        let result = Session::connect(
+            SessionConfig::new(tx_client()).step_timeout(Duration::from_millis(50)),
+        )
+        .await;
+        assert!(matches!(
+            result,
+            Err(LighterError::SessionStepFailed {
+                step: SessionStep::Nonce,
+                source,
+            }) if matches!(*source, LighterError::Timeout)
+        ));
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "test-util")]
+    async fn test_connect_against_the_mock_server() {
+        use crate::test_support::TEST_KEY;
+        use crate::testing::MockLighter;
+
+        let mock = Arc::new(MockLighter::start().await.unwrap());
+        let tx_client = TxClient::new(&mock.url(), TEST_KEY, 1, 0, 304).unwrap();
+        mock.set_response("/", 200, STATUS);
+        mock.set_response(
+            "/api/v1/apikeys",
+            200,
+            api_keys(&own_public_key(&tx_client)),
+        );
+        mock.set_response("/api/v1/orderBookDetails", 200, DETAILS);
+        let ws_client = WsClient::builder()
+            .url(mock.ws_url())
+            .order_books(vec![0])
+            .build()
+            .unwrap();
+
+        let connecting = tokio::spawn(Session::connect(
+            SessionConfig::new(tx_client)
+                .ws_client(ws_client)
+                .markets(vec![0]),
+        ));
+        mock.wait_for_subscriptions(1).await;
+        // Not ready before the snapshot
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!connecting.is_finished());
+        mock.push_frame(
+            r#"{"type":"subscribed/order_book","channel":"order_book:0","order_book":{"asks":[{"price":"3025.00","size":"1"}],"bids":[{"price":"3024.00","size":"1"}]}}"#,
+        );
+        let session = connecting.await.unwrap().unwrap();
+
+        assert!(session
+            .report()
+            .iter()
+            .all(|report| report.status == StepStatus::Done));
+        assert_eq!(session.report().len(), 6);
+        assert_eq!(session.market(0).unwrap().symbol, "ETH");
+        assert_eq!(session.tx_client().nonces().peek(1, 0), Some(0));
+        let ws = session.ws().unwrap();
+        assert!(ws.is_running());
+        assert!(ws.client().get_order_book("0").await.is_some());
+        // The REST steps reused the warmed-up host
+        assert_eq!(mock.requests_to("/").len(), 1);
+    }
+}
@@ -0,0 +1,356 @@
+//! Session statistics kept by the clients
+//!
+//! [`TxClient::stats`](crate::client::TxClient::stats) counts the orders a
+//! client signed and what became of the transactions it submitted;
+//! [`WsClient::stats`](crate::ws_client::WsClient::stats) counts the frames
+//! received on each channel, connections and the longest silence on the
+//! stream. Both return a serializable snapshot, so a session summary can be
+//! logged or written out at shutdown, and both start over with
+//! `reset_stats()`.
+//!
+//! Counting is a relaxed atomic add per event; only rejections, counted by
+//! code, take a lock.
+//!
+//! ```no_run
+//! use lighter_rs::client::TxClient;
+//!
+//! # fn example(tx_client: TxClient) -> lighter_rs::Result<()> {
+//! // ... trade ...
+//! let stats = tx_client.stats();
+//! println!("{} orders signed, {} accepted", stats.orders_signed, stats.accepted);
+//! println!("{}", serde_json::to_string(&stats)?);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::BTreeMap;
+#[cfg(feature = "native")]
+use std::collections::HashMap;
+#[cfg(feature = "native")]
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+#[cfg(feature = "native")]
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+
+use crate::failed_tx::TxFailure;
+
+/// Counts of a [`TxClient`](crate::client::TxClient) session
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TxStats {
+    /// New orders, counting each order of a grouped transaction, and
+    /// modifications signed
+    pub orders_signed: u64,
+    /// Transactions of every type signed
+    pub txs_signed: u64,
+    /// Transactions sent to the API, alone or in batches
+    pub submitted: u64,
+    /// Transactions the API applied
+    pub accepted: u64,
+    /// Transactions the API refused, by response code
+    pub rejected: BTreeMap<u16, u64>,
+    /// Transactions whose request failed, so whether the API applied them is
+    /// unknown
+    pub unconfirmed: u64,
+    /// Attempts made again after a failure, such as a kill switch step or a
+    /// failed transaction signed again
+    pub retries: u64,
+    /// Request body bytes of the submissions
+    pub bytes_sent: u64,
+}
+
+/// Counters behind [`TxStats`]
+#[derive(Debug, Default)]
+pub(crate) struct TxSessionStats {
+    orders_signed: AtomicU64,
+    txs_signed: AtomicU64,
+    submitted: AtomicU64,
+    accepted: AtomicU64,
+    rejected: Mutex<BTreeMap<u16, u64>>,
+    unconfirmed: AtomicU64,
+    retries: AtomicU64,
+    bytes_sent: AtomicU64,
+}
+
+impl TxSessionStats {
+    pub fn tx_signed(&self) {
+        add(&self.txs_signed, 1);
+    }
+
+    pub fn orders_signed(&self, count: usize) {
+        add(&self.orders_signed, count);
+    }
+
+    /// One request carrying `txs` transactions in `bytes` of body
+    pub fn sent(&self, txs: usize, bytes: usize) {
+        add(&self.submitted, txs);
+        add(&self.bytes_sent, bytes);
+    }
+
+    /// What became of one submitted transaction
+    pub fn outcome(&self, failure: Option<&TxFailure>) {
+        match failure {
+            None => add(&self.accepted, 1),
+            Some(TxFailure::Rejected { code, .. }) => {
+                *self
+                    .rejected
+                    .lock()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner())
+                    .entry(*code)
+                    .or_default() += 1;
+            }
+            Some(TxFailure::Unconfirmed { .. }) => add(&self.unconfirmed, 1),
+            Some(TxFailure::NotAttempted) => {}
+        }
+    }
+
+    #[cfg(feature = "native")]
+    pub fn retried(&self, count: usize) {
+        add(&self.retries, count);
+    }
+
+    pub fn snapshot(&self) -> TxStats {
+        TxStats {
+            orders_signed: load(&self.orders_signed),
+            txs_signed: load(&self.txs_signed),
+            submitted: load(&self.submitted),
+            accepted: load(&self.accepted),
+            rejected: self
+                .rejected
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .clone(),
+            unconfirmed: load(&self.unconfirmed),
+            retries: load(&self.retries),
+            bytes_sent: load(&self.bytes_sent),
+        }
+    }
+
+    pub fn reset(&self) {
+        for counter in [
+            &self.orders_signed,
+            &self.txs_signed,
+            &self.submitted,
+            &self.accepted,
+            &self.unconfirmed,
+            &self.retries,
+            &self.bytes_sent,
+        ] {
+            counter.store(0, Ordering::Relaxed);
+        }
+        self.rejected
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clear();
+    }
+}
+
+/// Counts of a [`WsClient`](crate::ws_client::WsClient) session
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WsStats {
+    /// Connections opened by [`WsClient::run`](crate::ws_client::WsClient::run)
+    pub connections: u64,
+    /// Connections opened after the first
+    pub reconnects: u64,
+    /// Frames received on each subscribed channel, such as `order_book/0`
+    pub messages: BTreeMap<String, u64>,
+    /// Frames outside the subscribed channels, such as `connected`
+    pub other_messages: u64,
+    /// Frames that couldn't be decoded
+    pub decode_errors: u64,
+    /// Longest wait between two frames on one connection, in milliseconds
+    pub max_gap_ms: u64,
+}
+
+/// Counters behind [`WsStats`]
+#[cfg(feature = "native")]
+#[derive(Debug)]
+pub(crate) struct WsSessionStats {
+    connections: AtomicU64,
+    reconnects: AtomicU64,
+    /// Whether any connection was ever opened; survives a reset, so the next
+    /// connection still counts as a reconnect
+    connected_before: AtomicBool,
+    /// One counter per subscribed channel, fixed when the client is built
+    messages: HashMap<String, AtomicU64>,
+    other_messages: AtomicU64,
+    decode_errors: AtomicU64,
+    max_gap_ms: AtomicU64,
+    /// Nanoseconds from `epoch` to the last frame, plus one; zero before the
+    /// first frame of a connection
+    last_frame: AtomicU64,
+    epoch: Instant,
+}
+
+#[cfg(feature = "native")]
+impl WsSessionStats {
+    pub fn new(channels: impl IntoIterator<Item = String>, epoch: Instant) -> Self {
+        Self {
+            connections: AtomicU64::new(0),
+            reconnects: AtomicU64::new(0),
+            connected_before: AtomicBool::new(false),
+            messages: channels
+                .into_iter()
+                .map(|channel| (channel, AtomicU64::new(0)))
+                .collect(),
+            other_messages: AtomicU64::new(0),
+            decode_errors: AtomicU64::new(0),
+            max_gap_ms: AtomicU64::new(0),
+            last_frame: AtomicU64::new(0),
+            epoch,
+        }
+    }
+
+    pub fn connected(&self) {
+        add(&self.connections, 1);
+        if self.connected_before.swap(true, Ordering::Relaxed) {
+            add(&self.reconnects, 1);
+        }
+        // Time spent disconnected is not a gap in the stream
+        self.last_frame.store(0, Ordering::Relaxed);
+    }
+
+    /// A frame arrived at `now`
+    pub fn frame_received(&self, now: Instant) {
+        let at = now.saturating_duration_since(self.epoch).as_nanos() as u64 + 1;
+        let last = self.last_frame.swap(at, Ordering::Relaxed);
+        if last != 0 {
+            let gap_ms = at.saturating_sub(last) / 1_000_000;
+            self.max_gap_ms.fetch_max(gap_ms, Ordering::Relaxed);
+        }
+    }
+
+    /// Count a decoded frame under its channel, if it is a subscribed one
+    pub fn message(&self, channel: Option<&str>) {
+        match channel.and_then(|channel| self.messages.get(channel)) {
+            Some(counter) => add(counter, 1),
+            None => add(&self.other_messages, 1),
+        }
+    }
+
+    pub fn decode_error(&self) {
+        add(&self.decode_errors, 1);
+    }
+
+    pub fn snapshot(&self) -> WsStats {
+        WsStats {
+            connections: load(&self.connections),
+            reconnects: load(&self.reconnects),
+            messages: self
+                .messages
+                .iter()
+                .map(|(channel, counter)| (channel.clone(), load(counter)))
+                .collect(),
+            other_messages: load(&self.other_messages),
+            decode_errors: load(&self.decode_errors),
+            max_gap_ms: load(&self.max_gap_ms),
+        }
+    }
+
+    pub fn reset(&self) {
+        for counter in self.messages.values().chain([
+            &self.connections,
+            &self.reconnects,
+            &self.other_messages,
+            &self.decode_errors,
+            &self.max_gap_ms,
+        ]) {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+fn add(counter: &AtomicU64, count: usize) {
+    counter.fetch_add(count as u64, Ordering::Relaxed);
+}
+
+fn load(counter: &AtomicU64) -> u64 {
+    counter.load(Ordering::Relaxed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    #[cfg(feature = "native")]
+    use std::time::Duration;
+
+    #[test]
+    fn test_tx_counts_add_up_across_threads() {
+        let stats = Arc::new(TxSessionStats::default());
+        let threads: Vec<_> = (0..8)
+            .map(|_| {
+                let stats = stats.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..1000 {
+                        stats.tx_signed();
+                        stats.orders_signed(2);
+                        stats.sent(1, 100);
+                        stats.outcome(None);
+                        stats.outcome(Some(&TxFailure::Rejected {
+                            code: 21104,
+                            message: None,
+                        }));
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.txs_signed, 8000);
+        assert_eq!(snapshot.orders_signed, 16000);
+        assert_eq!(snapshot.submitted, 8000);
+        assert_eq!(snapshot.bytes_sent, 800_000);
+        assert_eq!(snapshot.accepted, 8000);
+        assert_eq!(snapshot.rejected, BTreeMap::from([(21104, 8000)]));
+
+        stats.reset();
+        assert_eq!(stats.snapshot(), TxStats::default());
+    }
+
+    #[test]
+    #[cfg(feature = "native")]
+    fn test_ws_gap_is_measured_within_a_connection() {
+        let epoch = Instant::now();
+        let stats = WsSessionStats::new(["order_book/0".to_string()], epoch);
+        stats.connected();
+        stats.frame_received(epoch);
+        stats.frame_received(epoch + Duration::from_millis(1500));
+        stats.frame_received(epoch + Duration::from_millis(1700));
+        assert_eq!(stats.snapshot().max_gap_ms, 1500);
+
+        // The time disconnected doesn't count
+        stats.connected();
+        stats.frame_received(epoch + Duration::from_secs(60));
+        stats.frame_received(epoch + Duration::from_millis(60_400));
+        stats.message(Some("order_book/0"));
+        stats.message(Some("order_book/1"));
+        stats.message(None);
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.max_gap_ms, 1500);
+        assert_eq!(snapshot.connections, 2);
+        assert_eq!(snapshot.reconnects, 1);
+        assert_eq!(
+            snapshot.messages,
+            BTreeMap::from([("order_book/0".to_string(), 1)])
+        );
+        assert_eq!(snapshot.other_messages, 2);
+
+        stats.reset();
+        assert_eq!(
+            stats.snapshot(),
+            WsStats {
+                messages: BTreeMap::from([("order_book/0".to_string(), 0)]),
+                ..WsStats::default()
+            }
+        );
+        stats.connected();
+        assert_eq!(stats.snapshot().reconnects, 1);
+    }
+}
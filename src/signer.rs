@@ -3,11 +3,14 @@
 use crate::constants::{PRIVATE_KEY_LENGTH, PUBLIC_KEY_LENGTH, SIGNATURE_LENGTH};
 use crate::errors::{LighterError, Result};
 use crate::utils::hex_to_bytes;
-use goldilocks_crypto::{sign_with_nonce, Point, ScalarField};
+use goldilocks_crypto::{sign_with_nonce, verify_signature, Point, ScalarField};
+
+/// Schnorr signature over a transaction hash
+pub type Signature = [u8; SIGNATURE_LENGTH];
 
 /// Trait for signing messages
 pub trait Signer {
-    fn sign(&self, hashed_message: &[u8]) -> Result<Vec<u8>>;
+    fn sign(&self, hashed_message: &[u8]) -> Result<Signature>;
 }
 
 /// Trait for key management operations
@@ -48,6 +51,12 @@ impl PoseidonKeyManager {
         Self::new(&bytes)
     }
 
+    /// Check a signature over a 40-byte hashed message against this key
+    pub fn verify(&self, hashed_message: &[u8], signature: &[u8]) -> Result<bool> {
+        verify_signature(signature, hashed_message, &self.public_key)
+            .map_err(|e| LighterError::CryptoError(format!("Verification failed: {e:?}")))
+    }
+
     fn derive_public_key(private_key: &[u8]) -> Result<Vec<u8>> {
         // Convert private key bytes to ScalarField
         let scalar = ScalarField::from_bytes_le(private_key)
@@ -86,7 +95,7 @@ impl PoseidonKeyManager {
 }
 
 impl Signer for PoseidonKeyManager {
-    fn sign(&self, hashed_message: &[u8]) -> Result<Vec<u8>> {
+    fn sign(&self, hashed_message: &[u8]) -> Result<Signature> {
         // The hashed message should be 40 bytes (5 * 8 bytes for Fp5Element)
         if hashed_message.len() != 40 {
             return Err(LighterError::CryptoError(format!(
@@ -103,15 +112,12 @@ impl Signer for PoseidonKeyManager {
         let signature = sign_with_nonce(&self.private_key, hashed_message, &nonce.to_bytes_le())
             .map_err(|e| LighterError::CryptoError(format!("Signing failed: {e:?}")))?;
 
-        if signature.len() != SIGNATURE_LENGTH {
-            return Err(LighterError::CryptoError(format!(
-                "Invalid signature length: expected {}, got {}",
-                SIGNATURE_LENGTH,
-                signature.len()
-            )));
-        }
-
-        Ok(signature)
+        let len = signature.len();
+        signature.try_into().map_err(|_| {
+            LighterError::CryptoError(format!(
+                "Invalid signature length: expected {SIGNATURE_LENGTH}, got {len}"
+            ))
+        })
     }
 }
 
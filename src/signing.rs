@@ -0,0 +1,169 @@
+//! Where transaction hashing and signing run
+//!
+//! Signing is CPU-bound. Running it inline on the async executor is the
+//! cheapest option for occasional orders, but bursts of signatures can starve
+//! other tasks on the same runtime (such as a WebSocket read loop). The
+//! strategies here move that work elsewhere.
+//!
+//! Only [`SigningStrategy::Inline`] is available without the `native` feature
+//! (e.g. on wasm32), where there are no threads to move signing to.
+
+#[cfg(feature = "native")]
+use std::sync::mpsc;
+#[cfg(feature = "native")]
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "native")]
+use std::thread;
+
+#[cfg(feature = "native")]
+use tokio::sync::oneshot;
+
+use crate::errors::{LighterError, Result};
+
+/// How the client runs the hash+sign step of a transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SigningStrategy {
+    /// Sign on the calling task (default)
+    #[default]
+    Inline,
+    /// Sign on tokio's blocking thread pool via `spawn_blocking`
+    Blocking,
+    /// Sign on a dedicated pool of `n` OS threads owned by the client
+    Pool(usize),
+}
+
+#[cfg(feature = "native")]
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+/// Runs signing jobs according to a [`SigningStrategy`]
+pub(crate) enum SigningExecutor {
+    Inline,
+    #[cfg(feature = "native")]
+    Blocking,
+    #[cfg(feature = "native")]
+    Pool(SigningPool),
+}
+
+impl SigningExecutor {
+    pub(crate) fn new(strategy: SigningStrategy) -> Result<Self> {
+        Ok(match strategy {
+            SigningStrategy::Inline => SigningExecutor::Inline,
+            #[cfg(feature = "native")]
+            SigningStrategy::Blocking => SigningExecutor::Blocking,
+            #[cfg(feature = "native")]
+            SigningStrategy::Pool(threads) => SigningExecutor::Pool(SigningPool::new(threads)?),
+            #[cfg(not(feature = "native"))]
+            SigningStrategy::Blocking | SigningStrategy::Pool(_) => {
+                return Err(LighterError::InvalidConfiguration(format!(
+                    "{strategy:?} signing requires the `native` feature"
+                )))
+            }
+        })
+    }
+
+    /// Run `job` according to the strategy and return its output
+    pub(crate) async fn run<T, F>(&self, job: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+    {
+        match self {
+            SigningExecutor::Inline => Ok(job()),
+            #[cfg(feature = "native")]
+            SigningExecutor::Blocking => tokio::task::spawn_blocking(job)
+                .await
+                .map_err(|e| LighterError::CryptoError(format!("Signing task failed: {e}"))),
+            #[cfg(feature = "native")]
+            SigningExecutor::Pool(pool) => pool.run(job).await,
+        }
+    }
+}
+
+/// Fixed-size pool of signing threads
+#[cfg(feature = "native")]
+pub(crate) struct SigningPool {
+    sender: Mutex<mpsc::Sender<Job>>,
+}
+
+#[cfg(feature = "native")]
+impl SigningPool {
+    fn new(threads: usize) -> Result<Self> {
+        if threads == 0 {
+            return Err(LighterError::InvalidConfiguration(
+                "Signing pool needs at least one thread".to_string(),
+            ));
+        }
+
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+
+        for i in 0..threads {
+            let receiver = receiver.clone();
+            thread::Builder::new()
+                .name(format!("lighter-signer-{i}"))
+                .spawn(move || loop {
+                    // Exits once the pool (and with it the sender) is dropped
+                    let job = match receiver.lock() {
+                        Ok(receiver) => receiver.recv(),
+                        Err(_) => return,
+                    };
+                    match job {
+                        Ok(job) => job(),
+                        Err(_) => return,
+                    }
+                })
+                .map_err(|e| {
+                    LighterError::InvalidConfiguration(format!("Failed to spawn signer: {e}"))
+                })?;
+        }
+
+        Ok(Self {
+            sender: Mutex::new(sender),
+        })
+    }
+
+    async fn run<T, F>(&self, job: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+    {
+        let (tx, rx) = oneshot::channel();
+        let job: Job = Box::new(move || {
+            let _ = tx.send(job());
+        });
+
+        self.sender
+            .lock()
+            .map_err(|_| LighterError::CryptoError("Signing pool is poisoned".to_string()))?
+            .send(job)
+            .map_err(|_| LighterError::CryptoError("Signing pool has shut down".to_string()))?;
+
+        rx.await
+            .map_err(|_| LighterError::CryptoError("Signing job was dropped".to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_all_strategies_run_jobs() {
+        for strategy in [
+            SigningStrategy::Inline,
+            SigningStrategy::Blocking,
+            SigningStrategy::Pool(2),
+        ] {
+            let executor = SigningExecutor::new(strategy).unwrap();
+            assert_eq!(executor.run(|| 21 * 2).await.unwrap(), 42);
+        }
+    }
+
+    #[test]
+    fn test_empty_pool_rejected() {
+        assert!(matches!(
+            SigningExecutor::new(SigningStrategy::Pool(0)),
+            Err(LighterError::InvalidConfiguration(_))
+        ));
+    }
+}
@@ -0,0 +1,1854 @@
+//! Paper trading against a simulated exchange, enabled with the `simulator` feature
+//!
+//! [`SimulatedExchange`] accepts the same signed transactions as Lighter, keeps
+//! virtual balances, orders and positions, and matches them against an order
+//! book feed taken from a real [`WsClient`] or from recorded frames. It is a
+//! [`Transport`], so a [`TxClient`](crate::client::TxClient) can talk to it
+//! directly, and [`SimulatedExchange::serve`] exposes it on localhost so
+//! unmodified programs can point `LIGHTER_API_URL` and `LIGHTER_WS_HOST` at it.
+//!
+//! Fill modeling is deliberately simple:
+//! - an order that crosses the book fills in full at the touch, without
+//!   consuming book depth
+//! - a resting limit order fills at its own price once the book crosses it
+//! - IOC and market orders that don't cross are cancelled, as are post-only
+//!   orders that would cross on arrival
+//! - stop-loss and take-profit orders trigger off the touch, then behave as
+//!   market (types 2 and 4) or limit (types 3 and 5) orders
+//! - reduce-only orders never grow or flip a position
+//!
+//! Positions track a weighted average entry price, and realized PnL is
+//! credited to collateral. Fees, margin requirements and liquidations are not
+//! modeled.
+//!
+//! ```
+//! use lighter_rs::client::TxClient;
+//! use lighter_rs::simulator::SimulatedExchange;
+//! use lighter_rs::ws_client::{OrderBook, PriceLevel};
+//! use lighter_rs::Decimal;
+//! use std::sync::Arc;
+//!
+//! # async fn example() -> lighter_rs::Result<()> {
+//! let exchange = SimulatedExchange::builder()
+//!     .market(0, 2, 4)
+//!     .account(1, Decimal::new(10_000, 0))
+//!     .build()?;
+//! exchange.update_order_book(
+//!     0,
+//!     &OrderBook {
+//!         asks: vec![PriceLevel { price: Decimal::new(302500, 2), size: Decimal::ONE }],
+//!         bids: vec![PriceLevel { price: Decimal::new(302400, 2), size: Decimal::ONE }],
+//!         offset: None,
+//!     },
+//! );
+//!
+//! let tx_client = TxClient::builder()
+//!     .api_url("http://simulator")
+//!     .private_key("0x0102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f202122232425262728")
+//!     .account_index(1)
+//!     .chain_id(304)
+//!     .transport(Arc::new(exchange.clone()))
+//!     .build()?;
+//!
+//! let order = tx_client
+//!     .create_market_order(0, 1, 1000, 303000, 0, false, None)
+//!     .await?;
+//! tx_client.send_transaction(&order).await?;
+//!
+//! let account = exchange.account(1).unwrap();
+//! assert_eq!(account.positions[&0].base_amount, 1000);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use futures_util::{SinkExt, StreamExt};
+use goldilocks_crypto::verify_signature;
+use reqwest::Method;
+use rust_decimal::Decimal;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+use tokio::sync::broadcast;
+use tokio::task::{JoinHandle, JoinSet};
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::constants::*;
+use crate::errors::{LighterError, Result};
+use crate::loopback::{is_websocket_upgrade, serve_http};
+use crate::transport::{HttpRequest, HttpResponse, Transport, TransportFuture};
+use crate::types::*;
+use crate::ws_client::{OrderBook, OrderBookUpdate, PriceLevel, UpdateLevels, WsClient, WsFrame};
+
+const STATUS_PATH: &str = "/";
+const NEXT_NONCE_PATH: &str = "/api/v1/nextNonce";
+const SEND_TX_PATH: &str = "/api/v1/sendTx";
+const ORDER_BOOK_DETAILS_PATH: &str = "/api/v1/orderBookDetails";
+
+/// Frames buffered per subscriber before slow ones start skipping
+const FRAME_BUFFER: usize = 1024;
+
+/// Scaling of a market's integer prices and base amounts
+///
+/// A price of `302466` with `price_decimals: 2` is 3024.66, and a base amount
+/// of `1000` with `size_decimals: 4` is 0.1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MarketConfig {
+    pub price_decimals: u32,
+    pub size_decimals: u32,
+}
+
+impl MarketConfig {
+    fn price(&self, price: u64) -> Decimal {
+        Decimal::from_i128_with_scale(i128::from(price), self.price_decimals)
+    }
+
+    fn size(&self, base_amount: i64) -> Decimal {
+        Decimal::new(base_amount, self.size_decimals)
+    }
+}
+
+/// An open order on the simulated exchange
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SimOrder {
+    pub order_index: i64,
+    pub client_order_index: i64,
+    pub market_index: u8,
+    pub is_ask: bool,
+    pub order_type: u8,
+    pub time_in_force: u8,
+    pub reduce_only: bool,
+    pub price: u64,
+    pub trigger_price: u64,
+    /// Base amount still to be filled
+    pub remaining_base_amount: i64,
+    /// Whether the order is live; stop-loss and take-profit orders start out
+    /// untriggered
+    pub triggered: bool,
+}
+
+/// A fill of one of the account's orders
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SimFill {
+    pub order_index: i64,
+    pub client_order_index: i64,
+    pub market_index: u8,
+    pub is_ask: bool,
+    pub price: Decimal,
+    pub base_amount: i64,
+    /// PnL realized by the part of the fill that reduced a position
+    pub realized_pnl: Decimal,
+}
+
+/// Position in one market
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SimPosition {
+    /// Signed base amount: positive when long, negative when short
+    pub base_amount: i64,
+    /// Average entry price of the open position
+    pub entry_price: Decimal,
+    /// PnL realized in this market so far
+    pub realized_pnl: Decimal,
+}
+
+/// Virtual state of one account
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SimAccount {
+    /// USDC collateral, including realized PnL
+    pub collateral: Decimal,
+    /// Positions by market index
+    pub positions: BTreeMap<u8, SimPosition>,
+    /// Open orders, oldest first
+    pub orders: Vec<SimOrder>,
+    /// Every fill so far, oldest first
+    pub fills: Vec<SimFill>,
+}
+
+/// Builder for [`SimulatedExchange`]
+pub struct SimulatedExchangeBuilder {
+    markets: HashMap<u8, MarketConfig>,
+    accounts: HashMap<i64, Decimal>,
+    api_keys: HashMap<(i64, u8), Vec<u8>>,
+    chain_id: u32,
+}
+
+impl SimulatedExchangeBuilder {
+    /// Create a new simulated exchange builder
+    pub fn new() -> Self {
+        Self {
+            markets: HashMap::new(),
+            accounts: HashMap::new(),
+            api_keys: HashMap::new(),
+            chain_id: 304,
+        }
+    }
+
+    /// Add a tradable market with the given price and size scaling
+    pub fn market(mut self, market_index: u8, price_decimals: u32, size_decimals: u32) -> Self {
+        self.markets.insert(
+            market_index,
+            MarketConfig {
+                price_decimals,
+                size_decimals,
+            },
+        );
+        self
+    }
+
+    /// Fund an account with USDC collateral
+    ///
+    /// Accounts that aren't funded start with zero collateral.
+    pub fn account(mut self, account_index: i64, collateral: Decimal) -> Self {
+        self.accounts.insert(account_index, collateral);
+        self
+    }
+
+    /// Register an API key's public key
+    ///
+    /// Once an account has a registered key, its transactions must be signed
+    /// by a registered key. Accounts without one accept any signature.
+    pub fn api_key(
+        mut self,
+        account_index: i64,
+        api_key_index: u8,
+        public_key: impl Into<Vec<u8>>,
+    ) -> Self {
+        self.api_keys
+            .insert((account_index, api_key_index), public_key.into());
+        self
+    }
+
+    /// Set the chain ID signatures are checked against (defaults to 304)
+    pub fn chain_id(mut self, chain_id: u32) -> Self {
+        self.chain_id = chain_id;
+        self
+    }
+
+    /// Build the simulated exchange
+    pub fn build(self) -> Result<SimulatedExchange> {
+        for (market_index, config) in &self.markets {
+            if config.price_decimals > 28 || config.size_decimals > 28 {
+                return Err(LighterError::InvalidConfiguration(format!(
+                    "Market {market_index} has more than 28 decimals"
+                )));
+            }
+        }
+
+        let state = State {
+            markets: self.markets,
+            accounts: self
+                .accounts
+                .into_iter()
+                .map(|(account_index, collateral)| {
+                    let account = SimAccount {
+                        collateral,
+                        ..SimAccount::default()
+                    };
+                    (account_index, account)
+                })
+                .collect(),
+            next_order_index: MIN_ORDER_INDEX,
+            ..State::default()
+        };
+        let (frames, _) = broadcast::channel(FRAME_BUFFER);
+
+        Ok(SimulatedExchange {
+            shared: Arc::new(Shared {
+                state: Mutex::new(state),
+                api_keys: self.api_keys,
+                chain_id: self.chain_id,
+                frames,
+            }),
+        })
+    }
+}
+
+impl Default for SimulatedExchangeBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Default)]
+struct State {
+    markets: HashMap<u8, MarketConfig>,
+    books: HashMap<u8, OrderBook>,
+    accounts: HashMap<i64, SimAccount>,
+    nonces: HashMap<(i64, u8), i64>,
+    next_order_index: i64,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    api_keys: HashMap<(i64, u8), Vec<u8>>,
+    chain_id: u32,
+    frames: broadcast::Sender<String>,
+}
+
+/// Fills produced while handling one event, by account
+///
+/// Accounts with an empty list still changed and get an account frame.
+type Touched = BTreeMap<i64, Vec<SimFill>>;
+
+/// A transaction the exchange refused, reported in the `sendTx` response
+struct Rejection {
+    code: u16,
+    message: String,
+}
+
+impl Rejection {
+    fn invalid(message: impl Into<String>) -> Self {
+        Self {
+            code: 400,
+            message: message.into(),
+        }
+    }
+}
+
+type Handled<T> = std::result::Result<T, Rejection>;
+
+/// What matching decided for one order
+enum Action {
+    Rest,
+    Cancel,
+    Fill { base_amount: i64, price: Decimal },
+}
+
+/// Simulated Lighter exchange for paper trading
+///
+/// Cloning is cheap and every clone shares the same state.
+#[derive(Clone)]
+pub struct SimulatedExchange {
+    shared: Arc<Shared>,
+}
+
+impl SimulatedExchange {
+    /// Create a new simulated exchange builder
+    pub fn builder() -> SimulatedExchangeBuilder {
+        SimulatedExchangeBuilder::new()
+    }
+
+    /// Snapshot of an account, if it has been funded or has traded
+    pub fn account(&self, account_index: i64) -> Option<SimAccount> {
+        self.lock().accounts.get(&account_index).cloned()
+    }
+
+    /// Unrealized PnL of an account's positions, marked at each book's mid price
+    pub fn unrealized_pnl(&self, account_index: i64) -> Decimal {
+        let state = self.lock();
+        state
+            .accounts
+            .get(&account_index)
+            .map_or(Decimal::ZERO, |account| state.unrealized_pnl(account))
+    }
+
+    /// Nonce the exchange expects next from an API key
+    pub fn next_nonce(&self, account_index: i64, api_key_index: u8) -> i64 {
+        self.lock()
+            .nonces
+            .get(&(account_index, api_key_index))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Current order book of a market, if the feed has delivered one
+    pub fn order_book(&self, market_index: u8) -> Option<OrderBook> {
+        self.lock().books.get(&market_index).cloned()
+    }
+
+    /// Receive every frame the exchange's WebSocket stream emits
+    ///
+    /// Frames are the same JSON text served to WebSocket subscribers: order
+    /// book snapshots and updates, and account updates with fills.
+    pub fn subscribe(&self) -> broadcast::Receiver<String> {
+        self.shared.frames.subscribe()
+    }
+
+    /// Replace a market's order book and match resting orders against it
+    pub fn update_order_book(&self, market_index: u8, order_book: &OrderBook) {
+        let mut frames = Vec::new();
+        {
+            let mut state = self.lock();
+            let frame = match state.books.insert(market_index, order_book.clone()) {
+                None => Some(order_book_frame(
+                    "subscribed/order_book",
+                    market_index,
+                    order_book,
+                )),
+                Some(previous) => {
+                    let update = OrderBookUpdate {
+                        asks: diff_levels(&previous.asks, &order_book.asks),
+                        bids: diff_levels(&previous.bids, &order_book.bids),
+                        offset: None,
+                    };
+                    (!update.asks.is_empty() || !update.bids.is_empty())
+                        .then(|| order_book_frame("update/order_book", market_index, &update))
+                }
+            };
+            frames.extend(frame);
+
+            let mut touched = Touched::new();
+            state.match_market(market_index, &mut touched);
+            frames.extend(state.account_frames(touched));
+        }
+        self.publish(frames);
+    }
+
+    /// Feed one recorded WebSocket frame, for replaying captured market data
+    ///
+    /// Order book snapshots and updates move the book; other frames are
+    /// ignored.
+    pub fn apply_frame(&self, text: String) -> Result<()> {
+        match WsFrame::decode(text)? {
+            WsFrame::OrderBookSnapshot {
+                market_id,
+                order_book,
+            } => self.update_order_book(parse_market(&market_id)?, &order_book),
+            WsFrame::OrderBookUpdate { market_id, update } => {
+                let market_index = parse_market(&market_id)?;
+                let mut order_book = self.order_book(market_index).unwrap_or_default();
+                order_book.apply_update(&update);
+                self.update_order_book(market_index, &order_book);
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Feed order books from a WebSocket client, usually one connected to the
+    /// real exchange
+    ///
+    /// The returned task runs the client until its connection closes; account
+    /// frames it receives are ignored.
+    pub fn feed_from(&self, ws_client: WsClient) -> JoinHandle<Result<()>> {
+        let exchange = self.clone();
+        tokio::spawn(async move {
+            ws_client
+                .run(
+                    move |market_id, order_book| {
+                        if let Ok(market_index) = parse_market(&market_id) {
+                            exchange.update_order_book(market_index, &order_book);
+                        }
+                    },
+                    |_, _| {},
+                )
+                .await
+        })
+    }
+
+    /// Serve the REST and WebSocket APIs on `addr`
+    ///
+    /// Both share one port: WebSocket upgrade requests get the stream, every
+    /// other connection the REST endpoints. Serving stops when the returned
+    /// server is dropped.
+    pub async fn serve(&self, addr: impl ToSocketAddrs) -> Result<SimulatorServer> {
+        let listener = TcpListener::bind(addr)
+            .await
+            .map_err(|e| LighterError::Other(format!("Failed to bind simulator: {e}")))?;
+        let addr = listener
+            .local_addr()
+            .map_err(|e| LighterError::Other(format!("Failed to bind simulator: {e}")))?;
+
+        let exchange = self.clone();
+        let task = tokio::spawn(async move {
+            let mut connections = JoinSet::new();
+            while let Ok((socket, _)) = listener.accept().await {
+                connections.spawn(serve_connection(socket, exchange.clone()));
+            }
+        });
+
+        Ok(SimulatorServer { addr, task })
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.shared
+            .state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn publish(&self, frames: Vec<String>) {
+        for frame in frames {
+            // No subscribers is fine
+            let _ = self.shared.frames.send(frame);
+        }
+    }
+
+    /// Answer one REST request
+    fn handle(&self, request: &HttpRequest) -> HttpResponse {
+        let path = request.path();
+        if request.method == Method::GET && path == STATUS_PATH {
+            HttpResponse::new(
+                200,
+                json!({ "status": 200, "timestamp": chrono::Utc::now().timestamp() }).to_string(),
+            )
+        } else if request.method == Method::GET && path == NEXT_NONCE_PATH {
+            self.handle_next_nonce(request)
+        } else if request.method == Method::GET && path == ORDER_BOOK_DETAILS_PATH {
+            self.handle_order_book_details(request)
+        } else if request.method == Method::POST && path == SEND_TX_PATH {
+            self.handle_send_tx(request)
+        } else {
+            error_response(404, format!("Unknown endpoint {path}"))
+        }
+    }
+
+    fn handle_next_nonce(&self, request: &HttpRequest) -> HttpResponse {
+        #[derive(Deserialize)]
+        struct NonceQuery {
+            account_index: i64,
+            api_key_index: u8,
+        }
+
+        let query = request.url.split_once('?').map_or("", |(_, query)| query);
+        match serde_urlencoded::from_str::<NonceQuery>(query) {
+            Ok(query) => {
+                let nonce = self.next_nonce(query.account_index, query.api_key_index);
+                HttpResponse::new(
+                    200,
+                    json!({ "code": API_CODE_SUCCESS, "nonce": nonce }).to_string(),
+                )
+            }
+            Err(e) => error_response(400, format!("Invalid nonce query: {e}")),
+        }
+    }
+
+    /// Market scaling, with the book's mid standing in for the last trade price
+    fn handle_order_book_details(&self, request: &HttpRequest) -> HttpResponse {
+        #[derive(Deserialize)]
+        struct DetailsQuery {
+            market_id: u8,
+        }
+
+        let query = request.url.split_once('?').map_or("", |(_, query)| query);
+        let market_id = match serde_urlencoded::from_str::<DetailsQuery>(query) {
+            Ok(query) => query.market_id,
+            Err(e) => return error_response(400, format!("Invalid market query: {e}")),
+        };
+        let state = self.lock();
+        let Some(config) = state.markets.get(&market_id) else {
+            return error_response(400, format!("Unknown market {market_id}"));
+        };
+        let last_trade_price = state
+            .books
+            .get(&market_id)
+            .and_then(OrderBook::mid_price)
+            .unwrap_or_default();
+        HttpResponse::new(
+            200,
+            json!({
+                "code": API_CODE_SUCCESS,
+                "order_book_details": [{
+                    "market_id": market_id,
+                    "size_decimals": config.size_decimals,
+                    "price_decimals": config.price_decimals,
+                    "last_trade_price": last_trade_price.to_string(),
+                }],
+            })
+            .to_string(),
+        )
+    }
+
+    fn handle_send_tx(&self, request: &HttpRequest) -> HttpResponse {
+        let (Ok(Some(tx_type)), Ok(Some(tx_info))) =
+            (request.form_field("tx_type"), request.form_field("tx_info"))
+        else {
+            return error_response(400, "Missing tx_type or tx_info");
+        };
+        let Ok(tx_type) = tx_type.parse() else {
+            return error_response(400, format!("Invalid tx_type {tx_type}"));
+        };
+
+        let mut frames = Vec::new();
+        let result = {
+            let mut state = self.lock();
+            let mut touched = Touched::new();
+            let result = self.apply_tx(&mut state, tx_type, &tx_info, &mut touched);
+            frames.extend(state.account_frames(touched));
+            result
+        };
+        self.publish(frames);
+
+        let body = match result {
+            Ok(tx_hash) => json!({ "code": API_CODE_SUCCESS, "tx_hash": tx_hash }),
+            Err(rejection) => json!({ "code": rejection.code, "message": rejection.message }),
+        };
+        HttpResponse::new(200, body.to_string())
+    }
+
+    /// Apply a signed transaction, returning its hash
+    fn apply_tx(
+        &self,
+        state: &mut State,
+        tx_type: u8,
+        tx_info: &str,
+        touched: &mut Touched,
+    ) -> Handled<String> {
+        match tx_type {
+            TX_TYPE_L2_CREATE_ORDER => {
+                let tx: L2CreateOrderTxInfo = decode(tx_info)?;
+                let hash = self.verify(&tx, tx.account_index, tx.api_key_index)?;
+                state.market(tx.market_index)?;
+                if tx.order_type > ORDER_TYPE_TAKE_PROFIT_LIMIT {
+                    return Err(Rejection::invalid(format!(
+                        "Order type {} is not supported by the simulator",
+                        tx.order_type
+                    )));
+                }
+                state.use_nonce(tx.account_index, tx.api_key_index, tx.nonce)?;
+
+                let order = SimOrder {
+                    order_index: state.next_order_index,
+                    client_order_index: tx.client_order_index,
+                    market_index: tx.market_index,
+                    is_ask: tx.is_ask != 0,
+                    order_type: tx.order_type,
+                    time_in_force: tx.time_in_force,
+                    reduce_only: tx.reduce_only != 0,
+                    price: tx.price,
+                    trigger_price: tx.trigger_price,
+                    remaining_base_amount: tx.base_amount,
+                    triggered: !matches!(
+                        tx.order_type,
+                        ORDER_TYPE_STOP_LOSS
+                            | ORDER_TYPE_STOP_LOSS_LIMIT
+                            | ORDER_TYPE_TAKE_PROFIT
+                            | ORDER_TYPE_TAKE_PROFIT_LIMIT
+                    ),
+                };
+                state.next_order_index += 1;
+                state.place(tx.account_index, order, touched);
+                Ok(hash)
+            }
+            TX_TYPE_L2_CANCEL_ORDER => {
+                let tx: L2CancelOrderTxInfo = decode(tx_info)?;
+                let hash = self.verify(&tx, tx.account_index, tx.api_key_index)?;
+                let position = state.find_order(tx.account_index, tx.market_index, tx.index)?;
+                state.use_nonce(tx.account_index, tx.api_key_index, tx.nonce)?;
+
+                if let Some(account) = state.accounts.get_mut(&tx.account_index) {
+                    account.orders.remove(position);
+                }
+                touched.entry(tx.account_index).or_default();
+                Ok(hash)
+            }
+            TX_TYPE_L2_MODIFY_ORDER => {
+                let tx: L2ModifyOrderTxInfo = decode(tx_info)?;
+                let hash = self.verify(&tx, tx.account_index, tx.api_key_index)?;
+                let position = state.find_order(tx.account_index, tx.market_index, tx.index)?;
+                state.use_nonce(tx.account_index, tx.api_key_index, tx.nonce)?;
+
+                let Some(account) = state.accounts.get_mut(&tx.account_index) else {
+                    return Ok(hash);
+                };
+                let order = &mut account.orders[position];
+                order.price = tx.price;
+                order.trigger_price = tx.trigger_price;
+                order.remaining_base_amount = tx.base_amount;
+                let order_index = order.order_index;
+                touched.entry(tx.account_index).or_default();
+                // A modified order can cross and take liquidity like a new one
+                state.process(tx.account_index, order_index, true, touched);
+                Ok(hash)
+            }
+            TX_TYPE_L2_CANCEL_ALL_ORDERS => {
+                let tx: L2CancelAllOrdersTxInfo = decode(tx_info)?;
+                let hash = self.verify(&tx, tx.account_index, tx.api_key_index)?;
+                state.use_nonce(tx.account_index, tx.api_key_index, tx.nonce)?;
+
+                // Scheduled cancels are accepted but never fire
+                if tx.time_in_force == CANCEL_ALL_IMMEDIATE {
+                    if let Some(account) = state.accounts.get_mut(&tx.account_index) {
+                        account.orders.clear();
+                    }
+                    touched.entry(tx.account_index).or_default();
+                }
+                Ok(hash)
+            }
+            TX_TYPE_L2_WITHDRAW => {
+                let tx: L2WithdrawTxInfo = decode(tx_info)?;
+                let hash = self.verify(&tx, tx.from_account_index, tx.api_key_index)?;
+                let amount = usdc(tx.usdc_amount);
+                state.check_collateral(tx.from_account_index, amount)?;
+                state.use_nonce(tx.from_account_index, tx.api_key_index, tx.nonce)?;
+
+                state.credit(tx.from_account_index, -amount);
+                touched.entry(tx.from_account_index).or_default();
+                Ok(hash)
+            }
+            TX_TYPE_L2_TRANSFER => {
+                let tx: L2TransferTxInfo = decode(tx_info)?;
+                let hash = self.verify(&tx, tx.from_account_index, tx.api_key_index)?;
+                let amount = usdc(tx.usdc_amount);
+                let fee = usdc(tx.fee);
+                state.check_collateral(tx.from_account_index, amount + fee)?;
+                state.use_nonce(tx.from_account_index, tx.api_key_index, tx.nonce)?;
+
+                state.credit(tx.from_account_index, -(amount + fee));
+                state.credit(tx.to_account_index, amount);
+                touched.entry(tx.from_account_index).or_default();
+                touched.entry(tx.to_account_index).or_default();
+                Ok(hash)
+            }
+            // Accepted so setup code runs, but leverage, margin and keys
+            // aren't modeled
+            TX_TYPE_L2_UPDATE_LEVERAGE => {
+                let tx: L2UpdateLeverageTxInfo = decode(tx_info)?;
+                let hash = self.verify(&tx, tx.account_index, tx.api_key_index)?;
+                state.use_nonce(tx.account_index, tx.api_key_index, tx.nonce)?;
+                Ok(hash)
+            }
+            TX_TYPE_L2_UPDATE_MARGIN => {
+                let tx: L2UpdateMarginTxInfo = decode(tx_info)?;
+                let hash = self.verify(&tx, tx.account_index, tx.api_key_index)?;
+                state.use_nonce(tx.account_index, tx.api_key_index, tx.nonce)?;
+                Ok(hash)
+            }
+            TX_TYPE_L2_CHANGE_PUB_KEY => {
+                let tx: L2ChangePubKeyTxInfo = decode(tx_info)?;
+                let hash = self.verify(&tx, tx.account_index, tx.api_key_index)?;
+                state.use_nonce(tx.account_index, tx.api_key_index, tx.nonce)?;
+                Ok(hash)
+            }
+            other => Err(Rejection::invalid(format!(
+                "Transaction type {other} is not supported by the simulator"
+            ))),
+        }
+    }
+
+    /// Validate a transaction and check its signature, returning its hash
+    fn verify(&self, tx: &dyn TxInfo, account_index: i64, api_key_index: u8) -> Handled<String> {
+        tx.validate()
+            .map_err(|e| Rejection::invalid(e.to_string()))?;
+        let signature = tx
+            .signature()
+            .ok_or_else(|| Rejection::invalid("Transaction is not signed"))?;
+        let hash = tx
+            .hash(self.shared.chain_id)
+            .map_err(|e| Rejection::invalid(e.to_string()))?;
+
+        match self.shared.api_keys.get(&(account_index, api_key_index)) {
+            Some(public_key) => {
+                if !matches!(verify_signature(signature, &hash, public_key), Ok(true)) {
+                    return Err(Rejection::invalid("Invalid signature"));
+                }
+            }
+            None => {
+                let has_keys = self
+                    .shared
+                    .api_keys
+                    .keys()
+                    .any(|(account, _)| *account == account_index);
+                if has_keys {
+                    return Err(Rejection {
+                        code: API_CODE_API_KEY_NOT_FOUND,
+                        message: format!("API key {api_key_index} not found"),
+                    });
+                }
+            }
+        }
+        Ok(hex::encode(hash))
+    }
+}
+
+impl Transport for SimulatedExchange {
+    fn execute(&self, request: HttpRequest) -> TransportFuture<'_> {
+        let response = self.handle(&request);
+        Box::pin(async move { Ok(response) })
+    }
+}
+
+impl State {
+    fn market(&self, market_index: u8) -> Handled<MarketConfig> {
+        self.markets
+            .get(&market_index)
+            .copied()
+            .ok_or_else(|| Rejection::invalid(format!("Unknown market {market_index}")))
+    }
+
+    /// Consume a nonce, which must be exactly the next one expected
+    fn use_nonce(&mut self, account_index: i64, api_key_index: u8, nonce: i64) -> Handled<()> {
+        let expected = self
+            .nonces
+            .entry((account_index, api_key_index))
+            .or_insert(0);
+        if nonce != *expected {
+            return Err(Rejection {
+                code: API_CODE_INVALID_NONCE,
+                message: format!("Invalid nonce: expected {expected}, got {nonce}"),
+            });
+        }
+        *expected += 1;
+        Ok(())
+    }
+
+    /// Position of an open order in its account's order list, by order index
+    /// or client order index
+    fn find_order(&self, account_index: i64, market_index: u8, index: i64) -> Handled<usize> {
+        self.accounts
+            .get(&account_index)
+            .and_then(|account| {
+                account.orders.iter().position(|order| {
+                    order.market_index == market_index
+                        && (order.order_index == index || order.client_order_index == index)
+                })
+            })
+            .ok_or_else(|| Rejection::invalid(format!("Order {index} not found")))
+    }
+
+    fn check_collateral(&self, account_index: i64, amount: Decimal) -> Handled<()> {
+        let collateral = self
+            .accounts
+            .get(&account_index)
+            .map_or(Decimal::ZERO, |account| account.collateral);
+        if collateral < amount {
+            return Err(Rejection::invalid(format!(
+                "Insufficient collateral: {collateral} < {amount}"
+            )));
+        }
+        Ok(())
+    }
+
+    fn credit(&mut self, account_index: i64, amount: Decimal) {
+        self.accounts.entry(account_index).or_default().collateral += amount;
+    }
+
+    /// Add a new order and match it as a taker
+    fn place(&mut self, account_index: i64, order: SimOrder, touched: &mut Touched) {
+        let order_index = order.order_index;
+        self.accounts
+            .entry(account_index)
+            .or_default()
+            .orders
+            .push(order);
+        touched.entry(account_index).or_default();
+        self.process(account_index, order_index, true, touched);
+    }
+
+    /// Match every resting order in a market against its book, oldest first
+    fn match_market(&mut self, market_index: u8, touched: &mut Touched) {
+        let mut resting: Vec<(i64, i64)> = self
+            .accounts
+            .iter()
+            .flat_map(|(account_index, account)| {
+                account
+                    .orders
+                    .iter()
+                    .filter(|order| order.market_index == market_index)
+                    .map(|order| (order.order_index, *account_index))
+            })
+            .collect();
+        resting.sort_unstable();
+
+        for (order_index, account_index) in resting {
+            self.process(account_index, order_index, false, touched);
+        }
+    }
+
+    /// Match one open order against its market's book
+    fn process(
+        &mut self,
+        account_index: i64,
+        order_index: i64,
+        taker: bool,
+        touched: &mut Touched,
+    ) {
+        let Some(account) = self.accounts.get_mut(&account_index) else {
+            return;
+        };
+        let Some(i) = account
+            .orders
+            .iter()
+            .position(|order| order.order_index == order_index)
+        else {
+            return;
+        };
+        let market_index = account.orders[i].market_index;
+        let Some(config) = self.markets.get(&market_index) else {
+            return;
+        };
+        let position = account
+            .positions
+            .get(&market_index)
+            .map_or(0, |position| position.base_amount);
+
+        let was_triggered = account.orders[i].triggered;
+        let action = evaluate(
+            &mut account.orders[i],
+            config,
+            self.books.get(&market_index),
+            position,
+            taker,
+        );
+        match action {
+            Action::Rest => {
+                if account.orders[i].triggered != was_triggered {
+                    touched.entry(account_index).or_default();
+                }
+            }
+            Action::Cancel => {
+                account.orders.remove(i);
+                touched.entry(account_index).or_default();
+            }
+            Action::Fill { base_amount, price } => {
+                let order = account.orders.remove(i);
+                let fill = apply_fill(account, config, &order, base_amount, price);
+                // Orders fill in full unless reduce-only capped them, and then
+                // there is nothing left to reduce
+                touched.entry(account_index).or_default().push(fill);
+            }
+        }
+    }
+
+    fn unrealized_pnl(&self, account: &SimAccount) -> Decimal {
+        account
+            .positions
+            .iter()
+            .filter_map(|(market_index, position)| {
+                let config = self.markets.get(market_index)?;
+                let mid = self
+                    .books
+                    .get(market_index)
+                    .and_then(OrderBook::mid_price)?;
+                Some(config.size(position.base_amount) * (mid - position.entry_price))
+            })
+            .sum()
+    }
+
+    fn account_frames(&self, touched: Touched) -> Vec<String> {
+        touched
+            .into_iter()
+            .map(|(account_index, fills)| {
+                self.account_frame("update/account_all", account_index, &fills)
+            })
+            .collect()
+    }
+
+    /// Account frame in the shape of the `account_all` channel
+    fn account_frame(&self, msg_type: &str, account_index: i64, fills: &[SimFill]) -> String {
+        let default = SimAccount::default();
+        let account = self.accounts.get(&account_index).unwrap_or(&default);
+        let config = |market_index: &u8| self.markets.get(market_index).copied();
+
+        let orders: Vec<Value> = account
+            .orders
+            .iter()
+            .filter_map(|order| {
+                let config = config(&order.market_index)?;
+                Some(json!({
+                    "order_index": order.order_index,
+                    "client_order_index": order.client_order_index,
+                    "market_index": order.market_index,
+                    "is_ask": u8::from(order.is_ask),
+                    "order_type": order.order_type,
+                    "time_in_force": order.time_in_force,
+                    "reduce_only": u8::from(order.reduce_only),
+                    "price": config.price(order.price).to_string(),
+                    "trigger_price": config.price(order.trigger_price).to_string(),
+                    "size": config.size(order.remaining_base_amount).to_string(),
+                    "triggered": order.triggered,
+                    "status": "open",
+                }))
+            })
+            .collect();
+        let positions: Vec<Value> = account
+            .positions
+            .iter()
+            .filter(|(_, position)| position.base_amount != 0)
+            .filter_map(|(market_index, position)| {
+                let config = config(market_index)?;
+                Some(json!({
+                    "market_index": market_index,
+                    "sign": position.base_amount.signum(),
+                    "position": config.size(position.base_amount.abs()).to_string(),
+                    "entry_price": position.entry_price.to_string(),
+                    "realized_pnl": position.realized_pnl.to_string(),
+                }))
+            })
+            .collect();
+        let trades: Vec<Value> = fills
+            .iter()
+            .filter_map(|fill| {
+                let config = config(&fill.market_index)?;
+                Some(json!({
+                    "order_index": fill.order_index,
+                    "client_order_index": fill.client_order_index,
+                    "market_index": fill.market_index,
+                    "is_ask": u8::from(fill.is_ask),
+                    "price": fill.price.to_string(),
+                    "size": config.size(fill.base_amount).to_string(),
+                    "realized_pnl": fill.realized_pnl.to_string(),
+                }))
+            })
+            .collect();
+        let realized_pnl: Decimal = account
+            .positions
+            .values()
+            .map(|position| position.realized_pnl)
+            .sum();
+
+        json!({
+            "type": msg_type,
+            "channel": format!("account_all:{account_index}"),
+            "account": account_index,
+            "usdc_balance": account.collateral.to_string(),
+            "realized_pnl": realized_pnl.to_string(),
+            "unrealized_pnl": self.unrealized_pnl(account).to_string(),
+            "orders": orders,
+            "positions": positions,
+            "trades": trades,
+        })
+        .to_string()
+    }
+
+    /// Snapshot sent when a WebSocket client subscribes to `channel`
+    fn snapshot_frame(&self, channel: &str) -> Option<String> {
+        let (kind, id) = channel.split_once(':')?;
+        match kind {
+            "order_book" => {
+                let market_index = id.parse().ok()?;
+                let book = self.books.get(&market_index)?;
+                Some(order_book_frame(
+                    "subscribed/order_book",
+                    market_index,
+                    book,
+                ))
+            }
+            "account_all" => {
+                Some(self.account_frame("subscribed/account_all", id.parse().ok()?, &[]))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Decide what happens to an order given the current book
+fn evaluate(
+    order: &mut SimOrder,
+    config: &MarketConfig,
+    book: Option<&OrderBook>,
+    position: i64,
+    mut taker: bool,
+) -> Action {
+    let bid = book.and_then(OrderBook::best_bid);
+    let ask = book.and_then(OrderBook::best_ask);
+
+    if !order.triggered {
+        let trigger = config.price(order.trigger_price);
+        let stop_loss = matches!(
+            order.order_type,
+            ORDER_TYPE_STOP_LOSS | ORDER_TYPE_STOP_LOSS_LIMIT
+        );
+        let hit = match (stop_loss, order.is_ask) {
+            (true, true) => bid.is_some_and(|bid| bid <= trigger),
+            (true, false) => ask.is_some_and(|ask| ask >= trigger),
+            (false, true) => bid.is_some_and(|bid| bid >= trigger),
+            (false, false) => ask.is_some_and(|ask| ask <= trigger),
+        };
+        if !hit {
+            return Action::Rest;
+        }
+        order.triggered = true;
+        // A triggered order enters the book like a new one
+        taker = true;
+    }
+
+    let limit = config.price(order.price);
+    let touch = if order.is_ask { bid } else { ask };
+    let crosses = touch.filter(|&touch| {
+        if order.is_ask {
+            touch >= limit
+        } else {
+            touch <= limit
+        }
+    });
+    let immediate = order.time_in_force == TIME_IN_FORCE_IMMEDIATE_OR_CANCEL
+        || matches!(
+            order.order_type,
+            ORDER_TYPE_MARKET | ORDER_TYPE_STOP_LOSS | ORDER_TYPE_TAKE_PROFIT
+        );
+
+    let Some(touch) = crosses else {
+        return if immediate {
+            Action::Cancel
+        } else {
+            Action::Rest
+        };
+    };
+    if taker && order.time_in_force == TIME_IN_FORCE_POST_ONLY {
+        return Action::Cancel;
+    }
+
+    let mut base_amount = order.remaining_base_amount;
+    if order.reduce_only {
+        let reducible = if order.is_ask {
+            position.max(0)
+        } else {
+            (-position).max(0)
+        };
+        base_amount = base_amount.min(reducible);
+        if base_amount == 0 {
+            return Action::Cancel;
+        }
+    }
+
+    let price = if taker { touch } else { limit };
+    Action::Fill { base_amount, price }
+}
+
+/// Book a fill against the account's position and collateral
+fn apply_fill(
+    account: &mut SimAccount,
+    config: &MarketConfig,
+    order: &SimOrder,
+    base_amount: i64,
+    price: Decimal,
+) -> SimFill {
+    let position = account.positions.entry(order.market_index).or_default();
+    let signed = if order.is_ask {
+        -base_amount
+    } else {
+        base_amount
+    };
+    let old = position.base_amount;
+    let new = old + signed;
+
+    let mut realized_pnl = Decimal::ZERO;
+    if old != 0 && old.signum() != signed.signum() {
+        let closed = base_amount.min(old.abs());
+        realized_pnl =
+            config.size(closed) * (price - position.entry_price) * Decimal::from(old.signum());
+    }
+
+    position.entry_price = if new == 0 {
+        Decimal::ZERO
+    } else if old == 0 || old.signum() != new.signum() {
+        // Opened or flipped
+        price
+    } else if new.abs() > old.abs() {
+        (position.entry_price * Decimal::from(old.abs()) + price * Decimal::from(base_amount))
+            / Decimal::from(new.abs())
+    } else {
+        position.entry_price
+    };
+    position.base_amount = new;
+    position.realized_pnl += realized_pnl;
+    account.collateral += realized_pnl;
+
+    let fill = SimFill {
+        order_index: order.order_index,
+        client_order_index: order.client_order_index,
+        market_index: order.market_index,
+        is_ask: order.is_ask,
+        price,
+        base_amount,
+        realized_pnl,
+    };
+    account.fills.push(fill.clone());
+    fill
+}
+
+/// Levels that changed between two sides of a book, with removed levels at size 0
+fn diff_levels(previous: &[PriceLevel], next: &[PriceLevel]) -> UpdateLevels {
+    let mut levels: UpdateLevels = next
+        .iter()
+        .filter(|level| !previous.contains(level))
+        .copied()
+        .collect();
+    levels.extend(
+        previous
+            .iter()
+            .filter(|old| !next.iter().any(|level| level.price == old.price))
+            .map(|old| PriceLevel {
+                price: old.price,
+                size: Decimal::ZERO,
+            }),
+    );
+    levels
+}
+
+fn order_book_frame(
+    msg_type: &str,
+    market_index: u8,
+    order_book: &impl serde::Serialize,
+) -> String {
+    json!({
+        "type": msg_type,
+        "channel": format!("order_book:{market_index}"),
+        "order_book": order_book,
+    })
+    .to_string()
+}
+
+fn error_response(status: u16, message: impl Into<String>) -> HttpResponse {
+    HttpResponse::new(
+        status,
+        json!({ "code": status, "message": message.into() }).to_string(),
+    )
+}
+
+fn decode<T: DeserializeOwned>(tx_info: &str) -> Handled<T> {
+    serde_json::from_str(tx_info).map_err(|e| Rejection::invalid(format!("Invalid tx_info: {e}")))
+}
+
+fn usdc(amount: impl Into<Decimal>) -> Decimal {
+    amount.into() / Decimal::from(ONE_USDC)
+}
+
+fn parse_market(market_id: &str) -> Result<u8> {
+    market_id
+        .parse()
+        .map_err(|_| LighterError::InvalidResponse(format!("Invalid market id {market_id}")))
+}
+
+/// Channel a frame belongs to, in its `order_book:0` form
+fn frame_channel(frame: &str) -> Option<String> {
+    #[derive(Deserialize)]
+    struct Channel {
+        channel: String,
+    }
+    serde_json::from_str::<Channel>(frame)
+        .ok()
+        .map(|frame| frame.channel)
+}
+
+/// Channel a subscribe message asks for, normalized from `order_book/0` to
+/// `order_book:0`
+fn subscription_channel(message: &str) -> Option<String> {
+    let message: Value = serde_json::from_str(message).ok()?;
+    if message.get("type")?.as_str()? != "subscribe" {
+        return None;
+    }
+    Some(message.get("channel")?.as_str()?.replace('/', ":"))
+}
+
+/// Running REST and WebSocket server for a [`SimulatedExchange`]
+///
+/// The server stops when this is dropped.
+pub struct SimulatorServer {
+    addr: SocketAddr,
+    task: JoinHandle<()>,
+}
+
+impl SimulatorServer {
+    /// Address the server listens on
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Base URL for [`TxClient`](crate::client::TxClient)
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Host for [`WsClientBuilder::host`](crate::ws_client::WsClientBuilder::host)
+    pub fn ws_host(&self) -> String {
+        format!("ws://{}", self.addr)
+    }
+
+    /// Full WebSocket stream URL
+    pub fn ws_url(&self) -> String {
+        format!("ws://{}/stream", self.addr)
+    }
+}
+
+impl Drop for SimulatorServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn serve_connection(socket: TcpStream, exchange: SimulatedExchange) {
+    if is_websocket_upgrade(&socket).await {
+        serve_ws_connection(socket, exchange).await;
+    } else {
+        serve_http(socket, |request| exchange.handle(&request)).await;
+    }
+}
+
+async fn serve_ws_connection(socket: TcpStream, exchange: SimulatedExchange) {
+    // Subscribe before the handshake so no frame slips through
+    let mut frames = exchange.subscribe();
+    let Ok(stream) = tokio_tungstenite::accept_async(socket).await else {
+        return;
+    };
+    let (mut write, mut read) = stream.split();
+    if write
+        .send(Message::Text(json!({ "type": "connected" }).to_string()))
+        .await
+        .is_err()
+    {
+        return;
+    }
+
+    let mut channels = HashSet::new();
+    loop {
+        tokio::select! {
+            message = read.next() => match message {
+                Some(Ok(Message::Text(text))) => {
+                    let Some(channel) = subscription_channel(&text) else {
+                        continue;
+                    };
+                    let snapshot = exchange.lock().snapshot_frame(&channel);
+                    channels.insert(channel);
+                    if let Some(snapshot) = snapshot {
+                        if write.send(Message::Text(snapshot)).await.is_err() {
+                            return;
+                        }
+                    }
+                }
+                Some(Ok(Message::Ping(payload))) => {
+                    if write.send(Message::Pong(payload)).await.is_err() {
+                        return;
+                    }
+                }
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                Some(Ok(_)) => {}
+            },
+            frame = frames.recv() => match frame {
+                Ok(frame) => {
+                    let subscribed = frame_channel(&frame)
+                        .is_some_and(|channel| channels.contains(&channel));
+                    if subscribed && write.send(Message::Text(frame)).await.is_err() {
+                        return;
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(_)) => {}
+                Err(broadcast::error::RecvError::Closed) => return,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::TxClient;
+    use crate::signer::{KeyManager, PoseidonKeyManager};
+    use crate::test_support::TEST_KEY;
+
+    fn level(price: i64, size: i64) -> PriceLevel {
+        PriceLevel {
+            price: Decimal::new(price, 2),
+            size: Decimal::new(size, 4),
+        }
+    }
+
+    /// Book with a single ask and bid, prices in cents
+    fn book(bid: i64, ask: i64) -> OrderBook {
+        OrderBook {
+            asks: vec![level(ask + 100, 50_000), level(ask, 10_000)],
+            bids: vec![level(bid, 10_000), level(bid - 100, 50_000)],
+            offset: None,
+        }
+    }
+
+    fn exchange() -> SimulatedExchange {
+        SimulatedExchange::builder()
+            .market(0, 2, 4)
+            .account(1, Decimal::new(10_000, 0))
+            .build()
+            .unwrap()
+    }
+
+    fn tx_client(exchange: &SimulatedExchange) -> TxClient {
+        TxClient::builder()
+            .api_url("http://simulator")
+            .private_key(TEST_KEY)
+            .account_index(1)
+            .chain_id(304)
+            .transport(Arc::new(exchange.clone()))
+            .build()
+            .unwrap()
+    }
+
+    async fn limit(tx_client: &TxClient, price: u64, base_amount: i64, is_ask: u8) -> i64 {
+        let order = tx_client
+            .create_limit_order(0, 7, base_amount, price, is_ask, false, None)
+            .await
+            .unwrap();
+        assert_eq!(tx_client.send_transaction(&order).await.unwrap().code, 200);
+        order.nonce
+    }
+
+    #[tokio::test]
+    async fn test_market_order_fills_at_touch() {
+        let exchange = exchange();
+        exchange.update_order_book(0, &book(302400, 302500));
+        let tx_client = tx_client(&exchange);
+
+        let order = tx_client
+            .create_market_order(0, 1, 1000, 303000, 0, false, None)
+            .await
+            .unwrap();
+        let response = tx_client.send_transaction(&order).await.unwrap();
+        assert_eq!(response.code, 200);
+        assert_eq!(
+            response.tx_hash,
+            Some(hex::encode(order.hash(304).unwrap()))
+        );
+
+        let account = exchange.account(1).unwrap();
+        assert!(account.orders.is_empty());
+        assert_eq!(account.fills.len(), 1);
+        assert_eq!(account.fills[0].price, Decimal::new(302500, 2));
+        let position = &account.positions[&0];
+        assert_eq!(position.base_amount, 1000);
+        assert_eq!(position.entry_price, Decimal::new(302500, 2));
+        // Marked at mid 3024.50, 0.1 long from 3025.00
+        assert_eq!(exchange.unrealized_pnl(1), Decimal::new(-5, 2));
+    }
+
+    #[tokio::test]
+    async fn test_market_order_without_liquidity_is_cancelled() {
+        let exchange = exchange();
+        exchange.update_order_book(0, &book(302400, 302500));
+        let tx_client = tx_client(&exchange);
+
+        // Worst price below the ask
+        let order = tx_client
+            .create_market_order(0, 1, 1000, 302000, 0, false, None)
+            .await
+            .unwrap();
+        assert_eq!(tx_client.send_transaction(&order).await.unwrap().code, 200);
+
+        let account = exchange.account(1).unwrap();
+        assert!(account.orders.is_empty());
+        assert!(account.fills.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_resting_limit_fills_and_realizes_pnl() {
+        let exchange = exchange();
+        exchange.update_order_book(0, &book(302400, 302500));
+        let tx_client = tx_client(&exchange);
+
+        // Bid below the touch rests, then fills at its own price
+        limit(&tx_client, 300000, 1000, 0).await;
+        assert_eq!(exchange.account(1).unwrap().orders.len(), 1);
+        exchange.update_order_book(0, &book(299800, 299900));
+
+        let account = exchange.account(1).unwrap();
+        assert!(account.orders.is_empty());
+        assert_eq!(account.positions[&0].entry_price, Decimal::new(300000, 2));
+
+        // Sell it back higher
+        exchange.update_order_book(0, &book(310000, 310100));
+        let order = tx_client
+            .create_market_order(0, 2, 1000, 300000, 1, false, None)
+            .await
+            .unwrap();
+        tx_client.send_transaction(&order).await.unwrap();
+
+        let account = exchange.account(1).unwrap();
+        let position = &account.positions[&0];
+        assert_eq!(position.base_amount, 0);
+        // 0.1 * (3100.00 - 3000.00)
+        assert_eq!(position.realized_pnl, Decimal::new(10, 0));
+        assert_eq!(account.collateral, Decimal::new(10_010, 0));
+    }
+
+    #[tokio::test]
+    async fn test_averages_entry_and_flips() {
+        let exchange = exchange();
+        let tx_client = tx_client(&exchange);
+
+        exchange.update_order_book(0, &book(299900, 300000));
+        limit(&tx_client, 300000, 1000, 0).await;
+        exchange.update_order_book(0, &book(309900, 310000));
+        limit(&tx_client, 310000, 1000, 0).await;
+        let position = exchange.account(1).unwrap().positions[&0].clone();
+        assert_eq!(position.base_amount, 2000);
+        assert_eq!(position.entry_price, Decimal::new(305000, 2));
+
+        // Sell 0.3 at 3099: close 0.2, open 0.1 short
+        limit(&tx_client, 300000, 3000, 1).await;
+        let position = exchange.account(1).unwrap().positions[&0].clone();
+        assert_eq!(position.base_amount, -1000);
+        assert_eq!(position.entry_price, Decimal::new(309900, 2));
+        assert_eq!(position.realized_pnl, Decimal::new(980, 2));
+    }
+
+    #[tokio::test]
+    async fn test_reduce_only_never_grows_position() {
+        let exchange = exchange();
+        exchange.update_order_book(0, &book(302400, 302500));
+        let tx_client = tx_client(&exchange);
+
+        // Nothing to reduce
+        let order = tx_client
+            .create_market_order(0, 1, 1000, 300000, 1, true, None)
+            .await
+            .unwrap();
+        assert_eq!(tx_client.send_transaction(&order).await.unwrap().code, 200);
+        assert!(exchange.account(1).unwrap().positions.is_empty());
+
+        // Long 0.1, reduce-only sell of 0.3 only closes it
+        limit(&tx_client, 302500, 1000, 0).await;
+        let order = tx_client
+            .create_market_order(0, 2, 3000, 300000, 1, true, None)
+            .await
+            .unwrap();
+        tx_client.send_transaction(&order).await.unwrap();
+
+        let account = exchange.account(1).unwrap();
+        assert_eq!(account.positions[&0].base_amount, 0);
+        assert_eq!(account.fills.last().unwrap().base_amount, 1000);
+        assert!(account.orders.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_stop_loss_triggers_off_touch() {
+        let exchange = exchange();
+        exchange.update_order_book(0, &book(302400, 302500));
+        let tx_client = tx_client(&exchange);
+        limit(&tx_client, 302500, 1000, 0).await;
+
+        let req = CreateOrderTxReq {
+            market_index: 0,
+            client_order_index: 9,
+            base_amount: 1000,
+            price: 290000,
+            is_ask: 1,
+            order_type: ORDER_TYPE_STOP_LOSS,
+            time_in_force: TIME_IN_FORCE_IMMEDIATE_OR_CANCEL,
+            reduce_only: 1,
+            trigger_price: 300000,
+            order_expiry: 0,
+        };
+        let order = tx_client.create_order(&req, None).await.unwrap();
+        tx_client.send_transaction(&order).await.unwrap();
+        let account = exchange.account(1).unwrap();
+        assert_eq!(account.orders.len(), 1);
+        assert!(!account.orders[0].triggered);
+
+        exchange.update_order_book(0, &book(299500, 299600));
+        let account = exchange.account(1).unwrap();
+        assert!(account.orders.is_empty());
+        assert_eq!(account.positions[&0].base_amount, 0);
+        assert_eq!(account.fills.last().unwrap().price, Decimal::new(299500, 2));
+    }
+
+    #[tokio::test]
+    async fn test_post_only_that_would_cross_is_cancelled() {
+        let exchange = exchange();
+        exchange.update_order_book(0, &book(302400, 302500));
+        let tx_client = tx_client(&exchange);
+
+        let req = CreateOrderTxReq {
+            market_index: 0,
+            client_order_index: 3,
+            base_amount: 1000,
+            price: 302600,
+            is_ask: 0,
+            order_type: ORDER_TYPE_LIMIT,
+            time_in_force: TIME_IN_FORCE_POST_ONLY,
+            reduce_only: 0,
+            trigger_price: 0,
+            order_expiry: 0,
+        };
+        let order = tx_client.create_order(&req, None).await.unwrap();
+        tx_client.send_transaction(&order).await.unwrap();
+
+        let account = exchange.account(1).unwrap();
+        assert!(account.orders.is_empty());
+        assert!(account.fills.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_cancel_and_modify() {
+        let exchange = exchange();
+        exchange.update_order_book(0, &book(302400, 302500));
+        let tx_client = tx_client(&exchange);
+        limit(&tx_client, 300000, 1000, 0).await;
+        let order_index = exchange.account(1).unwrap().orders[0].order_index;
+
+        let modify = tx_client
+            .modify_order(
+                &ModifyOrderTxReq {
+                    market_index: 0,
+                    index: order_index,
+                    base_amount: 2000,
+                    price: 301000,
+                    trigger_price: 0,
+                },
+                None,
+            )
+            .await
+            .unwrap();
+        tx_client.send_transaction(&modify).await.unwrap();
+        let order = exchange.account(1).unwrap().orders[0].clone();
+        assert_eq!((order.price, order.remaining_base_amount), (301000, 2000));
+
+        let cancel = tx_client
+            .cancel_order(
+                &CancelOrderTxReq {
+                    market_index: 0,
+                    index: order_index,
+                },
+                None,
+            )
+            .await
+            .unwrap();
+        tx_client.send_transaction(&cancel).await.unwrap();
+        assert!(exchange.account(1).unwrap().orders.is_empty());
+
+        // Cancelling again finds nothing
+        let cancel = tx_client
+            .cancel_order(
+                &CancelOrderTxReq {
+                    market_index: 0,
+                    index: order_index,
+                },
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(tx_client.send_transaction(&cancel).await.unwrap().code, 400);
+    }
+
+    #[tokio::test]
+    async fn test_rejects_stale_nonce() {
+        let exchange = exchange();
+        let tx_client = tx_client(&exchange);
+        let opts = TransactOpts {
+            nonce: Some(5),
+            ..TransactOpts::default()
+        };
+        let order = tx_client
+            .create_limit_order(0, 1, 1000, 300000, 0, false, Some(opts))
+            .await
+            .unwrap();
+
+        let response = exchange
+            .execute(HttpRequest::post(
+                "http://simulator/api/v1/sendTx",
+                crate::client::HTTPClient::new("http://simulator")
+                    .unwrap()
+                    .send_tx_body(&order)
+                    .unwrap(),
+            ))
+            .await
+            .unwrap();
+        let response: Value = serde_json::from_str(&response.body).unwrap();
+        assert_eq!(response["code"], API_CODE_INVALID_NONCE);
+        assert_eq!(exchange.next_nonce(1, 0), 0);
+    }
+
+    #[tokio::test]
+    async fn test_checks_registered_keys() {
+        let key_manager = PoseidonKeyManager::from_hex(TEST_KEY).unwrap();
+        let exchange = SimulatedExchange::builder()
+            .market(0, 2, 4)
+            .api_key(1, 0, key_manager.pub_key())
+            .build()
+            .unwrap();
+        let tx_client = tx_client(&exchange);
+        let order = tx_client
+            .create_limit_order(0, 1, 1000, 300000, 0, false, None)
+            .await
+            .unwrap();
+        assert_eq!(tx_client.send_transaction(&order).await.unwrap().code, 200);
+
+        // Same key, unregistered index
+        let other_key = TxClient::builder()
+            .api_url("http://simulator")
+            .private_key(TEST_KEY)
+            .account_index(1)
+            .api_key_index(3)
+            .chain_id(304)
+            .transport(Arc::new(exchange.clone()))
+            .build()
+            .unwrap();
+        let order = other_key
+            .create_limit_order(0, 2, 1000, 300000, 0, false, None)
+            .await
+            .unwrap();
+        assert_eq!(
+            other_key.send_transaction(&order).await.unwrap().code,
+            API_CODE_API_KEY_NOT_FOUND
+        );
+
+        // Signed for another chain
+        let wrong_chain = TxClient::builder()
+            .api_url("http://simulator")
+            .private_key(TEST_KEY)
+            .account_index(1)
+            .chain_id(300)
+            .transport(Arc::new(exchange.clone()))
+            .build()
+            .unwrap();
+        let order = wrong_chain
+            .create_limit_order(0, 3, 1000, 300000, 0, false, None)
+            .await
+            .unwrap();
+        let response = wrong_chain.send_transaction(&order).await.unwrap();
+        assert_eq!(response.message.as_deref(), Some("Invalid signature"));
+    }
+
+    #[tokio::test]
+    async fn test_withdraw_and_transfer_move_collateral() {
+        let exchange = exchange();
+        let tx_client = tx_client(&exchange);
+
+        let withdraw = tx_client
+            .withdraw(
+                &WithdrawTxReq {
+                    usdc_amount: 1_500_000_000,
+                },
+                None,
+            )
+            .await
+            .unwrap();
+        tx_client.send_transaction(&withdraw).await.unwrap();
+        let transfer = tx_client
+            .transfer(
+                &TransferTxReq {
+                    to_account_index: 2,
+                    usdc_amount: 500_000_000,
+                    fee: 0,
+                    memo: [0; 32],
+                },
+                None,
+            )
+            .await
+            .unwrap();
+        tx_client.send_transaction(&transfer).await.unwrap();
+
+        assert_eq!(
+            exchange.account(1).unwrap().collateral,
+            Decimal::new(8_000, 0)
+        );
+        assert_eq!(
+            exchange.account(2).unwrap().collateral,
+            Decimal::new(500, 0)
+        );
+
+        let too_much = tx_client
+            .withdraw(
+                &WithdrawTxReq {
+                    usdc_amount: 9_000_000_000,
+                },
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            tx_client.send_transaction(&too_much).await.unwrap().code,
+            400
+        );
+    }
+
+    #[tokio::test]
+    async fn test_emits_book_diffs_and_account_fills() {
+        let exchange = exchange();
+        let mut frames = exchange.subscribe();
+        exchange.update_order_book(0, &book(302400, 302500));
+        exchange.update_order_book(0, &book(302400, 302600));
+
+        let snapshot: Value = serde_json::from_str(&frames.recv().await.unwrap()).unwrap();
+        assert_eq!(snapshot["type"], "subscribed/order_book");
+        let update = match WsFrame::decode(frames.recv().await.unwrap()).unwrap() {
+            WsFrame::OrderBookUpdate { market_id, update } => {
+                assert_eq!(market_id, "0");
+                update
+            }
+            other => panic!("expected an order book update, got {other:?}"),
+        };
+        let mut book = book(302400, 302500);
+        book.apply_update(&update);
+        assert_eq!(book.best_ask(), Some(Decimal::new(302600, 2)));
+        assert!(update.bids.is_empty());
+
+        let tx_client = tx_client(&exchange);
+        limit(&tx_client, 302600, 1000, 0).await;
+        let frame = WsFrame::decode(frames.recv().await.unwrap()).unwrap();
+        let WsFrame::Account { account_id, data } = frame else {
+            panic!("expected an account frame");
+        };
+        assert_eq!(account_id, "1");
+        assert_eq!(data["trades"][0]["price"], "3026.00");
+        assert_eq!(data["trades"][0]["size"], "0.1000");
+        assert_eq!(data["positions"][0]["sign"], 1);
+        assert_eq!(data["usdc_balance"], "10000");
+    }
+
+    #[tokio::test]
+    async fn test_replays_recorded_frames() {
+        let exchange = SimulatedExchange::builder()
+            .market(0, 2, 4)
+            .build()
+            .unwrap();
+        let frames = include_str!("../benches/fixtures/order_book_frames.jsonl");
+        for frame in frames.lines() {
+            exchange.apply_frame(frame.to_string()).unwrap();
+        }
+
+        let mut expected = OrderBook::default();
+        for frame in frames.lines() {
+            match WsFrame::decode(frame.to_string()).unwrap() {
+                WsFrame::OrderBookSnapshot {
+                    market_id,
+                    order_book,
+                } if market_id == "0" => expected = order_book,
+                WsFrame::OrderBookUpdate { market_id, update } if market_id == "0" => {
+                    expected.apply_update(&update)
+                }
+                _ => {}
+            }
+        }
+        assert_eq!(exchange.order_book(0), Some(expected));
+    }
+
+    #[tokio::test]
+    async fn test_serves_rest_and_websocket() {
+        let exchange = exchange();
+        exchange.update_order_book(0, &book(302400, 302500));
+        let server = exchange.serve("127.0.0.1:0").await.unwrap();
+
+        let ws_client = WsClient::builder()
+            .host(server.ws_host())
+            .order_books(vec![0])
+            .accounts(vec![1])
+            .build()
+            .unwrap();
+        let (books_tx, mut books) = tokio::sync::mpsc::unbounded_channel();
+        let (accounts_tx, mut accounts) = tokio::sync::mpsc::unbounded_channel();
+        let ws_task = tokio::spawn(async move {
+            ws_client
+                .run(
+                    move |market_id, order_book| {
+                        let _ = books_tx.send((market_id, order_book));
+                    },
+                    move |account_id, data| {
+                        let _ = accounts_tx.send((account_id, data));
+                    },
+                )
+                .await
+        });
+
+        let (market_id, order_book) = books.recv().await.unwrap();
+        assert_eq!(market_id, "0");
+        assert_eq!(order_book.best_ask(), Some(Decimal::new(302500, 2)));
+        let (_, snapshot) = accounts.recv().await.unwrap();
+        assert_eq!(snapshot["usdc_balance"], "10000");
+
+        let tx_client = TxClient::new(&server.url(), TEST_KEY, 1, 0, 304).unwrap();
+        let order = tx_client
+            .create_market_order(0, 1, 1000, 303000, 0, false, None)
+            .await
+            .unwrap();
+        assert_eq!(tx_client.send_transaction(&order).await.unwrap().code, 200);
+
+        let (account_id, update) = accounts.recv().await.unwrap();
+        assert_eq!(account_id, "1");
+        assert_eq!(update["trades"][0]["price"], "3025.00");
+        assert_eq!(update["positions"][0]["position"], "0.1000");
+
+        ws_task.abort();
+    }
+}
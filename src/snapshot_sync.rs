@@ -0,0 +1,380 @@
+//! Joining a REST snapshot with the WebSocket deltas around it
+//!
+//! Live state starts from a snapshot, but the stream keeps moving while the
+//! snapshot is fetched: deltas sent before it was taken are already in it and
+//! must not be applied again, while deltas sent after it must not be lost.
+//! [`SnapshotSync`] holds deltas back from [`SnapshotSync::begin_snapshot`]
+//! until [`SnapshotSync::complete_snapshot`], then hands back only the ones
+//! newer than the snapshot.
+//!
+//! Deltas are ordered by a key, either a [`SyncKey::Sequence`] number that
+//! increases by one per delta, so a skipped number is a detectable gap, or a
+//! [`SyncKey::Monotonic`] value such as a timestamp or stream offset.
+//! [`OrderTracker`](crate::tracker::OrderTracker),
+//! [`PositionManager`](crate::positions::PositionManager) and
+//! [`WsClient`](crate::ws_client::WsClient) order books all bootstrap this way.
+//!
+//! ```
+//! use lighter_rs::snapshot_sync::{Ingest, SnapshotSync, SyncKey};
+//!
+//! let mut sync = SnapshotSync::new(SyncKey::Sequence);
+//! sync.begin_snapshot();
+//!
+//! // Deltas arriving while the snapshot is in flight are held back
+//! assert!(matches!(sync.push(Some(41), "a"), Ingest::Buffered));
+//! assert!(matches!(sync.push(Some(42), "b"), Ingest::Buffered));
+//!
+//! // The snapshot already includes delta 41
+//! let replay = sync.complete_snapshot(41);
+//! assert_eq!(replay.events, vec!["b"]);
+//! assert_eq!(replay.stale, 1);
+//!
+//! assert!(matches!(sync.push(Some(43), "c"), Ingest::Apply("c")));
+//! ```
+
+#[cfg(feature = "native")]
+use serde_json::Value;
+#[cfg(feature = "native")]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// How delta keys relate to each other
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncKey {
+    /// Consecutive sequence numbers; a skipped number is a gap
+    Sequence,
+    /// Increasing values that may skip or repeat, such as timestamps
+    Monotonic,
+}
+
+/// Deltas were missed between `expected` and `received`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Gap {
+    pub expected: u64,
+    pub received: u64,
+}
+
+/// What to do with a delta passed to [`SnapshotSync::push`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Ingest<E> {
+    /// Held back until the snapshot completes
+    Buffered,
+    /// Newer than the state; apply it now
+    Apply(E),
+    /// Already covered by the snapshot or an earlier delta; drop it
+    Stale,
+    /// Deltas were missed. The delta was buffered and a new snapshot is needed
+    Gap(Gap),
+}
+
+/// Deltas to apply on top of a completed snapshot
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Replay<E> {
+    /// Buffered deltas newer than the snapshot, in arrival order
+    pub events: Vec<E>,
+    /// Buffered deltas dropped as already covered
+    pub stale: usize,
+    /// Set when deltas are missing after the snapshot. Deltas from the gap on
+    /// stay buffered for the next snapshot.
+    pub gap: Option<Gap>,
+}
+
+/// Buffers deltas while a snapshot is fetched and filters them against it
+///
+/// Starts live with no snapshot, passing every delta through. Deltas
+/// without a key can't be placed against a snapshot: they are applied when
+/// live and dropped as stale when buffered.
+#[derive(Debug, Clone)]
+pub struct SnapshotSync<E> {
+    key: SyncKey,
+    buffering: bool,
+    buffer: Vec<(Option<u64>, E)>,
+    /// Key of the latest snapshot
+    snapshot: Option<u64>,
+    /// Key of the latest delta applied
+    last: Option<u64>,
+}
+
+impl<E> SnapshotSync<E> {
+    pub fn new(key: SyncKey) -> Self {
+        Self {
+            key,
+            buffering: false,
+            buffer: Vec::new(),
+            snapshot: None,
+            last: None,
+        }
+    }
+
+    /// Whether deltas are being held back for a snapshot
+    pub fn is_buffering(&self) -> bool {
+        self.buffering
+    }
+
+    /// Start holding deltas back; call before requesting the snapshot
+    pub fn begin_snapshot(&mut self) {
+        self.buffering = true;
+    }
+
+    /// Offer one delta
+    pub fn push(&mut self, key: Option<u64>, event: E) -> Ingest<E> {
+        if self.buffering {
+            self.buffer.push((key, event));
+            return Ingest::Buffered;
+        }
+        let Some(key) = key else {
+            return Ingest::Apply(event);
+        };
+        if self.is_stale(key) {
+            return Ingest::Stale;
+        }
+        if let Some(gap) = self.gap_before(key) {
+            self.buffering = true;
+            self.buffer.push((Some(key), event));
+            return Ingest::Gap(gap);
+        }
+        self.last = Some(key);
+        Ingest::Apply(event)
+    }
+
+    /// Finish a snapshot taken at `key` and release the deltas newer than it
+    ///
+    /// For [`SyncKey::Monotonic`] keys, deltas at exactly `key` are taken to
+    /// be in the snapshot.
+    pub fn complete_snapshot(&mut self, key: u64) -> Replay<E> {
+        self.snapshot = Some(key);
+        self.last = Some(key);
+        self.buffering = false;
+
+        let mut replay = Replay {
+            events: Vec::new(),
+            stale: 0,
+            gap: None,
+        };
+        let mut buffer = std::mem::take(&mut self.buffer).into_iter();
+        for (delta_key, event) in buffer.by_ref() {
+            let Some(delta_key) = delta_key.filter(|&k| !self.is_stale(k)) else {
+                replay.stale += 1;
+                continue;
+            };
+            if let Some(gap) = self.gap_before(delta_key) {
+                replay.gap = Some(gap);
+                self.buffering = true;
+                self.buffer.push((Some(delta_key), event));
+                break;
+            }
+            self.last = Some(delta_key);
+            replay.events.push(event);
+        }
+        self.buffer.extend(buffer);
+        replay
+    }
+
+    /// Give up on a snapshot and release the buffered deltas as if they had
+    /// arrived live
+    pub fn cancel_snapshot(&mut self) -> Vec<E> {
+        self.buffering = false;
+        let mut events = Vec::new();
+        for (key, event) in std::mem::take(&mut self.buffer) {
+            if let Ingest::Apply(event) = self.push(key, event) {
+                events.push(event);
+            }
+        }
+        events
+    }
+
+    fn is_stale(&self, key: u64) -> bool {
+        match self.key {
+            SyncKey::Sequence => self.last.is_some_and(|last| key <= last),
+            SyncKey::Monotonic => {
+                self.snapshot.is_some_and(|snapshot| key <= snapshot)
+                    || self.last.is_some_and(|last| key < last)
+            }
+        }
+    }
+
+    fn gap_before(&self, key: u64) -> Option<Gap> {
+        let expected = self.last? + 1;
+        (self.key == SyncKey::Sequence && key > expected).then_some(Gap {
+            expected,
+            received: key,
+        })
+    }
+}
+
+/// Latest timestamp in an `account_all` frame
+///
+/// Reads the frame's own `timestamp`, or the latest of its orders' and
+/// trades' `timestamp` fields.
+#[cfg(feature = "native")]
+pub(crate) fn account_frame_timestamp(data: &Value) -> Option<u64> {
+    let entries = |field: &str| -> Vec<&Value> {
+        match data.get(field) {
+            Some(Value::Array(list)) => list.iter().collect(),
+            Some(Value::Object(groups)) => groups
+                .values()
+                .flat_map(|group| match group {
+                    Value::Array(list) => list.iter().collect(),
+                    entry => vec![entry],
+                })
+                .collect(),
+            _ => Vec::new(),
+        }
+    };
+    let timestamp = |value: &Value| match value.get("timestamp")? {
+        Value::Number(n) => n.as_u64(),
+        Value::String(s) => s.parse().ok(),
+        _ => None,
+    };
+
+    timestamp(data).or_else(|| {
+        entries("orders")
+            .into_iter()
+            .chain(entries("trades"))
+            .filter_map(timestamp)
+            .max()
+    })
+}
+
+/// Milliseconds since the Unix epoch, the key of a snapshot requested now
+#[cfg(feature = "native")]
+pub(crate) fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_older_than_snapshot_is_dropped() {
+        let mut sync = SnapshotSync::new(SyncKey::Sequence);
+        sync.begin_snapshot();
+        sync.push(Some(9), "old");
+        sync.push(Some(10), "included");
+
+        let replay = sync.complete_snapshot(10);
+        assert!(replay.events.is_empty());
+        assert_eq!(replay.stale, 2);
+        assert_eq!(replay.gap, None);
+
+        // A late duplicate after going live is dropped too
+        assert_eq!(sync.push(Some(10), "included"), Ingest::Stale);
+        assert_eq!(sync.push(Some(11), "next"), Ingest::Apply("next"));
+    }
+
+    #[test]
+    fn test_event_racing_snapshot() {
+        // Sent while the snapshot was being built: whether it lands depends
+        // on the snapshot's key, not on arrival order
+        let mut sync = SnapshotSync::new(SyncKey::Sequence);
+        sync.begin_snapshot();
+        sync.push(Some(21), "racing");
+        sync.push(Some(22), "after");
+        let replay = sync.complete_snapshot(20);
+        assert_eq!(replay.events, vec!["racing", "after"]);
+
+        let mut sync = SnapshotSync::new(SyncKey::Sequence);
+        sync.begin_snapshot();
+        sync.push(Some(21), "racing");
+        sync.push(Some(22), "after");
+        let replay = sync.complete_snapshot(21);
+        assert_eq!(replay.events, vec!["after"]);
+        assert_eq!(replay.stale, 1);
+
+        // Timestamps at the snapshot's own time are in the snapshot
+        let mut sync = SnapshotSync::new(SyncKey::Monotonic);
+        sync.begin_snapshot();
+        sync.push(Some(1_000), "racing");
+        sync.push(Some(1_001), "after");
+        assert_eq!(sync.complete_snapshot(1_000).events, vec!["after"]);
+        // ...including ones arriving after the snapshot completed
+        assert_eq!(sync.push(Some(1_000), "late"), Ingest::Stale);
+        assert_eq!(sync.push(Some(1_001), "same"), Ingest::Apply("same"));
+    }
+
+    #[test]
+    fn test_gap_between_snapshot_and_first_event() {
+        let mut sync = SnapshotSync::new(SyncKey::Sequence);
+        sync.begin_snapshot();
+        sync.push(Some(13), "a");
+        sync.push(Some(14), "b");
+
+        let replay = sync.complete_snapshot(10);
+        assert!(replay.events.is_empty());
+        assert_eq!(
+            replay.gap,
+            Some(Gap {
+                expected: 11,
+                received: 13
+            })
+        );
+        assert!(sync.is_buffering());
+
+        // A newer snapshot closes the gap and releases what is left
+        let replay = sync.complete_snapshot(13);
+        assert_eq!(replay.events, vec!["b"]);
+        assert_eq!(replay.gap, None);
+        assert!(!sync.is_buffering());
+    }
+
+    #[test]
+    fn test_gap_while_live_starts_buffering() {
+        let mut sync = SnapshotSync::new(SyncKey::Sequence);
+        sync.complete_snapshot(5);
+        assert_eq!(sync.push(Some(6), "a"), Ingest::Apply("a"));
+        assert_eq!(
+            sync.push(Some(8), "c"),
+            Ingest::Gap(Gap {
+                expected: 7,
+                received: 8
+            })
+        );
+        assert_eq!(sync.push(Some(9), "d"), Ingest::Buffered);
+        assert_eq!(sync.complete_snapshot(7).events, vec!["c", "d"]);
+
+        // Monotonic keys may skip
+        let mut sync = SnapshotSync::new(SyncKey::Monotonic);
+        sync.complete_snapshot(5);
+        assert_eq!(sync.push(Some(50), "a"), Ingest::Apply("a"));
+        assert_eq!(sync.push(Some(49), "old"), Ingest::Stale);
+    }
+
+    #[test]
+    fn test_keyless_events() {
+        let mut sync = SnapshotSync::new(SyncKey::Monotonic);
+        assert_eq!(sync.push(None, "live"), Ingest::Apply("live"));
+        sync.begin_snapshot();
+        sync.push(None, "unplaced");
+        let replay = sync.complete_snapshot(1);
+        assert!(replay.events.is_empty());
+        assert_eq!(replay.stale, 1);
+    }
+
+    #[test]
+    fn test_cancelled_snapshot_releases_buffer() {
+        let mut sync = SnapshotSync::new(SyncKey::Monotonic);
+        sync.complete_snapshot(10);
+        sync.begin_snapshot();
+        sync.push(Some(9), "old");
+        sync.push(None, "unplaced");
+        sync.push(Some(11), "new");
+        assert_eq!(sync.cancel_snapshot(), vec!["unplaced", "new"]);
+        assert!(!sync.is_buffering());
+    }
+
+    #[cfg(feature = "native")]
+    #[test]
+    fn test_account_frame_timestamp() {
+        use serde_json::json;
+
+        assert_eq!(account_frame_timestamp(&json!({"timestamp": 7})), Some(7));
+        let frame = json!({
+            "orders": {"0": [{"timestamp": 1_700}]},
+            "trades": [{"timestamp": "1900"}, {"timestamp": 1_800}],
+        });
+        assert_eq!(account_frame_timestamp(&frame), Some(1_900));
+        assert_eq!(account_frame_timestamp(&json!({"positions": {}})), None);
+    }
+}
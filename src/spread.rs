@@ -0,0 +1,547 @@
+//! Spreads between markets and external reference prices
+//!
+//! [`SpreadTracker`] keeps the latest mid of every leg it is fed, either a
+//! Lighter market's top of book or a reference price pushed in from
+//! elsewhere, and maintains a series of each configured spread. Legs tick at
+//! different times, so a spread is computed from the last known value of
+//! each leg, as long as neither is older than the staleness limit.
+//!
+//! Every update carries its own timestamp and "now" is the newest timestamp
+//! seen, so replaying a recorded book sequence gives the same series and
+//! events every time.
+//!
+//! ```
+//! use lighter_rs::spread::{Leg, SpreadKind, SpreadSpec, SpreadTracker};
+//! use lighter_rs::Decimal;
+//! use std::time::Duration;
+//!
+//! # fn example() -> lighter_rs::Result<()> {
+//! let tracker = SpreadTracker::new(Duration::from_secs(5), 1_000);
+//! tracker.add_spread(SpreadSpec {
+//!     thresholds: vec![Decimal::new(1002, 3)],
+//!     ..SpreadSpec::new("eth-basis", Leg::Market(0), Leg::reference("cex-eth"), SpreadKind::Ratio)
+//! })?;
+//!
+//! tracker.set_reference("cex-eth", Decimal::new(3000, 0), 1_000);
+//! tracker.on_top_of_book(0, Decimal::new(3005, 0), Decimal::new(3007, 0), 1_200);
+//! println!("basis {:?}", tracker.current_spread("eth-basis"));
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use tokio::sync::broadcast;
+
+use crate::errors::{LighterError, Result};
+use crate::ws_client::OrderBook;
+
+/// Events buffered per subscriber before slow ones start skipping
+const EVENT_BUFFER: usize = 64;
+
+/// One side of a spread
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Leg {
+    /// Mid of a Lighter market's top of book
+    Market(u8),
+    /// Price pushed in with [`SpreadTracker::set_reference`]
+    Reference(String),
+}
+
+impl Leg {
+    pub fn reference(name: impl Into<String>) -> Self {
+        Leg::Reference(name.into())
+    }
+}
+
+/// How the two legs are combined
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpreadKind {
+    /// `base - quote`
+    Difference,
+    /// `base / quote`
+    Ratio,
+}
+
+/// A spread to track
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpreadSpec {
+    pub name: String,
+    pub base: Leg,
+    pub quote: Leg,
+    pub kind: SpreadKind,
+    /// Weight of each new value in an exponential moving average, in (0, 1];
+    /// `None` keeps the raw values
+    pub smoothing: Option<Decimal>,
+    /// Levels whose crossing by the (smoothed) spread is reported as a
+    /// [`SpreadEvent::Crossed`]
+    pub thresholds: Vec<Decimal>,
+}
+
+impl SpreadSpec {
+    /// Unsmoothed spread without thresholds
+    pub fn new(name: impl Into<String>, base: Leg, quote: Leg, kind: SpreadKind) -> Self {
+        Self {
+            name: name.into(),
+            base,
+            quote,
+            kind,
+            smoothing: None,
+            thresholds: Vec::new(),
+        }
+    }
+}
+
+/// One value of a spread's series
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpreadSample {
+    /// Timestamp of the update that produced it, in milliseconds
+    pub at_ms: i64,
+    /// Spread from the legs' last known values
+    pub raw: Decimal,
+    /// Raw value after smoothing
+    pub value: Decimal,
+}
+
+/// Direction of a threshold crossing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Crossing {
+    Above,
+    Below,
+}
+
+/// Something a spread trader wants to hear about
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SpreadEvent {
+    /// The spread moved through one of its thresholds
+    Crossed {
+        spread: String,
+        threshold: Decimal,
+        direction: Crossing,
+        value: Decimal,
+        at_ms: i64,
+    },
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Quote {
+    price: Decimal,
+    at_ms: i64,
+}
+
+struct Series {
+    spec: SpreadSpec,
+    samples: VecDeque<SpreadSample>,
+}
+
+#[derive(Default)]
+struct State {
+    legs: HashMap<Leg, Quote>,
+    series: Vec<Series>,
+    /// Newest update timestamp seen
+    now_ms: i64,
+}
+
+impl State {
+    fn is_fresh(&self, leg: &Leg, max_staleness_ms: i64) -> Option<Decimal> {
+        let quote = self.legs.get(leg)?;
+        (self.now_ms - quote.at_ms <= max_staleness_ms).then_some(quote.price)
+    }
+}
+
+struct Inner {
+    max_staleness_ms: i64,
+    history: usize,
+    state: Mutex<State>,
+    events: broadcast::Sender<SpreadEvent>,
+}
+
+/// Spreads between markets and reference prices, fed top-of-book updates
+///
+/// Cloning is cheap and every clone shares the same state.
+#[derive(Clone)]
+pub struct SpreadTracker {
+    inner: Arc<Inner>,
+}
+
+impl SpreadTracker {
+    /// Track spreads whose legs may be at most `max_staleness` old, keeping
+    /// the latest `history` samples of each
+    pub fn new(max_staleness: Duration, history: usize) -> Self {
+        let (events, _) = broadcast::channel(EVENT_BUFFER);
+        Self {
+            inner: Arc::new(Inner {
+                max_staleness_ms: i64::try_from(max_staleness.as_millis()).unwrap_or(i64::MAX),
+                history: history.max(1),
+                state: Mutex::new(State::default()),
+                events,
+            }),
+        }
+    }
+
+    /// Start tracking a spread
+    pub fn add_spread(&self, spec: SpreadSpec) -> Result<()> {
+        if let Some(alpha) = spec.smoothing {
+            if alpha <= Decimal::ZERO || alpha > Decimal::ONE {
+                return Err(LighterError::ValidationError(format!(
+                    "Smoothing {alpha} is outside (0, 1]"
+                )));
+            }
+        }
+        let mut state = self.lock();
+        if state
+            .series
+            .iter()
+            .any(|series| series.spec.name == spec.name)
+        {
+            return Err(LighterError::ValidationError(format!(
+                "Spread {} is already tracked",
+                spec.name
+            )));
+        }
+        state.series.push(Series {
+            spec,
+            samples: VecDeque::new(),
+        });
+        Ok(())
+    }
+
+    /// Receive threshold crossings
+    pub fn subscribe(&self) -> broadcast::Receiver<SpreadEvent> {
+        self.inner.events.subscribe()
+    }
+
+    /// Update a market's leg with its best bid and ask at `at_ms`
+    pub fn on_top_of_book(
+        &self,
+        market_index: u8,
+        bid: Decimal,
+        ask: Decimal,
+        at_ms: i64,
+    ) -> Vec<SpreadEvent> {
+        self.update(Leg::Market(market_index), (bid + ask) / Decimal::TWO, at_ms)
+    }
+
+    /// Update a market's leg with its order book mid at `at_ms`
+    pub fn on_order_book(
+        &self,
+        market_index: u8,
+        order_book: &OrderBook,
+        at_ms: i64,
+    ) -> Vec<SpreadEvent> {
+        match order_book.mid_price() {
+            Some(mid) => self.update(Leg::Market(market_index), mid, at_ms),
+            None => Vec::new(),
+        }
+    }
+
+    /// Update an external reference price at `at_ms`
+    pub fn set_reference(&self, name: &str, price: Decimal, at_ms: i64) -> Vec<SpreadEvent> {
+        self.update(Leg::reference(name), price, at_ms)
+    }
+
+    /// Latest value of a spread, or `None` if a leg is missing or stale
+    pub fn current_spread(&self, name: &str) -> Option<Decimal> {
+        let state = self.lock();
+        let series = state
+            .series
+            .iter()
+            .find(|series| series.spec.name == name)?;
+        state.is_fresh(&series.spec.base, self.inner.max_staleness_ms)?;
+        state.is_fresh(&series.spec.quote, self.inner.max_staleness_ms)?;
+        series.samples.back().map(|sample| sample.value)
+    }
+
+    /// Z-score of the latest value against the last `window` values
+    ///
+    /// `None` with fewer than two values or no variation.
+    pub fn zscore(&self, name: &str, window: usize) -> Option<f64> {
+        let state = self.lock();
+        let series = state
+            .series
+            .iter()
+            .find(|series| series.spec.name == name)?;
+        let values: Vec<f64> = series
+            .samples
+            .iter()
+            .rev()
+            .take(window)
+            .filter_map(|sample| sample.value.to_f64())
+            .collect();
+        if values.len() < 2 {
+            return None;
+        }
+        let n = values.len() as f64;
+        let mean = values.iter().sum::<f64>() / n;
+        let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n;
+        let std_dev = variance.sqrt();
+        (std_dev > 0.0).then(|| (values[0] - mean) / std_dev)
+    }
+
+    /// Samples of a spread, oldest first
+    pub fn series(&self, name: &str) -> Vec<SpreadSample> {
+        self.lock()
+            .series
+            .iter()
+            .find(|series| series.spec.name == name)
+            .map(|series| series.samples.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    fn update(&self, leg: Leg, price: Decimal, at_ms: i64) -> Vec<SpreadEvent> {
+        let mut events = Vec::new();
+        {
+            let mut state = self.lock();
+            state.now_ms = state.now_ms.max(at_ms);
+            state.legs.insert(leg.clone(), Quote { price, at_ms });
+
+            let max_staleness_ms = self.inner.max_staleness_ms;
+            let State {
+                legs,
+                series,
+                now_ms,
+            } = &mut *state;
+            let fresh = |leg: &Leg| {
+                let quote = legs.get(leg)?;
+                (*now_ms - quote.at_ms <= max_staleness_ms).then_some(quote.price)
+            };
+            for series in series
+                .iter_mut()
+                .filter(|series| series.spec.base == leg || series.spec.quote == leg)
+            {
+                let (Some(base), Some(quote)) =
+                    (fresh(&series.spec.base), fresh(&series.spec.quote))
+                else {
+                    continue;
+                };
+                let raw = match series.spec.kind {
+                    SpreadKind::Difference => base - quote,
+                    SpreadKind::Ratio if quote.is_zero() => continue,
+                    SpreadKind::Ratio => base / quote,
+                };
+                let previous = series.samples.back().map(|sample| sample.value);
+                let value = match (series.spec.smoothing, previous) {
+                    (Some(alpha), Some(previous)) => previous + alpha * (raw - previous),
+                    _ => raw,
+                };
+
+                if let Some(previous) = previous {
+                    for &threshold in &series.spec.thresholds {
+                        let direction = if previous < threshold && value >= threshold {
+                            Crossing::Above
+                        } else if previous > threshold && value <= threshold {
+                            Crossing::Below
+                        } else {
+                            continue;
+                        };
+                        events.push(SpreadEvent::Crossed {
+                            spread: series.spec.name.clone(),
+                            threshold,
+                            direction,
+                            value,
+                            at_ms,
+                        });
+                    }
+                }
+
+                if series.samples.len() == self.inner.history {
+                    series.samples.pop_front();
+                }
+                series.samples.push_back(SpreadSample { at_ms, raw, value });
+            }
+        }
+
+        for event in &events {
+            // No subscribers is fine
+            let _ = self.inner.events.send(event.clone());
+        }
+        events
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.inner
+            .state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dec(value: &str) -> Decimal {
+        value.parse().unwrap()
+    }
+
+    /// Recorded (market, bid, ask, at_ms) ticks of two markets
+    const TICKS: [(u8, &str, &str, i64); 6] = [
+        (0, "3000", "3002", 1_000),
+        (1, "1499", "1501", 1_100),
+        (0, "3004", "3006", 1_500),
+        (0, "3010", "3012", 2_000),
+        (1, "1500", "1502", 2_100),
+        (1, "1510", "1512", 2_600),
+    ];
+
+    fn replay(tracker: &SpreadTracker) -> Vec<SpreadEvent> {
+        TICKS
+            .iter()
+            .flat_map(|&(market, bid, ask, at_ms)| {
+                tracker.on_top_of_book(market, dec(bid), dec(ask), at_ms)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_difference_uses_last_known_values() {
+        let tracker = SpreadTracker::new(Duration::from_secs(10), 100);
+        tracker
+            .add_spread(SpreadSpec::new(
+                "diff",
+                Leg::Market(0),
+                Leg::Market(1),
+                SpreadKind::Difference,
+            ))
+            .unwrap();
+        replay(&tracker);
+
+        // No value until both legs have ticked
+        let values: Vec<(i64, Decimal)> = tracker
+            .series("diff")
+            .iter()
+            .map(|sample| (sample.at_ms, sample.value))
+            .collect();
+        assert_eq!(
+            values,
+            vec![
+                (1_100, dec("1501")),
+                (1_500, dec("1505")),
+                (2_000, dec("1511")),
+                (2_100, dec("1510")),
+                (2_600, dec("1500")),
+            ]
+        );
+        assert_eq!(tracker.current_spread("diff"), Some(dec("1500")));
+        assert_eq!(tracker.current_spread("missing"), None);
+    }
+
+    #[test]
+    fn test_stale_leg_pauses_series() {
+        let tracker = SpreadTracker::new(Duration::from_millis(500), 100);
+        tracker
+            .add_spread(SpreadSpec::new(
+                "ratio",
+                Leg::Market(0),
+                Leg::reference("cex"),
+                SpreadKind::Ratio,
+            ))
+            .unwrap();
+        tracker.set_reference("cex", dec("1500"), 1_000);
+        tracker.on_top_of_book(0, dec("2999"), dec("3001"), 1_200);
+        assert_eq!(tracker.current_spread("ratio"), Some(dec("2")));
+
+        // The reference is 700ms old by now
+        tracker.on_top_of_book(0, dec("3014"), dec("3016"), 1_700);
+        assert_eq!(tracker.series("ratio").len(), 1);
+        assert_eq!(tracker.current_spread("ratio"), None);
+
+        tracker.set_reference("cex", dec("1507.5"), 1_800);
+        assert_eq!(tracker.current_spread("ratio"), Some(dec("2")));
+        assert_eq!(tracker.series("ratio").len(), 2);
+    }
+
+    #[test]
+    fn test_smoothing_and_threshold_events() {
+        let tracker = SpreadTracker::new(Duration::from_secs(10), 100);
+        let mut events = tracker.subscribe();
+        tracker
+            .add_spread(SpreadSpec {
+                smoothing: Some(dec("0.5")),
+                thresholds: vec![dec("1508")],
+                ..SpreadSpec::new(
+                    "diff",
+                    Leg::Market(0),
+                    Leg::Market(1),
+                    SpreadKind::Difference,
+                )
+            })
+            .unwrap();
+
+        let crossings = replay(&tracker);
+
+        // 1501, then halfway to each new raw value
+        let values: Vec<Decimal> = tracker.series("diff").iter().map(|s| s.value).collect();
+        assert_eq!(
+            values,
+            vec![
+                dec("1501"),
+                dec("1503"),
+                dec("1507"),
+                dec("1508.5"),
+                dec("1504.25")
+            ]
+        );
+        let expected = vec![
+            SpreadEvent::Crossed {
+                spread: "diff".to_string(),
+                threshold: dec("1508"),
+                direction: Crossing::Above,
+                value: dec("1508.5"),
+                at_ms: 2_100,
+            },
+            SpreadEvent::Crossed {
+                spread: "diff".to_string(),
+                threshold: dec("1508"),
+                direction: Crossing::Below,
+                value: dec("1504.25"),
+                at_ms: 2_600,
+            },
+        ];
+        assert_eq!(crossings, expected);
+        assert_eq!(events.try_recv().unwrap(), expected[0]);
+        assert_eq!(events.try_recv().unwrap(), expected[1]);
+    }
+
+    #[test]
+    fn test_zscore_and_history_limit() {
+        let tracker = SpreadTracker::new(Duration::from_secs(10), 4);
+        tracker
+            .add_spread(SpreadSpec::new(
+                "diff",
+                Leg::Market(0),
+                Leg::Market(1),
+                SpreadKind::Difference,
+            ))
+            .unwrap();
+        replay(&tracker);
+
+        // Only the last four values are kept: 1505, 1511, 1510, 1500
+        assert_eq!(tracker.series("diff").len(), 4);
+        let z = tracker.zscore("diff", 4).unwrap();
+        // mean 1506.5, population std dev sqrt(19.25)
+        assert!((z - (-6.5 / 19.25f64.sqrt())).abs() < 1e-9);
+        // Last two: 1510 and 1500
+        assert!((tracker.zscore("diff", 2).unwrap() + 1.0).abs() < 1e-9);
+        assert_eq!(tracker.zscore("diff", 1), None);
+    }
+
+    #[test]
+    fn test_rejects_bad_specs() {
+        let tracker = SpreadTracker::new(Duration::from_secs(1), 10);
+        let spec = SpreadSpec::new("a", Leg::Market(0), Leg::Market(1), SpreadKind::Ratio);
+        tracker.add_spread(spec.clone()).unwrap();
+        assert!(tracker.add_spread(spec.clone()).is_err());
+        assert!(tracker
+            .add_spread(SpreadSpec {
+                name: "b".to_string(),
+                smoothing: Some(Decimal::ZERO),
+                ..spec
+            })
+            .is_err());
+    }
+}
@@ -0,0 +1,349 @@
+//! Persisting client state across restarts
+//!
+//! A restarted bot can pick up where it left off instead of starting cold:
+//! the nonce cache, tracked orders, positions and trailing stop high-water
+//! marks each have a `save_state` method writing them to a [`StateStore`]
+//! and a `restore_and_reconcile` method reading them back.
+//!
+//! Restored state is never trusted on its own. Every `restore_and_reconcile`
+//! checks it against the REST API first and keeps only what the exchange
+//! confirms; a restored nonce that disagrees with the server's is replaced by
+//! the server's. A missing, unreadable or corrupt blob is logged and treated
+//! as no saved state, so the component starts cold.
+//!
+//! ```no_run
+//! use lighter_rs::client::TxClient;
+//! use lighter_rs::state_store::FileStateStore;
+//! use lighter_rs::tracker::OrderTracker;
+//! use std::sync::Arc;
+//!
+//! # async fn example(tx_client: TxClient) -> lighter_rs::Result<()> {
+//! let store = FileStateStore::new("./bot-state")?;
+//! let tx_client = Arc::new(tx_client);
+//! tx_client.restore_and_reconcile(&store).await?;
+//! let tracker = OrderTracker::new(tx_client.clone());
+//! let report = tracker.restore_and_reconcile(&store, None).await?;
+//! println!("kept {} orders, dropped {}", report.kept, report.discarded);
+//!
+//! // ... trade ...
+//!
+//! tx_client.save_state(&store)?;
+//! tracker.save_state(&store)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::client::TxClient;
+use crate::errors::{LighterError, Result};
+
+/// Format version written with every blob; blobs of another version are
+/// ignored
+pub const STATE_VERSION: u32 = 1;
+
+/// Named blobs of saved state
+pub trait StateStore: Send + Sync {
+    /// Store `blob` under `name`, replacing any earlier one
+    fn save(&self, name: &str, blob: &[u8]) -> Result<()>;
+
+    /// The blob stored under `name`, or `None` if there is none
+    fn load(&self, name: &str) -> Result<Option<Vec<u8>>>;
+}
+
+/// One file per blob in a directory
+///
+/// Blobs are written to a temporary file and renamed into place, so a crash
+/// mid-write leaves the previous blob intact.
+#[derive(Debug, Clone)]
+pub struct FileStateStore {
+    dir: PathBuf,
+}
+
+impl FileStateStore {
+    /// Store blobs in `dir`, creating it if needed
+    pub fn new(dir: impl Into<PathBuf>) -> Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir).map_err(|e| io_error(&dir, e))?;
+        Ok(Self { dir })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// File holding the blob `name`
+    ///
+    /// Names are limited to ASCII letters, digits, `-` and `_`, so they
+    /// can't point outside the directory.
+    pub fn path(&self, name: &str) -> Result<PathBuf> {
+        let valid = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            return Err(LighterError::ValidationError(format!(
+                "Invalid state name {name:?}"
+            )));
+        }
+        Ok(self.dir.join(format!("{name}.json")))
+    }
+}
+
+impl StateStore for FileStateStore {
+    fn save(&self, name: &str, blob: &[u8]) -> Result<()> {
+        let path = self.path(name)?;
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, blob).map_err(|e| io_error(&tmp, e))?;
+        std::fs::rename(&tmp, &path).map_err(|e| io_error(&path, e))
+    }
+
+    fn load(&self, name: &str) -> Result<Option<Vec<u8>>> {
+        let path = self.path(name)?;
+        match std::fs::read(&path) {
+            Ok(blob) => Ok(Some(blob)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(io_error(&path, e)),
+        }
+    }
+}
+
+fn io_error(path: &Path, e: std::io::Error) -> LighterError {
+    LighterError::Other(format!("State store I/O on {}: {e}", path.display()))
+}
+
+/// Blobs kept in memory, for tests and short-lived processes
+#[derive(Debug, Default)]
+pub struct MemoryStateStore {
+    blobs: Mutex<HashMap<String, Vec<u8>>>,
+}
+
+impl MemoryStateStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Vec<u8>>> {
+        self.blobs
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl StateStore for MemoryStateStore {
+    fn save(&self, name: &str, blob: &[u8]) -> Result<()> {
+        self.lock().insert(name.to_string(), blob.to_vec());
+        Ok(())
+    }
+
+    fn load(&self, name: &str) -> Result<Option<Vec<u8>>> {
+        Ok(self.lock().get(name).cloned())
+    }
+}
+
+/// What a `restore_and_reconcile` kept after checking against the exchange
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RestoreReport {
+    /// Whether usable saved state was found; `false` means a cold start
+    pub found: bool,
+    /// Restored entries the exchange confirmed
+    pub kept: usize,
+    /// Restored entries dropped or replaced by the exchange's view
+    pub discarded: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Envelope<T> {
+    version: u32,
+    /// Millisecond timestamp of the save
+    saved_at: i64,
+    state: T,
+}
+
+/// Serialize `state` as JSON and store it under `name`
+pub fn save_json<T: Serialize>(store: &dyn StateStore, name: &str, state: &T) -> Result<()> {
+    let envelope = Envelope {
+        version: STATE_VERSION,
+        saved_at: chrono::Utc::now().timestamp_millis(),
+        state,
+    };
+    store.save(name, &serde_json::to_vec(&envelope)?)
+}
+
+/// Load the state stored under `name` by [`save_json`]
+///
+/// Fails safe: a missing blob, a store error, a corrupt blob or one of
+/// another [`STATE_VERSION`] all give `None`, logging a warning for
+/// everything but the first.
+pub fn load_json<T: DeserializeOwned>(store: &dyn StateStore, name: &str) -> Option<T> {
+    let blob = match store.load(name) {
+        Ok(blob) => blob?,
+        Err(e) => {
+            tracing::warn!(name, error = %e, "Can't read saved state; starting cold");
+            return None;
+        }
+    };
+    match serde_json::from_slice::<Envelope<T>>(&blob) {
+        Ok(envelope) if envelope.version == STATE_VERSION => Some(envelope.state),
+        Ok(envelope) => {
+            tracing::warn!(
+                name,
+                version = envelope.version,
+                "Saved state has an unsupported version; starting cold"
+            );
+            None
+        }
+        Err(e) => {
+            tracing::warn!(name, error = %e, "Saved state is corrupt; starting cold");
+            None
+        }
+    }
+}
+
+const NONCES: &str = "nonces";
+
+impl TxClient {
+    /// Save the nonce cache to `store`
+    pub fn save_state(&self, store: &dyn StateStore) -> Result<()> {
+        save_json(store, NONCES, &self.nonces().export())
+    }
+
+    /// Restore the nonce cache from `store`, checking every nonce against the
+    /// API
+    ///
+    /// A restored nonce is kept only if it matches the server's next nonce;
+    /// otherwise the server's replaces it.
+    pub async fn restore_and_reconcile(&self, store: &dyn StateStore) -> Result<RestoreReport> {
+        let Some(snapshots) = load_json::<Vec<crate::nonce::NonceSnapshot>>(store, NONCES) else {
+            return Ok(RestoreReport::default());
+        };
+        let http = self.http_client()?;
+
+        let mut report = RestoreReport {
+            found: true,
+            ..RestoreReport::default()
+        };
+        for snapshot in snapshots {
+            let server = http
+                .get_next_nonce(snapshot.account_index, snapshot.api_key_index)
+                .await?;
+            if server == snapshot.next_nonce {
+                report.kept += 1;
+            } else {
+                tracing::warn!(
+                    account_index = snapshot.account_index,
+                    api_key_index = snapshot.api_key_index,
+                    restored = snapshot.next_nonce,
+                    server,
+                    "Restored nonce is stale; using the server's"
+                );
+                report.discarded += 1;
+            }
+            self.nonces()
+                .set(snapshot.account_index, snapshot.api_key_index, server);
+        }
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::mock_builder;
+    use crate::transport::{HttpResponse, MockTransport};
+    use std::sync::Arc;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("lighter-rs-state-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn test_file_store_round_trip_and_corruption() {
+        let dir = temp_dir("file");
+        let store = FileStateStore::new(&dir).unwrap();
+        assert_eq!(load_json::<Vec<i64>>(&store, "orders"), None);
+
+        save_json(&store, "orders", &vec![1i64, 2]).unwrap();
+        assert_eq!(load_json::<Vec<i64>>(&store, "orders"), Some(vec![1, 2]));
+        assert!(!dir.join("orders.json.tmp").exists());
+
+        // Truncated, wrong shape and future versions all start cold
+        std::fs::write(
+            dir.join("orders.json"),
+            br#"{"version":1,"saved_at":0,"sta"#,
+        )
+        .unwrap();
+        assert_eq!(load_json::<Vec<i64>>(&store, "orders"), None);
+        std::fs::write(
+            dir.join("orders.json"),
+            br#"{"version":1,"saved_at":0,"state":"x"}"#,
+        )
+        .unwrap();
+        assert_eq!(load_json::<Vec<i64>>(&store, "orders"), None);
+        std::fs::write(
+            dir.join("orders.json"),
+            br#"{"version":9,"saved_at":0,"state":[1]}"#,
+        )
+        .unwrap();
+        assert_eq!(load_json::<Vec<i64>>(&store, "orders"), None);
+
+        assert!(store.path("../escape").is_err());
+        assert!(store.save("", b"{}").is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_stale_restored_nonce_is_replaced() {
+        let mock = Arc::new(MockTransport::new());
+        mock.set_handler("/api/v1/nextNonce", |request| {
+            let nonce = if request.url.contains("api_key_index=0") {
+                12
+            } else {
+                30
+            };
+            Ok(HttpResponse::new(
+                200,
+                format!(r#"{{"code":200,"nonce":{nonce}}}"#),
+            ))
+        });
+        let tx_client = mock_builder(&mock).build().unwrap();
+
+        let store = MemoryStateStore::new();
+        assert_eq!(
+            tx_client.restore_and_reconcile(&store).await.unwrap(),
+            RestoreReport::default()
+        );
+        assert!(mock.requests().is_empty());
+
+        tx_client.nonces().set(1, 0, 12);
+        tx_client.nonces().set(1, 1, 25);
+        tx_client.save_state(&store).unwrap();
+        tx_client.nonces().invalidate_all();
+
+        let report = tx_client.restore_and_reconcile(&store).await.unwrap();
+        assert_eq!(
+            report,
+            RestoreReport {
+                found: true,
+                kept: 1,
+                discarded: 1
+            }
+        );
+        assert_eq!(tx_client.nonces().peek(1, 0), Some(12));
+        assert_eq!(tx_client.nonces().peek(1, 1), Some(30));
+
+        // A corrupt blob is a cold start, not an error
+        store.save(NONCES, b"not json").unwrap();
+        tx_client.nonces().invalidate_all();
+        assert!(!tx_client.restore_and_reconcile(&store).await.unwrap().found);
+        assert_eq!(tx_client.nonces().peek(1, 0), None);
+    }
+}
@@ -0,0 +1,116 @@
+//! Attributing orders, log lines and metrics to a strategy
+//!
+//! Strategies sharing an account tag what they sign with
+//! [`TransactOpts::strategy_id`](crate::types::TransactOpts::strategy_id).
+//! The id stays in the process and is never part of the signed transaction.
+//! It follows the transaction to:
+//!
+//! - the `submission` tracing span its send runs in, as the `strategy_id`
+//!   field, so every log line of the send carries it
+//! - the [`LatencyBreakdown::strategy_id`](crate::latency::LatencyBreakdown::strategy_id)
+//!   handed to the latency hook
+//! - the `strategy_id` of the [`TrackedOrder`](crate::tracker::TrackedOrder)
+//!   when the order is submitted through an order tracker, which is saved
+//!   and restored with the tracker's state
+//!
+//! A batch holding transactions of several strategies lists them all in its
+//! span, comma separated.
+//!
+//! For attribution that survives restarts even for orders a previous run
+//! left resting, give each strategy its own client order indexes with
+//! [`ClientOrderNamespace::for_strategy`](crate::order_namespace::ClientOrderNamespace::for_strategy).
+//!
+//! ```no_run
+//! use lighter_rs::client::TxClient;
+//! use lighter_rs::order_namespace::ClientOrderNamespace;
+//! use lighter_rs::types::TransactOpts;
+//!
+//! # async fn example() -> lighter_rs::Result<()> {
+//! let tx_client = TxClient::builder()
+//!     .api_url("https://mainnet.zklighter.elliot.ai")
+//!     .private_key("0x0102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f202122232425262728")
+//!     .client_order_namespace(ClientOrderNamespace::for_strategy("market-maker", 8)?)
+//!     .latency_hook(|latency| {
+//!         println!("{:?} took {:?}", latency.strategy_id, latency.total);
+//!     })
+//!     .build()?;
+//! let opts = TransactOpts {
+//!     strategy_id: Some("market-maker".to_string()),
+//!     ..Default::default()
+//! };
+//! let order = tx_client
+//!     .create_limit_order(0, tx_client.next_client_order_index(), 1000, 300000, 0, false, Some(opts))
+//!     .await?;
+//! tx_client.send_transaction(&order).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+/// Signed transactions whose strategy is kept for their send
+const MAX_PENDING: usize = 1024;
+
+/// Strategies of signed transactions not sent yet, by tx hash
+#[derive(Default)]
+pub(crate) struct StrategyLabels {
+    /// Oldest first
+    pending: Mutex<VecDeque<(String, Arc<str>)>>,
+}
+
+impl StrategyLabels {
+    /// Remember the strategy a transaction was signed for
+    pub fn signed(&self, tx_hash: Option<String>, strategy_id: &str) {
+        let Some(tx_hash) = tx_hash else {
+            return;
+        };
+        let mut pending = self.lock();
+        if pending.len() >= MAX_PENDING {
+            pending.pop_front();
+        }
+        pending.push_back((tx_hash, Arc::from(strategy_id)));
+    }
+
+    /// Strategy of a signed transaction, kept for its send
+    #[cfg(feature = "native")]
+    pub fn get(&self, tx_hash: &str) -> Option<Arc<str>> {
+        self.lock()
+            .iter()
+            .rfind(|(hash, _)| hash == tx_hash)
+            .map(|(_, strategy_id)| strategy_id.clone())
+    }
+
+    /// Strategy of a transaction about to be sent, forgotten from now on
+    pub fn take(&self, tx_hash: Option<&str>) -> Option<Arc<str>> {
+        let tx_hash = tx_hash?;
+        let mut pending = self.lock();
+        if pending.is_empty() {
+            return None;
+        }
+        let i = pending.iter().rposition(|(hash, _)| hash == tx_hash)?;
+        pending.remove(i).map(|(_, strategy_id)| strategy_id)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, VecDeque<(String, Arc<str>)>> {
+        self.pending
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Span a submission of `tx_count` transactions runs in
+pub(crate) fn submission_span(strategy_id: Option<&str>, tx_count: usize) -> tracing::Span {
+    tracing::info_span!("submission", strategy_id, tx_count)
+}
+
+/// Distinct strategies of a batch, comma separated in order of appearance
+pub(crate) fn batch_strategies(strategies: &[Option<Arc<str>>]) -> Option<String> {
+    let mut distinct: Vec<&str> = Vec::new();
+    for strategy_id in strategies.iter().flatten() {
+        if !distinct.contains(&strategy_id.as_ref()) {
+            distinct.push(strategy_id);
+        }
+    }
+    (!distinct.is_empty()).then(|| distinct.join(","))
+}
@@ -0,0 +1,274 @@
+//! Transfers from an account to its own sub-accounts
+//!
+//! A transfer can't be taken back, so [`TxClient::transfer_to_sub_account`]
+//! first looks up the accounts of the L1 address owning the client's
+//! account and refuses any destination outside them with
+//! [`LighterError::NotASubAccount`], unless `allow_external` is set.
+//! Funding several sub-accounts signs one transfer each with consecutive
+//! nonces, sent together in one batch.
+//!
+//! ```no_run
+//! use lighter_rs::client::TxClient;
+//!
+//! # async fn example(tx_client: TxClient) -> lighter_rs::Result<()> {
+//! // 100 USDC to each of three sub-accounts
+//! let responses = tx_client
+//!     .send_transfers_to_sub_accounts(
+//!         &[(281474976710648, 100_000_000), (281474976710649, 100_000_000), (281474976710650, 100_000_000)],
+//!         false,
+//!         None,
+//!     )
+//!     .await?;
+//! assert!(responses.iter().all(|response| response.is_success()));
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::client::{TxClient, TxResponse};
+use crate::constants::{
+    MAX_ACCOUNT_INDEX, MAX_TRANSFER_AMOUNT, MIN_ACCOUNT_INDEX, MIN_TRANSFER_AMOUNT,
+};
+use crate::errors::{LighterError, Result};
+use crate::types::{L2TransferTxInfo, TransactOpts, TransferTxReq};
+
+impl TxClient {
+    /// Sign a transfer of `usdc_amount` to a sub-account of the owner of the
+    /// client's account
+    ///
+    /// The transfer is signed without a fee and memo; use
+    /// [`TxClient::transfer`] for those. Unless `allow_external` is set, a
+    /// destination the owner's L1 address doesn't own, or the account itself,
+    /// fails with [`LighterError::NotASubAccount`] before anything is signed.
+    pub async fn transfer_to_sub_account(
+        &self,
+        sub_account_index: i64,
+        usdc_amount: i64,
+        allow_external: bool,
+        opts: Option<TransactOpts>,
+    ) -> Result<L2TransferTxInfo> {
+        let mut signed = self
+            .transfer_to_sub_accounts(&[(sub_account_index, usdc_amount)], allow_external, opts)
+            .await?;
+        Ok(signed.remove(0))
+    }
+
+    /// Sign a transfer to each `(sub_account_index, usdc_amount)` like
+    /// [`TxClient::transfer_to_sub_account`], with consecutive nonces
+    ///
+    /// The destinations are all checked with one lookup, and none is signed,
+    /// nor any nonce taken, if any fails the check or has an amount out of
+    /// range.
+    pub async fn transfer_to_sub_accounts(
+        &self,
+        transfers: &[(i64, i64)],
+        allow_external: bool,
+        opts: Option<TransactOpts>,
+    ) -> Result<Vec<L2TransferTxInfo>> {
+        if transfers.is_empty() {
+            return Ok(Vec::new());
+        }
+        // Refused before the batch takes its nonces, not half way through
+        for &(to_account_index, usdc_amount) in transfers {
+            check_transfer(to_account_index, usdc_amount)?;
+        }
+        let opts = opts.unwrap_or_default();
+        if !allow_external {
+            let account_index = opts.from_account_index.unwrap_or(self.account_index());
+            self.check_sub_accounts(account_index, transfers).await?;
+        }
+
+        let (opts, reservation) = self
+            .fill_opts_reserving(Some(opts), transfers.len() as i64)
+            .await?;
+        let first_nonce = opts.nonce.unwrap();
+        let mut signed = Vec::with_capacity(transfers.len());
+        for (i, &(to_account_index, usdc_amount)) in transfers.iter().enumerate() {
+            let req = TransferTxReq {
+                to_account_index,
+                usdc_amount,
+                fee: 0,
+                memo: [0; 32],
+            };
+            let opts = TransactOpts {
+                nonce: Some(first_nonce + i as i64),
+                ..opts.clone()
+            };
+            signed.push(self.transfer(&req, Some(opts)).await?);
+        }
+        reservation.keep();
+        Ok(signed)
+    }
+
+    /// Sign transfers like [`TxClient::transfer_to_sub_accounts`] and send
+    /// them in one batch, with a response for each
+    pub async fn send_transfers_to_sub_accounts(
+        &self,
+        transfers: &[(i64, i64)],
+        allow_external: bool,
+        opts: Option<TransactOpts>,
+    ) -> Result<Vec<TxResponse>> {
+        let signed = self
+            .transfer_to_sub_accounts(transfers, allow_external, opts)
+            .await?;
+        self.send_transactions(&signed).await
+    }
+
+    /// Fail unless every destination is another account of the owner of
+    /// `account_index`
+    async fn check_sub_accounts(&self, account_index: i64, transfers: &[(i64, i64)]) -> Result<()> {
+        let http = self.http().ok_or_else(|| {
+            LighterError::InvalidConfiguration(
+                "Checking sub-accounts needs an HTTPClient; pass allow_external to skip it"
+                    .to_string(),
+            )
+        })?;
+        let owner = http.get_account_state(account_index).await?.l1_address;
+        let owned = http.get_accounts_by_l1_address(&owner).await?;
+        match transfers.iter().find(|&&(to_account_index, _)| {
+            to_account_index == account_index || owned.binary_search(&to_account_index).is_err()
+        }) {
+            Some(&(to_account_index, _)) => Err(LighterError::NotASubAccount {
+                account_index,
+                to_account_index,
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The checks [`L2TransferTxInfo`] validation makes of one transfer's
+/// destination and amount
+fn check_transfer(to_account_index: i64, usdc_amount: i64) -> Result<()> {
+    if to_account_index < MIN_ACCOUNT_INDEX {
+        return Err(LighterError::ToAccountIndexTooLow(to_account_index));
+    }
+    if to_account_index > MAX_ACCOUNT_INDEX {
+        return Err(LighterError::ToAccountIndexTooHigh(to_account_index));
+    }
+    if !(MIN_TRANSFER_AMOUNT..=MAX_TRANSFER_AMOUNT).contains(&usdc_amount) {
+        return Err(LighterError::TransferAmountTooLow(usdc_amount));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::TX_TYPE_L2_TRANSFER;
+    use crate::test_support::mock_builder;
+    use crate::transport::{HttpResponse, MockTransport};
+    use std::sync::Arc;
+
+    const BATCH_PATH: &str = "/api/v1/sendTxBatch";
+    const SUB_ACCOUNTS: [i64; 3] = [281474976710648, 281474976710649, 281474976710650];
+
+    fn mock_client() -> (TxClient, Arc<MockTransport>) {
+        let mock = Arc::new(MockTransport::new());
+        mock.set_handler("/api/v1/account", |_| {
+            Ok(HttpResponse::new(
+                200,
+                r#"{"code":200,"accounts":[{"index":1,"l1_address":"0x01","collateral":"500.00","positions":[]}]}"#,
+            ))
+        });
+        mock.set_handler("/api/v1/accountsByL1Address", |_| {
+            Ok(HttpResponse::new(
+                200,
+                r#"{"code":200,"l1_address":"0x01","sub_accounts":[{"index":281474976710650},{"index":1},{"index":281474976710648},{"index":281474976710649}]}"#,
+            ))
+        });
+        mock.set_handler(BATCH_PATH, |_| {
+            Ok(HttpResponse::new(
+                200,
+                r#"{"code":200,"tx_hash":["0x1","0x2","0x3"]}"#,
+            ))
+        });
+        let tx_client = mock_builder(&mock).build().unwrap();
+        tx_client.nonces().set(1, 0, 5);
+        (tx_client, mock)
+    }
+
+    #[tokio::test]
+    async fn test_transfers_to_sub_accounts_are_batched() {
+        let (tx_client, mock) = mock_client();
+        let transfers = SUB_ACCOUNTS.map(|index| (index, 100_000_000));
+
+        let responses = tx_client
+            .send_transfers_to_sub_accounts(&transfers, false, None)
+            .await
+            .unwrap();
+        assert_eq!(responses.len(), 3);
+        assert!(responses.iter().all(TxResponse::is_success));
+
+        let fields: Vec<(String, String)> =
+            serde_urlencoded::from_bytes(&mock.requests_to(BATCH_PATH)[0].body).unwrap();
+        assert_eq!(
+            fields[0].1,
+            format!("[{TX_TYPE_L2_TRANSFER},{TX_TYPE_L2_TRANSFER},{TX_TYPE_L2_TRANSFER}]")
+        );
+        let sent: Vec<String> = serde_json::from_str(&fields[1].1).unwrap();
+        for ((tx, to_account_index), nonce) in sent.iter().zip(SUB_ACCOUNTS).zip(5..) {
+            let tx: serde_json::Value = serde_json::from_str(tx).unwrap();
+            assert_eq!(tx["ToAccountIndex"], to_account_index);
+            assert_eq!(tx["USDCAmount"], 100_000_000);
+            assert_eq!(tx["Nonce"], nonce);
+        }
+        // One lookup covers every destination
+        assert_eq!(mock.requests_to("/api/v1/accountsByL1Address").len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_transfer_to_an_unrelated_account_is_refused() {
+        let (tx_client, mock) = mock_client();
+
+        for to_account_index in [42, 1] {
+            assert!(matches!(
+                tx_client
+                    .transfer_to_sub_account(to_account_index, 100_000_000, false, None)
+                    .await,
+                Err(LighterError::NotASubAccount {
+                    account_index: 1,
+                    to_account_index: refused,
+                }) if refused == to_account_index
+            ));
+        }
+        // One stranger among sub-accounts stops them all
+        assert!(matches!(
+            tx_client
+                .transfer_to_sub_accounts(&[(SUB_ACCOUNTS[0], 1), (42, 1)], false, None)
+                .await,
+            Err(LighterError::NotASubAccount {
+                to_account_index: 42,
+                ..
+            })
+        ));
+        assert_eq!(tx_client.nonces().peek(1, 0), Some(5));
+
+        // Unless external destinations are allowed
+        let lookups = mock.requests_to("/api/v1/account").len();
+        let signed = tx_client
+            .transfer_to_sub_account(42, 100_000_000, true, None)
+            .await
+            .unwrap();
+        assert_eq!((signed.to_account_index, signed.nonce), (42, 5));
+        assert_eq!(mock.requests_to("/api/v1/account").len(), lookups);
+    }
+
+    #[tokio::test]
+    async fn test_a_bad_amount_takes_no_nonces() {
+        let (tx_client, mock) = mock_client();
+
+        assert!(matches!(
+            tx_client
+                .transfer_to_sub_accounts(
+                    &[(SUB_ACCOUNTS[0], 100_000_000), (SUB_ACCOUNTS[1], 0)],
+                    false,
+                    None,
+                )
+                .await,
+            Err(LighterError::TransferAmountTooLow(0))
+        ));
+        assert_eq!(tx_client.nonces().peek(1, 0), Some(5));
+        // Refused before looking the destinations up
+        assert!(mock.requests_to("/api/v1/accountsByL1Address").is_empty());
+    }
+}
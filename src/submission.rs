@@ -0,0 +1,522 @@
+//! Prioritized, paced submission of transactions
+//!
+//! When the market gaps, a quoter may want to re-quote every market at once.
+//! Sending all of it straight away trips the exchange's rate limits, and
+//! sending it first come, first served leaves urgent work, such as a kill
+//! switch cancel, stuck behind routine quotes. A [`SubmissionScheduler`]
+//! sits between strategies and the API: submissions wait in a priority
+//! queue and are sent within a rate budget, highest priority first.
+//!
+//! The budget defaults to the client's
+//! [`RiskLimits::max_orders_per_second`](crate::risk::RiskLimits::max_orders_per_second)
+//! and follows the same rule: at most that many submissions in any
+//! one-second window. On top of it, [`SchedulerConfig::burst`] shapes bursts
+//! by spacing submissions evenly once that many have gone out back to back.
+//!
+//! ```no_run
+//! use lighter_rs::client::TxClient;
+//! use lighter_rs::submission::{Priority, SchedulerConfig, Submission, SubmissionScheduler};
+//! use lighter_rs::types::{CancelOrderTxReq, SignedTx};
+//! use std::sync::Arc;
+//!
+//! # async fn example(tx_client: TxClient) -> lighter_rs::Result<()> {
+//! let scheduler = Arc::new(SubmissionScheduler::new(
+//!     Arc::new(tx_client),
+//!     SchedulerConfig {
+//!         max_per_second: Some(20),
+//!         burst: 5,
+//!     },
+//! ));
+//! let runner = scheduler.clone();
+//! tokio::spawn(async move { runner.run().await });
+//!
+//! // Signed when its turn comes, so the nonce is allocated in send order
+//! let cancel = Submission::deferred(|tx_client| {
+//!     Box::pin(async move {
+//!         let req = CancelOrderTxReq { market_index: 0, index: 42 };
+//!         SignedTx::new(&tx_client.cancel_order(&req, None).await?)
+//!     })
+//! });
+//! let outcome = scheduler.submit(Priority::URGENT, 0, cancel).await;
+//! println!("queued for {:?}: {:?}", outcome.queue_latency, outcome.result);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, VecDeque};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures_util::future::BoxFuture;
+use tokio::sync::{oneshot, Notify};
+
+use crate::client::{TxClient, TxResponse};
+use crate::clock::Clock;
+use crate::errors::Result;
+use crate::types::SignedTx;
+
+/// Window of the rate budget, in milliseconds
+const RATE_WINDOW_MS: i64 = 1000;
+
+/// Urgency of a submission; higher goes first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Priority(pub u8);
+
+impl Priority {
+    pub const LOW: Self = Self(0);
+    pub const NORMAL: Self = Self(64);
+    pub const HIGH: Self = Self(128);
+    /// Risk-reducing work such as kill switch cancels
+    pub const URGENT: Self = Self(u8::MAX);
+}
+
+/// Signs a transaction when its submission is dispatched
+pub type SignLater =
+    Box<dyn for<'a> FnOnce(&'a TxClient) -> BoxFuture<'a, Result<SignedTx>> + Send>;
+
+/// A transaction to submit
+pub enum Submission {
+    /// Signed up front
+    Signed(SignedTx),
+    /// Signed when its turn comes, so its nonce is allocated in send order
+    /// and its expiry counts from the send
+    Deferred(SignLater),
+}
+
+impl Submission {
+    /// Sign with `sign` once the submission is dispatched
+    pub fn deferred<F>(sign: F) -> Self
+    where
+        F: for<'a> FnOnce(&'a TxClient) -> BoxFuture<'a, Result<SignedTx>> + Send + 'static,
+    {
+        Submission::Deferred(Box::new(sign))
+    }
+}
+
+impl From<SignedTx> for Submission {
+    fn from(tx: SignedTx) -> Self {
+        Submission::Signed(tx)
+    }
+}
+
+/// Pacing of a [`SubmissionScheduler`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SchedulerConfig {
+    /// Most submissions in any one-second window; `None` uses the client's
+    /// `max_orders_per_second` risk limit, and no limit at all leaves
+    /// submissions unpaced
+    pub max_per_second: Option<usize>,
+    /// Submissions that may go out back to back before the rest are spaced
+    /// evenly across the window; capped at the rate
+    pub burst: usize,
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        Self {
+            max_per_second: None,
+            burst: 1,
+        }
+    }
+}
+
+/// What happened to one submission
+#[derive(Debug)]
+pub struct SubmissionOutcome {
+    pub market_index: u8,
+    pub priority: Priority,
+    /// Time spent waiting in the queue, before signing and sending
+    pub queue_latency: Duration,
+    pub result: Result<TxResponse>,
+}
+
+/// Resolves to the outcome of a queued submission
+///
+/// Dropping the handle withdraws the submission if it hasn't been dispatched
+/// yet.
+pub struct SubmissionHandle(oneshot::Receiver<SubmissionOutcome>);
+
+impl Future for SubmissionHandle {
+    type Output = SubmissionOutcome;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.0).poll(cx).map(|outcome| {
+            // The scheduler only drops the sender after replying
+            outcome.expect("submission dropped without an outcome")
+        })
+    }
+}
+
+struct Queued {
+    priority: Priority,
+    /// Arrival order, to keep submissions of equal priority first in, first out
+    sequence: u64,
+    market_index: u8,
+    enqueued_at_ms: i64,
+    submission: Submission,
+    reply: oneshot::Sender<SubmissionOutcome>,
+}
+
+impl PartialEq for Queued {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Queued {}
+
+impl PartialOrd for Queued {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Queued {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| other.sequence.cmp(&self.sequence))
+    }
+}
+
+/// Rate budget bookkeeping
+#[derive(Default)]
+struct Pacer {
+    /// Dispatch times within the last window, in milliseconds
+    recent: VecDeque<i64>,
+    /// Earliest time the next dispatch is due at the even spacing, in
+    /// microseconds
+    due_us: i64,
+}
+
+impl Pacer {
+    /// Milliseconds until a dispatch at `now_ms` fits the budget
+    fn wait_ms(&mut self, now_ms: i64, rate: usize, burst: usize) -> i64 {
+        while self
+            .recent
+            .front()
+            .is_some_and(|&sent| sent <= now_ms - RATE_WINDOW_MS)
+        {
+            self.recent.pop_front();
+        }
+        let window_wait = match self.recent.len().checked_sub(rate) {
+            Some(excess) => self.recent[excess] + RATE_WINDOW_MS - now_ms,
+            None => 0,
+        };
+
+        let interval_us = interval_us(rate);
+        let tolerance_us = (burst.clamp(1, rate) as i64 - 1) * interval_us;
+        let spacing_wait_us = self.due_us - tolerance_us - now_ms * 1000;
+        let spacing_wait = (spacing_wait_us.max(0) + 999) / 1000;
+
+        window_wait.max(spacing_wait)
+    }
+
+    fn record(&mut self, now_ms: i64, rate: Option<usize>) {
+        self.recent.push_back(now_ms);
+        if let Some(rate) = rate {
+            self.due_us = self.due_us.max(now_ms * 1000) + interval_us(rate);
+        }
+    }
+}
+
+fn interval_us(rate: usize) -> i64 {
+    (1_000_000 / rate.max(1) as i64).max(1)
+}
+
+struct Inner {
+    queue: BinaryHeap<Queued>,
+    next_sequence: u64,
+    pacer: Pacer,
+}
+
+/// Priority queue sending transactions within a rate budget
+///
+/// Submissions are queued with [`SubmissionScheduler::enqueue`] or
+/// [`SubmissionScheduler::submit`] and sent by [`SubmissionScheduler::run`],
+/// which must be running for anything to go out.
+pub struct SubmissionScheduler {
+    tx_client: Arc<TxClient>,
+    config: SchedulerConfig,
+    clock: Arc<dyn Clock>,
+    inner: Mutex<Inner>,
+    queued: Notify,
+}
+
+impl SubmissionScheduler {
+    pub fn new(tx_client: Arc<TxClient>, config: SchedulerConfig) -> Self {
+        Self {
+            clock: tx_client.clock().clone(),
+            tx_client,
+            config,
+            inner: Mutex::new(Inner {
+                queue: BinaryHeap::new(),
+                next_sequence: 0,
+                pacer: Pacer::default(),
+            }),
+            queued: Notify::new(),
+        }
+    }
+
+    /// Use `clock` instead of the client's
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Queue a submission for `market_index`
+    ///
+    /// It goes out after everything queued with a higher priority, and after
+    /// earlier submissions of the same priority.
+    pub fn enqueue(
+        &self,
+        priority: Priority,
+        market_index: u8,
+        submission: Submission,
+    ) -> SubmissionHandle {
+        let (reply, outcome) = oneshot::channel();
+        {
+            let mut inner = self.lock();
+            let sequence = inner.next_sequence;
+            inner.next_sequence += 1;
+            inner.queue.push(Queued {
+                priority,
+                sequence,
+                market_index,
+                enqueued_at_ms: self.clock.now_ms(),
+                submission,
+                reply,
+            });
+        }
+        self.queued.notify_one();
+        SubmissionHandle(outcome)
+    }
+
+    /// Queue a submission and wait for its outcome
+    pub async fn submit(
+        &self,
+        priority: Priority,
+        market_index: u8,
+        submission: Submission,
+    ) -> SubmissionOutcome {
+        self.enqueue(priority, market_index, submission).await
+    }
+
+    /// Number of submissions waiting
+    pub fn queued(&self) -> usize {
+        self.lock().queue.len()
+    }
+
+    /// Dispatch queued submissions within the rate budget, forever
+    ///
+    /// Each dispatched submission is signed if deferred, then sent on its own
+    /// task so a slow response doesn't hold up the queue. Withdrawn
+    /// submissions are skipped without using the budget.
+    pub async fn run(&self) {
+        loop {
+            let rate = self
+                .config
+                .max_per_second
+                .or_else(|| self.tx_client.risk_limits().max_orders_per_second);
+            let now_ms = self.clock.now_ms();
+
+            let next = {
+                let mut inner = self.lock();
+                inner.queue.retain(|queued| !queued.reply.is_closed());
+                if inner.queue.is_empty() {
+                    None
+                } else {
+                    let wait_ms = match rate {
+                        Some(rate) => inner.pacer.wait_ms(now_ms, rate, self.config.burst),
+                        None => 0,
+                    };
+                    if wait_ms > 0 {
+                        Some(Err(wait_ms))
+                    } else {
+                        inner.pacer.record(now_ms, rate);
+                        inner.queue.pop().map(Ok)
+                    }
+                }
+            };
+
+            match next {
+                None => self.queued.notified().await,
+                // Sleep, then pick again: something more urgent may have come in
+                Some(Err(wait_ms)) => {
+                    self.clock
+                        .sleep(Duration::from_millis(wait_ms as u64))
+                        .await
+                }
+                Some(Ok(queued)) => self.dispatch(queued, now_ms).await,
+            }
+        }
+    }
+
+    async fn dispatch(&self, queued: Queued, now_ms: i64) {
+        let Queued {
+            priority,
+            market_index,
+            enqueued_at_ms,
+            submission,
+            reply,
+            ..
+        } = queued;
+        let queue_latency = Duration::from_millis((now_ms - enqueued_at_ms).max(0) as u64);
+        let signed = match submission {
+            Submission::Signed(tx) => Ok(tx),
+            Submission::Deferred(sign) => sign(&self.tx_client).await,
+        };
+
+        let tx_client = self.tx_client.clone();
+        tokio::spawn(async move {
+            let result = match signed {
+                Ok(tx) => tx_client.send_signed(&tx).await,
+                Err(e) => Err(e),
+            };
+            // The submitter may have stopped waiting
+            let _ = reply.send(SubmissionOutcome {
+                market_index,
+                priority,
+                queue_latency,
+                result,
+            });
+        });
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        self.inner
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::risk::RiskLimits;
+    use crate::test_support::mock_builder;
+    use crate::transport::{HttpResponse, MockTransport};
+    use crate::types::CancelOrderTxReq;
+    use futures_util::future::join_all;
+
+    const SEND_TX_PATH: &str = "/api/v1/sendTx";
+
+    fn client(limits: RiskLimits) -> (Arc<TxClient>, Arc<MockTransport>) {
+        let mock = Arc::new(MockTransport::new());
+        mock.set_handler(SEND_TX_PATH, |_| {
+            Ok(HttpResponse::new(200, r#"{"code":200,"tx_hash":"0xabc"}"#))
+        });
+        let tx_client = mock_builder(&mock).risk_limits(limits).build().unwrap();
+        tx_client.nonces().set(1, 0, 0);
+        (Arc::new(tx_client), mock)
+    }
+
+    fn cancel(market_index: u8) -> Submission {
+        Submission::deferred(move |tx_client| {
+            Box::pin(async move {
+                let req = CancelOrderTxReq {
+                    market_index,
+                    index: 7,
+                };
+                SignedTx::new(&tx_client.cancel_order(&req, None).await?)
+            })
+        })
+    }
+
+    /// Market index of every transaction sent, in order
+    fn sent_markets(mock: &MockTransport) -> Vec<u64> {
+        mock.requests_to(SEND_TX_PATH)
+            .iter()
+            .map(|request| {
+                let form: Vec<(String, String)> =
+                    serde_urlencoded::from_bytes(&request.body).unwrap();
+                let tx: serde_json::Value = serde_json::from_str(&form[1].1).unwrap();
+                tx["MarketIndex"].as_u64().unwrap()
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_urgent_jumps_queue_and_paces_the_rest() {
+        let (tx_client, mock) = client(RiskLimits::default());
+        let clock = Arc::new(ManualClock::at_ms(0));
+        let scheduler = SubmissionScheduler::new(
+            tx_client,
+            SchedulerConfig {
+                max_per_second: Some(5),
+                burst: 2,
+            },
+        )
+        .clock(clock.clone());
+
+        let mut handles: Vec<SubmissionHandle> = (0..6)
+            .map(|market| scheduler.enqueue(Priority::NORMAL, market, cancel(market)))
+            .collect();
+        handles.insert(0, scheduler.enqueue(Priority::URGENT, 9, cancel(9)));
+        assert_eq!(scheduler.queued(), 7);
+
+        let outcomes = tokio::select! {
+            _ = scheduler.run() => unreachable!(),
+            outcomes = join_all(handles) => outcomes,
+        };
+
+        assert_eq!(sent_markets(&mock), vec![9, 0, 1, 2, 3, 4, 5]);
+        let latencies: Vec<u128> = outcomes
+            .iter()
+            .map(|outcome| outcome.queue_latency.as_millis())
+            .collect();
+        // Two back to back, then one every 200ms, never more than 5 a second;
+        // once the first pair leaves the window a pair can go together again
+        assert_eq!(latencies, vec![0, 0, 200, 400, 600, 1000, 1000]);
+        assert!(outcomes.iter().all(|outcome| outcome.result.is_ok()));
+        assert_eq!(outcomes[0].priority, Priority::URGENT);
+
+        // Nonces follow send order since signing waits for the turn
+        let nonces: Vec<i64> = mock
+            .requests_to(SEND_TX_PATH)
+            .iter()
+            .map(|request| {
+                let form: Vec<(String, String)> =
+                    serde_urlencoded::from_bytes(&request.body).unwrap();
+                let tx: serde_json::Value = serde_json::from_str(&form[1].1).unwrap();
+                tx["Nonce"].as_i64().unwrap()
+            })
+            .collect();
+        assert_eq!(nonces, (0..7).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn test_uses_client_budget_and_skips_withdrawn() {
+        let (tx_client, mock) = client(RiskLimits {
+            max_orders_per_second: Some(2),
+            ..RiskLimits::default()
+        });
+        let clock = Arc::new(ManualClock::at_ms(50_000));
+        let scheduler =
+            SubmissionScheduler::new(tx_client, SchedulerConfig::default()).clock(clock.clone());
+
+        let first = scheduler.enqueue(Priority::LOW, 0, cancel(0));
+        drop(scheduler.enqueue(Priority::HIGH, 1, cancel(1)));
+        let rest: Vec<SubmissionHandle> = (2..4)
+            .map(|market| scheduler.enqueue(Priority::LOW, market, cancel(market)))
+            .collect();
+
+        let outcomes = tokio::select! {
+            _ = scheduler.run() => unreachable!(),
+            outcomes = join_all(std::iter::once(first).chain(rest)) => outcomes,
+        };
+
+        assert_eq!(sent_markets(&mock), vec![0, 2, 3]);
+        let latencies: Vec<u128> = outcomes
+            .iter()
+            .map(|outcome| outcome.queue_latency.as_millis())
+            .collect();
+        assert_eq!(latencies, vec![0, 500, 1000]);
+        assert_eq!(clock.now_ms(), 51_000);
+    }
+}
@@ -0,0 +1,340 @@
+//! Marketable limit orders that sweep the book up to an average price
+//!
+//! A market order bounded by a single worst price fills every level up to
+//! it, so its average price can end up anywhere below that bound.
+//! [`plan_sweep`] instead walks the book from the touch and takes the most
+//! size whose average fill price stays within a limit, stopping part way
+//! through the level that would push it past. [`TxClient::create_sweep_order`]
+//! signs that size as an immediate or cancel limit order at the last level
+//! reached, so it can't fill any deeper than planned.
+//!
+//! ```no_run
+//! use lighter_rs::client::TxClient;
+//! use lighter_rs::constants::NIL_CLIENT_ORDER_INDEX;
+//! use lighter_rs::ws_client::OrderBook;
+//!
+//! # async fn example(tx_client: TxClient, book: OrderBook) -> lighter_rs::Result<()> {
+//! // Buy up to 2 ETH, paying at most 3001.50 on average
+//! let response = tx_client
+//!     .send_sweep_order(0, NIL_CLIENT_ORDER_INDEX, 20_000, 300_150, 0, &book, None)
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::cmp::Reverse;
+
+use rust_decimal::{Decimal, RoundingStrategy};
+
+use crate::client::{TxClient, TxResponse};
+use crate::constants::*;
+use crate::errors::{LighterError, Result};
+use crate::types::{L2CreateOrderTxInfo, TransactOpts};
+use crate::ws_client::{OrderBook, PriceLevel};
+
+/// Size a sweep can take from the book, and what it pays
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sweep {
+    /// Base size filled, at most the size asked for
+    pub size: Decimal,
+    /// Price of the deepest level reached, the limit price of the order
+    pub price: Decimal,
+    /// Size-weighted average price of the fills
+    pub avg_price: Decimal,
+}
+
+/// Walk `book` from the touch for up to `max_size`, keeping the average
+/// fill price at or better than `max_avg_price`
+///
+/// Buys take asks from the lowest and sells bids from the highest. Levels
+/// past the limit are still taken, whole or in part, while the better levels
+/// before them leave room in the average. The size is rounded down to
+/// `size_decimals`. Fails with [`LighterError::InsufficientLiquidity`] when
+/// nothing can be filled, as on an empty side of the book.
+pub fn plan_sweep(
+    book: &OrderBook,
+    is_ask: bool,
+    max_size: Decimal,
+    max_avg_price: Decimal,
+    size_decimals: u32,
+) -> Result<Sweep> {
+    let mut levels: Vec<PriceLevel> = if is_ask { &book.bids } else { &book.asks }
+        .iter()
+        .filter(|level| level.size > Decimal::ZERO)
+        .copied()
+        .collect();
+    if is_ask {
+        levels.sort_by_key(|level| Reverse(level.price));
+    } else {
+        levels.sort_by_key(|level| level.price);
+    }
+    // Prices measured as how much worse than the limit they are
+    let worse_by = |price: Decimal| {
+        if is_ask {
+            max_avg_price - price
+        } else {
+            price - max_avg_price
+        }
+    };
+
+    let mut fills: Vec<PriceLevel> = Vec::new();
+    // Room left in the average: what the fills so far saved against the limit
+    let mut room = Decimal::ZERO;
+    let mut remaining = max_size;
+    for level in levels {
+        if remaining <= Decimal::ZERO {
+            break;
+        }
+        let worse = worse_by(level.price);
+        let wanted = level.size.min(remaining);
+        let size = if worse <= Decimal::ZERO {
+            wanted
+        } else {
+            wanted.min(room / worse)
+        };
+        let size = size.round_dp_with_strategy(size_decimals, RoundingStrategy::ToZero);
+        if size.is_zero() {
+            break;
+        }
+        room -= worse * size;
+        remaining -= size;
+        fills.push(PriceLevel {
+            price: level.price,
+            size,
+        });
+        // A level cut short leaves no room for the worse ones after it
+        if size < wanted {
+            break;
+        }
+    }
+
+    let size: Decimal = fills.iter().map(|fill| fill.size).sum();
+    match fills.last() {
+        Some(last) => Ok(Sweep {
+            size,
+            price: last.price,
+            avg_price: fills
+                .iter()
+                .map(|fill| fill.price * fill.size)
+                .sum::<Decimal>()
+                / size,
+        }),
+        None => Err(LighterError::InsufficientLiquidity {
+            is_ask,
+            max_avg_price,
+        }),
+    }
+}
+
+impl TxClient {
+    /// Sign an immediate or cancel limit order for as much of
+    /// `max_base_amount` as `book` fills at an average of `max_avg_price` or
+    /// better
+    ///
+    /// Amounts and prices are integers, like [`TxClient::create_limit_order`]
+    /// takes, converted with the market's details, fetched and cached on
+    /// first use. See [`plan_sweep`]; a book that can't fill a single step
+    /// fails with [`LighterError::InsufficientLiquidity`] and signs nothing.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn create_sweep_order(
+        &self,
+        market_index: u8,
+        client_order_index: i64,
+        max_base_amount: i64,
+        max_avg_price: u64,
+        is_ask: u8,
+        book: &OrderBook,
+        opts: Option<TransactOpts>,
+    ) -> Result<L2CreateOrderTxInfo> {
+        let details = self.cached_market_details(market_index).await?;
+        let sweep = plan_sweep(
+            book,
+            is_ask != 0,
+            Decimal::new(max_base_amount, details.size_decimals),
+            Decimal::from_i128_with_scale(i128::from(max_avg_price), details.price_decimals),
+            details.size_decimals,
+        )?;
+        let units = |mut value: Decimal, decimals: u32| {
+            value.rescale(decimals);
+            value.mantissa()
+        };
+        let base_amount =
+            i64::try_from(units(sweep.size, details.size_decimals)).map_err(|_| {
+                LighterError::ValidationError(format!("Size {} is too large", sweep.size))
+            })?;
+        let price = u64::try_from(units(sweep.price, details.price_decimals)).map_err(|_| {
+            LighterError::ValidationError(format!("Price {} is negative", sweep.price))
+        })?;
+
+        self.create_limit_order_with_tif(
+            market_index,
+            client_order_index,
+            base_amount,
+            price,
+            is_ask,
+            TIME_IN_FORCE_IMMEDIATE_OR_CANCEL,
+            false,
+            opts,
+        )
+        .await
+    }
+
+    /// Sign an order like [`TxClient::create_sweep_order`] and send it
+    #[allow(clippy::too_many_arguments)]
+    pub async fn send_sweep_order(
+        &self,
+        market_index: u8,
+        client_order_index: i64,
+        max_base_amount: i64,
+        max_avg_price: u64,
+        is_ask: u8,
+        book: &OrderBook,
+        opts: Option<TransactOpts>,
+    ) -> Result<TxResponse> {
+        let order = self
+            .create_sweep_order(
+                market_index,
+                client_order_index,
+                max_base_amount,
+                max_avg_price,
+                is_ask,
+                book,
+                opts,
+            )
+            .await?;
+        self.send_transaction(&order).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::mock_builder;
+    use crate::transport::{HttpResponse, MockTransport};
+    use std::sync::Arc;
+
+    fn dec(value: &str) -> Decimal {
+        value.parse().unwrap()
+    }
+
+    fn levels(levels: &[(&str, &str)]) -> Vec<PriceLevel> {
+        levels
+            .iter()
+            .map(|(price, size)| PriceLevel {
+                price: dec(price),
+                size: dec(size),
+            })
+            .collect()
+    }
+
+    /// Asks 3000 x 1, 3001 x 1, 3004 x 2 and bids 2999 x 1, 2998 x 2, out
+    /// of order as the stream leaves them
+    fn book() -> OrderBook {
+        OrderBook {
+            asks: levels(&[("3001", "1"), ("3000", "1"), ("3004", "2")]),
+            bids: levels(&[("2998", "2"), ("2999", "1"), ("2990", "0")]),
+            offset: None,
+        }
+    }
+
+    #[test]
+    fn test_sweep_stops_where_the_average_reaches_the_limit() {
+        // 3000 and 3001 are 1.5 and 0.5 below the limit, room for 0.8 of
+        // 3004, 2.5 above it, before the average reaches 3001.5
+        let sweep = plan_sweep(&book(), false, dec("10"), dec("3001.5"), 4).unwrap();
+        assert_eq!(sweep.size, dec("2.8"));
+        assert_eq!(sweep.price, dec("3004"));
+        assert_eq!(sweep.avg_price, dec("3001.5"));
+
+        // Capped by the size asked for
+        let sweep = plan_sweep(&book(), false, dec("1.5"), dec("3001.5"), 4).unwrap();
+        assert_eq!(sweep.size, dec("1.5"));
+        assert_eq!(sweep.price, dec("3001"));
+
+        // Sells walk the bids down: 2999 is 0.5 better than a 2998.5 limit,
+        // room for 1 of the 2 at 2998, 0.5 worse
+        let sweep = plan_sweep(&book(), true, dec("10"), dec("2998.5"), 4).unwrap();
+        assert_eq!(sweep.size, dec("2"));
+        assert_eq!(sweep.price, dec("2998"));
+        assert_eq!(sweep.avg_price, dec("2998.5"));
+    }
+
+    #[test]
+    fn test_sweep_goes_on_past_a_worse_level_taken_whole() {
+        // 3000 is 1 below a 3001 limit, room for all 0.1 of 3002 and 0.95 of
+        // 3003 in what is left
+        let book = OrderBook {
+            asks: levels(&[("3000", "2"), ("3002", "0.1"), ("3003", "5")]),
+            bids: Vec::new(),
+            offset: None,
+        };
+        let sweep = plan_sweep(&book, false, dec("10"), dec("3001"), 4).unwrap();
+        assert_eq!(sweep.size, dec("3.05"));
+        assert_eq!(sweep.price, dec("3003"));
+        assert_eq!(sweep.avg_price, dec("3001"));
+    }
+
+    #[test]
+    fn test_sweep_rounds_down_to_the_step() {
+        // 3000 is 1 below a 3001 limit, room for a third of 3004, 3 above it
+        let sweep = plan_sweep(&book(), false, dec("10"), dec("3001"), 2).unwrap();
+        assert_eq!(sweep.size, dec("2.33"));
+        assert_eq!(sweep.price, dec("3004"));
+        assert!(sweep.avg_price <= dec("3001"));
+    }
+
+    #[test]
+    fn test_sweep_without_liquidity_is_an_error() {
+        for (book, is_ask, max_avg_price) in [
+            // Empty side
+            (OrderBook::default(), false, "3000"),
+            (
+                OrderBook {
+                    bids: Vec::new(),
+                    ..book()
+                },
+                true,
+                "2990",
+            ),
+            // Touch already worse than the limit
+            (book(), false, "2999.99"),
+            (book(), true, "2999.01"),
+        ] {
+            assert!(matches!(
+                plan_sweep(&book, is_ask, dec("1"), dec(max_avg_price), 4),
+                Err(LighterError::InsufficientLiquidity { .. })
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sweep_order_is_immediate_or_cancel_at_the_deepest_level() {
+        let mock = Arc::new(MockTransport::new());
+        mock.set_handler("/api/v1/orderBookDetails", |_| {
+            Ok(HttpResponse::new(
+                200,
+                r#"{"code":200,"order_book_details":[{"market_id":0,"symbol":"ETH","size_decimals":4,"price_decimals":2}]}"#,
+            ))
+        });
+        let tx_client = mock_builder(&mock).build().unwrap();
+        tx_client.nonces().set(1, 0, 3);
+
+        let order = tx_client
+            .create_sweep_order(0, 7, 100_000, 300_150, 0, &book(), None)
+            .await
+            .unwrap();
+        assert_eq!((order.base_amount, order.price), (28_000, 300_400));
+        assert_eq!(order.time_in_force, TIME_IN_FORCE_IMMEDIATE_OR_CANCEL);
+        assert_eq!(order.order_type, ORDER_TYPE_LIMIT);
+        assert_eq!(order.order_expiry, NIL_ORDER_EXPIRY);
+
+        // Nothing is signed for a book that can't fill the order
+        assert!(matches!(
+            tx_client
+                .create_sweep_order(0, 8, 100_000, 299_999, 0, &book(), None)
+                .await,
+            Err(LighterError::InsufficientLiquidity { is_ask: false, .. })
+        ));
+        assert_eq!(tx_client.nonces().peek(1, 0), Some(4));
+    }
+}
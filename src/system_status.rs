@@ -0,0 +1,277 @@
+//! Exchange maintenance and trading pauses
+//!
+//! The exchange occasionally pauses trading for upgrades, and orders sent in
+//! the meantime bounce. The status endpoint says when that is happening:
+//! [`HTTPClient::get_system_status`](crate::client::HTTPClient::get_system_status)
+//! reads it into a [`StatusUpdate`]. A status response may carry
+//! `trading_enabled` or `maintenance`, a `message` and the
+//! `markets_affected`; a `503 Service Unavailable` from the endpoint is read
+//! as a pause of every market.
+//!
+//! With [`TxClientBuilder::pause_check`](crate::client::TxClientBuilder::pause_check)
+//! the client consults the status before signing an order and fails fast with
+//! [`LighterError::TradingPaused`](crate::errors::LighterError::TradingPaused)
+//! while the order's market is paused. The status is cached for the given
+//! time to live, so the check costs one request per interval rather than one
+//! per order, and a status that can't be fetched never blocks trading.
+//!
+//! A [`StatusPoller`] refreshes the status on an interval and broadcasts every
+//! change:
+//!
+//! ```no_run
+//! use lighter_rs::client::TxClient;
+//! use lighter_rs::system_status::StatusPoller;
+//! use std::sync::Arc;
+//! use std::time::Duration;
+//!
+//! # async fn example(tx_client: TxClient) {
+//! let tx_client = Arc::new(tx_client);
+//! let poller = Arc::new(StatusPoller::new(tx_client.clone(), Duration::from_secs(5)));
+//! let mut updates = poller.subscribe();
+//! let runner = poller.clone();
+//! tokio::spawn(async move { runner.run().await });
+//!
+//! while let Ok(update) = updates.recv().await {
+//!     if !update.trading_enabled {
+//!         println!("trading paused: {:?}", update.message);
+//!     }
+//! }
+//! # }
+//! ```
+
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::{LighterError, Result};
+
+/// HTTP status the status endpoint answers with during maintenance
+const SERVICE_UNAVAILABLE: u16 = 503;
+
+/// Longest maintenance message kept from a non-JSON response body
+const MAX_MESSAGE_LEN: usize = 256;
+
+/// The exchange's trading status at one point in time
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatusUpdate {
+    pub trading_enabled: bool,
+    /// Maintenance notice or announcement, when the exchange gave one
+    pub message: Option<String>,
+    /// Markets the pause applies to; empty means every market
+    pub markets_affected: Vec<u8>,
+    /// When the status was fetched, in milliseconds since the Unix epoch
+    pub checked_at_ms: i64,
+}
+
+impl StatusUpdate {
+    /// Whether orders on `market_index` would bounce
+    pub fn is_paused(&self, market_index: u8) -> bool {
+        !self.trading_enabled
+            && (self.markets_affected.is_empty() || self.markets_affected.contains(&market_index))
+    }
+
+    /// Whether the two updates describe the same status, whenever they were
+    /// checked
+    pub fn same_status(&self, other: &StatusUpdate) -> bool {
+        self.trading_enabled == other.trading_enabled
+            && self.message == other.message
+            && self.markets_affected == other.markets_affected
+    }
+
+    /// Read a status endpoint response
+    pub(crate) fn from_response(status: u16, body: &str, checked_at_ms: i64) -> Result<Self> {
+        #[derive(Deserialize)]
+        struct StatusResponse {
+            #[serde(default)]
+            trading_enabled: Option<bool>,
+            #[serde(default)]
+            maintenance: bool,
+            #[serde(default)]
+            message: Option<String>,
+            #[serde(default)]
+            markets_affected: Vec<u8>,
+        }
+
+        if status == SERVICE_UNAVAILABLE {
+            let message = match serde_json::from_str::<StatusResponse>(body) {
+                Ok(response) => response.message,
+                Err(_) => Some(body.trim().chars().take(MAX_MESSAGE_LEN).collect())
+                    .filter(|message: &String| !message.is_empty()),
+            };
+            return Ok(Self {
+                trading_enabled: false,
+                message,
+                markets_affected: Vec::new(),
+                checked_at_ms,
+            });
+        }
+        if !(200..300).contains(&status) {
+            return Err(LighterError::ApiError(format!(
+                "Failed to get status: {status}"
+            )));
+        }
+
+        let response: StatusResponse = serde_json::from_str(body)?;
+        Ok(Self {
+            trading_enabled: response.trading_enabled.unwrap_or(!response.maintenance),
+            message: response.message.filter(|message| !message.is_empty()),
+            markets_affected: response.markets_affected,
+            checked_at_ms,
+        })
+    }
+}
+
+/// The latest status, reused for `ttl_ms`
+#[derive(Debug)]
+pub(crate) struct StatusCache {
+    ttl_ms: i64,
+    latest: Mutex<Option<StatusUpdate>>,
+}
+
+impl StatusCache {
+    pub(crate) fn new(ttl: std::time::Duration) -> Self {
+        Self {
+            ttl_ms: ttl.as_millis() as i64,
+            latest: Mutex::new(None),
+        }
+    }
+
+    pub(crate) fn latest(&self) -> Option<StatusUpdate> {
+        self.lock().clone()
+    }
+
+    /// The latest status if it was checked less than the TTL before `now_ms`
+    pub(crate) fn fresh(&self, now_ms: i64) -> Option<StatusUpdate> {
+        self.lock()
+            .as_ref()
+            .filter(|update| now_ms - update.checked_at_ms < self.ttl_ms)
+            .cloned()
+    }
+
+    pub(crate) fn store(&self, update: StatusUpdate) {
+        *self.lock() = Some(update);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Option<StatusUpdate>> {
+        self.latest
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(feature = "native")]
+pub use poller::StatusPoller;
+
+#[cfg(feature = "native")]
+mod poller {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use tokio::sync::broadcast;
+
+    use super::StatusUpdate;
+    use crate::client::TxClient;
+    use crate::clock::Clock;
+
+    /// Status updates buffered per subscriber before the oldest are dropped
+    const EVENT_BUFFER: usize = 64;
+
+    /// Polls the exchange status and broadcasts every change
+    pub struct StatusPoller {
+        tx_client: Arc<TxClient>,
+        interval: Duration,
+        clock: Arc<dyn Clock>,
+        events: broadcast::Sender<StatusUpdate>,
+    }
+
+    impl StatusPoller {
+        pub fn new(tx_client: Arc<TxClient>, interval: Duration) -> Self {
+            let (events, _) = broadcast::channel(EVENT_BUFFER);
+            Self {
+                clock: tx_client.clock().clone(),
+                tx_client,
+                interval,
+                events,
+            }
+        }
+
+        /// Use `clock` instead of the client's to wait between polls
+        pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+            self.clock = clock;
+            self
+        }
+
+        /// Receive the first status and every change after it
+        pub fn subscribe(&self) -> broadcast::Receiver<StatusUpdate> {
+            self.events.subscribe()
+        }
+
+        /// Poll forever
+        ///
+        /// Each status fetched also refreshes the client's cached status used
+        /// by [`TxClientBuilder::pause_check`](crate::client::TxClientBuilder::pause_check).
+        /// Failed polls are logged and retried on the next interval.
+        pub async fn run(&self) {
+            let mut last: Option<StatusUpdate> = None;
+            loop {
+                match self.tx_client.refresh_system_status().await {
+                    Ok(update) => {
+                        if !last.as_ref().is_some_and(|last| last.same_status(&update)) {
+                            if !update.trading_enabled {
+                                tracing::warn!(
+                                    message = ?update.message,
+                                    markets = ?update.markets_affected,
+                                    "Exchange trading is paused"
+                                );
+                            }
+                            // No subscribers is fine
+                            let _ = self.events.send(update.clone());
+                            last = Some(update);
+                        }
+                    }
+                    Err(e) => tracing::warn!(error = %e, "Failed to poll the exchange status"),
+                }
+                self.clock.sleep(self.interval).await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_responses() {
+        let update = StatusUpdate::from_response(
+            200,
+            r#"{"status":1,"network_id":1,"timestamp":1717777777}"#,
+            5,
+        )
+        .unwrap();
+        assert!(update.trading_enabled);
+        assert!(!update.is_paused(0));
+        assert_eq!(update.checked_at_ms, 5);
+
+        let update = StatusUpdate::from_response(
+            200,
+            r#"{"maintenance":true,"message":"Upgrade until 14:00 UTC","markets_affected":[1,2]}"#,
+            5,
+        )
+        .unwrap();
+        assert!(!update.trading_enabled);
+        assert!(!update.is_paused(0));
+        assert!(update.is_paused(2));
+        assert_eq!(update.message.as_deref(), Some("Upgrade until 14:00 UTC"));
+
+        let update = StatusUpdate::from_response(503, "<html>Maintenance</html>", 5).unwrap();
+        assert!(update.is_paused(0));
+        assert_eq!(update.message.as_deref(), Some("<html>Maintenance</html>"));
+        assert_eq!(
+            StatusUpdate::from_response(503, "", 5).unwrap().message,
+            None
+        );
+
+        assert!(StatusUpdate::from_response(500, "", 5).is_err());
+    }
+}
@@ -0,0 +1,28 @@
+//! Fixtures shared by the unit tests
+
+use std::sync::Arc;
+
+use crate::client::{TxClient, TxClientBuilder};
+use crate::transport::MockTransport;
+
+/// Private key the unit tests sign with
+pub(crate) const TEST_KEY: &str =
+    "0x0102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f202122232425262728";
+
+/// Builder of a client for account 1 on chain 304 sending its requests to
+/// `mock`, for tests to add what else they need before building
+pub(crate) fn mock_builder(mock: &Arc<MockTransport>) -> TxClientBuilder {
+    TxClient::builder()
+        .api_url("http://mock")
+        .private_key(TEST_KEY)
+        .account_index(1)
+        .chain_id(304)
+        .transport(mock.clone())
+}
+
+/// A client from [`mock_builder`] on a fresh mock
+pub(crate) fn mock_client() -> (TxClient, Arc<MockTransport>) {
+    let mock = Arc::new(MockTransport::new());
+    let tx_client = mock_builder(&mock).build().unwrap();
+    (tx_client, mock)
+}
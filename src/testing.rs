@@ -0,0 +1,712 @@
+//! Test harness with a mock Lighter server, enabled with the `test-util` feature
+//!
+//! [`MockLighter`] listens on localhost and serves the REST endpoints used by
+//! [`TxClient`](crate::client::TxClient) plus a minimal WebSocket stream, so
+//! code built on this crate can be tested end to end without the network.
+//!
+//! ```
+//! use lighter_rs::client::TxClient;
+//! use lighter_rs::testing::{MockLighter, SEND_TX_PATH};
+//! use lighter_rs::TxInfo;
+//!
+//! # async fn example() -> lighter_rs::Result<()> {
+//! let mock = MockLighter::start().await?;
+//! let tx_client = TxClient::new(
+//!     &mock.url(),
+//!     "0x0102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f202122232425262728",
+//!     1,
+//!     0,
+//!     304,
+//! )?;
+//!
+//! let order = tx_client
+//!     .create_limit_order(0, 1, 1000, 3_000_000_000, 0, false, None)
+//!     .await?;
+//! tx_client.send_transaction(&order).await?;
+//!
+//! let sent = mock.requests_to(SEND_TX_PATH);
+//! assert_eq!(sent[0].form_field("tx_info")?, Some(order.get_tx_info()?));
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use futures_util::{SinkExt, StreamExt};
+use serde_json::Value;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{broadcast, Notify};
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::errors::{LighterError, Result};
+use crate::loopback::serve_http;
+use crate::transport::{HttpRequest, HttpResponse};
+
+/// Path of the next nonce endpoint
+pub const NEXT_NONCE_PATH: &str = "/api/v1/nextNonce";
+/// Path of the transaction submission endpoint
+pub const SEND_TX_PATH: &str = "/api/v1/sendTx";
+
+#[derive(Default)]
+struct State {
+    canned: Mutex<HashMap<String, HttpResponse>>,
+    queued: Mutex<HashMap<String, VecDeque<HttpResponse>>>,
+    requests: Mutex<Vec<HttpRequest>>,
+    subscriptions: Mutex<Vec<Value>>,
+    subscribed: Notify,
+}
+
+impl State {
+    fn respond(&self, request: &HttpRequest) -> HttpResponse {
+        let path = request.path();
+        let queued = lock(&self.queued)
+            .get_mut(path)
+            .and_then(VecDeque::pop_front);
+        queued
+            .or_else(|| lock(&self.canned).get(path).cloned())
+            .unwrap_or_else(|| {
+                HttpResponse::new(
+                    404,
+                    format!(r#"{{"code":404,"message":"no mock response for {path}"}}"#),
+                )
+            })
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Mock Lighter API server listening on localhost
+///
+/// Out of the box, `nextNonce` answers with nonce 0 and `sendTx` accepts every
+/// transaction. Override any endpoint with [`MockLighter::set_response`], or
+/// script one-off replies with [`MockLighter::push_response`]; unknown paths
+/// get a 404. Every REST request is captured for assertions.
+///
+/// The WebSocket endpoint at [`MockLighter::ws_url`] greets each connection
+/// with a `connected` frame, records the subscription messages it receives and
+/// forwards frames sent with [`MockLighter::push_frame`] to every connection.
+///
+/// The server shuts down when the `MockLighter` is dropped.
+pub struct MockLighter {
+    rest_addr: SocketAddr,
+    ws_addr: SocketAddr,
+    state: Arc<State>,
+    frames: broadcast::Sender<String>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl MockLighter {
+    /// Start the REST and WebSocket servers on free localhost ports
+    pub async fn start() -> Result<Self> {
+        let bind_error =
+            |e: std::io::Error| LighterError::Other(format!("Failed to start MockLighter: {e}"));
+        let rest = TcpListener::bind("127.0.0.1:0").await.map_err(bind_error)?;
+        let ws = TcpListener::bind("127.0.0.1:0").await.map_err(bind_error)?;
+        let rest_addr = rest.local_addr().map_err(bind_error)?;
+        let ws_addr = ws.local_addr().map_err(bind_error)?;
+
+        let state = Arc::new(State::default());
+        let (frames, _) = broadcast::channel(1024);
+
+        let mock = Self {
+            rest_addr,
+            ws_addr,
+            state: state.clone(),
+            frames: frames.clone(),
+            tasks: vec![
+                tokio::spawn(serve_rest(rest, state.clone())),
+                tokio::spawn(serve_ws(ws, state, frames)),
+            ],
+        };
+        mock.set_response(NEXT_NONCE_PATH, 200, r#"{"code":200,"nonce":0}"#);
+        mock.set_response(
+            SEND_TX_PATH,
+            200,
+            r#"{"code":200,"tx_hash":"0x0000000000000000000000000000000000000000000000000000000000000000"}"#,
+        );
+        Ok(mock)
+    }
+
+    /// Base URL of the REST API, for [`TxClient::new`](crate::client::TxClient::new)
+    pub fn url(&self) -> String {
+        format!("http://{}", self.rest_addr)
+    }
+
+    /// URL of the WebSocket stream, for [`WsClientBuilder::url`](crate::ws_client::WsClientBuilder::url)
+    pub fn ws_url(&self) -> String {
+        format!("ws://{}/stream", self.ws_addr)
+    }
+
+    /// Answer every request to `path` with this response (unless one is queued)
+    pub fn set_response(&self, path: &str, status: u16, body: impl Into<String>) {
+        lock(&self.state.canned).insert(path.to_string(), HttpResponse::new(status, body));
+    }
+
+    /// Answer the next request to `path` with this response
+    ///
+    /// Queued responses are used in order before falling back to the one set
+    /// with [`MockLighter::set_response`].
+    pub fn push_response(&self, path: &str, status: u16, body: impl Into<String>) {
+        lock(&self.state.queued)
+            .entry(path.to_string())
+            .or_default()
+            .push_back(HttpResponse::new(status, body));
+    }
+
+    /// All REST requests received so far, in order
+    pub fn requests(&self) -> Vec<HttpRequest> {
+        lock(&self.state.requests).clone()
+    }
+
+    /// REST requests received so far for one path, in order
+    pub fn requests_to(&self, path: &str) -> Vec<HttpRequest> {
+        self.requests()
+            .into_iter()
+            .filter(|request| request.path() == path)
+            .collect()
+    }
+
+    /// Subscription messages received over WebSocket so far, in order
+    pub fn subscriptions(&self) -> Vec<Value> {
+        lock(&self.state.subscriptions).clone()
+    }
+
+    /// Wait until at least `count` subscription messages have been received
+    pub async fn wait_for_subscriptions(&self, count: usize) -> Vec<Value> {
+        loop {
+            let subscribed = self.state.subscribed.notified();
+            let subscriptions = self.subscriptions();
+            if subscriptions.len() >= count {
+                return subscriptions;
+            }
+            subscribed.await;
+        }
+    }
+
+    /// Send a text frame to every connected WebSocket client
+    ///
+    /// Frames pushed while no client is connected are dropped; wait for the
+    /// client's subscriptions first.
+    pub fn push_frame(&self, frame: impl Into<String>) {
+        let _ = self.frames.send(frame.into());
+    }
+}
+
+impl Drop for MockLighter {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+async fn serve_rest(listener: TcpListener, state: Arc<State>) {
+    let mut connections = tokio::task::JoinSet::new();
+    while let Ok((socket, _)) = listener.accept().await {
+        let state = state.clone();
+        connections.spawn(serve_http(socket, move |request| {
+            let response = state.respond(&request);
+            lock(&state.requests).push(request);
+            response
+        }));
+    }
+}
+
+async fn serve_ws(listener: TcpListener, state: Arc<State>, frames: broadcast::Sender<String>) {
+    let mut connections = tokio::task::JoinSet::new();
+    while let Ok((socket, _)) = listener.accept().await {
+        connections.spawn(serve_ws_connection(
+            socket,
+            state.clone(),
+            frames.subscribe(),
+        ));
+    }
+}
+
+async fn serve_ws_connection(
+    socket: TcpStream,
+    state: Arc<State>,
+    mut frames: broadcast::Receiver<String>,
+) {
+    let Ok(stream) = tokio_tungstenite::accept_async(socket).await else {
+        return;
+    };
+    let (mut write, mut read) = stream.split();
+    if write
+        .send(Message::Text(r#"{"type":"connected"}"#.to_string()))
+        .await
+        .is_err()
+    {
+        return;
+    }
+
+    loop {
+        tokio::select! {
+            message = read.next() => match message {
+                Some(Ok(Message::Text(text))) => {
+                    if let Ok(subscription) = serde_json::from_str(&text) {
+                        lock(&state.subscriptions).push(subscription);
+                        state.subscribed.notify_waiters();
+                    }
+                }
+                Some(Ok(Message::Ping(payload))) => {
+                    let _ = write.send(Message::Pong(payload)).await;
+                }
+                Some(Ok(_)) => {}
+                Some(Err(_)) | None => return,
+            },
+            frame = frames.recv() => match frame {
+                Ok(frame) => {
+                    if write.send(Message::Text(frame)).await.is_err() {
+                        return;
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(_)) => {}
+                Err(broadcast::error::RecvError::Closed) => return,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::TxClient;
+    use crate::test_support::TEST_KEY;
+    use crate::types::TxInfo;
+    use crate::ws_client::WsClient;
+    use reqwest::Method;
+
+    #[tokio::test]
+    async fn test_place_order_end_to_end() {
+        let mock = MockLighter::start().await.unwrap();
+        mock.set_response(NEXT_NONCE_PATH, 200, r#"{"code":200,"nonce":41}"#);
+        mock.push_response(SEND_TX_PATH, 200, r#"{"code":200,"tx_hash":"0xfeed"}"#);
+
+        let tx_client = TxClient::new(&mock.url(), TEST_KEY, 1, 0, 304).unwrap();
+        let order = tx_client
+            .create_limit_order(3, 77, 1000, 3_000_000_000, 1, false, None)
+            .await
+            .unwrap();
+        let response = tx_client.send_transaction(&order).await.unwrap();
+        assert!(response.is_success());
+        assert_eq!(response.tx_hash.as_deref(), Some("0xfeed"));
+
+        let nonce_requests = mock.requests_to(NEXT_NONCE_PATH);
+        assert_eq!(nonce_requests.len(), 1);
+        assert_eq!(
+            nonce_requests[0].url,
+            "/api/v1/nextNonce?account_index=1&api_key_index=0"
+        );
+
+        let sent = mock.requests_to(SEND_TX_PATH);
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].method, Method::POST);
+        assert_eq!(
+            sent[0].headers["content-type"],
+            "application/x-www-form-urlencoded"
+        );
+        assert_eq!(
+            sent[0].form_field("tx_type").unwrap().as_deref(),
+            Some("14")
+        );
+        assert_eq!(sent[0].form_field("price_protection").unwrap(), None);
+
+        let tx_info: Value =
+            serde_json::from_str(&sent[0].form_field("tx_info").unwrap().unwrap()).unwrap();
+        assert_eq!(tx_info["AccountIndex"], 1);
+        assert_eq!(tx_info["MarketIndex"], 3);
+        assert_eq!(tx_info["ClientOrderIndex"], 77);
+        assert_eq!(tx_info["BaseAmount"], 1000);
+        assert_eq!(tx_info["Price"], 3_000_000_000u32);
+        assert_eq!(tx_info["IsAsk"], 1);
+        assert_eq!(tx_info["Nonce"], 41);
+        assert_eq!(
+            tx_info,
+            serde_json::from_str::<Value>(&order.get_tx_info().unwrap()).unwrap()
+        );
+        assert!(tx_info["Sig"].is_string());
+    }
+
+    #[tokio::test]
+    async fn test_scripted_rejection_and_unknown_path() {
+        let mock = MockLighter::start().await.unwrap();
+        mock.push_response(
+            SEND_TX_PATH,
+            200,
+            r#"{"code":21104,"message":"invalid nonce"}"#,
+        );
+
+        let http = crate::client::HTTPClient::new(&mock.url()).unwrap();
+        let rejected = http.send_tx(14, "{}").await.unwrap();
+        assert!(rejected.is_nonce_error());
+        // The queue is drained, so the canned default answers again
+        assert!(http.send_tx(14, "{}").await.unwrap().is_success());
+
+        let response = reqwest::Client::new()
+            .get(format!("{}/api/v1/unknown", mock.url()))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 404);
+        assert_eq!(mock.requests().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_ws_subscriptions_and_pushed_frames() {
+        let mock = MockLighter::start().await.unwrap();
+        let ws_client = WsClient::builder()
+            .url(mock.ws_url())
+            .order_books(vec![0])
+            .accounts(vec![42])
+            .build()
+            .unwrap();
+
+        let (books, mut received) = tokio::sync::mpsc::unbounded_channel();
+        let run = tokio::spawn(async move {
+            ws_client
+                .run(
+                    move |market_id, order_book| {
+                        let _ = books.send((market_id, order_book));
+                    },
+                    |_, _| {},
+                )
+                .await
+        });
+
+        let subscriptions = mock.wait_for_subscriptions(2).await;
+        assert_eq!(subscriptions[0]["channel"], "order_book/0");
+        assert_eq!(subscriptions[1]["channel"], "account_all/42");
+
+        mock.push_frame(
+            r#"{"type":"subscribed/order_book","channel":"order_book:0","order_book":{"asks":[{"price":"3024.66","size":"0.1000"}],"bids":[]}}"#,
+        );
+        let (market_id, order_book) = received.recv().await.unwrap();
+        assert_eq!(market_id, "0");
+        assert_eq!(order_book.asks[0].price.to_string(), "3024.66");
+
+        // The client returns once the server goes away
+        drop(mock);
+        tokio::time::timeout(std::time::Duration::from_secs(5), run)
+            .await
+            .expect("client should stop when the server shuts down")
+            .unwrap()
+            .ok();
+    }
+
+    #[tokio::test]
+    async fn test_ws_mid_price_ages_while_the_book_is_frozen() {
+        use crate::clock::ManualClock;
+        use rust_decimal::Decimal;
+        use std::time::Duration;
+
+        let mock = MockLighter::start().await.unwrap();
+        let clock = Arc::new(ManualClock::at_ms(0));
+        let ws_client = Arc::new(
+            WsClient::builder()
+                .url(mock.ws_url())
+                .order_books(vec![0])
+                .clock(clock.clone())
+                .build()
+                .unwrap(),
+        );
+        let (books, mut received) = tokio::sync::mpsc::unbounded_channel();
+        let runner = ws_client.clone();
+        let run = tokio::spawn(async move {
+            runner
+                .run(
+                    move |_, _| {
+                        let _ = books.send(());
+                    },
+                    |_, _| {},
+                )
+                .await
+        });
+        mock.wait_for_subscriptions(1).await;
+        assert_eq!(ws_client.mid_price("0").await, None);
+
+        mock.push_frame(
+            r#"{"type":"subscribed/order_book","channel":"order_book:0","order_book":{"asks":[{"price":"3025.00","size":"1"}],"bids":[{"price":"3024.00","size":"1"}]}}"#,
+        );
+        received.recv().await.unwrap();
+        let max_staleness = Duration::from_secs(1);
+        let mid = ws_client.mid_price("0").await.unwrap();
+        assert_eq!(mid.value, Decimal::new(302450, 2));
+        assert!(mid.fresh(clock.as_ref(), max_staleness).is_ok());
+
+        // No updates for two seconds: the same mid is now stale
+        clock.advance(Duration::from_secs(2));
+        let mid = ws_client.mid_price("0").await.unwrap();
+        assert_eq!(mid.age(clock.as_ref()), Duration::from_secs(2));
+        assert!(mid.fresh(clock.as_ref(), max_staleness).is_err());
+
+        // An update restamps it
+        mock.push_frame(
+            r#"{"type":"update/order_book","channel":"order_book:0","order_book":{"asks":[{"price":"3026.00","size":"1"}],"bids":[]}}"#,
+        );
+        received.recv().await.unwrap();
+        let mid = ws_client.mid_price("0").await.unwrap();
+        assert_eq!(mid.value, Decimal::new(302450, 2));
+        assert_eq!(mid.age(clock.as_ref()), Duration::ZERO);
+
+        run.abort();
+    }
+
+    #[tokio::test]
+    async fn test_ws_subscription_timeout_and_resubscribe() {
+        use crate::ws_client::{SubscriptionStatus, WsEvent};
+        use std::time::Duration;
+
+        let mock = MockLighter::start().await.unwrap();
+        let ws_client = Arc::new(
+            WsClient::builder()
+                .url(mock.ws_url())
+                .order_books(vec![0, 1])
+                .subscription_timeout(Duration::from_millis(200))
+                .build()
+                .unwrap(),
+        );
+        let mut events = ws_client.subscribe_events();
+        let run = |ws_client: Arc<WsClient>| {
+            tokio::spawn(async move { ws_client.run(|_, _| {}, |_, _| {}).await })
+        };
+
+        let first = run(ws_client.clone());
+        mock.wait_for_subscriptions(2).await;
+        assert_eq!(
+            ws_client.subscription_status("order_book/1"),
+            Some(SubscriptionStatus::Pending)
+        );
+        assert_eq!(ws_client.subscription_status("order_book/7"), None);
+
+        // The server confirms market 0 and withholds market 1
+        mock.push_frame(
+            r#"{"type":"subscribed/order_book","channel":"order_book:0","order_book":{"asks":[],"bids":[]}}"#,
+        );
+        let event = tokio::time::timeout(Duration::from_secs(5), events.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            event,
+            WsEvent::SubscriptionTimedOut {
+                channel: "order_book/1".to_string()
+            }
+        );
+        assert_eq!(
+            ws_client.subscription_status("order_book/0"),
+            Some(SubscriptionStatus::Confirmed)
+        );
+        assert_eq!(
+            ws_client.subscription_status("order_book/1"),
+            Some(SubscriptionStatus::TimedOut)
+        );
+
+        // A new connection tracks the resent subscriptions afresh, and data
+        // on a channel confirms it
+        first.abort();
+        let second = run(ws_client.clone());
+        mock.wait_for_subscriptions(4).await;
+        assert_eq!(
+            ws_client.subscription_status("order_book/1"),
+            Some(SubscriptionStatus::Pending)
+        );
+        mock.push_frame(
+            r#"{"type":"update/order_book","channel":"order_book:1","order_book":{"asks":[],"bids":[]}}"#,
+        );
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while ws_client.subscription_status("order_book/1")
+                != Some(SubscriptionStatus::Confirmed)
+            {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .unwrap();
+        second.abort();
+    }
+
+    #[tokio::test]
+    async fn test_tx_stats_after_a_scripted_session() {
+        use crate::session_stats::TxStats;
+        use crate::types::{CancelOrderTxReq, CreateOrderTxReq, DecodedTx, SignedTx};
+        use crate::{ORDER_TYPE_LIMIT, TIME_IN_FORCE_GOOD_TILL_TIME};
+        use std::collections::BTreeMap;
+
+        const SEND_TX_BATCH_PATH: &str = "/api/v1/sendTxBatch";
+
+        let mock = MockLighter::start().await.unwrap();
+        let tx_client = TxClient::new(&mock.url(), TEST_KEY, 1, 0, 304).unwrap();
+        let reqs: Vec<CreateOrderTxReq> = (1..=3)
+            .map(|i| CreateOrderTxReq {
+                market_index: 0,
+                client_order_index: i,
+                base_amount: 1000,
+                price: 300000,
+                is_ask: 0,
+                order_type: ORDER_TYPE_LIMIT,
+                time_in_force: TIME_IN_FORCE_GOOD_TILL_TIME,
+                reduce_only: 0,
+                trigger_price: 0,
+                order_expiry: 0,
+            })
+            .collect();
+        let orders = tx_client.create_orders(&reqs, None).await.unwrap();
+
+        // Accepted, then refused for its nonce
+        tx_client.send_transaction(&orders[0]).await.unwrap();
+        mock.push_response(
+            SEND_TX_PATH,
+            200,
+            r#"{"code":21104,"message":"invalid nonce"}"#,
+        );
+        tx_client
+            .send_signed(&SignedTx::new(&orders[1]).unwrap())
+            .await
+            .unwrap();
+
+        // A batch applying its first transaction and refusing the second
+        let cancel = tx_client
+            .cancel_order(
+                &CancelOrderTxReq {
+                    market_index: 0,
+                    index: 9,
+                },
+                None,
+            )
+            .await
+            .unwrap();
+        mock.push_response(
+            SEND_TX_BATCH_PATH,
+            200,
+            r#"{"code":21120,"message":"order not found","tx_hash":["0xaa"]}"#,
+        );
+        let batch = [
+            SignedTx::new(&orders[2]).unwrap(),
+            SignedTx::new(&cancel).unwrap(),
+        ];
+        tx_client.send_batch(&batch).await.unwrap();
+
+        // The refused order signed again
+        tx_client
+            .resign(vec![DecodedTx::CreateOrder(orders[1].clone())])
+            .await
+            .unwrap();
+
+        let bytes_sent: usize = mock
+            .requests_to(SEND_TX_PATH)
+            .iter()
+            .chain(&mock.requests_to(SEND_TX_BATCH_PATH))
+            .map(|request| request.body.len())
+            .sum();
+        let stats = tx_client.stats();
+        assert_eq!(
+            stats,
+            TxStats {
+                orders_signed: 4,
+                txs_signed: 5,
+                submitted: 4,
+                accepted: 2,
+                rejected: BTreeMap::from([(21104, 1), (21120, 1)]),
+                unconfirmed: 0,
+                retries: 1,
+                bytes_sent: bytes_sent as u64,
+            }
+        );
+        let json = serde_json::to_string(&stats).unwrap();
+        assert_eq!(serde_json::from_str::<TxStats>(&json).unwrap(), stats);
+
+        tx_client.reset_stats();
+        assert_eq!(tx_client.stats(), TxStats::default());
+    }
+
+    #[tokio::test]
+    async fn test_ws_stats_after_a_scripted_session() {
+        use crate::clock::ManualClock;
+        use std::collections::BTreeMap;
+        use std::time::Duration;
+
+        let mock = MockLighter::start().await.unwrap();
+        let clock = Arc::new(ManualClock::at_ms(0));
+        let ws_client = Arc::new(
+            WsClient::builder()
+                .url(mock.ws_url())
+                .order_books(vec![0])
+                .accounts(vec![42])
+                .clock(clock.clone())
+                .build()
+                .unwrap(),
+        );
+        let (updates, mut received) = tokio::sync::mpsc::unbounded_channel();
+        let run = || {
+            let runner = ws_client.clone();
+            let books = updates.clone();
+            let accounts = updates.clone();
+            tokio::spawn(async move {
+                runner
+                    .run(
+                        move |_, _| {
+                            let _ = books.send(());
+                        },
+                        move |_, _| {
+                            let _ = accounts.send(());
+                        },
+                    )
+                    .await
+            })
+        };
+
+        let first = run();
+        mock.wait_for_subscriptions(2).await;
+        mock.push_frame(
+            r#"{"type":"subscribed/order_book","channel":"order_book:0","order_book":{"asks":[{"price":"3025.00","size":"1"}],"bids":[]}}"#,
+        );
+        received.recv().await.unwrap();
+        clock.advance(Duration::from_secs(3));
+        mock.push_frame(
+            r#"{"type":"update/order_book","channel":"order_book:0","order_book":{"asks":[{"price":"3026.00","size":"1"}],"bids":[]}}"#,
+        );
+        received.recv().await.unwrap();
+        mock.push_frame(
+            r#"{"type":"update/account_all","channel":"account_all:42","usdc_balance":"10"}"#,
+        );
+        received.recv().await.unwrap();
+        // An undecodable frame ends the connection
+        mock.push_frame("{not json");
+        assert!(first.await.unwrap().is_err());
+
+        let stats = ws_client.stats();
+        assert_eq!(stats.connections, 1);
+        assert_eq!(stats.reconnects, 0);
+        assert_eq!(
+            stats.messages,
+            BTreeMap::from([
+                ("order_book/0".to_string(), 2),
+                ("account_all/42".to_string(), 1),
+            ])
+        );
+        // The `connected` greeting
+        assert_eq!(stats.other_messages, 1);
+        assert_eq!(stats.decode_errors, 1);
+        assert_eq!(stats.max_gap_ms, 3000);
+
+        let second = run();
+        mock.wait_for_subscriptions(4).await;
+        let stats = ws_client.stats();
+        assert_eq!(stats.connections, 2);
+        assert_eq!(stats.reconnects, 1);
+        assert_eq!(stats.other_messages, 2);
+
+        ws_client.reset_stats();
+        let stats = ws_client.stats();
+        assert_eq!(stats.connections, 0);
+        assert_eq!(stats.messages["order_book/0"], 0);
+        assert_eq!(stats.max_gap_ms, 0);
+        second.abort();
+    }
+}
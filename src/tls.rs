@@ -0,0 +1,102 @@
+//! TLS backend selection
+//!
+//! The `rustls-tls` (default) and `native-tls` features choose the TLS
+//! implementation for the REST client and the WebSocket client together. Both
+//! clients are configured explicitly from the same features, so a TLS library
+//! enabled transitively by another crate can't put them on different backends.
+//! When both features are enabled, `native-tls` is used.
+//!
+//! On wasm32 the browser handles TLS and neither feature is needed.
+
+// Native builds talk to the API over https and wss
+#[cfg(all(
+    not(target_arch = "wasm32"),
+    not(any(feature = "rustls-tls", feature = "native-tls"))
+))]
+compile_error!("lighter-rs needs a TLS backend: enable the `rustls-tls` or `native-tls` feature");
+
+/// A TLS implementation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TlsBackend {
+    /// rustls with the Mozilla (webpki) root certificates
+    Rustls,
+    /// The platform TLS library and system trust store
+    NativeTls,
+}
+
+/// The TLS backend used by this build, if any
+#[cfg(feature = "native-tls")]
+pub const TLS_BACKEND: Option<TlsBackend> = Some(TlsBackend::NativeTls);
+/// The TLS backend used by this build, if any
+#[cfg(all(feature = "rustls-tls", not(feature = "native-tls")))]
+pub const TLS_BACKEND: Option<TlsBackend> = Some(TlsBackend::Rustls);
+/// The TLS backend used by this build, if any
+#[cfg(not(any(feature = "rustls-tls", feature = "native-tls")))]
+pub const TLS_BACKEND: Option<TlsBackend> = None;
+
+/// Select the TLS backend on a `reqwest` client builder
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn configure_http(builder: reqwest::ClientBuilder) -> reqwest::ClientBuilder {
+    #[cfg(feature = "native-tls")]
+    let builder = builder.use_native_tls();
+    #[cfg(all(feature = "rustls-tls", not(feature = "native-tls")))]
+    let builder = builder.use_rustls_tls();
+    builder
+}
+
+/// Build the WebSocket connector for the selected TLS backend
+///
+/// Naming the connector variant also checks at compile time that
+/// `tokio-tungstenite` was built with the matching TLS feature.
+#[cfg(all(feature = "native", not(target_arch = "wasm32")))]
+pub(crate) fn ws_connector() -> crate::Result<tokio_tungstenite::Connector> {
+    #[cfg(feature = "native-tls")]
+    {
+        let connector = native_tls::TlsConnector::new().map_err(|e| {
+            crate::LighterError::InvalidConfiguration(format!("Failed to set up TLS: {e}"))
+        })?;
+        Ok(tokio_tungstenite::Connector::NativeTls(connector))
+    }
+
+    #[cfg(all(feature = "rustls-tls", not(feature = "native-tls")))]
+    {
+        let mut roots = rustls::RootCertStore::empty();
+        roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+        let config = rustls::ClientConfig::builder()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        Ok(tokio_tungstenite::Connector::Rustls(std::sync::Arc::new(
+            config,
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backend_matches_features() {
+        let expected = if cfg!(feature = "native-tls") {
+            Some(TlsBackend::NativeTls)
+        } else if cfg!(feature = "rustls-tls") {
+            Some(TlsBackend::Rustls)
+        } else {
+            None
+        };
+        assert_eq!(TLS_BACKEND, expected);
+    }
+
+    #[cfg(feature = "native")]
+    #[test]
+    fn test_ws_connector_matches_backend() {
+        let backend = match ws_connector().unwrap() {
+            #[cfg(feature = "native-tls")]
+            tokio_tungstenite::Connector::NativeTls(_) => Some(TlsBackend::NativeTls),
+            #[cfg(feature = "rustls-tls")]
+            tokio_tungstenite::Connector::Rustls(_) => Some(TlsBackend::Rustls),
+            _ => None,
+        };
+        assert_eq!(backend, TLS_BACKEND);
+    }
+}
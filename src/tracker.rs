@@ -0,0 +1,2137 @@
+//! Order lifecycle tracking keyed by client order index
+//!
+//! [`OrderTracker`] submits orders through a [`TxClient`], then follows each
+//! one through the account WebSocket channel until it is filled, cancelled or
+//! rejected. After a restart, [`OrderTracker::reconcile`] seeds it from the
+//! active orders endpoint, and [`OrderTracker::adopt_existing`] does the same
+//! for only the orders in one bot's
+//! [namespace](crate::order_namespace::ClientOrderNamespace).
+//!
+//! Cancels and modifies need the order index the exchange assigned, which
+//! only arrives on the account channel after the order is accepted.
+//! [`OrderTracker::await_order_index`] waits for it, and
+//! [`OrderTracker::cancel_order`] and [`OrderTracker::modify_order`] take an
+//! [`OrderRef`] of either kind and resolve it themselves.
+//!
+//! Good-till-time orders leave the book once their expiry passes.
+//! [`OrderTracker::orders_expiring_within`] lists the open orders about to
+//! expire and [`OrderTracker::refresh_order`] places one again with a later
+//! expiry; the [`order_expiry`](crate::order_expiry) module watches for them
+//! on a timer.
+//!
+//! The account channel and the REST API can disagree on an order for a few
+//! seconds. The tracker keeps each source's latest report with its
+//! timestamp and prefers the newer one, so an order reported open after it
+//! was reported filled or cancelled stays open, keeping its share of the risk
+//! limits. A REST report that an order ended is only acted on once the
+//! account channel agrees, or once the [`ConflictPolicy`] window has passed
+//! without the account channel reporting the order again; REST reports that
+//! can't tell filled from cancelled always wait for the account channel. A
+//! disagreement lasting longer than the window is broadcast as
+//! [`TrackerEvent::StateConflict`]; call [`OrderTracker::check_conflicts`]
+//! periodically so held states are settled without new frames.
+//!
+//! ```no_run
+//! use lighter_rs::client::TxClient;
+//! use lighter_rs::tracker::OrderTracker;
+//! use lighter_rs::ws_client::WsClient;
+//! use std::sync::Arc;
+//! use std::time::Duration;
+//!
+//! # async fn example(tx_client: TxClient, ws_client: WsClient) -> lighter_rs::Result<()> {
+//! let tracker = OrderTracker::new(Arc::new(tx_client));
+//! tracker.reconcile(&[0], None).await?;
+//!
+//! let feed = tracker.clone();
+//! tokio::spawn(async move {
+//!     ws_client
+//!         .run(|_, _| {}, move |_, data| feed.apply_account_frame(&data))
+//!         .await
+//! });
+//!
+//! let order = tracker
+//!     .tx_client()
+//!     .create_limit_order(0, 42, 1000, 300000, 0, false, None)
+//!     .await?;
+//! tracker.submit(&order).await?;
+//! let state = tracker.await_terminal(42, Duration::from_secs(30)).await?;
+//! println!("order 42 ended as {state:?}");
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::{broadcast, watch};
+
+use crate::client::{ActiveOrder, HTTPClient, TxClient, TxResponse};
+use crate::constants::*;
+use crate::errors::{LighterError, Result};
+use crate::order_namespace::ClientOrderNamespace;
+use crate::risk::RiskState;
+use crate::snapshot_sync::{account_frame_timestamp, now_ms, Ingest, SnapshotSync, SyncKey};
+use crate::state_store::{load_json, save_json, RestoreReport, StateStore};
+use crate::types::{
+    CancelOrderTxReq, CreateOrderTxReq, L2CancelOrderTxInfo, L2CreateOrderTxInfo,
+    L2ModifyOrderTxInfo, ModifyOrderTxReq, SignedTx, TransactOpts, TxInfo,
+};
+use crate::utils::{json_decimal, json_integer};
+
+/// Name the open orders are saved under in a [`StateStore`]
+const ORDERS_STATE: &str = "orders";
+
+/// Tracker events kept for slow subscribers
+const EVENT_BUFFER: usize = 64;
+
+/// Where an order is in its lifecycle
+///
+/// Filled amounts are decimals, as the account channel reports them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OrderState {
+    /// Sent to the API, no answer yet
+    Submitted,
+    /// Accepted by the API, not yet seen on the book
+    Acknowledged,
+    /// Resting on the book
+    Open,
+    /// Partly filled, with the total filled base amount
+    PartiallyFilled(Decimal),
+    Filled,
+    Cancelled,
+    /// Refused by the API, with its response code
+    Rejected(u16),
+}
+
+impl OrderState {
+    /// Whether the order can't change any more
+    pub fn is_terminal(&self) -> bool {
+        matches!(
+            self,
+            OrderState::Filled | OrderState::Cancelled | OrderState::Rejected(_)
+        )
+    }
+
+    /// Whether the order may still be on the book
+    pub fn is_open(&self) -> bool {
+        !self.is_terminal()
+    }
+
+    /// Position in the lifecycle; states never move to a lower rank
+    fn rank(&self) -> u8 {
+        match self {
+            OrderState::Submitted => 0,
+            OrderState::Acknowledged => 1,
+            OrderState::Open => 2,
+            OrderState::PartiallyFilled(_) => 3,
+            OrderState::Filled | OrderState::Cancelled | OrderState::Rejected(_) => 4,
+        }
+    }
+
+    /// Map an order status string from the API
+    ///
+    /// Statuses the tracker doesn't act on, such as untriggered stop orders,
+    /// map to `None`.
+    fn from_status(status: &str, filled_base_amount: Decimal) -> Option<Self> {
+        match status {
+            "filled" => Some(OrderState::Filled),
+            "open" | "pending" | "in-progress" => {
+                if filled_base_amount > Decimal::ZERO {
+                    Some(OrderState::PartiallyFilled(filled_base_amount))
+                } else {
+                    Some(OrderState::Open)
+                }
+            }
+            status if status.starts_with("canceled") || status.starts_with("cancelled") => {
+                Some(OrderState::Cancelled)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Order change decoded from the account channel
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OrderEvent {
+    /// The exchange reported the order's state
+    Update {
+        client_order_index: i64,
+        order_index: i64,
+        market_index: u8,
+        state: OrderState,
+    },
+    /// Part of the order filled
+    Fill {
+        client_order_index: i64,
+        base_amount: Decimal,
+    },
+}
+
+impl OrderEvent {
+    /// Decode the order events in an `account_all` frame
+    ///
+    /// Reads order statuses from `orders` and fills from `trades` entries that
+    /// carry a `client_order_index`. Both may be a flat list or grouped by
+    /// market. Entries that don't parse are skipped.
+    pub fn from_account_frame(data: &Value) -> Vec<OrderEvent> {
+        let mut events: Vec<OrderEvent> = entries(data.get("orders"))
+            .filter_map(|order| {
+                let filled_base_amount = json_decimal(order.get("filled_base_amount"));
+                let state = OrderState::from_status(
+                    order.get("status")?.as_str()?,
+                    filled_base_amount.unwrap_or_default(),
+                )?;
+                Some(OrderEvent::Update {
+                    client_order_index: json_integer(order.get("client_order_index"))?,
+                    order_index: json_integer(order.get("order_index"))?,
+                    market_index: json_integer(order.get("market_index"))?.try_into().ok()?,
+                    state,
+                })
+            })
+            .collect();
+        events.extend(entries(data.get("trades")).filter_map(|trade| {
+            Some(OrderEvent::Fill {
+                client_order_index: json_integer(trade.get("client_order_index"))?,
+                base_amount: json_decimal(trade.get("size"))?,
+            })
+        }));
+        events
+    }
+
+    fn client_order_index(&self) -> i64 {
+        match self {
+            OrderEvent::Update {
+                client_order_index, ..
+            }
+            | OrderEvent::Fill {
+                client_order_index, ..
+            } => *client_order_index,
+        }
+    }
+}
+
+/// Objects in a list, or in lists grouped under an object's keys
+fn entries(value: Option<&Value>) -> impl Iterator<Item = &Value> {
+    let lists: Vec<&Vec<Value>> = match value {
+        Some(Value::Array(list)) => vec![list],
+        Some(Value::Object(groups)) => groups.values().filter_map(Value::as_array).collect(),
+        _ => Vec::new(),
+    };
+    lists.into_iter().flatten()
+}
+
+/// Where an order's state was reported
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StateSource {
+    /// The account channel
+    WebSocket,
+    /// The REST API, such as the active orders endpoint
+    Rest,
+}
+
+/// How the tracker settles the WebSocket and REST disagreeing on an order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConflictPolicy {
+    /// How long a disagreement may last before it is reported, and how long
+    /// a REST report that an order ended waits for the account channel
+    pub window: Duration,
+}
+
+impl Default for ConflictPolicy {
+    fn default() -> Self {
+        Self {
+            window: Duration::from_secs(5),
+        }
+    }
+}
+
+/// Something risk checks should hear about
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TrackerEvent {
+    /// The WebSocket and REST have disagreed on whether an order is still
+    /// open for longer than the conflict window; the order is kept open
+    /// until they agree
+    StateConflict {
+        client_order_index: i64,
+        ws_state: OrderState,
+        /// `None` when the active orders endpoint no longer lists the order
+        rest_state: Option<OrderState>,
+    },
+}
+
+/// One source's latest report on an order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Observation {
+    /// `None` for an order the active orders endpoint no longer lists, which
+    /// is filled or cancelled without saying which
+    state: Option<OrderState>,
+    /// Source timestamp, in milliseconds since the Unix epoch
+    at_ms: i64,
+}
+
+impl Observation {
+    fn is_terminal(&self) -> bool {
+        self.state.is_none_or(|state| state.is_terminal())
+    }
+}
+
+/// What the WebSocket and REST last said about one order
+#[derive(Debug, Default)]
+struct Views {
+    ws: Option<Observation>,
+    rest: Option<Observation>,
+    /// Clock time a terminal report started waiting for the other source
+    pending_since_ms: Option<i64>,
+    /// Clock time the current conflict was first seen
+    conflict_since_ms: Option<i64>,
+    reported: bool,
+}
+
+impl Views {
+    /// Record a report, unless the source already said something newer
+    fn observe(&mut self, source: StateSource, observation: Observation) {
+        let slot = match source {
+            StateSource::WebSocket => &mut self.ws,
+            StateSource::Rest => &mut self.rest,
+        };
+        if slot.is_none_or(|seen| seen.at_ms <= observation.at_ms) {
+            *slot = Some(observation);
+        }
+    }
+
+    /// State the order should move to, and whether the sources conflict
+    fn settle(&mut self, now_ms: i64, window_ms: i64) -> (Option<OrderState>, bool) {
+        let (ws, rest) = match (self.ws, self.rest) {
+            (Some(ws), Some(rest)) => (ws, rest),
+            // A source on its own is trusted
+            (only, None) | (None, only) => {
+                self.pending_since_ms = None;
+                return (only.and_then(|seen| seen.state), false);
+            }
+        };
+        let newer = if rest.at_ms > ws.at_ms { rest } else { ws };
+        if ws.is_terminal() == rest.is_terminal() {
+            self.pending_since_ms = None;
+            // REST may not know which terminal state it was
+            return (newer.state.or(ws.state), false);
+        }
+
+        let (terminal, open) = if ws.is_terminal() {
+            (ws, rest)
+        } else {
+            (rest, ws)
+        };
+        if open.at_ms > terminal.at_ms {
+            // Contradicted by a newer report
+            self.pending_since_ms = None;
+            return (open.state, true);
+        }
+        if terminal == ws {
+            // The account channel saw the order end after REST saw it open
+            self.pending_since_ms = None;
+            return (terminal.state, false);
+        }
+        let Some(state) = terminal.state else {
+            // Gone from REST, but filled or cancelled is anyone's guess
+            return (None, true);
+        };
+        if open.at_ms <= terminal.at_ms - window_ms {
+            self.pending_since_ms = None;
+            return (Some(state), false);
+        }
+        let since = *self.pending_since_ms.get_or_insert(now_ms);
+        if now_ms - since >= window_ms {
+            self.pending_since_ms = None;
+            (Some(state), false)
+        } else {
+            (None, false)
+        }
+    }
+
+    /// Track a conflict's duration, returning the event once it has lasted
+    /// `window_ms`
+    fn track_conflict(
+        &mut self,
+        conflicted: bool,
+        client_order_index: i64,
+        now_ms: i64,
+        window_ms: i64,
+    ) -> Option<TrackerEvent> {
+        if !conflicted {
+            self.conflict_since_ms = None;
+            self.reported = false;
+            return None;
+        }
+        let since = *self.conflict_since_ms.get_or_insert(now_ms);
+        if self.reported || now_ms - since < window_ms {
+            return None;
+        }
+        self.reported = true;
+        Some(TrackerEvent::StateConflict {
+            client_order_index,
+            ws_state: self.ws?.state?,
+            rest_state: self.rest?.state,
+        })
+    }
+
+    fn is_unsettled(&self) -> bool {
+        self.pending_since_ms.is_some() || self.conflict_since_ms.is_some()
+    }
+}
+
+/// An order, by the index the client picked or the one the exchange assigned
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OrderRef {
+    ClientOrderIndex(i64),
+    /// Exchange order index
+    OrderIndex(i64),
+}
+
+/// One tracked order
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrackedOrder {
+    pub client_order_index: i64,
+    pub market_index: u8,
+    /// Exchange order index, once the account channel has reported it
+    pub order_index: Option<i64>,
+    pub state: OrderState,
+    /// Base amount the account channel has reported filled, kept once the
+    /// order is cancelled
+    #[serde(default)]
+    pub filled_base_amount: Decimal,
+    /// Expiry in milliseconds since the Unix epoch, for orders that rest
+    /// until one
+    #[serde(default)]
+    pub order_expiry: Option<i64>,
+    /// Request the order was signed from, when it was submitted through the
+    /// tracker
+    #[serde(default)]
+    pub placed: Option<CreateOrderTxReq>,
+    /// Strategy the order was signed for, see the
+    /// [`strategy`](crate::strategy) module
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub strategy_id: Option<String>,
+}
+
+impl TrackedOrder {
+    /// Move to `state` unless that would go back in the lifecycle
+    fn advance(&mut self, state: OrderState) -> bool {
+        let mut filled_more = false;
+        if let OrderState::PartiallyFilled(filled) = state {
+            filled_more = filled > self.filled_base_amount;
+            self.filled_base_amount = self.filled_base_amount.max(filled);
+        }
+        if self.state.is_terminal() || state.rank() < self.state.rank() {
+            return filled_more;
+        }
+        if let (OrderState::PartiallyFilled(current), OrderState::PartiallyFilled(new)) =
+            (self.state, state)
+        {
+            if new <= current {
+                return false;
+            }
+        }
+        let changed = self.state != state;
+        self.state = state;
+        changed
+    }
+}
+
+struct Inner {
+    orders: Mutex<HashMap<i64, TrackedOrder>>,
+    /// Holds account frames back while `reconcile` fetches the snapshot
+    sync: Mutex<SnapshotSync<Value>>,
+    /// Each source's latest report, by client order index
+    views: Mutex<HashMap<i64, Views>>,
+    /// Bumped on every state change to wake `await_terminal`
+    changed: watch::Sender<u64>,
+    events: broadcast::Sender<TrackerEvent>,
+}
+
+struct OpenOrders(Arc<Inner>);
+
+impl RiskState for OpenOrders {
+    fn open_orders(&self) -> Option<usize> {
+        let orders = self
+            .0
+            .orders
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        Some(
+            orders
+                .values()
+                .filter(|tracked| tracked.state.is_open())
+                .count(),
+        )
+    }
+}
+
+/// Tracks submitted orders by client order index
+///
+/// Cloning is cheap and every clone shares the same state, so one clone can
+/// consume the WebSocket feed while another submits orders.
+#[derive(Clone)]
+pub struct OrderTracker {
+    tx_client: Arc<TxClient>,
+    inner: Arc<Inner>,
+    policy: ConflictPolicy,
+}
+
+impl OrderTracker {
+    /// Create a tracker that submits orders through `tx_client`
+    pub fn new(tx_client: Arc<TxClient>) -> Self {
+        let (changed, _) = watch::channel(0);
+        let (events, _) = broadcast::channel(EVENT_BUFFER);
+        Self {
+            tx_client,
+            inner: Arc::new(Inner {
+                orders: Mutex::new(HashMap::new()),
+                sync: Mutex::new(SnapshotSync::new(SyncKey::Monotonic)),
+                views: Mutex::new(HashMap::new()),
+                changed,
+                events,
+            }),
+            policy: ConflictPolicy::default(),
+        }
+    }
+
+    /// Settle WebSocket and REST disagreements with `policy`
+    pub fn conflict_policy(mut self, policy: ConflictPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Receive state conflicts
+    pub fn subscribe(&self) -> broadcast::Receiver<TrackerEvent> {
+        self.inner.events.subscribe()
+    }
+
+    /// Client used for signing and submission
+    pub fn tx_client(&self) -> &TxClient {
+        &self.tx_client
+    }
+
+    /// Submit a signed order and track it by its client order index
+    ///
+    /// The order is `Submitted` until the API answers, then `Acknowledged` or
+    /// `Rejected` with the response code. If the request fails outright the
+    /// error is returned and the order stays `Submitted`, since it may still
+    /// have reached the exchange; reconcile or cancel it to settle it.
+    pub async fn submit(&self, order: &L2CreateOrderTxInfo) -> Result<TxResponse> {
+        self.track_submitted(order)?;
+        let response = self.tx_client.send_transaction(order).await?;
+        self.track_response(order.client_order_index, response.code);
+        Ok(response)
+    }
+
+    /// Start tracking a signed order as `Submitted`, refusing a client order
+    /// index still in use
+    fn track_submitted(&self, order: &L2CreateOrderTxInfo) -> Result<()> {
+        let client_order_index = order.client_order_index;
+        {
+            let mut orders = self.lock();
+            if orders
+                .get(&client_order_index)
+                .is_some_and(|tracked| tracked.state.is_open())
+            {
+                return Err(LighterError::ValidationError(format!(
+                    "Client order index {client_order_index} is already in use"
+                )));
+            }
+            orders.insert(
+                client_order_index,
+                TrackedOrder {
+                    client_order_index,
+                    market_index: order.market_index,
+                    order_index: None,
+                    state: OrderState::Submitted,
+                    filled_base_amount: Decimal::ZERO,
+                    order_expiry: (order.order_expiry > 0).then_some(order.order_expiry),
+                    placed: Some(CreateOrderTxReq {
+                        market_index: order.market_index,
+                        client_order_index,
+                        base_amount: order.base_amount,
+                        price: order.price,
+                        is_ask: order.is_ask,
+                        order_type: order.order_type,
+                        time_in_force: order.time_in_force,
+                        reduce_only: order.reduce_only,
+                        trigger_price: order.trigger_price,
+                        order_expiry: order.order_expiry,
+                    }),
+                    strategy_id: order
+                        .get_tx_hash()
+                        .and_then(|tx_hash| self.tx_client.strategy_of(&tx_hash))
+                        .map(|strategy_id| strategy_id.to_string()),
+                },
+            );
+            self.views().remove(&client_order_index);
+        }
+        self.notify();
+        Ok(())
+    }
+
+    /// Move a submitted order on to the API's response code
+    fn track_response(&self, client_order_index: i64, code: u16) {
+        let state = if code == API_CODE_SUCCESS {
+            OrderState::Acknowledged
+        } else {
+            OrderState::Rejected(code)
+        };
+        self.update(client_order_index, |tracked| tracked.advance(state));
+    }
+
+    /// Apply one order event from the account channel, stamped now
+    ///
+    /// Orders placed outside the tracker are picked up from their first
+    /// `Update`; fills for unknown orders are ignored.
+    pub fn apply(&self, event: OrderEvent) {
+        let now_ms = self.tx_client.clock().now_ms();
+        self.apply_observed(StateSource::WebSocket, event, now_ms);
+    }
+
+    /// Apply one order event reported by `source` at `at_ms`, in
+    /// milliseconds since the Unix epoch
+    ///
+    /// The source and timestamp decide which report wins when the WebSocket
+    /// and REST disagree; see the [module docs](self).
+    pub fn apply_observed(&self, source: StateSource, event: OrderEvent, at_ms: i64) {
+        let client_order_index = event.client_order_index();
+        let mut conflict = None;
+        let changed = {
+            let mut orders = self.lock();
+            match event {
+                OrderEvent::Update {
+                    order_index,
+                    market_index,
+                    state,
+                    ..
+                } => {
+                    let tracked = orders.entry(client_order_index).or_insert(TrackedOrder {
+                        client_order_index,
+                        market_index,
+                        order_index: None,
+                        state: OrderState::Submitted,
+                        filled_base_amount: Decimal::ZERO,
+                        order_expiry: None,
+                        placed: None,
+                        strategy_id: None,
+                    });
+                    let learned_index = tracked.order_index.replace(order_index).is_none();
+                    let observation = Observation {
+                        state: Some(state),
+                        at_ms,
+                    };
+                    let (settled, event) = self.observe(client_order_index, source, observation);
+                    conflict = event;
+                    settled.is_some_and(|state| tracked.advance(state)) || learned_index
+                }
+                OrderEvent::Fill { base_amount, .. } => {
+                    let Some(tracked) = orders.get_mut(&client_order_index) else {
+                        return;
+                    };
+                    let filled = tracked.filled_base_amount + base_amount;
+                    tracked.advance(OrderState::PartiallyFilled(filled))
+                }
+            }
+        };
+        if changed {
+            self.notify();
+        }
+        if let Some(conflict) = conflict {
+            self.report(conflict);
+        }
+    }
+
+    /// Record a report and settle the order's state against the other
+    /// source's
+    fn observe(
+        &self,
+        client_order_index: i64,
+        source: StateSource,
+        observation: Observation,
+    ) -> (Option<OrderState>, Option<TrackerEvent>) {
+        let now_ms = self.tx_client.clock().now_ms();
+        let window_ms = self.policy.window.as_millis() as i64;
+        let mut views = self.views();
+        let view = views.entry(client_order_index).or_default();
+        view.observe(source, observation);
+        let (settled, conflicted) = view.settle(now_ms, window_ms);
+        let event = view.track_conflict(conflicted, client_order_index, now_ms, window_ms);
+        (settled, event)
+    }
+
+    /// Settle orders whose sources disagree as time passes
+    ///
+    /// Releases filled or cancelled reports that waited out the window
+    /// unchallenged and reports conflicts that outlasted it. Runs on every
+    /// event; call it on a timer as well so a quiet feed doesn't hold orders
+    /// open.
+    pub fn check_conflicts(&self) {
+        let now_ms = self.tx_client.clock().now_ms();
+        let window_ms = self.policy.window.as_millis() as i64;
+        let mut conflicts = Vec::new();
+        let changed = {
+            let mut orders = self.lock();
+            let mut views = self.views();
+            let mut changed = false;
+            for (&client_order_index, view) in views.iter_mut() {
+                if !view.is_unsettled() {
+                    continue;
+                }
+                let (settled, conflicted) = view.settle(now_ms, window_ms);
+                conflicts.extend(view.track_conflict(
+                    conflicted,
+                    client_order_index,
+                    now_ms,
+                    window_ms,
+                ));
+                if let (Some(state), Some(tracked)) = (settled, orders.get_mut(&client_order_index))
+                {
+                    changed |= tracked.advance(state);
+                }
+            }
+            changed
+        };
+        if changed {
+            self.notify();
+        }
+        for conflict in conflicts {
+            self.report(conflict);
+        }
+    }
+
+    fn report(&self, event: TrackerEvent) {
+        let TrackerEvent::StateConflict {
+            client_order_index,
+            ws_state,
+            rest_state,
+        } = &event;
+        tracing::warn!(
+            client_order_index,
+            ?ws_state,
+            ?rest_state,
+            "WebSocket and REST disagree on an order"
+        );
+        // No subscribers is fine
+        let _ = self.inner.events.send(event);
+    }
+
+    /// Apply every order event in an `account_all` frame
+    ///
+    /// Fits the account callback of [`WsClient::run`](crate::ws_client::WsClient::run).
+    /// While [`OrderTracker::reconcile`] is fetching, frames are held back and
+    /// only those newer than the snapshot are applied once it lands.
+    pub fn apply_account_frame(&self, data: &Value) {
+        let mut sync = self.sync();
+        if let Ingest::Apply(data) = sync.push(account_frame_timestamp(data), data.clone()) {
+            self.apply_frame(&data);
+        }
+    }
+
+    fn apply_frame(&self, data: &Value) {
+        let at_ms = account_frame_timestamp(data)
+            .map_or_else(|| self.tx_client.clock().now_ms(), |at_ms| at_ms as i64);
+        for event in OrderEvent::from_account_frame(data) {
+            self.apply_observed(StateSource::WebSocket, event, at_ms);
+        }
+    }
+
+    /// Seed the tracker from the exchange's open orders in `market_indexes`
+    ///
+    /// Meant for startup: every active order is tracked as `Open` or
+    /// `PartiallyFilled`. Tracked orders missing from the response are left
+    /// as they are, since the endpoint can't tell filled from cancelled.
+    ///
+    /// Account frames stamped after the request was sent are applied on top
+    /// of the snapshot; older ones are already reflected in it and dropped,
+    /// so fills aren't counted twice.
+    pub async fn reconcile(&self, market_indexes: &[u8], auth: Option<&str>) -> Result<()> {
+        self.seed(market_indexes, auth, |_| true).await?;
+        Ok(())
+    }
+
+    /// Take over the orders a previous run left resting in `namespace`
+    ///
+    /// Finds the markets with open orders from the account state, then seeds
+    /// the tracker like [`OrderTracker::reconcile`] with only the active
+    /// orders whose client order index falls in `namespace`, so orders of
+    /// other bots on the account are left alone. Returns the adopted orders.
+    pub async fn adopt_existing(
+        &self,
+        namespace: ClientOrderNamespace,
+        auth: Option<&str>,
+    ) -> Result<Vec<TrackedOrder>> {
+        let account = self
+            .http()?
+            .get_account_state(self.tx_client.account_index())
+            .await?;
+        let markets: Vec<u8> = account
+            .positions
+            .iter()
+            .filter(|position| position.open_order_count > 0)
+            .map(|position| position.market_id)
+            .collect();
+
+        let adopted = self
+            .seed(&markets, auth, |order| {
+                namespace.contains(order.client_order_index)
+            })
+            .await?;
+        let orders = self.lock();
+        Ok(adopted
+            .iter()
+            .filter_map(|client_order_index| orders.get(client_order_index).cloned())
+            .collect())
+    }
+
+    /// Save the open orders to `store`
+    pub fn save_state(&self, store: &dyn StateStore) -> Result<()> {
+        save_json(store, ORDERS_STATE, &self.open_orders())
+    }
+
+    /// Restore the open orders saved by [`OrderTracker::save_state`],
+    /// keeping only those the exchange still lists as active
+    ///
+    /// Queries the active orders of every market a restored order is in and
+    /// every market the account state reports open orders in, then seeds the
+    /// tracker like [`OrderTracker::reconcile`]. Restored orders missing from
+    /// the response have filled or been cancelled since and are dropped.
+    pub async fn restore_and_reconcile(
+        &self,
+        store: &dyn StateStore,
+        auth: Option<&str>,
+    ) -> Result<RestoreReport> {
+        let restored = load_json::<Vec<TrackedOrder>>(store, ORDERS_STATE);
+        let account = self
+            .http()?
+            .get_account_state(self.tx_client.account_index())
+            .await?;
+        let mut markets: Vec<u8> = account
+            .positions
+            .iter()
+            .filter(|position| position.open_order_count > 0)
+            .map(|position| position.market_id)
+            .chain(restored.iter().flatten().map(|order| order.market_index))
+            .collect();
+        markets.sort_unstable();
+        markets.dedup();
+
+        let active = self.seed(&markets, auth, |_| true).await?;
+        let Some(restored) = restored else {
+            return Ok(RestoreReport::default());
+        };
+        let kept = restored
+            .iter()
+            .filter(|order| active.contains(&order.client_order_index))
+            .count();
+        if kept < restored.len() {
+            tracing::info!(
+                dropped = restored.len() - kept,
+                "Restored orders are no longer active"
+            );
+        }
+        Ok(RestoreReport {
+            found: true,
+            kept,
+            discarded: restored.len() - kept,
+        })
+    }
+
+    fn http(&self) -> Result<&HTTPClient> {
+        self.tx_client.http().ok_or_else(|| {
+            LighterError::InvalidConfiguration(
+                "HTTPClient is not configured. Provide a valid API URL when creating TxClient."
+                    .to_string(),
+            )
+        })
+    }
+
+    /// Apply the active orders in `market_indexes` that pass `keep`,
+    /// returning their client order indexes
+    async fn seed(
+        &self,
+        market_indexes: &[u8],
+        auth: Option<&str>,
+        keep: impl Fn(&ActiveOrder) -> bool,
+    ) -> Result<Vec<i64>> {
+        let http = self.http()?;
+
+        self.sync().begin_snapshot();
+        let snapshot_ms = now_ms();
+        let mut listed = Vec::new();
+        for &market_index in market_indexes {
+            match http
+                .get_active_orders(self.tx_client.account_index(), market_index, auth)
+                .await
+            {
+                Ok(orders) => listed.extend(orders),
+                Err(e) => {
+                    let mut sync = self.sync();
+                    for data in sync.cancel_snapshot() {
+                        self.apply_frame(&data);
+                    }
+                    return Err(e);
+                }
+            }
+        }
+
+        let mut sync = self.sync();
+        self.mark_unlisted(market_indexes, &listed, snapshot_ms as i64);
+        let active: Vec<ActiveOrder> = listed.into_iter().filter(|order| keep(order)).collect();
+        for order in &active {
+            self.apply_observed(
+                StateSource::Rest,
+                Self::active_order_event(order),
+                snapshot_ms as i64,
+            );
+            if order.order_expiry > 0 {
+                let expiry = Some(order.order_expiry);
+                self.update(order.client_order_index, |tracked| {
+                    std::mem::replace(&mut tracked.order_expiry, expiry) != expiry
+                });
+            }
+        }
+        for data in sync.complete_snapshot(snapshot_ms).events {
+            self.apply_frame(&data);
+        }
+        Ok(active
+            .iter()
+            .map(|order| order.client_order_index)
+            .collect())
+    }
+
+    /// Record that REST no longer lists the orders in `market_indexes` the
+    /// tracker has seen on the book
+    ///
+    /// Orders not yet seen on the book are left alone, since the snapshot
+    /// may simply predate them.
+    fn mark_unlisted(&self, market_indexes: &[u8], listed: &[ActiveOrder], at_ms: i64) {
+        let unlisted: Vec<i64> = self
+            .lock()
+            .values()
+            .filter(|tracked| {
+                market_indexes.contains(&tracked.market_index)
+                    && matches!(
+                        tracked.state,
+                        OrderState::Open | OrderState::PartiallyFilled(_)
+                    )
+                    && !listed
+                        .iter()
+                        .any(|order| order.client_order_index == tracked.client_order_index)
+            })
+            .map(|tracked| tracked.client_order_index)
+            .collect();
+        for client_order_index in unlisted {
+            let observation = Observation { state: None, at_ms };
+            let (settled, conflict) =
+                self.observe(client_order_index, StateSource::Rest, observation);
+            if let Some(state) = settled {
+                self.update(client_order_index, |tracked| tracked.advance(state));
+            }
+            if let Some(conflict) = conflict {
+                self.report(conflict);
+            }
+        }
+    }
+
+    fn active_order_event(order: &ActiveOrder) -> OrderEvent {
+        let state = if order.filled_base_amount > Decimal::ZERO {
+            OrderState::PartiallyFilled(order.filled_base_amount)
+        } else {
+            OrderState::Open
+        };
+        OrderEvent::Update {
+            client_order_index: order.client_order_index,
+            order_index: order.order_index,
+            market_index: order.market_index,
+            state,
+        }
+    }
+
+    /// Current state of an order
+    pub fn state(&self, client_order_index: i64) -> Option<OrderState> {
+        self.lock()
+            .get(&client_order_index)
+            .map(|tracked| tracked.state)
+    }
+
+    /// Snapshot of one tracked order
+    pub fn order(&self, client_order_index: i64) -> Option<TrackedOrder> {
+        self.lock().get(&client_order_index).cloned()
+    }
+
+    /// Orders that aren't filled, cancelled or rejected, by client order index
+    pub fn open_orders(&self) -> Vec<TrackedOrder> {
+        let mut open: Vec<TrackedOrder> = self
+            .lock()
+            .values()
+            .filter(|tracked| tracked.state.is_open())
+            .cloned()
+            .collect();
+        open.sort_by_key(|tracked| tracked.client_order_index);
+        open
+    }
+
+    /// Open orders whose expiry falls within `window` from now, soonest
+    /// first
+    ///
+    /// Orders already past their expiry but not yet reported ended are
+    /// listed too. Expiries come from the signed order, or from the active
+    /// orders endpoint for orders picked up by [`OrderTracker::reconcile`].
+    pub fn orders_expiring_within(&self, window: Duration) -> Vec<TrackedOrder> {
+        let deadline = self.tx_client.clock().now_ms() + window.as_millis() as i64;
+        let mut expiring: Vec<TrackedOrder> = self
+            .lock()
+            .values()
+            .filter(|tracked| {
+                tracked.state.is_open()
+                    && tracked
+                        .order_expiry
+                        .is_some_and(|expiry| expiry <= deadline)
+            })
+            .cloned()
+            .collect();
+        expiring.sort_by_key(|tracked| (tracked.order_expiry, tracked.client_order_index));
+        expiring
+    }
+
+    /// Place an open order again with a new expiry, in milliseconds since
+    /// the Unix epoch
+    ///
+    /// A modify can't change an order's expiry, so the order is cancelled and
+    /// placed again under a new client order index, both in one sendTxBatch
+    /// request the API applies in order. The replacement restates the order's
+    /// remaining size, price and trigger price from
+    /// [`TxClient::restate_order`] and keeps the rest of the request it was
+    /// signed from, so only orders submitted through the tracker can be
+    /// refreshed. Returns the replacement, tracked like one passed to
+    /// [`OrderTracker::submit`]; a rejected batch is returned as an error.
+    pub async fn refresh_order(
+        &self,
+        client_order_index: i64,
+        new_expiry: i64,
+        auth: Option<&str>,
+    ) -> Result<TrackedOrder> {
+        let tracked = self
+            .order(client_order_index)
+            .filter(|tracked| tracked.state.is_open())
+            .ok_or_else(|| {
+                LighterError::ValidationError(format!(
+                    "Client order index {client_order_index} is not an open tracked order"
+                ))
+            })?;
+        let placed = tracked.placed.ok_or_else(|| {
+            LighterError::ValidationError(format!(
+                "Order {client_order_index} wasn't submitted through the tracker, so it can't be placed again"
+            ))
+        })?;
+        let live = self
+            .tx_client
+            .restate_order(tracked.market_index, client_order_index, auth)
+            .await?;
+
+        // The replacement is the same strategy's order
+        let opts = TransactOpts {
+            strategy_id: tracked.strategy_id,
+            ..TransactOpts::default()
+        };
+        let (opts, reservation) = self.tx_client.fill_opts_reserving(Some(opts), 2).await?;
+        let nonce = opts.nonce.unwrap();
+        let cancel = self
+            .tx_client
+            .cancel_order(
+                &CancelOrderTxReq {
+                    market_index: live.market_index,
+                    index: live.index,
+                },
+                Some(opts.clone()),
+            )
+            .await?;
+        let replacement = self
+            .tx_client
+            .create_order(
+                &CreateOrderTxReq {
+                    client_order_index: self.tx_client.next_client_order_index(),
+                    base_amount: live.base_amount,
+                    price: live.price,
+                    trigger_price: live.trigger_price,
+                    order_expiry: new_expiry,
+                    ..placed
+                },
+                Some(TransactOpts {
+                    nonce: Some(nonce + 1),
+                    ..opts
+                }),
+            )
+            .await?;
+        reservation.keep();
+
+        self.track_submitted(&replacement)?;
+        let response = self
+            .tx_client
+            .send_batch(&[SignedTx::new(&cancel)?, SignedTx::new(&replacement)?])
+            .await?;
+        self.track_response(replacement.client_order_index, response.code);
+        if !response.is_success() {
+            return Err(LighterError::ApiError(format!(
+                "Refresh of order {client_order_index} rejected with code {}: {}",
+                response.code,
+                response.message.as_deref().unwrap_or_default()
+            )));
+        }
+        self.order(replacement.client_order_index)
+            .ok_or_else(|| LighterError::InvalidResponse("Replacement order not tracked".into()))
+    }
+
+    /// Wait until an order is filled, cancelled or rejected
+    ///
+    /// Returns [`LighterError::Timeout`] if that doesn't happen within
+    /// `timeout`, and an error straight away for an untracked order.
+    pub async fn await_terminal(
+        &self,
+        client_order_index: i64,
+        timeout: Duration,
+    ) -> Result<OrderState> {
+        let mut changed = self.inner.changed.subscribe();
+        let wait = async {
+            loop {
+                match self.state(client_order_index) {
+                    None => {
+                        return Err(LighterError::ValidationError(format!(
+                            "Client order index {client_order_index} is not tracked"
+                        )))
+                    }
+                    Some(state) if state.is_terminal() => return Ok(state),
+                    Some(_) => {}
+                }
+                // The sender lives as long as `self`, so this can't fail
+                let _ = changed.changed().await;
+            }
+        };
+        tokio::time::timeout(timeout, wait)
+            .await
+            .map_err(|_| LighterError::Timeout)?
+    }
+
+    /// Wait until the exchange has reported the order index it assigned to
+    /// an order
+    ///
+    /// The index arrives with the order's first update on the account
+    /// channel, or from [`OrderTracker::reconcile`]. Orders not tracked yet
+    /// are waited for too, since that update may come before the tracker
+    /// hears of the order otherwise. Returns [`LighterError::Timeout`] if no
+    /// index arrives within `timeout`, and an error straight away once the
+    /// order has ended without one, such as when it was rejected.
+    pub async fn await_order_index(
+        &self,
+        client_order_index: i64,
+        timeout: Duration,
+    ) -> Result<i64> {
+        let mut changed = self.inner.changed.subscribe();
+        let wait = async {
+            loop {
+                match self.order(client_order_index) {
+                    Some(TrackedOrder {
+                        order_index: Some(order_index),
+                        ..
+                    }) => return Ok(order_index),
+                    Some(tracked) if tracked.state.is_terminal() => {
+                        return Err(LighterError::ValidationError(format!(
+                            "Client order index {client_order_index} ended as {:?} without an order index",
+                            tracked.state
+                        )))
+                    }
+                    _ => {}
+                }
+                // The sender lives as long as `self`, so this can't fail
+                let _ = changed.changed().await;
+            }
+        };
+        tokio::time::timeout(timeout, wait)
+            .await
+            .map_err(|_| LighterError::Timeout)?
+    }
+
+    /// Exchange order index of `order`, waiting up to `timeout` for it if
+    /// the order is given by its client order index
+    pub async fn resolve_order_index(&self, order: OrderRef, timeout: Duration) -> Result<i64> {
+        match order {
+            OrderRef::OrderIndex(order_index) => Ok(order_index),
+            OrderRef::ClientOrderIndex(client_order_index) => {
+                self.await_order_index(client_order_index, timeout).await
+            }
+        }
+    }
+
+    /// Sign a cancel for `order`, given by either identifier
+    ///
+    /// A client order index is resolved with
+    /// [`OrderTracker::await_order_index`] first.
+    pub async fn cancel_order(
+        &self,
+        market_index: u8,
+        order: OrderRef,
+        timeout: Duration,
+    ) -> Result<L2CancelOrderTxInfo> {
+        let req = CancelOrderTxReq {
+            market_index,
+            index: self.resolve_order_index(order, timeout).await?,
+        };
+        self.tx_client.cancel_order(&req, None).await
+    }
+
+    /// Sign a modify for `order`, given by either identifier
+    ///
+    /// The `index` of `req` is replaced with the resolved order index, as in
+    /// [`OrderTracker::cancel_order`].
+    pub async fn modify_order(
+        &self,
+        order: OrderRef,
+        req: &ModifyOrderTxReq,
+        timeout: Duration,
+    ) -> Result<L2ModifyOrderTxInfo> {
+        let req = ModifyOrderTxReq {
+            index: self.resolve_order_index(order, timeout).await?,
+            ..req.clone()
+        };
+        self.tx_client.modify_order(&req, None).await
+    }
+
+    /// Receiver woken on every state change
+    pub(crate) fn changes(&self) -> watch::Receiver<u64> {
+        self.inner.changed.subscribe()
+    }
+
+    /// Open order count for the client's risk checks
+    ///
+    /// Unlike the tracker itself this doesn't hold on to the client, so it can
+    /// be attached to the same client with
+    /// [`TxClient::attach_risk_state`] without a reference cycle.
+    pub fn risk_state(&self) -> Arc<dyn RiskState> {
+        Arc::new(OpenOrders(self.inner.clone()))
+    }
+
+    /// Stop tracking orders that have reached a terminal state
+    pub fn prune_terminal(&self) {
+        let mut orders = self.lock();
+        orders.retain(|_, tracked| tracked.state.is_open());
+        self.views()
+            .retain(|client_order_index, _| orders.contains_key(client_order_index));
+    }
+
+    fn update(&self, client_order_index: i64, f: impl FnOnce(&mut TrackedOrder) -> bool) {
+        let changed = self.lock().get_mut(&client_order_index).is_some_and(f);
+        if changed {
+            self.notify();
+        }
+    }
+
+    fn notify(&self) {
+        self.inner.changed.send_modify(|version| *version += 1);
+    }
+
+    fn sync(&self) -> MutexGuard<'_, SnapshotSync<Value>> {
+        self.inner
+            .sync
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn views(&self) -> MutexGuard<'_, HashMap<i64, Views>> {
+        self.inner
+            .views
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<i64, TrackedOrder>> {
+        self.inner
+            .orders
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::mock_builder;
+    use crate::transport::MockTransport;
+    use serde_json::json;
+
+    const NONCE_PATH: &str = "/api/v1/nextNonce";
+    const SEND_TX_PATH: &str = "/api/v1/sendTx";
+    const ACTIVE_ORDERS_PATH: &str = "/api/v1/accountActiveOrders";
+
+    fn tracker() -> (OrderTracker, Arc<MockTransport>) {
+        let mock = Arc::new(MockTransport::new());
+        mock.set_handler(NONCE_PATH, |_| {
+            Ok(crate::transport::HttpResponse::new(
+                200,
+                r#"{"code":200,"nonce":0}"#,
+            ))
+        });
+        let tx_client = mock_builder(&mock).build().unwrap();
+        (OrderTracker::new(Arc::new(tx_client)), mock)
+    }
+
+    async fn submit(tracker: &OrderTracker, client_order_index: i64) -> TxResponse {
+        let order = tracker
+            .tx_client()
+            .create_limit_order(0, client_order_index, 1000, 300000, 0, false, None)
+            .await
+            .unwrap();
+        tracker.submit(&order).await.unwrap()
+    }
+
+    fn order_frame(client_order_index: i64, status: &str, filled: &str) -> Value {
+        json!({
+            "orders": {
+                "0": [{
+                    "order_index": 281474976710700i64,
+                    "client_order_index": client_order_index,
+                    "market_index": 0,
+                    "status": status,
+                    "filled_base_amount": filled,
+                }]
+            }
+        })
+    }
+
+    #[tokio::test]
+    async fn test_follows_order_to_filled() {
+        let (tracker, mock) = tracker();
+        mock.push_response(SEND_TX_PATH, 200, r#"{"code":200,"tx_hash":"0xabc"}"#);
+
+        submit(&tracker, 7).await;
+        assert_eq!(tracker.state(7), Some(OrderState::Acknowledged));
+
+        tracker.apply_account_frame(&order_frame(7, "open", "0"));
+        assert_eq!(tracker.state(7), Some(OrderState::Open));
+        assert_eq!(tracker.order(7).unwrap().order_index, Some(281474976710700));
+
+        tracker.apply_account_frame(&order_frame(7, "open", "0.04"));
+        assert_eq!(
+            tracker.state(7),
+            Some(OrderState::PartiallyFilled(Decimal::new(4, 2)))
+        );
+        assert_eq!(tracker.open_orders().len(), 1);
+
+        tracker.apply_account_frame(&order_frame(7, "filled", "0.1"));
+        assert_eq!(
+            tracker
+                .await_terminal(7, Duration::from_secs(1))
+                .await
+                .unwrap(),
+            OrderState::Filled
+        );
+        assert!(tracker.open_orders().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_rejection_records_code() {
+        let (tracker, mock) = tracker();
+        mock.push_response(
+            SEND_TX_PATH,
+            200,
+            r#"{"code":21701,"message":"invalid base amount"}"#,
+        );
+
+        submit(&tracker, 3).await;
+        assert_eq!(tracker.state(3), Some(OrderState::Rejected(21701)));
+        assert!(tracker.open_orders().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_failed_request_stays_submitted() {
+        let (tracker, mock) = tracker();
+        mock.push_error(SEND_TX_PATH, LighterError::Timeout);
+
+        let order = tracker
+            .tx_client()
+            .create_limit_order(0, 5, 1000, 300000, 0, false, None)
+            .await
+            .unwrap();
+        assert!(tracker.submit(&order).await.is_err());
+        assert_eq!(tracker.state(5), Some(OrderState::Submitted));
+
+        // Reusing the index of an order that may be live is refused
+        assert!(matches!(
+            tracker.submit(&order).await,
+            Err(LighterError::ValidationError(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_states_never_regress() {
+        let (tracker, _) = tracker();
+        tracker.apply_account_frame(&order_frame(9, "open", "0.05"));
+        tracker.apply_account_frame(&order_frame(9, "open", "0"));
+        assert_eq!(
+            tracker.state(9),
+            Some(OrderState::PartiallyFilled(Decimal::new(5, 2)))
+        );
+
+        tracker.apply_account_frame(&order_frame(9, "canceled-post-only", "0.05"));
+        tracker.apply_account_frame(&order_frame(9, "filled", "0.1"));
+        assert_eq!(tracker.state(9), Some(OrderState::Cancelled));
+    }
+
+    #[tokio::test]
+    async fn test_fills_accumulate() {
+        let (tracker, mock) = tracker();
+        mock.push_response(SEND_TX_PATH, 200, r#"{"code":200}"#);
+        submit(&tracker, 11).await;
+
+        let trades = |size: &str| json!({ "trades": [{ "client_order_index": 11, "size": size }] });
+        tracker.apply_account_frame(&trades("0.02"));
+        tracker.apply_account_frame(&trades("0.03"));
+        assert_eq!(
+            tracker.state(11),
+            Some(OrderState::PartiallyFilled(Decimal::new(5, 2)))
+        );
+
+        // The filled amount outlives a cancel
+        tracker.apply(OrderEvent::Update {
+            client_order_index: 11,
+            order_index: 5,
+            market_index: 0,
+            state: OrderState::Cancelled,
+        });
+        let tracked = tracker.order(11).unwrap();
+        assert_eq!(tracked.state, OrderState::Cancelled);
+        assert_eq!(tracked.filled_base_amount, Decimal::new(5, 2));
+
+        // Fills for orders the tracker never saw are ignored
+        tracker
+            .apply_account_frame(&json!({ "trades": [{ "client_order_index": 12, "size": "1" }] }));
+        assert_eq!(tracker.state(12), None);
+    }
+
+    #[tokio::test]
+    async fn test_await_terminal_wakes_and_times_out() {
+        let (tracker, mock) = tracker();
+        mock.push_response(SEND_TX_PATH, 200, r#"{"code":200}"#);
+        submit(&tracker, 21).await;
+
+        assert!(matches!(
+            tracker.await_terminal(21, Duration::from_millis(20)).await,
+            Err(LighterError::Timeout)
+        ));
+        assert!(tracker
+            .await_terminal(99, Duration::from_millis(20))
+            .await
+            .is_err());
+
+        let feed = tracker.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            feed.apply_account_frame(&order_frame(21, "open", "0"));
+            feed.apply_account_frame(&order_frame(21, "canceled", "0"));
+        });
+        assert_eq!(
+            tracker
+                .await_terminal(21, Duration::from_secs(5))
+                .await
+                .unwrap(),
+            OrderState::Cancelled
+        );
+    }
+
+    #[tokio::test]
+    async fn test_await_order_index_out_of_order_and_timeout() {
+        let (tracker, mock) = tracker();
+        mock.push_response(SEND_TX_PATH, 200, r#"{"code":200}"#);
+
+        // The account channel may report the order before it is submitted here
+        let waiter = tracker.clone();
+        let waiting =
+            tokio::spawn(async move { waiter.await_order_index(31, Duration::from_secs(5)).await });
+        tokio::task::yield_now().await;
+        tracker.apply_account_frame(&order_frame(30, "open", "0"));
+        tracker.apply_account_frame(&order_frame(31, "open", "0"));
+        assert_eq!(waiting.await.unwrap().unwrap(), 281474976710700);
+
+        // A fill before the order update doesn't resolve the index
+        submit(&tracker, 32).await;
+        tracker.apply(OrderEvent::Fill {
+            client_order_index: 32,
+            base_amount: Decimal::ONE,
+        });
+        assert!(matches!(
+            tracker
+                .await_order_index(32, Duration::from_millis(20))
+                .await,
+            Err(LighterError::Timeout)
+        ));
+
+        // Both identifiers resolve to the same cancel
+        tracker.apply_account_frame(&order_frame(32, "open", "0"));
+        let by_client = tracker
+            .cancel_order(0, OrderRef::ClientOrderIndex(32), Duration::from_secs(1))
+            .await
+            .unwrap();
+        let by_exchange = tracker
+            .cancel_order(0, OrderRef::OrderIndex(281474976710700), Duration::ZERO)
+            .await
+            .unwrap();
+        assert_eq!(by_client.index, 281474976710700);
+        assert_eq!(by_exchange.index, by_client.index);
+
+        let modify = ModifyOrderTxReq {
+            market_index: 0,
+            index: 0,
+            base_amount: 2000,
+            price: 300100,
+            trigger_price: 0,
+        };
+        let modified = tracker
+            .modify_order(
+                OrderRef::ClientOrderIndex(32),
+                &modify,
+                Duration::from_secs(1),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            (modified.index, modified.base_amount),
+            (281474976710700, 2000)
+        );
+
+        // Rejected orders never get an index
+        mock.push_response(SEND_TX_PATH, 200, r#"{"code":21701}"#);
+        submit(&tracker, 33).await;
+        assert!(matches!(
+            tracker.await_order_index(33, Duration::from_secs(1)).await,
+            Err(LighterError::ValidationError(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_reconcile_seeds_from_active_orders() {
+        let (tracker, mock) = tracker();
+        mock.push_response(
+            ACTIVE_ORDERS_PATH,
+            200,
+            r#"{"code":200,"orders":[
+                {"order_index":281474976710701,"client_order_index":1,"market_index":0,
+                 "is_ask":false,"price":"3000.00","initial_base_amount":"0.1000",
+                 "remaining_base_amount":"0.1000","filled_base_amount":"0","status":"open"},
+                {"order_index":281474976710702,"client_order_index":2,"market_index":0,
+                 "is_ask":true,"price":"3100.00","initial_base_amount":"0.1000",
+                 "remaining_base_amount":"0.0600","filled_base_amount":"0.0400","status":"open"}
+            ]}"#,
+        );
+
+        tracker.reconcile(&[0], Some("token:1")).await.unwrap();
+
+        let open = tracker.open_orders();
+        assert_eq!(open.len(), 2);
+        assert_eq!(open[0].state, OrderState::Open);
+        assert_eq!(open[0].order_index, Some(281474976710701));
+        assert_eq!(
+            open[1].state,
+            OrderState::PartiallyFilled(Decimal::new(4, 2))
+        );
+
+        let request = &mock.requests_to(ACTIVE_ORDERS_PATH)[0];
+        assert!(request.url.contains("account_index=1"));
+        assert!(request.url.contains("market_id=0"));
+        assert!(request.url.contains("auth=token%3A1"));
+    }
+
+    #[tokio::test]
+    async fn test_expiring_orders_are_listed_and_refreshed() {
+        use crate::clock::{Clock, ManualClock, SystemClock};
+        use crate::transport::HttpResponse;
+
+        const DAY_MS: i64 = 24 * 3600 * 1000;
+        const BATCH_PATH: &str = "/api/v1/sendTxBatch";
+
+        // REST snapshots are stamped with the system time, so the manual
+        // clock starts there
+        let now_ms = SystemClock.now_ms();
+        let mock = Arc::new(MockTransport::new());
+        mock.set_handler(SEND_TX_PATH, |_| {
+            Ok(HttpResponse::new(200, r#"{"code":200}"#))
+        });
+        mock.set_handler(BATCH_PATH, |_| {
+            Ok(HttpResponse::new(
+                200,
+                r#"{"code":200,"tx_hash":["0x1","0x2"]}"#,
+            ))
+        });
+        mock.set_handler(ACTIVE_ORDERS_PATH, move |_| {
+            Ok(HttpResponse::new(
+                200,
+                format!(
+                    r#"{{"code":200,"orders":[
+                        {{"order_index":281474976710700,"client_order_index":7,"market_index":0,
+                         "is_ask":false,"price":"3000.00","remaining_base_amount":"0.0600",
+                         "filled_base_amount":"0.0400","order_expiry":{},"status":"open"}},
+                        {{"order_index":281474976710702,"client_order_index":2,"market_index":0,
+                         "is_ask":true,"price":"3100.00","remaining_base_amount":"0.1000",
+                         "filled_base_amount":"0","order_expiry":{},"status":"open"}}
+                    ]}}"#,
+                    now_ms + 28 * DAY_MS,
+                    now_ms + 7_200_000
+                ),
+            ))
+        });
+        mock.set_handler("/api/v1/orderBookDetails", |_| {
+            Ok(HttpResponse::new(
+                200,
+                r#"{"code":200,"order_book_details":[{"market_id":0,"size_decimals":4,"price_decimals":2,"last_trade_price":"3000.00"}]}"#,
+            ))
+        });
+        let clock = Arc::new(ManualClock::at_ms(now_ms));
+        let tx_client = mock_builder(&mock).clock(clock.clone()).build().unwrap();
+        tx_client.nonces().set(1, 0, 20);
+        let tracker = OrderTracker::new(Arc::new(tx_client));
+
+        // A 28-day limit order, an IOC market order without an expiry, and
+        // an order placed elsewhere found by reconcile
+        for order in [
+            tracker
+                .tx_client()
+                .create_limit_order(0, 7, 1000, 300000, 0, false, None)
+                .await
+                .unwrap(),
+            tracker
+                .tx_client()
+                .create_market_order(0, 8, 1000, 300000, 0, false, None)
+                .await
+                .unwrap(),
+        ] {
+            tracker.submit(&order).await.unwrap();
+        }
+        tracker.reconcile(&[0], None).await.unwrap();
+
+        let expiring = |window_ms: i64| -> Vec<i64> {
+            tracker
+                .orders_expiring_within(Duration::from_millis(window_ms as u64))
+                .iter()
+                .map(|tracked| tracked.client_order_index)
+                .collect()
+        };
+        assert_eq!(expiring(DAY_MS), vec![2]);
+        clock.advance(Duration::from_millis((27 * DAY_MS + DAY_MS / 2) as u64));
+        assert_eq!(expiring(DAY_MS), vec![2, 7]);
+
+        assert!(matches!(
+            tracker.refresh_order(2, now_ms + 56 * DAY_MS, None).await,
+            Err(LighterError::ValidationError(_))
+        ));
+
+        // The cancel and the replacement go out in one batch, the
+        // replacement restating the remaining size
+        let new_expiry = now_ms + 56 * DAY_MS;
+        let replacement = tracker.refresh_order(7, new_expiry, None).await.unwrap();
+        assert_ne!(replacement.client_order_index, 7);
+        assert_eq!(replacement.state, OrderState::Acknowledged);
+        assert_eq!(replacement.order_expiry, Some(new_expiry));
+        assert_eq!(replacement.placed.as_ref().unwrap().base_amount, 600);
+
+        let requests = mock.requests_to(BATCH_PATH);
+        assert_eq!(requests.len(), 1);
+        let form: Vec<(String, String)> = serde_urlencoded::from_bytes(&requests[0].body).unwrap();
+        let field = |name: &str| {
+            form.iter()
+                .find_map(|(field, value)| (field == name).then(|| value.clone()))
+                .unwrap()
+        };
+        let tx_types: Vec<u8> = serde_json::from_str(&field("tx_types")).unwrap();
+        assert_eq!(
+            tx_types,
+            vec![TX_TYPE_L2_CANCEL_ORDER, TX_TYPE_L2_CREATE_ORDER]
+        );
+        let tx_infos: Vec<Value> = serde_json::from_str::<Vec<String>>(&field("tx_infos"))
+            .unwrap()
+            .iter()
+            .map(|info| serde_json::from_str(info).unwrap())
+            .collect();
+        assert_eq!(tx_infos[0]["Index"], 281474976710700i64);
+        assert_eq!(
+            tx_infos[1]["Nonce"],
+            tx_infos[0]["Nonce"].as_i64().unwrap() + 1
+        );
+        assert_eq!(tx_infos[1]["OrderExpiry"], new_expiry);
+        assert_eq!(tx_infos[1]["BaseAmount"], 600);
+        assert_eq!(tx_infos[1]["Price"], 300000);
+
+        // Once the cancel is reported only the order placed elsewhere is left
+        tracker.apply_account_frame(&order_frame(7, "canceled", "0.04"));
+        assert_eq!(expiring(DAY_MS), vec![2]);
+    }
+
+    #[tokio::test]
+    async fn test_strategy_reaches_tracker_span_and_latency_hook() {
+        use std::io::Write;
+
+        #[derive(Clone, Default)]
+        struct Captured(Arc<Mutex<Vec<u8>>>);
+
+        impl Write for Captured {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().extend_from_slice(buf);
+                Ok(buf.len())
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let captured = Captured::default();
+        let writer = captured.clone();
+        let _subscriber = tracing::subscriber::set_default(
+            tracing_subscriber::fmt()
+                .with_max_level(tracing::Level::DEBUG)
+                .with_ansi(false)
+                .with_writer(move || writer.clone())
+                .finish(),
+        );
+
+        let mock = Arc::new(MockTransport::new());
+        mock.set_handler(SEND_TX_PATH, |_| {
+            Ok(crate::transport::HttpResponse::new(200, r#"{"code":200}"#))
+        });
+        let reported = Arc::new(Mutex::new(Vec::new()));
+        let tx_client = mock_builder(&mock)
+            .latency_hook({
+                let reported = reported.clone();
+                move |latency| reported.lock().unwrap().push(latency.strategy_id.clone())
+            })
+            .build()
+            .unwrap();
+        tx_client.nonces().set(1, 0, 0);
+        let tracker = OrderTracker::new(Arc::new(tx_client));
+
+        let opts = |strategy_id: Option<&str>| TransactOpts {
+            strategy_id: strategy_id.map(str::to_string),
+            ..TransactOpts::default()
+        };
+        for (client_order_index, strategy_id) in [(7, Some("market-maker")), (8, None)] {
+            let order = tracker
+                .tx_client()
+                .create_limit_order(
+                    0,
+                    client_order_index,
+                    1000,
+                    300000,
+                    0,
+                    false,
+                    Some(opts(strategy_id)),
+                )
+                .await
+                .unwrap();
+            tracker.submit(&order).await.unwrap();
+        }
+
+        assert_eq!(
+            tracker.order(7).unwrap().strategy_id.as_deref(),
+            Some("market-maker")
+        );
+        assert_eq!(tracker.order(8).unwrap().strategy_id, None);
+        assert_eq!(
+            *reported.lock().unwrap(),
+            vec![Some(Arc::from("market-maker")), None]
+        );
+        let output = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+        assert!(output.contains(r#"submission{strategy_id="market-maker" tx_count=1}"#));
+
+        // Purely local: the exchange never sees it
+        for request in mock.requests_to(SEND_TX_PATH) {
+            assert!(!String::from_utf8_lossy(&request.body).contains("market-maker"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_adopt_existing_keeps_only_namespace() {
+        let (tracker, mock) = tracker();
+        let ours = ClientOrderNamespace::new(3, 8).unwrap();
+        let theirs = ClientOrderNamespace::new(4, 8).unwrap();
+        mock.set_handler("/api/v1/account", |_| {
+            Ok(crate::transport::HttpResponse::new(
+                200,
+                r#"{"code":200,"accounts":[{"index":1,"collateral":"1000","positions":[
+                    {"market_id":0,"position":"0","avg_entry_price":"0","open_order_count":2},
+                    {"market_id":1,"position":"0","avg_entry_price":"0","open_order_count":0},
+                    {"market_id":2,"position":"0","avg_entry_price":"0","open_order_count":1}
+                ]}]}"#,
+            ))
+        });
+        let orders = [
+            (0, ours.encode(10).unwrap(), "0"),
+            (0, theirs.encode(10).unwrap(), "0"),
+            (2, ours.encode(11).unwrap(), "0.0400"),
+        ];
+        mock.set_handler(ACTIVE_ORDERS_PATH, move |request| {
+            let market = if request.url.contains("market_id=2") {
+                2
+            } else {
+                0
+            };
+            let orders: Vec<Value> = orders
+                .iter()
+                .filter(|(market_index, _, _)| *market_index == market)
+                .map(|(market_index, client_order_index, filled)| {
+                    json!({
+                        "order_index": 281474976710700i64 + client_order_index % 100,
+                        "client_order_index": client_order_index,
+                        "market_index": market_index,
+                        "is_ask": false, "price": "3000.00",
+                        "initial_base_amount": "0.1000", "remaining_base_amount": "0.1000",
+                        "filled_base_amount": filled, "status": "open",
+                    })
+                })
+                .collect();
+            Ok(crate::transport::HttpResponse::new(
+                200,
+                json!({ "code": 200, "orders": orders }).to_string(),
+            ))
+        });
+
+        let adopted = tracker.adopt_existing(ours, None).await.unwrap();
+        let indexes: Vec<i64> = adopted
+            .iter()
+            .map(|order| order.client_order_index)
+            .collect();
+        assert_eq!(
+            indexes,
+            vec![ours.encode(10).unwrap(), ours.encode(11).unwrap()]
+        );
+        assert_eq!(
+            adopted[1].state,
+            OrderState::PartiallyFilled(Decimal::new(4, 2))
+        );
+        assert_eq!(tracker.state(theirs.encode(10).unwrap()), None);
+        assert_eq!(tracker.open_orders().len(), 2);
+
+        // Only markets with open orders are queried
+        let queried: Vec<bool> = mock
+            .requests_to(ACTIVE_ORDERS_PATH)
+            .iter()
+            .map(|request| request.url.contains("market_id=1"))
+            .collect();
+        assert_eq!(queried, vec![false, false]);
+    }
+
+    #[tokio::test]
+    async fn test_restore_keeps_only_orders_still_active() {
+        use crate::state_store::{MemoryStateStore, StateStore};
+
+        let (tracker, mock) = tracker();
+        for client_order_index in [1, 2] {
+            tracker.apply(OrderEvent::Update {
+                client_order_index,
+                order_index: 281474976710700 + client_order_index,
+                market_index: 3,
+                state: OrderState::Open,
+            });
+        }
+        let store = MemoryStateStore::new();
+        tracker.save_state(&store).unwrap();
+
+        mock.set_handler("/api/v1/account", |_| {
+            Ok(crate::transport::HttpResponse::new(
+                200,
+                r#"{"code":200,"accounts":[{"index":1,"positions":[]}]}"#,
+            ))
+        });
+        mock.set_handler(ACTIVE_ORDERS_PATH, |_| {
+            Ok(crate::transport::HttpResponse::new(
+                200,
+                r#"{"code":200,"orders":[
+                    {"order_index":281474976710702,"client_order_index":2,"market_index":3,
+                     "is_ask":false,"price":"3000.00","initial_base_amount":"0.1000",
+                     "remaining_base_amount":"0.0900","filled_base_amount":"0.0100","status":"open"}
+                ]}"#,
+            ))
+        });
+
+        let restarted = OrderTracker::new(Arc::new(mock_builder(&mock).build().unwrap()));
+        let report = restarted.restore_and_reconcile(&store, None).await.unwrap();
+        assert_eq!(
+            report,
+            RestoreReport {
+                found: true,
+                kept: 1,
+                discarded: 1
+            }
+        );
+        assert_eq!(restarted.state(1), None);
+        assert_eq!(
+            restarted.state(2),
+            Some(OrderState::PartiallyFilled(Decimal::new(1, 2)))
+        );
+        assert!(mock.requests_to(ACTIVE_ORDERS_PATH)[0]
+            .url
+            .contains("market_id=3"));
+
+        // A corrupt save is a cold start
+        store.save(ORDERS_STATE, b"{").unwrap();
+        let report = restarted.restore_and_reconcile(&store, None).await.unwrap();
+        assert!(!report.found);
+    }
+
+    #[tokio::test]
+    async fn test_reconcile_replays_only_frames_newer_than_snapshot() {
+        let (tracker, mock) = tracker();
+        let feed = tracker.clone();
+        mock.set_handler(ACTIVE_ORDERS_PATH, move |_| {
+            let fill = |size: &str, timestamp: u64| {
+                json!({ "trades": [{
+                    "client_order_index": 2, "size": size, "timestamp": timestamp
+                }] })
+            };
+            // Already in the snapshot's filled amount
+            feed.apply_account_frame(&fill("0.0400", 1_000));
+            // Sent after the snapshot was requested
+            feed.apply_account_frame(&fill("0.0100", now_ms() + 60_000));
+            Ok(crate::transport::HttpResponse::new(
+                200,
+                r#"{"code":200,"orders":[
+                    {"order_index":281474976710702,"client_order_index":2,"market_index":0,
+                     "is_ask":true,"price":"3100.00","initial_base_amount":"0.1000",
+                     "remaining_base_amount":"0.0600","filled_base_amount":"0.0400","status":"open"}
+                ]}"#,
+            ))
+        });
+
+        tracker.reconcile(&[0], None).await.unwrap();
+        assert_eq!(
+            tracker.state(2),
+            Some(OrderState::PartiallyFilled(Decimal::new(5, 2)))
+        );
+
+        // Frames flow straight through again afterwards
+        tracker.apply_account_frame(&order_frame(2, "filled", "0.1000"));
+        assert_eq!(tracker.state(2), Some(OrderState::Filled));
+    }
+
+    /// Tracker on a manual clock with a five second conflict window
+    fn scripted_tracker() -> (
+        OrderTracker,
+        Arc<crate::clock::ManualClock>,
+        Arc<MockTransport>,
+    ) {
+        let clock = Arc::new(crate::clock::ManualClock::at_ms(0));
+        let mock = Arc::new(MockTransport::new());
+        let tx_client = mock_builder(&mock).clock(clock.clone()).build().unwrap();
+        let tracker = OrderTracker::new(Arc::new(tx_client)).conflict_policy(ConflictPolicy {
+            window: Duration::from_secs(5),
+        });
+        (tracker, clock, mock)
+    }
+
+    fn update(client_order_index: i64, state: OrderState) -> OrderEvent {
+        OrderEvent::Update {
+            client_order_index,
+            order_index: 281474976710700 + client_order_index,
+            market_index: 0,
+            state,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rest_terminal_waits_for_the_account_channel() {
+        let (tracker, clock, _) = scripted_tracker();
+        let risk = tracker.risk_state();
+        let ws = StateSource::WebSocket;
+        let rest = StateSource::Rest;
+
+        // REST says cancelled after the account channel said open
+        tracker.apply_observed(ws, update(1, OrderState::Open), 1_000);
+        tracker.apply_observed(rest, update(1, OrderState::Cancelled), 2_000);
+        assert_eq!(tracker.state(1), Some(OrderState::Open));
+        assert_eq!(risk.open_orders(), Some(1));
+
+        // An older frame arriving late changes nothing
+        tracker.apply_observed(ws, update(1, OrderState::Open), 500);
+        clock.advance(Duration::from_millis(4_999));
+        tracker.check_conflicts();
+        assert_eq!(tracker.state(1), Some(OrderState::Open));
+
+        // Unchallenged for the whole window, it is released
+        clock.advance(Duration::from_millis(1));
+        tracker.check_conflicts();
+        assert_eq!(tracker.state(1), Some(OrderState::Cancelled));
+        assert_eq!(risk.open_orders(), Some(0));
+
+        // The account channel agreeing releases it straight away
+        tracker.apply_observed(ws, update(2, OrderState::Open), 1_000);
+        tracker.apply_observed(rest, update(2, OrderState::Filled), 2_000);
+        assert_eq!(tracker.state(2), Some(OrderState::Open));
+        tracker.apply_observed(ws, update(2, OrderState::Filled), 2_100);
+        assert_eq!(tracker.state(2), Some(OrderState::Filled));
+
+        // The account channel ending an order REST last saw open is no conflict
+        tracker.apply_observed(rest, update(3, OrderState::Open), 1_000);
+        tracker.apply_observed(ws, update(3, OrderState::Cancelled), 1_001);
+        assert_eq!(tracker.state(3), Some(OrderState::Cancelled));
+    }
+
+    #[tokio::test]
+    async fn test_persistent_conflict_is_reported_once() {
+        let (tracker, clock, _) = scripted_tracker();
+        let mut events = tracker.subscribe();
+        let ws = StateSource::WebSocket;
+        let rest = StateSource::Rest;
+
+        // A cancel frame delivered after REST saw the order open again
+        tracker.apply_observed(rest, update(4, OrderState::Open), 3_000);
+        tracker.apply_observed(ws, update(4, OrderState::Cancelled), 2_000);
+        assert_eq!(tracker.state(4), Some(OrderState::Open));
+
+        clock.advance(Duration::from_secs(4));
+        tracker.check_conflicts();
+        assert!(events.try_recv().is_err());
+
+        clock.advance(Duration::from_secs(1));
+        tracker.check_conflicts();
+        assert_eq!(
+            events.try_recv().unwrap(),
+            TrackerEvent::StateConflict {
+                client_order_index: 4,
+                ws_state: OrderState::Cancelled,
+                rest_state: Some(OrderState::Open),
+            }
+        );
+        clock.advance(Duration::from_secs(10));
+        tracker.check_conflicts();
+        assert!(events.try_recv().is_err());
+        assert_eq!(tracker.state(4), Some(OrderState::Open));
+
+        // Agreement settles it
+        tracker.apply_observed(rest, update(4, OrderState::Cancelled), 4_000);
+        assert_eq!(tracker.state(4), Some(OrderState::Cancelled));
+    }
+
+    #[tokio::test]
+    async fn test_order_missing_from_rest_waits_for_the_account_channel() {
+        let (tracker, clock, mock) = scripted_tracker();
+        let mut events = tracker.subscribe();
+        tracker.apply_observed(StateSource::WebSocket, update(5, OrderState::Open), 1_000);
+        mock.push_response(ACTIVE_ORDERS_PATH, 200, r#"{"code":200,"orders":[]}"#);
+
+        tracker.reconcile(&[0], None).await.unwrap();
+        clock.advance(Duration::from_secs(5));
+        tracker.check_conflicts();
+        // Filled or cancelled is unknown, so the order stays open
+        assert_eq!(tracker.state(5), Some(OrderState::Open));
+        assert_eq!(
+            events.try_recv().unwrap(),
+            TrackerEvent::StateConflict {
+                client_order_index: 5,
+                ws_state: OrderState::Open,
+                rest_state: None,
+            }
+        );
+
+        tracker.apply_account_frame(&json!({
+            "timestamp": now_ms() + 1_000,
+            "orders": [{
+                "order_index": 281474976710705i64,
+                "client_order_index": 5,
+                "market_index": 0,
+                "status": "filled",
+                "filled_base_amount": "0.1",
+            }]
+        }));
+        assert_eq!(tracker.state(5), Some(OrderState::Filled));
+    }
+
+    #[tokio::test]
+    async fn test_cancel_an_order_known_only_from_the_account_channel() {
+        let (tracker, _mock) = tracker();
+        let tx_client = tracker.tx_client();
+
+        // After a restart, the account channel is all that names the order
+        let frame = json!({
+            "orders": { "3": [{
+                "order_index": "281474976710700",
+                "client_order_index": 4,
+                "market_index": 3,
+                "status": "open",
+            }]},
+        });
+        let Some(OrderEvent::Update {
+            order_index,
+            market_index,
+            ..
+        }) = OrderEvent::from_account_frame(&frame).pop()
+        else {
+            panic!("no order update decoded");
+        };
+
+        let cancel = tx_client
+            .cancel_order_by_order_index(market_index, order_index, None)
+            .await
+            .unwrap();
+        let tx_info: Value = serde_json::from_str(&cancel.get_tx_info().unwrap()).unwrap();
+        assert_eq!(tx_info["MarketIndex"], 3);
+        assert_eq!(tx_info["Index"], 281474976710700i64);
+
+        let cancel = tx_client
+            .cancel_order_by_client_index(market_index, 4, None)
+            .await
+            .unwrap();
+        assert_eq!(cancel.index, 4);
+
+        // Each refuses the other's indexes rather than cancel another order
+        assert!(matches!(
+            tx_client.cancel_order_by_order_index(3, 4, None).await,
+            Err(LighterError::OrderIndexTooLow(4))
+        ));
+        assert!(matches!(
+            tx_client
+                .cancel_order_by_client_index(3, order_index, None)
+                .await,
+            Err(LighterError::ClientOrderIndexTooHigh(_))
+        ));
+        assert!(matches!(
+            tx_client.cancel_order_by_client_index(3, 0, None).await,
+            Err(LighterError::ClientOrderIndexTooLow(0))
+        ));
+        assert!(matches!(
+            tx_client
+                .cancel_order_by_order_index(3, MAX_ORDER_INDEX + 1, None)
+                .await,
+            Err(LighterError::OrderIndexTooHigh(_))
+        ));
+    }
+
+    #[test]
+    fn test_decodes_flat_and_grouped_frames() {
+        let flat = json!({
+            "orders": [{
+                "order_index": "281474976710700",
+                "client_order_index": 4,
+                "market_index": 1,
+                "status": "open",
+            }],
+            "trades": { "1": [{ "client_order_index": 4, "size": 0.5 }] },
+        });
+        assert_eq!(
+            OrderEvent::from_account_frame(&flat),
+            vec![
+                OrderEvent::Update {
+                    client_order_index: 4,
+                    order_index: 281474976710700,
+                    market_index: 1,
+                    state: OrderState::Open,
+                },
+                OrderEvent::Fill {
+                    client_order_index: 4,
+                    base_amount: Decimal::new(5, 1),
+                },
+            ]
+        );
+        assert!(OrderEvent::from_account_frame(&json!({ "usdc_balance": "1" })).is_empty());
+    }
+}
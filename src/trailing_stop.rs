@@ -0,0 +1,800 @@
+//! Trailing stops emulated on the client
+//!
+//! The exchange has no native trailing stop, so [`TrailingStop`] keeps one
+//! locally: it follows the mid price, ratchets a stop level behind the best
+//! price seen, and sends a reduce-only market order once the price crosses
+//! it. Feed it the top-of-book stream and, to follow the exit order's fills,
+//! the account stream.
+//!
+//! Prices older than [`TrailingStopConfig::max_staleness`] pause the stop
+//! rather than move or fire it. Ages are measured on the monotonic clock;
+//! feed it [`FreshPrice`]s, such as
+//! [`WsClient::mid_price`](crate::ws_client::WsClient::mid_price), to have a
+//! price judged by when it was observed rather than when it was passed in.
+//!
+//! Its state can be exported and imported, or saved to a
+//! [`StateStore`](crate::state_store::StateStore), so a restarted process
+//! keeps the high-water mark.
+//!
+//! ```no_run
+//! use lighter_rs::client::TxClient;
+//! use lighter_rs::trailing_stop::{PositionSide, Trail, TrailingStop, TrailingStopConfig};
+//! use lighter_rs::Decimal;
+//! use std::sync::Arc;
+//! use std::time::Duration;
+//!
+//! # async fn example(tx_client: TxClient) -> lighter_rs::Result<()> {
+//! let stop = TrailingStop::attach(
+//!     Arc::new(tx_client),
+//!     0,
+//!     TrailingStopConfig {
+//!         side: PositionSide::Long,
+//!         trail: Trail::Bps(Decimal::new(150, 0)),
+//!         size: 1000,
+//!         price_decimals: 2,
+//!         size_decimals: 4,
+//!         max_slippage_bps: Decimal::new(50, 0),
+//!         max_staleness: Duration::from_secs(2),
+//!     },
+//! );
+//! stop.on_top_of_book(Decimal::new(302400, 2), Decimal::new(302500, 2))
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::broadcast;
+
+use crate::client::TxClient;
+use crate::clock::Clock;
+use crate::constants::*;
+use crate::errors::{LighterError, Result};
+use crate::fresh_price::FreshPrice;
+use crate::state_store::{load_json, save_json, RestoreReport, StateStore};
+use crate::tracker::{OrderEvent, OrderState};
+use crate::ws_client::OrderBook;
+
+/// Capacity of the event channel; slow subscribers miss older events
+const EVENT_BUFFER: usize = 64;
+
+/// Side of the position a stop protects
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PositionSide {
+    /// Stop trails below the highest price and sells
+    Long,
+    /// Stop trails above the lowest price and buys
+    Short,
+}
+
+/// How far the stop trails the best price
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trail {
+    /// A fraction of the best price, in basis points
+    Bps(Decimal),
+    /// A fixed price distance
+    Distance(Decimal),
+}
+
+/// Trailing stop settings
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrailingStopConfig {
+    pub side: PositionSide,
+    pub trail: Trail,
+    /// Base amount to close when the stop fires
+    pub size: i64,
+    pub price_decimals: u32,
+    pub size_decimals: u32,
+    /// Worst price of the exit order, in basis points through the price that
+    /// triggered it
+    pub max_slippage_bps: Decimal,
+    /// Prices older than this are ignored and pause the stop
+    pub max_staleness: Duration,
+}
+
+/// Persistent state of a trailing stop
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrailingStopState {
+    /// Best price seen: the high-water mark of a long, the low of a short
+    pub extreme: Option<Decimal>,
+    /// Base amount still to close
+    pub remaining: i64,
+    /// Whether the stop has been crossed; once set, every fresh price retries
+    /// the exit until `remaining` is closed
+    pub triggered: bool,
+    /// Client order index of the exit order in flight
+    pub exit_client_order_index: Option<i64>,
+}
+
+/// Something a trailing stop did
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TrailingStopEvent {
+    /// Prices are older than the staleness limit; the stop is paused
+    Stale { age: Duration },
+    /// Fresh prices arrived again after a pause
+    Resumed,
+    /// The stop fired and an exit order was accepted
+    Triggered {
+        price: Decimal,
+        stop_price: Decimal,
+        client_order_index: i64,
+        base_amount: i64,
+    },
+    /// The exit order was not accepted; it is retried on the next price
+    ExitFailed { error: String },
+    /// The whole size has been closed
+    Completed,
+}
+
+struct Inner {
+    state: TrailingStopState,
+    /// When the latest fresh price was observed, on the stop's clock
+    last_price_at: Option<Instant>,
+    paused: bool,
+}
+
+/// A trailing stop on one market
+pub struct TrailingStop {
+    tx_client: Arc<TxClient>,
+    market_index: u8,
+    config: TrailingStopConfig,
+    clock: Arc<dyn Clock>,
+    /// Serializes price updates so the stop never fires twice at once
+    firing: tokio::sync::Mutex<()>,
+    inner: Mutex<Inner>,
+    events: broadcast::Sender<TrailingStopEvent>,
+}
+
+impl TrailingStop {
+    /// Start trailing a position of `config.size` in one market
+    pub fn attach(tx_client: Arc<TxClient>, market_index: u8, config: TrailingStopConfig) -> Self {
+        let state = TrailingStopState {
+            remaining: config.size,
+            ..TrailingStopState::default()
+        };
+        let (events, _) = broadcast::channel(EVENT_BUFFER);
+        Self {
+            clock: tx_client.clock().clone(),
+            tx_client,
+            market_index,
+            config,
+            firing: tokio::sync::Mutex::new(()),
+            inner: Mutex::new(Inner {
+                state,
+                last_price_at: None,
+                paused: false,
+            }),
+            events,
+        }
+    }
+
+    /// Use `clock` instead of the client's to judge how old prices are
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Subscribe to the stop's events
+    pub fn subscribe(&self) -> broadcast::Receiver<TrailingStopEvent> {
+        self.events.subscribe()
+    }
+
+    /// Snapshot of the state, for persisting
+    pub fn export(&self) -> TrailingStopState {
+        self.lock().state.clone()
+    }
+
+    /// Restore state exported earlier, e.g. after a restart
+    pub fn import(&self, state: TrailingStopState) {
+        self.lock().state = state;
+    }
+
+    /// Save the state to `store`, under a name derived from the market
+    pub fn save_state(&self, store: &dyn StateStore) -> Result<()> {
+        save_json(store, &self.state_name(), &self.export())
+    }
+
+    /// Restore the state saved by [`TrailingStop::save_state`] if the
+    /// position it protects is still open
+    ///
+    /// The account's position in the market must still be on the stop's
+    /// side; the restored `remaining` is capped at its size. Otherwise the
+    /// saved state is discarded and the stop starts fresh.
+    pub async fn restore_and_reconcile(&self, store: &dyn StateStore) -> Result<RestoreReport> {
+        let Some(mut state) = load_json::<TrailingStopState>(store, &self.state_name()) else {
+            return Ok(RestoreReport::default());
+        };
+        let positions = self
+            .tx_client
+            .http_client()?
+            .get_account_positions(self.tx_client.account_index())
+            .await?;
+        let size = positions
+            .iter()
+            .find(|position| position.market_id == self.market_index)
+            .map_or(Decimal::ZERO, |position| position.size());
+        let open = match self.config.side {
+            PositionSide::Long => size,
+            PositionSide::Short => -size,
+        };
+        let open = (open * Decimal::from(10i64.pow(self.config.size_decimals)))
+            .trunc()
+            .to_i64()
+            .unwrap_or(0);
+
+        if open <= 0 {
+            tracing::warn!(
+                market_index = self.market_index,
+                "Position behind the saved trailing stop is closed; starting fresh"
+            );
+            return Ok(RestoreReport {
+                found: true,
+                kept: 0,
+                discarded: 1,
+            });
+        }
+        state.remaining = state.remaining.min(open);
+        self.import(state);
+        Ok(RestoreReport {
+            found: true,
+            kept: 1,
+            discarded: 0,
+        })
+    }
+
+    fn state_name(&self) -> String {
+        format!("trailing_stop_{}", self.market_index)
+    }
+
+    /// Current stop price, once a price has been seen
+    pub fn stop_price(&self) -> Option<Decimal> {
+        self.lock()
+            .state
+            .extreme
+            .map(|extreme| self.stop_for(extreme))
+    }
+
+    /// Follow the mid of a new best bid and ask, observed now
+    pub async fn on_top_of_book(&self, best_bid: Decimal, best_ask: Decimal) -> Result<()> {
+        let mid = (best_bid + best_ask) / Decimal::TWO;
+        self.on_fresh_price(FreshPrice::observed(mid, self.clock.as_ref()))
+            .await
+    }
+
+    /// Follow an order book's mid, observed now
+    pub async fn on_order_book(&self, order_book: &OrderBook) -> Result<()> {
+        match order_book.mid_price() {
+            Some(mid) => {
+                self.on_fresh_price(FreshPrice::observed(mid, self.clock.as_ref()))
+                    .await
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Follow a price observed at `observed_at_ms` (Unix milliseconds)
+    ///
+    /// For prices stamped by the exchange; the stamp's age on the wall clock
+    /// is carried over to the monotonic clock when the price arrives.
+    pub async fn on_price(&self, price: Decimal, observed_at_ms: i64) -> Result<()> {
+        let age = self.clock.now_ms().saturating_sub(observed_at_ms).max(0) as u64;
+        let now = self.clock.now_instant();
+        let observed_at = now.checked_sub(Duration::from_millis(age)).unwrap_or(now);
+        self.on_fresh_price(FreshPrice::new(price, observed_at))
+            .await
+    }
+
+    /// Follow a price, judged by when it was observed
+    ///
+    /// A price older than `max_staleness` is ignored and pauses the stop. A
+    /// price through the stop fires the exit straight away, even if it gapped
+    /// far past the stop level; the exit's worst price is set from the price
+    /// that fired it.
+    pub async fn on_fresh_price(&self, price: FreshPrice) -> Result<()> {
+        let _firing = self.firing.lock().await;
+
+        let age = price.age(self.clock.as_ref());
+        let FreshPrice {
+            value: price,
+            observed_at,
+        } = price;
+        let fire = {
+            let mut inner = self.lock();
+            if age > self.config.max_staleness {
+                if !inner.paused {
+                    inner.paused = true;
+                    tracing::warn!(
+                        market_index = self.market_index,
+                        ?age,
+                        "Trailing stop paused on stale prices"
+                    );
+                    let _ = self.events.send(TrailingStopEvent::Stale { age });
+                }
+                return Ok(());
+            }
+            if std::mem::take(&mut inner.paused) {
+                let _ = self.events.send(TrailingStopEvent::Resumed);
+            }
+            inner.last_price_at = Some(observed_at);
+
+            let state = &mut inner.state;
+            if state.remaining <= 0 {
+                return Ok(());
+            }
+            let extreme = match (state.extreme, self.config.side) {
+                (Some(extreme), PositionSide::Long) => extreme.max(price),
+                (Some(extreme), PositionSide::Short) => extreme.min(price),
+                (None, _) => price,
+            };
+            state.extreme = Some(extreme);
+            let stop_price = self.stop_for(extreme);
+            let crossed = match self.config.side {
+                PositionSide::Long => price <= stop_price,
+                PositionSide::Short => price >= stop_price,
+            };
+            state.triggered |= crossed;
+            (state.triggered && state.exit_client_order_index.is_none())
+                .then_some((stop_price, state.remaining))
+        };
+
+        if let Some((stop_price, base_amount)) = fire {
+            self.fire(price, stop_price, base_amount).await?;
+        }
+        Ok(())
+    }
+
+    /// Pause the stop if no fresh price arrived within `max_staleness`
+    ///
+    /// Call this from a timer to notice a feed that went quiet.
+    pub fn check_staleness(&self) {
+        let now = self.clock.now_instant();
+        let mut inner = self.lock();
+        let Some(last) = inner.last_price_at else {
+            return;
+        };
+        let age = now.saturating_duration_since(last);
+        if age > self.config.max_staleness && !inner.paused {
+            inner.paused = true;
+            tracing::warn!(
+                market_index = self.market_index,
+                ?age,
+                "Trailing stop paused, no fresh prices"
+            );
+            let _ = self.events.send(TrailingStopEvent::Stale { age });
+        }
+    }
+
+    /// Follow fills of the exit order
+    ///
+    /// A partially filled exit that the exchange then cancels is re-sent for
+    /// the rest on the next price.
+    pub fn on_event(&self, event: &OrderEvent) {
+        let mut inner = self.lock();
+        let state = &mut inner.state;
+        let Some(exit) = state.exit_client_order_index else {
+            return;
+        };
+        match *event {
+            OrderEvent::Fill {
+                client_order_index,
+                base_amount,
+            } if client_order_index == exit => {
+                let filled = (base_amount * Decimal::from(10i64.pow(self.config.size_decimals)))
+                    .round()
+                    .to_i64()
+                    .unwrap_or(0);
+                state.remaining -= filled;
+                if state.remaining <= 0 {
+                    state.remaining = 0;
+                    state.exit_client_order_index = None;
+                    let _ = self.events.send(TrailingStopEvent::Completed);
+                }
+            }
+            OrderEvent::Update {
+                client_order_index,
+                state: order_state,
+                ..
+            } if client_order_index == exit
+                && matches!(order_state, OrderState::Cancelled | OrderState::Rejected(_)) =>
+            {
+                // Filled exits are closed out by their fills instead
+                state.exit_client_order_index = None;
+            }
+            _ => {}
+        }
+    }
+
+    /// Apply every order event in an `account_all` frame
+    pub fn apply_account_frame(&self, data: &Value) {
+        for event in OrderEvent::from_account_frame(data) {
+            self.on_event(&event);
+        }
+    }
+
+    fn stop_for(&self, extreme: Decimal) -> Decimal {
+        let distance = match self.config.trail {
+            Trail::Bps(bps) => extreme * bps / Decimal::from(10_000),
+            Trail::Distance(distance) => distance,
+        };
+        match self.config.side {
+            PositionSide::Long => extreme - distance,
+            PositionSide::Short => extreme + distance,
+        }
+    }
+
+    /// Send the reduce-only exit for `base_amount`
+    async fn fire(&self, price: Decimal, stop_price: Decimal, base_amount: i64) -> Result<()> {
+        let is_ask = self.config.side == PositionSide::Long;
+        let slippage = self.config.max_slippage_bps / Decimal::from(10_000);
+        let scale = Decimal::from(10u64.pow(self.config.price_decimals));
+        let worst = if is_ask {
+            (price * (Decimal::ONE - slippage) * scale).floor()
+        } else {
+            (price * (Decimal::ONE + slippage) * scale).ceil()
+        };
+        let worst = worst
+            .to_u64()
+            .map(|worst| worst.max(MIN_ORDER_PRICE))
+            .filter(|worst| *worst <= MAX_ORDER_PRICE)
+            .ok_or_else(|| {
+                LighterError::ValidationError(format!("Exit price {worst} is out of range"))
+            })?;
+        let client_order_index = self.tx_client.next_client_order_index();
+
+        let sent = async {
+            let order = self
+                .tx_client
+                .create_market_order(
+                    self.market_index,
+                    client_order_index,
+                    base_amount,
+                    worst,
+                    u8::from(is_ask),
+                    true,
+                    None,
+                )
+                .await?;
+            let response = self.tx_client.send_transaction(&order).await?;
+            if !response.is_success() {
+                return Err(LighterError::ApiError(format!(
+                    "Exit rejected with code {}: {}",
+                    response.code,
+                    response.message.unwrap_or_default()
+                )));
+            }
+            Ok(())
+        }
+        .await;
+
+        match sent {
+            Ok(()) => {
+                self.lock().state.exit_client_order_index = Some(client_order_index);
+                tracing::info!(
+                    market_index = self.market_index,
+                    %price,
+                    %stop_price,
+                    base_amount,
+                    "Trailing stop fired"
+                );
+                let _ = self.events.send(TrailingStopEvent::Triggered {
+                    price,
+                    stop_price,
+                    client_order_index,
+                    base_amount,
+                });
+            }
+            Err(e) => {
+                tracing::warn!(market_index = self.market_index, error = %e, "Trailing stop exit failed");
+                let _ = self.events.send(TrailingStopEvent::ExitFailed {
+                    error: e.to_string(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        self.inner
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::test_support::mock_builder;
+    use crate::transport::{HttpResponse, MockTransport};
+
+    const SEND_TX_PATH: &str = "/api/v1/sendTx";
+
+    fn config(side: PositionSide) -> TrailingStopConfig {
+        TrailingStopConfig {
+            side,
+            trail: Trail::Bps(Decimal::new(100, 0)),
+            size: 1000,
+            price_decimals: 2,
+            size_decimals: 4,
+            max_slippage_bps: Decimal::new(50, 0),
+            max_staleness: Duration::from_secs(5),
+        }
+    }
+
+    fn stop(config: TrailingStopConfig) -> (TrailingStop, Arc<MockTransport>) {
+        let mock = Arc::new(MockTransport::new());
+        mock.set_handler(SEND_TX_PATH, |_| {
+            Ok(HttpResponse::new(200, r#"{"code":200,"tx_hash":"0xabc"}"#))
+        });
+        let tx_client = mock_builder(&mock).build().unwrap();
+        tx_client.nonces().set(1, 0, 0);
+        (TrailingStop::attach(Arc::new(tx_client), 0, config), mock)
+    }
+
+    fn now() -> i64 {
+        chrono::Utc::now().timestamp_millis()
+    }
+
+    /// Exit orders sent so far
+    fn exits(mock: &MockTransport) -> Vec<Value> {
+        mock.requests_to(SEND_TX_PATH)
+            .iter()
+            .map(|request| {
+                let form: Vec<(String, String)> =
+                    serde_urlencoded::from_bytes(&request.body).unwrap();
+                serde_json::from_str(&form[1].1).unwrap()
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_ratchets_and_fires_below_the_stop() {
+        let (stop, mock) = stop(config(PositionSide::Long));
+        stop.on_price(Decimal::new(100, 0), now()).await.unwrap();
+        stop.on_price(Decimal::new(105, 0), now()).await.unwrap();
+        // The stop never moves back down
+        stop.on_price(Decimal::new(104, 0), now()).await.unwrap();
+        assert_eq!(stop.stop_price(), Some(Decimal::new(10395, 2)));
+        assert!(exits(&mock).is_empty());
+
+        stop.on_price(Decimal::new(1039, 1), now()).await.unwrap();
+        let exits = exits(&mock);
+        assert_eq!(exits.len(), 1);
+        assert_eq!(exits[0]["IsAsk"], 1);
+        assert_eq!(exits[0]["ReduceOnly"], 1);
+        assert_eq!(exits[0]["BaseAmount"], 1000);
+        // 50 bps under 103.90
+        assert_eq!(exits[0]["Price"], 10338);
+
+        // No second exit while the first is in flight
+        stop.on_price(Decimal::new(103, 0), now()).await.unwrap();
+        assert_eq!(mock.requests_to(SEND_TX_PATH).len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_gap_through_fires_from_the_gap_price() {
+        let (stop, mock) = stop(config(PositionSide::Long));
+        let mut events = stop.subscribe();
+        stop.on_price(Decimal::new(105, 0), now()).await.unwrap();
+        stop.on_price(Decimal::new(90, 0), now()).await.unwrap();
+
+        assert_eq!(exits(&mock)[0]["Price"], 8955);
+        assert!(matches!(
+            events.try_recv().unwrap(),
+            TrailingStopEvent::Triggered { price, .. } if price == Decimal::new(90, 0)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_short_stop_trails_above_the_low() {
+        let (stop, mock) = stop(TrailingStopConfig {
+            trail: Trail::Distance(Decimal::new(2, 0)),
+            ..config(PositionSide::Short)
+        });
+        stop.on_price(Decimal::new(100, 0), now()).await.unwrap();
+        stop.on_price(Decimal::new(95, 0), now()).await.unwrap();
+        stop.on_price(Decimal::new(96, 0), now()).await.unwrap();
+        assert_eq!(stop.stop_price(), Some(Decimal::new(97, 0)));
+        assert!(exits(&mock).is_empty());
+
+        stop.on_price(Decimal::new(97, 0), now()).await.unwrap();
+        let exits = exits(&mock);
+        assert_eq!(exits[0]["IsAsk"], 0);
+        assert_eq!(exits[0]["Price"], 9749);
+    }
+
+    #[tokio::test]
+    async fn test_stale_prices_pause_instead_of_firing() {
+        let (stop, mock) = stop(config(PositionSide::Long));
+        let mut events = stop.subscribe();
+        stop.on_price(Decimal::new(105, 0), now()).await.unwrap();
+
+        // A stale price through the stop is ignored
+        stop.on_price(Decimal::new(90, 0), now() - 60_000)
+            .await
+            .unwrap();
+        assert!(exits(&mock).is_empty());
+        assert!(matches!(
+            events.try_recv().unwrap(),
+            TrailingStopEvent::Stale { .. }
+        ));
+
+        stop.on_price(Decimal::new(104, 0), now()).await.unwrap();
+        assert_eq!(events.try_recv().unwrap(), TrailingStopEvent::Resumed);
+        assert!(exits(&mock).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_frozen_feed_pauses_on_the_monotonic_clock() {
+        let (stop, mock) = stop(config(PositionSide::Long));
+        let clock = Arc::new(ManualClock::at_ms(0));
+        let stop = stop.clock(clock.clone());
+        let mut events = stop.subscribe();
+
+        let last = FreshPrice::observed(Decimal::new(105, 0), clock.as_ref());
+        stop.on_fresh_price(last).await.unwrap();
+
+        // The feed freezes; the quiet is noticed once the limit passes
+        clock.advance(Duration::from_secs(5));
+        stop.check_staleness();
+        assert!(events.try_recv().is_err());
+        clock.advance(Duration::from_millis(1));
+        stop.check_staleness();
+        assert_eq!(
+            events.try_recv().unwrap(),
+            TrailingStopEvent::Stale {
+                age: Duration::from_millis(5_001)
+            }
+        );
+
+        // A price through the stop that was observed before the freeze
+        // arrives late and is ignored
+        let late = FreshPrice::new(Decimal::new(90, 0), last.observed_at);
+        stop.on_fresh_price(late).await.unwrap();
+        assert!(exits(&mock).is_empty());
+
+        // Observed now, the same price fires the exit
+        let fresh = FreshPrice::observed(Decimal::new(90, 0), clock.as_ref());
+        stop.on_fresh_price(fresh).await.unwrap();
+        assert_eq!(events.try_recv().unwrap(), TrailingStopEvent::Resumed);
+        assert_eq!(exits(&mock).len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_quiet_feed_pauses_on_the_stops_clock() {
+        let (stop, _mock) = stop(config(PositionSide::Long));
+        let clock = Arc::new(ManualClock::at_ms(1_700_000_000_000));
+        let stop = stop.clock(clock.clone());
+        let mut events = stop.subscribe();
+        stop.on_price(Decimal::new(105, 0), clock.now_ms())
+            .await
+            .unwrap();
+
+        clock.advance(Duration::from_secs(5));
+        stop.check_staleness();
+        assert!(events.try_recv().is_err());
+
+        clock.advance(Duration::from_millis(1));
+        stop.check_staleness();
+        assert_eq!(
+            events.try_recv().unwrap(),
+            TrailingStopEvent::Stale {
+                age: Duration::from_millis(5_001)
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_partial_fill_resends_the_rest() {
+        let (stop, mock) = stop(config(PositionSide::Long));
+        let mut events = stop.subscribe();
+        stop.on_price(Decimal::new(105, 0), now()).await.unwrap();
+        stop.on_price(Decimal::new(100, 0), now()).await.unwrap();
+        let exit = stop.export().exit_client_order_index.unwrap();
+
+        // 0.06 of 0.1 fills, then the rest of the IOC order is cancelled
+        stop.on_event(&OrderEvent::Fill {
+            client_order_index: exit,
+            base_amount: Decimal::new(6, 2),
+        });
+        stop.on_event(&OrderEvent::Update {
+            client_order_index: exit,
+            order_index: MIN_ORDER_INDEX,
+            market_index: 0,
+            state: OrderState::Cancelled,
+        });
+        assert_eq!(stop.export().remaining, 400);
+
+        // Triggered stays set even though the price recovered
+        stop.on_price(Decimal::new(106, 0), now()).await.unwrap();
+        let exits = exits(&mock);
+        assert_eq!(exits.len(), 2);
+        assert_eq!(exits[1]["BaseAmount"], 400);
+
+        let exit = stop.export().exit_client_order_index.unwrap();
+        stop.on_event(&OrderEvent::Fill {
+            client_order_index: exit,
+            base_amount: Decimal::new(4, 2),
+        });
+        let events: Vec<_> = std::iter::from_fn(|| events.try_recv().ok()).collect();
+        assert_eq!(events.last(), Some(&TrailingStopEvent::Completed));
+        stop.on_price(Decimal::new(90, 0), now()).await.unwrap();
+        assert_eq!(mock.requests_to(SEND_TX_PATH).len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_rejected_exit_is_retried() {
+        let (stop, mock) = stop(config(PositionSide::Long));
+        mock.push_response(SEND_TX_PATH, 200, r#"{"code":21701,"message":"invalid"}"#);
+        let mut events = stop.subscribe();
+        stop.on_price(Decimal::new(105, 0), now()).await.unwrap();
+        stop.on_price(Decimal::new(100, 0), now()).await.unwrap();
+        assert!(matches!(
+            events.try_recv().unwrap(),
+            TrailingStopEvent::ExitFailed { .. }
+        ));
+
+        stop.on_price(Decimal::new(101, 0), now()).await.unwrap();
+        assert_eq!(mock.requests_to(SEND_TX_PATH).len(), 2);
+        assert!(stop.export().exit_client_order_index.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_exported_state_survives_restart() {
+        let (stop, _) = stop(config(PositionSide::Long));
+        stop.on_price(Decimal::new(105, 0), now()).await.unwrap();
+        let saved = serde_json::to_string(&stop.export()).unwrap();
+
+        let (restarted, mock) = self::stop(config(PositionSide::Long));
+        restarted.import(serde_json::from_str(&saved).unwrap());
+        assert_eq!(restarted.stop_price(), Some(Decimal::new(10395, 2)));
+        restarted
+            .on_price(Decimal::new(103, 0), now())
+            .await
+            .unwrap();
+        assert_eq!(exits(&mock).len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_restore_only_while_position_is_open() {
+        use crate::state_store::MemoryStateStore;
+
+        let (stop, mock) = stop(config(PositionSide::Long));
+        stop.on_price(Decimal::new(105, 0), now()).await.unwrap();
+        let store = MemoryStateStore::new();
+        stop.save_state(&store).unwrap();
+
+        let position = Arc::new(Mutex::new("0.0600"));
+        let served = position.clone();
+        mock.set_handler("/api/v1/account", move |_| {
+            let body = format!(
+                r#"{{"code":200,"accounts":[{{"index":1,"positions":[
+                    {{"market_id":0,"sign":1,"position":"{}","avg_entry_price":"100"}}
+                ]}}]}}"#,
+                served.lock().unwrap()
+            );
+            Ok(HttpResponse::new(200, body))
+        });
+
+        let restarted = TrailingStop::attach(stop.tx_client.clone(), 0, config(PositionSide::Long));
+        let report = restarted.restore_and_reconcile(&store).await.unwrap();
+        assert_eq!((report.kept, report.discarded), (1, 0));
+        let state = restarted.export();
+        assert_eq!(state.extreme, Some(Decimal::new(105, 0)));
+        // Capped at the 600 units still open
+        assert_eq!(state.remaining, 600);
+
+        *position.lock().unwrap() = "0";
+        let flat = TrailingStop::attach(stop.tx_client.clone(), 0, config(PositionSide::Long));
+        let report = flat.restore_and_reconcile(&store).await.unwrap();
+        assert_eq!((report.kept, report.discarded), (0, 1));
+        assert_eq!(flat.export().extreme, None);
+        assert_eq!(flat.export().remaining, 1000);
+    }
+}
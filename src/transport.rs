@@ -0,0 +1,414 @@
+//! HTTP transport used by the API client
+//!
+//! [`HTTPClient`](crate::client::HTTPClient) builds an [`HttpRequest`], hands it
+//! to a [`Transport`] and interprets the [`HttpResponse`]. The default
+//! [`ReqwestTransport`] talks to the network; [`MockTransport`] answers from a
+//! script, so nonce handling and submission logic can be tested offline.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use bytes::Bytes;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Client, Method};
+
+use crate::errors::{LighterError, Result};
+
+/// An HTTP request issued by the client
+#[derive(Debug, Clone)]
+pub struct HttpRequest {
+    pub method: Method,
+    pub url: String,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+}
+
+impl HttpRequest {
+    /// Create a GET request with an empty body
+    pub fn get(url: impl Into<String>) -> Self {
+        Self {
+            method: Method::GET,
+            url: url.into(),
+            headers: HeaderMap::new(),
+            body: Bytes::new(),
+        }
+    }
+
+    /// Create a POST request with the given body
+    pub fn post(url: impl Into<String>, body: Bytes) -> Self {
+        Self {
+            method: Method::POST,
+            url: url.into(),
+            headers: HeaderMap::new(),
+            body,
+        }
+    }
+
+    /// Add a header to the request
+    pub fn header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.headers.insert(name, value);
+        self
+    }
+
+    /// Path of the request URL, without scheme, host or query string
+    pub fn path(&self) -> &str {
+        let rest = self
+            .url
+            .split_once("://")
+            .map_or(self.url.as_str(), |(_, rest)| rest);
+        let path = rest.find('/').map_or("/", |i| &rest[i..]);
+        path.split(['?', '#']).next().unwrap_or(path)
+    }
+}
+
+/// An HTTP response as seen by the client
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub body: String,
+    /// Processing time the server reported in a `Server-Timing` or
+    /// `X-Response-Time` header
+    pub server_time: Option<Duration>,
+}
+
+impl HttpResponse {
+    /// Create a response with the given status code and body
+    pub fn new(status: u16, body: impl Into<String>) -> Self {
+        Self {
+            status,
+            body: body.into(),
+            server_time: None,
+        }
+    }
+
+    /// Set the processing time reported by the server
+    pub fn with_server_time(mut self, server_time: Duration) -> Self {
+        self.server_time = Some(server_time);
+        self
+    }
+
+    /// Whether the status code is in the 2xx range
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+}
+
+/// Future returned by [`Transport::execute`]
+#[cfg(not(target_arch = "wasm32"))]
+pub type TransportFuture<'a> = futures_util::future::BoxFuture<'a, Result<HttpResponse>>;
+
+/// Future returned by [`Transport::execute`]
+///
+/// Not `Send` on wasm32, where `fetch` futures are tied to the JS event loop.
+#[cfg(target_arch = "wasm32")]
+pub type TransportFuture<'a> = futures_util::future::LocalBoxFuture<'a, Result<HttpResponse>>;
+
+/// Executes HTTP requests on behalf of the client
+///
+/// Implementations return `Err` only when no response was received at all
+/// (connection failures, timeouts); non-2xx responses are returned as
+/// [`HttpResponse`]s for the client to interpret.
+pub trait Transport: Send + Sync {
+    /// Execute a request and return its response
+    fn execute(&self, request: HttpRequest) -> TransportFuture<'_>;
+}
+
+/// Transport backed by a `reqwest::Client`
+#[derive(Clone)]
+pub struct ReqwestTransport {
+    client: Client,
+}
+
+impl ReqwestTransport {
+    /// Create a transport on top of an existing `reqwest::Client`
+    pub fn new(client: Client) -> Self {
+        Self { client }
+    }
+
+    /// Get the underlying `reqwest::Client`
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+}
+
+impl Transport for ReqwestTransport {
+    fn execute(&self, request: HttpRequest) -> TransportFuture<'_> {
+        Box::pin(async move {
+            let request = self
+                .client
+                .request(request.method, &request.url)
+                .headers(request.headers)
+                .body(request.body)
+                .build()?;
+            #[cfg(feature = "wire-logging")]
+            crate::client::wire::log_request(&request);
+            #[cfg(feature = "wire-logging")]
+            let url = request.url().to_string();
+
+            let response = self.client.execute(request).await.map_err(map_error)?;
+            let status = response.status().as_u16();
+            let server_time = server_time(response.headers());
+            let body = response.text().await.map_err(map_error)?;
+            #[cfg(feature = "wire-logging")]
+            crate::client::wire::log_response(&url, status, &body);
+
+            Ok(HttpResponse {
+                status,
+                body,
+                server_time,
+            })
+        })
+    }
+}
+
+/// Server processing time from a response's headers
+///
+/// Reads the `dur` of the first `Server-Timing` metric that has one, in
+/// milliseconds, falling back to an `X-Response-Time` such as `12.5ms`.
+fn server_time(headers: &HeaderMap) -> Option<Duration> {
+    let millis = |value: &str| {
+        value
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .filter(|ms| ms.is_finite() && *ms >= 0.0)
+            .map(|ms| Duration::from_secs_f64(ms / 1000.0))
+    };
+    let server_timing = headers
+        .get_all("server-timing")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split([',', ';']))
+        .find_map(|param| param.trim().strip_prefix("dur=").and_then(millis));
+    server_timing.or_else(|| {
+        let value = headers.get("x-response-time")?.to_str().ok()?.trim();
+        millis(value.strip_suffix("ms").unwrap_or(value))
+    })
+}
+
+fn map_error(e: reqwest::Error) -> LighterError {
+    if e.is_timeout() {
+        LighterError::Timeout
+    } else {
+        LighterError::HttpError(e)
+    }
+}
+
+type Handler = Box<dyn Fn(&HttpRequest) -> Result<HttpResponse> + Send + Sync>;
+
+/// In-memory transport answering requests from a script
+///
+/// Responses are looked up by request path. Queued responses for a path are
+/// used first, in order; once they run out the path's handler (if any) answers.
+/// Requests to a path with neither fail with [`LighterError::Other`]. Every
+/// request is recorded for later assertions.
+///
+/// ```
+/// use std::sync::Arc;
+/// use lighter_rs::client::TxClient;
+/// use lighter_rs::transport::MockTransport;
+///
+/// # async fn example() -> lighter_rs::Result<()> {
+/// let mock = Arc::new(MockTransport::new());
+/// mock.push_response("/api/v1/nextNonce", 200, r#"{"code":200,"nonce":7}"#);
+///
+/// let tx_client = TxClient::builder()
+///     .api_url("http://mock")
+///     .private_key("0x0102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f202122232425262728")
+///     .transport(mock.clone())
+///     .build()?;
+/// tx_client.warm_up().await?;
+/// assert_eq!(mock.requests().len(), 1);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Default)]
+pub struct MockTransport {
+    queued: Mutex<HashMap<String, VecDeque<Result<HttpResponse>>>>,
+    handlers: Mutex<HashMap<String, Handler>>,
+    #[cfg(feature = "native")]
+    delays: Mutex<HashMap<String, Duration>>,
+    requests: Mutex<Vec<HttpRequest>>,
+}
+
+impl MockTransport {
+    /// Create a mock with no scripted responses
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue a response for the next request to `path`
+    pub fn push_response(&self, path: &str, status: u16, body: impl Into<String>) {
+        self.push_result(path, Ok(HttpResponse::new(status, body)));
+    }
+
+    /// Queue an error (such as [`LighterError::Timeout`]) for the next request to `path`
+    pub fn push_error(&self, path: &str, error: LighterError) {
+        self.push_result(path, Err(error));
+    }
+
+    fn push_result(&self, path: &str, result: Result<HttpResponse>) {
+        let mut queued = self.queued.lock().unwrap_or_else(|e| e.into_inner());
+        queued
+            .entry(path.to_string())
+            .or_default()
+            .push_back(result);
+    }
+
+    /// Answer requests to `path` with `handler` once its queue is empty
+    pub fn set_handler<F>(&self, path: &str, handler: F)
+    where
+        F: Fn(&HttpRequest) -> Result<HttpResponse> + Send + Sync + 'static,
+    {
+        let mut handlers = self.handlers.lock().unwrap_or_else(|e| e.into_inner());
+        handlers.insert(path.to_string(), Box::new(handler));
+    }
+
+    /// Hold every response to `path` for `delay`, to simulate a slow endpoint
+    ///
+    /// The request is recorded as soon as it is sent, before the delay.
+    #[cfg(feature = "native")]
+    pub fn set_delay(&self, path: &str, delay: Duration) {
+        let mut delays = self.delays.lock().unwrap_or_else(|e| e.into_inner());
+        delays.insert(path.to_string(), delay);
+    }
+
+    /// All requests received so far, in order
+    pub fn requests(&self) -> Vec<HttpRequest> {
+        self.requests
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Requests received so far for one path, in order
+    pub fn requests_to(&self, path: &str) -> Vec<HttpRequest> {
+        self.requests()
+            .into_iter()
+            .filter(|request| request.path() == path)
+            .collect()
+    }
+
+    fn respond(&self, request: &HttpRequest) -> Result<HttpResponse> {
+        let path = request.path();
+        let queued = self
+            .queued
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get_mut(path)
+            .and_then(VecDeque::pop_front);
+        if let Some(result) = queued {
+            return result;
+        }
+
+        let handlers = self.handlers.lock().unwrap_or_else(|e| e.into_inner());
+        match handlers.get(path) {
+            Some(handler) => handler(request),
+            None => Err(LighterError::Other(format!(
+                "no mock response for {} {path}",
+                request.method
+            ))),
+        }
+    }
+}
+
+impl Transport for MockTransport {
+    fn execute(&self, request: HttpRequest) -> TransportFuture<'_> {
+        let result = self.respond(&request);
+        #[cfg(feature = "native")]
+        let delay = self
+            .delays
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(request.path())
+            .copied();
+        self.requests
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(request);
+        Box::pin(async move {
+            #[cfg(feature = "native")]
+            if let Some(delay) = delay {
+                tokio::time::sleep(delay).await;
+            }
+            result
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_server_time_headers() {
+        let headers = |pairs: &[(&'static str, &'static str)]| {
+            let mut headers = HeaderMap::new();
+            for (name, value) in pairs {
+                headers.append(*name, HeaderValue::from_static(value));
+            }
+            headers
+        };
+        assert_eq!(server_time(&headers(&[])), None);
+        assert_eq!(
+            server_time(&headers(&[(
+                "server-timing",
+                "cache;desc=hit, app;dur=12.5"
+            )])),
+            Some(Duration::from_micros(12_500))
+        );
+        assert_eq!(
+            server_time(&headers(&[
+                ("server-timing", "edge"),
+                ("server-timing", "total;dur=3")
+            ])),
+            Some(Duration::from_millis(3))
+        );
+        assert_eq!(
+            server_time(&headers(&[("x-response-time", "7ms")])),
+            Some(Duration::from_millis(7))
+        );
+        assert_eq!(server_time(&headers(&[("x-response-time", "-1")])), None);
+    }
+
+    #[test]
+    fn test_request_path() {
+        let request = HttpRequest::get("https://api.lighter.xyz/api/v1/nextNonce?account_index=1");
+        assert_eq!(request.path(), "/api/v1/nextNonce");
+        assert_eq!(HttpRequest::get("http://mock").path(), "/");
+        assert_eq!(
+            HttpRequest::get("/api/v1/sendTx#x").path(),
+            "/api/v1/sendTx"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_mock_queue_then_handler() {
+        let mock = MockTransport::new();
+        mock.push_response("/a", 200, "first");
+        mock.push_error("/a", LighterError::Timeout);
+        mock.set_handler("/a", |request| {
+            Ok(HttpResponse::new(500, format!("{}", request.body.len())))
+        });
+
+        let get = || mock.execute(HttpRequest::get("http://mock/a"));
+        assert_eq!(get().await.unwrap(), HttpResponse::new(200, "first"));
+        assert!(matches!(get().await, Err(LighterError::Timeout)));
+        let post = mock
+            .execute(HttpRequest::post(
+                "http://mock/a",
+                Bytes::from_static(b"abc"),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(post, HttpResponse::new(500, "3"));
+        assert!(matches!(
+            mock.execute(HttpRequest::get("http://mock/b")).await,
+            Err(LighterError::Other(_))
+        ));
+
+        assert_eq!(mock.requests().len(), 4);
+        assert_eq!(mock.requests_to("/a").len(), 3);
+    }
+}
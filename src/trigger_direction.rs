@@ -0,0 +1,259 @@
+//! Direction checks for stop loss and take profit triggers
+//!
+//! A trigger on the wrong side of the market either gets the order rejected
+//! or fires it at once: a long's stop loss placed above the price sells
+//! straight away. A sell stop loss must trigger below the market and a buy
+//! stop loss above it; take profits are the other way round.
+//!
+//! [`TxClient::create_sl_order`](crate::client::TxClient::create_sl_order),
+//! [`TxClient::create_tp_order`](crate::client::TxClient::create_tp_order)
+//! and their limit variants check the trigger against the fresh mark price
+//! of an attached [`RiskState`](crate::risk::RiskState), such as a
+//! [`PositionManager`](crate::positions::PositionManager), and fail with
+//! [`LighterError::InvertedTrigger`]. Without a fresh mark the order is
+//! signed unchecked. [`TxClient::create_trigger_order`](crate::client::TxClient::create_trigger_order)
+//! takes a [`TriggerCheck`] to give the reference price explicitly, or to
+//! sign a trigger meant to fire at once with [`TriggerCheck::force`].
+//!
+//! ```
+//! use lighter_rs::constants::ORDER_TYPE_STOP_LOSS;
+//! use lighter_rs::trigger_direction::check_trigger;
+//! use lighter_rs::Decimal;
+//!
+//! let market = Decimal::new(3000, 0);
+//! // A long's stop below the market is fine, above it is not
+//! assert!(check_trigger(ORDER_TYPE_STOP_LOSS, true, Decimal::new(2900, 0), market).is_ok());
+//! assert!(check_trigger(ORDER_TYPE_STOP_LOSS, true, Decimal::new(3100, 0), market).is_err());
+//! ```
+
+use std::fmt;
+use std::time::Duration;
+
+use rust_decimal::Decimal;
+
+use crate::constants::*;
+use crate::errors::{LighterError, Result};
+
+/// Default of [`TriggerCheck::max_mark_staleness`]
+const DEFAULT_MAX_MARK_STALENESS: Duration = Duration::from_secs(5);
+
+/// Kind of order with a trigger
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriggerKind {
+    StopLoss,
+    TakeProfit,
+}
+
+impl TriggerKind {
+    /// Kind of an order type; `None` for orders without a trigger
+    pub fn of(order_type: u8) -> Option<Self> {
+        match order_type {
+            ORDER_TYPE_STOP_LOSS | ORDER_TYPE_STOP_LOSS_LIMIT => Some(TriggerKind::StopLoss),
+            ORDER_TYPE_TAKE_PROFIT | ORDER_TYPE_TAKE_PROFIT_LIMIT => Some(TriggerKind::TakeProfit),
+            _ => None,
+        }
+    }
+
+    /// Whether the trigger of a sell (`is_ask`) or buy must be below the
+    /// market
+    pub fn triggers_below(self, is_ask: bool) -> bool {
+        (self == TriggerKind::StopLoss) == is_ask
+    }
+}
+
+impl fmt::Display for TriggerKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            TriggerKind::StopLoss => "stop loss",
+            TriggerKind::TakeProfit => "take profit",
+        })
+    }
+}
+
+/// Refuse a trigger on the side of `reference_price` that fires the order
+/// at once
+///
+/// A trigger at the reference price is refused too. Orders without a
+/// trigger pass.
+pub fn check_trigger(
+    order_type: u8,
+    is_ask: bool,
+    trigger_price: Decimal,
+    reference_price: Decimal,
+) -> Result<()> {
+    let Some(kind) = TriggerKind::of(order_type) else {
+        return Ok(());
+    };
+    let on_side = if kind.triggers_below(is_ask) {
+        trigger_price < reference_price
+    } else {
+        trigger_price > reference_price
+    };
+    if !on_side {
+        return Err(LighterError::InvertedTrigger {
+            kind,
+            is_ask,
+            trigger_price,
+            reference_price,
+        });
+    }
+    Ok(())
+}
+
+/// Refuse a trigger order whose execution price can't fill once triggered
+///
+/// A triggered sell fills at its price or better, so its price must be at
+/// or below the trigger; a buy's at or above it. The stop loss and take
+/// profit helpers of [`TxClient`](crate::client::TxClient) check this
+/// before anything else.
+pub fn check_execution_price(is_ask: bool, price: u64, trigger_price: u64) -> Result<()> {
+    let fills = if is_ask {
+        price <= trigger_price
+    } else {
+        price >= trigger_price
+    };
+    if !fills {
+        return Err(LighterError::ExecutionPriceBeyondTrigger {
+            is_ask,
+            price,
+            trigger_price,
+        });
+    }
+    Ok(())
+}
+
+/// How a trigger order is checked before it is signed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TriggerCheck {
+    /// Price the trigger is judged against, in the integer units of the
+    /// order's prices; the attached state's mark when `None`
+    pub reference_price: Option<u64>,
+    /// Oldest mark the trigger is judged against; an older one leaves the
+    /// order unchecked
+    pub max_mark_staleness: Duration,
+    /// Sign the order whichever side its trigger is on
+    pub force: bool,
+}
+
+impl TriggerCheck {
+    /// Check against the attached state's mark
+    pub fn new() -> Self {
+        Self {
+            reference_price: None,
+            max_mark_staleness: DEFAULT_MAX_MARK_STALENESS,
+            force: false,
+        }
+    }
+
+    pub fn reference_price(mut self, reference_price: u64) -> Self {
+        self.reference_price = Some(reference_price);
+        self
+    }
+
+    pub fn max_mark_staleness(mut self, max_mark_staleness: Duration) -> Self {
+        self.max_mark_staleness = max_mark_staleness;
+        self
+    }
+
+    /// Skip the check, for triggers meant to fire at once
+    pub fn force(mut self) -> Self {
+        self.force = true;
+        self
+    }
+}
+
+impl Default for TriggerCheck {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_side_type_and_price_relation() {
+        let reference = Decimal::new(3000, 0);
+        let below = Decimal::new(2900, 0);
+        let above = Decimal::new(3100, 0);
+
+        // (order type, is_ask, trigger, accepted)
+        let cases = [
+            (ORDER_TYPE_STOP_LOSS, true, below, true),
+            (ORDER_TYPE_STOP_LOSS, true, reference, false),
+            (ORDER_TYPE_STOP_LOSS, true, above, false),
+            (ORDER_TYPE_STOP_LOSS, false, below, false),
+            (ORDER_TYPE_STOP_LOSS, false, reference, false),
+            (ORDER_TYPE_STOP_LOSS, false, above, true),
+            (ORDER_TYPE_STOP_LOSS_LIMIT, true, below, true),
+            (ORDER_TYPE_STOP_LOSS_LIMIT, true, reference, false),
+            (ORDER_TYPE_STOP_LOSS_LIMIT, true, above, false),
+            (ORDER_TYPE_STOP_LOSS_LIMIT, false, below, false),
+            (ORDER_TYPE_STOP_LOSS_LIMIT, false, reference, false),
+            (ORDER_TYPE_STOP_LOSS_LIMIT, false, above, true),
+            (ORDER_TYPE_TAKE_PROFIT, true, below, false),
+            (ORDER_TYPE_TAKE_PROFIT, true, reference, false),
+            (ORDER_TYPE_TAKE_PROFIT, true, above, true),
+            (ORDER_TYPE_TAKE_PROFIT, false, below, true),
+            (ORDER_TYPE_TAKE_PROFIT, false, reference, false),
+            (ORDER_TYPE_TAKE_PROFIT, false, above, false),
+            (ORDER_TYPE_TAKE_PROFIT_LIMIT, true, below, false),
+            (ORDER_TYPE_TAKE_PROFIT_LIMIT, true, reference, false),
+            (ORDER_TYPE_TAKE_PROFIT_LIMIT, true, above, true),
+            (ORDER_TYPE_TAKE_PROFIT_LIMIT, false, below, true),
+            (ORDER_TYPE_TAKE_PROFIT_LIMIT, false, reference, false),
+            (ORDER_TYPE_TAKE_PROFIT_LIMIT, false, above, false),
+        ];
+        for (order_type, is_ask, trigger, accepted) in cases {
+            let result = check_trigger(order_type, is_ask, trigger, reference);
+            assert_eq!(
+                result.is_ok(),
+                accepted,
+                "type {order_type}, is_ask {is_ask}, trigger {trigger}: {result:?}"
+            );
+            if let Err(e) = result {
+                assert!(matches!(e, LighterError::InvertedTrigger { .. }));
+            }
+        }
+
+        // Orders without a trigger aren't checked
+        for order_type in [ORDER_TYPE_LIMIT, ORDER_TYPE_MARKET] {
+            for is_ask in [true, false] {
+                assert!(check_trigger(order_type, is_ask, above, reference).is_ok());
+            }
+        }
+    }
+
+    #[test]
+    fn test_error_names_the_expected_side() {
+        let err = check_trigger(
+            ORDER_TYPE_STOP_LOSS,
+            true,
+            Decimal::new(3100, 0),
+            Decimal::new(3000, 0),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "A sell stop loss must trigger below the reference price 3000, got 3100"
+        );
+    }
+
+    #[test]
+    fn test_execution_price_must_fill_at_the_trigger() {
+        assert!(check_execution_price(true, 2990, 3000).is_ok());
+        assert!(check_execution_price(true, 3000, 3000).is_ok());
+        assert!(check_execution_price(false, 3010, 3000).is_ok());
+        assert_eq!(
+            check_execution_price(true, 3010, 3000)
+                .unwrap_err()
+                .to_string(),
+            "A sell trigger order's price must be at or below its trigger 3000, got 3010"
+        );
+        assert!(matches!(
+            check_execution_price(false, 2990, 3000),
+            Err(LighterError::ExecutionPriceBeyondTrigger { is_ask: false, .. })
+        ));
+    }
+}
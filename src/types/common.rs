@@ -1,18 +1,43 @@
 //! Common types and structures used across transactions
 
 use crate::errors::Result;
-use serde::{Deserialize, Serialize};
+use crate::signer::Signature;
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Serialize, Serializer};
 
 /// Transaction options for customizing transaction parameters
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+///
+/// Every `create_*`, `cancel_*`, `modify_*` and `update_*` method of
+/// [`TxClient`](crate::client::TxClient) takes these as its last argument;
+/// `None` takes the defaults of each field.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
 pub struct TransactOpts {
+    /// Account the transaction is signed for; the client's by default
     pub from_account_index: Option<i64>,
+    /// API key signing the transaction; the client's by default
     pub api_key_index: Option<u8>,
+    /// When the transaction expires, in Unix milliseconds; zero for ten
+    /// minutes from now
     #[serde(default)]
     pub expired_at: i64,
+    /// Nonce to sign with; taken from the nonce cache, or fetched from the
+    /// API, by default
     pub nonce: Option<i64>,
+    /// Sign without taking a nonce from the cache, so the next transaction
+    /// gets the same one; for inspecting what would be sent
     #[serde(default)]
     pub dry_run: bool,
+    /// Sign orders' expiries exactly as given, instead of filling in the
+    /// default expiry of resting orders and clearing it on immediate or
+    /// cancel ones
+    #[serde(default)]
+    pub keep_order_expiry: bool,
+    /// Strategy the transaction is attributed to in logs, metrics and the
+    /// order tracker; never sent to the exchange, see the
+    /// [`strategy`](crate::strategy) module
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub strategy_id: Option<String>,
 }
 
 /// Trait that all transaction types must implement
@@ -23,27 +48,308 @@ pub trait TxInfo {
     /// Get transaction info as JSON string
     fn get_tx_info(&self) -> Result<String>;
 
+    /// Append the transaction info JSON to `buf`
+    ///
+    /// Defaults to copying [`TxInfo::get_tx_info`]; implementations serialize
+    /// straight into the buffer instead.
+    fn write_tx_info(&self, buf: &mut Vec<u8>) -> Result<()> {
+        buf.extend_from_slice(self.get_tx_info()?.as_bytes());
+        Ok(())
+    }
+
     /// Get the transaction hash (if signed)
     fn get_tx_hash(&self) -> Option<String>;
 
+    /// Get the signature bytes (if signed)
+    fn signature(&self) -> Option<&[u8]>;
+
+    /// Attach a signature and the hex-encoded hash it was computed over
+    fn set_signature(&mut self, sig: Signature, signed_hash: String);
+
     /// Validate the transaction
     fn validate(&self) -> Result<()>;
 
     /// Hash the transaction for signing
     fn hash(&self, lighter_chain_id: u32) -> Result<Vec<u8>>;
+
+    /// The named values [`TxInfo::hash`] is computed over, in hashing order
+    ///
+    /// For recording what a signature commits to, such as the nonce and
+    /// expiry. `None` for types whose hash isn't implemented yet.
+    fn hash_preimage_fields(&self, _lighter_chain_id: u32) -> Option<HashPreimage> {
+        None
+    }
+}
+
+/// The values a transaction's Poseidon2 hash is computed over
+///
+/// Every field is kept as the `u64` field element it is hashed as, under its
+/// struct field name, in the order lighter-go hashes them; a field hashed as
+/// several elements gets a suffix for each. Serializes as a map in that order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HashPreimage {
+    fields: Vec<(&'static str, u64)>,
+}
+
+impl HashPreimage {
+    /// The fields every transaction hash starts with: chain and type, then
+    /// nonce and expiry ahead of the account
+    pub(crate) fn header(
+        lighter_chain_id: u32,
+        tx_type: u8,
+        nonce: i64,
+        expired_at: i64,
+        account_index: i64,
+        api_key_index: u8,
+    ) -> Self {
+        Self { fields: Vec::new() }
+            .field("chain_id", lighter_chain_id as u64)
+            .field("tx_type", tx_type as u64)
+            .field("nonce", nonce as u64)
+            .field("expired_at", expired_at as u64)
+            .field("account_index", account_index as u64)
+            .field("api_key_index", api_key_index as u64)
+    }
+
+    pub(crate) fn field(mut self, name: &'static str, value: u64) -> Self {
+        self.fields.push((name, value));
+        self
+    }
+
+    /// A 64-bit amount as two 32-bit limbs, low then high, as lighter-go
+    /// hashes USDC amounts
+    pub(crate) fn limbs(self, lo: &'static str, hi: &'static str, value: u64) -> Self {
+        self.field(lo, value & 0xFFFF_FFFF).field(hi, value >> 32)
+    }
+
+    /// Value of the field called `name`
+    pub fn get(&self, name: &str) -> Option<u64> {
+        self.fields
+            .iter()
+            .find(|(field, _)| *field == name)
+            .map(|(_, value)| *value)
+    }
+
+    /// Fields in hashing order
+    pub fn iter(&self) -> impl Iterator<Item = (&'static str, u64)> + '_ {
+        self.fields.iter().copied()
+    }
+
+    pub fn chain_id(&self) -> u32 {
+        self.header_field("chain_id") as u32
+    }
+
+    pub fn tx_type(&self) -> u8 {
+        self.header_field("tx_type") as u8
+    }
+
+    /// Nonce the signature commits to
+    pub fn nonce(&self) -> i64 {
+        self.header_field("nonce") as i64
+    }
+
+    /// Transaction expiry the signature commits to, in milliseconds since
+    /// the Unix epoch
+    pub fn expired_at(&self) -> i64 {
+        self.header_field("expired_at") as i64
+    }
+
+    pub fn account_index(&self) -> i64 {
+        self.header_field("account_index") as i64
+    }
+
+    pub fn api_key_index(&self) -> u8 {
+        self.header_field("api_key_index") as u8
+    }
+
+    fn header_field(&self, name: &str) -> u64 {
+        self.get(name).unwrap_or_default()
+    }
+
+    /// Poseidon2 hash of the fields, as [`TxInfo::hash`] returns it
+    pub fn hash(&self) -> Vec<u8> {
+        use poseidon_hash::{hash_to_quintic_extension, Goldilocks};
+
+        let elements: Vec<Goldilocks> = self
+            .fields
+            .iter()
+            .map(|(_, value)| Goldilocks::from(*value))
+            .collect();
+        // 5 field elements * 8 bytes = 40 bytes
+        hash_to_quintic_extension(&elements).to_bytes_le().to_vec()
+    }
+}
+
+impl Serialize for HashPreimage {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.fields.len()))?;
+        for (name, value) in &self.fields {
+            map.serialize_entry(name, value)?;
+        }
+        map.end()
+    }
+}
+
+/// A signed transaction of any type, ready for submission
+///
+/// Serializes to an envelope `{"tx_type":..,"tx_info":"..","tx_hash":".."}`
+/// that [`SignedTx::from_json`] imports again, so one service can sign and
+/// another submit with [`TxClient::send_signed`](crate::client::TxClient::send_signed).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedTx {
+    pub tx_type: u8,
+    pub tx_info: String,
+    pub tx_hash: Option<String>,
+}
+
+impl SignedTx {
+    /// Capture the type, JSON payload and hash of a signed transaction
+    pub fn new<T: TxInfo>(tx: &T) -> Result<Self> {
+        Ok(Self {
+            tx_type: tx.get_tx_type(),
+            tx_info: tx.get_tx_info()?,
+            tx_hash: tx.get_tx_hash(),
+        })
+    }
 }
 
 /// Order information structure used in order-related transactions
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
 pub struct OrderInfo {
+    #[serde(rename = "MarketIndex")]
     pub market_index: u8,
+    #[serde(rename = "ClientOrderIndex")]
     pub client_order_index: i64,
+    #[serde(rename = "BaseAmount")]
     pub base_amount: i64,
-    pub price: u32,
+    #[serde(rename = "Price")]
+    pub price: u64,
+    #[serde(rename = "IsAsk")]
     pub is_ask: u8,
+    #[serde(rename = "Type")]
     pub order_type: u8,
+    #[serde(rename = "TimeInForce")]
     pub time_in_force: u8,
+    #[serde(rename = "ReduceOnly")]
     pub reduce_only: u8,
-    pub trigger_price: u32,
+    #[serde(rename = "TriggerPrice")]
+    pub trigger_price: u64,
+    #[serde(rename = "OrderExpiry")]
     pub order_expiry: i64,
 }
+
+/// Strategies and checks shared by the property tests of the transaction types
+#[cfg(test)]
+pub(crate) mod proptest_support {
+    use std::fmt::Debug;
+    use std::sync::OnceLock;
+
+    use proptest::prelude::*;
+    use serde::de::DeserializeOwned;
+    use serde::Serialize;
+
+    use super::TxInfo;
+    use crate::client::HTTPClient;
+    use crate::signer::{PoseidonKeyManager, Signature, Signer};
+    use crate::test_support::TEST_KEY;
+
+    /// Optional signatures; proptest has no `Arbitrary` for 80-byte arrays
+    pub(crate) fn signature() -> impl Strategy<Value = Option<Signature>> {
+        proptest::option::of(proptest::array::uniform(any::<u8>()))
+    }
+
+    fn http() -> &'static HTTPClient {
+        static HTTP: OnceLock<HTTPClient> = OnceLock::new();
+        HTTP.get_or_init(|| HTTPClient::new("http://unused").unwrap())
+    }
+
+    fn key_manager() -> &'static PoseidonKeyManager {
+        static KEY: OnceLock<PoseidonKeyManager> = OnceLock::new();
+        KEY.get_or_init(|| PoseidonKeyManager::from_hex(TEST_KEY).unwrap())
+    }
+
+    fn decode<T: DeserializeOwned>(json: &str) -> std::result::Result<T, TestCaseError> {
+        serde_json::from_str(json).map_err(|e| TestCaseError::fail(format!("{e}: {json}")))
+    }
+
+    /// JSON encoding decodes back to an equal value
+    pub(crate) fn check_json_round_trip<T>(value: &T) -> std::result::Result<(), TestCaseError>
+    where
+        T: Serialize + DeserializeOwned + PartialEq + Debug,
+    {
+        let json = serde_json::to_string(value).unwrap();
+        prop_assert_eq!(&decode::<T>(&json)?, value);
+        Ok(())
+    }
+
+    /// The tx_info JSON, written either way, and the sendTx body built from it
+    /// all decode back to the transaction
+    pub(crate) fn check_tx_round_trip<T>(tx: &T) -> std::result::Result<(), TestCaseError>
+    where
+        T: TxInfo + DeserializeOwned + PartialEq + Debug,
+    {
+        let json = tx.get_tx_info().unwrap();
+        let mut written = Vec::new();
+        tx.write_tx_info(&mut written).unwrap();
+        prop_assert_eq!(written.as_slice(), json.as_bytes());
+        prop_assert_eq!(&decode::<T>(&json)?, tx);
+
+        let body = http().send_tx_body(tx).unwrap();
+        let form: Vec<(String, String)> = serde_urlencoded::from_bytes(&body).unwrap();
+        let expected: Vec<(String, String)> = http()
+            .to_form_data(tx.get_tx_type(), &json)
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), value))
+            .collect();
+        prop_assert_eq!(form, expected);
+        Ok(())
+    }
+
+    /// The hash is 40 bytes, stable across calls and determined by the wire
+    /// fields alone
+    pub(crate) fn check_hash_deterministic<T>(
+        tx: &T,
+        chain_id: u32,
+    ) -> std::result::Result<(), TestCaseError>
+    where
+        T: TxInfo + DeserializeOwned,
+    {
+        let hash = tx.hash(chain_id).unwrap();
+        prop_assert_eq!(hash.len(), 40);
+        prop_assert_eq!(&tx.hash(chain_id).unwrap(), &hash);
+
+        let decoded: T = decode(&tx.get_tx_info().unwrap())?;
+        prop_assert_eq!(decoded.hash(chain_id).unwrap(), hash);
+        Ok(())
+    }
+
+    /// Signing the hash gives a reproducible signature that verifies against
+    /// the signing key and no longer verifies once the hash changes
+    pub(crate) fn check_sign_verify<T: TxInfo>(
+        mut tx: T,
+        chain_id: u32,
+    ) -> std::result::Result<(), TestCaseError> {
+        let key = key_manager();
+        let hash = tx.hash(chain_id).unwrap();
+        let sig = key.sign(&hash).unwrap();
+        prop_assert_eq!(key.sign(&hash).unwrap(), sig);
+        prop_assert!(key.verify(&hash, &sig).unwrap());
+
+        let mut tampered = hash.clone();
+        tampered[0] ^= 1;
+        prop_assert!(!matches!(key.verify(&tampered, &sig), Ok(true)));
+
+        tx.set_signature(sig, hex::encode(&hash));
+        prop_assert_eq!(tx.signature(), Some(&sig[..]));
+        prop_assert_eq!(tx.get_tx_hash(), Some(hex::encode(&hash)));
+
+        // The exposed fields hash back to what was signed
+        if let Some(preimage) = tx.hash_preimage_fields(chain_id) {
+            prop_assert_eq!(preimage.chain_id(), chain_id);
+            prop_assert_eq!(preimage.tx_type(), tx.get_tx_type());
+            prop_assert_eq!(tx.get_tx_hash(), Some(hex::encode(preimage.hash())));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,294 @@
+//! Decoding executed transactions back into their typed structs
+
+use serde::de::DeserializeOwned;
+
+use super::common::{OrderInfo, SignedTx, TxInfo};
+use super::orders::*;
+use super::pools::*;
+use super::transfers::*;
+use crate::constants::*;
+use crate::errors::{LighterError, Result};
+
+/// A transaction decoded from its type and tx_info JSON
+///
+/// Decoded transactions carry the signature the API reported but no signed
+/// hash; re-hash them with [`TxInfo::hash`] to compare against the API's.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodedTx {
+    CreateOrder(L2CreateOrderTxInfo),
+    CancelOrder(L2CancelOrderTxInfo),
+    ModifyOrder(L2ModifyOrderTxInfo),
+    CancelAllOrders(L2CancelAllOrdersTxInfo),
+    CreateGroupedOrders(L2CreateGroupedOrdersTxInfo),
+    Transfer(L2TransferTxInfo),
+    Withdraw(L2WithdrawTxInfo),
+    ChangePubKey(L2ChangePubKeyTxInfo),
+    UpdateLeverage(L2UpdateLeverageTxInfo),
+    UpdateMargin(L2UpdateMarginTxInfo),
+    CreateSubAccount(L2CreateSubAccountTxInfo),
+    CreatePublicPool(L2CreatePublicPoolTxInfo),
+    UpdatePublicPool(L2UpdatePublicPoolTxInfo),
+    MintShares(L2MintSharesTxInfo),
+    BurnShares(L2BurnSharesTxInfo),
+    /// A type the SDK can't build, such as the exchange's internal
+    /// transactions, with its tx_info as received
+    Unknown {
+        tx_type: u8,
+        raw: String,
+    },
+}
+
+impl DecodedTx {
+    pub fn tx_type(&self) -> u8 {
+        match (self, self.as_tx_info()) {
+            (DecodedTx::Unknown { tx_type, .. }, _) => *tx_type,
+            (_, tx_info) => tx_info.map_or(0, |tx_info| tx_info.get_tx_type()),
+        }
+    }
+
+    /// The decoded transaction, or `None` when its type is unknown
+    pub fn as_tx_info(&self) -> Option<&dyn TxInfo> {
+        Some(match self {
+            DecodedTx::CreateOrder(tx) => tx,
+            DecodedTx::CancelOrder(tx) => tx,
+            DecodedTx::ModifyOrder(tx) => tx,
+            DecodedTx::CancelAllOrders(tx) => tx,
+            DecodedTx::CreateGroupedOrders(tx) => tx,
+            DecodedTx::Transfer(tx) => tx,
+            DecodedTx::Withdraw(tx) => tx,
+            DecodedTx::ChangePubKey(tx) => tx,
+            DecodedTx::UpdateLeverage(tx) => tx,
+            DecodedTx::UpdateMargin(tx) => tx,
+            DecodedTx::CreateSubAccount(tx) => tx,
+            DecodedTx::CreatePublicPool(tx) => tx,
+            DecodedTx::UpdatePublicPool(tx) => tx,
+            DecodedTx::MintShares(tx) => tx,
+            DecodedTx::BurnShares(tx) => tx,
+            DecodedTx::Unknown { .. } => return None,
+        })
+    }
+
+    pub fn is_unknown(&self) -> bool {
+        matches!(self, DecodedTx::Unknown { .. })
+    }
+}
+
+impl SignedTx {
+    /// Decode a transaction's tx_info JSON as the API reports it
+    ///
+    /// Types the SDK doesn't build decode to [`DecodedTx::Unknown`]; a
+    /// supported type whose JSON doesn't match fails with a JSON error.
+    pub fn from_api_json(tx_type: u8, tx_info: &str) -> Result<DecodedTx> {
+        fn parse<T: DeserializeOwned>(
+            tx_info: &str,
+            wrap: fn(T) -> DecodedTx,
+        ) -> Result<DecodedTx> {
+            Ok(wrap(serde_json::from_str(tx_info)?))
+        }
+
+        match tx_type {
+            TX_TYPE_L2_CREATE_ORDER => parse(tx_info, |mut tx: L2CreateOrderTxInfo| {
+                restore_order_info(&mut tx);
+                DecodedTx::CreateOrder(tx)
+            }),
+            TX_TYPE_L2_CANCEL_ORDER => parse(tx_info, DecodedTx::CancelOrder),
+            TX_TYPE_L2_MODIFY_ORDER => parse(tx_info, DecodedTx::ModifyOrder),
+            TX_TYPE_L2_CANCEL_ALL_ORDERS => parse(tx_info, DecodedTx::CancelAllOrders),
+            TX_TYPE_L2_CREATE_GROUPED_ORDERS => parse(tx_info, DecodedTx::CreateGroupedOrders),
+            TX_TYPE_L2_TRANSFER => parse(tx_info, DecodedTx::Transfer),
+            TX_TYPE_L2_WITHDRAW => parse(tx_info, DecodedTx::Withdraw),
+            TX_TYPE_L2_CHANGE_PUB_KEY => parse(tx_info, DecodedTx::ChangePubKey),
+            TX_TYPE_L2_UPDATE_LEVERAGE => parse(tx_info, DecodedTx::UpdateLeverage),
+            TX_TYPE_L2_UPDATE_MARGIN => parse(tx_info, DecodedTx::UpdateMargin),
+            TX_TYPE_L2_CREATE_SUB_ACCOUNT => parse(tx_info, DecodedTx::CreateSubAccount),
+            TX_TYPE_L2_CREATE_PUBLIC_POOL => parse(tx_info, DecodedTx::CreatePublicPool),
+            TX_TYPE_L2_UPDATE_PUBLIC_POOL => parse(tx_info, DecodedTx::UpdatePublicPool),
+            TX_TYPE_L2_MINT_SHARES => parse(tx_info, DecodedTx::MintShares),
+            TX_TYPE_L2_BURN_SHARES => parse(tx_info, DecodedTx::BurnShares),
+            tx_type => Ok(DecodedTx::Unknown {
+                tx_type,
+                raw: tx_info.to_string(),
+            }),
+        }
+    }
+
+    /// Decode this transaction's tx_info
+    pub fn decode(&self) -> Result<DecodedTx> {
+        Self::from_api_json(self.tx_type, &self.tx_info)
+    }
+
+    /// The JSON envelope of this transaction, for [`SignedTx::from_json`]
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    /// Import a transaction exported with [`SignedTx::to_json`]
+    ///
+    /// The tx_info must decode as its tx_type and carry a signature of
+    /// [`SIGNATURE_LENGTH`] bytes; it is kept byte for byte as exported.
+    pub fn from_json(json: &str) -> Result<Self> {
+        let signed: Self = serde_json::from_str(json)?;
+        let decoded = signed.decode()?;
+        match decoded.as_tx_info() {
+            Some(tx_info) if tx_info.signature().is_some() => Ok(signed),
+            Some(_) => Err(LighterError::ValidationError(format!(
+                "Transaction of type {} is not signed",
+                signed.tx_type
+            ))),
+            None => Err(LighterError::ValidationError(format!(
+                "Can't import transaction type {}",
+                signed.tx_type
+            ))),
+        }
+    }
+}
+
+/// Rebuild the unserialized copy of the order fields kept by the builders
+fn restore_order_info(tx: &mut L2CreateOrderTxInfo) {
+    tx.order_info = OrderInfo {
+        market_index: tx.market_index,
+        client_order_index: tx.client_order_index,
+        base_amount: tx.base_amount,
+        price: tx.price,
+        is_ask: tx.is_ask,
+        order_type: tx.order_type,
+        time_in_force: tx.time_in_force,
+        reduce_only: tx.reduce_only,
+        trigger_price: tx.trigger_price,
+        order_expiry: tx.order_expiry,
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    /// Decoding a transaction's own tx_info gives back the transaction
+    fn check_decodes<T>(
+        tx: &T,
+        unwrap: fn(DecodedTx) -> Option<T>,
+    ) -> std::result::Result<(), TestCaseError>
+    where
+        T: TxInfo + PartialEq + std::fmt::Debug,
+    {
+        let signed = SignedTx::new(tx).unwrap();
+        let decoded = signed.decode().unwrap();
+        prop_assert_eq!(decoded.tx_type(), tx.get_tx_type());
+        prop_assert_eq!(
+            decoded.as_tx_info().unwrap().get_tx_info().unwrap(),
+            signed.tx_info
+        );
+        let decoded = unwrap(decoded);
+        prop_assert_eq!(decoded.as_ref(), Some(tx));
+        Ok(())
+    }
+
+    macro_rules! variant {
+        ($variant:ident) => {
+            |decoded| match decoded {
+                DecodedTx::$variant(tx) => Some(tx),
+                _ => None,
+            }
+        };
+    }
+
+    proptest! {
+        #[test]
+        fn test_every_type_decodes(
+            create in any::<L2CreateOrderTxInfo>(),
+            cancel in any::<L2CancelOrderTxInfo>(),
+            modify in any::<L2ModifyOrderTxInfo>(),
+            cancel_all in any::<L2CancelAllOrdersTxInfo>(),
+            grouped in any::<L2CreateGroupedOrdersTxInfo>(),
+            transfer in any::<L2TransferTxInfo>(),
+            withdraw in any::<L2WithdrawTxInfo>(),
+            change_pub_key in any::<L2ChangePubKeyTxInfo>(),
+            (leverage, margin) in any::<(L2UpdateLeverageTxInfo, L2UpdateMarginTxInfo)>(),
+            sub_account in any::<L2CreateSubAccountTxInfo>(),
+            (create_pool, update_pool) in any::<(L2CreatePublicPoolTxInfo, L2UpdatePublicPoolTxInfo)>(),
+            (mint, burn) in any::<(L2MintSharesTxInfo, L2BurnSharesTxInfo)>(),
+        ) {
+            let mut create = create;
+            restore_order_info(&mut create);
+            check_decodes(&create, variant!(CreateOrder))?;
+            check_decodes(&cancel, variant!(CancelOrder))?;
+            check_decodes(&modify, variant!(ModifyOrder))?;
+            check_decodes(&cancel_all, variant!(CancelAllOrders))?;
+            check_decodes(&grouped, variant!(CreateGroupedOrders))?;
+            check_decodes(&transfer, variant!(Transfer))?;
+            check_decodes(&withdraw, variant!(Withdraw))?;
+            check_decodes(&change_pub_key, variant!(ChangePubKey))?;
+            check_decodes(&leverage, variant!(UpdateLeverage))?;
+            check_decodes(&margin, variant!(UpdateMargin))?;
+            check_decodes(&sub_account, variant!(CreateSubAccount))?;
+            check_decodes(&create_pool, variant!(CreatePublicPool))?;
+            check_decodes(&update_pool, variant!(UpdatePublicPool))?;
+            check_decodes(&mint, variant!(MintShares))?;
+            check_decodes(&burn, variant!(BurnShares))?;
+        }
+    }
+
+    #[test]
+    fn test_unknown_and_malformed() {
+        let raw = r#"{"AccountIndex":1,"MarketIndex":0}"#;
+        let decoded = SignedTx::from_api_json(TX_TYPE_INTERNAL_LIQUIDATE_POSITION, raw).unwrap();
+        assert!(decoded.is_unknown());
+        assert_eq!(decoded.tx_type(), TX_TYPE_INTERNAL_LIQUIDATE_POSITION);
+        assert!(decoded.as_tx_info().is_none());
+        assert_eq!(
+            decoded,
+            DecodedTx::Unknown {
+                tx_type: TX_TYPE_INTERNAL_LIQUIDATE_POSITION,
+                raw: raw.to_string()
+            }
+        );
+
+        assert!(matches!(
+            SignedTx::from_api_json(TX_TYPE_L2_CREATE_ORDER, raw),
+            Err(LighterError::JsonError(_))
+        ));
+    }
+
+    #[test]
+    fn test_signed_tx_json_round_trip() {
+        let cancel = L2CancelOrderTxInfo {
+            account_index: 1,
+            api_key_index: 0,
+            market_index: 0,
+            index: 5,
+            expired_at: 1_700_000_600_000,
+            nonce: 9,
+            sig: Some(std::array::from_fn(|i| i as u8)),
+            signed_hash: None,
+        };
+        let signed = SignedTx::new(&cancel).unwrap();
+        let imported = SignedTx::from_json(&signed.to_json().unwrap()).unwrap();
+        assert_eq!(imported, signed);
+        assert_eq!(imported.tx_type, TX_TYPE_L2_CANCEL_ORDER);
+        assert_eq!(
+            imported.decode().unwrap(),
+            DecodedTx::CancelOrder(cancel.clone())
+        );
+
+        // A signature of the wrong length fails to decode
+        let mut short: serde_json::Value = serde_json::from_str(&signed.tx_info).unwrap();
+        short["Sig"] = "AQI=".into();
+        let short = SignedTx {
+            tx_info: short.to_string(),
+            ..signed.clone()
+        };
+        let err = SignedTx::from_json(&short.to_json().unwrap()).unwrap_err();
+        assert!(err.to_string().contains("invalid signature length"));
+
+        let unsigned = SignedTx::new(&L2CancelOrderTxInfo {
+            sig: None,
+            ..cancel
+        })
+        .unwrap();
+        assert!(matches!(
+            SignedTx::from_json(&unsigned.to_json().unwrap()),
+            Err(LighterError::ValidationError(_))
+        ));
+    }
+}
@@ -1,6 +1,7 @@
 //! Transaction types and request builders for the Lighter Protocol
 
 pub mod common;
+pub mod decode;
 pub mod orders;
 pub mod pools;
 pub mod transfers;
@@ -8,6 +9,7 @@ pub mod validation;
 
 // Re-export commonly used types
 pub use common::*;
+pub use decode::*;
 pub use orders::*;
 pub use pools::*;
 pub use transfers::*;
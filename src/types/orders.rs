@@ -1,27 +1,33 @@
 //! Order-related transaction types
 
-use super::{OrderInfo, TxInfo};
+use super::{HashPreimage, OrderInfo, TxInfo};
 use crate::constants::*;
 use crate::errors::{LighterError, Result};
+use crate::signer::Signature;
 use serde::{Deserialize, Serialize};
 
 /// Create Order Transaction Request
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
 pub struct CreateOrderTxReq {
     pub market_index: u8,
     pub client_order_index: i64,
     pub base_amount: i64,
-    pub price: u32,
+    /// Price in ticks of the market's price decimals; the protocol's field
+    /// is 32 bits, so signing fails with [`LighterError::PriceTooHigh`]
+    /// above [`MAX_ORDER_PRICE`]
+    pub price: u64,
     pub is_ask: u8,
     pub order_type: u8,
     pub time_in_force: u8,
     pub reduce_only: u8,
-    pub trigger_price: u32,
+    pub trigger_price: u64,
     pub order_expiry: i64,
 }
 
 /// L2 Create Order Transaction Info
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
 pub struct L2CreateOrderTxInfo {
     #[serde(rename = "AccountIndex")]
     pub account_index: i64,
@@ -35,7 +41,7 @@ pub struct L2CreateOrderTxInfo {
     #[serde(rename = "BaseAmount")]
     pub base_amount: i64,
     #[serde(rename = "Price")]
-    pub price: u32,
+    pub price: u64,
     #[serde(rename = "IsAsk")]
     pub is_ask: u8,
     #[serde(rename = "Type")]
@@ -45,7 +51,7 @@ pub struct L2CreateOrderTxInfo {
     #[serde(rename = "ReduceOnly")]
     pub reduce_only: u8,
     #[serde(rename = "TriggerPrice")]
-    pub trigger_price: u32,
+    pub trigger_price: u64,
     #[serde(rename = "OrderExpiry")]
     pub order_expiry: i64,
     #[serde(rename = "ExpiredAt")]
@@ -55,50 +61,59 @@ pub struct L2CreateOrderTxInfo {
     #[serde(rename = "Sig")]
     #[serde(skip_serializing_if = "Option::is_none")]
     #[serde(with = "base64_serde", default)]
-    pub sig: Option<Vec<u8>>,
+    #[cfg_attr(
+        test,
+        proptest(strategy = "crate::types::common::proptest_support::signature()")
+    )]
+    pub sig: Option<Signature>,
     #[serde(skip)]
+    #[cfg_attr(test, proptest(value = "None"))]
     pub signed_hash: Option<String>,
 
     // Keep original order_info for internal use (not serialized)
     #[serde(skip)]
-    #[serde(default = "default_order_info")]
+    #[cfg_attr(test, proptest(value = "OrderInfo::default()"))]
     pub order_info: OrderInfo,
 }
 
-fn default_order_info() -> OrderInfo {
-    OrderInfo {
-        market_index: 0,
-        client_order_index: 0,
-        base_amount: 0,
-        price: 0,
-        is_ask: 0,
-        order_type: 0,
-        time_in_force: 0,
-        reduce_only: 0,
-        trigger_price: 0,
-        order_expiry: 0,
-    }
+/// Convert decoded signature bytes into a fixed-size signature
+fn signature_from_vec<E: serde::de::Error>(bytes: Vec<u8>) -> std::result::Result<Signature, E> {
+    let len = bytes.len();
+    bytes.try_into().map_err(|_| {
+        E::custom(format!(
+            "invalid signature length: expected {SIGNATURE_LENGTH}, got {len}"
+        ))
+    })
 }
 
 // Helper module for base64 serialization of signature
 pub(crate) mod base64_serde {
+    use super::signature_from_vec;
+    use crate::signer::Signature;
     use serde::{Deserialize, Deserializer, Serializer};
 
-    pub fn serialize<S>(bytes: &Option<Vec<u8>>, serializer: S) -> Result<S::Ok, S::Error>
+    // 80 bytes encode to 108 base64 characters
+    const ENCODED_LEN: usize = 108;
+
+    pub fn serialize<S>(sig: &Option<Signature>, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
-        match bytes {
-            Some(vec) => {
+        match sig {
+            Some(sig) => {
                 use base64::Engine;
-                let encoded = base64::engine::general_purpose::STANDARD.encode(vec);
-                serializer.serialize_str(&encoded)
+                let mut buf = [0u8; ENCODED_LEN];
+                let len = base64::engine::general_purpose::STANDARD
+                    .encode_slice(sig, &mut buf)
+                    .map_err(serde::ser::Error::custom)?;
+                // base64 output is always ASCII
+                serializer.serialize_str(std::str::from_utf8(&buf[..len]).unwrap())
             }
             None => serializer.serialize_none(),
         }
     }
 
-    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Vec<u8>>, D::Error>
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Signature>, D::Error>
     where
         D: Deserializer<'de>,
     {
@@ -106,41 +121,36 @@ pub(crate) mod base64_serde {
         match s {
             Some(b64_str) => {
                 use base64::Engine;
-                base64::engine::general_purpose::STANDARD
+                let bytes = base64::engine::general_purpose::STANDARD
                     .decode(&b64_str)
-                    .map(Some)
-                    .map_err(serde::de::Error::custom)
+                    .map_err(serde::de::Error::custom)?;
+                signature_from_vec(bytes).map(Some)
             }
             None => Ok(None),
         }
     }
 }
 
-// Helper module for hex serialization (for other transaction types)
-pub(crate) mod hex_serde {
+// Helper module for base64 serialization of plain bytes, as Go encodes `[]byte`
+pub(crate) mod base64_bytes_serde {
+    use base64::Engine;
     use serde::{Deserialize, Deserializer, Serializer};
 
-    pub fn serialize<S>(bytes: &Option<Vec<u8>>, serializer: S) -> Result<S::Ok, S::Error>
+    pub fn serialize<S>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
-        match bytes {
-            Some(vec) => serializer.serialize_str(&hex::encode(vec)),
-            None => serializer.serialize_none(),
-        }
+        serializer.serialize_str(&base64::engine::general_purpose::STANDARD.encode(bytes))
     }
 
-    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Vec<u8>>, D::Error>
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<u8>, D::Error>
     where
         D: Deserializer<'de>,
     {
-        let s: Option<String> = Option::deserialize(deserializer)?;
-        match s {
-            Some(hex_str) => hex::decode(&hex_str)
-                .map(Some)
-                .map_err(serde::de::Error::custom),
-            None => Ok(None),
-        }
+        let b64_str = String::deserialize(deserializer)?;
+        base64::engine::general_purpose::STANDARD
+            .decode(&b64_str)
+            .map_err(serde::de::Error::custom)
     }
 }
 
@@ -153,10 +163,23 @@ impl TxInfo for L2CreateOrderTxInfo {
         Ok(serde_json::to_string(self)?)
     }
 
+    fn write_tx_info(&self, buf: &mut Vec<u8>) -> Result<()> {
+        Ok(serde_json::to_writer(buf, self)?)
+    }
+
     fn get_tx_hash(&self) -> Option<String> {
         self.signed_hash.clone()
     }
 
+    fn signature(&self) -> Option<&[u8]> {
+        self.sig.as_ref().map(|sig| sig.as_slice())
+    }
+
+    fn set_signature(&mut self, sig: Signature, signed_hash: String) {
+        self.sig = Some(sig);
+        self.signed_hash = Some(signed_hash);
+    }
+
     fn validate(&self) -> Result<()> {
         // Validate account index
         if self.account_index < MIN_ACCOUNT_INDEX {
@@ -183,43 +206,36 @@ impl TxInfo for L2CreateOrderTxInfo {
     }
 
     fn hash(&self, lighter_chain_id: u32) -> Result<Vec<u8>> {
-        use poseidon_hash::{hash_to_quintic_extension, Goldilocks};
+        Ok(self.preimage(lighter_chain_id).hash())
+    }
+
+    fn hash_preimage_fields(&self, lighter_chain_id: u32) -> Option<HashPreimage> {
+        Some(self.preimage(lighter_chain_id))
+    }
+}
 
+impl L2CreateOrderTxInfo {
+    fn preimage(&self, lighter_chain_id: u32) -> HashPreimage {
         // Field order matches lighter-go implementation
         // See: lighter-go/types/txtypes/create_order.go
-        let mut elements = Vec::new();
-
-        // 1. Chain ID
-        elements.push(Goldilocks::from(lighter_chain_id as u64));
-
-        // 2. Transaction type
-        elements.push(Goldilocks::from(TX_TYPE_L2_CREATE_ORDER as u64));
-
-        // 3-4. CRITICAL: Nonce and ExpiredAt come BEFORE account info!
-        elements.push(Goldilocks::from(self.nonce as u64));
-        elements.push(Goldilocks::from(self.expired_at as u64));
-
-        // 5-6. Account info
-        elements.push(Goldilocks::from(self.account_index as u64));
-        elements.push(Goldilocks::from(self.api_key_index as u64));
-
-        // 7-16. Order info fields (now flattened at top level)
-        elements.push(Goldilocks::from(self.market_index as u64));
-        elements.push(Goldilocks::from(self.client_order_index as u64));
-        elements.push(Goldilocks::from(self.base_amount as u64));
-        elements.push(Goldilocks::from(self.price as u64));
-        elements.push(Goldilocks::from(self.is_ask as u64));
-        elements.push(Goldilocks::from(self.order_type as u64));
-        elements.push(Goldilocks::from(self.time_in_force as u64));
-        elements.push(Goldilocks::from(self.reduce_only as u64));
-        elements.push(Goldilocks::from(self.trigger_price as u64));
-        elements.push(Goldilocks::from(self.order_expiry as u64));
-
-        // Hash using Poseidon2
-        let hash_result = hash_to_quintic_extension(&elements);
-
-        // Convert Fp5Element to bytes (5 field elements * 8 bytes = 40 bytes)
-        Ok(hash_result.to_bytes_le().to_vec())
+        HashPreimage::header(
+            lighter_chain_id,
+            TX_TYPE_L2_CREATE_ORDER,
+            self.nonce,
+            self.expired_at,
+            self.account_index,
+            self.api_key_index,
+        )
+        .field("market_index", self.market_index as u64)
+        .field("client_order_index", self.client_order_index as u64)
+        .field("base_amount", self.base_amount as u64)
+        .field("price", self.price)
+        .field("is_ask", self.is_ask as u64)
+        .field("order_type", self.order_type as u64)
+        .field("time_in_force", self.time_in_force as u64)
+        .field("reduce_only", self.reduce_only as u64)
+        .field("trigger_price", self.trigger_price)
+        .field("order_expiry", self.order_expiry as u64)
     }
 }
 
@@ -232,53 +248,110 @@ impl L2CreateOrderTxInfo {
             return Err(LighterError::MarketIndexTooHigh(self.market_index));
         }
 
+        // Client order index, nil or in range
+        if self.client_order_index != NIL_CLIENT_ORDER_INDEX {
+            if self.client_order_index < MIN_CLIENT_ORDER_INDEX {
+                return Err(LighterError::ClientOrderIndexTooLow(
+                    self.client_order_index,
+                ));
+            }
+            if self.client_order_index > MAX_CLIENT_ORDER_INDEX {
+                return Err(LighterError::ClientOrderIndexTooHigh(
+                    self.client_order_index,
+                ));
+            }
+        }
+
         // Price
         if self.price < MIN_ORDER_PRICE {
             return Err(LighterError::PriceTooLow(self.price));
         }
+        check_price_fields(self.price, self.trigger_price)?;
 
         // IsAsk
         if self.is_ask != 0 && self.is_ask != 1 {
             return Err(LighterError::IsAskInvalid);
         }
 
+        // Time in force
+        if self.time_in_force > MAX_TIME_IN_FORCE {
+            return Err(LighterError::OrderTimeInForceInvalid);
+        }
+
+        // Post-only only rests limit orders on the book
+        if self.time_in_force == TIME_IN_FORCE_POST_ONLY && self.order_type != ORDER_TYPE_LIMIT {
+            return Err(LighterError::PostOnlyOrderTypeInvalid(self.order_type));
+        }
+
         Ok(())
     }
 }
 
+/// Refuse prices past the protocol's 32-bit price fields, which the exchange
+/// can't represent
+fn check_price_fields(price: u64, trigger_price: u64) -> Result<()> {
+    if price > MAX_ORDER_PRICE {
+        return Err(LighterError::PriceTooHigh(price));
+    }
+    if trigger_price > MAX_ORDER_TRIGGER_PRICE {
+        return Err(LighterError::OrderTriggerPriceInvalid);
+    }
+    Ok(())
+}
+
 /// Cancel Order Transaction Request
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
 pub struct CancelOrderTxReq {
     pub market_index: u8,
+    /// Client order index, or exchange order index from [`MIN_ORDER_INDEX`]
+    /// up; see
+    /// [`TxClient::cancel_order_by_client_index`](crate::client::TxClient::cancel_order_by_client_index)
+    /// and
+    /// [`TxClient::cancel_order_by_order_index`](crate::client::TxClient::cancel_order_by_order_index)
     pub index: i64,
 }
 
 /// Modify Order Transaction Request
-#[derive(Debug, Clone, Serialize, Deserialize)]
+///
+/// The protocol has no partial modify: every field is restated and replaces
+/// the resting order's. To move only the price or only the size, use
+/// [`TxClient::modify_price`](crate::client::TxClient::modify_price) or
+/// [`TxClient::modify_size`](crate::client::TxClient::modify_size), which
+/// fill the other fields from the live order.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
 pub struct ModifyOrderTxReq {
     pub market_index: u8,
+    /// Exchange order index
     pub index: i64,
+    /// New remaining size; restating the size the order was placed with
+    /// after a partial fill grows it back
     pub base_amount: i64,
-    pub price: u32,
-    pub trigger_price: u32,
+    pub price: u64,
+    /// Zero for orders without a trigger
+    pub trigger_price: u64,
 }
 
 /// Cancel All Orders Transaction Request
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
 pub struct CancelAllOrdersTxReq {
     pub time_in_force: u8,
     pub time: i64,
 }
 
 /// Create Grouped Orders Transaction Request
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
 pub struct CreateGroupedOrdersTxReq {
     pub grouping_type: u8,
     pub orders: Vec<CreateOrderTxReq>,
 }
 
 /// L2 Cancel Order Transaction Info
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
 pub struct L2CancelOrderTxInfo {
     #[serde(rename = "AccountIndex")]
     pub account_index: i64,
@@ -295,8 +368,13 @@ pub struct L2CancelOrderTxInfo {
     #[serde(rename = "Sig")]
     #[serde(skip_serializing_if = "Option::is_none")]
     #[serde(with = "base64_serde", default)]
-    pub sig: Option<Vec<u8>>,
+    #[cfg_attr(
+        test,
+        proptest(strategy = "crate::types::common::proptest_support::signature()")
+    )]
+    pub sig: Option<Signature>,
     #[serde(skip)]
+    #[cfg_attr(test, proptest(value = "None"))]
     pub signed_hash: Option<String>,
 }
 
@@ -309,10 +387,23 @@ impl TxInfo for L2CancelOrderTxInfo {
         Ok(serde_json::to_string(self)?)
     }
 
+    fn write_tx_info(&self, buf: &mut Vec<u8>) -> Result<()> {
+        Ok(serde_json::to_writer(buf, self)?)
+    }
+
     fn get_tx_hash(&self) -> Option<String> {
         self.signed_hash.clone()
     }
 
+    fn signature(&self) -> Option<&[u8]> {
+        self.sig.as_ref().map(|sig| sig.as_slice())
+    }
+
+    fn set_signature(&mut self, sig: Signature, signed_hash: String) {
+        self.sig = Some(sig);
+        self.signed_hash = Some(signed_hash);
+    }
+
     fn validate(&self) -> Result<()> {
         if self.account_index < MIN_ACCOUNT_INDEX || self.account_index > MAX_ACCOUNT_INDEX {
             return Err(LighterError::AccountIndexTooLow(self.account_index));
@@ -327,52 +418,63 @@ impl TxInfo for L2CancelOrderTxInfo {
     }
 
     fn hash(&self, lighter_chain_id: u32) -> Result<Vec<u8>> {
-        use poseidon_hash::{hash_to_quintic_extension, Goldilocks};
+        Ok(self.preimage(lighter_chain_id).hash())
+    }
 
+    fn hash_preimage_fields(&self, lighter_chain_id: u32) -> Option<HashPreimage> {
+        Some(self.preimage(lighter_chain_id))
+    }
+}
+
+impl L2CancelOrderTxInfo {
+    fn preimage(&self, lighter_chain_id: u32) -> HashPreimage {
         // Field order matches lighter-go implementation
         // See: lighter-go/types/txtypes/cancel_order.go
-        let mut elements = Vec::new();
-
-        // 1. Chain ID
-        elements.push(Goldilocks::from(lighter_chain_id as u64));
-
-        // 2. Transaction type
-        elements.push(Goldilocks::from(TX_TYPE_L2_CANCEL_ORDER as u64));
-
-        // 3-4. Nonce and ExpiredAt (BEFORE account info!)
-        elements.push(Goldilocks::from(self.nonce as u64));
-        elements.push(Goldilocks::from(self.expired_at as u64));
-
-        // 5-6. Account info
-        elements.push(Goldilocks::from(self.account_index as u64));
-        elements.push(Goldilocks::from(self.api_key_index as u64));
-
-        // 7-8. Cancel order fields
-        elements.push(Goldilocks::from(self.market_index as u64));
-        elements.push(Goldilocks::from(self.index as u64));
-
-        // Hash using Poseidon2
-        let hash_result = hash_to_quintic_extension(&elements);
-        Ok(hash_result.to_bytes_le().to_vec())
+        HashPreimage::header(
+            lighter_chain_id,
+            TX_TYPE_L2_CANCEL_ORDER,
+            self.nonce,
+            self.expired_at,
+            self.account_index,
+            self.api_key_index,
+        )
+        .field("market_index", self.market_index as u64)
+        .field("index", self.index as u64)
     }
 }
 
 /// L2 Modify Order Transaction Info
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
 pub struct L2ModifyOrderTxInfo {
+    #[serde(rename = "AccountIndex")]
     pub account_index: i64,
+    #[serde(rename = "ApiKeyIndex")]
     pub api_key_index: u8,
+    #[serde(rename = "MarketIndex")]
     pub market_index: u8,
+    #[serde(rename = "Index")]
     pub index: i64,
+    #[serde(rename = "BaseAmount")]
     pub base_amount: i64,
-    pub price: u32,
-    pub trigger_price: u32,
+    #[serde(rename = "Price")]
+    pub price: u64,
+    #[serde(rename = "TriggerPrice")]
+    pub trigger_price: u64,
+    #[serde(rename = "ExpiredAt")]
     pub expired_at: i64,
+    #[serde(rename = "Nonce")]
     pub nonce: i64,
+    #[serde(rename = "Sig")]
     #[serde(skip_serializing_if = "Option::is_none")]
-    #[serde(with = "hex_serde", default)]
-    pub sig: Option<Vec<u8>>,
+    #[serde(with = "base64_serde", default)]
+    #[cfg_attr(
+        test,
+        proptest(strategy = "crate::types::common::proptest_support::signature()")
+    )]
+    pub sig: Option<Signature>,
     #[serde(skip)]
+    #[cfg_attr(test, proptest(value = "None"))]
     pub signed_hash: Option<String>,
 }
 
@@ -385,10 +487,23 @@ impl TxInfo for L2ModifyOrderTxInfo {
         Ok(serde_json::to_string(self)?)
     }
 
+    fn write_tx_info(&self, buf: &mut Vec<u8>) -> Result<()> {
+        Ok(serde_json::to_writer(buf, self)?)
+    }
+
     fn get_tx_hash(&self) -> Option<String> {
         self.signed_hash.clone()
     }
 
+    fn signature(&self) -> Option<&[u8]> {
+        self.sig.as_ref().map(|sig| sig.as_slice())
+    }
+
+    fn set_signature(&mut self, sig: Signature, signed_hash: String) {
+        self.sig = Some(sig);
+        self.signed_hash = Some(signed_hash);
+    }
+
     fn validate(&self) -> Result<()> {
         if self.account_index < MIN_ACCOUNT_INDEX || self.account_index > MAX_ACCOUNT_INDEX {
             return Err(LighterError::AccountIndexTooLow(self.account_index));
@@ -396,55 +511,65 @@ impl TxInfo for L2ModifyOrderTxInfo {
         if self.nonce < MIN_NONCE {
             return Err(LighterError::NonceTooLow(self.nonce));
         }
+        check_price_fields(self.price, self.trigger_price)?;
         Ok(())
     }
 
     fn hash(&self, lighter_chain_id: u32) -> Result<Vec<u8>> {
-        use poseidon_hash::{hash_to_quintic_extension, Goldilocks};
+        Ok(self.preimage(lighter_chain_id).hash())
+    }
 
+    fn hash_preimage_fields(&self, lighter_chain_id: u32) -> Option<HashPreimage> {
+        Some(self.preimage(lighter_chain_id))
+    }
+}
+
+impl L2ModifyOrderTxInfo {
+    fn preimage(&self, lighter_chain_id: u32) -> HashPreimage {
         // Field order matches lighter-go implementation
         // See: lighter-go/types/txtypes/modify_order.go
-        let mut elements = Vec::new();
-
-        // 1. Chain ID
-        elements.push(Goldilocks::from(lighter_chain_id as u64));
-
-        // 2. Transaction type
-        elements.push(Goldilocks::from(TX_TYPE_L2_MODIFY_ORDER as u64));
-
-        // 3-4. Nonce and ExpiredAt (BEFORE account info!)
-        elements.push(Goldilocks::from(self.nonce as u64));
-        elements.push(Goldilocks::from(self.expired_at as u64));
-
-        // 5-6. Account info
-        elements.push(Goldilocks::from(self.account_index as u64));
-        elements.push(Goldilocks::from(self.api_key_index as u64));
-
-        // 7-11. Modify order fields
-        elements.push(Goldilocks::from(self.market_index as u64));
-        elements.push(Goldilocks::from(self.index as u64));
-        elements.push(Goldilocks::from(self.base_amount as u64));
-        elements.push(Goldilocks::from(self.price as u64));
-        elements.push(Goldilocks::from(self.trigger_price as u64));
-
-        // Hash using Poseidon2
-        let hash_result = hash_to_quintic_extension(&elements);
-        Ok(hash_result.to_bytes_le().to_vec())
+        HashPreimage::header(
+            lighter_chain_id,
+            TX_TYPE_L2_MODIFY_ORDER,
+            self.nonce,
+            self.expired_at,
+            self.account_index,
+            self.api_key_index,
+        )
+        .field("market_index", self.market_index as u64)
+        .field("index", self.index as u64)
+        .field("base_amount", self.base_amount as u64)
+        .field("price", self.price)
+        .field("trigger_price", self.trigger_price)
     }
 }
 
 /// L2 Cancel All Orders Transaction Info
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
 pub struct L2CancelAllOrdersTxInfo {
+    #[serde(rename = "AccountIndex")]
     pub account_index: i64,
+    #[serde(rename = "ApiKeyIndex")]
     pub api_key_index: u8,
+    #[serde(rename = "TimeInForce")]
     pub time_in_force: u8,
+    #[serde(rename = "Time")]
     pub time: i64,
+    #[serde(rename = "ExpiredAt")]
     pub expired_at: i64,
+    #[serde(rename = "Nonce")]
     pub nonce: i64,
+    #[serde(rename = "Sig")]
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub sig: Option<Vec<u8>>,
+    #[serde(with = "base64_serde", default)]
+    #[cfg_attr(
+        test,
+        proptest(strategy = "crate::types::common::proptest_support::signature()")
+    )]
+    pub sig: Option<Signature>,
     #[serde(skip)]
+    #[cfg_attr(test, proptest(value = "None"))]
     pub signed_hash: Option<String>,
 }
 
@@ -457,38 +582,97 @@ impl TxInfo for L2CancelAllOrdersTxInfo {
         Ok(serde_json::to_string(self)?)
     }
 
+    fn write_tx_info(&self, buf: &mut Vec<u8>) -> Result<()> {
+        Ok(serde_json::to_writer(buf, self)?)
+    }
+
     fn get_tx_hash(&self) -> Option<String> {
         self.signed_hash.clone()
     }
 
+    fn signature(&self) -> Option<&[u8]> {
+        self.sig.as_ref().map(|sig| sig.as_slice())
+    }
+
+    fn set_signature(&mut self, sig: Signature, signed_hash: String) {
+        self.sig = Some(sig);
+        self.signed_hash = Some(signed_hash);
+    }
+
     fn validate(&self) -> Result<()> {
         if self.account_index < MIN_ACCOUNT_INDEX || self.account_index > MAX_ACCOUNT_INDEX {
             return Err(LighterError::AccountIndexTooLow(self.account_index));
         }
+        match self.time_in_force {
+            // Scheduled at a time in the future; see TxClient::cancel_all_orders
+            CANCEL_ALL_SCHEDULED if self.time <= 0 => {
+                return Err(LighterError::CancelAllTimeIsNotInRange);
+            }
+            CANCEL_ALL_SCHEDULED => {}
+            CANCEL_ALL_IMMEDIATE | CANCEL_ALL_ABORT_SCHEDULED if self.time != 0 => {
+                return Err(LighterError::CancelAllTimeIsNotNil);
+            }
+            CANCEL_ALL_IMMEDIATE | CANCEL_ALL_ABORT_SCHEDULED => {}
+            _ => return Err(LighterError::InvalidCancelAllTimeInForce),
+        }
         if self.nonce < MIN_NONCE {
             return Err(LighterError::NonceTooLow(self.nonce));
         }
         Ok(())
     }
 
-    fn hash(&self, _lighter_chain_id: u32) -> Result<Vec<u8>> {
-        // TODO: Implement Poseidon2 hashing
-        Ok(vec![0u8; 40])
+    fn hash(&self, lighter_chain_id: u32) -> Result<Vec<u8>> {
+        Ok(self.preimage(lighter_chain_id).hash())
+    }
+
+    fn hash_preimage_fields(&self, lighter_chain_id: u32) -> Option<HashPreimage> {
+        Some(self.preimage(lighter_chain_id))
+    }
+}
+
+impl L2CancelAllOrdersTxInfo {
+    fn preimage(&self, lighter_chain_id: u32) -> HashPreimage {
+        // Field order matches lighter-go implementation
+        // See: lighter-go/types/txtypes/cancel_all_orders.go
+        HashPreimage::header(
+            lighter_chain_id,
+            TX_TYPE_L2_CANCEL_ALL_ORDERS,
+            self.nonce,
+            self.expired_at,
+            self.account_index,
+            self.api_key_index,
+        )
+        .field("time_in_force", self.time_in_force as u64)
+        .field("time", self.time as u64)
     }
 }
 
 /// L2 Create Grouped Orders Transaction Info
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
 pub struct L2CreateGroupedOrdersTxInfo {
+    #[serde(rename = "AccountIndex")]
     pub account_index: i64,
+    #[serde(rename = "ApiKeyIndex")]
     pub api_key_index: u8,
+    #[serde(rename = "GroupingType")]
     pub grouping_type: u8,
+    #[serde(rename = "Orders")]
     pub orders: Vec<OrderInfo>,
+    #[serde(rename = "ExpiredAt")]
     pub expired_at: i64,
+    #[serde(rename = "Nonce")]
     pub nonce: i64,
+    #[serde(rename = "Sig")]
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub sig: Option<Vec<u8>>,
+    #[serde(with = "base64_serde", default)]
+    #[cfg_attr(
+        test,
+        proptest(strategy = "crate::types::common::proptest_support::signature()")
+    )]
+    pub sig: Option<Signature>,
     #[serde(skip)]
+    #[cfg_attr(test, proptest(value = "None"))]
     pub signed_hash: Option<String>,
 }
 
@@ -501,10 +685,23 @@ impl TxInfo for L2CreateGroupedOrdersTxInfo {
         Ok(serde_json::to_string(self)?)
     }
 
+    fn write_tx_info(&self, buf: &mut Vec<u8>) -> Result<()> {
+        Ok(serde_json::to_writer(buf, self)?)
+    }
+
     fn get_tx_hash(&self) -> Option<String> {
         self.signed_hash.clone()
     }
 
+    fn signature(&self) -> Option<&[u8]> {
+        self.sig.as_ref().map(|sig| sig.as_slice())
+    }
+
+    fn set_signature(&mut self, sig: Signature, signed_hash: String) {
+        self.sig = Some(sig);
+        self.signed_hash = Some(signed_hash);
+    }
+
     fn validate(&self) -> Result<()> {
         if self.account_index < MIN_ACCOUNT_INDEX || self.account_index > MAX_ACCOUNT_INDEX {
             return Err(LighterError::AccountIndexTooLow(self.account_index));
@@ -515,18 +712,72 @@ impl TxInfo for L2CreateGroupedOrdersTxInfo {
         if self.nonce < MIN_NONCE {
             return Err(LighterError::NonceTooLow(self.nonce));
         }
+        for order in &self.orders {
+            check_price_fields(order.price, order.trigger_price)?;
+        }
         Ok(())
     }
 
-    fn hash(&self, _lighter_chain_id: u32) -> Result<Vec<u8>> {
-        // TODO: Implement Poseidon2 hashing
-        Ok(vec![0u8; 40])
+    fn hash(&self, lighter_chain_id: u32) -> Result<Vec<u8>> {
+        Ok(self.preimage(lighter_chain_id).hash())
+    }
+
+    fn hash_preimage_fields(&self, lighter_chain_id: u32) -> Option<HashPreimage> {
+        Some(self.preimage(lighter_chain_id))
+    }
+}
+
+impl L2CreateGroupedOrdersTxInfo {
+    fn preimage(&self, lighter_chain_id: u32) -> HashPreimage {
+        // Field order matches lighter-go implementation, the orders entering
+        // as one digest: each is hashed on its own and folded into the
+        // digest of those before it
+        // See: lighter-go/types/txtypes/create_grouped_orders.go
+        let [h0, h1, h2, h3] = self.orders_hash();
+        HashPreimage::header(
+            lighter_chain_id,
+            TX_TYPE_L2_CREATE_GROUPED_ORDERS,
+            self.nonce,
+            self.expired_at,
+            self.account_index,
+            self.api_key_index,
+        )
+        .field("grouping_type", self.grouping_type as u64)
+        .field("orders_hash_0", h0.to_canonical_u64())
+        .field("orders_hash_1", h1.to_canonical_u64())
+        .field("orders_hash_2", h2.to_canonical_u64())
+        .field("orders_hash_3", h3.to_canonical_u64())
+    }
+
+    fn orders_hash(&self) -> poseidon_hash::HashOut {
+        use poseidon_hash::{empty_hash_out, hash_n_to_one, hash_no_pad, Goldilocks};
+
+        self.orders
+            .iter()
+            .map(|order| {
+                hash_no_pad(&[
+                    Goldilocks::from(order.market_index as u64),
+                    Goldilocks::from(order.client_order_index as u64),
+                    Goldilocks::from(order.base_amount as u64),
+                    Goldilocks::from(order.price),
+                    Goldilocks::from(order.is_ask as u64),
+                    Goldilocks::from(order.order_type as u64),
+                    Goldilocks::from(order.time_in_force as u64),
+                    Goldilocks::from(order.reduce_only as u64),
+                    Goldilocks::from(order.trigger_price),
+                    Goldilocks::from(order.order_expiry as u64),
+                ])
+            })
+            .reduce(|orders, order| hash_n_to_one(&[orders, order]))
+            .unwrap_or_else(empty_hash_out)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::types::common::proptest_support::*;
+    use proptest::prelude::*;
 
     fn create_valid_order_info() -> OrderInfo {
         OrderInfo {
@@ -687,7 +938,7 @@ mod tests {
         let tx_info = L2CancelAllOrdersTxInfo {
             account_index: 12345,
             api_key_index: 0,
-            time_in_force: CANCEL_ALL_IMMEDIATE,
+            time_in_force: CANCEL_ALL_SCHEDULED,
             time: 1000000,
             expired_at: 1000000,
             nonce: 1,
@@ -716,6 +967,84 @@ mod tests {
         assert_eq!(tx_info.get_tx_type(), TX_TYPE_L2_CREATE_GROUPED_ORDERS);
     }
 
+    #[test]
+    fn test_create_order_refuses_unknown_time_in_force() {
+        // There is no fill-or-kill to sign
+        let order_info = OrderInfo {
+            time_in_force: MAX_TIME_IN_FORCE + 1,
+            ..create_valid_order_info()
+        };
+        let tx_info = create_test_tx_info_with_account(order_info, 12345, 0, 1);
+        assert!(matches!(
+            tx_info.validate(),
+            Err(LighterError::OrderTimeInForceInvalid)
+        ));
+    }
+
+    #[test]
+    fn test_create_grouped_orders_hash_commits_to_the_group() {
+        let take_profit = OrderInfo {
+            order_type: ORDER_TYPE_TAKE_PROFIT,
+            is_ask: 1,
+            reduce_only: 1,
+            trigger_price: 110_000_000,
+            price: 109_000_000,
+            time_in_force: TIME_IN_FORCE_IMMEDIATE_OR_CANCEL,
+            ..create_valid_order_info()
+        };
+        let stop_loss = OrderInfo {
+            client_order_index: 2,
+            order_type: ORDER_TYPE_STOP_LOSS,
+            trigger_price: 90_000_000,
+            price: 89_000_000,
+            ..take_profit.clone()
+        };
+        let tx_info = L2CreateGroupedOrdersTxInfo {
+            account_index: 12345,
+            api_key_index: 0,
+            grouping_type: GROUPING_TYPE_ONE_CANCELS_THE_OTHER,
+            orders: vec![take_profit, stop_loss],
+            expired_at: 1000000,
+            nonce: 1,
+            sig: None,
+            signed_hash: None,
+        };
+
+        let json: serde_json::Value =
+            serde_json::from_str(&tx_info.get_tx_info().unwrap()).unwrap();
+        assert_eq!(json["GroupingType"], GROUPING_TYPE_ONE_CANCELS_THE_OTHER);
+        assert_eq!(json["Orders"][1]["Type"], ORDER_TYPE_STOP_LOSS);
+        let decoded: L2CreateGroupedOrdersTxInfo = serde_json::from_value(json).unwrap();
+        assert_eq!(decoded, tx_info);
+
+        let preimage = tx_info.hash_preimage_fields(304).unwrap();
+        let names: Vec<&str> = preimage.iter().map(|(name, _)| name).collect();
+        assert_eq!(
+            names[5..],
+            [
+                "api_key_index",
+                "grouping_type",
+                "orders_hash_0",
+                "orders_hash_1",
+                "orders_hash_2",
+                "orders_hash_3"
+            ]
+        );
+        assert_eq!(tx_info.hash(304).unwrap(), preimage.hash());
+
+        // The grouping, and the orders and their order, are all signed
+        let hash = tx_info.hash(304).unwrap();
+        let mut other = tx_info.clone();
+        other.grouping_type = GROUPING_TYPE_ONE_TRIGGERS_THE_OTHER;
+        assert_ne!(other.hash(304).unwrap(), hash);
+        let mut other = tx_info.clone();
+        other.orders.reverse();
+        assert_ne!(other.hash(304).unwrap(), hash);
+        let mut other = tx_info.clone();
+        other.orders[1].trigger_price += 1;
+        assert_ne!(other.hash(304).unwrap(), hash);
+    }
+
     #[test]
     fn test_create_grouped_orders_too_many_orders() {
         let tx_info = L2CreateGroupedOrdersTxInfo {
@@ -756,4 +1085,167 @@ mod tests {
         assert!(json.contains("MarketIndex"));
         assert!(json.contains("BaseAmount"));
     }
+
+    #[test]
+    fn test_signature_serde_round_trip() {
+        let mut tx_info = create_test_tx_info_with_account(create_valid_order_info(), 12345, 0, 1);
+        let sig: Signature = std::array::from_fn(|i| i as u8);
+        tx_info.set_signature(sig, "00".to_string());
+        assert_eq!(tx_info.signature(), Some(&sig[..]));
+
+        // base64, as lighter-go encodes `[]byte`
+        let json = tx_info.get_tx_info().unwrap();
+        use base64::Engine;
+        let encoded = base64::engine::general_purpose::STANDARD.encode(sig);
+        assert!(json.contains(&format!("\"Sig\":\"{encoded}\"")));
+        let decoded: L2CreateOrderTxInfo = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded.sig, Some(sig));
+
+        // The same for every other type
+        let modify = L2ModifyOrderTxInfo {
+            account_index: 12345,
+            api_key_index: 0,
+            market_index: 0,
+            index: 1,
+            base_amount: 1000,
+            price: 100,
+            trigger_price: 0,
+            expired_at: 0,
+            nonce: 1,
+            sig: Some(sig),
+            signed_hash: None,
+        };
+        let json = modify.get_tx_info().unwrap();
+        assert!(json.contains(&format!("\"Sig\":\"{encoded}\"")));
+        let decoded: L2ModifyOrderTxInfo = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded.sig, Some(sig));
+    }
+
+    #[test]
+    fn test_signature_wrong_length_rejected() {
+        #[derive(Deserialize)]
+        struct Sig(#[serde(with = "base64_serde")] Option<Signature>);
+        let err = serde_json::from_str::<Sig>("\"0102\"")
+            .map(|sig| sig.0)
+            .unwrap_err();
+        assert!(err.to_string().contains("invalid signature length"));
+    }
+
+    #[test]
+    fn test_edge_values_round_trip() {
+        let order_info = OrderInfo {
+            client_order_index: i64::MIN,
+            base_amount: i64::MAX,
+            price: MAX_ORDER_PRICE,
+            trigger_price: 0,
+            order_expiry: i64::MAX,
+            ..create_valid_order_info()
+        };
+        let mut tx_info =
+            create_test_tx_info_with_account(order_info, MAX_ACCOUNT_INDEX, u8::MAX, i64::MAX);
+        tx_info.expired_at = i64::MAX;
+        tx_info.sig = Some([u8::MAX; SIGNATURE_LENGTH]);
+        // Not part of the wire format, so it does not survive decoding
+        tx_info.order_info = OrderInfo::default();
+
+        check_tx_round_trip(&tx_info).unwrap();
+        check_hash_deterministic(&tx_info, u32::MAX).unwrap();
+        check_sign_verify(tx_info, u32::MAX).unwrap();
+    }
+
+    #[test]
+    fn test_preimage_fields_rehash_to_the_signed_hash() {
+        let mut tx_info = create_test_tx_info_with_account(create_valid_order_info(), 12345, 3, 7);
+        tx_info.expired_at = 1_700_000_600_000;
+        let hash = tx_info.hash(304).unwrap();
+        tx_info.set_signature([0; SIGNATURE_LENGTH], hex::encode(&hash));
+
+        let preimage = tx_info.hash_preimage_fields(304).unwrap();
+        assert_eq!(preimage.chain_id(), 304);
+        assert_eq!(preimage.tx_type(), TX_TYPE_L2_CREATE_ORDER);
+        assert_eq!(preimage.nonce(), 7);
+        assert_eq!(preimage.expired_at(), 1_700_000_600_000);
+        assert_eq!(preimage.account_index(), 12345);
+        assert_eq!(preimage.api_key_index(), 3);
+        assert_eq!(preimage.get("price"), Some(100000000));
+        assert_eq!(tx_info.get_tx_hash(), Some(hex::encode(preimage.hash())));
+
+        // Serialized in hashing order, for audit records
+        let json = serde_json::to_string(&preimage).unwrap();
+        assert!(json.starts_with(r#"{"chain_id":304,"tx_type":14,"nonce":7,"expired_at":"#));
+        assert!(json.ends_with(r#""trigger_price":0,"order_expiry":0}"#));
+    }
+
+    proptest! {
+        #[test]
+        fn test_order_requests_round_trip(
+            create in any::<CreateOrderTxReq>(),
+            cancel in any::<CancelOrderTxReq>(),
+            modify in any::<ModifyOrderTxReq>(),
+            cancel_all in any::<CancelAllOrdersTxReq>(),
+            grouped in any::<CreateGroupedOrdersTxReq>(),
+            order_info in any::<OrderInfo>(),
+            opts in any::<crate::types::TransactOpts>(),
+        ) {
+            check_json_round_trip(&create)?;
+            check_json_round_trip(&cancel)?;
+            check_json_round_trip(&modify)?;
+            check_json_round_trip(&cancel_all)?;
+            check_json_round_trip(&grouped)?;
+            check_json_round_trip(&order_info)?;
+            check_json_round_trip(&opts)?;
+        }
+
+        #[test]
+        fn test_order_txs_round_trip(
+            create in any::<L2CreateOrderTxInfo>(),
+            cancel in any::<L2CancelOrderTxInfo>(),
+            modify in any::<L2ModifyOrderTxInfo>(),
+            cancel_all in any::<L2CancelAllOrdersTxInfo>(),
+            grouped in any::<L2CreateGroupedOrdersTxInfo>(),
+        ) {
+            check_tx_round_trip(&create)?;
+            check_tx_round_trip(&cancel)?;
+            check_tx_round_trip(&modify)?;
+            check_tx_round_trip(&cancel_all)?;
+            check_tx_round_trip(&grouped)?;
+        }
+
+        #[test]
+        fn test_order_tx_hashes_deterministic(
+            chain_id in any::<u32>(),
+            create in any::<L2CreateOrderTxInfo>(),
+            cancel in any::<L2CancelOrderTxInfo>(),
+            modify in any::<L2ModifyOrderTxInfo>(),
+            cancel_all in any::<L2CancelAllOrdersTxInfo>(),
+            grouped in any::<L2CreateGroupedOrdersTxInfo>(),
+        ) {
+            check_hash_deterministic(&create, chain_id)?;
+            check_hash_deterministic(&cancel, chain_id)?;
+            check_hash_deterministic(&modify, chain_id)?;
+            check_hash_deterministic(&cancel_all, chain_id)?;
+            check_hash_deterministic(&grouped, chain_id)?;
+        }
+    }
+
+    proptest! {
+        // Signing is slow in debug builds
+        #![proptest_config(ProptestConfig::with_cases(16))]
+
+        #[test]
+        fn test_order_txs_sign_and_verify(
+            chain_id in any::<u32>(),
+            create in any::<L2CreateOrderTxInfo>(),
+            cancel in any::<L2CancelOrderTxInfo>(),
+            modify in any::<L2ModifyOrderTxInfo>(),
+            cancel_all in any::<L2CancelAllOrdersTxInfo>(),
+            grouped in any::<L2CreateGroupedOrdersTxInfo>(),
+        ) {
+            check_sign_verify(create, chain_id)?;
+            check_sign_verify(cancel, chain_id)?;
+            check_sign_verify(modify, chain_id)?;
+            check_sign_verify(cancel_all, chain_id)?;
+            check_sign_verify(grouped, chain_id)?;
+        }
+    }
 }
@@ -3,10 +3,12 @@
 use super::TxInfo;
 use crate::constants::*;
 use crate::errors::{LighterError, Result};
+use crate::signer::Signature;
 use serde::{Deserialize, Serialize};
 
 /// Create Public Pool Transaction Request
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
 pub struct CreatePublicPoolTxReq {
     pub operator_fee: i64,
     pub initial_total_shares: i64,
@@ -14,7 +16,8 @@ pub struct CreatePublicPoolTxReq {
 }
 
 /// Update Public Pool Transaction Request
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
 pub struct UpdatePublicPoolTxReq {
     pub public_pool_index: i64,
     pub status: u8,
@@ -23,32 +26,49 @@ pub struct UpdatePublicPoolTxReq {
 }
 
 /// Mint Shares Transaction Request
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
 pub struct MintSharesTxReq {
     pub public_pool_index: i64,
     pub share_amount: i64,
 }
 
 /// Burn Shares Transaction Request
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
 pub struct BurnSharesTxReq {
     pub public_pool_index: i64,
     pub share_amount: i64,
 }
 
 /// L2 Create Public Pool Transaction Info
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
 pub struct L2CreatePublicPoolTxInfo {
+    #[serde(rename = "AccountIndex")]
     pub account_index: i64,
+    #[serde(rename = "ApiKeyIndex")]
     pub api_key_index: u8,
+    #[serde(rename = "OperatorFee")]
     pub operator_fee: i64,
+    #[serde(rename = "InitialTotalShares")]
     pub initial_total_shares: i64,
+    #[serde(rename = "MinOperatorShareRate")]
     pub min_operator_share_rate: i64,
+    #[serde(rename = "ExpiredAt")]
     pub expired_at: i64,
+    #[serde(rename = "Nonce")]
     pub nonce: i64,
+    #[serde(rename = "Sig")]
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub sig: Option<Vec<u8>>,
+    #[serde(with = "crate::types::orders::base64_serde", default)]
+    #[cfg_attr(
+        test,
+        proptest(strategy = "crate::types::common::proptest_support::signature()")
+    )]
+    pub sig: Option<Signature>,
     #[serde(skip)]
+    #[cfg_attr(test, proptest(value = "None"))]
     pub signed_hash: Option<String>,
 }
 
@@ -61,10 +81,23 @@ impl TxInfo for L2CreatePublicPoolTxInfo {
         Ok(serde_json::to_string(self)?)
     }
 
+    fn write_tx_info(&self, buf: &mut Vec<u8>) -> Result<()> {
+        Ok(serde_json::to_writer(buf, self)?)
+    }
+
     fn get_tx_hash(&self) -> Option<String> {
         self.signed_hash.clone()
     }
 
+    fn signature(&self) -> Option<&[u8]> {
+        self.sig.as_ref().map(|sig| sig.as_slice())
+    }
+
+    fn set_signature(&mut self, sig: Signature, signed_hash: String) {
+        self.sig = Some(sig);
+        self.signed_hash = Some(signed_hash);
+    }
+
     fn validate(&self) -> Result<()> {
         if self.account_index < MIN_ACCOUNT_INDEX || self.account_index > MAX_ACCOUNT_INDEX {
             return Err(LighterError::AccountIndexTooLow(self.account_index));
@@ -98,19 +131,35 @@ impl TxInfo for L2CreatePublicPoolTxInfo {
 }
 
 /// L2 Update Public Pool Transaction Info
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
 pub struct L2UpdatePublicPoolTxInfo {
+    #[serde(rename = "AccountIndex")]
     pub account_index: i64,
+    #[serde(rename = "ApiKeyIndex")]
     pub api_key_index: u8,
+    #[serde(rename = "PublicPoolIndex")]
     pub public_pool_index: i64,
+    #[serde(rename = "Status")]
     pub status: u8,
+    #[serde(rename = "OperatorFee")]
     pub operator_fee: i64,
+    #[serde(rename = "MinOperatorShareRate")]
     pub min_operator_share_rate: i64,
+    #[serde(rename = "ExpiredAt")]
     pub expired_at: i64,
+    #[serde(rename = "Nonce")]
     pub nonce: i64,
+    #[serde(rename = "Sig")]
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub sig: Option<Vec<u8>>,
+    #[serde(with = "crate::types::orders::base64_serde", default)]
+    #[cfg_attr(
+        test,
+        proptest(strategy = "crate::types::common::proptest_support::signature()")
+    )]
+    pub sig: Option<Signature>,
     #[serde(skip)]
+    #[cfg_attr(test, proptest(value = "None"))]
     pub signed_hash: Option<String>,
 }
 
@@ -123,10 +172,23 @@ impl TxInfo for L2UpdatePublicPoolTxInfo {
         Ok(serde_json::to_string(self)?)
     }
 
+    fn write_tx_info(&self, buf: &mut Vec<u8>) -> Result<()> {
+        Ok(serde_json::to_writer(buf, self)?)
+    }
+
     fn get_tx_hash(&self) -> Option<String> {
         self.signed_hash.clone()
     }
 
+    fn signature(&self) -> Option<&[u8]> {
+        self.sig.as_ref().map(|sig| sig.as_slice())
+    }
+
+    fn set_signature(&mut self, sig: Signature, signed_hash: String) {
+        self.sig = Some(sig);
+        self.signed_hash = Some(signed_hash);
+    }
+
     fn validate(&self) -> Result<()> {
         if self.account_index < MIN_ACCOUNT_INDEX || self.account_index > MAX_ACCOUNT_INDEX {
             return Err(LighterError::AccountIndexTooLow(self.account_index));
@@ -151,17 +213,31 @@ impl TxInfo for L2UpdatePublicPoolTxInfo {
 }
 
 /// L2 Mint Shares Transaction Info
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
 pub struct L2MintSharesTxInfo {
+    #[serde(rename = "AccountIndex")]
     pub account_index: i64,
+    #[serde(rename = "ApiKeyIndex")]
     pub api_key_index: u8,
+    #[serde(rename = "PublicPoolIndex")]
     pub public_pool_index: i64,
+    #[serde(rename = "ShareAmount")]
     pub share_amount: i64,
+    #[serde(rename = "ExpiredAt")]
     pub expired_at: i64,
+    #[serde(rename = "Nonce")]
     pub nonce: i64,
+    #[serde(rename = "Sig")]
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub sig: Option<Vec<u8>>,
+    #[serde(with = "crate::types::orders::base64_serde", default)]
+    #[cfg_attr(
+        test,
+        proptest(strategy = "crate::types::common::proptest_support::signature()")
+    )]
+    pub sig: Option<Signature>,
     #[serde(skip)]
+    #[cfg_attr(test, proptest(value = "None"))]
     pub signed_hash: Option<String>,
 }
 
@@ -174,10 +250,23 @@ impl TxInfo for L2MintSharesTxInfo {
         Ok(serde_json::to_string(self)?)
     }
 
+    fn write_tx_info(&self, buf: &mut Vec<u8>) -> Result<()> {
+        Ok(serde_json::to_writer(buf, self)?)
+    }
+
     fn get_tx_hash(&self) -> Option<String> {
         self.signed_hash.clone()
     }
 
+    fn signature(&self) -> Option<&[u8]> {
+        self.sig.as_ref().map(|sig| sig.as_slice())
+    }
+
+    fn set_signature(&mut self, sig: Signature, signed_hash: String) {
+        self.sig = Some(sig);
+        self.signed_hash = Some(signed_hash);
+    }
+
     fn validate(&self) -> Result<()> {
         if self.account_index < MIN_ACCOUNT_INDEX || self.account_index > MAX_ACCOUNT_INDEX {
             return Err(LighterError::AccountIndexTooLow(self.account_index));
@@ -201,17 +290,31 @@ impl TxInfo for L2MintSharesTxInfo {
 }
 
 /// L2 Burn Shares Transaction Info
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
 pub struct L2BurnSharesTxInfo {
+    #[serde(rename = "AccountIndex")]
     pub account_index: i64,
+    #[serde(rename = "ApiKeyIndex")]
     pub api_key_index: u8,
+    #[serde(rename = "PublicPoolIndex")]
     pub public_pool_index: i64,
+    #[serde(rename = "ShareAmount")]
     pub share_amount: i64,
+    #[serde(rename = "ExpiredAt")]
     pub expired_at: i64,
+    #[serde(rename = "Nonce")]
     pub nonce: i64,
+    #[serde(rename = "Sig")]
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub sig: Option<Vec<u8>>,
+    #[serde(with = "crate::types::orders::base64_serde", default)]
+    #[cfg_attr(
+        test,
+        proptest(strategy = "crate::types::common::proptest_support::signature()")
+    )]
+    pub sig: Option<Signature>,
     #[serde(skip)]
+    #[cfg_attr(test, proptest(value = "None"))]
     pub signed_hash: Option<String>,
 }
 
@@ -224,10 +327,23 @@ impl TxInfo for L2BurnSharesTxInfo {
         Ok(serde_json::to_string(self)?)
     }
 
+    fn write_tx_info(&self, buf: &mut Vec<u8>) -> Result<()> {
+        Ok(serde_json::to_writer(buf, self)?)
+    }
+
     fn get_tx_hash(&self) -> Option<String> {
         self.signed_hash.clone()
     }
 
+    fn signature(&self) -> Option<&[u8]> {
+        self.sig.as_ref().map(|sig| sig.as_slice())
+    }
+
+    fn set_signature(&mut self, sig: Signature, signed_hash: String) {
+        self.sig = Some(sig);
+        self.signed_hash = Some(signed_hash);
+    }
+
     fn validate(&self) -> Result<()> {
         if self.account_index < MIN_ACCOUNT_INDEX || self.account_index > MAX_ACCOUNT_INDEX {
             return Err(LighterError::AccountIndexTooLow(self.account_index));
@@ -253,6 +369,8 @@ impl TxInfo for L2BurnSharesTxInfo {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::types::common::proptest_support::*;
+    use proptest::prelude::*;
 
     #[test]
     fn test_create_public_pool_validation_success() {
@@ -421,4 +539,65 @@ mod tests {
         let result = tx_info.validate();
         assert!(result.is_err());
     }
+
+    proptest! {
+        #[test]
+        fn test_pool_requests_round_trip(
+            create in any::<CreatePublicPoolTxReq>(),
+            update in any::<UpdatePublicPoolTxReq>(),
+            mint in any::<MintSharesTxReq>(),
+            burn in any::<BurnSharesTxReq>(),
+        ) {
+            check_json_round_trip(&create)?;
+            check_json_round_trip(&update)?;
+            check_json_round_trip(&mint)?;
+            check_json_round_trip(&burn)?;
+        }
+
+        #[test]
+        fn test_pool_txs_round_trip(
+            create in any::<L2CreatePublicPoolTxInfo>(),
+            update in any::<L2UpdatePublicPoolTxInfo>(),
+            mint in any::<L2MintSharesTxInfo>(),
+            burn in any::<L2BurnSharesTxInfo>(),
+        ) {
+            check_tx_round_trip(&create)?;
+            check_tx_round_trip(&update)?;
+            check_tx_round_trip(&mint)?;
+            check_tx_round_trip(&burn)?;
+        }
+
+        #[test]
+        fn test_pool_tx_hashes_deterministic(
+            chain_id in any::<u32>(),
+            create in any::<L2CreatePublicPoolTxInfo>(),
+            update in any::<L2UpdatePublicPoolTxInfo>(),
+            mint in any::<L2MintSharesTxInfo>(),
+            burn in any::<L2BurnSharesTxInfo>(),
+        ) {
+            check_hash_deterministic(&create, chain_id)?;
+            check_hash_deterministic(&update, chain_id)?;
+            check_hash_deterministic(&mint, chain_id)?;
+            check_hash_deterministic(&burn, chain_id)?;
+        }
+    }
+
+    proptest! {
+        // Signing is slow in debug builds
+        #![proptest_config(ProptestConfig::with_cases(16))]
+
+        #[test]
+        fn test_pool_txs_sign_and_verify(
+            chain_id in any::<u32>(),
+            create in any::<L2CreatePublicPoolTxInfo>(),
+            update in any::<L2UpdatePublicPoolTxInfo>(),
+            mint in any::<L2MintSharesTxInfo>(),
+            burn in any::<L2BurnSharesTxInfo>(),
+        ) {
+            check_sign_verify(create, chain_id)?;
+            check_sign_verify(update, chain_id)?;
+            check_sign_verify(mint, chain_id)?;
+            check_sign_verify(burn, chain_id)?;
+        }
+    }
 }
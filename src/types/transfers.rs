@@ -3,7 +3,8 @@
 use serde::{Deserialize, Serialize};
 
 /// Transfer Transaction Request
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
 pub struct TransferTxReq {
     pub to_account_index: i64,
     pub usdc_amount: i64,
@@ -12,19 +13,26 @@ pub struct TransferTxReq {
 }
 
 /// Withdraw Transaction Request
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
 pub struct WithdrawTxReq {
     pub usdc_amount: u64,
 }
 
 /// Change Public Key Transaction Request
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ChangePubKeyReq {
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
+pub struct ChangePubKeyTxReq {
     pub pub_key: Vec<u8>,
 }
 
+/// Former name of [`ChangePubKeyTxReq`]
+#[deprecated(note = "renamed to ChangePubKeyTxReq")]
+pub type ChangePubKeyReq = ChangePubKeyTxReq;
+
 /// Update Leverage Transaction Request
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
 pub struct UpdateLeverageTxReq {
     pub market_index: u8,
     pub initial_margin_fraction: u16,
@@ -32,31 +40,49 @@ pub struct UpdateLeverageTxReq {
 }
 
 /// Update Margin Transaction Request
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
 pub struct UpdateMarginTxReq {
     pub market_index: u8,
     pub usdc_amount: i64,
     pub direction: u8,
 }
 
-use super::TxInfo;
+use super::{HashPreimage, TxInfo};
 use crate::constants::*;
 use crate::errors::{LighterError, Result};
+use crate::signer::Signature;
 
 /// L2 Transfer Transaction Info
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
 pub struct L2TransferTxInfo {
+    #[serde(rename = "FromAccountIndex")]
     pub from_account_index: i64,
+    #[serde(rename = "ApiKeyIndex")]
     pub api_key_index: u8,
+    #[serde(rename = "ToAccountIndex")]
     pub to_account_index: i64,
+    #[serde(rename = "USDCAmount")]
     pub usdc_amount: i64,
+    #[serde(rename = "Fee")]
     pub fee: i64,
+    #[serde(rename = "Memo")]
     pub memo: [u8; 32],
+    #[serde(rename = "ExpiredAt")]
     pub expired_at: i64,
+    #[serde(rename = "Nonce")]
     pub nonce: i64,
+    #[serde(rename = "Sig")]
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub sig: Option<Vec<u8>>,
+    #[serde(with = "crate::types::orders::base64_serde", default)]
+    #[cfg_attr(
+        test,
+        proptest(strategy = "crate::types::common::proptest_support::signature()")
+    )]
+    pub sig: Option<Signature>,
     #[serde(skip)]
+    #[cfg_attr(test, proptest(value = "None"))]
     pub signed_hash: Option<String>,
 }
 
@@ -69,21 +95,40 @@ impl TxInfo for L2TransferTxInfo {
         Ok(serde_json::to_string(self)?)
     }
 
+    fn write_tx_info(&self, buf: &mut Vec<u8>) -> Result<()> {
+        Ok(serde_json::to_writer(buf, self)?)
+    }
+
     fn get_tx_hash(&self) -> Option<String> {
         self.signed_hash.clone()
     }
 
+    fn signature(&self) -> Option<&[u8]> {
+        self.sig.as_ref().map(|sig| sig.as_slice())
+    }
+
+    fn set_signature(&mut self, sig: Signature, signed_hash: String) {
+        self.sig = Some(sig);
+        self.signed_hash = Some(signed_hash);
+    }
+
     fn validate(&self) -> Result<()> {
-        if self.from_account_index < MIN_ACCOUNT_INDEX
-            || self.from_account_index > MAX_ACCOUNT_INDEX
-        {
+        if self.from_account_index < MIN_ACCOUNT_INDEX {
             return Err(LighterError::FromAccountIndexTooLow(
                 self.from_account_index,
             ));
         }
-        if self.to_account_index < MIN_ACCOUNT_INDEX || self.to_account_index > MAX_ACCOUNT_INDEX {
+        if self.from_account_index > MAX_ACCOUNT_INDEX {
+            return Err(LighterError::FromAccountIndexTooHigh(
+                self.from_account_index,
+            ));
+        }
+        if self.to_account_index < MIN_ACCOUNT_INDEX {
             return Err(LighterError::ToAccountIndexTooLow(self.to_account_index));
         }
+        if self.to_account_index > MAX_ACCOUNT_INDEX {
+            return Err(LighterError::ToAccountIndexTooHigh(self.to_account_index));
+        }
         if self.usdc_amount < MIN_TRANSFER_AMOUNT || self.usdc_amount > MAX_TRANSFER_AMOUNT {
             return Err(LighterError::TransferAmountTooLow(self.usdc_amount));
         }
@@ -96,23 +141,69 @@ impl TxInfo for L2TransferTxInfo {
         Ok(())
     }
 
-    fn hash(&self, _lighter_chain_id: u32) -> Result<Vec<u8>> {
-        // TODO: Implement Poseidon2 hashing
-        Ok(vec![0u8; 40])
+    fn hash(&self, lighter_chain_id: u32) -> Result<Vec<u8>> {
+        Ok(self.preimage(lighter_chain_id).hash())
+    }
+
+    fn hash_preimage_fields(&self, lighter_chain_id: u32) -> Option<HashPreimage> {
+        Some(self.preimage(lighter_chain_id))
+    }
+}
+
+impl L2TransferTxInfo {
+    fn preimage(&self, lighter_chain_id: u32) -> HashPreimage {
+        // Field order matches lighter-go implementation
+        // See: lighter-go/types/txtypes/transfer.go
+        let preimage = HashPreimage::header(
+            lighter_chain_id,
+            TX_TYPE_L2_TRANSFER,
+            self.nonce,
+            self.expired_at,
+            self.from_account_index,
+            self.api_key_index,
+        )
+        .field("to_account_index", self.to_account_index as u64)
+        .limbs("usdc_amount_lo", "usdc_amount_hi", self.usdc_amount as u64)
+        .limbs("fee_lo", "fee_hi", self.fee as u64);
+
+        // Memo as field elements (32 bytes = 8 * u32), from little-endian
+        // bytes; a whole 8-byte word could exceed the Goldilocks modulus
+        const NAMES: [&str; 8] = [
+            "memo_0", "memo_1", "memo_2", "memo_3", "memo_4", "memo_5", "memo_6", "memo_7",
+        ];
+        self.memo
+            .chunks_exact(4)
+            .zip(NAMES)
+            .fold(preimage, |preimage, (chunk, name)| {
+                preimage.field(name, u32::from_le_bytes(chunk.try_into().unwrap()) as u64)
+            })
     }
 }
 
 /// L2 Withdraw Transaction Info
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
 pub struct L2WithdrawTxInfo {
+    #[serde(rename = "FromAccountIndex")]
     pub from_account_index: i64,
+    #[serde(rename = "ApiKeyIndex")]
     pub api_key_index: u8,
+    #[serde(rename = "USDCAmount")]
     pub usdc_amount: u64,
+    #[serde(rename = "ExpiredAt")]
     pub expired_at: i64,
+    #[serde(rename = "Nonce")]
     pub nonce: i64,
+    #[serde(rename = "Sig")]
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub sig: Option<Vec<u8>>,
+    #[serde(with = "crate::types::orders::base64_serde", default)]
+    #[cfg_attr(
+        test,
+        proptest(strategy = "crate::types::common::proptest_support::signature()")
+    )]
+    pub sig: Option<Signature>,
     #[serde(skip)]
+    #[cfg_attr(test, proptest(value = "None"))]
     pub signed_hash: Option<String>,
 }
 
@@ -125,45 +216,99 @@ impl TxInfo for L2WithdrawTxInfo {
         Ok(serde_json::to_string(self)?)
     }
 
+    fn write_tx_info(&self, buf: &mut Vec<u8>) -> Result<()> {
+        Ok(serde_json::to_writer(buf, self)?)
+    }
+
     fn get_tx_hash(&self) -> Option<String> {
         self.signed_hash.clone()
     }
 
+    fn signature(&self) -> Option<&[u8]> {
+        self.sig.as_ref().map(|sig| sig.as_slice())
+    }
+
+    fn set_signature(&mut self, sig: Signature, signed_hash: String) {
+        self.sig = Some(sig);
+        self.signed_hash = Some(signed_hash);
+    }
+
     fn validate(&self) -> Result<()> {
-        if self.from_account_index < MIN_ACCOUNT_INDEX
-            || self.from_account_index > MAX_ACCOUNT_INDEX
-        {
+        if self.from_account_index < MIN_ACCOUNT_INDEX {
             return Err(LighterError::FromAccountIndexTooLow(
                 self.from_account_index,
             ));
         }
-        if self.usdc_amount < MIN_WITHDRAWAL_AMOUNT || self.usdc_amount > MAX_WITHDRAWAL_AMOUNT {
+        if self.from_account_index > MAX_ACCOUNT_INDEX {
+            return Err(LighterError::FromAccountIndexTooHigh(
+                self.from_account_index,
+            ));
+        }
+        if self.usdc_amount < MIN_WITHDRAWAL_AMOUNT {
             return Err(LighterError::WithdrawalAmountTooLow(self.usdc_amount));
         }
+        if self.usdc_amount > MAX_WITHDRAWAL_AMOUNT {
+            return Err(LighterError::WithdrawalAmountTooHigh(self.usdc_amount));
+        }
         if self.nonce < MIN_NONCE {
             return Err(LighterError::NonceTooLow(self.nonce));
         }
         Ok(())
     }
 
-    fn hash(&self, _lighter_chain_id: u32) -> Result<Vec<u8>> {
-        // TODO: Implement Poseidon2 hashing
-        Ok(vec![0u8; 40])
+    fn hash(&self, lighter_chain_id: u32) -> Result<Vec<u8>> {
+        Ok(self.preimage(lighter_chain_id).hash())
+    }
+
+    fn hash_preimage_fields(&self, lighter_chain_id: u32) -> Option<HashPreimage> {
+        Some(self.preimage(lighter_chain_id))
+    }
+}
+
+impl L2WithdrawTxInfo {
+    fn preimage(&self, lighter_chain_id: u32) -> HashPreimage {
+        // Field order matches lighter-go implementation
+        // See: lighter-go/types/txtypes/withdraw.go
+        HashPreimage::header(
+            lighter_chain_id,
+            TX_TYPE_L2_WITHDRAW,
+            self.nonce,
+            self.expired_at,
+            self.from_account_index,
+            self.api_key_index,
+        )
+        .limbs("usdc_amount_lo", "usdc_amount_hi", self.usdc_amount)
     }
 }
 
 /// L2 Change Public Key Transaction Info
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
 pub struct L2ChangePubKeyTxInfo {
+    #[serde(rename = "AccountIndex")]
     pub account_index: i64,
+    #[serde(rename = "ApiKeyIndex")]
     pub api_key_index: u8,
+    #[serde(rename = "PubKey", with = "crate::types::orders::base64_bytes_serde")]
     pub pub_key: Vec<u8>,
+    #[serde(rename = "ExpiredAt")]
     pub expired_at: i64,
+    #[serde(rename = "Nonce")]
     pub nonce: i64,
+    /// The L1 wallet's signature over [`L2ChangePubKeyTxInfo::l1_signature_body`],
+    /// as 0x-prefixed hex; not part of the signed hash
+    #[serde(rename = "L1Sig", skip_serializing_if = "Option::is_none", default)]
+    pub l1_sig: Option<String>,
+    #[serde(rename = "Sig")]
     #[serde(skip_serializing_if = "Option::is_none")]
-    #[serde(with = "crate::types::orders::hex_serde", default)]
-    pub sig: Option<Vec<u8>>,
+    #[serde(with = "crate::types::orders::base64_serde", default)]
+    #[cfg_attr(
+        test,
+        proptest(strategy = "crate::types::common::proptest_support::signature()")
+    )]
+    pub sig: Option<Signature>,
     #[serde(skip)]
+    #[cfg_attr(test, proptest(value = "None"))]
     pub signed_hash: Option<String>,
 }
 
@@ -176,10 +321,23 @@ impl TxInfo for L2ChangePubKeyTxInfo {
         Ok(serde_json::to_string(self)?)
     }
 
+    fn write_tx_info(&self, buf: &mut Vec<u8>) -> Result<()> {
+        Ok(serde_json::to_writer(buf, self)?)
+    }
+
     fn get_tx_hash(&self) -> Option<String> {
         self.signed_hash.clone()
     }
 
+    fn signature(&self) -> Option<&[u8]> {
+        self.sig.as_ref().map(|sig| sig.as_slice())
+    }
+
+    fn set_signature(&mut self, sig: Signature, signed_hash: String) {
+        self.sig = Some(sig);
+        self.signed_hash = Some(signed_hash);
+    }
+
     fn validate(&self) -> Result<()> {
         if self.account_index < MIN_ACCOUNT_INDEX || self.account_index > MAX_ACCOUNT_INDEX {
             return Err(LighterError::AccountIndexTooLow(self.account_index));
@@ -194,53 +352,91 @@ impl TxInfo for L2ChangePubKeyTxInfo {
     }
 
     fn hash(&self, lighter_chain_id: u32) -> Result<Vec<u8>> {
-        use poseidon_hash::{hash_to_quintic_extension, Goldilocks};
-
-        // Field order matches lighter-go implementation
-        // See: lighter-go/types/txtypes/change_pub_key.go
-        let mut elements = Vec::new();
-
-        // 1. Chain ID
-        elements.push(Goldilocks::from(lighter_chain_id as u64));
-
-        // 2. Transaction type
-        elements.push(Goldilocks::from(TX_TYPE_L2_CHANGE_PUB_KEY as u64));
-
-        // 3-4. Nonce and ExpiredAt (BEFORE account info!)
-        elements.push(Goldilocks::from(self.nonce as u64));
-        elements.push(Goldilocks::from(self.expired_at as u64));
+        Ok(self.preimage(lighter_chain_id).hash())
+    }
 
-        // 5-6. Account info
-        elements.push(Goldilocks::from(self.account_index as u64));
-        elements.push(Goldilocks::from(self.api_key_index as u64));
+    fn hash_preimage_fields(&self, lighter_chain_id: u32) -> Option<HashPreimage> {
+        Some(self.preimage(lighter_chain_id))
+    }
+}
 
-        // 7+. Public key as field elements (40 bytes = 5 * u64)
-        // Convert from canonical little-endian bytes to field elements
-        for chunk in self.pub_key.chunks(8) {
-            let mut bytes = [0u8; 8];
-            bytes[..chunk.len()].copy_from_slice(chunk);
-            elements.push(Goldilocks::from(u64::from_le_bytes(bytes)));
-        }
+impl L2ChangePubKeyTxInfo {
+    /// Message the account's L1 wallet signs to authorize the new key
+    ///
+    /// Numbers are 0x-prefixed and zero-padded to 16 hex digits.
+    // See: lighter-go/types/txtypes/change_pub_key.go
+    pub fn l1_signature_body(&self) -> String {
+        format!(
+            "Register Lighter Account\n\npubkey: 0x{}\nnonce: 0x{:016x}\naccount index: 0x{:016x}\napi key index: 0x{:016x}\nOnly sign this message for a trusted client!",
+            hex::encode(&self.pub_key),
+            self.nonce,
+            self.account_index,
+            self.api_key_index
+        )
+    }
 
-        // Hash using Poseidon2
-        let hash_result = hash_to_quintic_extension(&elements);
-        Ok(hash_result.to_bytes_le().to_vec())
+    fn preimage(&self, lighter_chain_id: u32) -> HashPreimage {
+        // Field order matches lighter-go implementation
+        // See: lighter-go/types/txtypes/change_pub_key.go
+        let preimage = HashPreimage::header(
+            lighter_chain_id,
+            TX_TYPE_L2_CHANGE_PUB_KEY,
+            self.nonce,
+            self.expired_at,
+            self.account_index,
+            self.api_key_index,
+        );
+
+        // Public key as field elements (40 bytes = 5 * u64), converted from
+        // canonical little-endian bytes
+        const NAMES: [&str; 5] = [
+            "pub_key_0",
+            "pub_key_1",
+            "pub_key_2",
+            "pub_key_3",
+            "pub_key_4",
+        ];
+        self.pub_key
+            .chunks(8)
+            .enumerate()
+            .fold(preimage, |preimage, (i, chunk)| {
+                let mut bytes = [0u8; 8];
+                bytes[..chunk.len()].copy_from_slice(chunk);
+                let name = NAMES.get(i).copied().unwrap_or("pub_key");
+                preimage.field(name, u64::from_le_bytes(bytes))
+            })
     }
 }
 
 /// L2 Update Leverage Transaction Info
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
 pub struct L2UpdateLeverageTxInfo {
+    #[serde(rename = "AccountIndex")]
     pub account_index: i64,
+    #[serde(rename = "ApiKeyIndex")]
     pub api_key_index: u8,
+    #[serde(rename = "MarketIndex")]
     pub market_index: u8,
+    #[serde(rename = "InitialMarginFraction")]
     pub initial_margin_fraction: u16,
+    /// [`MARGIN_MODE_CROSS`] or [`MARGIN_MODE_ISOLATED`]
+    #[serde(rename = "MarginMode")]
+    pub margin_mode: u8,
+    #[serde(rename = "ExpiredAt")]
     pub expired_at: i64,
+    #[serde(rename = "Nonce")]
     pub nonce: i64,
+    #[serde(rename = "Sig")]
     #[serde(skip_serializing_if = "Option::is_none")]
-    #[serde(with = "crate::types::orders::hex_serde", default)]
-    pub sig: Option<Vec<u8>>,
+    #[serde(with = "crate::types::orders::base64_serde", default)]
+    #[cfg_attr(
+        test,
+        proptest(strategy = "crate::types::common::proptest_support::signature()")
+    )]
+    pub sig: Option<Signature>,
     #[serde(skip)]
+    #[cfg_attr(test, proptest(value = "None"))]
     pub signed_hash: Option<String>,
 }
 
@@ -253,10 +449,23 @@ impl TxInfo for L2UpdateLeverageTxInfo {
         Ok(serde_json::to_string(self)?)
     }
 
+    fn write_tx_info(&self, buf: &mut Vec<u8>) -> Result<()> {
+        Ok(serde_json::to_writer(buf, self)?)
+    }
+
     fn get_tx_hash(&self) -> Option<String> {
         self.signed_hash.clone()
     }
 
+    fn signature(&self) -> Option<&[u8]> {
+        self.sig.as_ref().map(|sig| sig.as_slice())
+    }
+
+    fn set_signature(&mut self, sig: Signature, signed_hash: String) {
+        self.sig = Some(sig);
+        self.signed_hash = Some(signed_hash);
+    }
+
     fn validate(&self) -> Result<()> {
         if self.account_index < MIN_ACCOUNT_INDEX || self.account_index > MAX_ACCOUNT_INDEX {
             return Err(LighterError::AccountIndexTooLow(self.account_index));
@@ -269,6 +478,9 @@ impl TxInfo for L2UpdateLeverageTxInfo {
                 self.initial_margin_fraction,
             ));
         }
+        if self.margin_mode != MARGIN_MODE_CROSS && self.margin_mode != MARGIN_MODE_ISOLATED {
+            return Err(LighterError::InvalidMarginMode);
+        }
         if self.nonce < MIN_NONCE {
             return Err(LighterError::NonceTooLow(self.nonce));
         }
@@ -276,46 +488,63 @@ impl TxInfo for L2UpdateLeverageTxInfo {
     }
 
     fn hash(&self, lighter_chain_id: u32) -> Result<Vec<u8>> {
-        use poseidon_hash::{hash_to_quintic_extension, Goldilocks};
-
-        // Field order follows standard pattern
-        let mut elements = Vec::new();
-
-        // 1-2. Chain ID and transaction type
-        elements.push(Goldilocks::from(lighter_chain_id as u64));
-        elements.push(Goldilocks::from(TX_TYPE_L2_UPDATE_LEVERAGE as u64));
-
-        // 3-4. Nonce and ExpiredAt
-        elements.push(Goldilocks::from(self.nonce as u64));
-        elements.push(Goldilocks::from(self.expired_at as u64));
-
-        // 5-6. Account info
-        elements.push(Goldilocks::from(self.account_index as u64));
-        elements.push(Goldilocks::from(self.api_key_index as u64));
+        Ok(self.preimage(lighter_chain_id).hash())
+    }
 
-        // 7-8. Leverage fields
-        elements.push(Goldilocks::from(self.market_index as u64));
-        elements.push(Goldilocks::from(self.initial_margin_fraction as u64));
+    fn hash_preimage_fields(&self, lighter_chain_id: u32) -> Option<HashPreimage> {
+        Some(self.preimage(lighter_chain_id))
+    }
+}
 
-        // Hash using Poseidon2
-        let hash_result = hash_to_quintic_extension(&elements);
-        Ok(hash_result.to_bytes_le().to_vec())
+impl L2UpdateLeverageTxInfo {
+    fn preimage(&self, lighter_chain_id: u32) -> HashPreimage {
+        // Field order matches lighter-go implementation
+        // See: lighter-go/types/txtypes/update_leverage.go
+        HashPreimage::header(
+            lighter_chain_id,
+            TX_TYPE_L2_UPDATE_LEVERAGE,
+            self.nonce,
+            self.expired_at,
+            self.account_index,
+            self.api_key_index,
+        )
+        .field("market_index", self.market_index as u64)
+        .field(
+            "initial_margin_fraction",
+            self.initial_margin_fraction as u64,
+        )
+        .field("margin_mode", self.margin_mode as u64)
     }
 }
 
 /// L2 Update Margin Transaction Info
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
 pub struct L2UpdateMarginTxInfo {
+    #[serde(rename = "AccountIndex")]
     pub account_index: i64,
+    #[serde(rename = "ApiKeyIndex")]
     pub api_key_index: u8,
+    #[serde(rename = "MarketIndex")]
     pub market_index: u8,
+    #[serde(rename = "USDCAmount")]
     pub usdc_amount: i64,
+    #[serde(rename = "Direction")]
     pub direction: u8,
+    #[serde(rename = "ExpiredAt")]
     pub expired_at: i64,
+    #[serde(rename = "Nonce")]
     pub nonce: i64,
+    #[serde(rename = "Sig")]
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub sig: Option<Vec<u8>>,
+    #[serde(with = "crate::types::orders::base64_serde", default)]
+    #[cfg_attr(
+        test,
+        proptest(strategy = "crate::types::common::proptest_support::signature()")
+    )]
+    pub sig: Option<Signature>,
     #[serde(skip)]
+    #[cfg_attr(test, proptest(value = "None"))]
     pub signed_hash: Option<String>,
 }
 
@@ -328,10 +557,23 @@ impl TxInfo for L2UpdateMarginTxInfo {
         Ok(serde_json::to_string(self)?)
     }
 
+    fn write_tx_info(&self, buf: &mut Vec<u8>) -> Result<()> {
+        Ok(serde_json::to_writer(buf, self)?)
+    }
+
     fn get_tx_hash(&self) -> Option<String> {
         self.signed_hash.clone()
     }
 
+    fn signature(&self) -> Option<&[u8]> {
+        self.sig.as_ref().map(|sig| sig.as_slice())
+    }
+
+    fn set_signature(&mut self, sig: Signature, signed_hash: String) {
+        self.sig = Some(sig);
+        self.signed_hash = Some(signed_hash);
+    }
+
     fn validate(&self) -> Result<()> {
         if self.account_index < MIN_ACCOUNT_INDEX || self.account_index > MAX_ACCOUNT_INDEX {
             return Err(LighterError::AccountIndexTooLow(self.account_index));
@@ -343,28 +585,67 @@ impl TxInfo for L2UpdateMarginTxInfo {
         {
             return Err(LighterError::InvalidUpdateMarginDirection);
         }
+        if self.usdc_amount < MIN_UPDATE_MARGIN_AMOUNT {
+            return Err(LighterError::UpdateMarginAmountTooLow(self.usdc_amount));
+        }
+        if self.usdc_amount > MAX_UPDATE_MARGIN_AMOUNT {
+            return Err(LighterError::UpdateMarginAmountTooHigh(self.usdc_amount));
+        }
         if self.nonce < MIN_NONCE {
             return Err(LighterError::NonceTooLow(self.nonce));
         }
         Ok(())
     }
 
-    fn hash(&self, _lighter_chain_id: u32) -> Result<Vec<u8>> {
-        // TODO: Implement Poseidon2 hashing
-        Ok(vec![0u8; 40])
+    fn hash(&self, lighter_chain_id: u32) -> Result<Vec<u8>> {
+        Ok(self.preimage(lighter_chain_id).hash())
+    }
+
+    fn hash_preimage_fields(&self, lighter_chain_id: u32) -> Option<HashPreimage> {
+        Some(self.preimage(lighter_chain_id))
+    }
+}
+
+impl L2UpdateMarginTxInfo {
+    fn preimage(&self, lighter_chain_id: u32) -> HashPreimage {
+        // Field order matches lighter-go implementation
+        // See: lighter-go/types/txtypes/update_margin.go
+        HashPreimage::header(
+            lighter_chain_id,
+            TX_TYPE_L2_UPDATE_MARGIN,
+            self.nonce,
+            self.expired_at,
+            self.account_index,
+            self.api_key_index,
+        )
+        .field("market_index", self.market_index as u64)
+        .limbs("usdc_amount_lo", "usdc_amount_hi", self.usdc_amount as u64)
+        .field("direction", self.direction as u64)
     }
 }
 
 /// L2 Create Sub Account Transaction Info
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
 pub struct L2CreateSubAccountTxInfo {
+    #[serde(rename = "AccountIndex")]
     pub account_index: i64,
+    #[serde(rename = "ApiKeyIndex")]
     pub api_key_index: u8,
+    #[serde(rename = "ExpiredAt")]
     pub expired_at: i64,
+    #[serde(rename = "Nonce")]
     pub nonce: i64,
+    #[serde(rename = "Sig")]
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub sig: Option<Vec<u8>>,
+    #[serde(with = "crate::types::orders::base64_serde", default)]
+    #[cfg_attr(
+        test,
+        proptest(strategy = "crate::types::common::proptest_support::signature()")
+    )]
+    pub sig: Option<Signature>,
     #[serde(skip)]
+    #[cfg_attr(test, proptest(value = "None"))]
     pub signed_hash: Option<String>,
 }
 
@@ -377,29 +658,65 @@ impl TxInfo for L2CreateSubAccountTxInfo {
         Ok(serde_json::to_string(self)?)
     }
 
+    fn write_tx_info(&self, buf: &mut Vec<u8>) -> Result<()> {
+        Ok(serde_json::to_writer(buf, self)?)
+    }
+
     fn get_tx_hash(&self) -> Option<String> {
         self.signed_hash.clone()
     }
 
+    fn signature(&self) -> Option<&[u8]> {
+        self.sig.as_ref().map(|sig| sig.as_slice())
+    }
+
+    fn set_signature(&mut self, sig: Signature, signed_hash: String) {
+        self.sig = Some(sig);
+        self.signed_hash = Some(signed_hash);
+    }
+
     fn validate(&self) -> Result<()> {
-        if self.account_index < MIN_ACCOUNT_INDEX || self.account_index > MAX_ACCOUNT_INDEX {
+        if self.account_index < MIN_ACCOUNT_INDEX {
             return Err(LighterError::AccountIndexTooLow(self.account_index));
         }
+        if self.account_index > MAX_ACCOUNT_INDEX {
+            return Err(LighterError::AccountIndexTooHigh(self.account_index));
+        }
         if self.nonce < MIN_NONCE {
             return Err(LighterError::NonceTooLow(self.nonce));
         }
         Ok(())
     }
 
-    fn hash(&self, _lighter_chain_id: u32) -> Result<Vec<u8>> {
-        // TODO: Implement Poseidon2 hashing
-        Ok(vec![0u8; 40])
+    fn hash(&self, lighter_chain_id: u32) -> Result<Vec<u8>> {
+        Ok(self.preimage(lighter_chain_id).hash())
+    }
+
+    fn hash_preimage_fields(&self, lighter_chain_id: u32) -> Option<HashPreimage> {
+        Some(self.preimage(lighter_chain_id))
+    }
+}
+
+impl L2CreateSubAccountTxInfo {
+    fn preimage(&self, lighter_chain_id: u32) -> HashPreimage {
+        // Field order matches lighter-go implementation
+        // See: lighter-go/types/txtypes/create_sub_account.go
+        HashPreimage::header(
+            lighter_chain_id,
+            TX_TYPE_L2_CREATE_SUB_ACCOUNT,
+            self.nonce,
+            self.expired_at,
+            self.account_index,
+            self.api_key_index,
+        )
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::types::common::proptest_support::*;
+    use proptest::prelude::*;
 
     #[test]
     fn test_transfer_validation_success() {
@@ -462,6 +779,47 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn test_transfer_hashes_amounts_as_limbs_and_the_memo_as_words() {
+        let mut memo = [0xFFu8; 32];
+        memo[..4].copy_from_slice(&[1, 2, 3, 4]);
+        let tx_info = L2TransferTxInfo {
+            from_account_index: 12345,
+            api_key_index: 3,
+            to_account_index: 54321,
+            // 5 * 2^32 + 7 and 2^32 + 2
+            usdc_amount: 21_474_836_487,
+            fee: 4_294_967_298,
+            memo,
+            expired_at: 1000000,
+            nonce: 1,
+            sig: None,
+            signed_hash: None,
+        };
+
+        let preimage = tx_info.hash_preimage_fields(304).unwrap();
+        let fields: Vec<_> = preimage.iter().collect();
+        assert_eq!(
+            fields[6..],
+            [
+                ("to_account_index", 54321),
+                ("usdc_amount_lo", 7),
+                ("usdc_amount_hi", 5),
+                ("fee_lo", 2),
+                ("fee_hi", 1),
+                ("memo_0", 0x0403_0201),
+                ("memo_1", 0xFFFF_FFFF),
+                ("memo_2", 0xFFFF_FFFF),
+                ("memo_3", 0xFFFF_FFFF),
+                ("memo_4", 0xFFFF_FFFF),
+                ("memo_5", 0xFFFF_FFFF),
+                ("memo_6", 0xFFFF_FFFF),
+                ("memo_7", 0xFFFF_FFFF),
+            ]
+        );
+        assert_eq!(tx_info.hash(304).unwrap(), preimage.hash());
+    }
+
     #[test]
     fn test_withdraw_validation_success() {
         let tx_info = L2WithdrawTxInfo {
@@ -478,6 +836,62 @@ mod tests {
         assert_eq!(tx_info.get_tx_type(), TX_TYPE_L2_WITHDRAW);
     }
 
+    #[test]
+    fn test_withdraw_amount_bounds() {
+        let withdraw = |usdc_amount| L2WithdrawTxInfo {
+            from_account_index: 12345,
+            api_key_index: 0,
+            usdc_amount,
+            expired_at: 1000000,
+            nonce: 1,
+            sig: None,
+            signed_hash: None,
+        };
+
+        assert!(matches!(
+            withdraw(0).validate(),
+            Err(LighterError::WithdrawalAmountTooLow(0))
+        ));
+        assert!(matches!(
+            withdraw(MAX_WITHDRAWAL_AMOUNT + 1).validate(),
+            Err(LighterError::WithdrawalAmountTooHigh(_))
+        ));
+        // 1 USDC in its 6-decimal representation
+        assert!(withdraw(1_000_000).validate().is_ok());
+        assert_ne!(withdraw(1_000_000).hash(304).unwrap(), vec![0u8; 40]);
+    }
+
+    #[test]
+    fn test_withdraw_hashes_the_amount_as_32_bit_limbs() {
+        let tx_info = L2WithdrawTxInfo {
+            from_account_index: 12345,
+            api_key_index: 3,
+            // 5 * 2^32 + 7
+            usdc_amount: 21_474_836_487,
+            expired_at: 1000000,
+            nonce: 1,
+            sig: None,
+            signed_hash: None,
+        };
+
+        let preimage = tx_info.hash_preimage_fields(304).unwrap();
+        let fields: Vec<_> = preimage.iter().collect();
+        assert_eq!(
+            fields,
+            vec![
+                ("chain_id", 304),
+                ("tx_type", TX_TYPE_L2_WITHDRAW as u64),
+                ("nonce", 1),
+                ("expired_at", 1000000),
+                ("account_index", 12345),
+                ("api_key_index", 3),
+                ("usdc_amount_lo", 7),
+                ("usdc_amount_hi", 5),
+            ]
+        );
+        assert_eq!(tx_info.hash(304).unwrap(), preimage.hash());
+    }
+
     #[test]
     fn test_change_pub_key_validation_success() {
         let tx_info = L2ChangePubKeyTxInfo {
@@ -486,12 +900,34 @@ mod tests {
             pub_key: vec![0u8; 40],
             expired_at: 1000000,
             nonce: 1,
+            l1_sig: None,
             sig: None,
             signed_hash: None,
         };
 
         assert!(tx_info.validate().is_ok());
         assert_eq!(tx_info.get_tx_type(), TX_TYPE_L2_CHANGE_PUB_KEY);
+        assert_eq!(
+            tx_info.l1_signature_body(),
+            format!(
+                "Register Lighter Account\n\npubkey: 0x{}\nnonce: 0x0000000000000001\n\
+                 account index: 0x0000000000003039\napi key index: 0x0000000000000000\n\
+                 Only sign this message for a trusted client!",
+                "00".repeat(40)
+            )
+        );
+
+        // The L1 signature rides along without changing the signed hash
+        let authorized = L2ChangePubKeyTxInfo {
+            l1_sig: Some("0xab".to_string()),
+            ..tx_info.clone()
+        };
+        assert_eq!(authorized.hash(304).unwrap(), tx_info.hash(304).unwrap());
+        assert!(authorized
+            .get_tx_info()
+            .unwrap()
+            .contains(r#""L1Sig":"0xab""#));
+        assert!(!tx_info.get_tx_info().unwrap().contains("L1Sig"));
     }
 
     #[test]
@@ -502,6 +938,7 @@ mod tests {
             pub_key: vec![0u8; 20],
             expired_at: 1000000,
             nonce: 1,
+            l1_sig: None,
             sig: None,
             signed_hash: None,
         };
@@ -518,6 +955,7 @@ mod tests {
             api_key_index: 0,
             market_index: 0,
             initial_margin_fraction: 5000,
+            margin_mode: MARGIN_MODE_ISOLATED,
             expired_at: 1000000,
             nonce: 1,
             sig: None,
@@ -526,6 +964,14 @@ mod tests {
 
         assert!(tx_info.validate().is_ok());
         assert_eq!(tx_info.get_tx_type(), TX_TYPE_L2_UPDATE_LEVERAGE);
+        let unknown_mode = L2UpdateLeverageTxInfo {
+            margin_mode: 2,
+            ..tx_info.clone()
+        };
+        assert!(matches!(
+            unknown_mode.validate(),
+            Err(LighterError::InvalidMarginMode)
+        ));
     }
 
     #[test]
@@ -544,6 +990,54 @@ mod tests {
 
         assert!(tx_info.validate().is_ok());
         assert_eq!(tx_info.get_tx_type(), TX_TYPE_L2_UPDATE_MARGIN);
+        for (usdc_amount, too_low) in [(0, true), (-1, true), (MAX_UPDATE_MARGIN_AMOUNT + 1, false)]
+        {
+            let result = L2UpdateMarginTxInfo {
+                usdc_amount,
+                ..tx_info.clone()
+            }
+            .validate();
+            if too_low {
+                assert!(matches!(
+                    result,
+                    Err(LighterError::UpdateMarginAmountTooLow(_))
+                ));
+            } else {
+                assert!(matches!(
+                    result,
+                    Err(LighterError::UpdateMarginAmountTooHigh(_))
+                ));
+            }
+        }
+    }
+
+    #[test]
+    fn test_update_margin_hashes_the_amount_as_32_bit_limbs() {
+        let tx_info = L2UpdateMarginTxInfo {
+            account_index: 12345,
+            api_key_index: 3,
+            market_index: 2,
+            // 5 * 2^32 + 7
+            usdc_amount: 21_474_836_487,
+            direction: MARGIN_ADD_TO_ISOLATED,
+            expired_at: 1000000,
+            nonce: 1,
+            sig: None,
+            signed_hash: None,
+        };
+
+        let preimage = tx_info.hash_preimage_fields(304).unwrap();
+        let fields: Vec<_> = preimage.iter().collect();
+        assert_eq!(
+            fields[6..],
+            [
+                ("market_index", 2),
+                ("usdc_amount_lo", 7),
+                ("usdc_amount_hi", 5),
+                ("direction", MARGIN_ADD_TO_ISOLATED as u64),
+            ]
+        );
+        assert_eq!(tx_info.hash(304).unwrap(), preimage.hash());
     }
 
     #[test]
@@ -582,4 +1076,79 @@ mod tests {
         assert!(tx_info.validate().is_ok());
         assert_eq!(tx_info.get_tx_type(), TX_TYPE_L2_CREATE_SUB_ACCOUNT);
     }
+
+    proptest! {
+        #[test]
+        fn test_transfer_requests_round_trip(
+            transfer in any::<TransferTxReq>(),
+            withdraw in any::<WithdrawTxReq>(),
+            change_pub_key in any::<ChangePubKeyTxReq>(),
+            leverage in any::<UpdateLeverageTxReq>(),
+            margin in any::<UpdateMarginTxReq>(),
+        ) {
+            check_json_round_trip(&transfer)?;
+            check_json_round_trip(&withdraw)?;
+            check_json_round_trip(&change_pub_key)?;
+            check_json_round_trip(&leverage)?;
+            check_json_round_trip(&margin)?;
+        }
+
+        #[test]
+        fn test_transfer_txs_round_trip(
+            transfer in any::<L2TransferTxInfo>(),
+            withdraw in any::<L2WithdrawTxInfo>(),
+            change_pub_key in any::<L2ChangePubKeyTxInfo>(),
+            leverage in any::<L2UpdateLeverageTxInfo>(),
+            margin in any::<L2UpdateMarginTxInfo>(),
+            sub_account in any::<L2CreateSubAccountTxInfo>(),
+        ) {
+            check_tx_round_trip(&transfer)?;
+            check_tx_round_trip(&withdraw)?;
+            check_tx_round_trip(&change_pub_key)?;
+            check_tx_round_trip(&leverage)?;
+            check_tx_round_trip(&margin)?;
+            check_tx_round_trip(&sub_account)?;
+        }
+
+        #[test]
+        fn test_transfer_tx_hashes_deterministic(
+            chain_id in any::<u32>(),
+            transfer in any::<L2TransferTxInfo>(),
+            withdraw in any::<L2WithdrawTxInfo>(),
+            change_pub_key in any::<L2ChangePubKeyTxInfo>(),
+            leverage in any::<L2UpdateLeverageTxInfo>(),
+            margin in any::<L2UpdateMarginTxInfo>(),
+            sub_account in any::<L2CreateSubAccountTxInfo>(),
+        ) {
+            check_hash_deterministic(&transfer, chain_id)?;
+            check_hash_deterministic(&withdraw, chain_id)?;
+            check_hash_deterministic(&change_pub_key, chain_id)?;
+            check_hash_deterministic(&leverage, chain_id)?;
+            check_hash_deterministic(&margin, chain_id)?;
+            check_hash_deterministic(&sub_account, chain_id)?;
+        }
+    }
+
+    proptest! {
+        // Signing is slow in debug builds
+        #![proptest_config(ProptestConfig::with_cases(16))]
+
+        #[test]
+        fn test_transfer_txs_sign_and_verify(
+            chain_id in any::<u32>(),
+            transfer in any::<L2TransferTxInfo>(),
+            withdraw in any::<L2WithdrawTxInfo>(),
+            change_pub_key in any::<L2ChangePubKeyTxInfo>(),
+            leverage in any::<L2UpdateLeverageTxInfo>(),
+            margin in any::<L2UpdateMarginTxInfo>(),
+            sub_account in any::<L2CreateSubAccountTxInfo>(),
+        ) {
+            check_sign_verify(transfer, chain_id)?;
+            check_sign_verify(withdraw, chain_id)?;
+            check_sign_verify(change_pub_key, chain_id)?;
+            check_sign_verify(leverage, chain_id)?;
+            check_sign_verify(margin, chain_id)?;
+            check_sign_verify(sub_account, chain_id)?;
+        }
+    }
 }
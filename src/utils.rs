@@ -1,7 +1,10 @@
 //! Utility functions for the Lighter SDK
 
+use crate::constants::{MAX_ORDER_PRICE, MIN_ORDER_PRICE, ONE_USDC};
 use crate::errors::{LighterError, Result};
 use hex;
+use rust_decimal::prelude::{FromPrimitive, ToPrimitive};
+use rust_decimal::{Decimal, RoundingStrategy};
 
 /// Convert hex string to bytes, handling optional 0x prefix
 pub fn hex_to_bytes(hex_str: &str) -> Result<Vec<u8>> {
@@ -39,6 +42,91 @@ pub fn validate_range<T: PartialOrd + std::fmt::Display>(
     Ok(())
 }
 
+/// Convert a positive USDC amount to its 6-decimal integer representation
+///
+/// Amounts with more than 6 decimals are refused rather than rounded.
+pub fn usdc_to_units(usdc: f64) -> Result<i64> {
+    Decimal::from_f64(usdc)
+        .map(|usdc| usdc * Decimal::from(ONE_USDC))
+        .filter(|units| units.is_sign_positive() && !units.is_zero() && units.fract().is_zero())
+        .and_then(|units| units.to_i64())
+        .ok_or(LighterError::InvalidUsdcAmount(usdc))
+}
+
+/// Convert a price to integer ticks of a market with `price_decimals`
+///
+/// Digits past the market's precision round to the nearest tick. Prices
+/// that round to no ticks or above [`MAX_ORDER_PRICE`] are refused rather
+/// than truncated.
+pub fn price_to_units(price: f64, price_decimals: u32) -> Result<u64> {
+    Decimal::from_f64(price)
+        .zip(10u64.checked_pow(price_decimals).map(Decimal::from))
+        .and_then(|(price, scale)| price.checked_mul(scale))
+        .map(|units| units.round_dp_with_strategy(0, RoundingStrategy::MidpointAwayFromZero))
+        .and_then(|units| units.to_u64())
+        .filter(|units| (MIN_ORDER_PRICE..=MAX_ORDER_PRICE).contains(units))
+        .ok_or(LighterError::InvalidOrderPrice {
+            price,
+            price_decimals,
+        })
+}
+
+/// Price of an order `slippage_bps` worse than `reference`, in units of a
+/// market with `price_decimals`
+///
+/// Buys are bounded above the reference and sells below it. The price is
+/// rounded towards the reference, so the bound never exceeds the tolerance;
+/// a slippage of 0 is the reference itself.
+pub fn slippage_price(
+    reference: Decimal,
+    slippage_bps: u32,
+    is_ask: bool,
+    price_decimals: u32,
+) -> Result<u64> {
+    let slippage = Decimal::from(slippage_bps) / Decimal::from(10_000);
+    let scale = 10u64.checked_pow(price_decimals).map(Decimal::from);
+    let price = if is_ask {
+        reference
+            .checked_mul(Decimal::ONE - slippage)
+            .zip(scale)
+            .and_then(|(price, scale)| price.checked_mul(scale))
+            .map(|price| price.ceil())
+    } else {
+        reference
+            .checked_mul(Decimal::ONE + slippage)
+            .zip(scale)
+            .and_then(|(price, scale)| price.checked_mul(scale))
+            .map(|price| price.floor())
+    };
+    price
+        .and_then(|price| price.to_u64())
+        .filter(|price| (MIN_ORDER_PRICE..=MAX_ORDER_PRICE).contains(price))
+        .ok_or(LighterError::SlippagePriceOutOfRange {
+            reference,
+            slippage_bps,
+        })
+}
+
+/// Integer given as a JSON number or string
+#[cfg(feature = "native")]
+pub(crate) fn json_integer(value: Option<&serde_json::Value>) -> Option<i64> {
+    match value? {
+        serde_json::Value::Number(n) => n.as_i64(),
+        serde_json::Value::String(s) => s.parse().ok(),
+        _ => None,
+    }
+}
+
+/// Decimal given as a JSON string or number
+#[cfg(feature = "native")]
+pub(crate) fn json_decimal(value: Option<&serde_json::Value>) -> Option<Decimal> {
+    match value? {
+        serde_json::Value::String(s) => s.parse().ok(),
+        serde_json::Value::Number(n) => n.to_string().parse().ok(),
+        _ => None,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -71,4 +159,104 @@ mod tests {
         assert!(validate_range(0, 1, 10, "test").is_err());
         assert!(validate_range(11, 1, 10, "test").is_err());
     }
+
+    #[test]
+    fn test_usdc_to_units() {
+        assert_eq!(usdc_to_units(1.0).unwrap(), 1_000_000);
+        assert_eq!(usdc_to_units(0.1).unwrap(), 100_000);
+        assert_eq!(usdc_to_units(12.345678).unwrap(), 12_345_678);
+        for usdc in [0.0, -1.0, 0.0000001, 1.2345678, f64::NAN, f64::INFINITY] {
+            assert!(matches!(
+                usdc_to_units(usdc),
+                Err(LighterError::InvalidUsdcAmount(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_price_to_units() {
+        assert_eq!(price_to_units(3000.12, 2).unwrap(), 300_012);
+        assert_eq!(price_to_units(3000.125, 2).unwrap(), 300_013);
+        assert_eq!(price_to_units(157.123, 6).unwrap(), 157_123_000);
+        // $100,000 BTC with 1 decimal, and the largest price with 6
+        assert_eq!(price_to_units(100_000.0, 1).unwrap(), 1_000_000);
+        assert_eq!(price_to_units(4294.967295, 6).unwrap(), u64::from(u32::MAX));
+
+        // Past the field, where a cast would have wrapped or saturated
+        for (price, price_decimals) in [
+            (100_000.0, 6),
+            (4294.967296, 6),
+            (1e30, 2),
+            (0.0, 2),
+            (0.004, 2),
+            (-1.0, 2),
+            (f64::NAN, 2),
+            (f64::INFINITY, 2),
+            (1.0, 30),
+        ] {
+            assert!(matches!(
+                price_to_units(price, price_decimals),
+                Err(LighterError::InvalidOrderPrice { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn test_slippage_price() {
+        // 3000.00 with 2 decimals, 1% either way
+        let eth = Decimal::new(300_000, 2);
+        assert_eq!(slippage_price(eth, 100, false, 2).unwrap(), 303_000);
+        assert_eq!(slippage_price(eth, 100, true, 2).unwrap(), 297_000);
+        assert_eq!(slippage_price(eth, 0, false, 2).unwrap(), 300_000);
+        assert_eq!(slippage_price(eth, 0, true, 2).unwrap(), 300_000);
+
+        // 157.123 with 6 decimals rounds towards the reference
+        let jpy = Decimal::new(157_123, 3);
+        assert_eq!(slippage_price(jpy, 3, false, 6).unwrap(), 157_170_136);
+        assert_eq!(slippage_price(jpy, 3, true, 6).unwrap(), 157_075_864);
+        assert_eq!(slippage_price(jpy, 0, false, 6).unwrap(), 157_123_000);
+
+        // Prices the field can't hold
+        for (reference, slippage_bps, is_ask) in [
+            (Decimal::new(u32::MAX as i64, 2), 1, false),
+            (Decimal::MAX, 100, false),
+            (eth, 10_000, true),
+            (Decimal::ZERO, 100, false),
+        ] {
+            assert!(matches!(
+                slippage_price(reference, slippage_bps, is_ask, 2),
+                Err(LighterError::SlippagePriceOutOfRange { .. })
+            ));
+        }
+
+        // Decimals past what a u64 scale can hold are refused, not a panic
+        for price_decimals in [20, u32::MAX] {
+            assert!(matches!(
+                slippage_price(eth, 100, false, price_decimals),
+                Err(LighterError::SlippagePriceOutOfRange { .. })
+            ));
+        }
+    }
+    #[cfg(feature = "native")]
+    #[test]
+    fn test_json_numbers_as_numbers_or_strings() {
+        use serde_json::json;
+
+        assert_eq!(json_integer(Some(&json!(42))), Some(42));
+        assert_eq!(json_integer(Some(&json!("-7"))), Some(-7));
+        assert_eq!(json_integer(Some(&json!(1.5))), None);
+        assert_eq!(json_integer(Some(&json!("x"))), None);
+        assert_eq!(json_integer(None), None);
+
+        assert_eq!(
+            json_decimal(Some(&json!("0.25"))),
+            Some(Decimal::new(25, 2))
+        );
+        assert_eq!(
+            json_decimal(Some(&json!(3000.5))),
+            Some(Decimal::new(30005, 1))
+        );
+        assert_eq!(json_decimal(Some(&json!(true))), None);
+        assert_eq!(json_decimal(None), None);
+    }
 }
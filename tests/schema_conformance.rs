@@ -0,0 +1,609 @@
+//! Conformance of request bodies and response types to the API definitions
+//!
+//! `tests/schema/openapi.json` is a snapshot of the Lighter API definitions,
+//! trimmed to the endpoints this crate calls, and `tests/schema/tx_info.json`
+//! describes the tx_info JSON of every transaction type as lighter-go's signer
+//! encodes it. These tests check that:
+//!
+//! - the sendTx bodies pinned in `tests/fixtures/signed_payloads.json`, and a
+//!   sendTxBatch body of the same transactions, carry the form fields their
+//!   schema requires and no others, and that each tx_info has exactly the
+//!   fields of its type, with matching JSON types and integer ranges
+//! - every response the client reads deserializes both with all the fields
+//!   of its schema and with only the required ones, so a field the client
+//!   relies on but the API may leave out fails here
+//!
+//! Failures name the endpoint and the field. When the API definitions change,
+//! replace the snapshot and work through the failures; a change to what is
+//! signed also needs the pinned payloads regenerated, see
+//! `tests/signed_payloads.rs`.
+
+#![cfg(not(target_arch = "wasm32"))]
+
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use base64::Engine;
+use lighter_rs::account::AccountState;
+use lighter_rs::candles::{Candle, CandleResolution};
+use lighter_rs::client::{
+    AccountPosition, AccountTrade, ActiveOrder, BatchTxResponse, FundingPayment, HTTPClient,
+    MarketDetails, TransactionStatus, TxResponse,
+};
+use lighter_rs::transport::{HttpResponse, MockTransport};
+use lighter_rs::types::SignedTx;
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+
+const COMPONENTS: &str = "#/components/schemas/";
+
+struct Schemas {
+    openapi: Value,
+    tx_info: Value,
+}
+
+impl Schemas {
+    fn load() -> Self {
+        let read = |name: &str| -> Value {
+            let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join(name);
+            let json = std::fs::read_to_string(&path)
+                .unwrap_or_else(|e| panic!("cannot read {}: {e}", path.display()));
+            serde_json::from_str(&json).unwrap_or_else(|e| panic!("{name}: {e}"))
+        };
+        Self {
+            openapi: read("tests/schema/openapi.json"),
+            tx_info: read("tests/schema/tx_info.json"),
+        }
+    }
+
+    fn component(&self, name: &str) -> &Value {
+        let schema = &self.openapi["components"]["schemas"][name];
+        assert!(schema.is_object(), "no schema {name} in the snapshot");
+        schema
+    }
+
+    fn resolve<'a>(&'a self, schema: &'a Value) -> &'a Value {
+        match schema["$ref"].as_str() {
+            Some(reference) => self.component(reference.trim_start_matches(COMPONENTS)),
+            None => schema,
+        }
+    }
+
+    /// Name of the schema of `endpoint`'s request or response body
+    fn body_schema(&self, method: &str, endpoint: &str, request: bool) -> &str {
+        let operation = &self.openapi["paths"][endpoint][method];
+        let content = if request {
+            &operation["requestBody"]["content"]["application/x-www-form-urlencoded"]
+        } else {
+            &operation["responses"]["200"]["content"]["application/json"]
+        };
+        content["schema"]["$ref"]
+            .as_str()
+            .unwrap_or_else(|| panic!("no {method} {endpoint} in the snapshot"))
+            .trim_start_matches(COMPONENTS)
+    }
+
+    fn tx_info_schema(&self, tx_type: u8) -> Option<&Value> {
+        self.tx_info["tx_types"]
+            .get(tx_type.to_string())
+            .filter(|schema| schema.is_object())
+    }
+
+    /// Check `value` against `schema`, flagging fields the schema doesn't define
+    fn check(&self, schema: &Value, value: &Value, path: &str, errors: &mut Vec<String>) {
+        let schema = self.resolve(schema);
+        let expected = schema["type"].as_str().unwrap_or("object");
+        let format = schema["format"].as_str().unwrap_or("");
+        let mut mismatch = || {
+            errors.push(format!(
+                "field {path:?}: expected {expected} {format}, got {value}"
+            ))
+        };
+        match expected {
+            "object" => {
+                let Some(object) = value.as_object() else {
+                    return mismatch();
+                };
+                let properties = schema["properties"]
+                    .as_object()
+                    .cloned()
+                    .unwrap_or_default();
+                for required in schema["required"].as_array().into_iter().flatten() {
+                    let required = required.as_str().unwrap();
+                    if !object.contains_key(required) {
+                        errors.push(format!("field {:?}: missing", join(path, required)));
+                    }
+                }
+                for (name, field) in object {
+                    match properties.get(name) {
+                        Some(property) => self.check(property, field, &join(path, name), errors),
+                        None => {
+                            errors.push(format!("field {:?}: not in the schema", join(path, name)))
+                        }
+                    }
+                }
+            }
+            "array" => {
+                let Some(items) = value.as_array() else {
+                    return mismatch();
+                };
+                let len = items.len() as u64;
+                if schema["minItems"].as_u64().is_some_and(|min| len < min)
+                    || schema["maxItems"].as_u64().is_some_and(|max| len > max)
+                {
+                    errors.push(format!("field {path:?}: {len} items, expected {}", schema));
+                }
+                for (i, item) in items.iter().enumerate() {
+                    self.check(&schema["items"], item, &format!("{path}[{i}]"), errors);
+                }
+            }
+            "integer" => {
+                let in_range = match value
+                    .as_i64()
+                    .map(i128::from)
+                    .or(value.as_u64().map(i128::from))
+                {
+                    Some(n) => match format {
+                        "uint8" => (0..=i128::from(u8::MAX)).contains(&n),
+                        "uint16" => (0..=i128::from(u16::MAX)).contains(&n),
+                        "uint32" => (0..=i128::from(u32::MAX)).contains(&n),
+                        "uint64" => (0..=i128::from(u64::MAX)).contains(&n),
+                        "int32" => (i128::from(i32::MIN)..=i128::from(i32::MAX)).contains(&n),
+                        _ => (i128::from(i64::MIN)..=i128::from(i64::MAX)).contains(&n),
+                    },
+                    None => false,
+                };
+                if !in_range {
+                    mismatch();
+                }
+            }
+            "number" => {
+                if !value.is_number() {
+                    mismatch();
+                }
+            }
+            "boolean" => {
+                if !value.is_boolean() {
+                    mismatch();
+                }
+            }
+            "string" => match value.as_str() {
+                Some(s) if format == "byte" => {
+                    if base64::engine::general_purpose::STANDARD.decode(s).is_err() {
+                        mismatch();
+                    }
+                }
+                Some(_) => {}
+                None => mismatch(),
+            },
+            other => panic!("schema type {other} at {path} is not supported"),
+        }
+    }
+
+    /// A value of `schema` with every field, or only the required ones
+    fn instance(&self, schema: &Value, required_only: bool) -> Value {
+        let schema = self.resolve(schema);
+        match schema["type"].as_str().unwrap_or("object") {
+            "object" => {
+                let required: Vec<&str> = schema["required"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(Value::as_str)
+                    .collect();
+                let mut object = Map::new();
+                for (name, property) in schema["properties"].as_object().into_iter().flatten() {
+                    if !required_only || required.contains(&name.as_str()) {
+                        object.insert(name.clone(), self.instance(property, required_only));
+                    }
+                }
+                Value::Object(object)
+            }
+            "array" => Value::Array(vec![self.instance(&schema["items"], required_only)]),
+            "integer" => Value::from(1),
+            "number" => Value::from(1.5),
+            "boolean" => Value::Bool(true),
+            _ if schema["format"] == "byte" => Value::from("AQ=="),
+            _ => Value::from("1"),
+        }
+    }
+
+    /// Check a form-encoded request body against its schema
+    ///
+    /// Returns the fields, for their contents to be checked in turn.
+    fn check_form(
+        &self,
+        schema_name: &str,
+        body: &[u8],
+        errors: &mut Vec<String>,
+    ) -> Map<String, Value> {
+        let schema = self.component(schema_name);
+        let fields: Vec<(String, String)> = serde_urlencoded::from_bytes(body).unwrap();
+        let mut form = Map::new();
+        for (name, raw) in fields {
+            // Form values are text; read them as the schema says they are
+            let value = match schema["properties"][&name]["type"].as_str() {
+                Some("integer") => raw
+                    .parse::<i64>()
+                    .map(Value::from)
+                    .unwrap_or(Value::from(raw)),
+                Some("boolean") => raw
+                    .parse::<bool>()
+                    .map(Value::from)
+                    .unwrap_or(Value::from(raw)),
+                _ => Value::from(raw),
+            };
+            form.insert(name, value);
+        }
+        let form = Value::Object(form);
+        self.check(schema, &form, "", errors);
+        match form {
+            Value::Object(form) => form,
+            _ => unreachable!(),
+        }
+    }
+
+    fn check_tx_info(&self, tx_type: u8, tx_info: &str, errors: &mut Vec<String>) {
+        let Some(schema) = self.tx_info_schema(tx_type) else {
+            errors.push(format!(
+                "tx_type {tx_type}: not in tests/schema/tx_info.json"
+            ));
+            return;
+        };
+        let title = schema["title"].as_str().unwrap_or("?");
+        let value: Value = match serde_json::from_str(tx_info) {
+            Ok(value) => value,
+            Err(e) => return errors.push(format!("tx_info of {title}: not JSON: {e}")),
+        };
+        let mut found = Vec::new();
+        self.check(schema, &value, "", &mut found);
+        errors.extend(
+            found
+                .into_iter()
+                .map(|error| format!("tx_info of {title} (tx_type {tx_type}) {error}")),
+        );
+    }
+}
+
+fn join(path: &str, name: &str) -> String {
+    if path.is_empty() {
+        name.to_string()
+    } else {
+        format!("{path}.{name}")
+    }
+}
+
+/// Deserialize `value` into `T`, naming the field serde stopped at
+fn deserialize<T: DeserializeOwned>(value: &Value) -> Result<T, String> {
+    // One field per line, so the error's line says which field failed
+    let json = serde_json::to_string_pretty(value).unwrap();
+    serde_json::from_str(&json).map_err(|e| explain(&json, &e.to_string()))
+}
+
+/// Name the field of pretty-printed `json` a serde error points at
+fn explain(json: &str, error: &str) -> String {
+    if error.contains("missing field") {
+        return format!("{error}, which the schema does not require");
+    }
+    let line: usize = error
+        .rsplit_once(" at line ")
+        .and_then(|(_, at)| at.split(' ').next()?.parse().ok())
+        .unwrap_or(0);
+    let lines: Vec<&str> = json.lines().collect();
+    let field = lines[..line.min(lines.len())]
+        .iter()
+        .rev()
+        .find_map(|line| {
+            let line = line.trim_start().strip_prefix('"')?;
+            let (name, rest) = line.split_once('"')?;
+            rest.starts_with(':').then_some(name)
+        });
+    match field {
+        Some(field) => format!("field {field:?}: {error}"),
+        None => error.to_string(),
+    }
+}
+
+/// Check that `T` reads every instance of the response schema `schema_name`
+fn check_response<T: DeserializeOwned>(
+    schemas: &Schemas,
+    endpoint: &str,
+    schema_name: &str,
+    errors: &mut Vec<String>,
+) {
+    let schema = schemas.component(schema_name);
+    let rust_type = std::any::type_name::<T>().rsplit("::").next().unwrap();
+    for (fields, required_only) in [("all fields", false), ("required fields only", true)] {
+        if let Err(e) = deserialize::<T>(&schemas.instance(schema, required_only)) {
+            errors.push(format!(
+                "{endpoint}: {schema_name} with {fields} does not deserialize into {rust_type}: {e}"
+            ));
+        }
+    }
+}
+
+/// Serve instances of the response schema of `GET endpoint` to `call`
+async fn check_endpoint<F, Fut, T>(
+    schemas: &Schemas,
+    endpoint: &str,
+    call: F,
+    errors: &mut Vec<String>,
+) where
+    F: Fn(HTTPClient) -> Fut,
+    Fut: Future<Output = lighter_rs::Result<T>>,
+{
+    let schema = schemas.component(schemas.body_schema("get", endpoint, false));
+    for (fields, required_only) in [("all fields", false), ("required fields only", true)] {
+        let mock = Arc::new(MockTransport::new());
+        let body = serde_json::to_string_pretty(&schemas.instance(schema, required_only)).unwrap();
+        let served = body.clone();
+        mock.set_handler(endpoint, move |_| {
+            Ok(HttpResponse::new(200, served.clone()))
+        });
+        if let Err(e) = call(HTTPClient::with_transport("http://mock", mock)).await {
+            errors.push(format!(
+                "GET {endpoint} with {fields}: {}",
+                explain(&body, &e.to_string())
+            ));
+        }
+    }
+}
+
+/// Cases of the pinned signed payloads
+fn pinned_cases() -> Vec<Value> {
+    let path =
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/signed_payloads.json");
+    let fixtures: Value = serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+    fixtures["cases"].as_array().unwrap().clone()
+}
+
+/// The pinned signed payloads, as batch entries
+fn pinned_requests() -> Vec<SignedTx> {
+    pinned_cases()
+        .iter()
+        .map(|case| {
+            let body = case["body"].as_str().unwrap();
+            let form: Vec<(String, String)> = serde_urlencoded::from_str(body).unwrap();
+            SignedTx {
+                tx_type: case["tx_type"].as_u64().unwrap() as u8,
+                tx_info: form.into_iter().find(|(k, _)| k == "tx_info").unwrap().1,
+                tx_hash: None,
+            }
+        })
+        .collect()
+}
+
+fn assert_conforms(errors: Vec<String>) {
+    assert!(
+        errors.is_empty(),
+        "{} disagreement(s) with tests/schema:\n  {}",
+        errors.len(),
+        errors.join("\n  ")
+    );
+}
+
+#[test]
+fn test_send_tx_bodies_match_the_schema() {
+    let schemas = Schemas::load();
+    let schema_name = schemas.body_schema("post", "/api/v1/sendTx", true);
+
+    let mut errors = Vec::new();
+    for case in pinned_cases() {
+        let mut found = Vec::new();
+        let body = case["body"].as_str().unwrap();
+        let form = schemas.check_form(schema_name, body.as_bytes(), &mut found);
+        let tx_type = form.get("tx_type").and_then(Value::as_u64).unwrap_or(0) as u8;
+        if let Some(tx_info) = form.get("tx_info").and_then(Value::as_str) {
+            schemas.check_tx_info(tx_type, tx_info, &mut found);
+        }
+        errors.extend(found.into_iter().map(|error| {
+            format!(
+                "POST /api/v1/sendTx ({}) {error}",
+                case["name"].as_str().unwrap()
+            )
+        }));
+    }
+    assert_conforms(errors);
+}
+
+#[tokio::test]
+async fn test_send_tx_batch_body_matches_the_schema() {
+    let schemas = Schemas::load();
+    let endpoint = "/api/v1/sendTxBatch";
+    let response = schemas.instance(
+        schemas.component(schemas.body_schema("post", endpoint, false)),
+        false,
+    );
+    let mock = Arc::new(MockTransport::new());
+    mock.set_handler(endpoint, move |_| {
+        Ok(HttpResponse::new(200, response.to_string()))
+    });
+    let http = HTTPClient::with_transport("http://mock", mock.clone());
+
+    let txs = pinned_requests();
+    let mut errors = Vec::new();
+    if let Err(e) = http.send_tx_batch(&txs).await {
+        errors.push(format!("POST {endpoint}: {e}"));
+    }
+
+    let request = mock.requests_to(endpoint).pop().expect("no batch was sent");
+    let mut found = Vec::new();
+    let form = schemas.check_form(
+        schemas.body_schema("post", endpoint, true),
+        &request.body,
+        &mut found,
+    );
+    let tx_types: Vec<u8> = form
+        .get("tx_types")
+        .and_then(Value::as_str)
+        .and_then(|types| serde_json::from_str(types).ok())
+        .unwrap_or_default();
+    let tx_infos: Vec<String> = form
+        .get("tx_infos")
+        .and_then(Value::as_str)
+        .and_then(|infos| serde_json::from_str(infos).ok())
+        .unwrap_or_default();
+    if tx_types.len() != txs.len() || tx_infos.len() != txs.len() {
+        found.push(format!(
+            "fields \"tx_types\" and \"tx_infos\": expected JSON arrays of {} transactions",
+            txs.len()
+        ));
+    }
+    for (tx_type, tx_info) in tx_types.iter().zip(&tx_infos) {
+        schemas.check_tx_info(*tx_type, tx_info, &mut found);
+    }
+    errors.extend(
+        found
+            .into_iter()
+            .map(|error| format!("POST {endpoint} {error}")),
+    );
+    assert_conforms(errors);
+}
+
+#[test]
+fn test_response_types_match_the_schema() {
+    let schemas = Schemas::load();
+    let mut errors = Vec::new();
+    check_response::<TxResponse>(&schemas, "POST /api/v1/sendTx", "TxHash", &mut errors);
+    check_response::<BatchTxResponse>(
+        &schemas,
+        "POST /api/v1/sendTxBatch",
+        "TxHashes",
+        &mut errors,
+    );
+    check_response::<TransactionStatus>(&schemas, "GET /api/v1/tx", "EnrichedTx", &mut errors);
+    check_response::<TransactionStatus>(&schemas, "GET /api/v1/accountTxs", "Tx", &mut errors);
+    check_response::<ActiveOrder>(
+        &schemas,
+        "GET /api/v1/accountActiveOrders",
+        "Order",
+        &mut errors,
+    );
+    check_response::<AccountTrade>(&schemas, "GET /api/v1/trades", "Trade", &mut errors);
+    check_response::<FundingPayment>(
+        &schemas,
+        "GET /api/v1/positionFunding",
+        "PositionFunding",
+        &mut errors,
+    );
+    check_response::<AccountState>(
+        &schemas,
+        "GET /api/v1/account",
+        "DetailedAccount",
+        &mut errors,
+    );
+    check_response::<AccountPosition>(
+        &schemas,
+        "GET /api/v1/account",
+        "AccountPosition",
+        &mut errors,
+    );
+    check_response::<MarketDetails>(
+        &schemas,
+        "GET /api/v1/orderBookDetails",
+        "OrderBookDetail",
+        &mut errors,
+    );
+    check_response::<Candle>(
+        &schemas,
+        "GET /api/v1/candlesticks",
+        "Candlestick",
+        &mut errors,
+    );
+    assert_conforms(errors);
+}
+
+#[tokio::test]
+async fn test_endpoints_read_their_responses() {
+    let schemas = Schemas::load();
+    let mut errors = Vec::new();
+    check_endpoint(
+        &schemas,
+        "/",
+        |http| async move { http.get_server_time().await },
+        &mut errors,
+    )
+    .await;
+    check_endpoint(
+        &schemas,
+        "/api/v1/nextNonce",
+        |http| async move { http.get_next_nonce(1, 1).await },
+        &mut errors,
+    )
+    .await;
+    check_endpoint(
+        &schemas,
+        "/api/v1/apikeys",
+        |http| async move { http.get_api_key_public_key(1, 1).await },
+        &mut errors,
+    )
+    .await;
+    check_endpoint(
+        &schemas,
+        "/api/v1/account",
+        |http| async move { http.get_account_state(1).await },
+        &mut errors,
+    )
+    .await;
+    check_endpoint(
+        &schemas,
+        "/api/v1/accountsByL1Address",
+        |http| async move { http.get_accounts_by_l1_address("0x01").await },
+        &mut errors,
+    )
+    .await;
+    check_endpoint(
+        &schemas,
+        "/api/v1/accountTxs",
+        |http| async move { http.get_account_txs(1, 10, "auth").await },
+        &mut errors,
+    )
+    .await;
+    check_endpoint(
+        &schemas,
+        "/api/v1/accountActiveOrders",
+        |http| async move { http.get_active_orders(1, 1, None).await },
+        &mut errors,
+    )
+    .await;
+    check_endpoint(
+        &schemas,
+        "/api/v1/trades",
+        |http| async move { http.get_account_trades(1, 10, "auth").await },
+        &mut errors,
+    )
+    .await;
+    check_endpoint(
+        &schemas,
+        "/api/v1/positionFunding",
+        |http| async move { http.get_position_funding(1, 10, "auth").await },
+        &mut errors,
+    )
+    .await;
+    check_endpoint(
+        &schemas,
+        "/api/v1/orderBookDetails",
+        |http| async move { http.get_market_details(1).await },
+        &mut errors,
+    )
+    .await;
+    check_endpoint(
+        &schemas,
+        "/api/v1/candlesticks",
+        |http| async move {
+            http.get_candles(1, CandleResolution::OneMinute, 0, 1, 10)
+                .await
+        },
+        &mut errors,
+    )
+    .await;
+    check_endpoint(
+        &schemas,
+        "/api/v1/tx",
+        |http| async move { http.get_transaction("1").await },
+        &mut errors,
+    )
+    .await;
+    assert_conforms(errors);
+}
@@ -0,0 +1,44 @@
+//! Signing on wasm32-unknown-unknown, run by the `wasm` CI job
+//!
+//! The job signs the create-order fixture natively with the
+//! `sign_create_order_fixture` example and passes the output in
+//! `LIGHTER_NATIVE_CREATE_ORDER`; the browser build must produce the same bytes.
+
+#![cfg(target_arch = "wasm32")]
+
+use lighter_rs::client::TxClient;
+use lighter_rs::types::{CreateOrderTxReq, TransactOpts, TxInfo};
+use serde::Deserialize;
+use wasm_bindgen_test::wasm_bindgen_test;
+
+const FIXTURE: &str = include_str!("../benches/fixtures/create_order.json");
+
+#[derive(Deserialize)]
+struct Fixture {
+    private_key: String,
+    chain_id: u32,
+    request: CreateOrderTxReq,
+    opts: TransactOpts,
+}
+
+#[wasm_bindgen_test]
+async fn test_create_order_matches_native() {
+    let native = option_env!("LIGHTER_NATIVE_CREATE_ORDER").expect(
+        "set LIGHTER_NATIVE_CREATE_ORDER to the output of \
+         `cargo run --example sign_create_order_fixture`",
+    );
+
+    let fixture: Fixture = serde_json::from_str(FIXTURE).unwrap();
+    let tx_client = TxClient::builder()
+        .private_key(&fixture.private_key)
+        .chain_id(fixture.chain_id)
+        .build()
+        .unwrap();
+    let order = tx_client
+        .create_order(&fixture.request, Some(fixture.opts))
+        .await
+        .unwrap();
+
+    assert!(order.sig.is_some());
+    assert_eq!(order.get_tx_info().unwrap(), native.trim());
+}